@@ -0,0 +1,427 @@
+//! 斜杠命令注册表，两个界面共用：ghost_text_editor 的 `App`，以及 grok-cli 的聊天界面。
+//! 命令实现 [`SlashCommand`]，注册表据此给出补全提示、解析参数、分发执行，
+//! 并在命令名打错时按编辑距离给出最接近的命令。只依赖标准库。
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+/// 命令执行返回的 future，借用命令本身和上下文
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = CommandOutcome> + 'a>>;
+
+/// 命令执行完之后界面要做的事
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutcome {
+    /// 作为回复显示给用户
+    Reply(String),
+    /// 依次作为用户消息发给模型，每条等上一条的回复结束再发
+    Prompts(Vec<String>),
+    /// 命令已经自己更新了界面，没有要显示的
+    Done,
+    /// 退出程序
+    Exit,
+}
+
+/// 命令名之后的参数：原样的文本，以及按空白切分后的各个参数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandArgs {
+    raw: String,
+    words: Vec<String>,
+}
+
+impl CommandArgs {
+    /// 按空白切分；用双引号或单引号括起来的部分算一个参数，可以包含空格
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut in_word = false;
+        let mut quote = None;
+        for c in raw.chars() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => word.push(c),
+                None if c == '"' || c == '\'' => {
+                    quote = Some(c);
+                    in_word = true;
+                }
+                None if c.is_whitespace() => {
+                    if in_word {
+                        words.push(std::mem::take(&mut word));
+                        in_word = false;
+                    }
+                }
+                None => {
+                    word.push(c);
+                    in_word = true;
+                }
+            }
+        }
+        if let Some(q) = quote {
+            return Err(format!("Unclosed {} in the command arguments", q));
+        }
+        if in_word {
+            words.push(word);
+        }
+        Ok(Self { raw: raw.trim().to_string(), words })
+    }
+
+    /// 命令名之后的全部文本，去掉首尾空白
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+}
+
+/// 一个斜杠命令。`C` 是执行时可以修改的界面状态
+pub trait SlashCommand<C> {
+    /// 不带斜杠的命令名
+    fn name(&self) -> &str;
+
+    /// 同样指向这个命令的其他名字
+    fn aliases(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// 一句话说明，显示在补全提示和帮助里
+    fn description(&self) -> &str;
+
+    /// 参数写法，例如 `[md|html] [path]`，显示在帮助里
+    fn usage(&self) -> &str {
+        ""
+    }
+
+    /// 命令名之后输入了 `args` 时可以补全成的完整参数
+    fn complete(&self, _args: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn execute<'a>(&'a self, ctx: &'a mut C, args: &'a CommandArgs) -> CommandFuture<'a>;
+}
+
+/// 把提示模板发给模型的命令，模板里的 `{args}` 换成命令名之后的文本。
+/// 用户设置里的自定义命令和 `.grok/commands/*.md` 都是这种命令
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptCommand {
+    name: String,
+    description: String,
+    templates: Vec<String>,
+}
+
+impl PromptCommand {
+    pub fn new(name: &str, description: &str, templates: Vec<String>) -> Self {
+        Self { name: name.to_string(), description: description.to_string(), templates }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// `.md` 命令文件：正文是提示模板。开头可以有 `---` 包起来的 front matter，
+    /// 其中的 `description:` 是命令说明；没有时用正文的第一个非空行
+    pub fn from_markdown(name: &str, text: &str) -> Self {
+        let mut description = None;
+        let mut body = text;
+        if let Some((front, after)) = text.strip_prefix("---").and_then(|rest| rest.split_once("\n---")) {
+            for line in front.lines() {
+                if let Some(value) = line.trim().strip_prefix("description:") {
+                    description = Some(value.trim().trim_matches('"').to_string());
+                }
+            }
+            body = after.split_once('\n').map(|(_, b)| b).unwrap_or("");
+        }
+        let body = body.trim();
+        let description = description.unwrap_or_else(|| {
+            body.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim_start_matches('#').trim().to_string()
+        });
+        Self::new(name, &description, vec![body.to_string()])
+    }
+
+    /// `args` 代入各个模板后的用户消息，空的不发
+    pub fn expand(&self, args: &str) -> Vec<String> {
+        self.templates
+            .iter()
+            .map(|template| template.replace("{args}", args).trim().to_string())
+            .filter(|prompt| !prompt.is_empty())
+            .collect()
+    }
+}
+
+impl<C> SlashCommand<C> for PromptCommand {
+    fn name(&self) -> &str {
+        PromptCommand::name(self)
+    }
+
+    fn description(&self) -> &str {
+        PromptCommand::description(self)
+    }
+
+    fn execute<'a>(&'a self, _ctx: &'a mut C, args: &'a CommandArgs) -> CommandFuture<'a> {
+        Box::pin(async move { CommandOutcome::Prompts(self.expand(args.raw())) })
+    }
+}
+
+/// `dir` 下每个 `.md` 文件一个提示命令，命令名是去掉扩展名的文件名，按名字排序。
+/// 目录不存在时没有命令；读不了的文件、名字带空白或正文为空的文件跳过，并给出一条警告
+pub fn load_markdown_commands(dir: &Path) -> (Vec<PromptCommand>, Vec<String>) {
+    let mut commands = Vec::new();
+    let mut warnings = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (commands, warnings);
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        match std::fs::read_to_string(&path) {
+            Ok(_) if name.is_empty() || name.contains(char::is_whitespace) => {
+                warnings.push(format!("Ignoring {}: command names cannot contain spaces", path.display()));
+            }
+            Ok(text) => {
+                let command = PromptCommand::from_markdown(&name, &text);
+                if command.templates.iter().all(|t| t.is_empty()) {
+                    warnings.push(format!("Ignoring {}: the file has no prompt", path.display()));
+                } else {
+                    commands.push(command);
+                }
+            }
+            Err(e) => warnings.push(format!("Ignoring {}: {}", path.display(), e)),
+        }
+    }
+    (commands, warnings)
+}
+
+/// 解析出的命令和它的参数
+pub type Resolved<'r, C> = Result<(&'r dyn SlashCommand<C>, CommandArgs), ResolveError>;
+
+/// 输入的命令没法执行的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// 没有这个命令；`suggestion` 是编辑距离最近的命令名
+    Unknown { name: String, suggestion: Option<String> },
+    /// 参数解析失败
+    BadArgs(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::Unknown { name, suggestion: Some(suggestion) } => {
+                write!(f, "Unknown command: /{}. Did you mean /{}?", name, suggestion)
+            }
+            ResolveError::Unknown { name, suggestion: None } => {
+                write!(f, "Unknown command: /{}. Type /help for available commands.", name)
+            }
+            ResolveError::BadArgs(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// 按注册顺序保存的命令，以及命令名和别名到命令的索引
+pub struct CommandRegistry<C> {
+    commands: Vec<Box<dyn SlashCommand<C>>>,
+    by_name: BTreeMap<String, usize>,
+}
+
+impl<C> Default for CommandRegistry<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> CommandRegistry<C> {
+    pub fn new() -> Self {
+        Self { commands: Vec::new(), by_name: BTreeMap::new() }
+    }
+
+    /// 命令名或别名已被占用时不注册，返回原因
+    pub fn register(&mut self, command: impl SlashCommand<C> + 'static) -> Result<(), String> {
+        let names: Vec<String> =
+            std::iter::once(command.name()).chain(command.aliases().iter().copied()).map(str::to_string).collect();
+        if let Some(taken) = names.iter().find(|name| self.by_name.contains_key(name.as_str())) {
+            return Err(format!("/{} is already a command", taken));
+        }
+        for name in names {
+            self.by_name.insert(name, self.commands.len());
+        }
+        self.commands.push(Box::new(command));
+        Ok(())
+    }
+
+    /// 输入框内容对应的补全提示。还在输命令名时，是名字或别名以它开头的命令（"/name - description"）；
+    /// 命令名后面有空格时，是该命令对参数的补全（"/name args"）
+    pub fn hints(&self, input: &str) -> Vec<String> {
+        let Some(typed) = input.strip_prefix('/') else {
+            return Vec::new();
+        };
+        match typed.split_once(char::is_whitespace) {
+            Some((name, args)) => match self.by_name.get(name) {
+                Some(&index) => {
+                    let command = &self.commands[index];
+                    command.complete(args.trim_start()).into_iter().map(|args| format!("/{} {}", command.name(), args)).collect()
+                }
+                None => Vec::new(),
+            },
+            None => {
+                let typed = typed.to_lowercase();
+                self.commands
+                    .iter()
+                    .filter(|command| {
+                        std::iter::once(command.name())
+                            .chain(command.aliases().iter().copied())
+                            .any(|name| name.to_lowercase().starts_with(&typed))
+                    })
+                    .map(|command| format!("/{} - {}", command.name(), command.description()))
+                    .collect()
+            }
+        }
+    }
+
+    /// 帮助里每个命令一行："/name usage - description"，有别名时附在后面
+    pub fn help_lines(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .map(|command| {
+                let mut line = format!("/{}", command.name());
+                if !command.usage().is_empty() {
+                    line.push(' ');
+                    line.push_str(command.usage());
+                }
+                line.push_str(" - ");
+                line.push_str(command.description());
+                if !command.aliases().is_empty() {
+                    let aliases: Vec<String> = command.aliases().iter().map(|alias| format!("/{}", alias)).collect();
+                    line.push_str(&format!(" (also {})", aliases.join(", ")));
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// 找到输入对应的命令并解析参数；不是以 `/` 开头的输入返回 `None`
+    pub fn resolve(&self, input: &str) -> Option<Resolved<'_, C>> {
+        let typed = input.trim().strip_prefix('/')?;
+        let (name, args) = typed.split_once(char::is_whitespace).unwrap_or((typed, ""));
+        let Some(&index) = self.by_name.get(name) else {
+            return Some(Err(ResolveError::Unknown { name: name.to_string(), suggestion: self.closest(name) }));
+        };
+        Some(match CommandArgs::parse(args) {
+            Ok(args) => Ok((self.commands[index].as_ref(), args)),
+            Err(reason) => Err(ResolveError::BadArgs(reason)),
+        })
+    }
+
+    /// 与 `name` 编辑距离最近的命令名或别名。距离超过名字长度的三分之一（至少容许 2）、
+    /// 或者不比整个重打一遍更近时，就不算打错而是没有这个命令
+    fn closest(&self, name: &str) -> Option<String> {
+        let len = name.chars().count();
+        let allowed = (len / 3).max(2);
+        self.by_name
+            .iter()
+            .map(|(candidate, &index)| (edit_distance(name, candidate), candidate, index))
+            .filter(|&(distance, _, _)| distance <= allowed && distance < len)
+            .min_by_key(|&(distance, candidate, _)| (distance, candidate.len()))
+            .map(|(_, _, index)| self.commands[index].name().to_string())
+    }
+}
+
+/// Levenshtein 编辑距离，按 char 计
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 记下收到的参数
+    struct Echo;
+
+    impl SlashCommand<Vec<String>> for Echo {
+        fn name(&self) -> &str {
+            "export"
+        }
+
+        fn aliases(&self) -> &[&'static str] {
+            &["ex"]
+        }
+
+        fn description(&self) -> &str {
+            "Write the transcript to a file"
+        }
+
+        fn usage(&self) -> &str {
+            "[md|html] [path]"
+        }
+
+        fn complete(&self, args: &str) -> Vec<String> {
+            ["md", "html"].iter().filter(|format| format.starts_with(args)).map(|format| format.to_string()).collect()
+        }
+
+        fn execute<'a>(&'a self, ctx: &'a mut Vec<String>, args: &'a CommandArgs) -> CommandFuture<'a> {
+            Box::pin(async move {
+                ctx.extend(args.words().iter().cloned());
+                CommandOutcome::Done
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_completes_parses_args_and_suggests_commands() {
+        let mut registry: CommandRegistry<Vec<String>> = CommandRegistry::new();
+        registry.register(Echo).unwrap();
+        let review = PromptCommand::from_markdown("review", "---\ndescription: Review a file\n---\nReview {args} for bugs.\n");
+        registry.register(review).unwrap();
+        assert!(registry.register(PromptCommand::new("ex", "taken", vec!["x".to_string()])).is_err());
+
+        assert_eq!(registry.hints("/e"), vec!["/export - Write the transcript to a file"]);
+        assert_eq!(registry.hints("/"), vec!["/export - Write the transcript to a file", "/review - Review a file"]);
+        assert_eq!(registry.hints("/export h"), vec!["/export html"]);
+        assert_eq!(registry.hints("hello"), Vec::<String>::new());
+        assert_eq!(
+            registry.help_lines()[0],
+            "/export [md|html] [path] - Write the transcript to a file (also /ex)"
+        );
+
+        let mut seen = Vec::new();
+        let (command, args) = registry.resolve("/ex md \"my notes.html\"").unwrap().unwrap();
+        assert_eq!(args.raw(), "md \"my notes.html\"");
+        assert_eq!(command.execute(&mut seen, &args).await, CommandOutcome::Done);
+        assert_eq!(seen, vec!["md", "my notes.html"]);
+
+        let (command, args) = registry.resolve("/review src/lib.rs").unwrap().unwrap();
+        assert_eq!(
+            command.execute(&mut seen, &args).await,
+            CommandOutcome::Prompts(vec!["Review src/lib.rs for bugs.".to_string()])
+        );
+
+        assert!(registry.resolve("not a command").is_none());
+        assert!(matches!(registry.resolve("/export 'open"), Some(Err(ResolveError::BadArgs(_)))));
+        let unknown = |input: &str| registry.resolve(input).unwrap().err().unwrap().to_string();
+        assert_eq!(unknown("/exprot md"), "Unknown command: /exprot. Did you mean /export?");
+        assert_eq!(unknown("/reveiw"), "Unknown command: /reveiw. Did you mean /review?");
+        assert_eq!(unknown("/zz"), "Unknown command: /zz. Type /help for available commands.");
+    }
+}
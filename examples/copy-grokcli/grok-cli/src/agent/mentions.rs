@@ -0,0 +1,103 @@
+//! `@path` mentions in a user message. Each mentioned file is read with `view_file`, so the
+//! workspace boundary and the view budget apply to it as they do to a tool call, and its
+//! contents go in front of the message the model gets. The chat history keeps the message as
+//! it was typed.
+
+use crate::tools::TextEditorTool;
+use std::path::Path;
+
+/// Characters that end a sentence rather than a path, as in "look at @src/main.rs."
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '"', '\''];
+
+/// A user message with the files it mentions in front of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandedMessage {
+    /// What the model is sent
+    pub content: String,
+    /// One line for each mention that could not be added
+    pub warnings: Vec<String>,
+}
+
+/// The `@path` words of `message` in order, each once. An `@` inside a word, as in an email
+/// address, is not a mention.
+pub fn mentioned_paths(message: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for word in message.split_whitespace() {
+        let Some(path) = word.strip_prefix('@') else { continue };
+        let path = path.trim_end_matches(TRAILING_PUNCTUATION);
+        if !path.is_empty() && !paths.iter().any(|seen| seen == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
+
+/// `message` with the contents of every file it mentions in front of it, each file cut at the
+/// view budget. A mention of nothing on disk is warned about when it looks like a path and is
+/// left alone when it does not, such as `@alice`.
+pub async fn expand_mentions(editor: &TextEditorTool, message: &str) -> ExpandedMessage {
+    let mut sections = Vec::new();
+    let mut warnings = Vec::new();
+    for path in mentioned_paths(message) {
+        if !Path::new(&path).exists() {
+            if path.contains('/') || path.contains('.') {
+                warnings.push(format!("⚠️ @{} was not found, so nothing from it was added to the prompt", path));
+            }
+            continue;
+        }
+        let refused = match editor.view_many(std::slice::from_ref(&path), None, false).await {
+            Ok(result) if result.success => {
+                sections.push(result.output.unwrap_or_default());
+                continue;
+            }
+            Ok(result) => result.error.unwrap_or_default(),
+            Err(e) => e.to_string(),
+        };
+        warnings.push(format!("⚠️ @{} was not added to the prompt: {}", path, refused));
+    }
+
+    let content = if sections.is_empty() {
+        message.to_string()
+    } else {
+        format!("Files mentioned in the message below:\n\n{}\n\n---\n\n{}", sections.join("\n\n"), message)
+    };
+    ExpandedMessage { content, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::WorkspaceGuard;
+
+    #[tokio::test]
+    async fn test_mentions_prepend_file_contents_and_warn_about_missing_paths() {
+        let root = std::env::temp_dir().join(format!("grok-mentions-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn answer() -> u32 {\n    42\n}\n").unwrap();
+        let lib = root.join("src/lib.rs").to_string_lossy().to_string();
+        let missing = root.join("src/gone.rs").to_string_lossy().to_string();
+
+        assert_eq!(
+            mentioned_paths(&format!("see @{}, then @{} and @{}. mail me@example.com", lib, missing, lib)),
+            vec![lib.clone(), missing.clone()]
+        );
+
+        let mut editor = TextEditorTool::new();
+        let message = format!("Explain @{} and @{} to @alice", lib, missing);
+        let expanded = expand_mentions(&editor, &message).await;
+        assert!(expanded.content.starts_with("Files mentioned in the message below:"));
+        assert!(expanded.content.contains(&format!("=== {} (lines 1-3 of 3) ===\n1: pub fn answer() -> u32 {{", lib)));
+        assert!(expanded.content.ends_with(&format!("\n\n---\n\n{}", message)));
+        assert_eq!(expanded.warnings, vec![format!("⚠️ @{} was not found, so nothing from it was added to the prompt", missing)]);
+
+        assert_eq!(expand_mentions(&editor, "no mentions here").await.content, "no mentions here");
+
+        editor.set_workspace_guard(Some(WorkspaceGuard::new(&root.join("src/nested"))));
+        let refused = expand_mentions(&editor, &format!("@{}", lib)).await;
+        assert_eq!(refused.content, format!("@{}", lib));
+        assert!(refused.warnings[0].contains("Outside the workspace"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
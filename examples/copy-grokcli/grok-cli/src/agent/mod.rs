@@ -1,9 +1,19 @@
-use crate::grok::client::GrokClient;
-use crate::types::{ChatEntry, ChatEntryType, GrokMessage, GrokTool, GrokToolCall, GrokToolCallFunction, ToolResult, StreamingChunk, StreamingChunkType};
-use crate::tools::{TextEditorTool, BashTool, TodoTool, SearchTool, ConfirmationTool, MorphEditorTool};
+use crate::grok::client::{GrokClient, RetryPolicy};
+use crate::grok::error::AgentError;
+use crate::grok::usage::{ModelPrice, UsageSummary};
+use crate::types::{ChatEntry, ChatEntryType, GrokMessage, GrokTool, GrokToolCall, GrokToolCallFunction, ToolChoice, ToolResult, StreamingChunk, StreamingChunkType, TokenUsage};
+use crate::tools::{TextEditorTool, EditJournal, LineEdit, BashTool, TodoTool, SearchTool, ConfirmationTool, MorphEditorTool, WorkspaceGuard, FormatOnWriteConfig, CheckConfig, LineEnding, DangerousCommandMode, DangerousCommandRule, BashConfinement, BashPolicy, GIT_CHANGES_DEFAULT_TOKENS, format_written_file, git_changes, run_check};
+use crate::utils::file_changes::{GitSnapshot, TurnChanges};
+use crate::utils::project_context::ProjectContext;
+use crate::utils::session_store::SessionFile;
+use crate::utils::transcript::{TranscriptFormat, TranscriptHeader};
+use crate::mcp::{self, MCPManager};
 use std::collections::HashMap;
 use std::pin::Pin;
 use futures::Stream;
+use tokio_util::sync::CancellationToken;
+
+mod mentions;
 
 #[derive(Clone)]
 pub struct GrokAgent {
@@ -17,6 +27,804 @@ pub struct GrokAgent {
     chat_history: Vec<ChatEntry>,
     messages: Vec<GrokMessage>,
     max_tool_rounds: u32,
+    /// Where `max_tool_rounds` came from, e.g. `built-in rule "grok"`
+    tool_rounds_source: String,
+    /// Set by `--max-tool-rounds` or `GROK_MAX_TOOL_ROUNDS`, which the per-model table never overrides
+    tool_rounds_explicit: bool,
+    /// `tool_rounds` from user settings, resolved again when the model changes
+    tool_rounds_config: ToolRoundsConfig,
+    format_on_write: FormatOnWriteConfig,
+    check: CheckConfig,
+    tool_choice: ToolChoice,
+    empty_response_retries: u32,
+    session_id: String,
+    /// Send only the last N user turns (plus the system prompt); `None` sends everything
+    history_window: Option<usize>,
+    /// Failed tool results in a row before the agent intervenes (0 disables)
+    max_consecutive_failures: u32,
+    tool_failure_action: ToolFailureAction,
+    /// Where file edits and bash commands are sent for approval unless the session accepted
+    /// them; without one they run directly
+    edit_confirmer: Option<tokio::sync::mpsc::Sender<EditConfirmation>>,
+    /// Replies for the confirmations sent to `edit_confirmer`, shared with every clone
+    pending_confirmations: PendingConfirmations,
+    /// Where `ask_user` questions go; without one the tool reports that nobody can answer
+    user_asker: Option<tokio::sync::mpsc::Sender<UserQuestion>>,
+    /// Files the current turn created, modified or deleted
+    turn_changes: TurnChanges,
+    /// Append a "Changed: …" entry at the end of each turn that touched files
+    change_summary: bool,
+    /// `git add` the files each turn changed
+    auto_stage: bool,
+    tool_result_summary: ToolResultSummaryConfig,
+    /// Tool calls run from a single assistant message; later ones are deferred. `None` runs all
+    max_tool_calls_per_message: Option<usize>,
+    /// Add an "About to …" note before each tool call the model did not explain itself
+    explain_tools: bool,
+    /// Ask for Conventional Commits summaries in `/commit-and-push` messages
+    conventional_commits: bool,
+    /// The `GROK.md` files whose instructions are in the system prompt
+    project_context: ProjectContext,
+    /// Tools offered to the model; `None` offers all of them
+    enabled_tools: Option<Vec<String>>,
+    /// Longer user messages are sent as several consecutive parts; `None` sends them whole
+    max_message_tokens: Option<usize>,
+    /// Tool results older than this many user turns are replaced by a one-line outcome in
+    /// `messages`; `None` keeps them in full
+    compress_tool_results_after: Option<usize>,
+    /// Most read-only tool calls from one assistant message that run at the same time
+    tool_concurrency: usize,
+    /// Estimated prompt tokens each request is trimmed to; `None` sends the whole window
+    context_tokens: Option<usize>,
+    /// Cancelled when the user stops the run; checked between tool calls and while streaming
+    cancellation: CancellationToken,
+    tool_timeouts: ToolTimeouts,
+    /// Tool rounds the streamed turn has run so far
+    stream_tool_rounds: u32,
+    stream_loop_guard: ToolLoopGuard,
+    /// How the last `process_user_message` turn ended, and the model's final reply if it gave one
+    last_turn_end: TurnEnd,
+    last_reply: Option<String>,
+    /// Connected MCP servers, shared by every clone of the agent
+    mcp: Option<std::sync::Arc<tokio::sync::RwLock<MCPManager>>>,
+    /// MCP tools as offered to the model, shared by every clone of the agent
+    mcp_tools: std::sync::Arc<std::sync::Mutex<Option<McpToolCache>>>,
+    /// Set while a streamed turn runs tool calls: lines bash prints, with the call printing them
+    tool_output: Option<tokio::sync::mpsc::Sender<(GrokToolCall, String)>>,
+    /// The root the file tools are kept under, unless `allow_outside_workspace` is on
+    workspace_guard: Option<WorkspaceGuard>,
+}
+
+/// Why a turn stopped; headless runs report it as `exit_reason`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnEnd {
+    /// The model answered without asking for more tools
+    #[default]
+    Completed,
+    MaxToolRounds,
+    LoopDetected,
+    /// Stopped after too many failed tool calls in a row
+    ToolFailures,
+    Cancelled,
+    /// The request failed, or the turn ended with an error entry such as a missing API key
+    Error,
+}
+
+impl TurnEnd {
+    /// Whether the turn ended without the model finishing its answer
+    pub fn is_failure(self) -> bool {
+        self != TurnEnd::Completed
+    }
+}
+
+/// Retries for blank model responses unless configured otherwise
+pub const DEFAULT_EMPTY_RESPONSE_RETRIES: u32 = 1;
+
+/// Failed tool results in a row before the agent steers or stops, unless configured otherwise
+pub const DEFAULT_MAX_CONSECUTIVE_FAILURES: u32 = 4;
+
+/// Shown in the transcript when the user stops a run
+pub const CANCELLED_NOTE: &str = "⏹️ Cancelled by user";
+
+/// Result for tool calls that were not run because the user stopped the run
+const CANCELLED_TOOL_CALL_NOTE: &str = "Not run: the user cancelled the request before this call.";
+
+/// The `Done` chunk that ends a stream the user cancelled. It carries no tool calls, so none run.
+fn cancelled_done_chunk(content: String) -> StreamingChunk {
+    StreamingChunk {
+        chunk_type: StreamingChunkType::Done,
+        content: Some(content),
+        tool_calls: None,
+        tool_call: None,
+        tool_result: None,
+        token_count: None,
+        usage: None,
+    }
+}
+
+/// A line for the transcript that is not part of the model's reply
+fn note_chunk(note: String) -> StreamingChunk {
+    StreamingChunk {
+        chunk_type: StreamingChunkType::Note,
+        content: Some(note),
+        tool_calls: None,
+        tool_call: None,
+        tool_result: None,
+        token_count: None,
+        usage: None,
+    }
+}
+
+/// A line a running tool call printed: a `ToolResult` chunk with the line as its content and no
+/// result yet
+fn tool_output_chunk(tool_call: GrokToolCall, line: String) -> StreamingChunk {
+    StreamingChunk {
+        chunk_type: StreamingChunkType::ToolResult,
+        content: Some(line),
+        tool_calls: None,
+        tool_call: Some(tool_call),
+        tool_result: None,
+        token_count: None,
+        usage: None,
+    }
+}
+
+/// Lines of tool output waiting for the stream to pass them on
+const TOOL_OUTPUT_BUFFER: usize = 64;
+
+/// Read-only tool calls run at once unless configured otherwise
+pub const DEFAULT_TOOL_CONCURRENCY: usize = 4;
+
+/// Tools that only read the workspace. Consecutive calls to them run concurrently; any other
+/// call runs alone, in order, so it sees what the calls before it did.
+const READ_ONLY_TOOLS: &[&str] = &["view_file", "search", "git_changes"];
+
+fn is_read_only_tool(name: &str) -> bool {
+    READ_ONLY_TOOLS.contains(&name)
+}
+
+/// Built-in limits, in seconds, for tools that can hang. Tools not listed, such as `ask_user`
+/// which waits for a person, have no limit unless one is configured.
+const DEFAULT_TOOL_TIMEOUTS: &[(&str, u64)] = &[("bash", 120), ("edit_file", 60), ("search", 30), ("view_file", 10)];
+
+/// How long each tool call may run before it is stopped and reported to the model as timed out
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolTimeouts {
+    limits: HashMap<String, std::time::Duration>,
+}
+
+impl Default for ToolTimeouts {
+    fn default() -> Self {
+        let limits = DEFAULT_TOOL_TIMEOUTS
+            .iter()
+            .map(|(tool, secs)| (tool.to_string(), std::time::Duration::from_secs(*secs)))
+            .collect();
+        Self { limits }
+    }
+}
+
+impl ToolTimeouts {
+    /// The built-in limits with `tool_timeouts` from user settings on top, in seconds; `0`
+    /// removes a tool's limit
+    pub fn with_overrides(overrides: &HashMap<String, u64>) -> Self {
+        let mut timeouts = Self::default();
+        for (tool, secs) in overrides {
+            timeouts.set(tool, (*secs > 0).then(|| std::time::Duration::from_secs(*secs)));
+        }
+        timeouts
+    }
+
+    pub fn set(&mut self, tool: &str, limit: Option<std::time::Duration>) {
+        match limit {
+            Some(limit) => self.limits.insert(tool.to_string(), limit),
+            None => self.limits.remove(tool),
+        };
+    }
+
+    pub fn for_tool(&self, tool: &str) -> Option<std::time::Duration> {
+        self.limits.get(tool).copied()
+    }
+}
+
+/// Run one tool call under `limit`. A call that runs over is dropped, which stops its work
+/// (a bash command is killed), and comes back as a failed result the model can react to.
+async fn run_with_timeout(
+    limit: Option<std::time::Duration>,
+    call: impl std::future::Future<Output = Result<ToolResult, Box<dyn std::error::Error>>>,
+) -> Result<ToolResult, Box<dyn std::error::Error>> {
+    let Some(limit) = limit else {
+        return call.await;
+    };
+    match tokio::time::timeout(limit, call).await {
+        Ok(result) => result,
+        Err(_) => Ok(ToolResult {
+            success: false,
+            output: None,
+            error: Some(format!("timed out after {} and was stopped", format_limit(limit))),
+            data: None,
+        }),
+    }
+}
+
+/// `120s`, or milliseconds for limits under a second
+fn format_limit(limit: std::time::Duration) -> String {
+    if limit.as_secs() > 0 && limit.subsec_millis() == 0 {
+        format!("{}s", limit.as_secs())
+    } else {
+        format!("{}ms", limit.as_millis())
+    }
+}
+
+/// What the agent does once `max_consecutive_failures` tool calls have failed in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolFailureAction {
+    /// Tell the model to step back and reassess, then keep going
+    #[default]
+    Steer,
+    /// End the turn with a warning
+    Stop,
+}
+
+/// The user's answer to a file edit or bash command prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditDecision {
+    Approve,
+    Reject,
+    /// Approve this call and the same kind for the rest of the session: auto-edit mode for
+    /// file edits, every bash command for bash
+    ApproveAll,
+}
+
+/// A file edit or bash command waiting for the user's decision, answered with
+/// [`GrokAgent::resolve_confirmation`]
+#[derive(Debug, Clone)]
+pub struct EditConfirmation {
+    /// The tool call's id
+    pub id: String,
+    /// Tool and path or command, e.g. `create_file(src/main.rs)`
+    pub description: String,
+    /// The `ConfirmationPending` transcript entry with the exact content or command
+    pub entry: ChatEntry,
+}
+
+impl EditConfirmation {
+    pub fn is_bash(&self) -> bool {
+        self.entry.tool_call.as_ref().is_some_and(|call| call.function.name == "bash")
+    }
+}
+
+/// Tool calls waiting for a decision, by tool call id
+#[derive(Debug, Clone, Default)]
+pub struct PendingConfirmations(std::sync::Arc<std::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<EditDecision>>>>);
+
+impl PendingConfirmations {
+    /// Answer the confirmation for call `id`; false when nothing waits for it
+    pub fn resolve(&self, id: &str, decision: EditDecision) -> bool {
+        match self.0.lock().unwrap().remove(id) {
+            Some(reply) => reply.send(decision).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// What the user is asked to approve: the call and the exact text it would write or run
+fn confirmation_detail(tool_call: &GrokToolCall) -> (String, String) {
+    let args: serde_json::Value = serde_json::from_str(&tool_call.function.arguments).unwrap_or_default();
+    let arg = |key: &str| args.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    match tool_call.function.name.as_str() {
+        "bash" => (format!("bash(`{}`)", arg("command")), format!("$ {}", arg("command"))),
+        "create_file" => (format!("create_file({})", arg("path")), format!("Create {}:\n{}", arg("path"), arg("content"))),
+        "str_replace_editor" => {
+            let replace_all = args.get("replace_all").and_then(|v| v.as_bool()).unwrap_or(false);
+            let fuzzy = args.get("fuzzy").and_then(|v| v.as_bool()).unwrap_or(false);
+            // The diff the edit will make; the raw replacement when it would not apply
+            let detail = match TextEditorTool::preview_str_replace(&arg("path"), &arg("old_str"), &arg("new_str"), replace_all, fuzzy) {
+                Some(diff) => format!("Edit {}:\n{}", arg("path"), diff.preview()),
+                None => {
+                    let mut detail = format!("Edit {}:", arg("path"));
+                    detail.extend(arg("old_str").lines().map(|line| format!("\n- {}", line)));
+                    detail.extend(arg("new_str").lines().map(|line| format!("\n+ {}", line)));
+                    detail
+                }
+            };
+            (format!("str_replace_editor({})", arg("path")), detail)
+        }
+        "insert_lines" | "delete_lines" => {
+            let line = |key: &str| args.get(key).and_then(|v| v.as_u64()).unwrap_or_default() as usize;
+            let (edit, call) = if tool_call.function.name == "insert_lines" {
+                (LineEdit::Insert { after: line("line"), content: arg("content") }, format!("insert_lines({}, after line {})", arg("path"), line("line")))
+            } else {
+                let (start, end) = (line("start_line"), line("end_line"));
+                (LineEdit::Delete { start, end }, format!("delete_lines({}, {}-{})", arg("path"), start, end))
+            };
+            let detail = match TextEditorTool::preview_line_edit(&arg("path"), &edit) {
+                Some(diff) => format!("Edit {}:\n{}", arg("path"), diff.preview()),
+                None => format!("Edit {}: the line numbers are not in the file", arg("path")),
+            };
+            (call, detail)
+        }
+        "undo_edit" if args.get("all").and_then(|v| v.as_bool()) == Some(true) => {
+            ("undo_edit(all)".to_string(), "Undo every file edit of this session, newest first:".to_string())
+        }
+        "undo_edit" => ("undo_edit(last)".to_string(), "Undo the last file edit:".to_string()),
+        "edit_file" => (
+            format!("edit_file({})", arg("target_file")),
+            format!("Edit {} ({}):\n{}", arg("target_file"), arg("instructions"), arg("code_edit")),
+        ),
+        name => (format!("{}(…)", name), tool_call.function.arguments.clone()),
+    }
+}
+
+/// MCP tools as offered to the model, and the [`MCPManager::revision`] they were listed at
+#[derive(Debug, Clone)]
+struct McpToolCache {
+    revision: u64,
+    tools: Vec<GrokTool>,
+}
+
+/// The result for a gated call nobody could approve; the model must not assume it happened
+fn unapproved_result(description: &str, nothing_done: &str) -> ToolResult {
+    ToolResult {
+        success: false,
+        output: None,
+        error: Some(format!(
+            "Nobody could approve {}, so nothing was {}. Pass --yolo to allow file edits and bash commands without asking.",
+            description, nothing_done
+        )),
+        data: None,
+    }
+}
+
+/// Tool results longer than this many characters are summarized, unless configured otherwise
+pub const DEFAULT_SUMMARY_THRESHOLD_CHARS: usize = 8000;
+
+fn default_summary_threshold() -> usize {
+    DEFAULT_SUMMARY_THRESHOLD_CHARS
+}
+
+/// How one tool's results are sent to the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolResultMode {
+    Verbatim,
+    Summarize,
+}
+
+/// Rounds for models that no tool-round rule matches
+pub const DEFAULT_TOOL_ROUNDS: u32 = 5;
+
+/// Built-in per-model round budgets: models that reason more get more rounds
+const BUILTIN_TOOL_ROUNDS: &[(&str, u32)] = &[("claude", 15), ("gpt-4", 12), ("grok", 10), ("qwen", 8)];
+
+/// One entry of the per-model tool-round table. `model` matches as a substring of the model
+/// name unless `exact` is set.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ToolRoundRule {
+    pub model: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exact: bool,
+    pub rounds: u32,
+}
+
+/// `tool_rounds` in user settings: rules tried before the built-in table, and the budget for
+/// models that nothing matches
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ToolRoundsConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<ToolRoundRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<u32>,
+}
+
+impl ToolRoundsConfig {
+    /// Rounds for `model` and the rule that set them. An exact rule wins; otherwise the first
+    /// substring match, trying the user's rules in order before the built-in table.
+    pub fn resolve(&self, model: &str) -> (u32, String) {
+        if let Some(rule) = self.rules.iter().find(|rule| rule.exact && rule.model == model) {
+            return (rule.rounds, format!("rule \"{}\" (exact)", rule.model));
+        }
+        let user = self.rules.iter().filter(|rule| !rule.exact).map(|rule| (rule.model.as_str(), rule.rounds, "rule"));
+        let builtin = BUILTIN_TOOL_ROUNDS.iter().map(|(pattern, rounds)| (*pattern, *rounds, "built-in rule"));
+        if let Some((pattern, rounds, kind)) = user.chain(builtin).find(|(pattern, _, _)| model.contains(pattern)) {
+            return (rounds, format!("{} \"{}\"", kind, pattern));
+        }
+        match self.default {
+            Some(rounds) => (rounds, "tool_rounds.default".to_string()),
+            None => (DEFAULT_TOOL_ROUNDS, "built-in default".to_string()),
+        }
+    }
+}
+
+/// Opt-in summarizing of large tool results before they go into the model's context. The chat
+/// history always keeps the full output.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ToolResultSummaryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_summary_threshold")]
+    pub threshold_chars: usize,
+    /// Model that writes the summaries; unset uses the session model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Per-tool mode. Edits and `ask_user` answers stay verbatim unless listed here.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tools: HashMap<String, ToolResultMode>,
+}
+
+impl Default for ToolResultSummaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_chars: DEFAULT_SUMMARY_THRESHOLD_CHARS,
+            model: None,
+            tools: HashMap::new(),
+        }
+    }
+}
+
+impl ToolResultSummaryConfig {
+    pub fn mode_for(&self, tool: &str) -> ToolResultMode {
+        self.tools.get(tool).copied().unwrap_or(match tool {
+            "create_file" | "str_replace_editor" | "insert_lines" | "delete_lines" | "edit_file" | "undo_edit" | "ask_user" => {
+                ToolResultMode::Verbatim
+            }
+            _ => ToolResultMode::Summarize,
+        })
+    }
+
+    pub fn should_summarize(&self, tool: &str, content: &str) -> bool {
+        self.enabled
+            && content.chars().count() > self.threshold_chars
+            && self.mode_for(tool) == ToolResultMode::Summarize
+    }
+}
+
+const TOOL_RESULT_SUMMARY_PROMPT: &str = "Summarize this tool output for a coding agent that will act on it. Keep file paths, line numbers, identifiers, error messages and numbers exact. Drop repetition and boilerplate. Answer with the summary only.";
+
+/// A question from the `ask_user` tool, answered with one of `options` or free text
+#[derive(Debug)]
+pub struct UserQuestion {
+    pub question: String,
+    pub options: Vec<String>,
+    pub reply: tokio::sync::oneshot::Sender<String>,
+}
+
+/// `ask_user` result when no human can answer (headless runs)
+pub const NO_HUMAN_AVAILABLE: &str =
+    "No human is available to answer. Proceed with the most reasonable default and state the assumption you made.";
+
+/// An answer that is just an option number ("2") picks that option
+pub fn resolve_answer(answer: &str, options: &[String]) -> String {
+    let answer = answer.trim();
+    answer
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| options.get(i))
+        .cloned()
+        .unwrap_or_else(|| answer.to_string())
+}
+
+/// Counts failed tool results in a row; any success resets it. Unlike the identical-call
+/// detector this catches a model trying many different calls that all fail.
+struct FailureStreak {
+    limit: u32,
+    count: u32,
+}
+
+impl FailureStreak {
+    fn new(limit: u32) -> Self {
+        Self { limit, count: 0 }
+    }
+
+    fn record(&mut self, success: bool) {
+        self.count = if success { 0 } else { self.count + 1 };
+    }
+
+    /// Whether the limit has been hit; the streak starts over so a steer can fire again later
+    fn tripped(&mut self) -> bool {
+        if self.limit == 0 || self.count < self.limit {
+            return false;
+        }
+        self.count = 0;
+        true
+    }
+}
+
+fn tool_failure_nudge(failures: u32) -> String {
+    format!(
+        "Your last {} tool calls all failed. Stop and reassess before calling another tool: re-read the relevant files, check your assumptions, and try a different approach or ask the user for help.",
+        failures
+    )
+}
+
+/// Rough token count for display, at about four characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Allowance for the role and framing of each message in a request
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// User turns at the end of the conversation that the context budget never trims
+const CONTEXT_KEEP_TURNS: usize = 2;
+
+const ELIDED_PREFIX: &str = "[tool output elided, ";
+
+/// Estimated tokens one message adds to a request, tool call arguments included
+fn message_tokens(message: &GrokMessage) -> usize {
+    let calls: usize = message
+        .tool_calls
+        .iter()
+        .flatten()
+        .map(|call| estimate_tokens(&call.function.name) + estimate_tokens(&call.function.arguments))
+        .sum();
+    MESSAGE_OVERHEAD_TOKENS + message.content.as_deref().map_or(0, estimate_tokens) + calls
+}
+
+/// `3412` as `3,412`
+fn with_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Trim `messages` to about `budget` estimated tokens. Tool results from before the last
+/// `keep_turns` user turns are replaced by a placeholder, oldest first; when that is not
+/// enough, the oldest whole turns are dropped. The system message and the last turns are
+/// always kept, and turns are only cut at a user message, so no tool result is left
+/// without the assistant message that called it.
+fn fit_context_budget(mut messages: Vec<GrokMessage>, budget: usize, keep_turns: usize) -> Vec<GrokMessage> {
+    let mut total: usize = messages.iter().map(message_tokens).sum();
+    if total <= budget {
+        return messages;
+    }
+    let offset = usize::from(messages.first().is_some_and(|m| m.role == "system"));
+    // The parts of a split message are one turn
+    let turn_starts: Vec<usize> = (offset..messages.len())
+        .filter(|&i| messages[i].role == "user" && (i == offset || messages[i - 1].role != "user"))
+        .collect();
+    if turn_starts.len() <= keep_turns {
+        return messages;
+    }
+    let protected_from = turn_starts[turn_starts.len() - keep_turns];
+
+    for message in &mut messages[offset..protected_from] {
+        if total <= budget {
+            break;
+        }
+        let Some(content) = message.content.as_deref().filter(|_| message.role == "tool") else {
+            continue;
+        };
+        if content.starts_with(ELIDED_PREFIX) {
+            continue;
+        }
+        let tokens = estimate_tokens(content);
+        let placeholder = format!("{}{} tokens]", ELIDED_PREFIX, with_thousands(tokens));
+        if estimate_tokens(&placeholder) < tokens {
+            total -= tokens - estimate_tokens(&placeholder);
+            message.content = Some(placeholder);
+        }
+    }
+
+    let mut drop_to = offset;
+    for &cut in turn_starts.iter().filter(|&&start| start > offset && start <= protected_from) {
+        if total <= budget {
+            break;
+        }
+        total -= messages[drop_to..cut].iter().map(message_tokens).sum::<usize>();
+        drop_to = cut;
+    }
+    messages.drain(offset..drop_to);
+    messages
+}
+
+/// Longest argument quoted in an explanation note
+const EXPLANATION_ARG_CHARS: usize = 80;
+
+/// "About to …" note for a tool call, built from its name and arguments
+pub fn explain_tool_call(tool_call: &GrokToolCall) -> String {
+    let args: serde_json::Value = serde_json::from_str(&tool_call.function.arguments).unwrap_or_default();
+    let arg = |key: &str| {
+        let value = args.get(key).and_then(|v| v.as_str()).unwrap_or("?").replace('\n', " ");
+        if value.chars().count() > EXPLANATION_ARG_CHARS {
+            format!("{}…", value.chars().take(EXPLANATION_ARG_CHARS).collect::<String>())
+        } else {
+            value
+        }
+    };
+    let action = match tool_call.function.name.as_str() {
+        "view_file" if args.get("paths").is_some() => format!("read {} files to see their current content", viewed_paths(&args).len()),
+        "view_file" => format!("read {} to see its current content", arg("path")),
+        "create_file" => format!("create {}", arg("path")),
+        "str_replace_editor" => format!("edit {} by replacing a block of text", arg("path")),
+        "insert_lines" => format!("insert lines into {} after line {}", arg("path"), args.get("line").unwrap_or(&serde_json::Value::Null)),
+        "delete_lines" => format!(
+            "delete lines {}-{} of {}",
+            args.get("start_line").unwrap_or(&serde_json::Value::Null),
+            args.get("end_line").unwrap_or(&serde_json::Value::Null),
+            arg("path")
+        ),
+        "edit_file" => format!("edit {}: {}", arg("target_file"), arg("instructions")),
+        "undo_edit" if args.get("all").and_then(|v| v.as_bool()) == Some(true) => "undo every file edit of this session".to_string(),
+        "undo_edit" => "undo the last file edit".to_string(),
+        "bash" => format!("run `{}`", arg("command")),
+        "search" => format!("search the project for \"{}\"", arg("query")),
+        "create_todo_list" | "update_todo_list" => "update the todo list to track progress".to_string(),
+        "check" => "run the project's checks to look for errors".to_string(),
+        "git_changes" => "review what has changed since the last commit".to_string(),
+        "ask_user" => format!("ask you: {}", arg("question")),
+        name => format!("call {} with {}", name, tool_call.function.arguments.chars().take(EXPLANATION_ARG_CHARS).collect::<String>()),
+    };
+    format!("💡 About to {}", action)
+}
+
+fn deferred_tool_call_note(limit: usize) -> String {
+    format!(
+        "Not run: only the first {} tool calls of a message are executed. Call this tool again in your next message if you still need it.",
+        limit
+    )
+}
+
+fn tool_failure_warning(failures: u32) -> String {
+    format!(
+        "🛑 {} tool calls failed in a row. Stopping because no progress is being made.",
+        failures
+    )
+}
+
+const EMPTY_RESPONSE_NUDGE: &str = "Your previous reply was empty. Please respond to my last message, either with an answer or by calling a tool.";
+
+// Warning markers are single wide emoji without a variation selector: "⚠️" is U+26A0 plus
+// U+FE0F, which ratatui measures as one cell while most terminals draw two, shifting the row.
+const MAX_ROUNDS_WARNING: &str = "🛑 Maximum tool execution rounds reached. Stopping to prevent infinite loops.";
+
+pub const TRUNCATED_WARNING: &str = "✂️ The response hit the provider's output length limit and was cut off. Ask the model to continue, or raise max_tokens in model_params.";
+
+/// Sent after a reply that stopped at the output length limit, so the model picks up where it stopped
+pub const CONTINUE_PROMPT: &str = "Your previous response was cut off by the output length limit. Continue exactly where it stopped, without repeating anything or adding a preamble.";
+
+/// Tokens left for the "[Part i/n …]" line on each part of a split message
+const PART_HEADER_TOKENS: usize = 32;
+
+fn part_header(part: usize, parts: usize) -> String {
+    format!("[Part {}/{} of one long message, split to fit the per-message limit. Read all parts before replying.]", part, parts)
+}
+
+/// Split `message` into parts of at most about `max_tokens` tokens, breaking at line ends
+/// where possible. Each part starts with a `[Part i/n …]` line; a message within the limit
+/// comes back unchanged as the only part.
+pub fn split_long_message(message: &str, max_tokens: usize) -> Vec<String> {
+    if estimate_tokens(message) <= max_tokens {
+        return vec![message.to_string()];
+    }
+    let max_chars = max_tokens.saturating_sub(PART_HEADER_TOKENS).max(1) * 4;
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for line in message.split_inclusive('\n') {
+        let mut line = line;
+        loop {
+            let line_chars = line.chars().count();
+            if current_chars + line_chars <= max_chars {
+                current.push_str(line);
+                current_chars += line_chars;
+                break;
+            }
+            if current_chars > 0 {
+                chunks.push(std::mem::take(&mut current));
+                current_chars = 0;
+                continue;
+            }
+            // A single line longer than a whole part is cut at a character boundary
+            let at = line.char_indices().nth(max_chars).map_or(line.len(), |(i, _)| i);
+            chunks.push(line[..at].to_string());
+            line = &line[at..];
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    let parts = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("{}\n{}", part_header(i + 1, parts), chunk))
+        .collect()
+}
+
+/// The original message from the consecutive user messages `split_long_message` produced
+fn join_message_parts(parts: &[GrokMessage]) -> String {
+    if parts.len() == 1 {
+        return parts[0].content.clone().unwrap_or_default();
+    }
+    parts
+        .iter()
+        .map(|part| {
+            let content = part.content.as_deref().unwrap_or_default();
+            content.split_once('\n').map_or(content, |(_, chunk)| chunk).to_string()
+        })
+        .collect()
+}
+
+fn infinite_loop_warning(tool: &str, calls: u32) -> String {
+    format!(
+        "🔁 Infinite loop detected: {} called {} times with identical parameters. This suggests the tool is not making progress. Stopping to prevent infinite loops.",
+        tool, calls
+    )
+}
+
+/// Notices a model that keeps making the same tool calls with the same arguments, in both
+/// `process_user_message` and the streamed tool rounds
+#[derive(Debug, Clone, Default)]
+struct ToolLoopGuard {
+    last_signature: String,
+    repeated: u32,
+}
+
+impl ToolLoopGuard {
+    /// The warning to stop with when `tool_calls` is the third identical round in a row
+    fn check(&mut self, tool_calls: &[GrokToolCall]) -> Option<String> {
+        let signature = tool_calls
+            .iter()
+            .map(|tc| format!("{}({})", tc.function.name, tc.function.arguments))
+            .collect::<Vec<_>>()
+            .join(";");
+        if signature.is_empty() || signature != self.last_signature {
+            self.repeated = 0;
+            self.last_signature = signature;
+            return None;
+        }
+        self.repeated += 1;
+        (self.repeated >= 2).then(|| infinite_loop_warning(&signature, self.repeated + 1))
+    }
+}
+
+/// A reply with no text and no tool calls, which would otherwise surface as the generic fallback
+fn is_empty_response(message: &GrokMessage) -> bool {
+    let has_content = message.content.as_deref().is_some_and(|c| !c.trim().is_empty());
+    let has_tool_calls = message.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty());
+    !has_content && !has_tool_calls
+}
+
+/// Marks a tool result whose content was replaced by `tool_outcome_summary`
+const COMPRESSED_PREFIX: &str = "[compressed] ";
+
+/// Tool results up to this long are already as short as a summary
+const COMPRESS_MIN_CHARS: usize = 200;
+
+/// The `path` and `paths` a `view_file` call names
+fn viewed_paths(args: &serde_json::Value) -> Vec<String> {
+    let listed = args.get("paths").and_then(|v| v.as_array()).into_iter().flatten();
+    listed.chain(args.get("path")).filter_map(|v| v.as_str().map(str::to_string)).collect()
+}
+
+/// One-line stand-in for an old tool result: what was done and how it went, without the output.
+/// The first line of a result says how it went (an edit's "Successfully …", an error message).
+fn tool_outcome_summary(tool_call: Option<&GrokToolCall>, content: &str) -> String {
+    let first_line: String = content.lines().next().unwrap_or_default().chars().take(EXPLANATION_ARG_CHARS).collect();
+    let lines = content.lines().count();
+    let args: serde_json::Value = tool_call
+        .and_then(|call| serde_json::from_str(&call.function.arguments).ok())
+        .unwrap_or_default();
+    let arg = |key: &str| args.get(key).and_then(|v| v.as_str()).unwrap_or("?").to_string();
+    let summary = match tool_call.map(|call| call.function.name.as_str()) {
+        Some("create_file" | "str_replace_editor" | "insert_lines" | "delete_lines" | "edit_file" | "undo_edit") => first_line,
+        Some("view_file") => {
+            format!("Viewed {} ({} lines); view it again if you need the content", viewed_paths(&args).join(", "), lines)
+        }
+        Some("bash") => format!("Ran `{}`: {} ({} lines of output omitted)", arg("command"), first_line, lines.saturating_sub(1)),
+        _ => format!("{} ({} more lines omitted)", first_line, lines.saturating_sub(1)),
+    };
+    format!("{}{}", COMPRESSED_PREFIX, summary)
 }
 
 impl GrokAgent {
@@ -28,23 +836,19 @@ impl GrokAgent {
         is_openai_compatible: Option<bool>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // Adaptive tool rounds configuration (inspired by LangGraph and industry best practices)
-        // Priority: explicit parameter > environment variable > model-based default > global default
-        let tool_rounds = max_tool_rounds
-            .or_else(|| std::env::var("GROK_MAX_TOOL_ROUNDS").ok().and_then(|v| v.parse().ok()))
-            .or_else(|| {
-                // Model-based defaults: complex models get more rounds
-                let model_name = model.as_deref().unwrap_or("grok-code-fast-1");
-                match model_name {
-                    m if m.contains("claude") => Some(15),      // Claude: more reasoning
-                    m if m.contains("gpt-4") => Some(12),       // GPT-4: good balance
-                    m if m.contains("grok") => Some(10),        // Grok: efficient
-                    m if m.contains("qwen") => Some(8),         // Qwen: lightweight
-                    _ => Some(5),                                // Unknown: conservative
-                }
-            })
-            .unwrap_or(5);
-        let client = GrokClient::new(api_key, model, Some(base_url), is_openai_compatible);
-        let text_editor = TextEditorTool::new();
+        // Priority: explicit parameter > environment variable > per-model table; the table from
+        // user settings is applied later with `set_tool_rounds_config`
+        let env_rounds = std::env::var("GROK_MAX_TOOL_ROUNDS").ok().and_then(|v| v.parse().ok());
+        let (tool_rounds, tool_rounds_source) = match (max_tool_rounds, env_rounds) {
+            (Some(rounds), _) => (rounds, "--max-tool-rounds".to_string()),
+            (None, Some(rounds)) => (rounds, "GROK_MAX_TOOL_ROUNDS".to_string()),
+            (None, None) => ToolRoundsConfig::default().resolve(model.as_deref().unwrap_or("grok-code-fast-1")),
+        };
+        let tool_rounds_explicit = max_tool_rounds.is_some() || env_rounds.is_some();
+        let client = GrokClient::new(api_key, model, Some(base_url), is_openai_compatible)?;
+        let session_id = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+        let mut text_editor = TextEditorTool::new();
+        text_editor.set_journal(EditJournal::for_session(&session_id));
         let bash = BashTool::new();
         let todo_tool = TodoTool::new();
         let search = SearchTool::new();
@@ -55,86 +859,934 @@ impl GrokAgent {
             None
         };
 
-        // Initialize with system message
-        let system_message = GrokMessage {
-            role: "system".to_string(),
-            content: Some("You are Grok CLI, an AI assistant that helps with file editing, coding tasks, and system operations.
+        let cwd = std::env::current_dir()?;
+        let project_context = ProjectContext::load(&cwd, dirs::home_dir().as_deref());
+
+        // Initialize with system message
+        let system_message = GrokMessage {
+            role: "system".to_string(),
+            content: Some("You are Grok CLI, an AI assistant that helps with file editing, coding tasks, and system operations.
+
+You have access to these tools:
+- view_file: View file contents or directory listings
+- create_file: Create new files with content (ONLY use this for files that don't exist yet)
+- str_replace_editor: Replace text in existing files (ALWAYS use this to edit or update existing files)
+- insert_lines: Insert text after a line number of an existing file (0 inserts at the top)
+- delete_lines: Delete a range of lines, both ends included, from an existing file
+- bash: Execute bash commands (use for searching, file discovery, navigation, and system operations)
+- check: Build-check the project (e.g. cargo check) and get errors with file and line
+- git_changes: Summarize what changed since the last commit, per file, with optional patches
+- ask_user: Ask the user a question when a decision needs a human, and wait for the answer
+- search: Unified search tool for finding text content or files (similar to Cursor's search functionality)
+- create_todo_list: Create a visual todo list for planning and tracking tasks
+- update_todo_list: Update existing todos in your todo list
+- request_confirmation: Request user confirmation for operations
+- check_session_acceptance: Check which operations are accepted for this session
+- edit_file: High-speed file editing with Morph Fast Apply (4,500+ tokens/sec with 98% accuracy)
+
+REAL-TIME INFORMATION:
+You have access to real-time web search and X (Twitter) data. When users ask for current information, latest news, or recent events, you automatically have access to up-to-date information from the web and social media.
+
+IMPORTANT TOOL USAGE RULES:
+- NEVER use create_file on files that already exist - this will overwrite them completely
+- ALWAYS use str_replace_editor to modify existing files, even for small changes
+- Before editing a file, use view_file to see its current contents
+- Use create_file ONLY when creating entirely new files that don't exist
+
+USER CONFIRMATION SYSTEM:
+File operations (create_file, str_replace_editor, insert_lines, delete_lines) and bash commands will automatically request user confirmation before execution. The confirmation system will show users the actual content or command before they decide. Users can choose to approve individual operations or approve all operations of that type for the session.
+
+If a user rejects an operation, the tool will return an error and you should not proceed with that specific operation.
+
+SEARCHING AND EXPLORATION:
+- Use search for fast, powerful text search across files or finding files by name (unified search tool)
+- Examples: search for text content like \"import.*react\", search for files like \"component.tsx\"
+- Use bash with commands like 'find', 'grep', 'rg', 'ls' for complex file operations and navigation
+- view_file is best for reading specific files you already know exist
+
+When a user asks you to edit, update, modify, or change an existing file:
+1. First use view_file to see the current contents
+2. Then use str_replace_editor to make the specific changes
+3. Never use create_file for existing files
+4. Use edit_file (Morph Fast Apply) for complex edits requiring full context
+
+TASK PLANNING WITH TODO LISTS:
+- For complex requests with multiple steps, ALWAYS create a todo list first to plan your approach
+- Use create_todo_list to break down tasks into manageable items with priorities
+- Mark tasks as 'in_progress' when you start working on them (only one at a time)
+- Mark tasks as 'completed' immediately when finished
+- Use update_todo_list to track your progress throughout the task
+- Todo lists provide visual feedback with colors: ✅ Green (completed), 🔄 Cyan (in progress), ⏳ Yellow (pending)
+- Always create todos with priorities: 'high' (🔴), 'medium' (🟡), 'low' (🟢)
+
+Be helpful, direct, and efficient. Always explain what you're doing and show the results.
+
+IMPORTANT RESPONSE GUIDELINES:
+- After using tools, do NOT respond with pleasantries like \"Thanks for...\" or \"Great!\"
+- Only provide necessary explanations or next steps if relevant to the task
+- Keep responses concise and focused on the actual work being done
+- If a tool execution completes the user's request, you can remain silent or give a brief confirmation
+
+Current working directory: ".to_string() + &cwd.to_string_lossy() + project_context.section().as_deref().unwrap_or_default()),
+            tool_calls: None,
+            tool_call_id: None,
+        };
+
+        Ok(GrokAgent {
+            grok_client: client,
+            text_editor,
+            bash,
+            todo_tool,
+            search,
+            confirmation_tool,
+            morph_editor,
+            chat_history: Vec::new(),
+            messages: vec![system_message],
+            max_tool_rounds: tool_rounds,
+            tool_rounds_source,
+            tool_rounds_explicit,
+            tool_rounds_config: ToolRoundsConfig::default(),
+            format_on_write: FormatOnWriteConfig::default(),
+            check: CheckConfig::default(),
+            tool_choice: ToolChoice::default(),
+            empty_response_retries: DEFAULT_EMPTY_RESPONSE_RETRIES,
+            session_id,
+            history_window: None,
+            max_consecutive_failures: DEFAULT_MAX_CONSECUTIVE_FAILURES,
+            tool_failure_action: ToolFailureAction::default(),
+            edit_confirmer: None,
+            pending_confirmations: PendingConfirmations::default(),
+            user_asker: None,
+            turn_changes: TurnChanges::default(),
+            change_summary: true,
+            auto_stage: false,
+            tool_result_summary: ToolResultSummaryConfig::default(),
+            max_tool_calls_per_message: None,
+            explain_tools: false,
+            conventional_commits: false,
+            project_context,
+            enabled_tools: None,
+            max_message_tokens: None,
+            compress_tool_results_after: None,
+            tool_concurrency: DEFAULT_TOOL_CONCURRENCY,
+            context_tokens: None,
+            cancellation: CancellationToken::new(),
+            tool_timeouts: ToolTimeouts::default(),
+            stream_tool_rounds: 0,
+            stream_loop_guard: ToolLoopGuard::default(),
+            last_turn_end: TurnEnd::default(),
+            last_reply: None,
+            mcp: None,
+            mcp_tools: Default::default(),
+            tool_output: None,
+            workspace_guard: None,
+        })
+    }
+
+    /// Enable formatting of files after the agent writes them (disabled by default)
+    pub fn set_format_on_write(&mut self, config: FormatOnWriteConfig) {
+        self.format_on_write = config;
+    }
+
+    /// Configure the `check` tool and whether it runs after every edit
+    pub fn set_check_config(&mut self, config: CheckConfig) {
+        self.check = config;
+    }
+
+    /// Set how the model may use tools. `required` and a specific tool apply to the first
+    /// request of each turn; follow-up rounds fall back to `auto` so the loop can finish.
+    pub fn set_tool_choice(&mut self, tool_choice: ToolChoice) {
+        self.tool_choice = tool_choice;
+    }
+
+    /// Extra provider parameters merged into each request body
+    pub fn set_model_params(&mut self, params: serde_json::Map<String, serde_json::Value>) {
+        self.grok_client.set_model_params(params);
+    }
+
+    /// Set one provider parameter, keeping the others
+    pub fn set_model_param(&mut self, key: &str, value: serde_json::Value) {
+        self.grok_client.model_params.insert(key.to_string(), value);
+    }
+
+    /// Number of nudged retries when the model returns an empty message (0 disables)
+    pub fn set_empty_response_retries(&mut self, retries: u32) {
+        self.empty_response_retries = retries;
+    }
+
+    /// Failed tool results in a row (0 disables) before `action` is taken
+    pub fn set_tool_failure_limit(&mut self, max_consecutive_failures: u32, action: ToolFailureAction) {
+        self.max_consecutive_failures = max_consecutive_failures;
+        self.tool_failure_action = action;
+    }
+
+    /// Run at most `limit` tool calls from one assistant message (`None` or `Some(0)` runs all).
+    /// The rest are answered with a note so the model can request them again next round.
+    pub fn set_max_tool_calls_per_message(&mut self, limit: Option<usize>) {
+        self.max_tool_calls_per_message = limit.filter(|&n| n > 0);
+    }
+
+    pub fn set_explain_tools(&mut self, enabled: bool) {
+        self.explain_tools = enabled;
+    }
+
+    pub fn explain_tools(&self) -> bool {
+        self.explain_tools
+    }
+
+    pub fn set_conventional_commits(&mut self, enabled: bool) {
+        self.conventional_commits = enabled;
+    }
+
+    /// Offer only these tools to the model; calls to any other tool fail
+    pub fn set_max_message_tokens(&mut self, max_tokens: Option<usize>) {
+        self.max_message_tokens = max_tokens.filter(|&n| n > 0);
+    }
+
+    pub fn max_message_tokens(&self) -> Option<usize> {
+        self.max_message_tokens
+    }
+
+    /// The user messages `message` is sent as: itself, or its parts when it is over `max_message_tokens`
+    pub fn message_parts(&self, message: &str) -> Vec<String> {
+        match self.max_message_tokens {
+            Some(max_tokens) => split_long_message(message, max_tokens),
+            None => vec![message.to_string()],
+        }
+    }
+
+    fn push_user_message(&mut self, message: &str) {
+        for part in self.message_parts(message) {
+            self.messages.push(GrokMessage {
+                role: "user".to_string(),
+                content: Some(part),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+        }
+        self.compress_old_tool_results();
+    }
+
+    pub fn set_compress_tool_results_after(&mut self, turns: Option<usize>) {
+        self.compress_tool_results_after = turns.filter(|&n| n > 0);
+    }
+
+    /// Shrink tool results from before the last `compress_tool_results_after` user turns to a
+    /// one-line outcome. Only `messages` changes; the chat history keeps the full output.
+    fn compress_old_tool_results(&mut self) {
+        let Some(keep) = self.compress_tool_results_after else {
+            return;
+        };
+        let turn_starts: Vec<usize> = (0..self.messages.len())
+            .filter(|&i| self.messages[i].role == "user" && (i == 0 || self.messages[i - 1].role != "user"))
+            .collect();
+        if turn_starts.len() <= keep {
+            return;
+        }
+        let cutoff = turn_starts[turn_starts.len() - keep];
+        let calls: HashMap<String, GrokToolCall> = self.messages[..cutoff]
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().flatten())
+            .map(|call| (call.id.clone(), call.clone()))
+            .collect();
+        for message in &mut self.messages[..cutoff] {
+            let Some(content) = message.content.as_deref().filter(|_| message.role == "tool") else {
+                continue;
+            };
+            if content.starts_with(COMPRESSED_PREFIX) || content.chars().count() <= COMPRESS_MIN_CHARS {
+                continue;
+            }
+            let call = message.tool_call_id.as_ref().and_then(|id| calls.get(id));
+            message.content = Some(tool_outcome_summary(call, content));
+        }
+    }
+
+    pub fn set_enabled_tools(&mut self, tools: Option<Vec<String>>) {
+        self.enabled_tools = tools;
+    }
+
+    /// Undo the session's last file edit, or all of them, as `/undo` and the `undo_edit` tool do
+    pub async fn undo_edits(&mut self, all: bool) -> ToolResult {
+        for path in self.text_editor.journal().pending_paths(all) {
+            self.turn_changes.record_before(&path);
+        }
+        let result = if all { self.text_editor.undo_all().await } else { self.text_editor.undo_last().await };
+        result.unwrap_or_else(|e| ToolResult { success: false, output: None, error: Some(format!("Undo failed: {}", e)), data: None })
+    }
+
+    /// Offer the tools of `manager`'s servers to the model as `mcp__<server>__<tool>`
+    pub fn set_mcp_manager(&mut self, manager: std::sync::Arc<tokio::sync::RwLock<MCPManager>>) {
+        self.mcp = Some(manager);
+        *self.mcp_tools.lock().unwrap() = None;
+    }
+
+    /// The MCP tools. When servers were added or removed since the last call, each server is
+    /// asked for its tools again with [`MCPManager::list_tools`] before they are rebuilt.
+    async fn mcp_tools(&self) -> Vec<GrokTool> {
+        let Some(manager) = &self.mcp else { return Vec::new() };
+        {
+            let manager = manager.read().await;
+            // A server that exited must be relisted so its tools are dropped
+            let fresh = |cache: &&McpToolCache| cache.revision == manager.revision() && !manager.has_exited_servers();
+            if let Some(cache) = self.mcp_tools.lock().unwrap().as_ref().filter(fresh) {
+                return cache.tools.clone();
+            }
+        }
+
+        let mut manager = manager.write().await;
+        let servers: Vec<String> = manager.tools().keys().cloned().collect();
+        for server in servers {
+            // Remote servers cannot be relisted and keep the tools found when they connected
+            let _ = manager.list_tools(&server).await;
+        }
+        let tools = manager.grok_tools();
+        *self.mcp_tools.lock().unwrap() = Some(McpToolCache { revision: manager.revision(), tools: tools.clone() });
+        tools
+    }
+
+    async fn call_mcp_tool(&self, name: &str, arguments: &str) -> ToolResult {
+        let Some(manager) = &self.mcp else {
+            return mcp::to_tool_result(Err(format!("Unknown tool: {}", name)));
+        };
+        let manager = manager.read().await;
+        let Some((server, tool)) = manager.resolve_tool(name) else {
+            return mcp::to_tool_result(Err(format!("Unknown tool: {}", name)));
+        };
+        let arguments = if arguments.trim().is_empty() {
+            serde_json::json!({})
+        } else {
+            match serde_json::from_str(arguments) {
+                Ok(arguments) => arguments,
+                Err(e) => return mcp::to_tool_result(Err(format!("Invalid arguments for {}: {}", name, e))),
+            }
+        };
+        mcp::to_tool_result(manager.call_tool(&server, &tool, arguments).await)
+    }
+
+    fn tool_enabled(&self, name: &str) -> bool {
+        self.enabled_tools.as_ref().is_none_or(|tools| tools.iter().any(|tool| tool == name))
+    }
+
+    /// Add instructions to the end of the system prompt, keeping the built-in tool rules
+    pub fn append_system_prompt(&mut self, instructions: &str) {
+        if let Some(system) = self.messages.first_mut().filter(|m| m.role == "system") {
+            let content = system.content.get_or_insert_with(String::new);
+            content.push_str("\n\nPROJECT INSTRUCTIONS:\n");
+            content.push_str(instructions.trim());
+        }
+    }
+
+    /// The system prompt sent with every request: the built-in prompt plus any project instructions
+    pub fn system_prompt(&self) -> &str {
+        self.messages
+            .first()
+            .filter(|m| m.role == "system")
+            .and_then(|m| m.content.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Replace the system prompt for the rest of the session; the conversation is kept
+    pub fn set_system_prompt(&mut self, prompt: &str) {
+        match self.messages.first_mut().filter(|m| m.role == "system") {
+            Some(system) => system.content = Some(prompt.to_string()),
+            None => self.messages.insert(
+                0,
+                GrokMessage { role: "system".to_string(), content: Some(prompt.to_string()), tool_calls: None, tool_call_id: None },
+            ),
+        }
+    }
+
+    /// Read the `GROK.md` files again and put their instructions in the system prompt in place of
+    /// the ones read before. A system prompt replaced with `/system edit` gets them at its end.
+    pub fn reload_project_context(&mut self) -> &ProjectContext {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let context = ProjectContext::load(&cwd, dirs::home_dir().as_deref());
+        let (old, new) = (self.project_context.section().unwrap_or_default(), context.section().unwrap_or_default());
+        let prompt = self.system_prompt();
+        let prompt = match prompt.find(&old) {
+            Some(start) if !old.is_empty() => format!("{}{}{}", &prompt[..start], new, &prompt[start + old.len()..]),
+            _ => format!("{}{}", prompt, new),
+        };
+        self.set_system_prompt(&prompt);
+        self.project_context = context;
+        &self.project_context
+    }
+
+    /// Notes for the tool calls that will run, when explain mode is on and the assistant
+    /// message came without text of its own
+    pub fn explain_tool_calls(&self, content: &str, tool_calls: &[GrokToolCall]) -> Vec<String> {
+        if !self.explain_tools || !content.trim().is_empty() {
+            return Vec::new();
+        }
+        self.split_tool_calls(tool_calls).0.iter().map(explain_tool_call).collect()
+    }
+
+    /// Keep explanation notes in the transcript; they are not sent to the model
+    fn record_explanations(&mut self, notes: Vec<String>) -> Vec<ChatEntry> {
+        notes
+            .into_iter()
+            .map(|content| {
+                let entry = ChatEntry {
+                    entry_type: ChatEntryType::Assistant,
+                    content,
+                    timestamp: chrono::Utc::now(),
+                    tool_calls: None,
+                    tool_call: None,
+                    tool_result: None,
+                    is_streaming: None,
+                };
+                self.chat_history.push(entry.clone());
+                entry
+            })
+            .collect()
+    }
+
+    /// Keep a line about a `@path` mention that was not added to the prompt in the transcript
+    fn record_mention_warnings(&mut self, warnings: Vec<String>) -> Vec<ChatEntry> {
+        let entries: Vec<ChatEntry> = warnings
+            .into_iter()
+            .map(|content| ChatEntry {
+                entry_type: ChatEntryType::System,
+                content,
+                timestamp: chrono::Utc::now(),
+                tool_calls: None,
+                tool_call: None,
+                tool_result: None,
+                is_streaming: None,
+            })
+            .collect();
+        self.chat_history.extend(entries.iter().cloned());
+        entries
+    }
+
+    /// Keep a warning that ends the agent loop in the transcript
+    fn record_warning(&mut self, content: String) -> ChatEntry {
+        let entry = ChatEntry {
+            entry_type: ChatEntryType::Assistant,
+            content,
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: None,
+            tool_result: None,
+            is_streaming: None,
+        };
+        self.chat_history.push(entry.clone());
+        entry
+    }
+
+    /// Use `token` to stop the following run: no more tool calls start, the stream ends early
+    /// and no further request is sent once it is cancelled
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.cancellation = token;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Note the cancellation in the transcript. The conversation sent to the model is left as
+    /// it is: every tool call in it already has a result.
+    pub fn record_cancellation(&mut self) -> ChatEntry {
+        let entry = ChatEntry {
+            entry_type: ChatEntryType::Assistant,
+            content: CANCELLED_NOTE.to_string(),
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: None,
+            tool_result: None,
+            is_streaming: None,
+        };
+        self.chat_history.push(entry.clone());
+        entry
+    }
+
+    pub fn set_tool_timeouts(&mut self, timeouts: ToolTimeouts) {
+        self.tool_timeouts = timeouts;
+    }
+
+    /// How many read-only tool calls may run at once; `1` runs every call in turn
+    pub fn set_tool_concurrency(&mut self, limit: usize) {
+        self.tool_concurrency = limit.max(1);
+    }
+
+    /// Run `tool_calls` and return their results in the same order. Runs of consecutive
+    /// read-only calls are executed together, `tool_concurrency` at a time. Once the run is
+    /// cancelled no further calls start, so fewer results than calls can come back.
+    async fn execute_tools(&mut self, tool_calls: &[GrokToolCall]) -> Vec<Result<ToolResult, String>> {
+        let mut results = Vec::with_capacity(tool_calls.len());
+        let mut rest = tool_calls;
+        while let Some(first) = rest.first() {
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+            let batch = rest
+                .iter()
+                .take_while(|call| is_read_only_tool(&call.function.name) && self.tool_enabled(&call.function.name))
+                .count();
+            if batch == 0 {
+                results.push(self.execute_tool(first).await.map_err(|e| e.to_string()));
+                rest = &rest[1..];
+                continue;
+            }
+            let agent = &*self;
+            for chunk in rest[..batch].chunks(self.tool_concurrency) {
+                let finished = futures::future::join_all(chunk.iter().map(|call| async move {
+                    let limit = agent.tool_timeouts.for_tool(&call.function.name);
+                    run_with_timeout(limit, agent.dispatch_read_only_tool(call)).await.map_err(|e| e.to_string())
+                }))
+                .await;
+                results.extend(finished);
+            }
+            rest = &rest[batch..];
+        }
+        results
+    }
+
+    /// Split a message's tool calls into the ones to run and the ones to defer
+    fn split_tool_calls<'a>(&self, tool_calls: &'a [GrokToolCall]) -> (&'a [GrokToolCall], &'a [GrokToolCall]) {
+        let limit = self.max_tool_calls_per_message.unwrap_or(usize::MAX).min(tool_calls.len());
+        tool_calls.split_at(limit)
+    }
+
+    /// Answer a tool call past the per-message limit without running it
+    fn defer_tool_call(&mut self, tool_call: &GrokToolCall) -> ChatEntry {
+        let note = deferred_tool_call_note(self.max_tool_calls_per_message.unwrap_or_default());
+        self.skip_tool_call(tool_call, note)
+    }
+
+    /// Record `note` as the failed result of a call that was not run, since every call in an
+    /// assistant message needs a result
+    fn skip_tool_call(&mut self, tool_call: &GrokToolCall, note: String) -> ChatEntry {
+        self.messages.push(GrokMessage {
+            role: "tool".to_string(),
+            content: Some(note.clone()),
+            tool_calls: None,
+            tool_call_id: Some(tool_call.id.clone()),
+        });
+        let entry = ChatEntry {
+            entry_type: ChatEntryType::ToolResult,
+            content: note.clone(),
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: Some(tool_call.clone()),
+            tool_result: Some(ToolResult {
+                success: false,
+                output: None,
+                error: Some(note),
+                data: None,
+            }),
+            is_streaming: None,
+        };
+        self.chat_history.push(entry.clone());
+        entry
+    }
+
+    /// Answer identical requests from the on-disk response cache (off by default)
+    pub fn set_response_cache(&mut self, cache: Option<crate::grok::response_cache::ResponseCache>) {
+        self.grok_client.set_response_cache(cache);
+    }
+
+    pub fn response_cache(&self) -> Option<&crate::grok::response_cache::ResponseCache> {
+        self.grok_client.response_cache()
+    }
+
+    /// Transcript text for a reply, marked when the last response came from the cache. The
+    /// marker is only shown; the model gets the reply as it was.
+    fn cache_marked(&self, content: String) -> String {
+        if self.grok_client.last_from_cache() {
+            format!("{}\n(from cache)", content)
+        } else {
+            content
+        }
+    }
+
+    /// Summarize large tool results before they are sent to the model (off by default)
+    pub fn set_tool_result_summary(&mut self, config: ToolResultSummaryConfig) {
+        self.tool_result_summary = config;
+    }
+
+    /// What the model sees for a tool result: the content itself, or a summary of it when the
+    /// policy says so. A failed summary request falls back to the full content.
+    async fn tool_result_for_model(&self, tool_call: &GrokToolCall, content: String) -> String {
+        if !self.tool_result_summary.should_summarize(&tool_call.function.name, &content) {
+            return content;
+        }
+        let request = vec![
+            GrokMessage {
+                role: "system".to_string(),
+                content: Some(TOOL_RESULT_SUMMARY_PROMPT.to_string()),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            GrokMessage {
+                role: "user".to_string(),
+                content: Some(format!(
+                    "Tool: {}\nArguments: {}\n\nOutput:\n{}",
+                    tool_call.function.name, tool_call.function.arguments, content
+                )),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ];
+        let summary = self
+            .grok_client
+            .chat(request, None, self.tool_result_summary.model.clone(), None)
+            .await
+            .ok()
+            .and_then(|response| response.choices.into_iter().next())
+            .and_then(|choice| choice.message.content)
+            .filter(|summary| !summary.trim().is_empty());
+        match summary {
+            Some(summary) => format!(
+                "[Summary of {} characters of {} output; the user sees the full result]\n{}",
+                content.chars().count(),
+                tool_call.function.name,
+                summary.trim()
+            ),
+            None => content,
+        }
+    }
+
+    /// A commit message for the staged changes from a request outside the conversation; empty
+    /// when the model answered with nothing
+    pub async fn generate_commit_message(&self, stat: &str, diff: &str) -> Result<String, AgentError> {
+        let request = vec![
+            GrokMessage {
+                role: "system".to_string(),
+                content: Some(crate::prompts::commit_message::system_prompt(self.conventional_commits)),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            GrokMessage {
+                role: "user".to_string(),
+                content: Some(crate::prompts::commit_message::request(stat, diff)),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ];
+        self.request_once(request).await
+    }
+
+    /// A starter `GROK.md` for the repository `overview` describes; empty when the model answered
+    /// with nothing
+    pub async fn generate_project_instructions(&self, overview: &str) -> Result<String, AgentError> {
+        let request = vec![
+            GrokMessage {
+                role: "system".to_string(),
+                content: Some(crate::prompts::init::SYSTEM_PROMPT.to_string()),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            GrokMessage {
+                role: "user".to_string(),
+                content: Some(crate::prompts::init::request(overview)),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ];
+        self.request_once(request).await
+    }
+
+    /// The answer to `request`, sent with the session model and no tools
+    async fn request_once(&self, request: Vec<GrokMessage>) -> Result<String, AgentError> {
+        let response = self.grok_client.chat(request, None, None, None).await?;
+        let reply = response.choices.into_iter().next().and_then(|choice| choice.message.content).unwrap_or_default();
+        Ok(crate::prompts::parse_reply(&reply))
+    }
+
+    /// Send `ask_user` questions to `asker` and wait for the answer
+    pub fn set_user_asker(&mut self, asker: tokio::sync::mpsc::Sender<UserQuestion>) {
+        self.user_asker = Some(asker);
+    }
+
+    async fn ask_user(&self, question: &str, options: Vec<String>) -> ToolResult {
+        let no_human = || ToolResult {
+            success: true,
+            output: Some(NO_HUMAN_AVAILABLE.to_string()),
+            error: None,
+            data: Some(serde_json::json!({ "answered": false })),
+        };
+        let Some(asker) = &self.user_asker else {
+            return no_human();
+        };
+        let (reply, answer) = tokio::sync::oneshot::channel();
+        let request = UserQuestion { question: question.to_string(), options, reply };
+        if asker.send(request).await.is_err() {
+            return no_human();
+        }
+        match answer.await {
+            Ok(answer) => ToolResult {
+                success: true,
+                output: Some(format!("The user answered: {}", answer)),
+                error: None,
+                data: Some(serde_json::json!({ "answered": true, "answer": answer })),
+            },
+            Err(_) => no_human(),
+        }
+    }
+
+    /// Turn the per-turn "Changed: …" summary on or off (on by default)
+    pub fn set_change_summary(&mut self, enabled: bool) {
+        self.change_summary = enabled;
+    }
+
+    pub fn set_auto_stage(&mut self, enabled: bool) {
+        self.auto_stage = enabled;
+    }
+
+    /// End the turn: stage the files it changed when auto-stage is on, and return the entry
+    /// summarizing the changes and what was staged, also added to the chat history.
+    /// `None` when there is nothing to report.
+    pub fn finish_turn(&mut self) -> Option<ChatEntry> {
+        let summary = self.change_summary.then(|| self.turn_changes.summary()).flatten();
+        let staged = if self.auto_stage { self.turn_changes.stage() } else { Vec::new() };
+        self.turn_changes.clear();
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let staged = (!staged.is_empty()).then(|| {
+            let files: Vec<String> = staged
+                .iter()
+                .map(|path| path.strip_prefix(&cwd).unwrap_or(path).display().to_string())
+                .collect();
+            format!("📥 Staged: {}", files.join(", "))
+        });
+        let content = match (summary, staged) {
+            (Some(summary), Some(staged)) => format!("{}\n{}", summary, staged),
+            (summary, staged) => summary.or(staged)?,
+        };
+        let entry = ChatEntry {
+            entry_type: ChatEntryType::Assistant,
+            content,
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: None,
+            tool_result: None,
+            is_streaming: None,
+        };
+        self.chat_history.push(entry.clone());
+        Some(entry)
+    }
+
+    /// Ask for approval of every file edit and bash command through `confirmer` unless the
+    /// session accepted them. Each request waits until [`Self::resolve_confirmation`] answers it.
+    pub fn set_edit_confirmer(&mut self, confirmer: tokio::sync::mpsc::Sender<EditConfirmation>) {
+        self.edit_confirmer = Some(confirmer);
+    }
+
+    /// Answer a confirmation sent to the confirmer. Any clone of the agent can answer it.
+    pub fn resolve_confirmation(&self, id: &str, decision: EditDecision) -> bool {
+        self.pending_confirmations.resolve(id, decision)
+    }
+
+    /// A handle that answers confirmations without the agent, e.g. from another task
+    pub fn pending_confirmations(&self) -> PendingConfirmations {
+        self.pending_confirmations.clone()
+    }
+
+    /// Run file edits and bash commands without asking for the rest of the session (`--yolo`)
+    pub fn set_auto_accept(&mut self, enabled: bool) {
+        self.set_auto_edit(enabled);
+        self.confirmation_tool.set_session_flag("bash_commands", enabled);
+    }
+
+    /// Auto-approve `create_file`, `str_replace_editor`, `insert_lines`, `delete_lines`, `edit_file` and `undo_edit`. The flag is shared with
+    /// every clone of this agent, so it holds for the whole session.
+    pub fn set_auto_edit(&mut self, enabled: bool) {
+        self.confirmation_tool.set_session_flag("file_operations", enabled);
+    }
+
+    pub fn auto_edit(&self) -> bool {
+        self.confirmation_tool.file_operations_accepted()
+    }
+
+    /// Wait for the user to approve a file edit or bash command. `None` means go ahead; when
+    /// there is nobody to ask (no confirmer, or it has gone away) the call is rejected.
+    async fn confirm_tool_call(&mut self, tool_call: &GrokToolCall) -> Option<ToolResult> {
+        let bash = tool_call.function.name == "bash";
+        let accepted = if bash { self.confirmation_tool.bash_commands_accepted() } else { self.auto_edit() };
+        if accepted {
+            return None;
+        }
+        let (description, mut detail) = confirmation_detail(tool_call);
+        if tool_call.function.name == "undo_edit" {
+            // Undo restores or deletes whole files; name every one it would touch
+            let args: serde_json::Value = serde_json::from_str(&tool_call.function.arguments).unwrap_or_default();
+            let all = args.get("all").and_then(|v| v.as_bool()) == Some(true);
+            for path in self.text_editor.journal().pending_paths(all) {
+                detail.push_str(&format!("\n  {}", path.display()));
+            }
+        }
+        let nothing_done = if bash { "run" } else { "written" };
+        let Some(confirmer) = self.edit_confirmer.clone() else {
+            return Some(unapproved_result(&description, nothing_done));
+        };
+        let entry = ChatEntry {
+            entry_type: ChatEntryType::ConfirmationPending,
+            content: detail,
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: Some(tool_call.clone()),
+            tool_result: None,
+            is_streaming: None,
+        };
+        self.chat_history.push(entry.clone());
+        let (reply, decision) = tokio::sync::oneshot::channel();
+        self.pending_confirmations.0.lock().unwrap().insert(tool_call.id.clone(), reply);
+        let request = EditConfirmation { id: tool_call.id.clone(), description: description.clone(), entry };
+        if confirmer.send(request).await.is_err() {
+            self.pending_confirmations.0.lock().unwrap().remove(&tool_call.id);
+            return Some(unapproved_result(&description, nothing_done));
+        }
+
+        match decision.await.unwrap_or(EditDecision::Reject) {
+            EditDecision::Approve => None,
+            EditDecision::ApproveAll if bash => {
+                self.confirmation_tool.set_session_flag("bash_commands", true);
+                None
+            }
+            EditDecision::ApproveAll => {
+                self.set_auto_edit(true);
+                None
+            }
+            EditDecision::Reject => Some(ToolResult {
+                success: false,
+                output: None,
+                error: Some(format!(
+                    "The user rejected {}. Nothing was {}; ask what they would like instead.",
+                    description, nothing_done
+                )),
+                data: None,
+            }),
+        }
+    }
+
+    pub fn get_tool_choice(&self) -> &ToolChoice {
+        &self.tool_choice
+    }
+
+    pub async fn tool_names(&self) -> Vec<String> {
+        self.get_all_tools().await.into_iter().map(|tool| tool.function.name).collect()
+    }
+
+    fn follow_up_tool_choice(&self) -> ToolChoice {
+        match self.tool_choice {
+            ToolChoice::None => ToolChoice::None,
+            _ => ToolChoice::Auto,
+        }
+    }
+
+    /// Limit requests to the last `turns` user turns; `None` or `Some(0)` sends the full history.
+    /// The stored conversation is unaffected.
+    pub fn set_history_window(&mut self, turns: Option<usize>) {
+        self.history_window = turns.filter(|&n| n > 0);
+    }
+
+    pub fn get_history_window(&self) -> Option<usize> {
+        self.history_window
+    }
+
+    /// Trim each request to about `tokens` estimated prompt tokens; `None` turns it off
+    pub fn set_context_tokens(&mut self, tokens: Option<usize>) {
+        self.context_tokens = tokens.filter(|&n| n > 0);
+    }
+
+    /// The messages sent with the next request: the history window, then the context budget
+    fn request_messages(&self) -> Vec<GrokMessage> {
+        let messages = self.windowed_messages();
+        match self.context_tokens {
+            Some(budget) => fit_context_budget(messages, budget, CONTEXT_KEEP_TURNS),
+            None => messages,
+        }
+    }
 
-You have access to these tools:
-- view_file: View file contents or directory listings
-- create_file: Create new files with content (ONLY use this for files that don't exist yet)
-- str_replace_editor: Replace text in existing files (ALWAYS use this to edit or update existing files)
-- bash: Execute bash commands (use for searching, file discovery, navigation, and system operations)
-- search: Unified search tool for finding text content or files (similar to Cursor's search functionality)
-- create_todo_list: Create a visual todo list for planning and tracking tasks
-- update_todo_list: Update existing todos in your todo list
-- request_confirmation: Request user confirmation for operations
-- check_session_acceptance: Check which operations are accepted for this session
-- edit_file: High-speed file editing with Morph Fast Apply (4,500+ tokens/sec with 98% accuracy)
+    /// Messages to send with the next request, before the context budget: the system prompt plus
+    /// the windowed history. The window always starts at a user message so tool calls stay paired
+    /// with their results.
+    fn windowed_messages(&self) -> Vec<GrokMessage> {
+        let Some(window) = self.history_window else {
+            return self.messages.clone();
+        };
+        let (system, history) = match self.messages.first() {
+            Some(first) if first.role == "system" => (Some(first), &self.messages[1..]),
+            _ => (None, &self.messages[..]),
+        };
 
-REAL-TIME INFORMATION:
-You have access to real-time web search and X (Twitter) data. When users ask for current information, latest news, or recent events, you automatically have access to up-to-date information from the web and social media.
+        let user_turns: Vec<usize> = history
+            .iter()
+            .enumerate()
+            // The parts of a split message are one turn
+            .filter(|(i, m)| m.role == "user" && (*i == 0 || history[i - 1].role != "user"))
+            .map(|(i, _)| i)
+            .collect();
+        let start = if user_turns.len() > window {
+            user_turns[user_turns.len() - window]
+        } else {
+            0
+        };
 
-IMPORTANT TOOL USAGE RULES:
-- NEVER use create_file on files that already exist - this will overwrite them completely
-- ALWAYS use str_replace_editor to modify existing files, even for small changes
-- Before editing a file, use view_file to see its current contents
-- Use create_file ONLY when creating entirely new files that don't exist
+        system.into_iter().cloned().chain(history[start..].iter().cloned()).collect()
+    }
 
-USER CONFIRMATION SYSTEM:
-File operations (create_file, str_replace_editor) and bash commands will automatically request user confirmation before execution. The confirmation system will show users the actual content or command before they decide. Users can choose to approve individual operations or approve all operations of that type for the session.
+    /// Extra dangerous command rules from user settings, on top of the built-in ones
+    pub fn add_dangerous_command_rules(&mut self, rules: Vec<DangerousCommandRule>) {
+        self.bash.add_dangerous_rules(rules);
+    }
 
-If a user rejects an operation, the tool will return an error and you should not proceed with that specific operation.
+    /// Built-in and user dangerous command rules, and what happens to a command that matches
+    pub fn dangerous_command_policy(&self) -> (&[DangerousCommandRule], &[DangerousCommandRule], DangerousCommandMode) {
+        let (builtin, user) = self.bash.dangerous_rules();
+        (builtin, user, self.bash.dangerous_mode())
+    }
 
-SEARCHING AND EXPLORATION:
-- Use search for fast, powerful text search across files or finding files by name (unified search tool)
-- Examples: search for text content like \"import.*react\", search for files like \"component.tsx\"
-- Use bash with commands like 'find', 'grep', 'rg', 'ls' for complex file operations and navigation
-- view_file is best for reading specific files you already know exist
+    pub fn set_user_dangerous_command_rules(&mut self, rules: Vec<DangerousCommandRule>) {
+        self.bash.set_user_dangerous_rules(rules);
+    }
 
-When a user asks you to edit, update, modify, or change an existing file:
-1. First use view_file to see the current contents
-2. Then use str_replace_editor to make the specific changes
-3. Never use create_file for existing files
-4. Use edit_file (Morph Fast Apply) for complex edits requiring full context
+    pub fn set_dangerous_command_mode(&mut self, mode: DangerousCommandMode) {
+        self.bash.set_dangerous_mode(mode);
+    }
 
-TASK PLANNING WITH TODO LISTS:
-- For complex requests with multiple steps, ALWAYS create a todo list first to plan your approach
-- Use create_todo_list to break down tasks into manageable items with priorities
-- Mark tasks as 'in_progress' when you start working on them (only one at a time)
-- Mark tasks as 'completed' immediately when finished
-- Use update_todo_list to track your progress throughout the task
-- Todo lists provide visual feedback with colors: ✅ Green (completed), 🔄 Cyan (in progress), ⏳ Yellow (pending)
-- Always create todos with priorities: 'high' (🔴), 'medium' (🟡), 'low' (🟢)
+    /// Keep bash commands inside a workspace; `None` lifts the limit
+    pub fn set_bash_confinement(&mut self, confinement: Option<BashConfinement>) {
+        self.bash.set_confinement(confinement);
+    }
 
-Be helpful, direct, and efficient. Always explain what you're doing and show the results.
+    /// Refuse bash commands `bashPolicy` does not allow; `None` runs everything
+    pub fn set_bash_policy(&mut self, policy: Option<BashPolicy>) {
+        self.bash.set_policy(policy);
+    }
 
-IMPORTANT RESPONSE GUIDELINES:
-- After using tools, do NOT respond with pleasantries like \"Thanks for...\" or \"Great!\"
-- Only provide necessary explanations or next steps if relevant to the task
-- Keep responses concise and focused on the actual work being done
-- If a tool execution completes the user's request, you can remain silent or give a brief confirmation
+    /// Keep the file tools inside `guard`'s root; `None` lets them use any path
+    pub fn set_workspace_guard(&mut self, guard: Option<WorkspaceGuard>) {
+        self.text_editor.set_workspace_guard(guard.clone());
+        if let Some(morph_editor) = self.morph_editor.as_mut() {
+            morph_editor.set_workspace_guard(guard.clone());
+        }
+        self.workspace_guard = guard;
+    }
 
-Current working directory: ".to_string() + &std::env::current_dir()?.to_string_lossy()),
-            tool_calls: None,
-            tool_call_id: None,
-        };
+    /// What `/status` says about the paths the file tools may use
+    pub fn workspace_restriction(&self) -> String {
+        match &self.workspace_guard {
+            Some(guard) => format!("File tools: limited to {}", guard.root().display()),
+            None => "File tools: any path (allow_outside_workspace)".to_string(),
+        }
+    }
 
-        Ok(GrokAgent {
-            grok_client: client,
-            text_editor,
-            bash,
-            todo_tool,
-            search,
-            confirmation_tool,
-            morph_editor,
-            chat_history: Vec::new(),
-            messages: vec![system_message],
-            max_tool_rounds: tool_rounds,
-        })
+    /// Line ending used for files the agent creates from scratch
+    pub fn set_new_file_line_ending(&mut self, line_ending: LineEnding) {
+        self.text_editor.set_new_file_line_ending(line_ending);
+    }
+
+    /// Bytes of file content one multi-file `view_file` call returns; `None` keeps the default
+    pub fn set_view_budget(&mut self, bytes: Option<usize>) {
+        self.text_editor.set_view_budget(bytes.filter(|&n| n > 0).unwrap_or(crate::tools::DEFAULT_VIEW_BUDGET_BYTES));
     }
 
-    pub async fn process_user_message(&mut self, message: &str) -> Result<Vec<ChatEntry>, Box<dyn std::error::Error>> {
+    pub async fn process_user_message(&mut self, message: &str) -> Result<Vec<ChatEntry>, AgentError> {
+        // A held dangerous command runs on the next attempt only if this reply is exactly YES
+        self.bash.confirm_pending(message);
+        self.turn_changes.clear();
+
         // Add user message to conversation
         let user_entry = ChatEntry {
             entry_type: ChatEntryType::User,
@@ -146,30 +1798,30 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
             is_streaming: None,
         };
         self.chat_history.push(user_entry.clone());
-        self.messages.push(GrokMessage {
-            role: "user".to_string(),
-            content: Some(message.to_string()),
-            tool_calls: None,
-            tool_call_id: None,
-        });
+        let expanded = mentions::expand_mentions(&self.text_editor, message).await;
+        self.push_user_message(&expanded.content);
 
         let mut new_entries = vec![user_entry.clone()];
+        new_entries.extend(self.record_mention_warnings(expanded.warnings));
         let mut tool_rounds = 0;
-        let mut last_tool_signature: String = String::new();  // Track tool name + arguments for loop detection
-        let mut repeated_calls = 0;  // Count repeated identical tool calls
+        let mut loop_guard = ToolLoopGuard::default();
+        self.last_turn_end = TurnEnd::Error;
+        self.last_reply = None;
+        let mut failures = FailureStreak::new(self.max_consecutive_failures);
 
         // Get all available tools
         let tools = self.get_all_tools().await;
 
+        self.grok_client.set_tool_choice(self.tool_choice.clone());
         let mut current_response = match self.grok_client.chat(
-            self.messages.clone(),
+            self.request_messages(),
             Some(tools),
             None,
             None,
         ).await {
             Ok(response) => response,
             Err(e) => {
-                if e.to_string().contains("No API key set") {
+                if e == AgentError::MissingApiKey {
                     let error_entry = ChatEntry {
                         entry_type: ChatEntryType::Assistant,
                         content: "No API key configured. Please set your API key in settings before proceeding with chat functionality.".to_string(),
@@ -180,18 +1832,50 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                         is_streaming: None,
                     };
                     self.chat_history.push(error_entry.clone());
-                    return Ok(vec![user_entry, error_entry]);
+                    self.last_turn_end = TurnEnd::Error;
+                    new_entries.push(error_entry);
+                    return Ok(new_entries);
                 } else {
                     return Err(e);
                 }
             }
         };
 
+        let mut empty_retries_left = self.empty_response_retries;
+        let mut end = TurnEnd::MaxToolRounds;
+
         // Agent loop - continue until no more tool calls or max rounds reached
         while tool_rounds < self.max_tool_rounds {
+            let response_is_empty = current_response
+                .choices
+                .first()
+                .is_none_or(|choice| is_empty_response(&choice.message));
+            if response_is_empty && empty_retries_left > 0 {
+                empty_retries_left -= 1;
+
+                // The nudge only goes into this request, not the saved conversation
+                let mut retry_messages = self.request_messages();
+                retry_messages.push(GrokMessage {
+                    role: "user".to_string(),
+                    content: Some(EMPTY_RESPONSE_NUDGE.to_string()),
+                    tool_calls: None,
+                    tool_call_id: None,
+                });
+                current_response = self.grok_client.chat(
+                    retry_messages,
+                    Some(self.get_all_tools().await),
+                    None,
+                    None,
+                ).await?;
+                continue;
+            }
+
             let assistant_message = match current_response.choices.first() {
                 Some(choice) => &choice.message,
-                None => break,
+                None => {
+                    end = TurnEnd::Completed;
+                    break;
+                }
             };
 
             // Check if there are tool calls (must be non-empty)
@@ -200,13 +1884,14 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     // Empty tool_calls array - treat as no tool calls
                     let final_entry = ChatEntry {
                         entry_type: ChatEntryType::Assistant,
-                        content: assistant_message.content.clone().unwrap_or_else(|| "I understand, but I don't have a specific response.".to_string()),
+                        content: self.cache_marked(assistant_message.content.clone().unwrap_or_else(|| "I understand, but I don't have a specific response.".to_string())),
                         timestamp: chrono::Utc::now(),
                         tool_calls: None,
                         tool_call: None,
                         tool_result: None,
                         is_streaming: None,
                     };
+                    self.last_reply = Some(final_entry.content.clone());
                     self.chat_history.push(final_entry.clone());
                     new_entries.push(final_entry);
 
@@ -216,55 +1901,23 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                         tool_calls: None,
                         tool_call_id: None,
                     });
+                    end = TurnEnd::Completed;
                     break; // Exit the loop
                 }
                 
                 tool_rounds += 1;
 
-                // Loop detection: check if same tools with same arguments are being called repeatedly
-                // Create a signature of tool name + arguments for comparison
-                let current_signature = tool_calls
-                    .iter()
-                    .map(|tc| format!("{}({})", tc.function.name, tc.function.arguments))
-                    .collect::<Vec<_>>()
-                    .join(";");
-                
-                if current_signature == last_tool_signature && !current_signature.is_empty() {
-                    repeated_calls += 1;
-                    if repeated_calls >= 2 {
-                        // Same tool call with same arguments 3 times in a row - infinite loop
-                        let tool_desc = if current_signature.is_empty() {
-                            "unknown tool".to_string()
-                        } else {
-                            current_signature.clone()
-                        };
-                        
-                        let warning_entry = ChatEntry {
-                            entry_type: ChatEntryType::Assistant,
-                            content: format!(
-                                "⚠️ Infinite loop detected: {} called {} times with identical parameters. This suggests the tool is not making progress. Stopping to prevent infinite loops.",
-                                tool_desc,
-                                repeated_calls + 1
-                            ),
-                            timestamp: chrono::Utc::now(),
-                            tool_calls: None,
-                            tool_call: None,
-                            tool_result: None,
-                            is_streaming: None,
-                        };
-                        self.chat_history.push(warning_entry.clone());
-                        new_entries.push(warning_entry);
-                        break;
-                    }
-                } else {
-                    repeated_calls = 0;
-                    last_tool_signature = current_signature;
+                // Same tool calls with the same arguments three rounds in a row: stop
+                if let Some(warning) = loop_guard.check(tool_calls) {
+                    new_entries.push(self.record_warning(warning));
+                    end = TurnEnd::LoopDetected;
+                    break;
                 }
 
                 // Add assistant message with tool calls
                 let assistant_entry = ChatEntry {
                     entry_type: ChatEntryType::Assistant,
-                    content: assistant_message.content.clone().unwrap_or_else(|| "Using tools to help you...".to_string()),
+                    content: self.cache_marked(assistant_message.content.clone().unwrap_or_else(|| "Using tools to help you...".to_string())),
                     timestamp: chrono::Utc::now(),
                     tool_calls: Some(tool_calls.clone()),
                     tool_call: None,
@@ -278,8 +1931,17 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                 self.messages.push(assistant_message.clone());
 
                 // Execute tool calls
-                for tool_call in tool_calls {
-                    let result = self.execute_tool(tool_call).await?;
+                let notes = self.explain_tool_calls(assistant_message.content.as_deref().unwrap_or_default(), tool_calls);
+                new_entries.extend(self.record_explanations(notes));
+                let (to_run, deferred) = self.split_tool_calls(tool_calls);
+                let results = self.execute_tools(to_run).await;
+                let ran = results.len();
+                for (tool_call, result) in to_run.iter().zip(results) {
+                    let result = result.map_err(|source| AgentError::ToolExecution {
+                        tool: tool_call.function.name.clone(),
+                        source,
+                    })?;
+                    failures.record(result.success);
                     let result_content = if result.success {
                         result.output.clone().unwrap_or_else(|| "Success".to_string())
                     } else {
@@ -300,24 +1962,66 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     new_entries.push(tool_result_entry);
 
                     // Add tool result to messages with proper format (needed for AI context)
+                    let model_content = self.tool_result_for_model(tool_call, result_content).await;
                     self.messages.push(GrokMessage {
                         role: "tool".to_string(),
-                        content: Some(result_content),
+                        content: Some(model_content),
                         tool_calls: None,
                         tool_call_id: Some(tool_call.id.clone()),
                     });
                 }
+                for tool_call in &to_run[ran..] {
+                    let entry = self.skip_tool_call(tool_call, CANCELLED_TOOL_CALL_NOTE.to_string());
+                    new_entries.push(entry);
+                }
+                for tool_call in deferred {
+                    let entry = self.defer_tool_call(tool_call);
+                    new_entries.push(entry);
+                }
+                if self.is_cancelled() {
+                    new_entries.push(self.record_cancellation());
+                    end = TurnEnd::Cancelled;
+                    break;
+                }
+
+                if failures.tripped() {
+                    match self.tool_failure_action {
+                        ToolFailureAction::Stop => {
+                            let warning_entry = ChatEntry {
+                                entry_type: ChatEntryType::Assistant,
+                                content: tool_failure_warning(self.max_consecutive_failures),
+                                timestamp: chrono::Utc::now(),
+                                tool_calls: None,
+                                tool_call: None,
+                                tool_result: None,
+                                is_streaming: None,
+                            };
+                            self.chat_history.push(warning_entry.clone());
+                            new_entries.push(warning_entry);
+                            end = TurnEnd::ToolFailures;
+                            break;
+                        }
+                        // Kept in the conversation so later rounds still see why the model changed course
+                        ToolFailureAction::Steer => self.messages.push(GrokMessage {
+                            role: "user".to_string(),
+                            content: Some(tool_failure_nudge(self.max_consecutive_failures)),
+                            tool_calls: None,
+                            tool_call_id: None,
+                        }),
+                    }
+                }
 
                 // Get next response - this might contain more tool calls
+                self.grok_client.set_tool_choice(self.follow_up_tool_choice());
                 current_response = match self.grok_client.chat(
-                    self.messages.clone(),
+                    self.request_messages(),
                     Some(self.get_all_tools().await),
                     None,
                     None,
                 ).await {
                     Ok(response) => response,
                     Err(e) => {
-                        if e.to_string().contains("No API key set") {
+                        if e == AgentError::MissingApiKey {
                             let error_entry = ChatEntry {
                                 entry_type: ChatEntryType::Assistant,
                                 content: "No API key configured. Please set your API key in settings before proceeding with chat functionality.".to_string(),
@@ -329,6 +2033,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                             };
                             self.chat_history.push(error_entry.clone());
                             new_entries.push(error_entry);
+                            end = TurnEnd::Error;
                             break; // Exit the loop
                         } else {
                             return Err(e);
@@ -339,13 +2044,14 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                 // No more tool calls, add final response
                 let final_entry = ChatEntry {
                     entry_type: ChatEntryType::Assistant,
-                    content: assistant_message.content.clone().unwrap_or_else(|| "I understand, but I don't have a specific response.".to_string()),
+                    content: self.cache_marked(assistant_message.content.clone().unwrap_or_else(|| "I understand, but I don't have a specific response.".to_string())),
                     timestamp: chrono::Utc::now(),
                     tool_calls: None,
                     tool_call: None,
                     tool_result: None,
                     is_streaming: None,
                 };
+                self.last_reply = Some(final_entry.content.clone());
                 self.chat_history.push(final_entry.clone());
                 new_entries.push(final_entry);
 
@@ -355,43 +2061,136 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     tool_calls: None,
                     tool_call_id: None,
                 });
+                end = TurnEnd::Completed;
+                if current_response.choices.first().is_some_and(|choice| choice.finish_reason == "length") {
+                    let warning_entry = ChatEntry {
+                        entry_type: ChatEntryType::Assistant,
+                        content: TRUNCATED_WARNING.to_string(),
+                        timestamp: chrono::Utc::now(),
+                        tool_calls: None,
+                        tool_call: None,
+                        tool_result: None,
+                        is_streaming: None,
+                    };
+                    self.chat_history.push(warning_entry.clone());
+                    new_entries.push(warning_entry);
+                }
                 break; // Exit the loop
             }
         }
 
-        if tool_rounds >= self.max_tool_rounds {
-            let warning_entry = ChatEntry {
-                entry_type: ChatEntryType::Assistant,
-                content: "Maximum tool execution rounds reached. Stopping to prevent infinite loops.".to_string(),
-                timestamp: chrono::Utc::now(),
-                tool_calls: None,
-                tool_call: None,
-                tool_result: None,
-                is_streaming: None,
-            };
-            self.chat_history.push(warning_entry.clone());
-            new_entries.push(warning_entry);
+        if end == TurnEnd::MaxToolRounds {
+            new_entries.push(self.record_warning(MAX_ROUNDS_WARNING.to_string()));
         }
+        new_entries.extend(self.finish_turn());
+        self.last_turn_end = end;
 
         Ok(new_entries)
     }
 
+    /// How the last `process_user_message` turn ended
+    pub fn last_turn_end(&self) -> TurnEnd {
+        self.last_turn_end
+    }
+
+    /// The model's final answer in the last `process_user_message` turn, without tool output or warnings
+    pub fn last_reply(&self) -> Option<&str> {
+        self.last_reply.as_deref()
+    }
+
+    /// Run a single tool call directly, without the model, for debugging tool behavior
+    pub async fn replay_tool(&mut self, name: &str, arguments: &str) -> Result<ToolResult, Box<dyn std::error::Error>> {
+        let tool_names = self.tool_names().await;
+        if !tool_names.iter().any(|tool| tool == name) {
+            return Err(format!("Unknown tool: {}. Available: {}", name, tool_names.join(", ")).into());
+        }
+        let parsed: serde_json::Value = serde_json::from_str(arguments)
+            .map_err(|e| format!("Arguments must be a JSON object: {}", e))?;
+        if !parsed.is_object() {
+            return Err("Arguments must be a JSON object".into());
+        }
+
+        let tool_call = GrokToolCall {
+            id: format!("replay_{}", uuid::Uuid::new_v4().simple()),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        };
+        self.execute_tool(&tool_call).await
+    }
+
     async fn execute_tool(&mut self, tool_call: &GrokToolCall) -> Result<ToolResult, Box<dyn std::error::Error>> {
+        if !self.tool_enabled(&tool_call.function.name) {
+            return Ok(ToolResult {
+                success: false,
+                output: None,
+                error: Some(format!("Tool {} is disabled in this project's settings", tool_call.function.name)),
+                data: None,
+            });
+        }
+        let written_path_key = match tool_call.function.name.as_str() {
+            "create_file" | "str_replace_editor" | "insert_lines" | "delete_lines" => Some("path"),
+            "edit_file" => Some("target_file"),
+            _ => None,
+        };
+        let gated = written_path_key.is_some() || matches!(tool_call.function.name.as_str(), "bash" | "undo_edit");
+        let rejected = if gated { self.confirm_tool_call(tool_call).await } else { None };
+        if let Some(rejected) = rejected {
+            return Ok(rejected);
+        }
+        if let Some(key) = written_path_key {
+            let args: HashMap<String, serde_json::Value> = serde_json::from_str(&tool_call.function.arguments)?;
+            let path = args.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            self.turn_changes.record_before(std::path::Path::new(&path));
+        }
+
+        let git_before = (tool_call.function.name == "bash")
+            .then(|| GitSnapshot::take(std::path::Path::new(self.bash.get_current_directory())))
+            .flatten();
+        let limit = self.tool_timeouts.for_tool(&tool_call.function.name);
+        let mut result = run_with_timeout(limit, self.dispatch_tool(tool_call)).await?;
+        if let Some(before) = git_before {
+            self.turn_changes.record_bash(&before);
+        }
+        if let (true, Some(key)) = (result.success, written_path_key) {
+            let args: HashMap<String, serde_json::Value> = serde_json::from_str(&tool_call.function.arguments)?;
+            if let Some(path) = args.get(key).and_then(|v| v.as_str())
+                && let Some(note) = format_written_file(&mut self.bash, &self.format_on_write, path).await
+            {
+                let output = result.output.take().unwrap_or_default();
+                result.output = Some(format!("{}\n{}", output, note));
+            }
+
+            // The edit itself succeeded either way; the diagnostics are for the model's next round
+            if self.check.auto_after_edit {
+                let check = run_check(&self.check, self.bash.get_current_directory()).await;
+                if let Some(report) = check.output.or(check.error) {
+                    let output = result.output.take().unwrap_or_default();
+                    result.output = Some(format!("{}\nCheck after edit: {}", output, report));
+                }
+            }
+            if tool_call.function.name != "edit_file"
+                && let Some(path) = args.get(key).and_then(|v| v.as_str())
+            {
+                self.text_editor.note_rewritten(path).await;
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn dispatch_tool(&mut self, tool_call: &GrokToolCall) -> Result<ToolResult, Box<dyn std::error::Error>> {
         let args: HashMap<String, serde_json::Value> = serde_json::from_str(&tool_call.function.arguments)?;
 
         match tool_call.function.name.as_str() {
-            "view_file" => {
+            name if is_read_only_tool(name) => self.dispatch_read_only_tool(tool_call).await,
+            "create_file" => {
                 let path = args.get("path").and_then(|v| v.as_str()).ok_or("Missing 'path' argument")?;
-                let start_line = args.get("start_line").and_then(|v| v.as_u64()).map(|v| v as usize);
-                let end_line = args.get("end_line").and_then(|v| v.as_u64()).map(|v| v as usize);
-
-                let view_range = if let (Some(start), Some(end)) = (start_line, end_line) {
-                    Some((start, end))
-                } else {
-                    None
-                };
+                let content = args.get("content").and_then(|v| v.as_str()).ok_or("Missing 'content' argument")?;
 
-                match self.text_editor.view(path, view_range).await {
+                match self.text_editor.create(path, content).await {
                     Ok(result) => Ok(result),
                     Err(e) => Ok(ToolResult {
                         success: false,
@@ -401,11 +2200,14 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     }),
                 }
             },
-            "create_file" => {
+            "str_replace_editor" => {
                 let path = args.get("path").and_then(|v| v.as_str()).ok_or("Missing 'path' argument")?;
-                let content = args.get("content").and_then(|v| v.as_str()).ok_or("Missing 'content' argument")?;
+                let old_str = args.get("old_str").and_then(|v| v.as_str()).ok_or("Missing 'old_str' argument")?;
+                let new_str = args.get("new_str").and_then(|v| v.as_str()).ok_or("Missing 'new_str' argument")?;
+                let replace_all = args.get("replace_all").and_then(|v| v.as_bool()).unwrap_or(false);
+                let fuzzy = args.get("fuzzy").and_then(|v| v.as_bool()).unwrap_or(false);
 
-                match self.text_editor.create(path, content).await {
+                match self.text_editor.str_replace(path, old_str, new_str, replace_all, fuzzy).await {
                     Ok(result) => Ok(result),
                     Err(e) => Ok(ToolResult {
                         success: false,
@@ -415,13 +2217,19 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     }),
                 }
             },
-            "str_replace_editor" => {
+            "insert_lines" | "delete_lines" => {
                 let path = args.get("path").and_then(|v| v.as_str()).ok_or("Missing 'path' argument")?;
-                let old_str = args.get("old_str").and_then(|v| v.as_str()).ok_or("Missing 'old_str' argument")?;
-                let new_str = args.get("new_str").and_then(|v| v.as_str()).ok_or("Missing 'new_str' argument")?;
-                let replace_all = args.get("replace_all").and_then(|v| v.as_bool()).unwrap_or(false);
+                let line = |key: &str| -> Result<usize, String> {
+                    args.get(key).and_then(|v| v.as_u64()).map(|n| n as usize).ok_or(format!("Missing '{}' argument", key))
+                };
+                let result = if tool_call.function.name == "insert_lines" {
+                    let content = args.get("content").and_then(|v| v.as_str()).ok_or("Missing 'content' argument")?;
+                    self.text_editor.insert(path, line("line")?, content).await
+                } else {
+                    self.text_editor.delete_range(path, line("start_line")?, line("end_line")?).await
+                };
 
-                match self.text_editor.str_replace(path, old_str, new_str, replace_all).await {
+                match result {
                     Ok(result) => Ok(result),
                     Err(e) => Ok(ToolResult {
                         success: false,
@@ -431,10 +2239,35 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     }),
                 }
             },
+            "check" => Ok(run_check(&self.check, self.bash.get_current_directory()).await),
+            "ask_user" => {
+                let question = args.get("question").and_then(|v| v.as_str()).ok_or("Missing 'question' argument")?;
+                let options: Vec<String> = args
+                    .get("options")
+                    .and_then(|v| v.as_array())
+                    .map(|values| values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+                    .unwrap_or_default();
+                Ok(self.ask_user(question, options).await)
+            },
             "bash" => {
                 let command = args.get("command").and_then(|v| v.as_str()).ok_or("Missing 'command' argument")?;
+                let outcome = match self.tool_output.clone() {
+                    Some(output) => {
+                        let (line_tx, mut line_rx) = tokio::sync::mpsc::channel::<String>(TOOL_OUTPUT_BUFFER);
+                        let call = tool_call.clone();
+                        let forward = async move {
+                            while let Some(line) = line_rx.recv().await {
+                                let _ = output.send((call.clone(), line)).await;
+                            }
+                        };
+                        // The error is made a string before it is held while `forward` finishes
+                        let run = async { self.bash.execute_streaming(command, None, Some(line_tx)).await.map_err(|e| e.to_string()) };
+                        tokio::join!(run, forward).0
+                    }
+                    None => self.bash.execute(command, None).await.map_err(|e| e.to_string()),
+                };
 
-                match self.bash.execute(command, None).await {
+                match outcome {
                     Ok(result) => Ok(result),
                     Err(e) => Ok(ToolResult {
                         success: false,
@@ -457,63 +2290,16 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                         output: None,
                         error: Some(e.to_string()),
                         data: None,
-                    }),
-                }
-            },
-            "update_todo_list" => {
-                let updates_value = args.get("updates").ok_or("Missing 'updates' argument")?;
-
-                // Deserialize the updates array from the JSON value
-                let updates: Vec<crate::tools::TodoUpdate> = serde_json::from_value(updates_value.clone())?;
-
-                match self.todo_tool.update_todo_list(updates).await {
-                    Ok(result) => Ok(result),
-                    Err(e) => Ok(ToolResult {
-                        success: false,
-                        output: None,
-                        error: Some(e.to_string()),
-                        data: None,
-                    }),
-                }
-            },
-            "search" => {
-                let query = args.get("query").and_then(|v| v.as_str()).ok_or("Missing 'query' argument")?;
-                let search_type = args.get("search_type").and_then(|v| v.as_str()).map(|s| s.to_string());
-                let include_pattern = args.get("include_pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
-                let exclude_pattern = args.get("exclude_pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
-                let case_sensitive = args.get("case_sensitive").and_then(|v| v.as_bool());
-                let whole_word = args.get("whole_word").and_then(|v| v.as_bool());
-                let regex = args.get("regex").and_then(|v| v.as_bool());
-                let max_results = args.get("max_results").and_then(|v| v.as_u64()).map(|v| v as u32);
-                let file_types_value = args.get("file_types");
-                let exclude_files_value = args.get("exclude_files");
-                let include_hidden = args.get("include_hidden").and_then(|v| v.as_bool());
-
-                let file_types = if let Some(types_value) = file_types_value {
-                    Some(serde_json::from_value(types_value.clone())?)
-                } else {
-                    None
-                };
-
-                let exclude_files = if let Some(files_value) = exclude_files_value {
-                    Some(serde_json::from_value(files_value.clone())?)
-                } else {
-                    None
-                };
-
-                match self.search.search(
-                    query,
-                    search_type,
-                    include_pattern,
-                    exclude_pattern,
-                    case_sensitive,
-                    whole_word,
-                    regex,
-                    max_results,
-                    file_types,
-                    exclude_files,
-                    include_hidden,
-                ).await {
+                    }),
+                }
+            },
+            "update_todo_list" => {
+                let updates_value = args.get("updates").ok_or("Missing 'updates' argument")?;
+
+                // Deserialize the updates array from the JSON value
+                let updates: Vec<crate::tools::TodoUpdate> = serde_json::from_value(updates_value.clone())?;
+
+                match self.todo_tool.update_todo_list(updates).await {
                     Ok(result) => Ok(result),
                     Err(e) => Ok(ToolResult {
                         success: false,
@@ -583,6 +2369,115 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     })
                 }
             },
+            "undo_edit" => {
+                let all = args.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
+                Ok(self.undo_edits(all).await)
+            },
+            name if name.starts_with(mcp::MCP_TOOL_PREFIX) => Ok(self.call_mcp_tool(name, &tool_call.function.arguments).await),
+            _ => Ok(ToolResult {
+                success: false,
+                output: None,
+                error: Some(format!("Unknown tool: {}", tool_call.function.name)),
+                data: None,
+            }),
+        }
+    }
+
+    /// The tools in [`READ_ONLY_TOOLS`]; they only need `&self`, so several can run at once
+    async fn dispatch_read_only_tool(&self, tool_call: &GrokToolCall) -> Result<ToolResult, Box<dyn std::error::Error>> {
+        let args: HashMap<String, serde_json::Value> = serde_json::from_str(&tool_call.function.arguments)?;
+
+        match tool_call.function.name.as_str() {
+            "view_file" => {
+                let paths: Option<Vec<String>> = args
+                    .get("paths")
+                    .and_then(|v| v.as_array())
+                    .map(|values| values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect());
+                let path = args.get("path").and_then(|v| v.as_str());
+                let include_hidden = args.get("include_hidden").and_then(|v| v.as_bool()).unwrap_or(false);
+                let start_line = args.get("start_line").and_then(|v| v.as_u64()).map(|v| v as usize);
+                let end_line = args.get("end_line").and_then(|v| v.as_u64()).map(|v| v as usize);
+
+                let view_range = if let (Some(start), Some(end)) = (start_line, end_line) {
+                    Some((start, end))
+                } else {
+                    None
+                };
+
+                let viewed = match (paths, path) {
+                    // `path` and `paths` together are all viewed
+                    (Some(mut paths), path) if !paths.is_empty() => {
+                        paths.extend(path.map(str::to_string));
+                        self.text_editor.view_many(&paths, view_range, include_hidden).await
+                    }
+                    (_, Some(path)) => self.text_editor.view(path, view_range, include_hidden).await,
+                    _ => return Err("Missing 'path' argument".into()),
+                };
+                match viewed {
+                    Ok(result) => Ok(result),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        output: None,
+                        error: Some(e.to_string()),
+                        data: None,
+                    }),
+                }
+            },
+            "git_changes" => {
+                let path = args.get("path").and_then(|v| v.as_str()).filter(|p| !p.is_empty());
+                let include_patch = args.get("include_patch").and_then(|v| v.as_bool()).unwrap_or(false);
+                let max_tokens = args.get("max_tokens").and_then(|v| v.as_u64()).map_or(GIT_CHANGES_DEFAULT_TOKENS, |n| n as usize);
+                Ok(git_changes(self.bash.get_current_directory(), path, include_patch, max_tokens))
+            },
+            "search" => {
+                let query = args.get("query").and_then(|v| v.as_str()).ok_or("Missing 'query' argument")?;
+                let search_type = args.get("search_type").and_then(|v| v.as_str()).map(|s| s.to_string());
+                let include_pattern = args.get("include_pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
+                let exclude_pattern = args.get("exclude_pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
+                let case_sensitive = args.get("case_sensitive").and_then(|v| v.as_bool());
+                let whole_word = args.get("whole_word").and_then(|v| v.as_bool());
+                let regex = args.get("regex").and_then(|v| v.as_bool());
+                let max_results = args.get("max_results").and_then(|v| v.as_u64()).map(|v| v as u32);
+                let file_types_value = args.get("file_types");
+                let exclude_files_value = args.get("exclude_files");
+                let include_hidden = args.get("include_hidden").and_then(|v| v.as_bool());
+                let no_ignore = args.get("no_ignore").and_then(|v| v.as_bool());
+
+                let file_types = if let Some(types_value) = file_types_value {
+                    Some(serde_json::from_value(types_value.clone())?)
+                } else {
+                    None
+                };
+
+                let exclude_files = if let Some(files_value) = exclude_files_value {
+                    Some(serde_json::from_value(files_value.clone())?)
+                } else {
+                    None
+                };
+
+                match self.search.search(
+                    query,
+                    search_type,
+                    include_pattern,
+                    exclude_pattern,
+                    case_sensitive,
+                    whole_word,
+                    regex,
+                    max_results,
+                    file_types,
+                    exclude_files,
+                    include_hidden,
+                    no_ignore,
+                ).await {
+                    Ok(result) => Ok(result),
+                    Err(e) => Ok(ToolResult {
+                        success: false,
+                        output: None,
+                        error: Some(e.to_string()),
+                        data: None,
+                    }),
+                }
+            },
             _ => Ok(ToolResult {
                 success: false,
                 output: None,
@@ -593,20 +2488,29 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
     }
 
     async fn get_all_tools(&self) -> Vec<GrokTool> {
-        vec![
+        let tools = vec![
             // view_file tool
             GrokTool {
                 tool_type: "function".to_string(),
                 function: crate::types::GrokToolFunction {
                     name: "view_file".to_string(),
-                    description: "View contents of a file or list directory contents".to_string(),
+                    description: "View contents of a file or list directory contents. A glob path or a paths array views several files at once, each under an === path (lines a-b) === header, within a byte budget".to_string(),
                     parameters: crate::types::GrokToolParameters {
                         param_type: "object".to_string(),
                         properties: {
                             let mut props = std::collections::HashMap::new();
                             props.insert("path".to_string(), serde_json::json!({
                                 "type": "string",
-                                "description": "Path to the file or directory to view"
+                                "description": "Path to the file or directory to view, or a glob such as src/**/*.rs"
+                            }));
+                            props.insert("paths".to_string(), serde_json::json!({
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Several files or globs to view in one call (optional)"
+                            }));
+                            props.insert("include_hidden".to_string(), serde_json::json!({
+                                "type": "boolean",
+                                "description": "Include hidden files and .git in globs and directory listings (default: false)"
                             }));
                             props.insert("start_line".to_string(), serde_json::json!({
                                 "type": "integer",
@@ -618,7 +2522,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                             }));
                             props
                         },
-                        required: vec!["path".to_string()],
+                        required: vec![],
                     },
                 },
             },
@@ -672,12 +2576,72 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                                 "type": "boolean",
                                 "description": "Whether to replace all occurrences (default: false)"
                             }));
+                            props.insert("fuzzy".to_string(), serde_json::json!({
+                                "type": "boolean",
+                                "description": "If old_str is not found exactly, match it again ignoring indentation, trailing whitespace and small typos; applied only when that matches one place (default: false)"
+                            }));
                             props
                         },
                         required: vec!["path".to_string(), "old_str".to_string(), "new_str".to_string()],
                     },
                 },
             },
+            // insert_lines tool
+            GrokTool {
+                tool_type: "function".to_string(),
+                function: crate::types::GrokToolFunction {
+                    name: "insert_lines".to_string(),
+                    description: "Insert text after a given line of an existing file. Returns the inserted lines with the lines around them.".to_string(),
+                    parameters: crate::types::GrokToolParameters {
+                        param_type: "object".to_string(),
+                        properties: {
+                            let mut props = std::collections::HashMap::new();
+                            props.insert("path".to_string(), serde_json::json!({
+                                "type": "string",
+                                "description": "Path to the file to edit"
+                            }));
+                            props.insert("line".to_string(), serde_json::json!({
+                                "type": "integer",
+                                "description": "Line number to insert after, counted from 1; 0 inserts at the top and the file's line count appends"
+                            }));
+                            props.insert("content".to_string(), serde_json::json!({
+                                "type": "string",
+                                "description": "Lines to insert"
+                            }));
+                            props
+                        },
+                        required: vec!["path".to_string(), "line".to_string(), "content".to_string()],
+                    },
+                },
+            },
+            // delete_lines tool
+            GrokTool {
+                tool_type: "function".to_string(),
+                function: crate::types::GrokToolFunction {
+                    name: "delete_lines".to_string(),
+                    description: "Delete a range of lines from an existing file. Returns the lines around the deletion.".to_string(),
+                    parameters: crate::types::GrokToolParameters {
+                        param_type: "object".to_string(),
+                        properties: {
+                            let mut props = std::collections::HashMap::new();
+                            props.insert("path".to_string(), serde_json::json!({
+                                "type": "string",
+                                "description": "Path to the file to edit"
+                            }));
+                            props.insert("start_line".to_string(), serde_json::json!({
+                                "type": "integer",
+                                "description": "First line to delete, counted from 1"
+                            }));
+                            props.insert("end_line".to_string(), serde_json::json!({
+                                "type": "integer",
+                                "description": "Last line to delete, included"
+                            }));
+                            props
+                        },
+                        required: vec!["path".to_string(), "start_line".to_string(), "end_line".to_string()],
+                    },
+                },
+            },
             // bash tool
             GrokTool {
                 tool_type: "function".to_string(),
@@ -698,6 +2662,70 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     },
                 },
             },
+            // check tool
+            GrokTool {
+                tool_type: "function".to_string(),
+                function: crate::types::GrokToolFunction {
+                    name: "check".to_string(),
+                    description: "Build-check the project (cargo check, tsc or go vet, or the project's configured command) and return errors and warnings with file, line and message. Run it after edits and fix the errors it reports".to_string(),
+                    parameters: crate::types::GrokToolParameters {
+                        param_type: "object".to_string(),
+                        properties: std::collections::HashMap::new(),
+                        required: vec![],
+                    },
+                },
+            },
+            GrokTool {
+                tool_type: "function".to_string(),
+                function: crate::types::GrokToolFunction {
+                    name: "git_changes".to_string(),
+                    description: "Summarize how the working tree differs from git HEAD: each changed or untracked file with its status, added and removed line counts and hunk ranges, and optionally the patch. Use it to review your own edits or write a changelog instead of running git diff through bash".to_string(),
+                    parameters: crate::types::GrokToolParameters {
+                        param_type: "object".to_string(),
+                        properties: {
+                            let mut props = std::collections::HashMap::new();
+                            props.insert("path".to_string(), serde_json::json!({
+                                "type": "string",
+                                "description": "Only report changes under this file or directory"
+                            }));
+                            props.insert("include_patch".to_string(), serde_json::json!({
+                                "type": "boolean",
+                                "description": "Also return the diff text of each file (default: false)"
+                            }));
+                            props.insert("max_tokens".to_string(), serde_json::json!({
+                                "type": "integer",
+                                "description": format!("Approximate size limit of the result (default: {})", GIT_CHANGES_DEFAULT_TOKENS)
+                            }));
+                            props
+                        },
+                        required: vec![],
+                    },
+                },
+            },
+            GrokTool {
+                tool_type: "function".to_string(),
+                function: crate::types::GrokToolFunction {
+                    name: "ask_user".to_string(),
+                    description: "Pause and ask the user a question when a decision needs a human (a preference, a trade-off, missing information). Returns their answer. Use sparingly and only for decisions you cannot make yourself".to_string(),
+                    parameters: crate::types::GrokToolParameters {
+                        param_type: "object".to_string(),
+                        properties: {
+                            let mut props = std::collections::HashMap::new();
+                            props.insert("question".to_string(), serde_json::json!({
+                                "type": "string",
+                                "description": "The question to ask"
+                            }));
+                            props.insert("options".to_string(), serde_json::json!({
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Suggested answers to choose from; the user may also answer in their own words"
+                            }));
+                            props
+                        },
+                        required: vec!["question".to_string()],
+                    },
+                },
+            },
             // create_todo_list tool
             GrokTool {
                 tool_type: "function".to_string(),
@@ -774,8 +2802,8 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                             }));
                             props.insert("search_type".to_string(), serde_json::json!({
                                 "type": "string",
-                                "enum": ["text", "files", "both"],
-                                "description": "Type of search to perform: 'text' for content search, 'files' for file name search, 'both' for both",
+                                "enum": ["text", "files", "both", "symbols"],
+                                "description": "Type of search to perform: 'text' for content search, 'files' for file name search, 'both' for both, 'symbols' for function, struct, enum, trait, class, interface and type definitions (Rust, Python, TypeScript/JavaScript, Go) whose name contains the query, exact names first",
                                 "default": "both"
                             }));
                             props.insert("include_pattern".to_string(), serde_json::json!({
@@ -803,7 +2831,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                             }));
                             props.insert("max_results".to_string(), serde_json::json!({
                                 "type": "integer",
-                                "description": "Maximum number of results to return",
+                                "description": "Maximum number of results to return: matching lines for a text search, files for a file search",
                                 "minimum": 1,
                                 "maximum": 100,
                                 "default": 50
@@ -813,7 +2841,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                                 "items": {
                                     "type": "string"
                                 },
-                                "description": "File types to include in search (e.g., ['js', 'ts', 'py'])"
+                                "description": "File types to include in search, as ripgrep type names or extensions (e.g., ['js', 'ts', 'py', 'rust'])"
                             }));
                             props.insert("exclude_files".to_string(), serde_json::json!({
                                 "type": "array",
@@ -827,6 +2855,11 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                                 "description": "Whether to include hidden files in search",
                                 "default": false
                             }));
+                            props.insert("no_ignore".to_string(), serde_json::json!({
+                                "type": "boolean",
+                                "description": "Also search files that .gitignore, .ignore and global git excludes skip",
+                                "default": false
+                            }));
                             props
                         },
                         required: vec!["query".to_string()],
@@ -884,6 +2917,26 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     },
                 },
             },
+            // undo_edit tool
+            GrokTool {
+                tool_type: "function".to_string(),
+                function: crate::types::GrokToolFunction {
+                    name: "undo_edit".to_string(),
+                    description: "Undo the last create_file, str_replace_editor, insert_lines or delete_lines change made in this session, or all of them newest first. Refuses when the file changed since it was written.".to_string(),
+                    parameters: crate::types::GrokToolParameters {
+                        param_type: "object".to_string(),
+                        properties: {
+                            let mut props = std::collections::HashMap::new();
+                            props.insert("all".to_string(), serde_json::json!({
+                                "type": "boolean",
+                                "description": "Undo every edit of the session instead of only the last one (default false)"
+                            }));
+                            props
+                        },
+                        required: vec![],
+                    },
+                },
+            },
             // edit_file tool (Morph Fast Apply)
             GrokTool {
                 tool_type: "function".to_string(),
@@ -912,53 +2965,361 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                     },
                 },
             },
-        ]
+        ];
+        let mcp_tools = self.mcp_tools().await;
+        tools.into_iter().chain(mcp_tools).filter(|tool| self.tool_enabled(&tool.function.name)).collect()
+    }
+
+    /// Stream the reply to `message`. Tool calls the model makes are run as they finish
+    /// streaming, their results come through as `ToolResult` chunks and the next response is
+    /// streamed after them, until the model answers without tools or `max_tool_rounds` is hit.
+    pub async fn process_user_message_stream(
+        &mut self,
+        message: &str,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamingChunk, AgentError>> + Send + '_>>, AgentError> {
+        use futures::stream::StreamExt;
+
+        self.bash.confirm_pending(message);
+        self.turn_changes.clear();
+        self.start_stream_turn();
+
+        // Add user message to conversation, with the files it mentions in front of it
+        let expanded = mentions::expand_mentions(&self.text_editor, message).await;
+        self.push_user_message(&expanded.content);
+
+        let user_entry = ChatEntry {
+            entry_type: ChatEntryType::User,
+            content: message.to_string(),
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: None,
+            tool_result: None,
+            is_streaming: Some(true),
+        };
+        self.chat_history.push(user_entry);
+        let warnings = self.record_mention_warnings(expanded.warnings);
+
+        let first = self.stream_response(self.tool_choice.clone()).await?;
+        let notes = futures::stream::iter(warnings.into_iter().map(|entry| Ok(note_chunk(entry.content))));
+        Ok(self.stream_tool_rounds(Box::pin(notes.chain(first))))
+    }
+
+    /// Send the last streamed request again, e.g. after it failed because the network was down.
+    /// Tool results it followed are already in the conversation and are not run again, and the
+    /// tool rounds of the turn carry on from where they were.
+    pub async fn retry_stream(
+        &mut self,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamingChunk, AgentError>> + Send + '_>>, AgentError> {
+        let first = self.stream_response(self.grok_client.tool_choice.clone()).await?;
+        Ok(self.stream_tool_rounds(first))
+    }
+
+    /// Record a streamed reply that stopped at the output length limit and stream the rest of it
+    pub async fn continue_truncated_stream(
+        &mut self,
+        partial: &str,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamingChunk, AgentError>> + Send + '_>>, AgentError> {
+        self.start_stream_turn();
+        for (role, content) in [("assistant", partial), ("user", CONTINUE_PROMPT)] {
+            self.messages.push(GrokMessage {
+                role: role.to_string(),
+                content: Some(content.to_string()),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+        }
+        let first = self.stream_response(self.follow_up_tool_choice()).await?;
+        Ok(self.stream_tool_rounds(first))
+    }
+
+    fn start_stream_turn(&mut self) {
+        self.stream_tool_rounds = 0;
+        self.stream_loop_guard = ToolLoopGuard::default();
+    }
+
+    /// Pass on `first`, and while a response ends in tool calls, run them and stream the next
+    /// response. Only the `Done` chunk of the last response is passed on. A turn stopped by the
+    /// loop guard or `max_tool_rounds` ends with a `Note` chunk saying why.
+    fn stream_tool_rounds(
+        &mut self,
+        first: Pin<Box<dyn Stream<Item = Result<StreamingChunk, AgentError>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = Result<StreamingChunk, AgentError>> + Send + '_>> {
+        use futures::stream::StreamExt;
+
+        Box::pin(async_stream::stream! {
+            let mut response = first;
+            loop {
+                let mut finished_with_calls = None;
+                while let Some(chunk) = response.next().await {
+                    match chunk {
+                        Ok(chunk) if matches!(chunk.chunk_type, StreamingChunkType::Done)
+                            && chunk.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()) =>
+                        {
+                            finished_with_calls = Some(chunk);
+                        }
+                        chunk => yield chunk,
+                    }
+                }
+                let Some(done) = finished_with_calls else { break };
+                let content = done.content.clone().unwrap_or_default();
+                let calls = done.tool_calls.clone().unwrap_or_default();
+                let stop = if let Some(warning) = self.stream_loop_guard.check(&calls) {
+                    Some(warning)
+                } else if self.stream_tool_rounds >= self.max_tool_rounds {
+                    Some(MAX_ROUNDS_WARNING.to_string())
+                } else {
+                    None
+                };
+                if let Some(warning) = stop {
+                    self.record_warning(warning.clone());
+                    yield Ok(note_chunk(warning));
+                    yield Ok(StreamingChunk { tool_calls: None, ..done });
+                    break;
+                }
+
+                self.stream_tool_rounds += 1;
+                for note in self.explain_tool_calls(&content, &calls) {
+                    yield Ok(note_chunk(note));
+                }
+                // Lines the calls print are passed on while they run
+                let (output_tx, mut output_rx) = tokio::sync::mpsc::channel(TOOL_OUTPUT_BUFFER);
+                self.tool_output = Some(output_tx);
+                let chunks = {
+                    let run = self.run_streamed_tool_calls(&content, &calls);
+                    tokio::pin!(run);
+                    loop {
+                        let (call, line) = tokio::select! {
+                            chunks = &mut run => break chunks,
+                            Some(output) = output_rx.recv() => output,
+                        };
+                        yield Ok(tool_output_chunk(call, line));
+                    }
+                };
+                // Lines printed just before the calls finished are still queued
+                self.tool_output = None;
+                while let Ok((call, line)) = output_rx.try_recv() {
+                    yield Ok(tool_output_chunk(call, line));
+                }
+                for chunk in chunks {
+                    yield Ok(chunk);
+                }
+                response = match self.stream_response(self.follow_up_tool_choice()).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        yield Err(e);
+                        break;
+                    }
+                };
+            }
+        })
     }
 
-    pub async fn process_user_message_stream(
-        &mut self,
-        message: &str,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamingChunk, Box<dyn std::error::Error + Send>>> + Send>>, Box<dyn std::error::Error + Send>> {
-        // Add user message to conversation
+    /// Record a streamed assistant turn that ended in tool calls, run the calls, and return one
+    /// `ToolResult` chunk per call, in call order, for the UI to show before the next stream
+    pub async fn run_streamed_tool_calls(&mut self, content: &str, tool_calls: &[GrokToolCall]) -> Vec<StreamingChunk> {
         self.messages.push(GrokMessage {
-            role: "user".to_string(),
-            content: Some(message.to_string()),
-            tool_calls: None,
+            role: "assistant".to_string(),
+            content: (!content.is_empty()).then(|| content.to_string()),
+            tool_calls: Some(tool_calls.to_vec()),
             tool_call_id: None,
         });
 
-        let user_entry = ChatEntry {
-            entry_type: ChatEntryType::User,
-            content: message.to_string(),
+        // The caller shows these before the calls run; here they only go into the transcript
+        let notes = self.explain_tool_calls(content, tool_calls);
+        self.record_explanations(notes);
+
+        let mut chunks = Vec::new();
+        let (to_run, deferred) = self.split_tool_calls(tool_calls);
+        let results = self.execute_tools(to_run).await;
+        let ran = results.len();
+        for (tool_call, result) in to_run.iter().zip(results) {
+            let result = result.unwrap_or_else(|e| ToolResult {
+                success: false,
+                output: None,
+                error: Some(e),
+                data: None,
+            });
+            let result_content = if result.success {
+                result.output.clone().unwrap_or_else(|| "Success".to_string())
+            } else {
+                result.error.clone().unwrap_or_else(|| "Error occurred".to_string())
+            };
+
+            self.chat_history.push(ChatEntry {
+                entry_type: ChatEntryType::ToolResult,
+                content: result_content.clone(),
+                timestamp: chrono::Utc::now(),
+                tool_calls: None,
+                tool_call: Some(tool_call.clone()),
+                tool_result: Some(result.clone()),
+                is_streaming: None,
+            });
+            let model_content = self.tool_result_for_model(tool_call, result_content).await;
+            self.messages.push(GrokMessage {
+                role: "tool".to_string(),
+                content: Some(model_content),
+                tool_calls: None,
+                tool_call_id: Some(tool_call.id.clone()),
+            });
+            chunks.push(StreamingChunk {
+                chunk_type: StreamingChunkType::ToolResult,
+                content: None,
+                tool_calls: None,
+                tool_call: Some(tool_call.clone()),
+                tool_result: Some(result),
+                token_count: None,
+                usage: None,
+            });
+        }
+        let skipped: Vec<ChatEntry> = to_run[ran..]
+            .iter()
+            .map(|tool_call| self.skip_tool_call(tool_call, CANCELLED_TOOL_CALL_NOTE.to_string()))
+            .collect();
+        let deferred: Vec<ChatEntry> = deferred.iter().map(|tool_call| self.defer_tool_call(tool_call)).collect();
+        for entry in skipped.into_iter().chain(deferred) {
+            chunks.push(StreamingChunk {
+                chunk_type: StreamingChunkType::ToolResult,
+                content: None,
+                tool_calls: None,
+                tool_call: entry.tool_call,
+                tool_result: entry.tool_result,
+                token_count: None,
+                usage: None,
+            });
+        }
+        chunks
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.grok_client.set_retry_policy(policy);
+    }
+
+    /// How many times the last request was sent again before the provider answered it
+    pub fn last_retries(&self) -> u32 {
+        self.grok_client.last_retries()
+    }
+
+    /// Requests, tokens and estimated cost of this session so far, shared with every clone
+    pub fn get_usage(&self) -> UsageSummary {
+        self.grok_client.usage().summary()
+    }
+
+    /// The todo list the model keeps with `create_todo_list` and `update_todo_list`, shared with
+    /// every clone
+    pub fn current_todos(&self) -> Vec<crate::tools::TodoItem> {
+        self.todo_tool.get_current()
+    }
+
+    /// Prices from `model_prices` in user settings, tried before the built-in ones
+    pub fn set_model_prices(&mut self, prices: std::collections::BTreeMap<String, ModelPrice>) {
+        self.grok_client.usage().set_prices(prices);
+    }
+
+    pub fn max_tool_rounds(&self) -> u32 {
+        self.max_tool_rounds
+    }
+
+    /// Where the tool-round budget came from, shown by `/status`
+    pub fn tool_rounds_source(&self) -> &str {
+        &self.tool_rounds_source
+    }
+
+    /// Use the user's per-model tool-round table, unless the budget was set explicitly
+    pub fn set_tool_rounds_config(&mut self, config: &ToolRoundsConfig) {
+        self.tool_rounds_config = config.clone();
+        if !self.tool_rounds_explicit {
+            (self.max_tool_rounds, self.tool_rounds_source) = config.resolve(self.grok_client.get_current_model());
+        }
+    }
+
+    /// Models the provider offers, from its `/models` endpoint
+    pub async fn list_models(&self) -> Result<Vec<String>, AgentError> {
+        self.grok_client.list_models().await
+    }
+
+    /// Send the following requests to `model`. The tool-round budget follows the new model
+    /// unless it was set explicitly. Returns the note recorded in the chat history.
+    pub fn set_model(&mut self, model: &str) -> ChatEntry {
+        let previous = self.grok_client.get_current_model().to_string();
+        self.grok_client.set_model(model);
+        if !self.tool_rounds_explicit {
+            (self.max_tool_rounds, self.tool_rounds_source) = self.tool_rounds_config.resolve(model);
+        }
+        let entry = ChatEntry {
+            entry_type: ChatEntryType::System,
+            content: format!("Model switched from {} to {}", previous, model),
             timestamp: chrono::Utc::now(),
             tool_calls: None,
             tool_call: None,
             tool_result: None,
-            is_streaming: Some(true),
+            is_streaming: None,
         };
-        self.chat_history.push(user_entry);
+        self.chat_history.push(entry.clone());
+        entry
+    }
+
+    async fn stream_response(
+        &mut self,
+        tool_choice: ToolChoice,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamingChunk, AgentError>> + Send>>, AgentError> {
+        // A cancelled run sends no further request; the caller gets the end of the stream
+        if self.is_cancelled() {
+            return Ok(Box::pin(futures::stream::once(async { Ok(cancelled_done_chunk(String::new())) })));
+        }
 
         // Get all available tools
         let tools = self.get_all_tools().await;
 
         // Get streaming response from the client
-        let stream = self.grok_client.chat_stream(
-            self.messages.clone(),
+        self.grok_client.set_tool_choice(tool_choice);
+        let request = self.grok_client.chat_stream(
+            self.request_messages(),
             Some(tools),
             None,
             None,
-        ).await?;
+        );
+        // The client may be waiting to retry; a stop does not wait for it
+        let stream = tokio::select! {
+            stream = request => stream?,
+            _ = self.cancellation.cancelled() => {
+                return Ok(Box::pin(futures::stream::once(async { Ok(cancelled_done_chunk(String::new())) })));
+            }
+        };
+        let from_cache = self.grok_client.last_from_cache();
+        let cancellation = self.cancellation.clone();
 
         use async_stream::stream;
         use futures::stream::StreamExt;
 
         let stream = Box::pin(stream! {
+            if from_cache {
+                yield Ok(StreamingChunk {
+                    chunk_type: StreamingChunkType::CacheHit,
+                    content: None,
+                    tool_calls: None,
+                    tool_call: None,
+                    tool_result: None,
+                    token_count: None,
+                    usage: None,
+                });
+            }
             let mut stream_pinned = std::pin::pin!(stream);
             let mut accumulated_content = String::new();
             let mut accumulated_tool_calls: Vec<GrokToolCall> = Vec::new();
             let mut current_tool_call_index: Option<usize> = None;
-            
-            while let Some(result) = stream_pinned.next().await {
+
+            loop {
+                let next = tokio::select! {
+                    next = stream_pinned.next() => next,
+                    _ = cancellation.cancelled() => None,
+                };
+                let Some(result) = next else {
+                    // Stopped by the user: end with what arrived so far, without the tool calls
+                    if cancellation.is_cancelled() {
+                        yield Ok(cancelled_done_chunk(accumulated_content.clone()));
+                    }
+                    break;
+                };
                 match result {
                     Ok(json) => {
                         // Parse the streaming response
@@ -978,6 +3339,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                                                 tool_call: None,
                                                 tool_result: None,
                                                 token_count: None,
+                                                usage: None,
                                             });
                                         }
                                     }
@@ -1022,6 +3384,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                                                     tool_call: None,
                                                     tool_result: None,
                                                     token_count: None,
+                                                    usage: None,
                                                 });
                                             }
                                         }
@@ -1030,6 +3393,17 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
 
                                 // Check for finish_reason
                                 if let Some(finish_reason) = choice.get("finish_reason").and_then(|fr| fr.as_str()) {
+                                    if finish_reason == "length" && accumulated_tool_calls.is_empty() {
+                                        yield Ok(StreamingChunk {
+                                            chunk_type: StreamingChunkType::Truncated,
+                                            content: Some(accumulated_content.clone()),
+                                            tool_calls: None,
+                                            tool_call: None,
+                                            tool_result: None,
+                                            token_count: None,
+                                            usage: None,
+                                        });
+                                    }
                                     if finish_reason == "stop" || finish_reason == "tool_calls" {
                                         // Emit done chunk
                                         yield Ok(StreamingChunk {
@@ -1039,6 +3413,7 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                                             tool_call: None,
                                             tool_result: None,
                                             token_count: None,
+                                            usage: None,
                                         });
                                     }
                                 }
@@ -1046,17 +3421,17 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
                         }
 
                         // Check for usage (token count)
-                        if let Some(usage) = json.get("usage") {
-                            if let Some(total_tokens) = usage.get("total_tokens").and_then(|t| t.as_u64()) {
-                                yield Ok(StreamingChunk {
-                                    chunk_type: StreamingChunkType::TokenCount,
-                                    content: None,
-                                    tool_calls: None,
-                                    tool_call: None,
-                                    tool_result: None,
-                                    token_count: Some(total_tokens as u32),
-                                });
-                            }
+                        if let Some(usage) = json.get("usage").and_then(|usage| serde_json::from_value::<TokenUsage>(usage.clone()).ok()) {
+                            let total_tokens = usage.total_tokens.max(usage.prompt_tokens + usage.completion_tokens);
+                            yield Ok(StreamingChunk {
+                                chunk_type: StreamingChunkType::TokenCount,
+                                content: None,
+                                tool_calls: None,
+                                tool_call: None,
+                                tool_result: None,
+                                token_count: Some(total_tokens as u32),
+                                usage: Some(usage),
+                            });
                         }
                     }
                     Err(e) => {
@@ -1073,4 +3448,862 @@ Current working directory: ".to_string() + &std::env::current_dir()?.to_string_l
     pub fn get_chat_history(&self) -> &Vec<ChatEntry> {
         &self.chat_history
     }
-}
\ No newline at end of file
+
+    pub fn get_current_model(&self) -> &str {
+        self.grok_client.get_current_model()
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// This session as it would be saved, with `entries` as the transcript (the UI keeps its own)
+    pub fn session_file(&self, entries: &[ChatEntry]) -> SessionFile {
+        SessionFile::new(&self.session_id, self.get_current_model(), entries.to_vec(), self.messages.clone())
+    }
+
+    /// Write `entries` as a Markdown or HTML transcript headed by the model, the time and this
+    /// session's usage, to `path` or to `grok-transcript-<session>.<ext>`. Returns where it went.
+    pub async fn export_transcript(
+        &self,
+        entries: &[ChatEntry],
+        format: TranscriptFormat,
+        path: Option<&std::path::Path>,
+    ) -> Result<std::path::PathBuf, String> {
+        let path = path.map(std::path::Path::to_path_buf).unwrap_or_else(|| format.default_path(&self.session_id));
+        let header = TranscriptHeader {
+            model: self.get_current_model().to_string(),
+            exported_at: chrono::Local::now(),
+            usage: self.get_usage().describe(),
+        };
+        tokio::fs::write(&path, crate::utils::transcript::render(format, &header, entries))
+            .await
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    /// Continue a saved session. Its conversation is restored after this agent's system
+    /// prompt, so the prompt describes the current working directory rather than the one the
+    /// session was saved in. Sessions saved without a conversation get their user/assistant
+    /// turns replayed from the transcript; tool traffic is not replayed.
+    pub fn restore_session(&mut self, session: SessionFile) {
+        self.session_id = session.id;
+        self.messages.truncate(1);
+        if !session.messages.is_empty() {
+            let saved = session.messages.into_iter().skip_while(|m| m.role == "system");
+            self.messages.extend(saved);
+            self.chat_history = session.entries;
+            return;
+        }
+        let entries = session.entries;
+        for entry in &entries {
+            let role = match entry.entry_type {
+                ChatEntryType::User => "user",
+                ChatEntryType::Assistant if !entry.content.trim().is_empty() => "assistant",
+                _ => continue,
+            };
+            self.messages.push(GrokMessage {
+                role: role.to_string(),
+                content: Some(entry.content.clone()),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+        }
+        self.chat_history = entries;
+    }
+
+    /// The conversation sent to the model, system prompt included
+    pub fn conversation(&self) -> Vec<GrokMessage> {
+        self.messages.clone()
+    }
+
+    /// Replace the conversation with one a clone of this agent built, e.g. a streaming task
+    pub fn restore_conversation(&mut self, messages: Vec<GrokMessage>) {
+        self.messages = messages;
+    }
+
+    /// Remove the last user message and everything after it (the assistant reply, its tool
+    /// calls and their results) and return that user message so it can be sent again.
+    /// Failure nudges the agent added itself are not user turns.
+    pub fn pop_last_turn(&mut self) -> Option<String> {
+        let nudge = tool_failure_nudge(self.max_consecutive_failures);
+        let end = self
+            .messages
+            .iter()
+            .rposition(|m| m.role == "user" && m.content.as_deref() != Some(nudge.as_str()))?;
+        let mut start = end;
+        while start > 0 && self.messages[start - 1].role == "user" {
+            start -= 1;
+        }
+        let message = join_message_parts(&self.messages[start..=end]);
+        self.messages.truncate(start);
+        if let Some(idx) = self.chat_history.iter().rposition(|e| matches!(e.entry_type, ChatEntryType::User)) {
+            self.chat_history.truncate(idx);
+        }
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assistant(content: Option<&str>, tool_calls: Option<Vec<GrokToolCall>>) -> GrokMessage {
+        GrokMessage {
+            role: "assistant".to_string(),
+            content: content.map(|c| c.to_string()),
+            tool_calls,
+            tool_call_id: None,
+        }
+    }
+
+    #[test]
+    fn test_is_empty_response() {
+        assert!(is_empty_response(&assistant(None, None)));
+        assert!(is_empty_response(&assistant(Some("  \n"), Some(vec![]))));
+        assert!(!is_empty_response(&assistant(Some("done"), None)));
+
+        let call = GrokToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction {
+                name: "bash".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        assert!(!is_empty_response(&assistant(None, Some(vec![call]))));
+    }
+
+    #[test]
+    fn test_warning_messages_are_clean_and_measure_correctly() {
+        for message in [MAX_ROUNDS_WARNING.to_string(), infinite_loop_warning("bash", 3)] {
+            // Latin-1 and C1 characters are what mis-decoded UTF-8 emoji turn into
+            assert!(
+                !message.chars().any(|c| ('\u{80}'..='\u{ff}').contains(&c) || c == '\u{fffd}' || c == '\u{fe0f}'),
+                "{}",
+                message
+            );
+            // Every non-ASCII marker must be measured as the two cells a terminal draws it in
+            for c in message.chars().filter(|c| !c.is_ascii()) {
+                assert_eq!(ratatui::text::Span::raw(c.to_string()).width(), 2, "{:?}", c);
+            }
+        }
+        assert!(infinite_loop_warning("bash", 3).contains("bash called 3 times"));
+    }
+
+    #[tokio::test]
+    async fn test_replay_tool_validates_and_runs() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        assert!(agent.replay_tool("no_such_tool", "{}").await.is_err());
+        assert!(agent.replay_tool("view_file", "[1]").await.is_err());
+
+        let file = std::env::temp_dir().join(format!("grok-replay-{}.txt", std::process::id()));
+        std::fs::write(&file, "hello replay\n").unwrap();
+        let args = serde_json::json!({ "path": file.to_string_lossy() }).to_string();
+        let result = agent.replay_tool("view_file", &args).await.unwrap();
+        assert!(result.success);
+        assert!(result.output.unwrap_or_default().contains("hello replay"));
+        std::fs::remove_file(&file).ok();
+    }
+
+    #[tokio::test]
+    async fn test_file_edits_wait_for_approval_unless_auto_edit_is_on() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        agent.set_edit_confirmer(tx);
+        let pending = agent.pending_confirmations();
+        let decisions = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            for decision in [EditDecision::Reject, EditDecision::ApproveAll] {
+                let request = rx.recv().await.unwrap();
+                assert!(pending.resolve(&request.id, decision));
+                seen.push(request.description);
+            }
+            seen
+        });
+
+        let file = std::env::temp_dir().join(format!("grok-auto-edit-{}.txt", std::process::id()));
+        let args = serde_json::json!({ "path": file.to_string_lossy(), "content": "hi\n" }).to_string();
+        let rejected = agent.replay_tool("create_file", &args).await.unwrap();
+        assert!(!rejected.success);
+        assert!(!file.exists());
+
+        assert!(agent.replay_tool("create_file", &args).await.unwrap().success);
+        assert!(agent.auto_edit());
+        // A clone made for streaming sees the same session flag
+        let clone = agent.clone();
+        agent.set_auto_edit(false);
+        assert!(!clone.auto_edit());
+
+        let seen = decisions.await.unwrap();
+        assert_eq!(seen.len(), 2);
+        assert!(seen[0].starts_with("create_file("));
+        std::fs::remove_file(&file).ok();
+    }
+
+    #[tokio::test]
+    async fn test_undo_edit_waits_for_approval_and_counts_as_a_turn_change() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        agent.set_edit_confirmer(tx);
+        let pending = agent.pending_confirmations();
+        let decisions = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            for decision in [EditDecision::Reject, EditDecision::Approve] {
+                let request = rx.recv().await.unwrap();
+                seen.push(request.entry.content.clone());
+                assert!(pending.resolve(&request.id, decision));
+            }
+            seen
+        });
+
+        let file = std::env::temp_dir().join(format!("grok-undo-gate-{}.txt", std::process::id()));
+        agent.set_auto_edit(true);
+        let args = serde_json::json!({ "path": file.to_string_lossy(), "content": "hi\n" }).to_string();
+        assert!(agent.replay_tool("create_file", &args).await.unwrap().success);
+        agent.set_auto_edit(false);
+        agent.turn_changes.clear();
+
+        assert!(!agent.replay_tool("undo_edit", "{}").await.unwrap().success);
+        assert!(file.exists());
+        assert!(agent.replay_tool("undo_edit", "{}").await.unwrap().success);
+        assert!(!file.exists());
+        assert!(agent.turn_changes.summary().unwrap().contains("deleted"));
+
+        let seen = decisions.await.unwrap();
+        assert!(seen[0].starts_with("Undo the last file edit:"));
+        assert!(seen[0].contains("grok-undo-gate-"));
+    }
+
+    #[tokio::test]
+    async fn test_bash_commands_wait_for_approval_with_the_exact_command() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        agent.set_edit_confirmer(tx);
+        let approver = agent.clone();
+        let decisions = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            for decision in [EditDecision::Reject, EditDecision::ApproveAll] {
+                let request = rx.recv().await.unwrap();
+                assert!(request.is_bash());
+                assert_eq!(request.entry.entry_type, ChatEntryType::ConfirmationPending);
+                seen.push(request.entry.content.clone());
+                // Any clone of the agent can answer
+                assert!(approver.resolve_confirmation(&request.id, decision));
+            }
+            seen
+        });
+
+        let args = serde_json::json!({ "command": "echo gated" }).to_string();
+        let rejected = agent.replay_tool("bash", &args).await.unwrap();
+        assert!(!rejected.success);
+        assert!(rejected.error.unwrap().contains("Nothing was run"));
+        assert!(agent.replay_tool("bash", &args).await.unwrap().output.unwrap().contains("gated"));
+        // "Always" covers later commands, but not file edits
+        assert!(agent.replay_tool("bash", &args).await.unwrap().success);
+        assert!(!agent.auto_edit());
+        assert_eq!(decisions.await.unwrap(), vec!["$ echo gated", "$ echo gated"]);
+        assert!(!agent.resolve_confirmation("unknown", EditDecision::Approve));
+
+        let mut headless = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        headless.set_edit_confirmer(tx);
+        headless.set_auto_accept(true);
+        assert!(headless.replay_tool("bash", &args).await.unwrap().success);
+        assert!(headless.auto_edit());
+
+        // Nobody to ask, or a confirmer that has gone away, is a rejection
+        let mut unattended = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let refused = unattended.replay_tool("bash", &args).await.unwrap();
+        assert!(!refused.success);
+        assert!(refused.error.unwrap().contains("nothing was run"));
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        drop(rx);
+        unattended.set_edit_confirmer(tx);
+        assert!(!unattended.replay_tool("bash", &args).await.unwrap().success);
+        assert!(unattended.pending_confirmations().0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ask_user_returns_the_answer_or_no_human_available() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let args = serde_json::json!({ "question": "Which database?", "options": ["sqlite", "postgres"] }).to_string();
+        let headless = agent.replay_tool("ask_user", &args).await.unwrap();
+        assert_eq!(headless.output.as_deref(), Some(NO_HUMAN_AVAILABLE));
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<UserQuestion>(1);
+        agent.set_user_asker(tx);
+        tokio::spawn(async move {
+            let request = rx.recv().await.unwrap();
+            assert_eq!(request.question, "Which database?");
+            let answer = resolve_answer("2", &request.options);
+            request.reply.send(answer).unwrap();
+        });
+        let answered = agent.replay_tool("ask_user", &args).await.unwrap();
+        assert_eq!(answered.data.unwrap()["answer"], "postgres");
+        assert_eq!(resolve_answer(" use mysql ", &[]), "use mysql");
+    }
+
+    #[test]
+    fn test_tool_result_summary_policy() {
+        let big = "x".repeat(DEFAULT_SUMMARY_THRESHOLD_CHARS + 1);
+        assert!(!ToolResultSummaryConfig::default().should_summarize("view_file", &big));
+
+        let mut config: ToolResultSummaryConfig = serde_json::from_value(serde_json::json!({
+            "enabled": true,
+            "tools": { "bash": "verbatim", "str_replace_editor": "summarize" }
+        }))
+        .unwrap();
+        assert_eq!(config.threshold_chars, DEFAULT_SUMMARY_THRESHOLD_CHARS);
+        assert!(config.should_summarize("view_file", &big));
+        assert!(!config.should_summarize("view_file", "short"));
+        // Edits stay exact by default; overrides win either way
+        assert!(!config.should_summarize("create_file", &big));
+        assert!(config.should_summarize("str_replace_editor", &big));
+        assert!(!config.should_summarize("bash", &big));
+        config.tools.clear();
+        assert!(config.should_summarize("bash", &big));
+    }
+
+    #[test]
+    fn test_failure_streak_trips_after_limit_and_resets_on_success() {
+        let mut streak = FailureStreak::new(3);
+        for success in [false, false, true, false, false] {
+            streak.record(success);
+            assert!(!streak.tripped());
+        }
+        streak.record(false);
+        assert!(streak.tripped());
+        // Starts over after tripping
+        assert!(!streak.tripped());
+
+        let mut disabled = FailureStreak::new(0);
+        for _ in 0..10 {
+            disabled.record(false);
+        }
+        assert!(!disabled.tripped());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_tool_calls_run_and_report_results() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_auto_accept(true);
+        let call = GrokToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction {
+                name: "bash".to_string(),
+                arguments: r#"{"command":"echo hi"}"#.to_string(),
+            },
+        };
+
+        let chunks = agent.run_streamed_tool_calls("", &[call]).await;
+        assert_eq!(chunks.len(), 1);
+        assert!(matches!(chunks[0].chunk_type, StreamingChunkType::ToolResult));
+        assert_eq!(chunks[0].tool_call.as_ref().map(|c| c.id.as_str()), Some("1"));
+        let result = chunks[0].tool_result.as_ref().unwrap();
+        assert!(result.success);
+        assert_eq!(result.output.as_deref().map(str::trim), Some("hi"));
+
+        assert_eq!(agent.messages.last().unwrap().role, "tool");
+        assert_eq!(agent.chat_history.last().unwrap().entry_type, ChatEntryType::ToolResult);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_tool_calls_run_together_and_report_in_call_order() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_auto_accept(true);
+        agent.set_tool_concurrency(2);
+        let dir = std::env::temp_dir().join(format!("grok-parallel-tools-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        std::fs::write(&a, "first").unwrap();
+        std::fs::write(&b, "second").unwrap();
+        let call = |id: &str, name: &str, arguments: serde_json::Value| GrokToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction { name: name.to_string(), arguments: arguments.to_string() },
+        };
+        let calls = vec![
+            call("1", "view_file", serde_json::json!({ "path": a })),
+            call("2", "view_file", serde_json::json!({ "path": b })),
+            call("3", "view_file", serde_json::json!({ "path": dir.join("missing.txt") })),
+            call("4", "bash", serde_json::json!({ "command": format!("echo changed > {}", a.display()) })),
+            call("5", "view_file", serde_json::json!({ "path": a })),
+        ];
+
+        let chunks = agent.run_streamed_tool_calls("", &calls).await;
+        let outputs: Vec<(bool, String)> = chunks
+            .iter()
+            .map(|c| {
+                let result = c.tool_result.as_ref().unwrap();
+                (result.success, result.output.clone().unwrap_or_default())
+            })
+            .collect();
+        assert!(outputs[0].1.contains("first") && outputs[1].1.contains("second"), "{:?}", outputs);
+        assert!(!outputs[2].0);
+        assert!(outputs[3].0);
+        // The view after the bash call runs after it and sees the change
+        assert!(outputs[4].1.contains("changed"), "{:?}", outputs);
+        let tool_ids: Vec<&str> = agent.messages.iter().filter_map(|m| m.tool_call_id.as_deref()).collect();
+        assert_eq!(tool_ids, vec!["1", "2", "3", "4", "5"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_run_skips_remaining_tool_calls_and_ends_the_stream() {
+        use futures::StreamExt;
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_auto_accept(true);
+        let cancellation = CancellationToken::new();
+        agent.set_cancellation(cancellation.clone());
+        let calls: Vec<GrokToolCall> = (1..=2)
+            .map(|n| GrokToolCall {
+                id: n.to_string(),
+                call_type: "function".to_string(),
+                function: GrokToolCallFunction { name: "bash".to_string(), arguments: format!(r#"{{"command":"echo {}"}}"#, n) },
+            })
+            .collect();
+
+        assert!(agent.run_streamed_tool_calls("", &calls[..1]).await[0].tool_result.as_ref().unwrap().success);
+        cancellation.cancel();
+        let chunks = agent.run_streamed_tool_calls("", &calls[1..]).await;
+        let result = chunks[0].tool_result.as_ref().unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some(CANCELLED_TOOL_CALL_NOTE));
+        // The skipped call still has its tool message
+        assert_eq!(agent.messages.last().unwrap().tool_call_id.as_deref(), Some("2"));
+
+        // No request is sent; the stream only carries the final Done chunk
+        let stream_chunks: Vec<_> = agent.retry_stream().await.unwrap().collect().await;
+        assert_eq!(stream_chunks.len(), 1);
+        let done = stream_chunks[0].as_ref().unwrap();
+        assert!(matches!(done.chunk_type, StreamingChunkType::Done));
+        assert!(done.tool_calls.is_none());
+
+        assert_eq!(agent.record_cancellation().content, CANCELLED_NOTE);
+        assert_eq!(agent.chat_history.last().unwrap().content, CANCELLED_NOTE);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_past_their_timeout_are_stopped() {
+        use std::time::Duration;
+        let ok = || ToolResult { success: true, output: Some("done".to_string()), error: None, data: None };
+        // A fake tool that sleeps past its limit
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(ok())
+        };
+        let result = run_with_timeout(Some(Duration::from_millis(50)), slow).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("timed out after 50ms and was stopped"));
+        assert!(run_with_timeout(None, async { Ok(ok()) }).await.unwrap().success);
+
+        let timeouts = ToolTimeouts::with_overrides(&HashMap::from([("bash".to_string(), 0), ("check".to_string(), 300)]));
+        assert_eq!(timeouts.for_tool("bash"), None);
+        assert_eq!(timeouts.for_tool("check"), Some(Duration::from_secs(300)));
+        assert_eq!(timeouts.for_tool("view_file"), Some(Duration::from_secs(10)));
+        assert_eq!(ToolTimeouts::default().for_tool("ask_user"), None);
+
+        // A timed-out bash command is killed together with the programs it started
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_auto_accept(true);
+        let mut timeouts = ToolTimeouts::default();
+        timeouts.set("bash", Some(Duration::from_millis(500)));
+        agent.set_tool_timeouts(timeouts);
+        let pid_file = std::env::temp_dir().join(format!("grok-timeout-{}.pid", std::process::id()));
+        let call = GrokToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction {
+                name: "bash".to_string(),
+                arguments: serde_json::json!({ "command": format!("sleep 30 & echo $! > {}; wait", pid_file.display()) }).to_string(),
+            },
+        };
+        let started = std::time::Instant::now();
+        let result = agent.execute_tool(&call).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(result.error.as_deref(), Some("timed out after 500ms and was stopped"));
+
+        #[cfg(target_os = "linux")]
+        {
+            let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+            // Gone, or a zombie waiting for its new parent to reap it
+            let running = || {
+                std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                    .is_ok_and(|stat| stat.rsplit(')').next().is_some_and(|rest| !rest.trim_start().starts_with('Z')))
+            };
+            let deadline = std::time::Instant::now() + Duration::from_secs(2);
+            while running() && std::time::Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert!(!running(), "sleep {} survived the timeout", pid);
+        }
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_past_the_per_message_limit_are_deferred() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_auto_accept(true);
+        agent.set_max_tool_calls_per_message(Some(1));
+        let calls: Vec<GrokToolCall> = (1..=3)
+            .map(|n| GrokToolCall {
+                id: n.to_string(),
+                call_type: "function".to_string(),
+                function: GrokToolCallFunction {
+                    name: "bash".to_string(),
+                    arguments: format!(r#"{{"command":"echo {}"}}"#, n),
+                },
+            })
+            .collect();
+
+        let chunks = agent.run_streamed_tool_calls("", &calls).await;
+        let results: Vec<(bool, Option<&str>)> = chunks
+            .iter()
+            .map(|c| {
+                let result = c.tool_result.as_ref().unwrap();
+                (result.success, result.output.as_deref().map(str::trim))
+            })
+            .collect();
+        assert_eq!(results, vec![(true, Some("1")), (false, None), (false, None)]);
+        // Every call still gets a tool message, so the conversation stays valid
+        let tool_ids: Vec<&str> = agent
+            .messages
+            .iter()
+            .filter_map(|m| m.tool_call_id.as_deref())
+            .collect();
+        assert_eq!(tool_ids, vec!["1", "2", "3"]);
+        assert!(agent.messages.last().unwrap().content.as_deref().unwrap().starts_with("Not run"));
+
+        agent.set_max_tool_calls_per_message(Some(0));
+        assert_eq!(agent.split_tool_calls(&calls).0.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_explain_mode_notes_unexplained_tool_calls() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let call = |name: &str, arguments: &str| GrokToolCall {
+            id: name.to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction { name: name.to_string(), arguments: arguments.to_string() },
+        };
+        let calls = vec![call("bash", r#"{"command":"cargo test"}"#), call("view_file", r#"{"path":"src/lib.rs"}"#)];
+        assert!(agent.explain_tool_calls("", &calls).is_empty());
+
+        agent.set_explain_tools(true);
+        assert_eq!(
+            agent.explain_tool_calls("  ", &calls),
+            vec!["💡 About to run `cargo test`", "💡 About to read src/lib.rs to see its current content"]
+        );
+        // The model already said what it is doing
+        assert!(agent.explain_tool_calls("Running the tests first.", &calls).is_empty());
+        // Only calls that will run are explained
+        agent.set_max_tool_calls_per_message(Some(1));
+        assert_eq!(agent.explain_tool_calls("", &calls).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_project_tool_list_and_system_prompt() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_enabled_tools(Some(vec!["view_file".to_string(), "search".to_string()]));
+        assert_eq!(agent.tool_names().await, vec!["view_file", "search"]);
+        let call = GrokToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction { name: "bash".to_string(), arguments: r#"{"command":"echo hi"}"#.to_string() },
+        };
+        let chunks = agent.run_streamed_tool_calls("", &[call]).await;
+        let result = chunks[0].tool_result.as_ref().unwrap();
+        assert!(!result.success);
+        assert!(result.error.as_deref().unwrap().contains("disabled"));
+
+        agent.append_system_prompt("Review only.\n");
+        let system = agent.messages[0].content.as_deref().unwrap();
+        assert!(system.starts_with("You are Grok CLI"));
+        assert!(system.ends_with("PROJECT INSTRUCTIONS:\nReview only."));
+        assert_eq!(agent.system_prompt(), system.to_string());
+
+        // The conversation after the system prompt is kept
+        let messages = agent.messages.len();
+        agent.set_system_prompt("Answer in French.");
+        assert_eq!(agent.system_prompt(), "Answer in French.");
+        assert_eq!(agent.messages.len(), messages);
+        assert_eq!(estimate_tokens("Answer in French."), 5);
+    }
+
+    #[tokio::test]
+    async fn test_tool_rounds_table_prefers_exact_then_user_rules() {
+        let config: ToolRoundsConfig = serde_json::from_str(
+            r#"{"rules": [{"model": "grok-code-fast-1", "exact": true, "rounds": 40}, {"model": "grok-3", "rounds": 20}], "default": 8}"#,
+        )
+        .unwrap();
+        assert_eq!(config.resolve("grok-code-fast-1"), (40, "rule \"grok-code-fast-1\" (exact)".to_string()));
+        assert_eq!(config.resolve("grok-3-mini").0, 20);
+        assert_eq!(config.resolve("grok-4"), (10, "built-in rule \"grok\"".to_string()));
+        assert_eq!(config.resolve("llama-3").0, 8);
+        assert_eq!(ToolRoundsConfig::default().resolve("llama-3"), (DEFAULT_TOOL_ROUNDS, "built-in default".to_string()));
+        assert_eq!(ToolRoundsConfig::default().resolve("claude-sonnet").0, 15);
+
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_tool_rounds_config(&config);
+        assert_eq!(agent.max_tool_rounds(), 40);
+        let mut explicit = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, Some(3), None).await.unwrap();
+        explicit.set_tool_rounds_config(&config);
+        assert_eq!((explicit.max_tool_rounds(), explicit.tool_rounds_source()), (3, "--max-tool-rounds"));
+    }
+
+    #[tokio::test]
+    async fn test_pop_last_turn_drops_reply_and_tool_turns() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let user = |content: String| GrokMessage {
+            role: "user".to_string(),
+            content: Some(content),
+            tool_calls: None,
+            tool_call_id: None,
+        };
+        agent.messages.push(user("first".to_string()));
+        agent.messages.push(assistant(Some("done"), None));
+        agent.messages.push(user("second".to_string()));
+        let call = GrokToolCall {
+            id: "call_1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction {
+                name: "view_file".to_string(),
+                arguments: r#"{"path":"a"}"#.to_string(),
+            },
+        };
+        agent.messages.push(assistant(None, Some(vec![call])));
+        agent.messages.push(GrokMessage {
+            role: "tool".to_string(),
+            content: Some("Error".to_string()),
+            tool_calls: None,
+            tool_call_id: Some("call_1".to_string()),
+        });
+        agent.messages.push(user(tool_failure_nudge(agent.max_consecutive_failures)));
+        agent.messages.push(assistant(Some("retry"), None));
+
+        assert_eq!(agent.pop_last_turn().as_deref(), Some("second"));
+        assert_eq!(agent.conversation().len(), 3);
+        assert_eq!(agent.pop_last_turn().as_deref(), Some("first"));
+        assert_eq!(agent.pop_last_turn(), None);
+        assert_eq!(agent.conversation()[0].role, "system");
+    }
+
+    #[tokio::test]
+    async fn test_restored_session_keeps_the_current_system_prompt() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let call = GrokToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction { name: "bash".to_string(), arguments: "{}".to_string() },
+        };
+        let saved = vec![
+            GrokMessage { role: "system".to_string(), content: Some("Current working directory: /old".to_string()), tool_calls: None, tool_call_id: None },
+            GrokMessage { role: "user".to_string(), content: Some("list files".to_string()), tool_calls: None, tool_call_id: None },
+            assistant(None, Some(vec![call])),
+            GrokMessage { role: "tool".to_string(), content: Some("a.rs".to_string()), tool_calls: None, tool_call_id: Some("1".to_string()) },
+        ];
+        let mut session = SessionFile::new("saved1", "grok-4", Vec::new(), saved);
+        session.working_directory = "/old".to_string();
+
+        agent.restore_session(session);
+        assert_eq!(agent.session_id(), "saved1");
+        assert_eq!(agent.messages.len(), 4);
+        let system = agent.messages[0].content.as_deref().unwrap();
+        assert!(system.ends_with(&std::env::current_dir().unwrap().to_string_lossy().to_string()));
+        assert_eq!(agent.messages[3].tool_call_id.as_deref(), Some("1"));
+        assert_eq!(agent.session_file(&[]).messages.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_history_window_keeps_system_prompt_and_last_turns() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        for turn in 0..3 {
+            agent.messages.push(GrokMessage {
+                role: "user".to_string(),
+                content: Some(format!("question {}", turn)),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+            agent.messages.push(assistant(Some("answer"), None));
+        }
+
+        assert_eq!(agent.request_messages().len(), 7);
+        agent.set_history_window(Some(2));
+        let window = agent.request_messages();
+        assert_eq!(window.len(), 5);
+        assert_eq!(window[0].role, "system");
+        assert_eq!(window[1].content.as_deref(), Some("question 1"));
+
+        agent.set_history_window(Some(0));
+        assert_eq!(agent.get_history_window(), None);
+    }
+
+    /// Every tool message follows an assistant message that made the call
+    fn assert_tool_results_paired(messages: &[GrokMessage]) {
+        for (i, message) in messages.iter().enumerate().filter(|(_, m)| m.role == "tool") {
+            let id = message.tool_call_id.as_deref().unwrap();
+            let called = messages[..i]
+                .iter()
+                .rev()
+                .take_while(|m| m.role == "tool" || m.role == "assistant")
+                .filter_map(|m| m.tool_calls.as_ref())
+                .flatten()
+                .any(|call| call.id == id);
+            assert!(called, "tool result {} at {} has no tool call before it", id, i);
+        }
+    }
+
+    #[test]
+    fn test_context_budget_elides_old_tool_results_then_drops_whole_turns() {
+        let mut messages = vec![GrokMessage {
+            role: "system".to_string(),
+            content: Some("system prompt".to_string()),
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+        for turn in 0..6 {
+            let call = GrokToolCall {
+                id: format!("call_{}", turn),
+                call_type: "function".to_string(),
+                function: GrokToolCallFunction { name: "view_file".to_string(), arguments: r#"{"path":"a.rs"}"#.to_string() },
+            };
+            messages.push(GrokMessage { role: "user".to_string(), content: Some(format!("question {}", turn)), tool_calls: None, tool_call_id: None });
+            messages.push(assistant(None, Some(vec![call])));
+            messages.push(GrokMessage {
+                role: "tool".to_string(),
+                content: Some("x".repeat(4000 + turn)),
+                tool_calls: None,
+                tool_call_id: Some(format!("call_{}", turn)),
+            });
+            messages.push(assistant(Some("answer"), None));
+        }
+        let total: usize = messages.iter().map(message_tokens).sum();
+        let contents = |messages: &[GrokMessage]| messages.iter().map(|m| m.content.clone()).collect::<Vec<_>>();
+        assert_eq!(contents(&fit_context_budget(messages.clone(), total, CONTEXT_KEEP_TURNS)), contents(&messages));
+
+        // Eliding the four oldest results is enough; nothing is dropped
+        let elided = fit_context_budget(messages.clone(), total - 3000, CONTEXT_KEEP_TURNS);
+        assert_eq!(elided.len(), messages.len());
+        assert_eq!(elided[3].content.as_deref(), Some("[tool output elided, 1,000 tokens]"));
+        assert_eq!(elided[15].content.as_deref(), Some("[tool output elided, 1,001 tokens]"));
+        assert_eq!(elided[19].content, messages[19].content);
+        assert_tool_results_paired(&elided);
+
+        // A tight budget drops old turns whole but keeps the system prompt and the last two
+        let trimmed = fit_context_budget(messages.clone(), 2080, CONTEXT_KEEP_TURNS);
+        assert_eq!(trimmed[0].role, "system");
+        assert_eq!(trimmed[1].content.as_deref(), Some("question 4"));
+        assert_eq!(trimmed.len(), 9);
+        assert_tool_results_paired(&trimmed);
+        // Even a budget nothing fits in keeps them
+        assert_eq!(contents(&fit_context_budget(messages, 10, CONTEXT_KEEP_TURNS)), contents(&trimmed));
+    }
+
+    #[tokio::test]
+    async fn test_long_messages_are_split_into_parts_that_count_as_one_turn() {
+        let message: String = (0..400).map(|i| format!("line {:03} of the pasted log\n", i)).collect();
+        let parts = split_long_message(&message, 500);
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| estimate_tokens(part) <= 500), "{:?}", parts.iter().map(|p| estimate_tokens(p)).collect::<Vec<_>>());
+        assert!(parts[0].starts_with(&format!("[Part 1/{}", parts.len())));
+        // Lines are kept whole, and one line longer than a part is cut
+        assert!(parts[1].lines().nth(1).unwrap().starts_with("line "));
+        assert_eq!(split_long_message(&"x".repeat(5000), 500).len(), 3);
+        assert_eq!(split_long_message("short", 500), vec!["short"]);
+
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        let before = agent.messages.len();
+        agent.set_max_message_tokens(Some(500));
+        agent.push_user_message(&message);
+        assert_eq!(agent.messages.len(), before + parts.len());
+        agent.messages.push(assistant(Some("got it"), None));
+        agent.set_history_window(Some(1));
+        assert_eq!(agent.request_messages().len(), 1 + parts.len() + 1);
+
+        assert_eq!(agent.pop_last_turn().as_deref(), Some(message.as_str()));
+        assert_eq!(agent.messages.len(), before);
+    }
+
+    #[tokio::test]
+    async fn test_old_tool_results_are_compressed_but_recent_ones_kept() {
+        let mut agent = GrokAgent::new("API_KEY_NOT_SET", "https://api.x.ai/v1".to_string(), None, None, None).await.unwrap();
+        agent.set_compress_tool_results_after(Some(2));
+        let call = |id: &str, name: &str, arguments: &str| GrokToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction { name: name.to_string(), arguments: arguments.to_string() },
+        };
+        let tool = |id: &str, content: String| GrokMessage {
+            role: "tool".to_string(),
+            content: Some(content),
+            tool_calls: None,
+            tool_call_id: Some(id.to_string()),
+        };
+        let file: String = (0..50).map(|i| format!("{} | let x{} = {};\n", i, i, i)).collect();
+        agent.push_user_message("read it");
+        agent.messages.push(assistant(None, Some(vec![call("a", "view_file", r#"{"path":"src/a.rs"}"#), call("b", "str_replace_editor", "{}")])));
+        agent.messages.push(tool("a", file.clone()));
+        agent.messages.push(tool("b", format!("Successfully replaced text in src/a.rs\n{}", file)));
+        agent.messages.push(tool("c", "ok".to_string()));
+        agent.messages.push(assistant(Some("done"), None));
+        let first_turn = agent.messages.len();
+
+        agent.push_user_message("run it");
+        agent.messages.push(assistant(None, Some(vec![call("d", "bash", r#"{"command":"cargo test"}"#)])));
+        agent.messages.push(tool("d", format!("running 3 tests\n{}", file)));
+        // Two turns are kept in full
+        assert_eq!(agent.messages[first_turn - 4].content.as_deref(), Some(file.as_str()));
+
+        agent.push_user_message("next");
+        let content = |i: usize| agent.messages[i].content.clone().unwrap();
+        assert_eq!(content(first_turn - 4), "[compressed] Viewed src/a.rs (50 lines); view it again if you need the content");
+        assert_eq!(content(first_turn - 3), "[compressed] Successfully replaced text in src/a.rs");
+        assert_eq!(content(first_turn - 2), "ok");
+        assert_eq!(content(first_turn + 2), format!("running 3 tests\n{}", file));
+        assert!(agent.get_chat_history().iter().all(|entry| !entry.content.starts_with(COMPRESSED_PREFIX)));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_turn_runs_tool_calls_and_stops_repeated_rounds() {
+        use crate::grok::client::tests::{http_response, mock_server};
+        use futures::StreamExt;
+        let sse = |events: &[&str]| {
+            let body: String = events.iter().map(|event| format!("data: {}\n\n", event)).collect::<String>() + "data: [DONE]\n\n";
+            http_response("200 OK", "Content-Type: text/event-stream\r\n", &body)
+        };
+        let call = r#"{"choices": [{"delta": {"tool_calls": [{"index": 0, "id": "call_1", "function": {"name": "bash", "arguments": "{\"command\":\"echo ran\"}"}}]}, "finish_reason": "tool_calls"}]}"#;
+        let answer = r#"{"choices": [{"delta": {"content": "All done"}, "finish_reason": "stop"}]}"#;
+
+        // The tool call runs inside the stream and the answer to its result follows it
+        let (base_url, hits) = mock_server(vec![sse(&[call]), sse(&[answer])]).await;
+        let mut agent = GrokAgent::new("key", base_url, None, None, None).await.unwrap();
+        agent.set_auto_accept(true);
+        let chunks: Vec<StreamingChunk> =
+            agent.process_user_message_stream("run it").await.unwrap().map(|chunk| chunk.unwrap()).collect().await;
+        let kinds: Vec<&StreamingChunkType> = chunks.iter().map(|chunk| &chunk.chunk_type).filter(|kind| !matches!(kind, StreamingChunkType::ToolCalls)).collect();
+        // The line bash printed comes first, as a result chunk without a result
+        assert!(matches!(kinds[..], [StreamingChunkType::ToolResult, StreamingChunkType::ToolResult, StreamingChunkType::Content, StreamingChunkType::Done]), "{:?}", kinds);
+        let printed = chunks.iter().find(|chunk| matches!(chunk.chunk_type, StreamingChunkType::ToolResult)).unwrap();
+        assert_eq!((printed.content.as_deref(), printed.tool_result.is_none()), (Some("ran"), true));
+        assert_eq!(printed.tool_call.as_ref().unwrap().id, "call_1");
+        let result = chunks.iter().find_map(|chunk| chunk.tool_result.as_ref()).unwrap();
+        assert_eq!(result.output.as_deref(), Some("ran"));
+        assert!(chunks.last().unwrap().tool_calls.is_none());
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(agent.messages.iter().filter(|m| m.role == "tool").count(), 1);
+
+        // The third identical round is not run, as in the non-streaming loop
+        let (base_url, hits) = mock_server(vec![sse(&[call]), sse(&[call]), sse(&[call])]).await;
+        let mut agent = GrokAgent::new("key", base_url, None, None, None).await.unwrap();
+        let chunks: Vec<StreamingChunk> =
+            agent.process_user_message_stream("run it").await.unwrap().map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(chunks.iter().filter(|chunk| chunk.tool_result.is_some()).count(), 2);
+        let note = chunks.iter().find(|chunk| matches!(chunk.chunk_type, StreamingChunkType::Note)).unwrap();
+        assert!(note.content.as_deref().unwrap().starts_with("🔁 Infinite loop detected: bash("));
+        assert!(matches!(chunks.last().unwrap().chunk_type, StreamingChunkType::Done));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(agent.chat_history.last().unwrap().content.starts_with("🔁"));
+    }
+}
@@ -1,5 +1,5 @@
 pub mod mcp {
-    use clap::Subcommand;
+    use clap::{Args, Subcommand};
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
 
@@ -7,25 +7,151 @@ pub mod mcp {
     pub enum McpCommand {
         /// Add an MCP server
         #[command(arg_required_else_help = true)]
-        Add {
-            /// Name for the MCP server
-            name: String,
-            /// Transport type (stdio, http, sse, streamable_http)
-            #[arg(short = 't', long = "transport", default_value = "stdio")]
-            transport: String,
-            /// Command to run for stdio transport
-            #[arg(long = "command")]
-            command: Option<String>,
-            /// Arguments for the command
-            #[arg(long = "args")]
-            args: Option<String>,
-        },
+        Add(AddServer),
         /// Remove an MCP server
         #[command(arg_required_else_help = true)]
         Remove {
             /// Name of the MCP server to remove
             name: String,
+            /// Settings file to remove it from
+            #[arg(long = "scope", value_enum, default_value = "project")]
+            scope: McpScope,
         },
+        /// List the configured MCP servers
+        List,
+        /// Start a server, run the handshake and list its tools
+        #[command(arg_required_else_help = true)]
+        Test {
+            /// Name of the MCP server to test
+            name: String,
+        },
+    }
+
+    /// Which settings file holds a server
+    #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum McpScope {
+        /// `.grok/settings.json` in the current project
+        Project,
+        /// `~/.grok/user-settings.json`, for every project
+        User,
+    }
+
+    impl std::fmt::Display for McpScope {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.pad(match self {
+                McpScope::Project => "project",
+                McpScope::User => "user",
+            })
+        }
+    }
+
+    /// What `grok mcp add` takes
+    #[derive(Args, Debug)]
+    pub struct AddServer {
+        /// Name for the MCP server
+        pub name: String,
+        /// Transport type (stdio, http, sse, streamable_http)
+        #[arg(short = 't', long = "transport", default_value = "stdio")]
+        pub transport: String,
+        /// Command to run for stdio transport
+        #[arg(long = "command")]
+        pub command: Option<String>,
+        /// One argument for the command; repeat for each, e.g. --arg -y --arg @scope/server
+        #[arg(long = "arg", visible_alias = "args", value_name = "ARG", allow_hyphen_values = true)]
+        pub args: Vec<String>,
+        /// Environment variable for the stdio server as KEY=VALUE; repeatable
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env)]
+        pub env: Vec<(String, String)>,
+        /// Server URL for the http, sse and streamable_http transports
+        #[arg(long = "url")]
+        pub url: Option<String>,
+        /// HTTP header sent to the server as "Name: value"; repeatable
+        #[arg(long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+        pub headers: Vec<(String, String)>,
+        /// Settings file to save the server in
+        #[arg(long = "scope", value_enum, default_value = "project")]
+        pub scope: McpScope,
+        /// Replace a server that already has this name
+        #[arg(long = "force")]
+        pub force: bool,
+    }
+
+    fn parse_env(value: &str) -> Result<(String, String), String> {
+        match value.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+            _ => Err(format!("expected KEY=VALUE, got '{}'", value)),
+        }
+    }
+
+    fn parse_header(value: &str) -> Result<(String, String), String> {
+        match value.split_once(':') {
+            Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.trim().to_string())),
+            _ => Err(format!("expected \"Name: value\", got '{}'", value)),
+        }
+    }
+
+    impl AddServer {
+        /// The config to save, or why these flags do not make a usable server: stdio needs a
+        /// command, the other transports need a URL, and each only takes its own flags
+        pub fn into_config(self) -> Result<MCPServerConfig, String> {
+            let remote = match self.transport.as_str() {
+                "stdio" => false,
+                "http" | "sse" | "streamable_http" => true,
+                other => return Err(format!("Unsupported transport '{}'; use stdio, http, sse or streamable_http", other)),
+            };
+            let transport = if remote {
+                let Some(url) = self.url else {
+                    return Err(format!("The {} transport needs --url", self.transport));
+                };
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(format!("--url must start with http:// or https://, got '{}'", url));
+                }
+                if self.command.is_some() || !self.args.is_empty() || !self.env.is_empty() {
+                    return Err(format!("--command, --arg and --env only apply to stdio servers, not {}", self.transport));
+                }
+                TransportConfig {
+                    transport_type: self.transport,
+                    command: None,
+                    args: None,
+                    env: None,
+                    url: Some(url),
+                    headers: (!self.headers.is_empty()).then(|| self.headers.into_iter().collect()),
+                }
+            } else {
+                let Some(command) = self.command.filter(|command| !command.trim().is_empty()) else {
+                    return Err("The stdio transport needs --command".to_string());
+                };
+                if self.url.is_some() || !self.headers.is_empty() {
+                    return Err("--url and --header only apply to http, sse and streamable_http servers".to_string());
+                }
+                TransportConfig {
+                    transport_type: self.transport,
+                    command: Some(command),
+                    args: (!self.args.is_empty()).then_some(self.args),
+                    env: (!self.env.is_empty()).then(|| self.env.into_iter().collect()),
+                    url: None,
+                    headers: None,
+                }
+            };
+            Ok(MCPServerConfig { name: self.name, transport, command: None, args: None, env: None })
+        }
+    }
+
+    /// The command line a stdio server is started with, or the URL of a remote one; the
+    /// transport's fields win over the legacy top-level `command` and `args`
+    pub fn resolved_target(config: &MCPServerConfig) -> String {
+        if let Some(url) = &config.transport.url {
+            return url.clone();
+        }
+        let Some(command) = config.transport.command.as_ref().or(config.command.as_ref()) else {
+            return "(no command)".to_string();
+        };
+        let args = config.transport.args.as_ref().or(config.args.as_ref());
+        std::iter::once(command)
+            .chain(args.into_iter().flatten())
+            .map(|word| if word.is_empty() || word.contains(char::is_whitespace) { format!("'{}'", word) } else { word.clone() })
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 
     #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -55,31 +181,176 @@ pub mod mcp {
         #[serde(skip_serializing_if = "Option::is_none")]
         pub headers: Option<HashMap<String, String>>,
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(subcommand)]
+            command: McpCommand,
+        }
+
+        fn add(args: &[&str]) -> Result<MCPServerConfig, String> {
+            match Cli::try_parse_from(["grok"].iter().chain(args)).map_err(|e| e.to_string())?.command {
+                McpCommand::Add(add) => add.into_config(),
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn test_add_takes_repeated_args_env_and_headers_and_round_trips() {
+            let config = add(&[
+                "add", "fs", "--command", "npx", "--arg", "-y", "--arg", "@modelcontextprotocol/server-filesystem",
+                "--arg", "/path", "--env", "NODE_ENV=production", "--env", "TOKEN=a=b",
+            ])
+            .unwrap();
+            assert_eq!(
+                config.transport.args.as_deref(),
+                Some(&["-y".to_string(), "@modelcontextprotocol/server-filesystem".to_string(), "/path".to_string()][..])
+            );
+            assert_eq!(config.transport.env.as_ref().unwrap()["TOKEN"], "a=b");
+
+            let json = serde_json::to_value(&config).unwrap();
+            assert_eq!(json["transport"]["type"], "stdio");
+            assert!(json["transport"].get("url").is_none());
+            let back: MCPServerConfig = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&back).unwrap(), json);
+            // The startup loader reads the same shape
+            let loaded: crate::mcp::MCPServerConfig = serde_json::from_value(json).unwrap();
+            assert_eq!(loaded.transport.command.as_deref(), Some("npx"));
+
+            let remote = add(&["add", "docs", "-t", "http", "--url", "https://mcp.example.com/mcp", "--header", "Authorization: Bearer t"]).unwrap();
+            let json = serde_json::to_value(&remote).unwrap();
+            assert_eq!(json["transport"]["headers"]["Authorization"], "Bearer t");
+            assert!(json["transport"].get("command").is_none());
+
+            assert!(add(&["add", "broken"]).unwrap_err().contains("needs --command"));
+            assert!(add(&["add", "remote", "-t", "sse"]).unwrap_err().contains("needs --url"));
+            assert!(add(&["add", "mixed", "-t", "http", "--url", "http://localhost:9/mcp", "--arg", "x"]).is_err());
+            assert!(add(&["add", "bad-env", "--command", "srv", "--env", "NOVALUE"]).is_err());
+        }
+
+        #[test]
+        fn test_resolved_target_prefers_the_transport_fields() {
+            let config = add(&["add", "fs", "--command", "npx", "--arg", "-y", "--arg", "/my docs"]).unwrap();
+            assert_eq!(resolved_target(&config), "npx -y '/my docs'");
+            let legacy: MCPServerConfig = serde_json::from_str(
+                r#"{"name": "old", "transport": {"type": "stdio"}, "command": "server", "args": ["--port", "1"]}"#,
+            )
+            .unwrap();
+            assert_eq!(resolved_target(&legacy), "server --port 1");
+            let remote = add(&["add", "docs", "-t", "sse", "--url", "https://mcp.example.com/sse"]).unwrap();
+            assert_eq!(resolved_target(&remote), "https://mcp.example.com/sse");
+        }
+    }
+}
+
+pub mod history {
+    use clap::Subcommand;
+
+    #[derive(Subcommand)]
+    pub enum HistoryCommand {
+        /// Print a saved session as Markdown
+        #[command(arg_required_else_help = true)]
+        Show {
+            /// Session id (a unique prefix is enough)
+            id: String,
+        },
+        /// Continue a saved session in the interactive UI
+        #[command(arg_required_else_help = true)]
+        Resume {
+            /// Session id (a unique prefix is enough)
+            id: String,
+        },
+        /// Delete a saved session
+        #[command(arg_required_else_help = true)]
+        Delete {
+            /// Session id (a unique prefix is enough)
+            id: String,
+        },
+    }
 }
 
 // Export the functions that are used in main.rs
-pub use crate::commands::mcp_functions::{add_mcp_server, remove_mcp_server};
+pub use crate::commands::mcp_functions::{add_mcp_server, list_mcp_servers, remove_mcp_server};
 
 mod mcp_functions {
-    use crate::commands::mcp::MCPServerConfig;
-    use serde::{Deserialize, Serialize};
+    use crate::commands::mcp::{MCPServerConfig, McpScope};
+    use crate::utils::settings_manager::SettingsManager;
     use std::collections::HashMap;
 
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct ProjectSettings {
-        #[serde(skip_serializing_if = "Option::is_none")]
-        pub mcp_servers: Option<HashMap<String, MCPServerConfig>>,
+    type Servers = HashMap<String, serde_json::Value>;
+
+    /// The servers saved in `scope` and the file they are in
+    async fn load_servers(manager: &SettingsManager, scope: McpScope) -> Result<(Servers, String), Box<dyn std::error::Error>> {
+        // A settings file that does not load is reported, not overwritten with defaults
+        let (servers, path) = match scope {
+            McpScope::Project => (manager.load_project_settings().await?.mcp_servers, manager.project_settings_path()),
+            McpScope::User => (manager.load_user_settings().await?.mcp_servers, manager.user_settings_path()),
+        };
+        Ok((servers.unwrap_or_default(), path.display().to_string()))
+    }
+
+    /// Write `servers` back to `scope`, leaving the file's other settings alone
+    async fn save_servers(manager: &SettingsManager, scope: McpScope, servers: Servers) -> Result<(), Box<dyn std::error::Error>> {
+        let servers = (!servers.is_empty()).then_some(servers);
+        match scope {
+            McpScope::Project => {
+                let mut settings = manager.load_project_settings().await?;
+                settings.mcp_servers = servers;
+                manager.save_project_settings(&settings).await
+            }
+            McpScope::User => {
+                let mut settings = manager.load_user_settings().await?;
+                settings.mcp_servers = servers;
+                manager.save_user_settings(&settings).await
+            }
+        }
     }
 
-    pub fn add_mcp_server(name: String, _config: MCPServerConfig) -> Result<(), Box<dyn std::error::Error>> {
-        // In a complete implementation, this would save the server config to project settings
-        println!("Added MCP server: {}", name);
-        Ok(())
+    /// Save `config` under `mcp_servers` in `scope`. A server with the same name is an error
+    /// unless `force` is set, when it is replaced. Returns the settings file and whether a
+    /// server was replaced.
+    pub async fn add_mcp_server(config: MCPServerConfig, scope: McpScope, force: bool) -> Result<(String, bool), Box<dyn std::error::Error>> {
+        let manager = crate::utils::settings_manager::get_settings_manager().await?;
+        let (mut servers, path) = load_servers(&manager, scope).await?;
+        let replaced = servers.contains_key(&config.name);
+        if replaced && !force {
+            return Err(format!("An MCP server named '{}' is already in {}; pass --force to replace it", config.name, path).into());
+        }
+        servers.insert(config.name.clone(), serde_json::to_value(&config)?);
+        save_servers(&manager, scope, servers).await?;
+        Ok((path, replaced))
     }
 
-    pub fn remove_mcp_server(name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // In a complete implementation, this would remove the server config from project settings
-        println!("Removed MCP server: {}", name);
-        Ok(())
+    /// Remove the server called `name` from `scope`; returns the settings file
+    pub async fn remove_mcp_server(name: &str, scope: McpScope) -> Result<String, Box<dyn std::error::Error>> {
+        let manager = crate::utils::settings_manager::get_settings_manager().await?;
+        let (mut servers, path) = load_servers(&manager, scope).await?;
+        if servers.remove(name).is_none() {
+            return Err(format!("No MCP server named '{}' in {}", name, path).into());
+        }
+        save_servers(&manager, scope, servers).await?;
+        Ok(path)
     }
-}
\ No newline at end of file
+
+    /// Every configured server, project ones first, each sorted by name. An entry that is not a
+    /// valid server config comes back as the reason it does not parse.
+    pub async fn list_mcp_servers() -> Result<Vec<(McpScope, String, Result<MCPServerConfig, String>)>, Box<dyn std::error::Error>> {
+        let manager = crate::utils::settings_manager::get_settings_manager().await?;
+        let mut listed = Vec::new();
+        for scope in [McpScope::Project, McpScope::User] {
+            let (servers, _) = load_servers(&manager, scope).await?;
+            let mut servers: Vec<(String, serde_json::Value)> = servers.into_iter().collect();
+            servers.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, value) in servers {
+                let config = serde_json::from_value(value).map_err(|e| format!("invalid config: {}", e));
+                listed.push((scope, name, config));
+            }
+        }
+        Ok(listed)
+    }
+}
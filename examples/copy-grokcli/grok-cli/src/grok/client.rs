@@ -1,4 +1,7 @@
-use crate::types::{GrokMessage, GrokTool};
+use crate::grok::error::AgentError;
+use crate::grok::response_cache::ResponseCache;
+use crate::grok::usage::UsageTracker;
+use crate::types::{GrokMessage, GrokTool, TokenUsage, ToolChoice};
 use reqwest;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
@@ -13,6 +16,17 @@ pub struct GrokClient {
     pub is_openai_compatible: bool,
     http_client: reqwest::Client,
     pub default_max_tokens: u32,
+    pub tool_choice: ToolChoice,
+    /// Extra provider parameters merged into every request body (`--model-params`)
+    pub model_params: serde_json::Map<String, serde_json::Value>,
+    response_cache: Option<ResponseCache>,
+    /// Whether the last `chat` or `chat_stream` call was answered from the response cache
+    last_from_cache: std::sync::atomic::AtomicBool,
+    retry_policy: RetryPolicy,
+    /// How many times the last `chat` or `chat_stream` request was sent again before it went through
+    last_retries: std::sync::atomic::AtomicU32,
+    /// Tokens used by the requests sent; clones of the client add to the same totals
+    usage: UsageTracker,
 }
 
 impl Clone for GrokClient {
@@ -24,6 +38,13 @@ impl Clone for GrokClient {
             is_openai_compatible: self.is_openai_compatible,
             http_client: reqwest::Client::new(), // Create a new client since reqwest::Client doesn't implement Clone
             default_max_tokens: self.default_max_tokens,
+            tool_choice: self.tool_choice.clone(),
+            model_params: self.model_params.clone(),
+            response_cache: self.response_cache.clone(),
+            last_from_cache: std::sync::atomic::AtomicBool::new(self.last_from_cache()),
+            retry_policy: self.retry_policy.clone(),
+            last_retries: std::sync::atomic::AtomicU32::new(self.last_retries()),
+            usage: self.usage.clone(),
         }
     }
 }
@@ -31,6 +52,52 @@ impl Clone for GrokClient {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GrokResponse {
     pub choices: Vec<GrokChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Times the request was sent again before this response arrived; not part of the API
+    #[serde(skip)]
+    pub retries: u32,
+}
+
+/// How requests are sent again after a rate limit, a server error, or a network failure.
+/// Other errors, such as a rejected key, are returned at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 turns retrying off
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    pub base_delay: std::time::Duration,
+    /// Longest wait between attempts. A `Retry-After` asking for longer is not waited out here;
+    /// the error is returned so the caller can decide.
+    pub max_delay: std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: std::time::Duration::from_secs(2),
+            max_delay: std::time::Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (from 1) after `error`, or `None` when it is not retried.
+    /// The provider's `Retry-After` is used as given; otherwise the backoff doubles and up to a
+    /// quarter of it is taken off at random, so clients that failed together do not retry together.
+    pub fn delay(&self, retry: u32, error: &AgentError) -> Option<std::time::Duration> {
+        let transient = matches!(error, AgentError::RateLimited(_) | AgentError::ServerError(_) | AgentError::Network(_));
+        if !transient || retry > self.max_retries {
+            return None;
+        }
+        if let Some(retry_after) = error.retry_after() {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))).min(self.max_delay);
+        let jitter = (uuid::Uuid::new_v4().as_u128() % 1000) as u32;
+        Some(backoff - backoff / 4 * jitter / 1000)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,8 +118,106 @@ pub struct SearchOptions {
     pub search_parameters: Option<SearchParameters>,
 }
 
+/// Hosts whose OpenAI-compatible API lives under a version path such as `/v1`
+const VERSIONED_API_HOSTS: &[&str] = &[
+    "api.x.ai",
+    "api.openai.com",
+    "api.deepseek.com",
+    "api.groq.com",
+    "openrouter.ai",
+    "generativelanguage.googleapis.com",
+];
+
+/// Validate a configured base URL and strip trailing slashes.
+///
+/// Obviously malformed values (no scheme, unsupported scheme, no host) are
+/// rejected here so they fail at startup instead of as a 404 mid-request.
+pub fn normalize_base_url(raw: &str) -> Result<String, String> {
+    let trimmed = raw.trim().trim_end_matches('/');
+    let url = reqwest::Url::parse(trimmed)
+        .map_err(|e| format!("Invalid base URL '{}': {}. Expected something like https://api.x.ai/v1", raw, e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("Invalid base URL '{}': scheme must be http or https", raw));
+    }
+    if url.host_str().is_none_or(|host| host.is_empty()) {
+        return Err(format!("Invalid base URL '{}': missing host", raw));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("Invalid base URL '{}': remove the query string or fragment", raw));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Warn about base URLs that parse but are probably misconfigured
+pub fn base_url_warnings(base_url: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let Ok(url) = reqwest::Url::parse(base_url) else {
+        return warnings;
+    };
+    let host = url.host_str().unwrap_or_default();
+
+    if url.path().ends_with("/chat/completions") {
+        warnings.push(format!(
+            "Base URL '{}' already ends with /chat/completions; requests append it again. Use the API root instead.",
+            base_url
+        ));
+    }
+
+    let has_version = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .any(|segment| segment.starts_with('v') && segment[1..].starts_with(|c: char| c.is_ascii_digit()));
+    if !has_version && VERSIONED_API_HOSTS.contains(&host) {
+        warnings.push(format!(
+            "Base URL '{}' has no version path; {} usually expects one (e.g. {}/v1)",
+            base_url, host, base_url
+        ));
+    }
+
+    let is_local = host == "localhost" || host == "127.0.0.1" || host == "::1" || host == "[::1]";
+    if url.scheme() == "http" && !is_local {
+        warnings.push(format!("Base URL '{}' uses plain http; your API key is sent unencrypted", base_url));
+    }
+    warnings
+}
+
+/// Whole-request timeout, which also bounds a stream that stops sending data
+pub const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Request fields the client builds itself; `--model-params` cannot override them
+const MANAGED_PAYLOAD_KEYS: &[&str] = &["model", "messages", "tools", "tool_choice", "stream", "search_parameters"];
+
+/// Parse `--model-params` / `model_params`, which must be a JSON object
+pub fn parse_model_params(raw: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    match serde_json::from_str(raw) {
+        Ok(serde_json::Value::Object(params)) => Ok(params),
+        Ok(other) => Err(format!("Model params must be a JSON object, got: {}", other)),
+        Err(e) => Err(format!("Model params are not valid JSON: {}", e)),
+    }
+}
+
+/// Describe model params that will be ignored because the client manages those fields
+pub fn model_params_warnings(params: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+    params
+        .keys()
+        .filter(|key| MANAGED_PAYLOAD_KEYS.contains(&key.as_str()))
+        .map(|key| format!("Ignoring model param '{}': it is set by grok itself", key))
+        .collect()
+}
+
+fn payload_model(payload: &serde_json::Value) -> &str {
+    payload["model"].as_str().unwrap_or_default()
+}
+
+fn retry_after_header(response: &reqwest::Response) -> Option<String> {
+    response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok().map(str::to_string)
+}
+
 impl GrokClient {
-    pub fn new(api_key: &str, model: Option<String>, base_url: Option<String>, is_openai_compatible: Option<bool>) -> Self {
+    pub fn new(api_key: &str, model: Option<String>, base_url: Option<String>, is_openai_compatible: Option<bool>) -> Result<Self, String> {
+        let base_url = normalize_base_url(base_url.as_deref().unwrap_or("https://api.x.ai/v1"))?;
+
         let default_max_tokens = std::env::var("GROK_MAX_TOKENS")
             .ok()
             .and_then(|val| val.parse().ok())
@@ -60,21 +225,106 @@ impl GrokClient {
 
         // Create HTTP client with timeout
         let http_client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(120))
+            .timeout(REQUEST_TIMEOUT)
             .connect_timeout(std::time::Duration::from_secs(30))
             .build()
             .unwrap_or_else(|_| reqwest::Client::new());
 
-        Self {
+        Ok(Self {
             api_key: api_key.to_string(),
-            base_url: base_url.unwrap_or_else(|| "https://api.x.ai/v1".to_string()),
+            base_url,
             model: model.unwrap_or_else(|| "grok-code-fast-1".to_string()),
             is_openai_compatible: is_openai_compatible.unwrap_or(false),
             http_client,
             default_max_tokens,
+            tool_choice: ToolChoice::default(),
+            model_params: serde_json::Map::new(),
+            response_cache: None,
+            last_from_cache: std::sync::atomic::AtomicBool::new(false),
+            retry_policy: RetryPolicy::default(),
+            last_retries: std::sync::atomic::AtomicU32::new(0),
+            usage: UsageTracker::default(),
+        })
+    }
+
+    /// Answer identical requests from disk (off unless configured)
+    pub fn set_response_cache(&mut self, cache: Option<ResponseCache>) {
+        self.response_cache = cache;
+    }
+
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.response_cache.as_ref()
+    }
+
+    pub fn last_from_cache(&self) -> bool {
+        self.last_from_cache.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn set_last_from_cache(&self, from_cache: bool) {
+        self.last_from_cache.store(from_cache, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    pub fn last_retries(&self) -> u32 {
+        self.last_retries.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn usage(&self) -> &UsageTracker {
+        &self.usage
+    }
+
+    /// Send `payload` to the chat completions endpoint until it succeeds or the retry policy
+    /// gives up. Returns the successful response and the number of retries it took.
+    async fn send_with_retries(&self, payload: &serde_json::Value) -> Result<(reqwest::Response, u32), AgentError> {
+        let mut retries = 0;
+        self.last_retries.store(0, std::sync::atomic::Ordering::Relaxed);
+        loop {
+            let error = match self
+                .http_client
+                .post(format!("{}/chat/completions", self.base_url))
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(payload)
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => {
+                    self.last_retries.store(retries, std::sync::atomic::Ordering::Relaxed);
+                    return Ok((response, retries));
+                }
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    let retry_after = retry_after_header(&response);
+                    let error_text = response.text().await.unwrap_or_default();
+                    AgentError::from_http(status, retry_after.as_deref(), &error_text)
+                }
+                Err(e) => AgentError::from_transport(e),
+            };
+            match self.retry_policy.delay(retries + 1, &error) {
+                Some(delay) => {
+                    retries += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    self.last_retries.store(retries, std::sync::atomic::Ordering::Relaxed);
+                    return Err(error);
+                }
+            }
         }
     }
 
+    pub fn set_tool_choice(&mut self, tool_choice: ToolChoice) {
+        self.tool_choice = tool_choice;
+    }
+
+    /// Provider parameters merged into each request; `temperature` and `max_tokens` override the defaults
+    pub fn set_model_params(&mut self, params: serde_json::Map<String, serde_json::Value>) {
+        self.model_params = params;
+    }
+
     pub fn set_model(&mut self, model: &str) {
         self.model = model.to_string();
     }
@@ -83,16 +333,75 @@ impl GrokClient {
         &self.model
     }
 
+    /// Ping `<base_url>/models` and describe the result, used by `grok doctor`
+    pub async fn check_connection(&self) -> Result<String, String> {
+        let url = format!("{}/models", self.base_url);
+        let response = self
+            .http_client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .timeout(std::time::Duration::from_secs(15))
+            .send()
+            .await
+            .map_err(|e| format!("Could not reach {}: {}", self.base_url, e))?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200..=299 => Ok(format!("{} responded {}", url, status)),
+            401 | 403 => Err(format!("{} is reachable but rejected the API key ({})", self.base_url, status)),
+            404 => Err(format!(
+                "{} returned 404; the base URL path is probably wrong (missing /v1?)",
+                url
+            )),
+            _ => Err(format!("{} responded {}", url, status)),
+        }
+    }
+
+    /// Model ids from `<base_url>/models`, sorted. xAI and OpenAI-compatible servers both
+    /// answer with `{"data": [{"id": "…"}, …]}`.
+    pub async fn list_models(&self) -> Result<Vec<String>, AgentError> {
+        if self.api_key == "API_KEY_NOT_SET" {
+            return Err(AgentError::MissingApiKey);
+        }
+        let response = self
+            .http_client
+            .get(format!("{}/models", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .timeout(std::time::Duration::from_secs(15))
+            .send()
+            .await
+            .map_err(AgentError::from_transport)?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let retry_after = retry_after_header(&response);
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AgentError::from_http(status, retry_after.as_deref(), &error_text));
+        }
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| AgentError::InvalidResponse(format!("Could not read the model list: {}", e)))?;
+        let mut models: Vec<String> = body["data"]
+            .as_array()
+            .ok_or_else(|| AgentError::InvalidResponse("The model list has no `data` array".to_string()))?
+            .iter()
+            .filter_map(|model| model["id"].as_str().map(str::to_string))
+            .collect();
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
+
     pub async fn chat(
         &self,
         messages: Vec<GrokMessage>,
         tools: Option<Vec<GrokTool>>,
         model: Option<String>,
         search_options: Option<SearchOptions>,
-    ) -> Result<GrokResponse, Box<dyn std::error::Error>> {
+    ) -> Result<GrokResponse, AgentError> {
         // Check if we have a valid API key
         if self.api_key == "API_KEY_NOT_SET" {
-            return Err("No API key set. Please configure your API key.".into());
+            return Err(AgentError::MissingApiKey);
         }
 
         let request_payload = self.create_request_payload(
@@ -101,55 +410,27 @@ impl GrokClient {
             tools,
             search_options,
         );
+        let cached = self
+            .response_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&request_payload))
+            .and_then(|value| serde_json::from_value::<GrokResponse>(value).ok());
+        self.set_last_from_cache(cached.is_some());
+        if let Some(response) = cached {
+            self.last_retries.store(0, std::sync::atomic::Ordering::Relaxed);
+            return Ok(response);
+        }
 
-        // Retry logic with exponential backoff
-        let mut retries = 0;
-        let max_retries = 3;
-        
-        loop {
-            match self
-                .http_client
-                .post(format!("{}/chat/completions", self.base_url))
-                .header("Authorization", format!("Bearer {}", self.api_key))
-                .header("Content-Type", "application/json")
-                .json(&request_payload)
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    if !response.status().is_success() {
-                        let status = response.status();
-                        let error_text = response.text().await.unwrap_or_default();
-                        
-                        // Retry on server errors (5xx) and rate limits (429)
-                        if (status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS) && retries < max_retries {
-                            retries += 1;
-                            let wait_time = std::time::Duration::from_secs(2_u64.pow(retries as u32));
-                            eprintln!("⚠️  API error ({}). Retrying in {:?}...", status, wait_time);
-                            tokio::time::sleep(wait_time).await;
-                            continue;
-                        }
-                        
-                        return Err(format!("Grok API error ({}): {}", status, error_text).into());
-                    }
-
-                    let response: GrokResponse = response.json().await?;
-                    return Ok(response);
-                }
-                Err(e) => {
-                    // Retry on timeout and connection errors
-                    if (e.is_timeout() || e.is_connect()) && retries < max_retries {
-                        retries += 1;
-                        let wait_time = std::time::Duration::from_secs(2_u64.pow(retries as u32));
-                        eprintln!("⚠️  Connection error: {}. Retrying in {:?}...", e, wait_time);
-                        tokio::time::sleep(wait_time).await;
-                        continue;
-                    }
-                    
-                    return Err(Box::new(e));
-                }
-            }
+        let (response, retries) = self.send_with_retries(&request_payload).await?;
+        let body: serde_json::Value = response.json().await.map_err(AgentError::from_transport)?;
+        let mut parsed: GrokResponse =
+            serde_json::from_value(body.clone()).map_err(|e| AgentError::InvalidResponse(e.to_string()))?;
+        if let Some(cache) = &self.response_cache {
+            cache.put(&request_payload, &body);
         }
+        self.usage.record(payload_model(&request_payload), 1, parsed.usage.unwrap_or_default());
+        parsed.retries = retries;
+        Ok(parsed)
     }
 
     pub async fn chat_stream(
@@ -158,10 +439,10 @@ impl GrokClient {
         tools: Option<Vec<GrokTool>>,
         model: Option<String>,
         search_options: Option<SearchOptions>,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value, Box<dyn std::error::Error + Send>>> + Send>>, Box<dyn std::error::Error + Send>> {
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value, AgentError>> + Send>>, AgentError> {
         // Check if we have a valid API key
         if self.api_key == "API_KEY_NOT_SET" {
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "No API key set. Please configure your API key.")));
+            return Err(AgentError::MissingApiKey);
         }
 
         let request_payload = self.create_request_payload(
@@ -174,44 +455,47 @@ impl GrokClient {
         // Add stream parameter to payload
         let mut payload = request_payload;
         payload["stream"] = serde_json::Value::Bool(true);
+        // Without this, OpenAI-style streams leave out the final usage event
+        if payload.get("stream_options").is_none() {
+            payload["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
 
-        let api_key = self.api_key.clone();
-        let base_url = self.base_url.clone();
-        let http_client = self.http_client.clone();
+        // A cached stream is replayed event by event
+        let cached = self
+            .response_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&payload))
+            .and_then(|value| serde_json::from_value::<Vec<serde_json::Value>>(value).ok());
+        self.set_last_from_cache(cached.is_some());
+        if let Some(events) = cached {
+            self.last_retries.store(0, std::sync::atomic::Ordering::Relaxed);
+            return Ok(Box::pin(futures::stream::iter(events.into_iter().map(Ok))));
+        }
 
-        let stream = Box::pin(stream! {
-            let response = match http_client
-                .post(format!("{}/chat/completions", base_url))
-                .header("Authorization", format!("Bearer {}", api_key))
-                .header("Content-Type", "application/json")
-                .json(&payload)
-                .send()
-                .await
-            {
-                Ok(resp) => resp,
-                Err(e) => {
-                    yield Err(Box::new(e) as Box<dyn std::error::Error + Send>);
-                    return;
-                }
-            };
+        // Retries happen here, before the stream is handed out, so a request is only sent
+        // again while none of its reply has been delivered. A request that still fails is the
+        // stream's only item, where callers look for network errors.
+        let response = match self.send_with_retries(&payload).await {
+            Ok((response, _)) => response,
+            Err(e) => return Ok(Box::pin(futures::stream::once(async { Err(e) }))),
+        };
+        let cache = self.response_cache.clone();
+        let usage = self.usage.clone();
+        let model = payload_model(&payload).to_string();
+        usage.record(&model, 1, TokenUsage::default());
 
-            if !response.status().is_success() {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                let error_msg = format!("Grok API error ({}): {}", status, error_text);
-                yield Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, error_msg)) as Box<dyn std::error::Error + Send>);
-                return;
-            }
+        let stream = Box::pin(stream! {
 
             // Read the response body and parse SSE format
             let body = match response.text().await {
                 Ok(b) => b,
                 Err(e) => {
-                    yield Err(Box::new(e) as Box<dyn std::error::Error + Send>);
+                    yield Err(AgentError::StreamInterrupted(e.to_string()));
                     return;
                 }
             };
 
+            let mut events = Vec::new();
             for line in body.lines() {
                 let line = line.trim();
                 
@@ -231,6 +515,12 @@ impl GrokClient {
                     // Try to parse the data as JSON
                     match serde_json::from_str::<serde_json::Value>(data) {
                         Ok(json) => {
+                            if cache.is_some() {
+                                events.push(json.clone());
+                            }
+                            if let Some(tokens) = json.get("usage").and_then(|u| serde_json::from_value::<TokenUsage>(u.clone()).ok()) {
+                                usage.record(&model, 0, tokens);
+                            }
                             yield Ok(json);
                         }
                         Err(_) => {
@@ -239,6 +529,9 @@ impl GrokClient {
                     }
                 }
             }
+            if let Some(cache) = cache.filter(|_| !events.is_empty()) {
+                cache.put(&payload, &serde_json::Value::Array(events));
+            }
         });
 
         Ok(stream)
@@ -248,10 +541,10 @@ impl GrokClient {
         &self,
         query: &str,
         search_parameters: Option<SearchParameters>,
-    ) -> Result<GrokResponse, Box<dyn std::error::Error>> {
+    ) -> Result<GrokResponse, AgentError> {
         // Check if we have a valid API key
         if self.api_key == "API_KEY_NOT_SET" {
-            return Err("No API key set. Please configure your API key.".into());
+            return Err(AgentError::MissingApiKey);
         }
 
         let search_message = GrokMessage {
@@ -272,6 +565,20 @@ impl GrokClient {
         .await
     }
 
+    /// Map the tool choice to the request field. xAI and OpenAI-compatible servers share the
+    /// OpenAI shape; "required" is the OpenAI name for xAI's "any" semantics.
+    fn tool_choice_value(&self) -> serde_json::Value {
+        match &self.tool_choice {
+            ToolChoice::Auto => serde_json::json!("auto"),
+            ToolChoice::None => serde_json::json!("none"),
+            ToolChoice::Required => serde_json::json!("required"),
+            ToolChoice::Function(name) => serde_json::json!({
+                "type": "function",
+                "function": { "name": name }
+            }),
+        }
+    }
+
     fn create_request_payload(
         &self,
         model: &str,
@@ -286,10 +593,16 @@ impl GrokClient {
             "max_tokens": self.default_max_tokens,
         });
 
+        for (key, value) in &self.model_params {
+            if !MANAGED_PAYLOAD_KEYS.contains(&key.as_str()) {
+                payload[key.as_str()] = value.clone();
+            }
+        }
+
         if let Some(tool_list) = tools {
             if !tool_list.is_empty() {
                 payload["tools"] = serde_json::to_value(tool_list).unwrap();
-                payload["tool_choice"] = serde_json::Value::String("auto".to_string());
+                payload["tool_choice"] = self.tool_choice_value();
             }
         }
 
@@ -304,4 +617,175 @@ impl GrokClient {
 
         payload
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::types::GrokToolFunction;
+
+    #[test]
+    fn test_tool_choice_in_payload() {
+        let mut client = GrokClient::new("key", None, None, Some(true)).unwrap();
+        let tools = vec![GrokTool {
+            tool_type: "function".to_string(),
+            function: GrokToolFunction {
+                name: "bash".to_string(),
+                description: "Run a command".to_string(),
+                parameters: crate::types::GrokToolParameters {
+                    param_type: "object".to_string(),
+                    properties: std::collections::HashMap::new(),
+                    required: vec![],
+                },
+            },
+        }];
+
+        let payload = client.create_request_payload("m", vec![], Some(tools.clone()), None);
+        assert_eq!(payload["tool_choice"], "auto");
+
+        client.set_tool_choice(ToolChoice::parse("bash").unwrap());
+        let payload = client.create_request_payload("m", vec![], Some(tools), None);
+        assert_eq!(payload["tool_choice"]["function"]["name"], "bash");
+
+        assert_eq!(ToolChoice::parse("required").unwrap(), ToolChoice::Required);
+    }
+
+    #[test]
+    fn test_model_params_merge_into_payload() {
+        let mut client = GrokClient::new("key", None, None, Some(true)).unwrap();
+        let params = parse_model_params(r#"{"top_p": 0.9, "seed": 7, "temperature": 0.1, "model": "other"}"#).unwrap();
+        assert_eq!(model_params_warnings(&params).len(), 1);
+        client.set_model_params(params);
+
+        let payload = client.create_request_payload("m", vec![], None, None);
+        assert_eq!(payload["top_p"], 0.9);
+        assert_eq!(payload["seed"], 7);
+        assert_eq!(payload["temperature"], 0.1);
+        assert_eq!(payload["model"], "m");
+
+        assert!(parse_model_params("[1, 2]").is_err());
+        assert!(parse_model_params("{top_p").is_err());
+    }
+
+    #[test]
+    fn test_base_url_normalization() {
+        assert_eq!(normalize_base_url(" https://api.x.ai/v1/ ").unwrap(), "https://api.x.ai/v1");
+        assert!(normalize_base_url("api.x.ai/v1").is_err());
+        assert!(normalize_base_url("ftp://api.x.ai/v1").is_err());
+
+        assert!(base_url_warnings("https://api.x.ai/v1").is_empty());
+        assert!(base_url_warnings("http://localhost:11434/v1").is_empty());
+        assert_eq!(base_url_warnings("https://api.openai.com").len(), 1);
+        assert_eq!(base_url_warnings("http://example.com/v1").len(), 1);
+    }
+
+    /// Serve the canned HTTP responses in order, one per connection, and count the requests
+    pub(crate) async fn mock_server(responses: Vec<String>) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                // Read the whole request so the client sees the response, not a reset
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text[..end]
+                            .lines()
+                            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+        (base_url, hits)
+    }
+
+    pub(crate) fn http_response(status: &str, headers: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}", status, body.len(), headers, body)
+    }
+
+    #[tokio::test]
+    async fn test_list_models_reads_the_model_ids() {
+        let (base_url, _) = mock_server(vec![
+            http_response("200 OK", "", r#"{"object": "list", "data": [{"id": "grok-4", "object": "model"}, {"id": "grok-3-mini"}]}"#),
+            http_response("404 Not Found", "", r#"{"error": {"message": "no such route"}}"#),
+        ])
+        .await;
+        let client = GrokClient::new("key", None, Some(base_url), Some(true)).unwrap();
+
+        assert_eq!(client.list_models().await.unwrap(), vec!["grok-3-mini", "grok-4"]);
+        assert!(client.list_models().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried_until_the_request_goes_through() {
+        let ok = r#"{"choices": [{"message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}]}"#;
+        let failing_twice = || {
+            vec![
+                http_response("429 Too Many Requests", "Retry-After: 0\r\n", r#"{"error": {"message": "slow down"}}"#),
+                http_response("502 Bad Gateway", "", "bad gateway"),
+            ]
+        };
+        let fast = RetryPolicy { base_delay: std::time::Duration::from_millis(10), ..RetryPolicy::default() };
+
+        let mut responses = failing_twice();
+        responses.push(http_response("200 OK", "Content-Type: application/json\r\n", ok));
+        let (base_url, hits) = mock_server(responses).await;
+        let mut client = GrokClient::new("key", None, Some(base_url), Some(true)).unwrap();
+        client.set_retry_policy(fast.clone());
+        let response = client.chat(vec![], None, None, None).await.unwrap();
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("hi"));
+        assert_eq!((response.retries, client.last_retries()), (2, 2));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // A stream is retried the same way before any of it is handed out
+        let mut responses = failing_twice();
+        responses.push(http_response("200 OK", "Content-Type: text/event-stream\r\n", "data: {\"id\": 1}\n\ndata: [DONE]\n\n"));
+        let (base_url, _) = mock_server(responses).await;
+        let mut client = GrokClient::new("key", None, Some(base_url), Some(true)).unwrap();
+        client.set_retry_policy(fast.clone());
+        let events: Vec<_> = futures::StreamExt::collect(client.chat_stream(vec![], None, None, None).await.unwrap()).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].as_ref().unwrap()["id"], 1);
+        assert_eq!(client.last_retries(), 2);
+
+        // Out of retries, the last error is returned; errors that do not pass are not retried
+        let (base_url, hits) = mock_server(failing_twice()).await;
+        let mut client = GrokClient::new("key", None, Some(base_url), Some(true)).unwrap();
+        client.set_retry_policy(RetryPolicy { max_retries: 1, ..fast.clone() });
+        assert!(matches!(client.chat(vec![], None, None, None).await, Err(AgentError::ServerError(_))));
+        assert_eq!((hits.load(std::sync::atomic::Ordering::SeqCst), client.last_retries()), (2, 1));
+        let (base_url, hits) = mock_server(vec![http_response("401 Unauthorized", "", "bad key")]).await;
+        let client = GrokClient::new("key", None, Some(base_url), Some(true)).unwrap();
+        assert!(matches!(client.chat(vec![], None, None, None).await, Err(AgentError::Authentication(_))));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // Backoff doubles with jitter, and a Retry-After longer than the policy allows is not waited out
+        let policy = RetryPolicy::default();
+        let server = AgentError::from_response(503, "busy");
+        for retry in 1..=3 {
+            let delay = policy.delay(retry, &server).unwrap();
+            let full = std::time::Duration::from_secs(2u64.pow(retry));
+            assert!(delay <= full && delay >= full * 3 / 4, "{:?}", delay);
+        }
+        assert_eq!(policy.delay(4, &server), None);
+        assert_eq!(policy.delay(1, &AgentError::from_http(429, Some("60"), "")), None);
+    }
+}
@@ -0,0 +1,320 @@
+/// Longest raw body kept when a provider error is not JSON
+const RAW_BODY_CHARS: usize = 300;
+
+/// Longest wait between retries while the network is down
+const MAX_OFFLINE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A failed turn: a request error, classified from the HTTP status and the provider's error
+/// body, or a tool call that could not run. Callers match on the kind of error instead of
+/// the message text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentError {
+    MissingApiKey,
+    /// The request never got an answer: no connection, a timeout, or the connection dropped
+    /// before the response arrived. Unlike the other variants the provider never saw or
+    /// rejected it, so sending it again once the network is back is safe.
+    Network(String),
+    /// The provider accepted the request but the streamed response broke off before its end
+    StreamInterrupted(String),
+    /// A 2xx response whose body is not a completion
+    InvalidResponse(String),
+    /// A tool call failed in a way that ended the turn, rather than with a failed result the
+    /// model could read
+    ToolExecution { tool: String, source: String },
+    Authentication(ProviderError),
+    ModelNotFound(ProviderError),
+    ContextLengthExceeded(ProviderError),
+    RateLimited(ProviderError),
+    QuotaExceeded(ProviderError),
+    InvalidRequest(ProviderError),
+    ServerError(ProviderError),
+    Other(ProviderError),
+}
+
+/// The fields providers put in a non-2xx response body
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProviderError {
+    pub status: u16,
+    pub message: String,
+    /// `type` in OpenAI-style bodies, `status` in Google's
+    pub error_type: Option<String>,
+    pub code: Option<String>,
+    /// From the `Retry-After` header, when the provider sent one
+    pub retry_after: Option<std::time::Duration>,
+}
+
+fn as_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+impl ProviderError {
+    /// Read the error body. Handles the OpenAI shape `{"error": {"message", "type", "code"}}`,
+    /// xAI's `{"code", "error": "<message>"}`, Google's `{"error": {"message", "status"}}`,
+    /// bare `{"message"}` / `{"detail"}` bodies, and plain text.
+    pub fn parse(status: u16, body: &str) -> Self {
+        let mut error = ProviderError { status, ..Default::default() };
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+            let text = body.trim();
+            error.message = if text.chars().count() > RAW_BODY_CHARS {
+                format!("{}…", text.chars().take(RAW_BODY_CHARS).collect::<String>())
+            } else {
+                text.to_string()
+            };
+            return error;
+        };
+
+        let detail = match json.get("error") {
+            Some(inner @ serde_json::Value::Object(_)) => inner,
+            _ => &json,
+        };
+        error.message = ["message", "error", "detail"]
+            .iter()
+            .find_map(|key| detail.get(*key).and_then(as_text))
+            .unwrap_or_default();
+        error.error_type = ["type", "status"].iter().find_map(|key| detail.get(*key).and_then(as_text));
+        error.code = detail.get("code").and_then(as_text);
+        error
+    }
+
+    /// `Retry-After` is either a number of seconds or an HTTP date
+    pub fn parse_retry_after(value: &str) -> Option<std::time::Duration> {
+        let value = value.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(std::time::Duration::from_secs(seconds));
+        }
+        let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        Some((at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+    }
+
+    /// Type, code and message in one lowercase string, for classification
+    fn haystack(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.error_type.as_deref().unwrap_or_default(),
+            self.code.as_deref().unwrap_or_default(),
+            self.message
+        )
+        .to_lowercase()
+    }
+}
+
+impl AgentError {
+    /// `Network` for transport failures, `InvalidResponse` for a body that could not be decoded
+    pub fn from_transport(error: reqwest::Error) -> Self {
+        let detail = match std::error::Error::source(&error) {
+            Some(source) => format!("{}: {}", error, source),
+            None => error.to_string(),
+        };
+        if error.is_connect() || error.is_timeout() || error.is_request() || error.is_body() {
+            AgentError::Network(detail)
+        } else {
+            AgentError::InvalidResponse(detail)
+        }
+    }
+
+    /// Whether nothing of the response arrived, so the same request is worth sending again
+    /// until the connection is back
+    pub fn is_network(&self) -> bool {
+        matches!(self, AgentError::Network(_) | AgentError::StreamInterrupted(_))
+    }
+
+    /// How long a rate-limited request should wait before it is sent again, when the
+    /// provider said
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            AgentError::RateLimited(error) => error.retry_after,
+            _ => None,
+        }
+    }
+
+    /// Wait before offline retry number `attempt` (from 1): 2s, 4s, 8s, … up to 30s
+    pub fn offline_retry_delay(attempt: u32) -> std::time::Duration {
+        std::time::Duration::from_secs(2u64.saturating_pow(attempt.clamp(1, 6))).min(MAX_OFFLINE_RETRY_DELAY)
+    }
+
+    /// Classify a non-2xx response from its status, `Retry-After` header and body
+    pub fn from_http(status: u16, retry_after: Option<&str>, body: &str) -> Self {
+        let mut error = Self::from_response(status, body);
+        if let AgentError::RateLimited(provider) = &mut error {
+            provider.retry_after = retry_after.and_then(ProviderError::parse_retry_after);
+        }
+        error
+    }
+
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let error = ProviderError::parse(status, body);
+        let text = error.haystack();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| text.contains(needle));
+
+        if mentions(&["context_length", "context length", "maximum context", "too many tokens", "prompt is too long"]) {
+            AgentError::ContextLengthExceeded(error)
+        } else if mentions(&["insufficient_quota", "quota", "billing", "credits"]) {
+            AgentError::QuotaExceeded(error)
+        } else if status == 429 || mentions(&["rate_limit", "rate limit"]) {
+            AgentError::RateLimited(error)
+        } else if status == 401 || status == 403 || mentions(&["invalid_api_key", "incorrect api key", "invalid api key"]) {
+            AgentError::Authentication(error)
+        } else if mentions(&["model_not_found", "model not found", "does not exist", "unknown model"])
+            || (status == 404 && text.contains("model"))
+        {
+            AgentError::ModelNotFound(error)
+        } else if status >= 500 {
+            AgentError::ServerError(error)
+        } else if status == 400 || status == 422 {
+            AgentError::InvalidRequest(error)
+        } else {
+            AgentError::Other(error)
+        }
+    }
+
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            AgentError::MissingApiKey
+            | AgentError::Network(_)
+            | AgentError::StreamInterrupted(_)
+            | AgentError::InvalidResponse(_)
+            | AgentError::ToolExecution { .. } => None,
+            AgentError::Authentication(e)
+            | AgentError::ModelNotFound(e)
+            | AgentError::ContextLengthExceeded(e)
+            | AgentError::RateLimited(e)
+            | AgentError::QuotaExceeded(e)
+            | AgentError::InvalidRequest(e)
+            | AgentError::ServerError(e)
+            | AgentError::Other(e) => Some(e),
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            AgentError::MissingApiKey => "No API key set",
+            AgentError::Network(_) => "Network error",
+            AgentError::StreamInterrupted(_) => "The response broke off",
+            AgentError::InvalidResponse(_) => "The provider sent a response that could not be read",
+            AgentError::ToolExecution { .. } => "A tool call failed",
+            AgentError::Authentication(_) => "The API key was rejected",
+            AgentError::ModelNotFound(_) => "Model not found",
+            AgentError::ContextLengthExceeded(_) => "Context length exceeded",
+            AgentError::RateLimited(_) => "Rate limited",
+            AgentError::QuotaExceeded(_) => "Quota exceeded",
+            AgentError::InvalidRequest(_) => "The provider rejected the request",
+            AgentError::ServerError(_) => "The provider had a server error",
+            AgentError::Other(_) => "Request failed",
+        }
+    }
+
+    /// What the user can do about it
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            AgentError::MissingApiKey => Some("Set GROK_API_KEY, pass --api-key, or add api_key to ~/.grok/user-settings.json."),
+            AgentError::Network(_) | AgentError::StreamInterrupted(_) => {
+                Some("Check the connection and the base URL; `grok doctor` tests them.")
+            }
+            AgentError::Authentication(_) => Some("Check the API key and that it belongs to the provider at the base URL; `grok doctor` tests it."),
+            AgentError::ModelNotFound(_) => Some("Check --model / GROK_MODEL; `grok doctor` shows the model in use."),
+            AgentError::ContextLengthExceeded(_) => Some("Shorten the conversation with /clear or send fewer turns with /window <n>."),
+            AgentError::RateLimited(_) => Some("Wait a moment and try again."),
+            AgentError::QuotaExceeded(_) => Some("Check the plan and billing for this API key."),
+            AgentError::ServerError(_) => Some("This is usually temporary; try again shortly."),
+            AgentError::InvalidResponse(_) => Some("Check that the base URL points at an OpenAI-compatible API."),
+            AgentError::InvalidRequest(_) | AgentError::Other(_) | AgentError::ToolExecution { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.summary())?;
+        match self {
+            AgentError::Network(detail) | AgentError::StreamInterrupted(detail) | AgentError::InvalidResponse(detail) => {
+                write!(f, ": {}", detail.trim_end_matches('.'))?;
+            }
+            AgentError::ToolExecution { tool, source } => write!(f, ": {}: {}", tool, source.trim_end_matches('.'))?,
+            _ => {}
+        }
+        if let Some(error) = self.provider_error() {
+            write!(f, " ({})", error.status)?;
+            if !error.message.is_empty() {
+                write!(f, ": {}", error.message.trim_end_matches('.'))?;
+            }
+        }
+        if let Some(suggestion) = self.suggestion() {
+            write!(f, ". {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_error_bodies_are_classified() {
+        let openai = AgentError::from_response(
+            400,
+            r#"{"error": {"message": "This model's maximum context length is 8192 tokens.", "type": "invalid_request_error", "code": "context_length_exceeded"}}"#,
+        );
+        assert!(matches!(openai, AgentError::ContextLengthExceeded(_)));
+        let error = openai.provider_error().unwrap();
+        assert_eq!(error.code.as_deref(), Some("context_length_exceeded"));
+        assert_eq!(error.error_type.as_deref(), Some("invalid_request_error"));
+        assert!(openai.to_string().starts_with("Context length exceeded (400): This model's maximum"));
+        assert!(openai.to_string().ends_with("/window <n>."));
+
+        let xai = AgentError::from_response(404, r#"{"code": "Some requested entity was not found", "error": "The model grok-9 does not exist or your team does not have access to it."}"#);
+        assert!(matches!(xai, AgentError::ModelNotFound(_)));
+        assert!(xai.to_string().contains("grok-9 does not exist"));
+
+        let google = AgentError::from_response(429, r#"{"error": {"code": 429, "message": "Resource has been exhausted", "status": "RESOURCE_EXHAUSTED"}}"#);
+        assert!(matches!(google, AgentError::RateLimited(_)));
+        assert_eq!(google.provider_error().unwrap().code.as_deref(), Some("429"));
+
+        assert!(matches!(AgentError::from_response(401, r#"{"detail": "Unauthorized"}"#), AgentError::Authentication(_)));
+        let html = AgentError::from_response(502, "<html>Bad gateway</html>");
+        assert!(matches!(html, AgentError::ServerError(_)));
+        assert_eq!(html.provider_error().unwrap().message, "<html>Bad gateway</html>");
+        assert_eq!(AgentError::MissingApiKey.to_string(), "No API key set. Set GROK_API_KEY, pass --api-key, or add api_key to ~/.grok/user-settings.json.");
+    }
+
+    #[test]
+    fn test_rate_limits_carry_the_retry_after_delay() {
+        let limited = AgentError::from_http(429, Some("7"), r#"{"error": {"message": "Slow down", "type": "rate_limit_error"}}"#);
+        assert!(matches!(limited, AgentError::RateLimited(_)));
+        assert_eq!(limited.retry_after(), Some(std::time::Duration::from_secs(7)));
+        assert_eq!(AgentError::from_http(429, None, "").retry_after(), None);
+        // Only a rate limit says when to come back
+        assert_eq!(AgentError::from_http(503, Some("7"), "busy").retry_after(), None);
+
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let wait = ProviderError::parse_retry_after(&date).unwrap().as_secs();
+        assert!((85..=90).contains(&wait), "{}", wait);
+        assert_eq!(ProviderError::parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(std::time::Duration::ZERO));
+        assert_eq!(ProviderError::parse_retry_after("soon"), None);
+
+        let tool = AgentError::ToolExecution { tool: "bash".to_string(), source: "spawn failed".to_string() };
+        assert_eq!(tool.to_string(), "A tool call failed: bash: spawn failed");
+        assert!(AgentError::StreamInterrupted("connection reset".to_string()).is_network());
+    }
+
+    #[tokio::test]
+    async fn test_only_transport_failures_are_network_errors() {
+        // Nothing listens on port 9 of localhost, so the connection is refused
+        let refused = reqwest::Client::new().get("http://127.0.0.1:9/").send().await.unwrap_err();
+        let network = AgentError::from_transport(refused);
+        assert!(network.is_network());
+        assert!(network.to_string().starts_with("Network error: "));
+
+        let provider = AgentError::from_response(503, "overloaded");
+        assert!(!provider.is_network());
+
+        let delays: Vec<u64> = (1..=7).map(|n| AgentError::offline_retry_delay(n).as_secs()).collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 30, 30, 30]);
+    }
+}
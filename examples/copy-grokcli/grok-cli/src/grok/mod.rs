@@ -1 +1,4 @@
-pub mod client;
\ No newline at end of file
+pub mod client;
+pub mod error;
+pub mod response_cache;
+pub mod usage;
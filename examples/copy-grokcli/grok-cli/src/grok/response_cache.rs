@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Size limit used unless `max_size_mb` is set
+pub const DEFAULT_CACHE_MAX_MB: u64 = 100;
+
+/// `response_cache` in user settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Least recently used responses are evicted above this size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+    /// Defaults to `~/.grok/cache/responses`; `--cache-dir` overrides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// Entry count and size on disk, plus lookups since startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub total_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn describe(&self) -> String {
+        let lookups = self.hits + self.misses;
+        let hit_rate = if lookups == 0 {
+            "no lookups yet".to_string()
+        } else {
+            format!("{:.0}% of {} lookups", self.hits as f64 * 100.0 / lookups as f64, lookups)
+        };
+        format!(
+            "Response cache: {} entries, {:.1} MB, hit rate {}",
+            self.entries,
+            self.total_bytes as f64 / (1024.0 * 1024.0),
+            hit_rate
+        )
+    }
+}
+
+/// Responses stored on disk by a hash of the full request payload. An identical request,
+/// same model, messages, tools and parameters, is answered from disk instead of the API.
+/// Clones share the hit counters.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+/// Stable across runs and Rust versions, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn cache_key(payload: &serde_json::Value) -> String {
+    let bytes = payload.to_string().into_bytes();
+    format!("{:016x}-{:x}", fnv1a(&bytes), bytes.len())
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, max_size_mb: Option<u64>) -> Self {
+        Self {
+            dir,
+            max_bytes: max_size_mb.unwrap_or(DEFAULT_CACHE_MAX_MB) * 1024 * 1024,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// `~/.grok/cache/responses`
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".grok").join("cache").join("responses"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path_for(&self, payload: &serde_json::Value) -> PathBuf {
+        self.dir.join(format!("{}.json", cache_key(payload)))
+    }
+
+    /// The stored response for `payload`; a hit counts as a use for LRU eviction
+    pub fn get(&self, payload: &serde_json::Value) -> Option<serde_json::Value> {
+        let path = self.path_for(payload);
+        let value = std::fs::read(&path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        match value {
+            Some(value) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                if let Ok(file) = std::fs::File::options().append(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Some(value)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Store a response, then evict the least recently used entries above the size limit.
+    /// Failing to write only means the next identical request goes to the API again.
+    pub fn put(&self, payload: &serde_json::Value, response: &serde_json::Value) {
+        if std::fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        let _ = std::fs::write(self.path_for(payload), response.to_string());
+        self.evict();
+    }
+
+    /// Cache files with their size and last use, oldest first
+    fn files(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                Some((entry.path(), meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+            })
+            .collect();
+        files.sort_by_key(|(_, _, modified)| *modified);
+        files
+    }
+
+    fn evict(&self) {
+        let files = self.files();
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        for (path, size, _) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let files = self.files();
+        CacheStats {
+            entries: files.len(),
+            total_bytes: files.iter().map(|(_, size, _)| size).sum(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Delete every cached response and return how many there were
+    pub fn clear(&self) -> usize {
+        self.files()
+            .into_iter()
+            .filter(|(path, _, _)| std::fs::remove_file(path).is_ok())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cache_hits_evicts_least_recently_used_and_clears() {
+        let dir = std::env::temp_dir().join(format!("grok-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut cache = ResponseCache::new(dir.clone(), None);
+        let request = |n: u32| serde_json::json!({ "model": "m", "messages": [{ "role": "user", "content": n }] });
+        let response = serde_json::json!({ "body": "x".repeat(400) });
+
+        assert_eq!(cache.get(&request(1)), None);
+        cache.put(&request(1), &response);
+        assert_eq!(cache.get(&request(1)), Some(response.clone()));
+        assert_eq!(cache.get(&request(2)), None);
+
+        // Room for two entries: using 1 again makes 2 the oldest, so it goes when 3 arrives
+        cache.max_bytes = 1000;
+        cache.put(&request(2), &response);
+        std::thread::sleep(Duration::from_millis(20));
+        cache.get(&request(1));
+        std::thread::sleep(Duration::from_millis(20));
+        cache.put(&request(3), &response);
+        assert!(cache.get(&request(1)).is_some());
+        assert!(cache.get(&request(2)).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 3, 3));
+        assert!(stats.describe().contains("hit rate 50% of 6 lookups"), "{}", stats.describe());
+        assert_eq!(cache.clear(), 2);
+        assert_eq!(cache.stats().entries, 0);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
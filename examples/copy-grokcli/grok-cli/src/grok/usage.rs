@@ -0,0 +1,210 @@
+use crate::types::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Dollars per million tokens for one model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.completion_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// List prices for xAI models, matched as substrings of the model name, most specific first.
+/// `model_prices` in user settings is tried before these.
+const BUILTIN_PRICES: &[(&str, f64, f64)] = &[
+    ("grok-code-fast", 0.20, 1.50),
+    ("grok-4-fast", 0.20, 0.50),
+    ("grok-4", 3.00, 15.00),
+    ("grok-3-mini", 0.30, 0.50),
+    ("grok-3", 3.00, 15.00),
+    ("grok-2", 2.00, 10.00),
+];
+
+/// Requests and tokens for one model
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelUsage {
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl ModelUsage {
+    fn tokens(&self) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            total_tokens: self.prompt_tokens + self.completion_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct UsageState {
+    models: BTreeMap<String, ModelUsage>,
+    prices: BTreeMap<String, ModelPrice>,
+}
+
+/// Tokens the session's requests used, by model, from the `usage` the provider reports.
+/// Clones share the totals, so a request sent from a copy of the agent still counts.
+/// Answers from the response cache cost nothing and are not counted.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    state: Arc<Mutex<UsageState>>,
+}
+
+/// What `GrokAgent::get_usage` reports
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageSummary {
+    pub total: ModelUsage,
+    /// Per model, with its estimated cost when it has a price
+    pub models: Vec<(String, ModelUsage, Option<f64>)>,
+    /// Estimated dollars for the models that have a price; `None` when none has one
+    pub cost_estimate: Option<f64>,
+}
+
+impl UsageTracker {
+    /// Prices from user settings, keyed by model name or a part of it
+    pub fn set_prices(&self, prices: BTreeMap<String, ModelPrice>) {
+        self.state.lock().unwrap().prices = prices;
+    }
+
+    /// Add `requests` requests to `model` and the tokens they used
+    pub fn record(&self, model: &str, requests: u32, usage: TokenUsage) {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.models.entry(model.to_string()).or_default();
+        entry.requests += requests;
+        entry.prompt_tokens += usage.prompt_tokens;
+        entry.completion_tokens += usage.completion_tokens;
+    }
+
+    pub fn summary(&self) -> UsageSummary {
+        let state = self.state.lock().unwrap();
+        let mut summary = UsageSummary::default();
+        for (model, usage) in &state.models {
+            summary.total.requests += usage.requests;
+            summary.total.prompt_tokens += usage.prompt_tokens;
+            summary.total.completion_tokens += usage.completion_tokens;
+            let cost = price_for(&state.prices, model).map(|price| price.cost(&usage.tokens()));
+            if let Some(cost) = cost {
+                *summary.cost_estimate.get_or_insert(0.0) += cost;
+            }
+            summary.models.push((model.clone(), *usage, cost));
+        }
+        summary
+    }
+}
+
+/// An exact entry of the user's table, then the first of their entries the model name
+/// contains, then the built-in prices
+fn price_for(prices: &BTreeMap<String, ModelPrice>, model: &str) -> Option<ModelPrice> {
+    if let Some(price) = prices.get(model) {
+        return Some(*price);
+    }
+    prices
+        .iter()
+        .find(|(pattern, _)| model.contains(pattern.as_str()))
+        .map(|(_, price)| *price)
+        .or_else(|| {
+            BUILTIN_PRICES
+                .iter()
+                .find(|(pattern, _, _)| model.contains(pattern))
+                .map(|(_, input, output)| ModelPrice { input: *input, output: *output })
+        })
+}
+
+fn usage_line(usage: &ModelUsage) -> String {
+    format!(
+        "{} request{}, {} prompt + {} completion tokens",
+        usage.requests,
+        if usage.requests == 1 { "" } else { "s" },
+        usage.prompt_tokens,
+        usage.completion_tokens
+    )
+}
+
+impl UsageSummary {
+    /// Lines for `/status`
+    pub fn describe(&self) -> String {
+        if self.models.is_empty() {
+            return "Usage: no requests yet".to_string();
+        }
+        let cost = match self.cost_estimate {
+            Some(cost) => format!(", est. ${:.4}", cost),
+            None => String::new(),
+        };
+        let mut text = format!("Usage: {}{}", usage_line(&self.total), cost);
+        if self.models.len() > 1 || self.cost_estimate.is_none() {
+            for (model, usage, cost) in &self.models {
+                let cost = match cost {
+                    Some(cost) => format!("${:.4}", cost),
+                    None => "no price (set model_prices)".to_string(),
+                };
+                text.push_str(&format!("\n  {}: {}, {}", model, usage_line(usage), cost));
+            }
+        }
+        text
+    }
+
+    /// The `usage` object of headless JSON output
+    pub fn to_json(&self) -> serde_json::Value {
+        let counts = |usage: &ModelUsage| {
+            serde_json::json!({
+                "requests": usage.requests,
+                "prompt_tokens": usage.prompt_tokens,
+                "completion_tokens": usage.completion_tokens,
+            })
+        };
+        let mut value = counts(&self.total);
+        value["cost_estimate"] = serde_json::json!(self.cost_estimate);
+        value["models"] = self
+            .models
+            .iter()
+            .map(|(model, usage, cost)| {
+                let mut entry = counts(usage);
+                entry["model"] = serde_json::json!(model);
+                entry["cost_estimate"] = serde_json::json!(cost);
+                entry
+            })
+            .collect();
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_adds_up_per_model_and_prices_user_models_first() {
+        let tracker = UsageTracker::default();
+        let shared = tracker.clone();
+        let usage = |prompt, completion| TokenUsage { prompt_tokens: prompt, completion_tokens: completion, total_tokens: prompt + completion };
+        tracker.record("grok-code-fast-1", 1, usage(1_000_000, 0));
+        shared.record("grok-code-fast-1", 1, usage(0, 200_000));
+        tracker.record("local-llama", 1, usage(500, 50));
+
+        let summary = tracker.summary();
+        assert_eq!(summary.total, ModelUsage { requests: 3, prompt_tokens: 1_000_500, completion_tokens: 200_050 });
+        // $0.20 per million prompt tokens and $1.50 per million completion tokens
+        assert!((summary.cost_estimate.unwrap() - 0.50).abs() < 1e-9);
+        assert_eq!(summary.models[1].2, None);
+        assert!(summary.describe().contains("local-llama: 1 request, 500 prompt + 50 completion tokens, no price"));
+
+        tracker.set_prices(BTreeMap::from([
+            ("llama".to_string(), ModelPrice { input: 1.0, output: 10.0 }),
+            ("grok-code-fast-1".to_string(), ModelPrice { input: 0.0, output: 0.0 }),
+        ]));
+        let summary = shared.summary();
+        assert!((summary.models[1].2.unwrap() - 0.001).abs() < 1e-9);
+        assert!((summary.cost_estimate.unwrap() - 0.001).abs() < 1e-9);
+        assert!(summary.describe().starts_with("Usage: 3 requests, 1000500 prompt + 200050 completion tokens, est. $0.0010"));
+        assert_eq!(UsageTracker::default().summary().describe(), "Usage: no requests yet");
+    }
+}
@@ -5,6 +5,7 @@ mod types;
 mod utils;
 mod mcp;
 mod commands;
+mod prompts;
 mod ui;
 
 use clap::{Parser, Subcommand};
@@ -17,6 +18,31 @@ enum Commands {
         #[command(subcommand)]
         command: crate::commands::mcp::McpCommand,
     },
+    /// Check the resolved configuration and ping the API
+    Doctor,
+    /// Run one tool call directly, without the model, and print its result
+    ReplayTool {
+        /// Tool name, e.g. str_replace_editor
+        name: String,
+        /// Tool arguments as a JSON object
+        arguments: String,
+    },
+    /// List, show, or resume saved sessions
+    History {
+        #[command(subcommand)]
+        command: Option<crate::commands::history::HistoryCommand>,
+    },
+}
+
+/// What a headless run prints
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum OutputFormat {
+    /// Only the final answer on stdout; tool results and warnings go to stderr
+    Text,
+    /// One JSON document with the entries, token usage and exit reason
+    Json,
+    /// One JSON entry per line
+    Jsonl,
 }
 
 #[derive(Parser)]
@@ -27,6 +53,18 @@ struct CliArgs {
     #[arg(value_parser)]
     message: Vec<String>,
 
+    /// Use an alternate user settings file (or set GROK_CONFIG env var)
+    #[arg(long = "config", value_name = "PATH", conflicts_with = "no_config")]
+    config: Option<std::path::PathBuf>,
+
+    /// Ignore settings files and run purely from flags and environment
+    #[arg(long = "no-config")]
+    no_config: bool,
+
+    /// Print extra diagnostics at startup, such as a health check of each MCP server
+    #[arg(short = 'v', long = "verbose")]
+    verbose: bool,
+
     /// Set working directory
     #[arg(short = 'd', long = "directory", default_value = ".")]
     directory: String,
@@ -39,17 +77,83 @@ struct CliArgs {
     #[arg(short = 'u', long = "base-url")]
     base_url: Option<String>,
 
-    /// AI model to use
+    /// AI model to use (or set GROK_MODEL env var)
     #[arg(short = 'm', long = "model")]
     model: Option<String>,
 
+    /// API provider, e.g. xai or openai (or set GROK_PROVIDER env var)
+    #[arg(long = "provider")]
+    provider: Option<String>,
+
+    /// Use the OpenAI-compatible request format (or set GROK_OPENAI_COMPATIBLE env var)
+    #[arg(long = "openai-compatible")]
+    openai_compatible: Option<bool>,
+
     /// Process a single prompt and exit (headless mode)
     #[arg(long = "prompt")]
     prompt: Option<String>,
 
-    /// Maximum number of tool execution rounds (default: 400)
-    #[arg(long = "max-tool-rounds", default_value = "400")]
-    max_tool_rounds: u32,
+    /// Extra provider parameters as a JSON object, merged into each request body
+    #[arg(long = "model-params", value_name = "JSON")]
+    model_params: Option<String>,
+
+    /// In headless mode, answer the model's ask_user questions on stdin instead of letting it
+    /// proceed with a default
+    #[arg(long = "interactive-asks")]
+    interactive_asks: bool,
+
+    /// In headless mode, stream the assistant's text to this file as it arrives ("-" for stdout)
+    /// instead of printing JSON entries at the end
+    #[arg(long = "output-file", value_name = "PATH", requires = "prompt")]
+    output_file: Option<String>,
+
+    /// What headless mode prints when the turn is over
+    #[arg(long = "output-format", value_enum, default_value = "jsonl", requires = "prompt", conflicts_with = "output_file")]
+    output_format: OutputFormat,
+
+    /// In headless mode, write the transcript here once the prompt is done: HTML for a .html
+    /// path, Markdown otherwise
+    #[arg(long = "export", value_name = "PATH", requires = "prompt")]
+    export: Option<std::path::PathBuf>,
+
+    /// With --output-file, log each tool call and its outcome to stderr
+    #[arg(long = "log-tools", requires = "output_file")]
+    log_tools: bool,
+
+    /// Run file edits and bash commands without asking for approval (or set auto_accept in
+    /// user settings). Headless runs reject them otherwise, unless --interactive-asks is given
+    #[arg(long = "yolo")]
+    yolo: bool,
+
+    /// Block bash commands that match a dangerous rule instead of asking for a typed YES
+    #[arg(long = "sandbox")]
+    sandbox: bool,
+
+    /// Run bash in the current directory and refuse commands that use paths outside it or `cd` out of it
+    #[arg(long = "confine-bash")]
+    confine_bash: bool,
+
+    /// Exit with an error after this many seconds without input or model progress (0 disables).
+    /// Defaults to 600 when stdin is not a terminal, otherwise off
+    #[arg(long = "idle-timeout", value_name = "SECS")]
+    idle_timeout: Option<u64>,
+
+    /// Maximum number of tool execution rounds (or set GROK_MAX_TOOL_ROUNDS). Defaults to the
+    /// per-model table, which `tool_rounds` in user settings extends
+    #[arg(long = "max-tool-rounds", value_name = "N")]
+    max_tool_rounds: Option<u32>,
+
+    /// Maximum tool calls run from a single assistant message; the rest are deferred to the next round (0: no limit)
+    #[arg(long = "max-tool-calls-per-message", value_name = "N")]
+    max_tool_calls_per_message: Option<usize>,
+
+    /// Enable the response cache and keep it in this directory instead of ~/.grok/cache/responses
+    #[arg(long = "cache-dir", value_name = "PATH")]
+    cache_dir: Option<std::path::PathBuf>,
+
+    /// Continue a saved session in the interactive UI, like `grok history resume <id>`
+    #[arg(long = "resume", value_name = "ID", conflicts_with = "prompt")]
+    resume: Option<String>,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -57,17 +161,48 @@ struct CliArgs {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load environment variables
-    dotenvy::dotenv().ok();
+    // Load ~/.grok/.env, then the nearest project .env on top of it
+    let project_env = std::env::current_dir().ok().and_then(|dir| utils::config_sources::project_env_file(&dir));
+    let env_report = utils::config_sources::load_env_files(
+        utils::config_sources::home_env_file().as_deref(),
+        project_env.as_deref(),
+    );
+    utils::config_sources::record_env_sources(&env_report);
 
     let args = CliArgs::parse();
 
+    if args.no_config {
+        utils::settings_manager::set_config_source(utils::settings_manager::ConfigSource::Disabled);
+    } else if let Some(path) = &args.config {
+        // Resolve before --directory changes the working directory
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        utils::settings_manager::set_config_source(utils::settings_manager::ConfigSource::File(path));
+    }
+
     // Handle subcommands first
+    let mut resume_session_id = args.resume.clone();
+    let mut run_doctor = false;
+    let mut replay_tool = None;
     match args.command {
         Some(Commands::Mcp { command }) => {
             handle_mcp_command(command).await?;
             return Ok(());
         }
+        Some(Commands::History { command: Some(crate::commands::history::HistoryCommand::Resume { id }) }) => {
+            // Resuming needs the full client setup below
+            resume_session_id = Some(id);
+        }
+        Some(Commands::History { command }) => {
+            handle_history_command(command).await?;
+            return Ok(());
+        }
+        Some(Commands::Doctor) => {
+            // Needs the resolved configuration below
+            run_doctor = true;
+        }
+        Some(Commands::ReplayTool { name, arguments }) => {
+            replay_tool = Some((name, arguments));
+        }
         None => {
             // Continue with main app logic
         }
@@ -80,14 +215,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let settings_manager = utils::settings_manager::get_settings_manager().await?;
     let settings = settings_manager.load_user_settings().await?;
+    let mut config_warnings = env_report.warnings.clone();
+    let project_overrides = settings_manager.read_project_overrides(&mut config_warnings).await.unwrap_or_default();
+    let project_settings = settings_manager.read_project_settings(&mut config_warnings).await.unwrap_or_default();
+    let project_overrides_path = settings_manager.project_overrides_path();
+    let project_source = || utils::config_sources::ValueSource::ProjectSettings(project_overrides_path.clone());
 
-    // Get API key from args, environment, or settings
-    let api_key_env = args.api_key
-        .or_else(|| std::env::var("GROK_API_KEY").ok());
+    let resolved_api_key = utils::config_sources::resolve_setting("api_key", args.api_key.clone(), "GROK_API_KEY", None, settings.api_key.clone(), &mut config_warnings);
+    let resolved_base_url = utils::config_sources::resolve_setting("base_url", args.base_url.clone(), "GROK_BASE_URL", None, settings.base_url.clone(), &mut config_warnings);
+    let resolved_model = utils::config_sources::resolve_setting(
+        "model",
+        args.model.clone(),
+        "GROK_MODEL",
+        project_overrides.model.clone().map(|model| (model, project_overrides_path.clone())),
+        settings.default_model.clone(),
+        &mut config_warnings,
+    );
+    for warning in &config_warnings {
+        eprintln!("⚠️  {}", warning);
+    }
 
-    let api_key = if let Some(key) = api_key_env {
-        key
-    } else if let Some(key) = settings.api_key {
+    // Get API key from args, environment, or settings
+    let api_key = if let Some(key) = resolved_api_key.as_ref().map(|resolved| resolved.value.clone()) {
         key
     } else {
         // No API key provided - if in headless mode, exit with error; if interactive, allow to continue but warn
@@ -100,16 +249,220 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    let base_url = args.base_url
-        .or_else(|| std::env::var("GROK_BASE_URL").ok())
-        .or(settings.base_url)
-        .unwrap_or_else(|| "https://api.x.ai/v1".to_string());
+    let resolved_base_url = resolved_base_url.unwrap_or_else(|| utils::config_sources::ResolvedValue {
+        name: "base_url",
+        value: "https://api.x.ai/v1".to_string(),
+        source: utils::config_sources::ValueSource::Default,
+        secret: false,
+    });
+    let base_url = resolved_base_url.value.clone();
+    let model = resolved_model.as_ref().map(|resolved| resolved.value.clone());
+    // Project overrides without a flag or environment equivalent are listed with their file
+    let project_values = [
+        ("temperature", project_overrides.temperature.map(|t| t.to_string())),
+        ("tools", project_overrides.tools.as_ref().map(|tools| tools.join(", "))),
+        ("system_prompt", project_overrides.system_prompt.as_ref().map(|p| format!("{} chars appended", p.chars().count()))),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        value.map(|value| utils::config_sources::ResolvedValue { name, value, source: project_source(), secret: false })
+    });
+    utils::config_sources::record_resolved(
+        resolved_api_key
+            .into_iter()
+            .chain([resolved_base_url])
+            .chain(resolved_model)
+            .chain(project_values)
+            .collect(),
+    );
+
+    // Provider resolution follows the same precedence as the other fields: flag > env > settings
+    let env_provider = std::env::var("GROK_PROVIDER").ok();
+    let env_openai_compatible = match std::env::var("GROK_OPENAI_COMPATIBLE") {
+        Ok(value) => {
+            let parsed = utils::settings_manager::parse_bool_value(&value);
+            if parsed.is_none() {
+                eprintln!("⚠️  Ignoring GROK_OPENAI_COMPATIBLE={}: expected true or false", value);
+            }
+            parsed
+        }
+        Err(_) => None,
+    };
+
+    let (is_openai_compatible, provider_warnings) = utils::settings_manager::resolve_openai_compatible(&[
+        utils::settings_manager::ProviderLayer {
+            source: "command line",
+            provider: args.provider.as_deref(),
+            openai_compatible: args.openai_compatible,
+        },
+        utils::settings_manager::ProviderLayer {
+            source: "environment",
+            provider: env_provider.as_deref(),
+            openai_compatible: env_openai_compatible,
+        },
+        utils::settings_manager::ProviderLayer {
+            source: "user settings",
+            provider: settings.provider.as_deref(),
+            openai_compatible: settings.is_openai_compatible,
+        },
+    ]);
+
+    for warning in provider_warnings {
+        eprintln!("⚠️  {}", warning);
+    }
+
+    if run_doctor {
+        return run_doctor_checks(&api_key, &base_url, model, is_openai_compatible).await;
+    }
+
+    let base_url = match grok::client::normalize_base_url(&base_url) {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    for warning in grok::client::base_url_warnings(&base_url) {
+        eprintln!("⚠️  {}", warning);
+    }
+
+    let empty_response_retries = match std::env::var("GROK_EMPTY_RESPONSE_RETRIES") {
+        Ok(value) => value.parse().ok().or_else(|| {
+            eprintln!("⚠️  Ignoring GROK_EMPTY_RESPONSE_RETRIES={}: expected a number", value);
+            None
+        }),
+        Err(_) => None,
+    }
+    .or(settings.empty_response_retries)
+    .unwrap_or(agent::DEFAULT_EMPTY_RESPONSE_RETRIES);
+
+    let mut retry_policy = grok::client::RetryPolicy::default();
+    if let Some(max_retries) = match std::env::var("GROK_MAX_RETRIES") {
+        Ok(value) => value.parse().ok().or_else(|| {
+            eprintln!("⚠️  Ignoring GROK_MAX_RETRIES={}: expected a number", value);
+            None
+        }),
+        Err(_) => None,
+    }
+    .or(settings.max_retries)
+    {
+        retry_policy.max_retries = max_retries;
+    }
+
+    let max_consecutive_tool_failures = match std::env::var("GROK_MAX_CONSECUTIVE_FAILURES") {
+        Ok(value) => value.parse().ok().or_else(|| {
+            eprintln!("⚠️  Ignoring GROK_MAX_CONSECUTIVE_FAILURES={}: expected a number", value);
+            None
+        }),
+        Err(_) => None,
+    }
+    .or(settings.max_consecutive_tool_failures)
+    .unwrap_or(agent::DEFAULT_MAX_CONSECUTIVE_FAILURES);
+    let tool_failure_action = settings.tool_failure_action.unwrap_or_default();
+    let auto_accept = args.yolo || settings.auto_accept.unwrap_or(false);
+
+    let tool_concurrency = match std::env::var("GROK_TOOL_CONCURRENCY") {
+        Ok(value) => value.parse().ok().or_else(|| {
+            eprintln!("⚠️  Ignoring GROK_TOOL_CONCURRENCY={}: expected a number", value);
+            None
+        }),
+        Err(_) => None,
+    }
+    .or(settings.tool_concurrency)
+    .unwrap_or(agent::DEFAULT_TOOL_CONCURRENCY);
+
+    let context_tokens = match std::env::var("GROK_CONTEXT_TOKENS") {
+        Ok(value) => value.parse().ok().or_else(|| {
+            eprintln!("⚠️  Ignoring GROK_CONTEXT_TOKENS={}: expected a number", value);
+            None
+        }),
+        Err(_) => None,
+    }
+    .or(settings.context_tokens);
+
+    let mut model_params = match args
+        .model_params
+        .clone()
+        .or_else(|| settings.model_params.as_ref().map(|value| value.to_string()))
+    {
+        Some(raw) => match grok::client::parse_model_params(&raw) {
+            Ok(params) => params,
+            Err(e) => {
+                eprintln!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => serde_json::Map::new(),
+    };
+    // A project temperature beats user settings but not --model-params
+    if let Some(temperature) = project_overrides.temperature
+        && !(args.model_params.is_some() && model_params.contains_key("temperature"))
+    {
+        model_params.insert("temperature".to_string(), serde_json::json!(temperature));
+    }
+    for warning in grok::client::model_params_warnings(&model_params) {
+        eprintln!("⚠️  {}", warning);
+    }
+
+    let cache_config = settings.response_cache.clone().unwrap_or_default();
+    let response_cache = match (&args.cache_dir, cache_config.enabled) {
+        (Some(dir), _) => Some(dir.clone()),
+        (None, true) => cache_config.dir.clone().or_else(grok::response_cache::ResponseCache::default_dir),
+        (None, false) => None,
+    }
+    .map(|dir| grok::response_cache::ResponseCache::new(dir, cache_config.max_size_mb));
+
+    // A bashPolicy workspace confines bash to that directory, as --confine-bash does to the current one
+    let policy_workspace = settings.bash_policy.as_ref().and_then(|policy| policy.workspace.clone());
+    let confine_bash = args.confine_bash || settings.confine_bash.unwrap_or(false) || policy_workspace.is_some();
+    let bash_confinement = confine_bash.then(|| {
+        let workspace = policy_workspace
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from(".")));
+        tools::BashConfinement::new(&workspace, settings.restricted_shell.unwrap_or(false))
+    });
+
+    // The file tools stay in the directory grok runs in, which --directory set above
+    let workspace_guard = (!settings.allow_outside_workspace.unwrap_or(false))
+        .then(|| tools::WorkspaceGuard::new(&std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))));
 
-    let model = args.model
-        .or_else(|| std::env::var("GROK_MODEL").ok())
-        .or(settings.default_model);
+    let format_on_write = project_settings.format_on_write.unwrap_or_default();
+    let check_config = project_settings.check.unwrap_or_default();
+    let new_file_line_ending = project_settings.new_file_line_ending.unwrap_or_default();
 
-    let is_openai_compatible = settings.is_openai_compatible;
+    if let Some((name, arguments)) = replay_tool {
+        let mut agent = agent::GrokAgent::new(&api_key, base_url, model, args.max_tool_rounds, is_openai_compatible).await?;
+        agent.set_format_on_write(format_on_write);
+        agent.set_check_config(check_config);
+        agent.set_new_file_line_ending(new_file_line_ending);
+        agent.set_view_budget(settings.view_max_bytes);
+        agent.set_workspace_guard(workspace_guard.clone());
+        // The user typed this exact call on the command line
+        agent.set_auto_accept(true);
+
+        let result = agent.replay_tool(&name, &arguments).await?;
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        if !result.success {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Connect MCP servers concurrently; a failing server is reported but never blocks startup
+    let (mcp_manager, mcp_report) = mcp::initialize_mcp_servers().await?;
+    for (name, reason) in &mcp_report.failed {
+        eprintln!("⚠️  MCP server '{}' unavailable: {}", name, reason);
+    }
+    if args.verbose {
+        for (name, health) in mcp_manager.health_check_all().await? {
+            match health {
+                Ok(health) => eprintln!("✅ MCP server '{}': {}", name, health.describe()),
+                // Servers that did not connect were already reported above
+                Err(_) if mcp_report.failed.iter().any(|(failed, _)| *failed == name) => {}
+                Err(reason) => eprintln!("⚠️  MCP server '{}' failed the health check: {}", name, reason),
+            }
+        }
+    }
+    let mcp_manager = std::sync::Arc::new(tokio::sync::RwLock::new(mcp_manager));
 
     if let Some(prompt) = args.prompt {
         // Headless mode: process prompt and exit
@@ -118,61 +471,504 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             std::process::exit(1);
         }
 
-        let mut agent = agent::GrokAgent::new(&api_key, base_url, model, Some(args.max_tool_rounds), is_openai_compatible).await?;
+        let mut agent = agent::GrokAgent::new(&api_key, base_url, model, args.max_tool_rounds, is_openai_compatible).await?;
+        agent.set_format_on_write(format_on_write);
+        agent.set_check_config(check_config);
+        agent.set_new_file_line_ending(new_file_line_ending);
+        agent.set_empty_response_retries(empty_response_retries);
+        agent.set_retry_policy(retry_policy.clone());
+        agent.set_tool_failure_limit(max_consecutive_tool_failures, tool_failure_action);
+        agent.set_history_window(settings.history_window);
+        agent.set_view_budget(settings.view_max_bytes);
+        agent.set_workspace_guard(workspace_guard.clone());
+        agent.set_change_summary(settings.change_summary.unwrap_or(true));
+        agent.set_auto_stage(settings.auto_stage.unwrap_or(false));
+        agent.set_max_message_tokens(settings.max_message_tokens);
+        agent.set_compress_tool_results_after(settings.compress_tool_results_after);
+        agent.set_tool_concurrency(tool_concurrency);
+        agent.set_context_tokens(context_tokens);
+        agent.set_tool_timeouts(settings.tool_timeouts.as_ref().map(agent::ToolTimeouts::with_overrides).unwrap_or_default());
+        agent.set_tool_result_summary(settings.tool_result_summary.clone().unwrap_or_default());
+        agent.set_max_tool_calls_per_message(args.max_tool_calls_per_message.or(settings.max_tool_calls_per_message));
+        agent.set_explain_tools(settings.explain_tools.unwrap_or(false));
+        agent.set_conventional_commits(settings.conventional_commits.unwrap_or(false));
+        agent.set_tool_rounds_config(&settings.tool_rounds.clone().unwrap_or_default());
+        agent.set_model_prices(settings.model_prices.clone().unwrap_or_default());
+        agent.set_enabled_tools(project_overrides.tools.clone());
+        agent.set_mcp_manager(mcp_manager.clone());
+        if let Some(prompt) = &project_overrides.system_prompt {
+            agent.append_system_prompt(prompt);
+        }
+        agent.set_response_cache(response_cache.clone());
+        agent.set_model_params(model_params);
+        agent.add_dangerous_command_rules(settings.dangerous_commands.unwrap_or_default());
+        agent.set_bash_confinement(bash_confinement.clone());
+        agent.set_bash_policy(settings.bash_policy.clone());
+        // Nobody can type YES in headless mode
+        agent.set_dangerous_command_mode(tools::DangerousCommandMode::Block);
+        let stdin = args.interactive_asks.then(StdinLines::spawn);
+        if let Some(stdin) = &stdin {
+            agent.set_user_asker(spawn_stdin_asker(stdin.clone()));
+        }
+        if auto_accept {
+            agent.set_auto_accept(true);
+        } else {
+            agent.set_edit_confirmer(spawn_headless_confirmer(agent.pending_confirmations(), stdin));
+        }
+
+        if let Some(path) = &args.output_file {
+            if let Err(e) = stream_to_output(&mut agent, &prompt, path, args.log_tools).await {
+                eprintln!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+            if let Ok(store) = utils::session_store::SessionStore::new() {
+                let _ = store.save(&agent.session_file(agent.get_chat_history())).await;
+            }
+            export_headless_transcript(&agent, args.export.as_deref()).await;
+            return Ok(());
+        }
 
         // Process the prompt
-        let chat_entries = agent.process_user_message(&prompt).await?;
+        // Returning the error would print its Debug form; provider errors read better as text
+        let chat_entries = match agent.process_user_message(&prompt).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                if args.output_format == OutputFormat::Json {
+                    let document = serde_json::json!({
+                        "entries": [],
+                        "usage": agent.get_usage().to_json(),
+                        "exit_reason": agent::TurnEnd::Error,
+                        "error": e.to_string(),
+                    });
+                    println!("{}", serde_json::to_string_pretty(&document)?);
+                }
+                eprintln!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if let Ok(store) = utils::session_store::SessionStore::new() {
+            let _ = store.save(&agent.session_file(agent.get_chat_history())).await;
+        }
+        export_headless_transcript(&agent, args.export.as_deref()).await;
 
         // Output results
-        for entry in chat_entries {
-            println!("{}", serde_json::to_string(&entry)?);
+        print_headless_output(args.output_format, &agent, &chat_entries)?;
+        let end = agent.last_turn_end();
+        if end.is_failure() {
+            std::process::exit(if end == agent::TurnEnd::Error { 1 } else { 2 });
         }
     } else {
         // Interactive mode: launch UI
         println!("🤖 Starting Grok CLI Conversational Assistant...\n");
 
-        let agent = agent::GrokAgent::new(&api_key, base_url, model, Some(args.max_tool_rounds), is_openai_compatible).await?;
+        let mut agent = agent::GrokAgent::new(&api_key, base_url, model, args.max_tool_rounds, is_openai_compatible).await?;
+        agent.set_format_on_write(format_on_write);
+        agent.set_check_config(check_config);
+        agent.set_new_file_line_ending(new_file_line_ending);
+        agent.set_empty_response_retries(empty_response_retries);
+        agent.set_retry_policy(retry_policy.clone());
+        agent.set_tool_failure_limit(max_consecutive_tool_failures, tool_failure_action);
+        agent.set_history_window(settings.history_window);
+        agent.set_view_budget(settings.view_max_bytes);
+        agent.set_workspace_guard(workspace_guard.clone());
+        agent.set_change_summary(settings.change_summary.unwrap_or(true));
+        agent.set_auto_stage(settings.auto_stage.unwrap_or(false));
+        agent.set_max_message_tokens(settings.max_message_tokens);
+        agent.set_compress_tool_results_after(settings.compress_tool_results_after);
+        agent.set_tool_concurrency(tool_concurrency);
+        agent.set_context_tokens(context_tokens);
+        agent.set_tool_timeouts(settings.tool_timeouts.as_ref().map(agent::ToolTimeouts::with_overrides).unwrap_or_default());
+        agent.set_tool_result_summary(settings.tool_result_summary.clone().unwrap_or_default());
+        agent.set_max_tool_calls_per_message(args.max_tool_calls_per_message.or(settings.max_tool_calls_per_message));
+        agent.set_explain_tools(settings.explain_tools.unwrap_or(false));
+        agent.set_conventional_commits(settings.conventional_commits.unwrap_or(false));
+        agent.set_tool_rounds_config(&settings.tool_rounds.clone().unwrap_or_default());
+        agent.set_model_prices(settings.model_prices.clone().unwrap_or_default());
+        agent.set_enabled_tools(project_overrides.tools.clone());
+        agent.set_mcp_manager(mcp_manager.clone());
+        if let Some(prompt) = &project_overrides.system_prompt {
+            agent.append_system_prompt(prompt);
+        }
+        agent.set_response_cache(response_cache.clone());
+        agent.set_model_params(model_params);
+        agent.add_dangerous_command_rules(settings.dangerous_commands.unwrap_or_default());
+        agent.set_bash_confinement(bash_confinement.clone());
+        agent.set_bash_policy(settings.bash_policy.clone());
+        if args.sandbox {
+            agent.set_dangerous_command_mode(tools::DangerousCommandMode::Block);
+        }
+        agent.set_auto_accept(auto_accept);
+        if let Some(id) = resume_session_id {
+            let (_, session) = utils::session_store::SessionStore::new()?.load(&id).await?;
+            agent.restore_session(session);
+        }
         let initial_message = args.message.join(" ");
 
-        ui::run_app(agent, initial_message).await?;
+        let idle_timeout = match args.idle_timeout {
+            Some(0) => None,
+            Some(secs) => Some(std::time::Duration::from_secs(secs)),
+            None if !std::io::IsTerminal::is_terminal(&std::io::stdin()) => Some(ui::DEFAULT_NON_TTY_IDLE_TIMEOUT),
+            None => None,
+        };
+
+        let (custom_commands, command_warnings) =
+            utils::custom_commands::CustomCommands::new(
+                settings.custom_commands.clone().unwrap_or_default(),
+                std::path::Path::new(".grok/commands"),
+            );
+        for warning in command_warnings {
+            eprintln!("⚠️  {}", warning);
+        }
+
+        if let Err(e) = ui::run_app(agent, initial_message, settings.pretty_tool_output.unwrap_or(true), custom_commands, idle_timeout).await {
+            if e.is::<ui::IdleTimeout>() {
+                eprintln!("⏱️  {}", e);
+                // Same code as coreutils `timeout`
+                std::process::exit(124);
+            }
+            return Err(e);
+        }
     }
 
     Ok(())
 }
 
 async fn handle_mcp_command(command: crate::commands::mcp::McpCommand) -> Result<(), Box<dyn std::error::Error>> {
-    use crate::commands::mcp::{MCPServerConfig, TransportConfig};
-
     match command {
-        crate::commands::mcp::McpCommand::Add { name, transport, command, args } => {
-            // Create the transport configuration
-            let transport_config = TransportConfig {
-                transport_type: transport,
-                command,
-                args: args.map(|s| vec![s]), // Simplified - in a real implementation handle multiple args
-                env: None,
-                url: None,
-                headers: None,
+        crate::commands::mcp::McpCommand::Add(add) => {
+            let (scope, force) = (add.scope, add.force);
+            let server_config = add.into_config()?;
+            let name = server_config.name.clone();
+            let (path, replaced) = crate::commands::add_mcp_server(server_config, scope, force).await?;
+            if replaced {
+                println!("Replaced MCP server {} in {}", name, path);
+            } else {
+                println!("Added MCP server {} to {}", name, path);
+            }
+        },
+        crate::commands::mcp::McpCommand::Remove { name, scope } => {
+            let path = crate::commands::remove_mcp_server(&name, scope).await?;
+            println!("Removed MCP server {} from {}", name, path);
+        },
+        crate::commands::mcp::McpCommand::List => {
+            let servers = crate::commands::list_mcp_servers().await?;
+            if servers.is_empty() {
+                println!("No MCP servers configured. Add one with `grok mcp add <name> --command <cmd>`.");
+                return Ok(());
+            }
+            println!("{:<20} {:<8} {:<16} COMMAND / URL", "NAME", "SCOPE", "TRANSPORT");
+            for (scope, name, config) in servers {
+                let (transport, target) = match &config {
+                    Ok(config) => (config.transport.transport_type.clone(), crate::commands::mcp::resolved_target(config)),
+                    Err(reason) => ("?".to_string(), reason.clone()),
+                };
+                println!("{:<20} {:<8} {:<16} {}", name, scope, transport, target);
+            }
+        },
+        crate::commands::mcp::McpCommand::Test { name } => {
+            let (config, invalid) = mcp::load_mcp_config().await?;
+            if let Some((_, reason)) = invalid.iter().find(|(n, _)| *n == name) {
+                eprintln!("❌ MCP server {} has invalid settings: {}", name, reason);
+                std::process::exit(1);
+            }
+            let Some(server) = config.servers.iter().find(|server| server.name == name) else {
+                eprintln!("❌ No MCP server named {}. See `grok mcp list`.", name);
+                std::process::exit(1);
             };
+            match mcp::probe_server(server, mcp::get_mcp_manager().await.timeout()).await {
+                Ok(health) => {
+                    println!("✅ {} ({})", name, health.describe());
+                    if !health.tools.is_empty() {
+                        println!();
+                        println!("{:<32} DESCRIPTION", "TOOL");
+                        for tool in &health.tools {
+                            let description = tool.description.as_deref().unwrap_or_default().lines().next().unwrap_or_default();
+                            println!("{:<32} {}", tool.name, description);
+                        }
+                    }
+                }
+                Err(reason) => {
+                    eprintln!("❌ MCP server {} failed: {}", name, reason);
+                    std::process::exit(1);
+                }
+            }
+        },
+    }
+    Ok(())
+}
 
-            // Create the server configuration
-            let server_config = MCPServerConfig {
-                name: name.clone(),
-                transport: transport_config,
-                command: None,
-                args: None,
-                env: None,
-            };
+async fn handle_history_command(command: Option<crate::commands::history::HistoryCommand>) -> Result<(), Box<dyn std::error::Error>> {
+    use crate::commands::history::HistoryCommand;
+    let store = utils::session_store::SessionStore::new()?;
 
-            // Add the server (in a real implementation this would save to config file)
-            crate::commands::add_mcp_server(name, server_config)?;
-            println!("Added MCP server successfully");
-        },
-        crate::commands::mcp::McpCommand::Remove { name } => {
-            // Remove the server (in a real implementation this would remove from config file)
-            crate::commands::remove_mcp_server(&name)?;
-            println!("Removed MCP server successfully");
-        },
+    match command {
+        None => {
+            let sessions = store.list().await;
+            if sessions.is_empty() {
+                println!("No saved sessions in this project yet.");
+                return Ok(());
+            }
+            println!("{:<14} {:<17} {:<22} {:>5}  FIRST MESSAGE", "ID", "UPDATED", "MODEL", "TURNS");
+            for session in sessions {
+                println!(
+                    "{:<14} {:<17} {:<22} {:>5}  {}",
+                    session.id,
+                    session.updated_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    session.model,
+                    session.turn_count,
+                    session.first_user_message
+                );
+            }
+        }
+        Some(HistoryCommand::Show { id }) => {
+            let (meta, session) = store.load(&id).await?;
+            print!("{}", utils::session_store::session_to_markdown(&meta, &session.entries));
+        }
+        Some(HistoryCommand::Delete { id }) => {
+            let meta = store.delete(&id).await?;
+            println!("Deleted session {} ({})", meta.id, meta.first_user_message);
+        }
+        Some(HistoryCommand::Resume { .. }) => unreachable!("resume is handled before startup"),
+    }
+    Ok(())
+}
+
+async fn run_doctor_checks(
+    api_key: &str,
+    base_url: &str,
+    model: Option<String>,
+    is_openai_compatible: Option<bool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Configuration:");
+    for line in utils::config_sources::resolved_lines() {
+        println!("  {}", line);
+    }
+    println!("Base URL: {}", base_url);
+    let client = match grok::client::GrokClient::new(api_key, model, Some(base_url.to_string()), is_openai_compatible) {
+        Ok(client) => client,
+        Err(e) => {
+            println!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Model:    {}", client.get_current_model());
+
+    let mut healthy = true;
+    if api_key == "API_KEY_NOT_SET" {
+        println!("❌ No API key set");
+        healthy = false;
+    } else {
+        println!("✅ API key set");
+    }
+    for warning in grok::client::base_url_warnings(&client.base_url) {
+        println!("⚠️  {}", warning);
+    }
+    match client.check_connection().await {
+        Ok(message) => println!("✅ {}", message),
+        Err(message) => {
+            println!("❌ {}", message);
+            healthy = false;
+        }
+    }
+
+    if !healthy {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `--export`: the transcript of the headless run; a failure is reported on stderr only
+async fn export_headless_transcript(agent: &agent::GrokAgent, path: Option<&std::path::Path>) {
+    let Some(path) = path else { return };
+    let format = utils::transcript::TranscriptFormat::for_path(path);
+    if let Err(e) = agent.export_transcript(agent.get_chat_history(), format, Some(path)).await {
+        eprintln!("❌ Failed to export the transcript: {}", e);
+    }
+}
+
+/// Answer `ask_user` questions from stdin; the question goes to stderr so stdout stays JSON
+/// Headless `--output-file`: write the reply's text to `path` (`-` for stdout) chunk by chunk,
+/// running tool calls between rounds the same way the interactive UI does
+async fn stream_to_output(
+    agent: &mut agent::GrokAgent,
+    prompt: &str,
+    path: &str,
+    log_tools: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use futures::stream::StreamExt;
+    use std::io::Write;
+
+    let mut out: Box<dyn Write> = if path == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(std::fs::File::create(path)?)
+    };
+    let mut ends_with_newline = true;
+    // Tool calls run inside the stream; their results arrive between the rounds' text
+    let mut stream = agent.process_user_message_stream(prompt).await.map_err(|e| e.to_string())?;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        match chunk.chunk_type {
+            types::StreamingChunkType::Content => {
+                if let Some(content) = chunk.content.filter(|c| !c.is_empty()) {
+                    out.write_all(content.as_bytes())?;
+                    out.flush()?;
+                    ends_with_newline = content.ends_with('\n');
+                }
+            }
+            types::StreamingChunkType::ToolResult => {
+                if let (true, Some(call), Some(result)) = (log_tools, &chunk.tool_call, &chunk.tool_result) {
+                    eprintln!("🔧 {} {}", call.function.name, call.function.arguments);
+                    match &result.error {
+                        Some(error) if !result.success => eprintln!("🔧 {} failed: {}", call.function.name, error),
+                        _ => eprintln!("🔧 {} ok", call.function.name),
+                    }
+                }
+                // Keep text from different rounds on separate lines
+                if !ends_with_newline {
+                    out.write_all(b"\n")?;
+                    ends_with_newline = true;
+                }
+            }
+            // Reported on stderr so the output file holds only the reply
+            types::StreamingChunkType::Note => eprintln!("{}", chunk.content.unwrap_or_default()),
+            types::StreamingChunkType::CacheHit => eprintln!("(from cache)"),
+            types::StreamingChunkType::Truncated => eprintln!("{}", agent::TRUNCATED_WARNING),
+            types::StreamingChunkType::Done | types::StreamingChunkType::ToolCalls | types::StreamingChunkType::TokenCount => {}
+        }
+    }
+    drop(stream);
+    if !ends_with_newline && path == "-" {
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    if let Some(entry) = agent.finish_turn() {
+        eprintln!("{}", entry.content);
+    }
+    Ok(())
+}
+
+/// Print a finished headless turn in `format`
+fn print_headless_output(
+    format: OutputFormat,
+    agent: &agent::GrokAgent,
+    entries: &[types::ChatEntry],
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        OutputFormat::Jsonl => {
+            for entry in entries {
+                println!("{}", serde_json::to_string(entry)?);
+            }
+        }
+        OutputFormat::Json => {
+            let document = serde_json::json!({
+                "entries": entries,
+                "usage": agent.get_usage().to_json(),
+                "exit_reason": agent.last_turn_end(),
+            });
+            println!("{}", serde_json::to_string_pretty(&document)?);
+        }
+        OutputFormat::Text => {
+            let reply = agent.last_reply();
+            for entry in entries {
+                match entry.entry_type {
+                    types::ChatEntryType::ToolResult => {
+                        let name = entry.tool_call.as_ref().map(|call| call.function.name.as_str()).unwrap_or("tool");
+                        match &entry.tool_result {
+                            Some(result) if !result.success => {
+                                eprintln!("🔧 {} failed: {}", name, result.error.as_deref().unwrap_or_default())
+                            }
+                            _ => eprintln!("🔧 {} ok", name),
+                        }
+                    }
+                    types::ChatEntryType::Assistant if reply != Some(entry.content.as_str()) && !entry.content.is_empty() => {
+                        eprintln!("{}", entry.content)
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(reply) = reply {
+                println!("{}", reply);
+            }
+        }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Lines from stdin for the headless confirmer and asker. One task owns the only reader:
+/// separate `BufReader`s would each buffer ahead and swallow lines meant for the other.
+#[derive(Clone)]
+struct StdinLines(tokio::sync::mpsc::Sender<tokio::sync::oneshot::Sender<Option<String>>>);
+
+impl StdinLines {
+    fn spawn() -> Self {
+        use tokio::io::AsyncBufReadExt;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<tokio::sync::oneshot::Sender<Option<String>>>(1);
+        tokio::spawn(async move {
+            let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+            while let Some(reply) = rx.recv().await {
+                let _ = reply.send(lines.next_line().await.ok().flatten());
+            }
+        });
+        Self(tx)
+    }
+
+    /// The next line, or `None` once stdin is closed
+    async fn next_line(&self) -> Option<String> {
+        let (reply, line) = tokio::sync::oneshot::channel();
+        self.0.send(reply).await.ok()?;
+        line.await.ok().flatten()
+    }
+}
+
+/// Approvals for headless runs: asked on stdin with `--interactive-asks`, otherwise rejected,
+/// since nobody is there to approve them
+fn spawn_headless_confirmer(pending: agent::PendingConfirmations, stdin: Option<StdinLines>) -> tokio::sync::mpsc::Sender<agent::EditConfirmation> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<agent::EditConfirmation>(1);
+    tokio::spawn(async move {
+        let mut warned = false;
+        while let Some(request) = rx.recv().await {
+            let decision = if let Some(stdin) = &stdin {
+                eprintln!("✋ {}\n{}", request.description, request.entry.content);
+                eprint!("Allow? [y]es  [n]o  [a]lways: ");
+                match stdin.next_line().await {
+                    Some(line) => match line.trim().to_lowercase().as_str() {
+                        "y" | "yes" => agent::EditDecision::Approve,
+                        "a" | "always" => agent::EditDecision::ApproveAll,
+                        _ => agent::EditDecision::Reject,
+                    },
+                    None => agent::EditDecision::Reject,
+                }
+            } else {
+                if !std::mem::replace(&mut warned, true) {
+                    eprintln!(
+                        "⚠️  Rejected {}: headless runs need approval for file edits and bash commands. Pass --yolo to allow them or --interactive-asks to approve each one.",
+                        request.description
+                    );
+                }
+                agent::EditDecision::Reject
+            };
+            pending.resolve(&request.id, decision);
+        }
+    });
+    tx
+}
+
+fn spawn_stdin_asker(stdin: StdinLines) -> tokio::sync::mpsc::Sender<agent::UserQuestion> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<agent::UserQuestion>(1);
+    tokio::spawn(async move {
+        while let Some(question) = rx.recv().await {
+            eprintln!("❓ {}", question.question);
+            for (idx, option) in question.options.iter().enumerate() {
+                eprintln!("  {}. {}", idx + 1, option);
+            }
+            eprint!("> ");
+            // Closed stdin drops the reply, which the agent treats as nobody answering
+            if let Some(line) = stdin.next_line().await {
+                let _ = question.reply.send(agent::resolve_answer(&line, &question.options));
+            }
+        }
+    });
+    tx
+}
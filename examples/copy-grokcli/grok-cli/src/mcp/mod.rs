@@ -1,5 +1,11 @@
+use crate::types::{GrokTool, GrokToolFunction, GrokToolParameters, ToolResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+mod stdio;
+
+pub use stdio::{McpTool, StdioConnection};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct MCPServerConfig {
@@ -35,61 +41,508 @@ pub struct MCPConfig {
     pub servers: Vec<MCPServerConfig>,
 }
 
-/// Load MCP configuration from project settings
-pub fn load_mcp_config() -> MCPConfig {
-    // For now, return an empty configuration
-    // In a full implementation, this would load from a config file
-    MCPConfig {
-        servers: Vec::new(),
+/// Load the servers saved with `grok mcp add`. A project server hides a user server with the
+/// same name. Entries that do not parse are returned with the reason instead.
+pub async fn load_mcp_config() -> Result<(MCPConfig, Vec<(String, String)>), Box<dyn std::error::Error>> {
+    let mut servers: Vec<MCPServerConfig> = Vec::new();
+    let mut invalid = Vec::new();
+    for (_, name, config) in crate::commands::list_mcp_servers().await? {
+        if servers.iter().any(|server| server.name == name) || invalid.iter().any(|(n, _)| n == &name) {
+            continue;
+        }
+        // The commands module keeps its own copy of the config types; both read the same JSON
+        let config = config.and_then(|config| {
+            serde_json::to_value(config)
+                .and_then(serde_json::from_value::<MCPServerConfig>)
+                .map_err(|e| format!("invalid config: {}", e))
+        });
+        match config {
+            Ok(mut config) => {
+                config.name = name;
+                servers.push(config);
+            }
+            Err(reason) => invalid.push((name, reason)),
+        }
     }
+    Ok((MCPConfig { servers }, invalid))
+}
+
+/// Prefix of the names MCP tools are offered to the model under: `mcp__<server>__<tool>`
+pub const MCP_TOOL_PREFIX: &str = "mcp__";
+
+/// The name `tool` of `server` is offered under. Characters function names cannot contain
+/// become `_`.
+pub fn namespaced_tool_name(server: &str, tool: &str) -> String {
+    let clean = |part: &str| -> String {
+        part.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' }).collect()
+    };
+    format!("{}{}__{}", MCP_TOOL_PREFIX, clean(server), clean(tool))
+}
+
+/// A `tools/list` entry as a function tool for the chat request
+fn to_grok_tool(server: &str, tool: &McpTool) -> GrokTool {
+    let schema = &tool.input_schema;
+    let properties = schema
+        .get("properties")
+        .and_then(|properties| serde_json::from_value(properties.clone()).ok())
+        .unwrap_or_default();
+    let required = schema
+        .get("required")
+        .and_then(|required| serde_json::from_value(required.clone()).ok())
+        .unwrap_or_default();
+    let description = match &tool.description {
+        Some(description) => format!("[MCP server {}] {}", server, description),
+        None => format!("[MCP server {}] {}", server, tool.name),
+    };
+    GrokTool {
+        tool_type: "function".to_string(),
+        function: GrokToolFunction {
+            name: namespaced_tool_name(server, &tool.name),
+            description,
+            parameters: GrokToolParameters {
+                param_type: schema.get("type").and_then(|t| t.as_str()).unwrap_or("object").to_string(),
+                properties,
+                required,
+            },
+        },
+    }
+}
+
+/// A `tools/call` result as a tool result: the text parts become the output, and `isError`
+/// or a failed request makes it a failure
+pub fn to_tool_result(result: Result<serde_json::Value, String>) -> ToolResult {
+    let result = match result {
+        Ok(result) => result,
+        Err(error) => return ToolResult { success: false, output: None, error: Some(error), data: None },
+    };
+    let text: Vec<String> = result
+        .get("content")
+        .and_then(|content| content.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .map(|part| match part.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => part.get("text").and_then(|t| t.as_str()).unwrap_or_default().to_string(),
+                    Some(other) => format!("[{} content]", other),
+                    None => part.to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let text = text.join("\n");
+    let data = result.get("structuredContent").cloned();
+    if result.get("isError").and_then(|e| e.as_bool()).unwrap_or(false) {
+        let error = if text.is_empty() { "The MCP tool reported an error".to_string() } else { text };
+        ToolResult { success: false, output: None, error: Some(error), data }
+    } else {
+        ToolResult { success: true, output: Some(text), error: None, data }
+    }
+}
+
+/// Default number of servers connected at the same time during startup
+pub const DEFAULT_MCP_CONCURRENCY: usize = 8;
+/// Default time a single server may take to connect and list its tools
+pub const DEFAULT_MCP_TIMEOUT_SECS: u64 = 10;
+
+/// Outcome of connecting to all configured servers at startup
+#[derive(Debug, Default)]
+pub struct MCPStartupReport {
+    /// Server name and the tools it exposes
+    pub connected: Vec<(String, Vec<String>)>,
+    /// Server name and why it could not be used
+    pub failed: Vec<(String, String)>,
 }
 
 pub struct MCPManager {
-    // In a full implementation, this would maintain connections to MCP servers
-    // For now, we'll just have a placeholder
+    /// Running stdio servers, kept open for `list_tools` and `call_tool`
+    connections: HashMap<String, Arc<StdioConnection>>,
+    /// Tools discovered per connected server
+    tools: HashMap<String, Vec<McpTool>>,
+    /// Bumped whenever `tools` changes, so callers know when a cached tool list is stale
+    revision: u64,
+    concurrency: usize,
+    timeout: std::time::Duration,
 }
 
 impl MCPManager {
-    pub fn new() -> Self {
-        MCPManager {}
+    /// Connect at most `concurrency` servers at once, each within `timeout`
+    pub fn with_limits(concurrency: usize, timeout: std::time::Duration) -> Self {
+        MCPManager {
+            connections: HashMap::new(),
+            tools: HashMap::new(),
+            revision: 0,
+            concurrency: concurrency.max(1),
+            timeout,
+        }
     }
 
-    pub async fn add_server(&mut self, _config: MCPServerConfig) -> Result<(), Box<dyn std::error::Error>> {
-        // In a full implementation, this would connect to the MCP server
-        Ok(())
+    /// Forget the server; a stdio server is stopped once no call still uses it
+    fn remove_server(&mut self, server_name: &str) {
+        self.connections.remove(server_name);
+        self.tools.remove(server_name);
+        self.revision += 1;
     }
 
-    pub async fn remove_server(&mut self, _server_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // In a full implementation, this would disconnect from the MCP server
-        Ok(())
+    /// Ask the server for its tools again and remember them. A stdio server that has exited
+    /// is dropped, so its tools are no longer offered.
+    pub async fn list_tools(&mut self, server_name: &str) -> Result<Vec<McpTool>, String> {
+        let connection = self.connection(server_name)?;
+        let listed = connection.list_tools().await;
+        if connection.has_exited() {
+            self.remove_server(server_name);
+            return Err(listed.err().unwrap_or_else(|| format!("MCP server {} has exited", server_name)));
+        }
+        let tools = listed?;
+        self.tools.insert(server_name.to_string(), tools.clone());
+        self.revision += 1;
+        Ok(tools)
     }
 
-    pub async fn call_tool(&mut self, tool_name: &str, arguments: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-        // For now, return an error indicating the tool is not implemented
-        // In a full implementation, this would call the actual MCP tool
-        Ok(serde_json::json!({
-            "success": false,
-            "error": format!("MCP tool {} not implemented", tool_name),
-            "arguments": arguments
-        }))
+    /// Whether any stdio server has exited since it connected
+    pub fn has_exited_servers(&self) -> bool {
+        self.connections.values().any(|connection| connection.has_exited())
+    }
+
+    /// Run `tool_name` on `server_name`. The result is the server's `tools/call` result,
+    /// with `content` and, when the tool failed, `isError`.
+    pub async fn call_tool(&self, server_name: &str, tool_name: &str, arguments: serde_json::Value) -> Result<serde_json::Value, String> {
+        self.connection(server_name)?.call_tool(tool_name, arguments).await
     }
 
-    pub async fn initialize_mcp_servers(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let config = load_mcp_config();
-        
-        for server_config in config.servers {
-            self.add_server(server_config).await?;
+    fn connection(&self, server_name: &str) -> Result<Arc<StdioConnection>, String> {
+        match self.connections.get(server_name) {
+            Some(connection) => Ok(connection.clone()),
+            None if self.tools.contains_key(server_name) => {
+                Err(format!("MCP server {} does not use the stdio transport, the only one that can run tools", server_name))
+            }
+            None => Err(format!("MCP server {} is not connected", server_name)),
         }
-        
-        Ok(())
+    }
+
+    fn insert(&mut self, name: String, connection: Option<StdioConnection>, tools: Vec<McpTool>) {
+        match connection {
+            Some(connection) => {
+                self.connections.insert(name.clone(), Arc::new(connection));
+            }
+            None => {
+                self.connections.remove(&name);
+            }
+        }
+        self.tools.insert(name, tools);
+        self.revision += 1;
+    }
+
+    pub async fn initialize_mcp_servers(&mut self) -> Result<MCPStartupReport, Box<dyn std::error::Error>> {
+        let (config, invalid) = load_mcp_config().await?;
+        let mut report = self.connect_all(config.servers).await;
+        report.failed.extend(invalid);
+        report.failed.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(report)
+    }
+
+    /// Connect to all servers concurrently, each under its own timeout, so one slow or
+    /// unreachable server doesn't hold up the others. Failures are reported, not fatal.
+    pub async fn connect_all(&mut self, servers: Vec<MCPServerConfig>) -> MCPStartupReport {
+        use futures::StreamExt;
+
+        let timeout = self.timeout;
+        let results: Vec<(String, Result<Connected, String>)> = futures::stream::iter(servers)
+            .map(|config| async move {
+                let result = match tokio::time::timeout(timeout, connect_server(&config, timeout)).await {
+                    Ok(Ok(connected)) => Ok(connected),
+                    Ok(Err(e)) => Err(e),
+                    Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+                };
+                (config.name, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut report = MCPStartupReport::default();
+        for (name, result) in results {
+            match result {
+                Ok((connection, tools)) => {
+                    report.connected.push((name.clone(), tools.iter().map(|tool| tool.name.clone()).collect()));
+                    self.insert(name, connection, tools);
+                }
+                Err(reason) => report.failed.push((name, reason)),
+            }
+        }
+        report.connected.sort_by(|a, b| a.0.cmp(&b.0));
+        report.failed.sort_by(|a, b| a.0.cmp(&b.0));
+        report
+    }
+
+    /// How long one server may take to connect, and each request to it
+    pub fn timeout(&self) -> std::time::Duration {
+        self.timeout
+    }
+
+    pub fn tools(&self) -> &HashMap<String, Vec<McpTool>> {
+        &self.tools
+    }
+
+    /// Probe every configured server at the same time, each within [`HEALTH_CHECK_TIMEOUT`],
+    /// by server name. Servers whose settings do not parse are reported as failures.
+    pub async fn health_check_all(&self) -> Result<Vec<(String, Result<ServerHealth, String>)>, Box<dyn std::error::Error>> {
+        use futures::StreamExt;
+
+        let (config, invalid) = load_mcp_config().await?;
+        let mut results: Vec<(String, Result<ServerHealth, String>)> = futures::stream::iter(config.servers)
+            .map(|config| async move {
+                let health = probe_server(&config, HEALTH_CHECK_TIMEOUT).await;
+                (config.name, health)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+        results.extend(invalid.into_iter().map(|(name, reason)| (name, Err(reason))));
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(results)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Every server's tools as function tools named `mcp__<server>__<tool>`, by server name
+    pub fn grok_tools(&self) -> Vec<GrokTool> {
+        let mut servers: Vec<&String> = self.tools.keys().collect();
+        servers.sort();
+        servers
+            .into_iter()
+            .flat_map(|server| self.tools[server].iter().map(move |tool| to_grok_tool(server, tool)))
+            .collect()
+    }
+
+    /// The server and tool an `mcp__<server>__<tool>` name stands for
+    pub fn resolve_tool(&self, name: &str) -> Option<(String, String)> {
+        self.tools.iter().find_map(|(server, tools)| {
+            tools
+                .iter()
+                .find(|tool| namespaced_tool_name(server, &tool.name) == name)
+                .map(|tool| (server.clone(), tool.name.clone()))
+        })
     }
 }
 
-pub fn get_mcp_manager() -> MCPManager {
-    MCPManager::new()
+/// A stdio server's open connection, or `None` for the remote transports, and its tools
+type Connected = (Option<StdioConnection>, Vec<McpTool>);
+
+/// Connect to one server and list its tools. Stdio servers are started and run the MCP
+/// handshake; remote transports are only checked for a url so far and expose no tools.
+async fn connect_server(config: &MCPServerConfig, timeout: std::time::Duration) -> Result<Connected, String> {
+    match config.transport.transport_type.as_str() {
+        "stdio" => {
+            let connection = spawn_stdio(config, timeout).await?;
+            let tools = connection.list_tools().await?;
+            Ok((Some(connection), tools))
+        }
+        "http" | "sse" | "streamable_http" => {
+            if config.transport.url.is_none() {
+                return Err(format!("{} transport requires a url", config.transport.transport_type));
+            }
+            Ok((None, Vec::new()))
+        }
+        other => Err(format!("unsupported transport: {}", other)),
+    }
+}
+
+/// Start a stdio server from the transport's command, args and env, or the legacy top-level ones
+async fn spawn_stdio(config: &MCPServerConfig, timeout: std::time::Duration) -> Result<StdioConnection, String> {
+    let command = config
+        .transport
+        .command
+        .as_ref()
+        .or(config.command.as_ref())
+        .ok_or("stdio transport requires a command")?;
+    let args = config.transport.args.as_ref().or(config.args.as_ref()).cloned().unwrap_or_default();
+    let env = config.transport.env.as_ref().or(config.env.as_ref()).cloned().unwrap_or_default();
+    StdioConnection::spawn(command, &args, &env, timeout).await
+}
+
+/// Time each server gets to answer the startup health check
+pub const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// What a successful probe of one server found
+#[derive(Debug, Clone)]
+pub struct ServerHealth {
+    /// `serverInfo` from `initialize`
+    pub server_info: serde_json::Value,
+    pub tools: Vec<McpTool>,
+    /// Starting the server and the `initialize` handshake
+    pub initialize: std::time::Duration,
+    pub list_tools: std::time::Duration,
 }
 
-pub async fn initialize_mcp_servers() -> Result<(), Box<dyn std::error::Error>> {
-    let mut manager = get_mcp_manager();
-    manager.initialize_mcp_servers().await
-}
\ No newline at end of file
+impl ServerHealth {
+    /// e.g. `fake-mcp-server 1.0.0: 2 tools, initialize 12ms, tools/list 1ms`
+    pub fn describe(&self) -> String {
+        let server = match (self.server_info["name"].as_str(), self.server_info["version"].as_str()) {
+            (Some(name), Some(version)) => format!("{} {}: ", name, version),
+            (Some(name), None) => format!("{}: ", name),
+            _ => String::new(),
+        };
+        format!(
+            "{}{} tool{}, initialize {}ms, tools/list {}ms",
+            server,
+            self.tools.len(),
+            if self.tools.len() == 1 { "" } else { "s" },
+            self.initialize.as_millis(),
+            self.list_tools.as_millis()
+        )
+    }
+}
+
+/// Start the server on its own connection, run the handshake and list its tools, all within
+/// `timeout`. The connection is closed afterwards.
+pub async fn probe_server(config: &MCPServerConfig, timeout: std::time::Duration) -> Result<ServerHealth, String> {
+    match config.transport.transport_type.as_str() {
+        "stdio" => {}
+        "http" | "sse" | "streamable_http" => {
+            return Err(format!("the {} transport cannot be probed yet; only stdio servers are started", config.transport.transport_type));
+        }
+        other => return Err(format!("unsupported transport: {}", other)),
+    }
+    let probe = async {
+        let started = std::time::Instant::now();
+        let connection = spawn_stdio(config, timeout).await?;
+        let initialize = started.elapsed();
+        let started = std::time::Instant::now();
+        let tools = connection.list_tools().await?;
+        Ok(ServerHealth { server_info: connection.server_info.clone(), tools, initialize, list_tools: started.elapsed() })
+    };
+    tokio::time::timeout(timeout, probe)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {}s", timeout.as_secs())))
+}
+
+/// A manager limited by `GROK_MCP_CONCURRENCY` and `GROK_MCP_TIMEOUT_SECS`, or else the
+/// `mcp_concurrency` and `mcp_timeout_secs` user settings
+pub async fn get_mcp_manager() -> MCPManager {
+    let settings = match crate::utils::settings_manager::get_settings_manager().await {
+        Ok(manager) => manager.load_user_settings().await.ok(),
+        Err(_) => None,
+    };
+    let concurrency = std::env::var("GROK_MCP_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|n: &usize| *n > 0)
+        .or(settings.as_ref().and_then(|s| s.mcp_concurrency))
+        .unwrap_or(DEFAULT_MCP_CONCURRENCY);
+    let timeout_secs = std::env::var("GROK_MCP_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .or(settings.as_ref().and_then(|s| s.mcp_timeout_secs))
+        .unwrap_or(DEFAULT_MCP_TIMEOUT_SECS);
+    MCPManager::with_limits(concurrency, std::time::Duration::from_secs(timeout_secs))
+}
+
+/// Connect every configured server, returning the manager that holds the connections
+pub async fn initialize_mcp_servers() -> Result<(MCPManager, MCPStartupReport), Box<dyn std::error::Error>> {
+    let mut manager = get_mcp_manager().await;
+    let report = manager.initialize_mcp_servers().await?;
+    Ok((manager, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(name: &str, transport_type: &str, url: Option<&str>) -> MCPServerConfig {
+        MCPServerConfig {
+            name: name.to_string(),
+            transport: TransportConfig {
+                transport_type: transport_type.to_string(),
+                command: None,
+                args: None,
+                env: None,
+                url: url.map(|u| u.to_string()),
+                headers: None,
+            },
+            command: None,
+            args: None,
+            env: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_all_reports_failures_without_aborting() {
+        let mut manager = MCPManager::with_limits(2, std::time::Duration::from_secs(1));
+        let report = manager
+            .connect_all(vec![
+                server("remote", "http", Some("http://localhost:1/mcp")),
+                server("broken", "stdio", None),
+                server("odd", "carrier-pigeon", None),
+            ])
+            .await;
+
+        assert_eq!(report.connected.len(), 1);
+        assert_eq!(report.connected[0].0, "remote");
+        assert_eq!(report.failed.len(), 2);
+        assert!(manager.tools().contains_key("remote"));
+    }
+
+    #[tokio::test]
+    async fn test_mcp_tools_are_namespaced_and_results_converted() {
+        let mut manager = MCPManager::with_limits(1, std::time::Duration::from_secs(1));
+        let tool: McpTool = serde_json::from_value(serde_json::json!({
+            "name": "search.issues",
+            "description": "Find issues",
+            "inputSchema": {"type": "object", "properties": {"q": {"type": "string"}}, "required": ["q"]}
+        }))
+        .unwrap();
+        manager.insert("git hub".to_string(), None, vec![tool]);
+        let revision = manager.revision();
+
+        let tools = manager.grok_tools();
+        assert_eq!(tools[0].function.name, "mcp__git_hub__search_issues");
+        assert_eq!(tools[0].function.description, "[MCP server git hub] Find issues");
+        assert_eq!(tools[0].function.parameters.required, ["q"]);
+        assert_eq!(
+            manager.resolve_tool("mcp__git_hub__search_issues"),
+            Some(("git hub".to_string(), "search.issues".to_string()))
+        );
+
+        let result = to_tool_result(Ok(serde_json::json!({
+            "content": [{"type": "text", "text": "2 issues"}, {"type": "image", "data": "…"}]
+        })));
+        assert!(result.success);
+        assert_eq!(result.output.as_deref(), Some("2 issues\n[image content]"));
+        let result = to_tool_result(Ok(serde_json::json!({"content": [{"type": "text", "text": "bad query"}], "isError": true})));
+        assert_eq!((result.success, result.error.as_deref()), (false, Some("bad query")));
+
+        manager.remove_server("git hub");
+        assert!(manager.revision() > revision);
+        assert!(manager.grok_tools().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exited_stdio_server_is_dropped_on_relist() {
+        // Answers `initialize` with no tools, then exits once the handshake is done
+        let script = r#"read line; echo '{"jsonrpc":"2.0","id":1,"result":{"serverInfo":{"name":"brief"}}}'; read line; read line; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}'"#;
+        let mut config = server("brief", "stdio", None);
+        config.transport.command = Some("sh".to_string());
+        config.transport.args = Some(vec!["-c".to_string(), script.to_string()]);
+
+        let mut manager = MCPManager::with_limits(1, std::time::Duration::from_secs(5));
+        let report = manager.connect_all(vec![config]).await;
+        assert_eq!(report.connected.len(), 1, "{:?}", report.failed);
+
+        for _ in 0..50 {
+            if manager.has_exited_servers() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert!(manager.has_exited_servers());
+
+        let revision = manager.revision();
+        assert!(manager.list_tools("brief").await.is_err());
+        assert!(!manager.tools().contains_key("brief"));
+        assert!(!manager.has_exited_servers());
+        assert!(manager.revision() > revision);
+    }
+}
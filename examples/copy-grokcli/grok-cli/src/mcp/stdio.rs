@@ -0,0 +1,262 @@
+//! MCP over stdio: the client starts the server process and they exchange newline-delimited
+//! JSON-RPC 2.0 messages on its stdin and stdout. The server's stderr is kept so a crash can be
+//! reported with what it printed last.
+//!
+//! Only depends on tokio and serde_json so the integration tests can build it on its own.
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::oneshot;
+
+/// Protocol revision sent in `initialize`
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Lines of the server's stderr kept for error messages
+const STDERR_TAIL_LINES: usize = 20;
+
+/// How long a crashed server's stderr and exit status are waited for before the error is
+/// reported without them
+const EXIT_GRACE: Duration = Duration::from_millis(500);
+
+/// One tool from `tools/list`
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema of the arguments
+    #[serde(default, rename = "inputSchema")]
+    pub input_schema: Value,
+}
+
+type Reply = Result<Value, String>;
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Reply>>>>;
+type Writer = Arc<tokio::sync::Mutex<ChildStdin>>;
+type StderrTail = Arc<Mutex<VecDeque<String>>>;
+
+/// A running stdio server after a successful `initialize` handshake. Requests may be sent
+/// concurrently; replies are matched to them by id. The process is killed when this is dropped.
+pub struct StdioConnection {
+    child: Arc<tokio::sync::Mutex<Child>>,
+    stdin: Writer,
+    pending: Pending,
+    next_id: AtomicU64,
+    stderr_tail: StderrTail,
+    /// Set once the server closed its stdout, normally because it exited; holds the reason
+    closed: Arc<Mutex<Option<String>>>,
+    exited: Arc<AtomicBool>,
+    timeout: Duration,
+    /// `serverInfo` from the `initialize` result
+    pub server_info: Value,
+}
+
+impl StdioConnection {
+    /// Start `command` with `args` and `env` added to this process's environment, then run the
+    /// `initialize` handshake. Each request, the handshake included, waits at most `timeout`.
+    pub async fn spawn(command: &str, args: &[String], env: &HashMap<String, String>, timeout: Duration) -> Result<Self, String> {
+        let mut child = Command::new(command)
+            .args(args)
+            .envs(env)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Could not start {}: {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("The server's stdin is not available")?;
+        let stdout = child.stdout.take().ok_or("The server's stdout is not available")?;
+        let stderr = child.stderr.take().ok_or("The server's stderr is not available")?;
+
+        let stderr_tail: StderrTail = Arc::new(Mutex::new(VecDeque::new()));
+        let tail = stderr_tail.clone();
+        let stderr_task = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let mut tail = tail.lock().unwrap();
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        });
+
+        let mut connection = Self {
+            child: Arc::new(tokio::sync::Mutex::new(child)),
+            stdin: Arc::new(tokio::sync::Mutex::new(stdin)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(1),
+            stderr_tail,
+            closed: Arc::new(Mutex::new(None)),
+            exited: Arc::new(AtomicBool::new(false)),
+            timeout,
+            server_info: Value::Null,
+        };
+        connection.read_replies(stdout, stderr_task);
+
+        let result = connection
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "grok-cli", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await
+            .map_err(|e| format!("initialize failed: {}", e))?;
+        connection.server_info = result.get("serverInfo").cloned().unwrap_or(Value::Null);
+        connection.notify("notifications/initialized", json!({})).await?;
+        Ok(connection)
+    }
+
+    /// Route each reply on stdout to the request waiting for it, and answer the server's own
+    /// requests. When stdout closes, every waiting request fails with how the process ended
+    /// and the tail of its stderr.
+    fn read_replies(&self, stdout: ChildStdout, stderr_task: tokio::task::JoinHandle<()>) {
+        let pending = self.pending.clone();
+        let closed = self.closed.clone();
+        let exited = self.exited.clone();
+        let tail = self.stderr_tail.clone();
+        let child = self.child.clone();
+        let stdin = self.stdin.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                // Anything that is not JSON-RPC, such as a startup banner, is skipped
+                let Ok(message) = serde_json::from_str::<Value>(&line) else { continue };
+                if let Some(answer) = answer_server_request(&message) {
+                    let _ = write_message(&stdin, &answer).await;
+                    continue;
+                }
+                let Some(id) = message.get("id").and_then(Value::as_u64) else { continue };
+                let reply = match message.get("error") {
+                    Some(error) => Err(describe_rpc_error(error)),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                if let Some(waiting) = pending.lock().unwrap().remove(&id) {
+                    let _ = waiting.send(reply);
+                }
+            }
+
+            let _ = tokio::time::timeout(EXIT_GRACE, stderr_task).await;
+            let status = tokio::time::timeout(EXIT_GRACE, async { child.lock().await.wait().await }).await;
+            exited.store(true, Ordering::SeqCst);
+            let reason = match status {
+                Ok(Ok(status)) => format!("the MCP server exited ({})", status),
+                _ => "the MCP server closed its output".to_string(),
+            };
+            let reason = with_stderr_tail(reason, &tail);
+            *closed.lock().unwrap() = Some(reason.clone());
+            for (_, waiting) in pending.lock().unwrap().drain() {
+                let _ = waiting.send(Err(reason.clone()));
+            }
+        });
+    }
+
+    /// Send a request and wait for its reply, at most the connection's timeout
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        if let Some(reason) = self.closed.lock().unwrap().clone() {
+            return Err(reason);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if let Err(e) = write_message(&self.stdin, &message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(self.closed.lock().unwrap().clone().unwrap_or_else(|| with_stderr_tail(e, &self.stderr_tail)));
+        }
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => Err(self.closed.lock().unwrap().clone().unwrap_or_else(|| "the MCP server connection closed".to_string())),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(format!("{} timed out after {}s", method, self.timeout.as_secs_f64()))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        write_message(&self.stdin, &json!({"jsonrpc": "2.0", "method": method, "params": params}))
+            .await
+            .map_err(|e| with_stderr_tail(e, &self.stderr_tail))
+    }
+
+    /// Every tool the server offers, following `nextCursor` across pages
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>, String> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            let page: Vec<McpTool> = serde_json::from_value(result.get("tools").cloned().unwrap_or_else(|| json!([])))
+                .map_err(|e| format!("tools/list returned an invalid tool: {}", e))?;
+            tools.extend(page);
+            cursor = result.get("nextCursor").and_then(Value::as_str).map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Run `name` with `arguments`; the result holds `content` and, when the tool failed, `isError`
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+        self.request("tools/call", json!({"name": name, "arguments": arguments})).await
+    }
+
+    /// Whether the server process has exited
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for StdioConnection {
+    fn drop(&mut self) {
+        if let Ok(mut child) = self.child.try_lock() {
+            let _ = child.start_kill();
+        }
+    }
+}
+
+async fn write_message(stdin: &Writer, message: &Value) -> Result<(), String> {
+    let mut line = message.to_string();
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await.map_err(|e| format!("Could not write to the MCP server: {}", e))?;
+    stdin.flush().await.map_err(|e| format!("Could not write to the MCP server: {}", e))
+}
+
+/// The reply to a request the server sent us: `ping` is answered, anything else is declined
+fn answer_server_request(message: &Value) -> Option<Value> {
+    let method = message.get("method")?.as_str()?;
+    let id = message.get("id")?.clone();
+    Some(match method {
+        "ping" => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
+        _ => json!({"jsonrpc": "2.0", "id": id, "error": {"code": -32601, "message": format!("Method not found: {}", method)}}),
+    })
+}
+
+fn describe_rpc_error(error: &Value) -> String {
+    let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+    match error.get("code").and_then(Value::as_i64) {
+        Some(code) => format!("{} (code {})", message, code),
+        None => message.to_string(),
+    }
+}
+
+fn with_stderr_tail(message: String, tail: &StderrTail) -> String {
+    let tail = tail.lock().unwrap();
+    if tail.is_empty() {
+        message
+    } else {
+        format!("{}; last stderr output:\n{}", message, tail.iter().cloned().collect::<Vec<_>>().join("\n"))
+    }
+}
@@ -0,0 +1,35 @@
+//! The request `/commit-and-push` sends for a commit message: the staged diff and how the
+//! message should be written.
+
+/// Characters of the staged diff sent with the request; the `--stat` summary is always sent whole
+pub const MAX_DIFF_CHARS: usize = 12_000;
+
+const PROMPT: &str = "Write a git commit message for the staged changes below. Start with a summary line of at most 72 characters in the imperative mood, such as \"Add …\" or \"Fix …\", with no trailing period. If the change needs explaining, add a blank line and a short body that says what changed and why, wrapped at 72 columns. Describe only what the diff shows. Answer with the commit message only, without quotes or code fences.";
+
+const CONVENTIONAL_PROMPT: &str = "Follow the Conventional Commits format. The summary line is `type(scope): description`, where type is one of feat, fix, docs, style, refactor, perf, test, build, ci or chore, the scope is optional, and the description starts in lower case. Mark a breaking change with `!` after the type or scope and a `BREAKING CHANGE:` footer.";
+
+/// The system prompt, with the Conventional Commits rules when `conventional` is set
+pub fn system_prompt(conventional: bool) -> String {
+    if conventional {
+        format!("{} {}", PROMPT, CONVENTIONAL_PROMPT)
+    } else {
+        PROMPT.to_string()
+    }
+}
+
+/// The user message: the `--stat` summary, then the diff cut at a line end near `MAX_DIFF_CHARS`
+pub fn request(stat: &str, diff: &str) -> String {
+    let total = diff.chars().count();
+    if total <= MAX_DIFF_CHARS {
+        return format!("Files changed:\n{}\n\nDiff:\n{}", stat.trim_end(), diff.trim_end());
+    }
+    let cut = diff.char_indices().nth(MAX_DIFF_CHARS).map_or(diff.len(), |(index, _)| index);
+    let kept = &diff[..diff[..cut].rfind('\n').unwrap_or(cut)];
+    format!(
+        "Files changed:\n{}\n\nDiff (the first {} of {} characters; the summary above lists every file):\n{}",
+        stat.trim_end(),
+        kept.chars().count(),
+        total,
+        kept
+    )
+}
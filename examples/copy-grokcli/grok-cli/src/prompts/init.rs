@@ -0,0 +1,8 @@
+//! The request `/init` sends for a starter `GROK.md`
+
+pub const SYSTEM_PROMPT: &str = "Write a GROK.md file of instructions for an AI coding agent that will work in this repository. From the file listing and file excerpts below, describe in Markdown: what the project is, how the code is laid out, how to build, test and lint it, and the conventions a contributor should follow. Only state what the excerpts show or strongly imply; leave out anything you would have to guess. Keep it under 60 lines. Answer with the file's contents only, without a code fence.";
+
+/// The user message: the overview of the repository
+pub fn request(overview: &str) -> String {
+    format!("Repository overview:\n\n{}", overview)
+}
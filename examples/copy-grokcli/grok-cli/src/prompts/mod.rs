@@ -0,0 +1,18 @@
+//! Prompts for the requests the agent sends outside the conversation
+
+pub mod commit_message;
+pub mod init;
+
+/// The model's answer without surrounding blank lines, quotes or a code fence
+pub fn parse_reply(reply: &str) -> String {
+    let mut message = reply.trim();
+    if let Some(fenced) = message.strip_prefix("```") {
+        // The fence line may name a language
+        let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+        message = body.trim_end().strip_suffix("```").unwrap_or(body).trim();
+    }
+    if message.len() > 1 && message.starts_with('"') && message.ends_with('"') {
+        message = message[1..message.len() - 1].trim();
+    }
+    message.to_string()
+}
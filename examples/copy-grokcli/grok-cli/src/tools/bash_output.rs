@@ -0,0 +1,83 @@
+//! Reading a bash command's output as it runs: each line goes to an optional live listener and
+//! into a capture that keeps the start and the end of output too long for the model.
+
+use std::collections::VecDeque;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Bytes kept of each of stdout and stderr; past this only the first half and the last lines
+/// are kept
+pub const BASH_OUTPUT_LIMIT_BYTES: usize = 200 * 1024;
+
+/// One output stream, with the middle dropped once it outgrows its limit
+pub struct CapturedOutput {
+    head: String,
+    tail: VecDeque<String>,
+    tail_bytes: usize,
+    omitted_lines: usize,
+    omitted_bytes: usize,
+    limit: usize,
+}
+
+impl CapturedOutput {
+    pub fn new(limit: usize) -> Self {
+        Self { head: String::new(), tail: VecDeque::new(), tail_bytes: 0, omitted_lines: 0, omitted_bytes: 0, limit }
+    }
+
+    /// Add a line, newline included. The first half of the limit fills the head; the lines
+    /// after it stay in the tail for as long as head and tail fit in the limit together.
+    pub fn push(&mut self, line: &str) {
+        if self.tail.is_empty() && self.omitted_lines == 0 && self.head.len() + line.len() <= self.limit / 2 {
+            self.head.push_str(line);
+            return;
+        }
+        self.tail.push_back(line.to_string());
+        self.tail_bytes += line.len();
+        while self.head.len() + self.tail_bytes > self.limit {
+            let Some(dropped) = self.tail.pop_front() else { break };
+            self.tail_bytes -= dropped.len();
+            self.omitted_lines += 1;
+            self.omitted_bytes += dropped.len();
+        }
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.omitted_lines > 0
+    }
+
+    /// Everything kept, with a line saying how much was dropped between head and tail
+    pub fn into_string(self) -> String {
+        let mut text = self.head;
+        if self.omitted_lines > 0 {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&format!("… {} lines ({} bytes) of output not shown …\n", self.omitted_lines, self.omitted_bytes));
+        }
+        text.extend(self.tail);
+        text
+    }
+}
+
+/// Read `reader` to its end, line by line. Each line is also offered to `live` without its
+/// newline; lines are dropped rather than waited on when the listener falls behind.
+pub async fn read_output<R: AsyncRead + Unpin>(
+    reader: R,
+    live: Option<tokio::sync::mpsc::Sender<String>>,
+    limit: usize,
+) -> std::io::Result<CapturedOutput> {
+    let mut reader = BufReader::new(reader);
+    let mut captured = CapturedOutput::new(limit);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if let Some(live) = &live {
+            let _ = live.try_send(text.trim_end_matches(['\n', '\r']).to_string());
+        }
+        captured.push(&text);
+    }
+    Ok(captured)
+}
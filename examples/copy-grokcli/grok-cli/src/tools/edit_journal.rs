@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Where each session keeps the content files had before the agent edited them
+pub const UNDO_DIR: &str = ".grok/undo";
+
+/// One file edit the agent made, newest last
+#[derive(Debug, Clone)]
+struct JournalEntry {
+    path: PathBuf,
+    /// The path as the tool call gave it, for messages
+    shown: String,
+    /// Snapshot of the earlier content; `None` when the edit created the file
+    before: Option<PathBuf>,
+    /// What the agent left in the file. Undoing refuses when the file no longer has it.
+    after: Vec<u8>,
+}
+
+/// Snapshots of every file `TextEditorTool` changes during a session, kept under
+/// `.grok/undo/<session>/`, so the edits can be undone newest first
+#[derive(Debug, Clone)]
+pub struct EditJournal {
+    dir: PathBuf,
+    entries: Vec<JournalEntry>,
+    snapshots: usize,
+}
+
+/// What undoing the newest edit did
+#[derive(Debug, Clone, PartialEq)]
+pub enum UndoOutcome {
+    Restored(String),
+    /// The edit created the file, so undoing it deleted the file
+    Deleted(String),
+    /// The file changed after the agent wrote it; nothing was undone
+    Conflict(String),
+    NothingToUndo,
+}
+
+impl UndoOutcome {
+    pub fn describe(&self) -> String {
+        match self {
+            UndoOutcome::Restored(path) => format!("Restored {} to its content before the edit", path),
+            UndoOutcome::Deleted(path) => format!("Deleted {}, which the agent had created", path),
+            UndoOutcome::Conflict(path) => format!(
+                "Conflict: {} changed after the agent edited it, so the edit was not undone. Revert or keep the newer changes by hand.",
+                path
+            ),
+            UndoOutcome::NothingToUndo => "No file edits to undo in this session".to_string(),
+        }
+    }
+}
+
+impl EditJournal {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir: std::path::absolute(&dir).unwrap_or(dir), entries: Vec::new(), snapshots: 0 }
+    }
+
+    /// A journal for `session` under `.grok/undo/` in the current directory
+    pub fn for_session(session: &str) -> Self {
+        Self::new(Path::new(UNDO_DIR).join(session))
+    }
+
+    /// Note an edit that is about to write `after` to `path`, saving its current content
+    /// first. Call [`EditJournal::forget_last`] if the write then fails.
+    pub async fn record(
+        &mut self,
+        path: &Path,
+        shown: &str,
+        before: Option<&[u8]>,
+        after: Vec<u8>,
+    ) -> std::io::Result<()> {
+        let before = match before {
+            Some(content) => {
+                fs::create_dir_all(&self.dir).await?;
+                self.snapshots += 1;
+                let snapshot = self.dir.join(format!("{:04}.before", self.snapshots));
+                fs::write(&snapshot, content).await?;
+                Some(snapshot)
+            }
+            None => None,
+        };
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.entries.push(JournalEntry { path, shown: shown.to_string(), before, after });
+        Ok(())
+    }
+
+    pub fn forget_last(&mut self) {
+        self.entries.pop();
+    }
+
+    /// The newest edit of `path` now ends with what is on disk, e.g. after a formatter ran
+    pub async fn refresh(&mut self, path: &Path) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(entry) = self.entries.iter_mut().rev().find(|entry| entry.path == path)
+            && let Ok(content) = fs::read(&path).await
+        {
+            entry.after = content;
+        }
+    }
+
+    /// The files [`EditJournal::undo_last`] (or, with `all`, every undo) would touch
+    pub fn pending_paths(&self, all: bool) -> Vec<PathBuf> {
+        let count = if all { self.entries.len() } else { self.entries.len().min(1) };
+        self.entries.iter().rev().take(count).map(|entry| entry.path.clone()).collect()
+    }
+
+    /// Undo the newest edit, unless the file changed since the agent wrote it
+    pub async fn undo_last(&mut self) -> std::io::Result<UndoOutcome> {
+        let Some(entry) = self.entries.last() else { return Ok(UndoOutcome::NothingToUndo) };
+        let current = fs::read(&entry.path).await.ok();
+        if current.as_deref() != Some(entry.after.as_slice()) {
+            return Ok(UndoOutcome::Conflict(entry.shown.clone()));
+        }
+        let outcome = match &entry.before {
+            Some(snapshot) => {
+                fs::write(&entry.path, fs::read(snapshot).await?).await?;
+                UndoOutcome::Restored(entry.shown.clone())
+            }
+            None => {
+                fs::remove_file(&entry.path).await?;
+                UndoOutcome::Deleted(entry.shown.clone())
+            }
+        };
+        self.entries.pop();
+        Ok(outcome)
+    }
+
+    /// Undo edits newest first until none are left or one conflicts
+    pub async fn undo_all(&mut self) -> std::io::Result<Vec<UndoOutcome>> {
+        let mut outcomes = Vec::new();
+        loop {
+            match self.undo_last().await? {
+                UndoOutcome::NothingToUndo if !outcomes.is_empty() => return Ok(outcomes),
+                outcome @ (UndoOutcome::NothingToUndo | UndoOutcome::Conflict(_)) => {
+                    outcomes.push(outcome);
+                    return Ok(outcomes);
+                }
+                outcome => outcomes.push(outcome),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::TextEditorTool;
+
+    #[tokio::test]
+    async fn test_undo_walks_back_several_edits_of_one_file_and_refuses_conflicts() {
+        let dir = std::env::temp_dir().join(format!("grok-undo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        let path = file.to_str().unwrap();
+        let mut editor = TextEditorTool::new();
+        editor.set_journal(EditJournal::new(dir.join("undo")));
+
+        assert!(editor.create(path, "one\n").await.unwrap().success);
+        assert!(editor.str_replace(path, "one", "two", false, false).await.unwrap().success);
+        assert!(editor.str_replace(path, "two", "three", false, false).await.unwrap().success);
+        assert!(editor.create(path, "four\n").await.unwrap().success);
+        assert_eq!(editor.journal().pending_paths(true).len(), 4);
+
+        let undone = editor.undo_last().await.unwrap();
+        assert!(undone.success, "{:?}", undone);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "three\n");
+        assert!(editor.undo_last().await.unwrap().success);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "two\n");
+
+        // A change made outside the agent is never overwritten
+        std::fs::write(&file, "edited by hand\n").unwrap();
+        let conflict = editor.undo_last().await.unwrap();
+        assert!(!conflict.success);
+        assert!(conflict.error.unwrap().starts_with("Conflict:"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "edited by hand\n");
+
+        std::fs::write(&file, "two\n").unwrap();
+        let all = editor.undo_all().await.unwrap();
+        assert!(all.success, "{:?}", all);
+        assert!(all.output.unwrap().contains("Deleted"));
+        assert!(!file.exists());
+        assert_eq!(editor.undo_last().await.unwrap().error.unwrap(), UndoOutcome::NothingToUndo.describe());
+
+        // Undoing a create refuses when the content is not what the agent wrote
+        assert!(editor.create(path, "mine\n").await.unwrap().success);
+        std::fs::write(&file, "theirs\n").unwrap();
+        assert!(!editor.undo_last().await.unwrap().success);
+        assert!(file.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
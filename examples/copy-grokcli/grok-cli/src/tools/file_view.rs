@@ -0,0 +1,209 @@
+//! `view_file` over several files at once: glob expansion, directory listings with sizes and
+//! modified times, and the delimited listing capped at a byte budget.
+
+use std::path::{Path, PathBuf};
+
+/// Total bytes of file content one `view_file` call returns across all its files
+pub const DEFAULT_VIEW_BUDGET_BYTES: usize = 64 * 1024;
+
+/// Whether `path` should be expanded as a glob rather than read as a path
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[', '{'])
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.') && name != "." && name != ".."
+}
+
+/// Each pattern's matching files in order, without duplicates. A glob is walked from its
+/// leading literal directories and never descends into hidden entries unless `include_hidden`;
+/// a plain path is kept as it is, including a directory.
+pub fn expand_paths(patterns: &[String], include_hidden: bool) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        if !is_glob(pattern) {
+            paths.push(PathBuf::from(pattern));
+            continue;
+        }
+        let matcher = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid glob {}: {}", pattern, e))?
+            .compile_matcher();
+        let base: PathBuf = Path::new(pattern).components().take_while(|part| !is_glob(&part.as_os_str().to_string_lossy())).collect();
+        let root = if base.as_os_str().is_empty() { PathBuf::from(".") } else { base.clone() };
+
+        let mut matched: Vec<PathBuf> = walkdir::WalkDir::new(&root)
+            .into_iter()
+            .filter_entry(|entry| include_hidden || entry.depth() == 0 || !is_hidden(&entry.file_name().to_string_lossy()))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                // Walking "." yields "./x"; the pattern was written without the prefix
+                let path = entry.into_path();
+                if base.as_os_str().is_empty() { path.strip_prefix(".").map(Path::to_path_buf).unwrap_or(path) } else { path }
+            })
+            .filter(|path| matcher.is_match(path))
+            .collect();
+        if matched.is_empty() {
+            return Err(format!("No files match {}", pattern));
+        }
+        matched.sort();
+        paths.extend(matched);
+    }
+    let mut seen = std::collections::HashSet::new();
+    paths.retain(|path| seen.insert(path.clone()));
+    Ok(paths)
+}
+
+/// One line per entry, sorted, with its size and modified time; hidden entries only with
+/// `include_hidden`
+pub async fn list_directory(dir: &Path, include_hidden: bool) -> std::io::Result<Vec<String>> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut listed = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !include_hidden && is_hidden(&name) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata().await else {
+            listed.push(name);
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .map(|time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        listed.push(if metadata.is_dir() {
+            format!("{}/  (directory, modified {})", name, modified)
+        } else {
+            format!("{}  ({} bytes, modified {})", name, metadata.len(), modified)
+        });
+    }
+    listed.sort();
+    Ok(listed)
+}
+
+/// One file's part of a multi-file view
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ViewedFile {
+    pub path: String,
+    /// Lines shown, counted from 1; `None` when none were
+    pub lines: Option<(usize, usize)>,
+    pub total_lines: usize,
+    /// The budget ran out before the requested lines did
+    pub truncated: bool,
+    /// Why nothing was shown, e.g. a binary file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+/// `=== path (lines a-b) ===` sections of numbered lines. Each file shows `view_range` (or all
+/// of it) until `budget` bytes of lines are used up; the file where that happens is cut at a
+/// line with a notice, and the files after it are only named.
+pub async fn render_files(paths: &[PathBuf], view_range: Option<(usize, usize)>, include_hidden: bool, budget: usize) -> (String, Vec<ViewedFile>) {
+    let mut sections = Vec::new();
+    let mut viewed = Vec::new();
+    let mut remaining = budget;
+    for path in paths {
+        let shown = path.to_string_lossy().to_string();
+        let mut file = ViewedFile { path: shown.clone(), lines: None, total_lines: 0, truncated: false, skipped: None };
+
+        if path.is_dir() {
+            match list_directory(path, include_hidden).await {
+                Ok(entries) => sections.push(format!("=== {}/ (directory) ===\n{}", shown, entries.join("\n"))),
+                Err(e) => file.skipped = Some(e.to_string()),
+            }
+        } else if remaining == 0 {
+            file.skipped = Some("byte budget reached".to_string());
+        } else {
+            match tokio::fs::read(path).await {
+                Err(e) => file.skipped = Some(e.to_string()),
+                Ok(bytes) if bytes.contains(&0) => file.skipped = Some(format!("binary, {} bytes", bytes.len())),
+                Ok(bytes) => {
+                    let content = String::from_utf8_lossy(&bytes);
+                    let lines: Vec<&str> = content.lines().collect();
+                    file.total_lines = lines.len();
+                    let (start, end) = view_range.unwrap_or((1, lines.len()));
+                    let (start, end) = (start.max(1), end.min(lines.len()));
+
+                    let mut body = Vec::new();
+                    for number in start..=end {
+                        let line = format!("{}: {}", number, lines[number - 1]);
+                        if line.len() + 1 > remaining {
+                            file.truncated = true;
+                            break;
+                        }
+                        remaining -= line.len() + 1;
+                        body.push(line);
+                    }
+                    let last = start + body.len() - usize::from(!body.is_empty());
+                    if body.is_empty() && file.truncated {
+                        remaining = 0;
+                        file.skipped = Some("byte budget reached".to_string());
+                    } else if body.is_empty() {
+                        sections.push(format!("=== {} (empty or no lines in range; {} lines) ===", shown, lines.len()));
+                    } else {
+                        file.lines = Some((start, last));
+                        let mut section = format!("=== {} (lines {}-{} of {}) ===\n{}", shown, start, last, lines.len(), body.join("\n"));
+                        if file.truncated {
+                            remaining = 0;
+                            section.push_str(&format!(
+                                "\n… cut at the {}-byte view budget; view {} from line {} for the rest",
+                                budget,
+                                shown,
+                                last + 1
+                            ));
+                        }
+                        sections.push(section);
+                    }
+                }
+            }
+        }
+        if let Some(reason) = &file.skipped {
+            sections.push(format!("=== {} (not shown: {}) ===", shown, reason));
+        }
+        viewed.push(file);
+    }
+    (sections.join("\n\n"), viewed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_view_expands_globs_skips_hidden_files_and_cuts_at_the_budget() {
+        let dir = std::env::temp_dir().join(format!("grok-view-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/nested")).unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join("src/a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.join("src/nested/b.rs"), "fn b() {}\nfn c() {}\nfn d() {}\n").unwrap();
+        std::fs::write(dir.join("src/notes.md"), "not rust\n").unwrap();
+        std::fs::write(dir.join(".git/hook.rs"), "hidden\n").unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        let pattern = format!("{}/**/*.rs", root);
+        let paths = expand_paths(&[pattern.clone(), format!("{}/src/a.rs", root)], false).unwrap();
+        assert_eq!(paths, vec![dir.join("src/a.rs"), dir.join("src/nested/b.rs")]);
+        assert_eq!(expand_paths(std::slice::from_ref(&pattern), true).unwrap().len(), 3);
+        assert!(expand_paths(&[format!("{}/*.py", root)], false).unwrap_err().starts_with("No files match"));
+
+        let (listing, viewed) = render_files(&paths, None, false, 1024).await;
+        assert!(listing.starts_with(&format!("=== {}/src/a.rs (lines 1-1 of 1) ===\n1: fn a() {{}}\n\n", root)), "{}", listing);
+        assert!(viewed.iter().all(|file| !file.truncated));
+
+        // "1: fn a() {}\n" is 13 bytes and each line of b.rs is 13 too: b.rs is cut after its first line
+        let (listing, viewed) = render_files(&[paths[0].clone(), paths[1].clone(), paths[0].clone()], None, false, 30).await;
+        assert!(listing.contains("(lines 1-1 of 3) ===\n1: fn b() {}\n… cut at the 30-byte view budget"), "{}", listing);
+        assert!(listing.ends_with("a.rs (not shown: byte budget reached) ==="), "{}", listing);
+        assert!(viewed[1].truncated);
+
+        let entries = list_directory(&dir, false).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].starts_with("src/  (directory, modified "), "{:?}", entries);
+        assert_eq!(list_directory(&dir, true).await.unwrap().len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,150 @@
+//! Whitespace-tolerant matching for `str_replace_editor` with `fuzzy` set, used after the exact
+//! match fails. Like `CodeMatcher::find_and_replace` in the main crate, lines are compared with
+//! their whitespace normalized first and then by Levenshtein distance, but every candidate is
+//! collected so an ambiguous match is reported instead of applied.
+
+use std::ops::Range;
+
+/// Edits allowed per 10 characters of a normalized line, so short lines must match exactly
+const EDITS_PER_TEN_CHARS: usize = 1;
+
+/// What a fuzzy replacement found
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuzzyOutcome {
+    /// Exactly one run of lines matched; `content` has it replaced
+    Replaced { content: String, start_line: usize, end_line: usize },
+    /// Several runs matched, as line ranges counted from 1
+    Ambiguous(Vec<(usize, usize)>),
+    NotFound,
+}
+
+/// Replace the one run of whole lines in `content` that matches `old_str` line by line once
+/// whitespace is normalized, allowing a few edits per line. Both are LF-normalized. When the
+/// matched lines are indented differently from `old_str`, `new_str` is re-indented the same way.
+pub fn fuzzy_replace(content: &str, old_str: &str, new_str: &str) -> FuzzyOutcome {
+    // The match covers whole lines without the final newline, so a newline ending `old_str`
+    // is not part of what is matched, nor the one ending `new_str`
+    let (old_str, new_str) = match old_str.strip_suffix('\n') {
+        Some(old_str) => (old_str, new_str.strip_suffix('\n').unwrap_or(new_str)),
+        None => (old_str, new_str),
+    };
+    let wanted: Vec<String> = old_str.split('\n').map(normalize_line).collect();
+    if wanted.iter().all(String::is_empty) {
+        return FuzzyOutcome::NotFound;
+    }
+
+    let lines = line_spans(content);
+    if wanted.len() > lines.len() {
+        return FuzzyOutcome::NotFound;
+    }
+    let matches: Vec<usize> = (0..=lines.len() - wanted.len())
+        .filter(|&start| {
+            wanted
+                .iter()
+                .zip(&lines[start..start + wanted.len()])
+                .all(|(wanted, span)| lines_match(wanted, &normalize_line(&content[span.clone()])))
+        })
+        .collect();
+
+    match matches.as_slice() {
+        [] => FuzzyOutcome::NotFound,
+        [start] => {
+            let (first, last) = (&lines[*start], &lines[start + wanted.len() - 1]);
+            let replacement = reindent(new_str, indent(old_str), indent(&content[first.clone()]));
+            let mut replaced = content.to_string();
+            replaced.replace_range(first.start..last.end, &replacement);
+            FuzzyOutcome::Replaced { content: replaced, start_line: start + 1, end_line: start + wanted.len() }
+        }
+        starts => FuzzyOutcome::Ambiguous(starts.iter().map(|start| (start + 1, start + wanted.len())).collect()),
+    }
+}
+
+/// Byte range of each line, without its newline
+fn line_spans(content: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for (offset, _) in content.match_indices('\n') {
+        spans.push(start..offset);
+        start = offset + 1;
+    }
+    if start < content.len() {
+        spans.push(start..content.len());
+    }
+    spans
+}
+
+/// The line with indentation and trailing whitespace dropped and inner whitespace runs
+/// collapsed to one space
+fn normalize_line(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn lines_match(wanted: &str, actual: &str) -> bool {
+    if wanted == actual {
+        return true;
+    }
+    let allowed = wanted.chars().count().max(actual.chars().count()) / 10 * EDITS_PER_TEN_CHARS;
+    allowed > 0 && levenshtein(wanted, actual) <= allowed
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Leading whitespace of the first non-blank line
+fn indent(text: &str) -> &str {
+    let line = text.split('\n').find(|line| !line.trim().is_empty()).unwrap_or_default();
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// `text` with `from` at the start of its lines swapped for `to`
+fn reindent(text: &str, from: &str, to: &str) -> String {
+    if from == to {
+        return text.to_string();
+    }
+    text.split('\n')
+        .map(|line| match line.strip_prefix(from) {
+            Some(rest) if !line.trim().is_empty() => format!("{}{}", to, rest),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_replace_tolerates_tabs_trailing_space_and_newlines_but_not_ambiguity() {
+        let content = "fn main() {\n\tlet total = add(1, 2);  \n\tprintln!(\"{}\", total);\n}\n";
+        let outcome = fuzzy_replace(content, "    let total = add(1,2);\n    println!(\"{}\", total);\n", "    let total = add(1, 3);\n    println!(\"{}\", total);\n");
+        assert_eq!(
+            outcome,
+            FuzzyOutcome::Replaced {
+                content: "fn main() {\n\tlet total = add(1, 3);\n\tprintln!(\"{}\", total);\n}\n".to_string(),
+                start_line: 2,
+                end_line: 3,
+            }
+        );
+
+        // A file without a final newline still matches an old_str that has one
+        let FuzzyOutcome::Replaced { content, .. } = fuzzy_replace("a\n  last line", "last   line\n", "new last\n") else { panic!() };
+        assert_eq!(content, "a\n  new last");
+
+        assert_eq!(fuzzy_replace("x = 1\ny\nx = 1\n", " x  = 1 ", "x = 2"), FuzzyOutcome::Ambiguous(vec![(1, 1), (3, 3)]));
+        // Short lines get no edit tolerance
+        assert_eq!(fuzzy_replace("x = 1\n", "x = 2", "x = 3"), FuzzyOutcome::NotFound);
+        assert_eq!(fuzzy_replace("x\n", "\n", "y"), FuzzyOutcome::NotFound);
+    }
+}
@@ -0,0 +1,354 @@
+//! The walk and match behind `SearchTool`: the `ignore` crate's parallel walker, which honors
+//! `.gitignore`, `.ignore` and the global excludes, and ripgrep's `grep-searcher` and
+//! `grep-regex` for content, which handle binary files, memory maps and large files.
+
+use super::symbol_index::{match_rank, Symbol, SymbolIndexer};
+use grep_matcher::Matcher;
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{BinaryDetection, MmapChoice, Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch};
+use ignore::{WalkBuilder, WalkState};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Results returned when the call does not set `max_results`
+pub const DEFAULT_MAX_RESULTS: usize = 50;
+
+/// Upper bound of `max_results`, as in the tool schema
+pub const MAX_RESULTS_LIMIT: usize = 100;
+
+/// Longest line shown in a result; minified files would otherwise flood the output
+const MAX_LINE_CHARS: usize = 200;
+
+/// Directories never searched into: `.git` always, `node_modules` unless `no_ignore`
+const ALWAYS_SKIPPED: &str = ".git";
+const SKIPPED_UNLESS_NO_IGNORE: &str = "node_modules";
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Treat the query as a regular expression instead of literal text
+    pub regex: bool,
+    pub include_pattern: Option<String>,
+    pub exclude_pattern: Option<String>,
+    /// Type names such as `rust` or `py`, or bare extensions
+    pub file_types: Vec<String>,
+    pub exclude_files: Vec<String>,
+    pub include_hidden: bool,
+    /// Search files `.gitignore`, `.ignore` and the global excludes would skip
+    pub no_ignore: bool,
+    /// Cap on matching lines for a text search and on files for a name search
+    pub max_results: usize,
+}
+
+impl SearchOptions {
+    /// `max_results` from a tool call, defaulted and kept within the schema's 1..=100
+    pub fn clamp_max_results(max_results: Option<u32>) -> usize {
+        max_results.map_or(DEFAULT_MAX_RESULTS, |n| n as usize).clamp(1, MAX_RESULTS_LIMIT)
+    }
+}
+
+/// One matching line, with the lines on either side of it
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LineMatch {
+    pub line: usize,
+    /// Of the first match on the line, counted from 1 in bytes
+    pub column: usize,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: Vec<LineMatch>,
+}
+
+/// Matches grouped by file and sorted by path. `truncated` is set when the cap stopped the search.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct TextSearch {
+    pub files: Vec<FileMatches>,
+    pub truncated: bool,
+}
+
+/// A walker over `root` with the options' ignore rules, globs and types
+fn walker(root: &Path, options: &SearchOptions) -> Result<WalkBuilder, String> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root);
+    let negated = options.exclude_pattern.iter().chain(&options.exclude_files).map(|glob| format!("!{}", glob));
+    for glob in options.include_pattern.iter().cloned().chain(negated) {
+        overrides.add(&glob).map_err(|e| format!("Invalid glob {}: {}", glob, e))?;
+    }
+    let overrides = overrides.build().map_err(|e| e.to_string())?;
+
+    let mut types = ignore::types::TypesBuilder::new();
+    types.add_defaults();
+    let known: Vec<String> = types.definitions().iter().map(|definition| definition.name().to_string()).collect();
+    for file_type in &options.file_types {
+        // Names ripgrep does not know, such as `rs`, are taken as extensions
+        if !known.contains(file_type) {
+            types.add(file_type, &format!("*.{}", file_type)).map_err(|e| format!("Invalid file type {}: {}", file_type, e))?;
+        }
+        types.select(file_type);
+    }
+    let types = types.build().map_err(|e| e.to_string())?;
+
+    let respect_ignores = !options.no_ignore;
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(!options.include_hidden)
+        .ignore(respect_ignores)
+        .git_ignore(respect_ignores)
+        .git_global(respect_ignores)
+        .git_exclude(respect_ignores)
+        .parents(respect_ignores)
+        .require_git(false)
+        .follow_links(true)
+        .overrides(overrides)
+        .types(types)
+        .filter_entry(move |entry| {
+            let name = entry.file_name();
+            name != ALWAYS_SKIPPED && (!respect_ignores || name != SKIPPED_UNLESS_NO_IGNORE)
+        });
+    Ok(builder)
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string()
+}
+
+fn shorten(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((at, _)) => format!("{}…", &line[..at]),
+        None => line.to_string(),
+    }
+}
+
+fn line_text(bytes: &[u8]) -> String {
+    let line = String::from_utf8_lossy(bytes);
+    shorten(line.trim_end_matches(['\n', '\r']))
+}
+
+/// Collects one file's matches. The searcher reports a line next to a match as context
+/// only when it does not match itself, so the neighbours are filled in from every line seen.
+struct FileSink<'a> {
+    matcher: &'a RegexMatcher,
+    claimed: &'a AtomicUsize,
+    truncated: &'a AtomicBool,
+    cap: usize,
+    matches: Vec<LineMatch>,
+    previous: Option<(usize, String)>,
+    binary: bool,
+}
+
+impl FileSink<'_> {
+    fn saw_line(&mut self, line: usize, text: &str) {
+        if let Some(last) = self.matches.last_mut()
+            && last.line + 1 == line
+            && last.after.is_none()
+        {
+            last.after = Some(text.to_string());
+        }
+        self.previous = Some((line, text.to_string()));
+    }
+}
+
+impl Sink for FileSink<'_> {
+    type Error = std::io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, found: &SinkMatch<'_>) -> Result<bool, std::io::Error> {
+        let line = found.line_number().unwrap_or_default() as usize;
+        let text = line_text(found.bytes());
+        // Each match takes a slot first, so the cap holds across threads
+        if self.claimed.fetch_add(1, Ordering::SeqCst) >= self.cap {
+            self.truncated.store(true, Ordering::Relaxed);
+            self.saw_line(line, &text);
+            return Ok(false);
+        }
+        let column = self.matcher.find(found.bytes()).ok().flatten().map_or(0, |at| at.start());
+        let before = self.previous.take().filter(|(previous, _)| previous + 1 == line).map(|(_, text)| text);
+        self.saw_line(line, &text);
+        self.matches.push(LineMatch { line, column: column + 1, text, before, after: None });
+        Ok(true)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, std::io::Error> {
+        let line = context.line_number().unwrap_or_default() as usize;
+        self.saw_line(line, &line_text(context.bytes()));
+        Ok(true)
+    }
+
+    fn binary_data(&mut self, _searcher: &Searcher, _offset: u64) -> Result<bool, std::io::Error> {
+        self.binary = true;
+        Ok(false)
+    }
+}
+
+/// Lines under `root` matching `query`, at most `options.max_results` of them. The walk runs
+/// on several threads and stops early once the cap is reached or `cancel` is set.
+pub fn search_text(root: &Path, query: &str, options: &SearchOptions, cancel: &AtomicBool) -> Result<TextSearch, String> {
+    let matcher = RegexMatcherBuilder::new()
+        .fixed_strings(!options.regex)
+        .case_insensitive(!options.case_sensitive)
+        .word(options.whole_word)
+        .line_terminator(Some(b'\n'))
+        .build(query)
+        .map_err(|e| format!("Invalid regex {}: {}", query, e))?;
+    let mut searcher = SearcherBuilder::new();
+    searcher
+        .line_number(true)
+        .before_context(1)
+        .after_context(1)
+        .binary_detection(BinaryDetection::quit(b'\0'));
+    // SAFETY: a file truncated while it is mapped can abort the search; ripgrep takes the same risk
+    searcher.memory_map(unsafe { MmapChoice::auto() });
+
+    let found: Mutex<Vec<FileMatches>> = Mutex::new(Vec::new());
+    let claimed = AtomicUsize::new(0);
+    let truncated = AtomicBool::new(false);
+    let cap = options.max_results;
+    walker(root, options)?.build_parallel().run(|| {
+        let (matcher, found, claimed, truncated) = (&matcher, &found, &claimed, &truncated);
+        let mut searcher = searcher.build();
+        Box::new(move |entry| {
+            if cancel.load(Ordering::Relaxed) || truncated.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            let Ok(entry) = entry else { return WalkState::Continue };
+            if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                return WalkState::Continue;
+            }
+            let mut sink = FileSink { matcher, claimed, truncated, cap, matches: Vec::new(), previous: None, binary: false };
+            if searcher.search_path(matcher, entry.path(), &mut sink).is_err() || sink.binary {
+                return WalkState::Continue;
+            }
+            if !sink.matches.is_empty() {
+                found.lock().unwrap().push(FileMatches { path: relative(root, entry.path()), matches: sink.matches });
+            }
+            WalkState::Continue
+        })
+    });
+
+    let mut files = found.into_inner().unwrap();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(TextSearch { files, truncated: truncated.into_inner() })
+}
+
+/// Definitions under `root` whose name contains `query`, ignoring case: exact names first, then
+/// names in another case, prefixes and the rest, each sorted by path and line. At most
+/// `options.max_results` are returned.
+pub fn search_symbols(root: &Path, query: &str, options: &SearchOptions, cancel: &AtomicBool) -> Result<Vec<Symbol>, String> {
+    let indexer = SymbolIndexer::new();
+    let found: Mutex<Vec<(u8, Symbol)>> = Mutex::new(Vec::new());
+    walker(root, options)?.build_parallel().run(|| {
+        let (indexer, found) = (&indexer, &found);
+        Box::new(move |entry| {
+            if cancel.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            let Ok(entry) = entry else { return WalkState::Continue };
+            let extension = entry.path().extension().map(|extension| extension.to_string_lossy().to_string()).unwrap_or_default();
+            if !entry.file_type().is_some_and(|kind| kind.is_file()) || !indexer.supports(&extension) {
+                return WalkState::Continue;
+            }
+            let Ok(content) = std::fs::read_to_string(entry.path()) else { return WalkState::Continue };
+            let matching: Vec<(u8, Symbol)> = indexer
+                .symbols_in(&relative(root, entry.path()), &extension, &content)
+                .into_iter()
+                .filter_map(|symbol| Some((match_rank(&symbol.name, query)?, symbol)))
+                .collect();
+            found.lock().unwrap().extend(matching);
+            WalkState::Continue
+        })
+    });
+
+    let mut found = found.into_inner().unwrap();
+    found.sort_by(|(a_rank, a), (b_rank, b)| a_rank.cmp(b_rank).then_with(|| a.path.cmp(&b.path)).then(a.line.cmp(&b.line)));
+    Ok(found.into_iter().take(options.max_results).map(|(_, symbol)| symbol).collect())
+}
+
+/// Every file under `root` the walk visits, as (path relative to `root`, file name)
+pub fn list_files(root: &Path, options: &SearchOptions, cancel: &AtomicBool) -> Result<Vec<(String, String)>, String> {
+    let files: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+    walker(root, options)?.build_parallel().run(|| {
+        let files = &files;
+        Box::new(move |entry| {
+            if cancel.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            if let Ok(entry) = entry
+                && entry.file_type().is_some_and(|kind| kind.is_file())
+            {
+                let name = entry.file_name().to_string_lossy().to_string();
+                files.lock().unwrap().push((relative(root, entry.path()), name));
+            }
+            WalkState::Continue
+        })
+    });
+    let mut files = files.into_inner().unwrap();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_honors_gitignore_and_caps_matches_with_context() {
+        let root = std::env::temp_dir().join(format!("grok-search-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in ["src", "target/debug", "node_modules/pkg", ".hidden"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "use std::io;\nfn parse() {}\n// Parse twice\nfn reparse() {}\n").unwrap();
+        std::fs::write(root.join("src/notes.md"), "parse me\n").unwrap();
+        std::fs::write(root.join("target/debug/out.rs"), "fn parse() {}\n").unwrap();
+        std::fs::write(root.join("node_modules/pkg/index.js"), "parse()\n").unwrap();
+        std::fs::write(root.join(".hidden/x.rs"), "parse\n").unwrap();
+        std::fs::write(root.join("src/blob.bin"), b"parse\0").unwrap();
+        // A NUL far past the first block still marks the file binary
+        let mut late_nul = b"parse\n".repeat(4096);
+        late_nul.push(0);
+        std::fs::write(root.join("src/late.dat"), late_nul).unwrap();
+        let cancel = AtomicBool::new(false);
+        let options = SearchOptions { max_results: 10, ..Default::default() };
+
+        let found = search_text(&root, "parse", &options, &cancel).unwrap();
+        let paths: Vec<&str> = found.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/lib.rs", "src/notes.md"]);
+        assert_eq!(
+            found.files[0].matches[0],
+            LineMatch {
+                line: 2,
+                column: 4,
+                text: "fn parse() {}".to_string(),
+                before: Some("use std::io;".to_string()),
+                after: Some("// Parse twice".to_string()),
+            }
+        );
+        assert_eq!(found.files[0].matches.len(), 3);
+        assert!(!found.truncated);
+
+        let exact = SearchOptions { case_sensitive: true, whole_word: true, file_types: vec!["rust".to_string()], ..options.clone() };
+        let found = search_text(&root, "parse", &exact, &cancel).unwrap();
+        assert_eq!(found.files.len(), 1);
+        assert_eq!(found.files[0].matches.iter().map(|m| m.line).collect::<Vec<_>>(), vec![2]);
+
+        let everything = SearchOptions { no_ignore: true, include_hidden: true, file_types: vec!["rs".to_string()], ..options.clone() };
+        assert_eq!(search_text(&root, "parse", &everything, &cancel).unwrap().files.len(), 3);
+
+        let capped = search_text(&root, "parse", &SearchOptions { max_results: 2, ..options.clone() }, &cancel).unwrap();
+        assert!(capped.truncated);
+        assert_eq!(capped.files.iter().map(|file| file.matches.len()).sum::<usize>(), 2);
+
+        let files = list_files(&root, &options, &cancel).unwrap();
+        assert!(files.iter().all(|(path, _)| path.starts_with("src/")), "{:?}", files);
+        assert!(search_text(&root, "(", &SearchOptions { regex: true, ..options }, &cancel).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
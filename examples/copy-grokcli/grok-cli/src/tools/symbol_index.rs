@@ -0,0 +1,232 @@
+//! Definitions a `search_type: "symbols"` search looks for. The main crate's
+//! `CodeFileHandler::extract_functions` and `extract_classes` check line prefixes such as `fn `
+//! and `class `; here each language gets a table of regexes that also allow visibility,
+//! `async`, `export` and similar modifiers in front of the name.
+
+use regex::Regex;
+
+/// Longest signature kept; the rest of a long line is dropped
+const MAX_SIGNATURE_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Fn,
+    Struct,
+    Enum,
+    Trait,
+    Class,
+    Interface,
+    Type,
+}
+
+impl SymbolKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SymbolKind::Fn => "fn",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Class => "class",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Type => "type",
+        }
+    }
+}
+
+/// A definition found in a file
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub path: String,
+    pub line: usize,
+    /// The defining line, trimmed and cut before its body
+    pub signature: String,
+}
+
+struct LanguageTable {
+    extensions: &'static [&'static str],
+    /// The first capture group of each regex is the name
+    patterns: Vec<(SymbolKind, Regex)>,
+}
+
+/// Definitions of functions, types and classes in Rust, Python, TypeScript/JavaScript and Go
+pub struct SymbolIndexer {
+    languages: Vec<LanguageTable>,
+}
+
+impl Default for SymbolIndexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolIndexer {
+    pub fn new() -> Self {
+        let table = |extensions, patterns: &[(SymbolKind, &str)]| LanguageTable {
+            extensions,
+            patterns: patterns.iter().map(|(kind, pattern)| (*kind, Regex::new(pattern).expect("symbol pattern"))).collect(),
+        };
+        const RUST_VISIBILITY: &str = r"^\s*(?:pub(?:\([^)]*\))?\s+)?";
+        let rust = |rest: &str| format!("{}{}", RUST_VISIBILITY, rest);
+        let (rust_fn, rust_struct, rust_enum, rust_trait, rust_type) = (
+            rust(r#"(?:(?:const|async|unsafe|extern(?:\s+"[^"]*")?)\s+)*fn\s+([A-Za-z_][A-Za-z0-9_]*)"#),
+            rust(r"struct\s+([A-Za-z_][A-Za-z0-9_]*)"),
+            rust(r"enum\s+([A-Za-z_][A-Za-z0-9_]*)"),
+            rust(r"(?:unsafe\s+)?trait\s+([A-Za-z_][A-Za-z0-9_]*)"),
+            rust(r"type\s+([A-Za-z_][A-Za-z0-9_]*)"),
+        );
+        const TS_EXPORT: &str = r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?";
+        let ts = |rest: &str| format!("{}{}", TS_EXPORT, rest);
+        let (ts_fn, ts_arrow, ts_class, ts_interface, ts_type, ts_enum) = (
+            ts(r"(?:async\s+)?function\s*\*?\s*([A-Za-z_$][\w$]*)"),
+            ts(r"(?:const|let)\s+([A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:\([^)]*\)|[A-Za-z_$][\w$]*)\s*(?::[^=]+)?=>"),
+            ts(r"(?:abstract\s+)?class\s+([A-Za-z_$][\w$]*)"),
+            ts(r"interface\s+([A-Za-z_$][\w$]*)"),
+            ts(r"type\s+([A-Za-z_$][\w$]*)\s*(?:<[^>]*>)?\s*="),
+            ts(r"(?:const\s+)?enum\s+([A-Za-z_$][\w$]*)"),
+        );
+        Self {
+            languages: vec![
+                table(
+                    &["rs"],
+                    &[
+                        (SymbolKind::Fn, &rust_fn),
+                        (SymbolKind::Struct, &rust_struct),
+                        (SymbolKind::Enum, &rust_enum),
+                        (SymbolKind::Trait, &rust_trait),
+                        (SymbolKind::Type, &rust_type),
+                    ],
+                ),
+                table(
+                    &["py", "pyi"],
+                    &[(SymbolKind::Fn, r"^\s*(?:async\s+)?def\s+([A-Za-z_]\w*)"), (SymbolKind::Class, r"^\s*class\s+([A-Za-z_]\w*)")],
+                ),
+                table(
+                    &["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"],
+                    &[
+                        (SymbolKind::Fn, &ts_fn),
+                        (SymbolKind::Fn, &ts_arrow),
+                        (SymbolKind::Class, &ts_class),
+                        (SymbolKind::Interface, &ts_interface),
+                        (SymbolKind::Type, &ts_type),
+                        (SymbolKind::Enum, &ts_enum),
+                    ],
+                ),
+                table(
+                    &["go"],
+                    &[
+                        (SymbolKind::Fn, r"^func\s+(?:\([^)]*\)\s*)?([A-Za-z_]\w*)"),
+                        (SymbolKind::Struct, r"^\s*type\s+([A-Za-z_]\w*)\s+struct\b"),
+                        (SymbolKind::Interface, r"^\s*type\s+([A-Za-z_]\w*)\s+interface\b"),
+                    ],
+                ),
+            ],
+        }
+    }
+
+    /// Whether files with this extension are indexed
+    pub fn supports(&self, extension: &str) -> bool {
+        self.languages.iter().any(|language| language.extensions.contains(&extension))
+    }
+
+    /// Every definition in `content`, a file with `extension` shown as `path`
+    pub fn symbols_in(&self, path: &str, extension: &str, content: &str) -> Vec<Symbol> {
+        let Some(language) = self.languages.iter().find(|language| language.extensions.contains(&extension)) else {
+            return Vec::new();
+        };
+        let mut symbols = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let found = language.patterns.iter().find_map(|(kind, pattern)| Some((kind, pattern.captures(line)?.get(1)?.as_str())));
+            if let Some((kind, name)) = found {
+                symbols.push(Symbol {
+                    name: name.to_string(),
+                    kind: *kind,
+                    path: path.to_string(),
+                    line: index + 1,
+                    signature: signature(line),
+                });
+            }
+        }
+        symbols
+    }
+}
+
+fn signature(line: &str) -> String {
+    let line = line.trim();
+    let line = line.strip_suffix('{').unwrap_or(line).trim_end();
+    match line.char_indices().nth(MAX_SIGNATURE_CHARS) {
+        Some((at, _)) => format!("{}…", &line[..at]),
+        None => line.to_string(),
+    }
+}
+
+/// How well `name` answers `query`: 0 for the exact name, then the name in another case, a
+/// prefix, and anywhere in the name; `None` when it does not contain the query at all
+pub fn match_rank(name: &str, query: &str) -> Option<u8> {
+    let (lower_name, lower_query) = (name.to_lowercase(), query.to_lowercase());
+    if name == query {
+        Some(0)
+    } else if lower_name == lower_query {
+        Some(1)
+    } else if lower_name.starts_with(&lower_query) {
+        Some(2)
+    } else if lower_name.contains(&lower_query) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_indexer_finds_definitions_in_each_language() {
+        let indexer = SymbolIndexer::new();
+        let names = |extension: &str, content: &str| -> Vec<(String, SymbolKind)> {
+            indexer.symbols_in("x", extension, content).into_iter().map(|symbol| (symbol.name, symbol.kind)).collect()
+        };
+
+        let rust = names("rs", "pub(crate) async fn run() {}\nstruct Plain;\n    pub enum Mode {\n// fn not_this\nunsafe trait Raw {}\nlet fnord = 1;");
+        assert_eq!(
+            rust,
+            vec![
+                ("run".to_string(), SymbolKind::Fn),
+                ("Plain".to_string(), SymbolKind::Struct),
+                ("Mode".to_string(), SymbolKind::Enum),
+                ("Raw".to_string(), SymbolKind::Trait),
+            ]
+        );
+        assert_eq!(names("py", "class Chat(Base):\n    async def send(self):"), vec![("Chat".to_string(), SymbolKind::Class), ("send".to_string(), SymbolKind::Fn)]);
+        assert_eq!(
+            names("tsx", "export default class App {}\nexport const useChat = async (id: string) => {}\ninterface Props {}\nconst x = 1;"),
+            vec![
+                ("App".to_string(), SymbolKind::Class),
+                ("useChat".to_string(), SymbolKind::Fn),
+                ("Props".to_string(), SymbolKind::Interface),
+            ]
+        );
+        assert_eq!(
+            names("go", "func (s *Server) Serve() error {\ntype Server struct {\ntype Handler interface {"),
+            vec![
+                ("Serve".to_string(), SymbolKind::Fn),
+                ("Server".to_string(), SymbolKind::Struct),
+                ("Handler".to_string(), SymbolKind::Interface),
+            ]
+        );
+        assert!(names("c", "int main() {}").is_empty());
+
+        let symbol = &indexer.symbols_in("src/lib.rs", "rs", "\n    pub fn parse(input: &str) -> Ast {\n")[0];
+        assert_eq!((symbol.line, symbol.signature.as_str()), (2, "pub fn parse(input: &str) -> Ast"));
+        assert_eq!(serde_json::to_value(symbol).unwrap()["kind"], "fn");
+
+        assert_eq!(match_rank("ChatOrchestrator", "ChatOrchestrator"), Some(0));
+        assert_eq!(match_rank("chatorchestrator", "ChatOrchestrator"), Some(1));
+        assert_eq!(match_rank("ChatOrchestratorConfig", "chatorch"), Some(2));
+        assert_eq!(match_rank("new_chat", "Chat"), Some(3));
+        assert_eq!(match_rank("run", "Chat"), None);
+    }
+}
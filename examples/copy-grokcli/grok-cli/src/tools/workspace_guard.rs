@@ -0,0 +1,114 @@
+//! Keeps the file tools inside the workspace root. Paths are checked after symlinks are
+//! resolved, so a link inside the workspace that points out of it is refused too.
+
+use super::normalize_lexically;
+use std::path::{Component, Path, PathBuf};
+
+/// The directory `view_file`, `create_file`, `str_replace_editor` and the other file tools may
+/// read and write under
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceGuard {
+    root: PathBuf,
+}
+
+impl WorkspaceGuard {
+    pub fn new(root: &Path) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| normalize_lexically(root));
+        Self { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where `path` really points: relative to the current directory, with every symlink in the
+    /// part that exists resolved. The rest, e.g. a file about to be created, is resolved lexically.
+    pub fn resolve(path: &Path) -> PathBuf {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir().unwrap_or_default().join(path)
+        };
+        let components: Vec<Component> = absolute.components().collect();
+        for existing in (1..=components.len()).rev() {
+            let Ok(mut resolved) = components[..existing].iter().collect::<PathBuf>().canonicalize() else { continue };
+            for component in &components[existing..] {
+                match component {
+                    Component::ParentDir => {
+                        resolved.pop();
+                    }
+                    Component::Normal(part) => resolved.push(part),
+                    _ => {}
+                }
+            }
+            return resolved;
+        }
+        normalize_lexically(&absolute)
+    }
+
+    /// Why `path` may not be used; `None` when it is inside the workspace
+    pub fn violation(&self, path: &str) -> Option<String> {
+        let resolved = Self::resolve(Path::new(path));
+        if resolved.starts_with(&self.root) {
+            return None;
+        }
+        let target = if resolved == normalize_lexically(Path::new(path)) {
+            String::new()
+        } else {
+            format!(" (it resolves to {})", resolved.display())
+        };
+        Some(format!(
+            "⛔ Outside the workspace: {}{} is not under {}. Set \"allow_outside_workspace\": true in ~/.grok/user-settings.json to use paths outside it.",
+            path,
+            target,
+            self.root.display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_workspace_guard_refuses_traversal_absolute_paths_and_escaping_symlinks() {
+        let base = std::env::temp_dir().join(format!("grok-workspace-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (root, outside) = (base.join("project"), base.join("outside"));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret"), "key").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("inner")).unwrap();
+
+        let guard = WorkspaceGuard::new(&root);
+        let inside = |path: &Path| guard.violation(&path.to_string_lossy()).is_none();
+        assert!(inside(&root.join("src/main.rs")));
+        assert!(inside(&root.join("src/new/dir/file.rs")));
+        assert!(inside(&root.join("src/../README.md")));
+        assert!(inside(&root.join("inner/lib.rs")));
+
+        assert!(!inside(&root.join("src/../../outside/secret")));
+        assert!(!inside(&root.join("../project-other/file")));
+        assert!(!inside(Path::new("/etc/passwd")));
+        assert!(!inside(&root.join("escape/secret")));
+        assert!(!inside(&root.join("escape/not-yet-created")));
+
+        let error = guard.violation(&root.join("escape/secret").to_string_lossy()).unwrap();
+        assert!(error.starts_with(&format!("⛔ Outside the workspace: {}/escape/secret (it resolves to ", root.display())), "{}", error);
+        assert!(error.contains(&format!("is not under {}.", guard.root().display())), "{}", error);
+        assert!(guard.violation("/etc/passwd").unwrap().starts_with("⛔ Outside the workspace: /etc/passwd is not under "));
+
+        let mut editor = crate::tools::TextEditorTool::new();
+        editor.set_workspace_guard(Some(guard));
+        let escaping = root.join("escape/secret").to_string_lossy().to_string();
+        let viewed = editor.view(&escaping, None, false).await.unwrap();
+        assert!(!viewed.success && viewed.error.unwrap().starts_with("⛔ Outside the workspace"));
+        assert!(!editor.create(&escaping, "overwritten").await.unwrap().success);
+        assert_eq!(std::fs::read_to_string(outside.join("secret")).unwrap(), "key");
+        let created = root.join("src/new.rs").to_string_lossy().to_string();
+        assert!(editor.create(&created, "fn new() {}\n").await.unwrap().success);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
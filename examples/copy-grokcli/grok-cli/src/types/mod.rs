@@ -33,15 +33,28 @@ pub enum EditorCommandType {
     StrReplace,
     Create,
     Insert,
+    DeleteRange,
     UndoEdit,
 }
 
+/// Written as snake_case; sessions saved before that used the variant names and still load
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
 pub enum ChatEntryType {
+    #[serde(alias = "User")]
     User,
+    #[serde(alias = "Assistant")]
     Assistant,
+    #[serde(alias = "ToolResult")]
     ToolResult,
+    #[serde(alias = "ToolCall")]
     ToolCall,
+    /// A file edit or bash command waiting for the user's approval; the content is exactly what
+    /// would be written or run
+    #[serde(alias = "ConfirmationPending")]
+    ConfirmationPending,
+    /// A note from the CLI itself, such as a model switch; it is not sent to the model
+    System,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,6 +90,44 @@ pub struct GrokTool {
     pub function: GrokToolFunction,
 }
 
+/// How the model may use tools on a request
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ToolChoice {
+    /// Model decides whether to call tools
+    #[default]
+    Auto,
+    /// Model must answer without tools
+    None,
+    /// Model must call at least one tool
+    Required,
+    /// Model must call this tool
+    Function(String),
+}
+
+impl ToolChoice {
+    /// Parse `auto`, `none`, `required`, or a tool name
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "" => Err("Expected auto, none, required, or a tool name".to_string()),
+            "auto" => Ok(ToolChoice::Auto),
+            "none" => Ok(ToolChoice::None),
+            "required" | "any" => Ok(ToolChoice::Required),
+            name => Ok(ToolChoice::Function(name.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for ToolChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolChoice::Auto => write!(f, "auto"),
+            ToolChoice::None => write!(f, "none"),
+            ToolChoice::Required => write!(f, "required"),
+            ToolChoice::Function(name) => write!(f, "{}", name),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GrokToolFunction {
     pub name: String,
@@ -119,6 +170,20 @@ pub struct StreamingChunk {
     pub tool_result: Option<ToolResult>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token_count: Option<u32>,
+    /// Prompt and completion tokens of a `TokenCount` chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+/// Token counts the provider reports for one request, the `usage` object of a response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -128,4 +193,11 @@ pub enum StreamingChunkType {
     ToolResult,
     Done,
     TokenCount,
+    /// The response that follows was served from the response cache
+    CacheHit,
+    /// The response stopped at the output length limit; carries the text streamed so far
+    Truncated,
+    /// A line for the transcript that the model did not write, e.g. an explain-mode note or
+    /// the warning that stopped the tool rounds
+    Note,
 }
\ No newline at end of file
@@ -0,0 +1,535 @@
+//! The chat UI's slash commands. They are registered with the command registry the main crate's
+//! editor UI also uses, which gives the hints, parses the arguments and runs what Enter submits.
+//! Custom commands from settings and `.grok/commands/*.md` are registered after the built-ins.
+
+use super::{edit_in_external_editor, git_command, model_picker, policy, save_replaced_attempt, save_session, ChatState};
+use crate::agent::GrokAgent;
+use crate::types::{ChatEntryType, ToolChoice};
+use command_registry::{CommandArgs, CommandFuture, CommandOutcome, CommandRegistry, SlashCommand};
+use crate::utils::custom_commands::CustomCommands;
+use crate::utils::project_context;
+use crate::utils::transcript::TranscriptFormat;
+use ratatui::{prelude::CrosstermBackend, Terminal};
+
+/// What a command can use and change while it runs
+pub struct CommandContext<'a> {
+    pub agent: &'a mut GrokAgent,
+    pub state: &'a mut ChatState,
+    pub terminal: &'a mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    /// A response is streaming; commands that change the conversation wait for it
+    pub streaming: bool,
+}
+
+type Run = for<'a, 'c> fn(&'a mut CommandContext<'c>, &'a CommandArgs) -> CommandFuture<'a>;
+
+/// A built-in command: its help entry, the values its first argument completes to, and its handler
+struct Builtin {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    completions: &'static [&'static str],
+    run: Run,
+}
+
+impl<'c> SlashCommand<CommandContext<'c>> for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn usage(&self) -> &str {
+        self.usage
+    }
+
+    fn complete(&self, args: &str) -> Vec<String> {
+        if args.contains(char::is_whitespace) {
+            return Vec::new();
+        }
+        self.completions.iter().filter(|value| value.starts_with(args)).map(|value| value.to_string()).collect()
+    }
+
+    fn execute<'a>(&'a self, ctx: &'a mut CommandContext<'c>, args: &'a CommandArgs) -> CommandFuture<'a> {
+        (self.run)(ctx, args)
+    }
+}
+
+const fn builtin(name: &'static str, usage: &'static str, description: &'static str, completions: &'static [&'static str], run: Run) -> Builtin {
+    Builtin { name, usage, description, completions, run }
+}
+
+fn builtins() -> Vec<Builtin> {
+    vec![
+        builtin("help", "", "Show this help message", &[], help),
+        builtin("clear", "", "Clear chat history", &[], clear),
+        builtin("status", "", "Show application status", &[], status),
+        builtin("model", "", "Show current model", &[], model),
+        builtin("models", "", "Pick a model from the provider's list (Shift+Enter also saves it as the default)", &[], models),
+        builtin("tool-choice", "[auto|none|required|<tool>]", "Show or set tool use", &["auto", "none", "required"], tool_choice),
+        builtin("replay-tool", "<name> <json-args>", "Run one tool directly and show its result", &[], replay_tool),
+        builtin("window", "[n|off]", "Send only the last n turns to the model", &["off"], window),
+        builtin("regenerate", "[temperature]", "Drop the last response and ask again, optionally at another temperature", &[], regenerate),
+        builtin(
+            "continue",
+            "",
+            "Ask for the rest of a reply that was cut off at the length limit (or press Enter on an empty prompt)",
+            &[],
+            continue_reply,
+        ),
+        builtin("explain", "[on|off]", "Toggle an \"About to …\" note before tool calls the model did not explain", &["on", "off"], explain),
+        builtin("cache", "[stats|clear]", "Show response cache entries, size and hit rate, or delete them", &["stats", "clear"], cache),
+        builtin("settings", "", "Show the resolved api key, base URL and model, and where each came from", &[], settings),
+        builtin("system", "[edit]", "Show the system prompt and its size, or edit it in $EDITOR for this session", &["edit"], system),
+        builtin("policy", "[list|add|remove|test]", "Show, edit or test the dangerous command rules", &["list", "add", "remove", "test"], policy_command),
+        builtin("save", "", "Save this session now; /resume <id> continues a saved one", &[], save),
+        builtin("export", "[md|html] [path]", "Write the transcript as Markdown or a self-contained HTML page", &["md", "html"], export),
+        builtin("commit-and-push", "", "Commit the staged changes with an AI-written message and push them", &[], commit_and_push),
+        builtin("init", "[--force]", "Write a starter GROK.md from the repository structure (--force replaces one)", &["--force"], init),
+        builtin("reload-context", "", "Read GROK.md / AGENTS.md again and update the project instructions", &[], reload_context),
+        builtin("undo", "[all]", "Undo the agent's last file edit, or every edit this session", &["all"], undo),
+        builtin("resume", "<id>", "Continue a saved session", &[], resume),
+        builtin("exit", "", "Exit the application", &[], exit),
+    ]
+}
+
+/// The built-in commands, then the custom ones. `CustomCommands` already dropped the custom
+/// commands that would shadow a built-in, so registering them cannot fail.
+pub fn registry<'c>(custom: &CustomCommands) -> CommandRegistry<CommandContext<'c>> {
+    let mut registry = CommandRegistry::new();
+    for command in builtins() {
+        let _ = registry.register(command);
+    }
+    for command in custom.commands() {
+        let _ = registry.register(command.clone());
+    }
+    registry
+}
+
+fn reply<'a>(text: impl Into<String>) -> CommandFuture<'a> {
+    Box::pin(std::future::ready(CommandOutcome::Reply(text.into())))
+}
+
+fn help<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    let lines = registry(&ctx.state.custom_commands).help_lines();
+    let (builtin, custom) = lines.split_at(builtins().len());
+    let mut help = format!(
+        "Available commands:\n{}\n\n\
+        Esc / Ctrl+X - Stop the running response after the current tool call (Esc exits when nothing is running)\n\
+        Shift+Tab - Toggle auto-edit mode (apply file edits without asking)\n\
+        Ctrl+T - Show or hide the todo list panel",
+        builtin.join("\n")
+    );
+    if !custom.is_empty() {
+        help.push_str(&format!("\n\nCustom commands:\n{}", custom.join("\n")));
+    }
+    reply(help)
+}
+
+fn clear<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    ctx.state.chat_history.clear();
+    Box::pin(std::future::ready(CommandOutcome::Done))
+}
+
+fn status<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    let agent = &ctx.agent;
+    reply(format!(
+        "Status: Running\nModel: {}\nTool rounds: {} ({})\n{}\n{}\nReady for input.",
+        agent.get_current_model(),
+        agent.max_tool_rounds(),
+        agent.tool_rounds_source(),
+        agent.workspace_restriction(),
+        agent.get_usage().describe()
+    ))
+}
+
+fn model<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(format!("Current model: {}. Use /models to switch.", ctx.agent.get_current_model()))
+}
+
+fn models<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        if ctx.streaming {
+            return CommandOutcome::Reply("Wait for the current response to finish before switching models.".to_string());
+        }
+        let listed = ctx.agent.list_models().await.map_err(|e| e.to_string());
+        let configured = match crate::utils::settings_manager::get_settings_manager().await {
+            Ok(manager) => manager.get_available_models().await,
+            Err(_) => Vec::new(),
+        };
+        let picker = model_picker::ModelPicker::new(listed, configured, ctx.agent.get_current_model());
+        let text = picker.describe();
+        ctx.state.model_picker = Some(picker);
+        CommandOutcome::Reply(text)
+    })
+}
+
+fn tool_choice<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        let value = args.raw();
+        if value.is_empty() {
+            return CommandOutcome::Reply(format!("Tool choice: {}", ctx.agent.get_tool_choice()));
+        }
+        let tool_names = ctx.agent.tool_names().await;
+        CommandOutcome::Reply(match ToolChoice::parse(value) {
+            Ok(ToolChoice::Function(name)) if !tool_names.contains(&name) => {
+                format!("Unknown tool: {}. Available: {}", name, tool_names.join(", "))
+            }
+            Ok(choice) => {
+                ctx.agent.set_tool_choice(choice);
+                format!("Tool choice set to: {}", ctx.agent.get_tool_choice())
+            }
+            Err(e) => e,
+        })
+    })
+}
+
+fn replay_tool<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        // The JSON arguments are passed on as typed, quotes and all
+        CommandOutcome::Reply(match args.raw().split_once(char::is_whitespace) {
+            Some((name, arguments)) => match ctx.agent.replay_tool(name, arguments.trim()).await {
+                Ok(result) => serde_json::to_string_pretty(&result).unwrap_or_default(),
+                Err(e) => format!("Replay failed: {}", e),
+            },
+            None => "Usage: /replay-tool <name> <json-args>".to_string(),
+        })
+    })
+}
+
+fn window<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    let agent = &mut ctx.agent;
+    reply(match args.raw() {
+        "" => match agent.get_history_window() {
+            Some(n) => format!("History window: last {} turns", n),
+            None => "History window: off (full history is sent)".to_string(),
+        },
+        "off" | "0" => {
+            agent.set_history_window(None);
+            "History window off: the full history is sent".to_string()
+        }
+        n => match n.parse::<usize>() {
+            Ok(n) => {
+                agent.set_history_window(Some(n));
+                format!("History window set: last {} turns plus the system prompt", n)
+            }
+            Err(_) => "Usage: /window [n|off]".to_string(),
+        },
+    })
+}
+
+/// `/regenerate [temperature]`: the temperature, if one was given
+fn parse_regenerate(args: &CommandArgs) -> Result<Option<f64>, String> {
+    match args.words() {
+        [] => Ok(None),
+        [value] => match value.parse::<f64>() {
+            Ok(t) if (0.0..=2.0).contains(&t) => Ok(Some(t)),
+            _ => Err("Usage: /regenerate [temperature between 0 and 2]".to_string()),
+        },
+        _ => Err("Usage: /regenerate [temperature between 0 and 2]".to_string()),
+    }
+}
+
+/// Drop the last reply and send its user message again
+fn regenerate<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        let temperature = match parse_regenerate(args) {
+            Ok(_) if ctx.streaming => {
+                return CommandOutcome::Reply("Wait for the current response to finish before regenerating.".to_string());
+            }
+            Ok(temperature) => temperature,
+            Err(usage) => return CommandOutcome::Reply(usage),
+        };
+        let Some(message) = ctx.agent.pop_last_turn() else {
+            return CommandOutcome::Reply("Nothing to regenerate yet.".to_string());
+        };
+        save_replaced_attempt(ctx.agent, &ctx.state.chat_history).await;
+        let history = &mut ctx.state.chat_history;
+        if let Some(idx) = history.iter().rposition(|e| matches!(e.entry_type, ChatEntryType::User)) {
+            history.truncate(idx);
+        }
+        ctx.state.regenerate_temperature = temperature;
+        CommandOutcome::Prompts(vec![message])
+    })
+}
+
+/// Only reached when there is nothing to continue; the UI streams the rest of a cut-off reply itself
+fn continue_reply<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    if ctx.streaming {
+        reply("Wait for the current response to finish before continuing it.")
+    } else {
+        reply("Nothing to continue: the last reply was not cut off.")
+    }
+}
+
+fn explain<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    let enabled = match args.raw() {
+        "" => Some(!ctx.agent.explain_tools()),
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    };
+    reply(match enabled {
+        Some(true) => {
+            ctx.agent.set_explain_tools(true);
+            "Explain mode on: tool calls the model does not explain get an \"About to …\" note first".to_string()
+        }
+        Some(false) => {
+            ctx.agent.set_explain_tools(false);
+            "Explain mode off".to_string()
+        }
+        None => "Usage: /explain [on|off]".to_string(),
+    })
+}
+
+fn cache<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(match (ctx.agent.response_cache(), args.raw()) {
+        (None, _) => "The response cache is off. Enable it with \"response_cache\": {\"enabled\": true} in ~/.grok/user-settings.json or --cache-dir <path>.".to_string(),
+        (Some(cache), "" | "stats") => format!("{}\nDirectory: {}", cache.stats().describe(), cache.dir().display()),
+        (Some(cache), "clear") => format!("Removed {} cached responses.", cache.clear()),
+        _ => "Usage: /cache [stats|clear]".to_string(),
+    })
+}
+
+fn settings<'a>(_ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    let lines = crate::utils::config_sources::resolved_lines();
+    reply(format!("Configuration (flag > environment / .env > project settings > user settings):\n{}", lines.join("\n")))
+}
+
+fn system<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(match args.raw() {
+        "" => {
+            let prompt = ctx.agent.system_prompt();
+            format!(
+                "System prompt (~{} tokens, {} characters). /system edit opens it in $EDITOR.\n\n{}",
+                crate::agent::estimate_tokens(prompt),
+                prompt.chars().count(),
+                prompt
+            )
+        }
+        "edit" if ctx.streaming => "Wait for the current response to finish before editing the system prompt.".to_string(),
+        "edit" => {
+            let current = ctx.agent.system_prompt().to_string();
+            match edit_in_external_editor(ctx.terminal, &current) {
+                Ok(edited) if edited.trim().is_empty() => "The edited system prompt is empty; keeping the current one.".to_string(),
+                Ok(edited) if edited.trim_end() == current.trim_end() => "System prompt unchanged.".to_string(),
+                Ok(edited) => {
+                    let edited = edited.trim_end();
+                    ctx.agent.set_system_prompt(edited);
+                    format!(
+                        "System prompt updated for this session (~{} tokens, was ~{}).",
+                        crate::agent::estimate_tokens(edited),
+                        crate::agent::estimate_tokens(&current)
+                    )
+                }
+                Err(e) => e,
+            }
+        }
+        _ => "Usage: /system [edit]".to_string(),
+    })
+}
+
+fn policy_command<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        let (builtin, user, mode) = ctx.agent.dangerous_command_policy();
+        CommandOutcome::Reply(match policy::parse(args.raw()) {
+            Ok(policy::PolicyCommand::List) => policy::describe(builtin, user, mode),
+            Ok(policy::PolicyCommand::Test(command)) => policy::test(builtin, user, mode, &command),
+            Ok(change) => match policy::apply(builtin, user, &change) {
+                Ok(rules) => {
+                    let saved = policy::save_user_rules(&rules).await;
+                    ctx.agent.set_user_dangerous_command_rules(rules);
+                    let done = match &change {
+                        policy::PolicyCommand::Add(rule) => format!("Added rule '{}' (`{}`).", rule.name, rule.pattern),
+                        _ => "Removed the rule.".to_string(),
+                    };
+                    match saved {
+                        Ok(()) => format!("{} Saved to ~/.grok/user-settings.json.", done),
+                        Err(e) => format!("{} It applies to this session only; saving the settings failed: {}", done, e),
+                    }
+                }
+                Err(reason) => reason,
+            },
+            Err(usage) => usage,
+        })
+    })
+}
+
+fn save<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        CommandOutcome::Reply(match crate::utils::session_store::SessionStore::new() {
+            Ok(store) => match store.save(&ctx.agent.session_file(&ctx.state.chat_history)).await {
+                Ok(true) => format!(
+                    "Saved session {id} to .grok/sessions/{id}.json. Continue it later with /resume {id} or grok --resume {id}.",
+                    id = ctx.agent.session_id()
+                ),
+                Ok(false) => "Nothing to save yet: the session has no messages.".to_string(),
+                Err(e) => format!("Failed to save the session: {}", e),
+            },
+            Err(e) => format!("Failed to save the session: {}", e),
+        })
+    })
+}
+
+/// `/export [md|html] [path]`: the format, and the path if one was given. A path alone picks
+/// the format from its extension.
+fn parse_export(args: &CommandArgs) -> Result<(TranscriptFormat, Option<std::path::PathBuf>), String> {
+    match args.words() {
+        [] => Ok((TranscriptFormat::Markdown, None)),
+        [word] => match TranscriptFormat::parse(word) {
+            Some(format) => Ok((format, None)),
+            None => Ok((TranscriptFormat::for_path(std::path::Path::new(word)), Some(word.into()))),
+        },
+        [format, path] => match TranscriptFormat::parse(format) {
+            Some(format) => Ok((format, Some(path.into()))),
+            None => Err("Usage: /export [md|html] [path]".to_string()),
+        },
+        _ => Err("Usage: /export [md|html] [path]".to_string()),
+    }
+}
+
+fn export<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        CommandOutcome::Reply(match parse_export(args) {
+            Ok((format, path)) => match ctx.agent.export_transcript(&ctx.state.chat_history, format, path.as_deref()).await {
+                Ok(path) => format!("Exported the transcript to {}.", path.display()),
+                Err(e) => format!("Failed to export the transcript: {}", e),
+            },
+            Err(usage) => usage,
+        })
+    })
+}
+
+fn commit_and_push<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        if ctx.streaming {
+            return CommandOutcome::Reply("Wait for the current response to finish before committing.".to_string());
+        }
+        let dir = std::env::current_dir().unwrap_or_default();
+        CommandOutcome::Reply(git_command::start(ctx.agent, &mut ctx.state.pending_commit, &dir).await)
+    })
+}
+
+fn init<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        let force = match args.raw() {
+            "" => false,
+            "--force" => true,
+            _ => return CommandOutcome::Reply("Usage: /init [--force]".to_string()),
+        };
+        if ctx.streaming {
+            return CommandOutcome::Reply("Wait for the current response to finish before running /init.".to_string());
+        }
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let path = cwd.join("GROK.md");
+        if path.exists() && !force {
+            return CommandOutcome::Reply(format!(
+                "{} already exists. Edit it and run /reload-context, or /init --force to replace it.",
+                path.display()
+            ));
+        }
+        let overview = project_context::repository_overview(&cwd);
+        CommandOutcome::Reply(match ctx.agent.generate_project_instructions(&overview).await {
+            Ok(text) if text.is_empty() => "The model answered with nothing, so no GROK.md was written.".to_string(),
+            Ok(text) => match tokio::fs::write(&path, format!("{}\n", text)).await {
+                Ok(()) => format!(
+                    "Wrote {} ({} lines). Its instructions apply from now on; edit it and run /reload-context after a change.\n\n{}",
+                    path.display(),
+                    text.lines().count(),
+                    ctx.agent.reload_project_context().describe()
+                ),
+                Err(e) => format!("Failed to write {}: {}", path.display(), e),
+            },
+            Err(e) => format!("Could not get a GROK.md from the model: {}", e),
+        })
+    })
+}
+
+fn reload_context<'a>(ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    if ctx.streaming {
+        return reply("Wait for the current response to finish before reloading the project instructions.");
+    }
+    reply(ctx.agent.reload_project_context().describe())
+}
+
+fn undo<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        CommandOutcome::Reply(match args.raw() {
+            _ if ctx.streaming => "Wait for the current response to finish before undoing edits.".to_string(),
+            scope @ ("" | "all") => {
+                let result = ctx.agent.undo_edits(scope == "all").await;
+                result.output.or(result.error).unwrap_or_default()
+            }
+            _ => "Usage: /undo [all]".to_string(),
+        })
+    })
+}
+
+fn resume<'a>(ctx: &'a mut CommandContext<'_>, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        let id = args.raw();
+        if ctx.streaming {
+            return CommandOutcome::Reply("Wait for the current response to finish before resuming another session.".to_string());
+        }
+        if id.is_empty() {
+            return CommandOutcome::Reply("Usage: /resume <id>. Run `grok history` to list saved sessions.".to_string());
+        }
+        let loaded = match crate::utils::session_store::SessionStore::new() {
+            Ok(store) => store.load(id).await,
+            Err(e) => Err(e),
+        };
+        CommandOutcome::Reply(match loaded {
+            Ok((meta, session)) => {
+                // The current session stays available under its own id
+                save_session(ctx.agent, &ctx.state.chat_history).await;
+                ctx.state.chat_history = session.entries.clone();
+                ctx.state.truncated_reply = None;
+                ctx.agent.restore_session(session);
+                format!("Resumed session {} ({} turns with {}).", meta.id, meta.turn_count, meta.model)
+            }
+            Err(e) => e.to_string(),
+        })
+    })
+}
+
+fn exit<'a>(_ctx: &'a mut CommandContext<'_>, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(std::future::ready(CommandOutcome::Exit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::custom_commands::BUILTIN_COMMANDS;
+
+    #[test]
+    fn test_builtin_commands_hint_complete_and_suggest() {
+        // Custom commands are checked against this list before any registry exists
+        for command in builtins() {
+            assert!(BUILTIN_COMMANDS.contains(&command.name), "/{} is missing from BUILTIN_COMMANDS", command.name);
+        }
+
+        let registry = registry(&CustomCommands::default());
+        assert_eq!(
+            registry.hints("/ex"),
+            vec![
+                "/explain - Toggle an \"About to …\" note before tool calls the model did not explain",
+                "/export - Write the transcript as Markdown or a self-contained HTML page",
+                "/exit - Exit the application",
+            ]
+        );
+        assert_eq!(registry.hints("/export "), vec!["/export md", "/export html"]);
+        assert_eq!(registry.hints("/export md out"), Vec::<String>::new());
+        assert_eq!(
+            registry.resolve("/regenrate").unwrap().err().unwrap().to_string(),
+            "Unknown command: /regenrate. Did you mean /regenerate?"
+        );
+
+        let (_, args) = registry.resolve("/export html \"my notes.html\"").unwrap().unwrap();
+        assert_eq!(parse_export(&args), Ok((TranscriptFormat::Html, Some("my notes.html".into()))));
+        let (_, args) = registry.resolve("/export notes.md").unwrap().unwrap();
+        assert_eq!(parse_export(&args), Ok((TranscriptFormat::Markdown, Some("notes.md".into()))));
+        let (_, args) = registry.resolve("/regenerate 3").unwrap().unwrap();
+        assert!(parse_regenerate(&args).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! The `@` mention popup. Workspace files are listed on a background thread with the `ignore`
+//! walker and matched against what follows the `@` with the fzf-style scorer the main crate's
+//! editor UI uses for its own `@` mentions.
+
+#[path = "../../../../../src/utils/fuzzy_score.rs"]
+mod fuzzy_score;
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Files the index keeps; a larger workspace is matched on the first this many of its files
+pub const MAX_INDEXED_FILES: usize = 50_000;
+
+/// Matches the popup shows
+pub const VISIBLE_FILES: usize = 8;
+
+/// An index older than this is walked again when a new mention starts
+const REFRESH_AFTER: Duration = Duration::from_secs(30);
+
+const SPINNER: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// A file the popup offers, and which of its characters the query matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMatch {
+    /// Relative to the workspace root
+    pub path: String,
+    /// Char indices into `path`
+    pub positions: Vec<usize>,
+}
+
+/// The workspace's files, relative to its root
+pub struct FileIndex {
+    root: PathBuf,
+    files: Vec<String>,
+    /// The last walk stopped at `MAX_INDEXED_FILES`
+    capped: bool,
+    /// The walk that is running and when it started
+    indexing: Option<(mpsc::Receiver<Vec<String>>, Instant)>,
+    indexed_at: Option<Instant>,
+}
+
+impl FileIndex {
+    /// An index of `root`, walked in the background from now on
+    pub fn new(root: PathBuf) -> Self {
+        let mut index = Self { root, files: Vec::new(), capped: false, indexing: None, indexed_at: None };
+        index.refresh();
+        index
+    }
+
+    /// Walk the workspace again. The files of the last walk are matched until it finishes.
+    pub fn refresh(&mut self) {
+        if self.indexing.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let root = self.root.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(walk(&root, MAX_INDEXED_FILES));
+        });
+        self.indexing = Some((receiver, Instant::now()));
+    }
+
+    /// Walk again if the index is older than `REFRESH_AFTER`, e.g. as a new mention starts
+    pub fn refresh_if_stale(&mut self) {
+        if self.indexed_at.is_none_or(|at| at.elapsed() >= REFRESH_AFTER) {
+            self.refresh();
+        }
+    }
+
+    /// Take the files of a walk that finished; true when the index changed
+    pub fn poll(&mut self) -> bool {
+        let Some((receiver, _)) = &self.indexing else {
+            return false;
+        };
+        match receiver.try_recv() {
+            Ok(files) => {
+                self.capped = files.len() >= MAX_INDEXED_FILES;
+                self.files = files;
+                self.indexing = None;
+                self.indexed_at = Some(Instant::now());
+                true
+            }
+            Err(mpsc::TryRecvError::Empty) => false,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.indexing = None;
+                false
+            }
+        }
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.indexing.is_some()
+    }
+
+    /// The best `VISIBLE_FILES` files for `query`, or the first ones when it is empty
+    pub fn matches(&self, query: &str) -> Vec<FileMatch> {
+        fuzzy_score::rank(&self.files, query, VISIBLE_FILES)
+            .into_iter()
+            .map(|(index, found)| FileMatch { path: self.files[index].clone(), positions: found.positions })
+            .collect()
+    }
+
+    /// The popup's title, with a spinner while a walk runs
+    pub fn title(&self) -> String {
+        match &self.indexing {
+            Some((_, started)) => {
+                let frame = SPINNER[(started.elapsed().as_millis() / 100) as usize % SPINNER.len()];
+                format!("{} Indexing files…", frame)
+            }
+            None if self.capped => format!("Files (first {} indexed, Tab to insert)", MAX_INDEXED_FILES),
+            None => format!("Files ({}, Tab to insert)", self.files.len()),
+        }
+    }
+}
+
+/// `@path` with the matched characters of the path in `matched` and the rest in `base`
+pub fn highlighted(file: &FileMatch, base: Style, matched: Style) -> Line<'static> {
+    let mut spans = vec![Span::styled("@", base)];
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (index, c) in file.path.chars().enumerate() {
+        let is_matched = file.positions.binary_search(&index).is_ok();
+        if is_matched != run_matched && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), if run_matched { matched } else { base }));
+        }
+        run_matched = is_matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, if run_matched { matched } else { base }));
+    }
+    Line::from(spans)
+}
+
+/// The style of matched characters on top of a hint's own style
+pub fn matched_style(base: Style) -> Style {
+    base.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+}
+
+/// Files under `root` that `.gitignore` and the other ignore files let through, skipping hidden
+/// ones, sorted and at most `limit` of them
+fn walk(root: &Path, limit: usize) -> Vec<String> {
+    let mut files = Vec::new();
+    for entry in ignore::WalkBuilder::new(root).build().flatten() {
+        if entry.file_type().is_some_and(|kind| kind.is_file()) {
+            files.push(entry.path().strip_prefix(root).unwrap_or(entry.path()).to_string_lossy().to_string());
+            if files.len() >= limit {
+                break;
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_index_walks_in_the_background_and_ranks_paths() {
+        let root = std::env::temp_dir().join(format!("grok-file-picker-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in ["src/ui", "target"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["src/main.rs", "src/domain.rs", "src/ui/mod.rs", "target/main.o", "README.md"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        std::fs::write(root.join(".ignore"), "target/\n").unwrap();
+
+        let mut index = FileIndex::new(root.clone());
+        assert!(index.is_indexing());
+        assert!(index.title().ends_with("Indexing files…"));
+        let started = Instant::now();
+        while !index.poll() {
+            assert!(started.elapsed() < Duration::from_secs(10), "the walk did not finish");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(index.title(), "Files (4, Tab to insert)");
+
+        let paths = |query: &str| index.matches(query).into_iter().map(|file| file.path).collect::<Vec<_>>();
+        assert_eq!(paths("main"), vec!["src/main.rs", "src/domain.rs"]);
+        assert_eq!(paths("ui mod"), vec!["src/ui/mod.rs"]);
+        assert_eq!(paths("").len(), 4);
+
+        let file = index.matches("mr").into_iter().find(|file| file.path == "src/main.rs").unwrap();
+        assert_eq!(file.positions, vec![4, 9]);
+        let base = Style::default();
+        let line = highlighted(&file, base, matched_style(base));
+        let text: Vec<&str> = line.spans.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(text, vec!["@", "src/", "m", "ain.", "r", "s"]);
+        assert_eq!(line.spans[2].style, matched_style(base));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
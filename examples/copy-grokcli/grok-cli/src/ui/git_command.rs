@@ -0,0 +1,303 @@
+//! `/commit-and-push`: commit what is staged with a message the model writes from the diff, then
+//! push. The commit and the push run as bash tool calls, so their output streams into the chat
+//! and ends as a tool result like any other command.
+
+use crate::agent::GrokAgent;
+use crate::tools::BashTool;
+use crate::types::{GrokToolCall, GrokToolCallFunction, ToolResult};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The repository the current directory is in, its branch and where that branch pushes to
+#[derive(Debug, Clone)]
+pub struct GitCommand {
+    root: PathBuf,
+    git_dir: PathBuf,
+    branch: String,
+    push: Option<PushTarget>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PushTarget {
+    /// The branch's upstream, such as `origin/main`
+    Upstream(String),
+    /// The branch has no upstream yet: push to this remote and make it the upstream
+    Remote(String),
+}
+
+/// Counts from `git status --porcelain`
+#[derive(Debug, Default, PartialEq)]
+struct Status {
+    staged: usize,
+    /// Tracked files with changes that are not staged
+    unstaged: usize,
+    untracked: usize,
+}
+
+/// A `/commit-and-push` waiting for the user
+pub enum PendingCommit {
+    /// Nothing is staged; y stages the changed tracked files and asks the model for a message
+    Stage(GitCommand),
+    /// The model's message; y commits and pushes, c only commits, e edits it, n cancels
+    Message { git: GitCommand, message: String },
+}
+
+impl PendingCommit {
+    /// The question shown in place of the input
+    pub fn prompt(&self) -> String {
+        match self {
+            PendingCommit::Stage(_) => "Stage all changed tracked files (git add -u) and write a commit message? [y]es  [n]o".to_string(),
+            PendingCommit::Message { git, .. } => match git.push_target() {
+                Some(target) => format!("Commit and push to {}? [y]es  [c]ommit only  [e]dit message  [n]o", target),
+                None => "Commit? The branch has no remote to push to. [y]es  [e]dit message  [n]o".to_string(),
+            },
+        }
+    }
+}
+
+/// A git command run like a bash tool call
+pub struct GitStep {
+    pub call: GrokToolCall,
+    command: String,
+    push: bool,
+}
+
+impl GitStep {
+    fn new(id: &str, command: String, push: bool) -> Self {
+        let call = GrokToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction {
+                name: "bash".to_string(),
+                arguments: serde_json::json!({ "command": command }).to_string(),
+            },
+        };
+        Self { call, command, push }
+    }
+
+    /// Run the command, sending each line it prints to `lines`
+    pub async fn run(&self, lines: tokio::sync::mpsc::Sender<String>) -> ToolResult {
+        let mut result = match BashTool::new().execute_streaming(&self.command, None, Some(lines)).await {
+            Ok(result) => result,
+            Err(e) => ToolResult { success: false, output: None, error: Some(format!("Could not run git: {}", e)), data: None },
+        };
+        if self.push && !result.success {
+            let error = result.error.get_or_insert_with(String::new);
+            if error.contains("[rejected]") || error.contains("non-fast-forward") {
+                error.push_str("\nThe remote has commits this branch does not. The commit is made; pull them (git pull --rebase), then run git push.");
+            }
+        }
+        result
+    }
+}
+
+/// `git <args>` in `dir`: its output, or what it printed on stderr
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Could not run git: {}. Is it installed and on PATH?", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(unix)]
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(windows)]
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text)
+}
+
+impl GitCommand {
+    /// The repository `dir` is in. Fails outside a repository and on a detached HEAD, which has
+    /// no branch to push.
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let root = git(dir, &["rev-parse", "--show-toplevel"])
+            .map_err(|e| format!("Not inside a git repository, so there is nothing to commit ({}).", e))?;
+        let root = PathBuf::from(root);
+        let git_dir = PathBuf::from(git(&root, &["rev-parse", "--absolute-git-dir"])?);
+        let branch = git(&root, &["symbolic-ref", "--quiet", "--short", "HEAD"]).map_err(|_| {
+            "HEAD is detached, so a commit would not be on any branch. Check out a branch (git switch <branch>) and run /commit-and-push again."
+                .to_string()
+        })?;
+        let push = match git(&root, &["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{u}"]) {
+            Ok(upstream) => Some(PushTarget::Upstream(upstream)),
+            Err(_) => {
+                let remotes = git(&root, &["remote"]).unwrap_or_default();
+                let mut remotes = remotes.lines();
+                let first = remotes.next().map(str::to_string);
+                if remotes.chain(first.as_deref()).any(|remote| remote == "origin") {
+                    Some(PushTarget::Remote("origin".to_string()))
+                } else {
+                    first.map(PushTarget::Remote)
+                }
+            }
+        };
+        Ok(Self { root, git_dir, branch, push })
+    }
+
+    /// Where a push goes, such as `origin/main`; `None` without a remote
+    pub fn push_target(&self) -> Option<String> {
+        match &self.push {
+            Some(PushTarget::Upstream(upstream)) => Some(upstream.clone()),
+            Some(PushTarget::Remote(remote)) => Some(format!("{}/{} (new upstream)", remote, self.branch)),
+            None => None,
+        }
+    }
+
+    fn status(&self) -> Result<Status, String> {
+        let output = git(&self.root, &["status", "--porcelain"])?;
+        let mut status = Status::default();
+        for line in output.lines() {
+            let mut codes = line.chars();
+            match (codes.next(), codes.next()) {
+                (Some('?'), _) => status.untracked += 1,
+                (Some(index), Some(work_tree)) => {
+                    if index != ' ' {
+                        status.staged += 1;
+                    }
+                    if work_tree != ' ' {
+                        status.unstaged += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(status)
+    }
+
+    /// `git add -u`: stage every change to a tracked file, deletions included
+    pub fn stage_tracked(&self) -> Result<(), String> {
+        git(&self.root, &["add", "-u"]).map(|_| ())
+    }
+
+    /// The `--stat` summary and the diff of what is staged
+    fn staged_diff(&self) -> Result<(String, String), String> {
+        let stat = git(&self.root, &["diff", "--staged", "--stat"])?;
+        let diff = git(&self.root, &["diff", "--staged"])?;
+        Ok((stat, diff))
+    }
+
+    /// The commit, then the push if `push` is set and there is somewhere to push to. The message
+    /// goes through a file in the git directory, so no shell quoting can change it.
+    pub fn steps(&self, message: &str, push: bool) -> Result<Vec<GitStep>, String> {
+        let message_file = self.git_dir.join("GROK_COMMIT_MSG");
+        std::fs::write(&message_file, format!("{}\n", message.trim_end()))
+            .map_err(|e| format!("Could not write the commit message to {}: {}", message_file.display(), e))?;
+        let id = chrono::Utc::now().timestamp_millis();
+        let mut steps = vec![GitStep::new(
+            &format!("commit_{}", id),
+            format!("git -C {} commit -F {}", quote(&self.root.to_string_lossy()), quote(&message_file.to_string_lossy())),
+            false,
+        )];
+        if push && let Some(target) = &self.push {
+            let args = match target {
+                PushTarget::Upstream(_) => String::new(),
+                PushTarget::Remote(remote) => format!(" -u {} {}", quote(remote), quote(&self.branch)),
+            };
+            // Fail instead of waiting for a password on the terminal the UI is drawn on
+            let prompt = if cfg!(unix) { "GIT_TERMINAL_PROMPT=0 " } else { "" };
+            steps.push(GitStep::new(
+                &format!("push_{}", id),
+                format!("{}git -C {} push{}", prompt, quote(&self.root.to_string_lossy()), args),
+                true,
+            ));
+        }
+        Ok(steps)
+    }
+}
+
+/// Start `/commit-and-push` in `dir`: ask the model for a message for what is staged, or offer to
+/// stage the changed tracked files first. Returns the reply for the chat.
+pub async fn start(agent: &GrokAgent, pending: &mut Option<PendingCommit>, dir: &Path) -> String {
+    let git = match GitCommand::open(dir) {
+        Ok(git) => git,
+        Err(e) => return format!("❌ {}", e),
+    };
+    match git.status() {
+        Ok(status) if status.staged > 0 => propose(agent, pending, git).await,
+        Ok(status) if status.unstaged > 0 => {
+            *pending = Some(PendingCommit::Stage(git));
+            format!("Nothing is staged, but {} tracked file(s) have changes.", status.unstaged)
+        }
+        Ok(status) if status.untracked > 0 => format!(
+            "Nothing to commit: nothing is staged and no tracked file changed. Add the {} untracked file(s) you want with git add, then run /commit-and-push again.",
+            status.untracked
+        ),
+        Ok(_) => "Nothing to commit: the working tree is clean.".to_string(),
+        Err(e) => format!("❌ git status failed: {}", e),
+    }
+}
+
+/// Ask the model for a message for what is staged and hold it for the user to confirm
+pub async fn propose(agent: &GrokAgent, pending: &mut Option<PendingCommit>, git: GitCommand) -> String {
+    let (stat, diff) = match git.staged_diff() {
+        Ok(changes) => changes,
+        Err(e) => return format!("❌ git diff --staged failed: {}", e),
+    };
+    match agent.generate_commit_message(&stat, &diff).await {
+        Ok(message) if message.is_empty() => "❌ The model answered with an empty commit message; nothing was committed.".to_string(),
+        Ok(message) => {
+            let reply = format!("Proposed commit message:\n\n{}\n\n{}", message, stat);
+            *pending = Some(PendingCommit::Message { git, message });
+            reply
+        }
+        Err(e) => format!("❌ Could not get a commit message from the model: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_git_command_counts_changes_and_commits_with_the_message() {
+        let repo = std::env::temp_dir().join(format!("grok-git-command-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo);
+        std::fs::create_dir_all(&repo).unwrap();
+        let run = |args: &[&str]| git(&repo, args).is_ok();
+        if !run(&["init", "-q", "-b", "main"]) {
+            // No git on this machine
+            return;
+        }
+        assert!(run(&["config", "user.name", "t"]) && run(&["config", "user.email", "t@t"]));
+        std::fs::write(repo.join("lib.rs"), "fn a() {}\n").unwrap();
+        assert!(run(&["add", "lib.rs"]) && run(&["commit", "-qm", "init"]));
+
+        let git_command = GitCommand::open(&repo).unwrap();
+        assert_eq!(git_command.push_target(), None);
+        std::fs::write(repo.join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        std::fs::write(repo.join("notes.md"), "").unwrap();
+        assert_eq!(git_command.status().unwrap(), Status { staged: 0, unstaged: 1, untracked: 1 });
+        git_command.stage_tracked().unwrap();
+        assert_eq!(git_command.status().unwrap(), Status { staged: 1, unstaged: 0, untracked: 1 });
+        let (stat, diff) = git_command.staged_diff().unwrap();
+        assert!(stat.contains("lib.rs") && diff.contains("+fn b() {}"));
+
+        let steps = git_command.steps("Add b\n\nIt's needed by the 'c' module.", true).unwrap();
+        assert_eq!(steps.len(), 1, "no remote, so nothing to push");
+        let (lines, _output) = tokio::sync::mpsc::channel(64);
+        let result = steps[0].run(lines).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(git(&repo, &["log", "-1", "--format=%B"]).unwrap(), "Add b\n\nIt's needed by the 'c' module.");
+
+        assert!(run(&["checkout", "-q", "--detach"]));
+        assert!(GitCommand::open(&repo).unwrap_err().starts_with("HEAD is detached"));
+        let outside = GitCommand::open(&std::env::temp_dir());
+        assert!(outside.is_err_and(|e| e.starts_with("Not inside a git repository")));
+
+        let diff = "+line\n".repeat(crate::prompts::commit_message::MAX_DIFF_CHARS);
+        let request = crate::prompts::commit_message::request(" 1 file changed", &diff);
+        assert!(request.contains(&format!("of {} characters", diff.len())) && request.ends_with("+line"));
+        assert_eq!(crate::prompts::parse_reply("```text\nfeat: add b\n```\n"), "feat: add b");
+
+        std::fs::remove_dir_all(&repo).unwrap();
+    }
+}
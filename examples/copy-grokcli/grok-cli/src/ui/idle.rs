@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+/// Idle timeout used when stdin is not a terminal and `--idle-timeout` is not given
+pub const DEFAULT_NON_TTY_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Returned from the UI when nothing happened for too long, so `main` can exit non-zero
+#[derive(Debug)]
+pub struct IdleTimeout(pub Duration);
+
+impl std::fmt::Display for IdleTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No user input or model progress for {}s, exiting (--idle-timeout)",
+            self.0.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for IdleTimeout {}
+
+/// Tracks the last key press or stream update
+pub struct IdleTimer {
+    timeout: Duration,
+    last_activity: Instant,
+}
+
+impl IdleTimer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, last_activity: Instant::now() }
+    }
+
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// While a response is streaming the limit is never shorter than the request timeout, so a
+    /// stalled request ends with the client's own error instead of racing it
+    pub fn limit(&self, streaming: bool) -> Duration {
+        if streaming {
+            self.timeout.max(crate::grok::client::REQUEST_TIMEOUT)
+        } else {
+            self.timeout
+        }
+    }
+
+    pub fn expired(&self, streaming: bool, now: Instant) -> bool {
+        now.duration_since(self.last_activity) >= self.limit(streaming)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grok::client::REQUEST_TIMEOUT;
+
+    #[test]
+    fn test_idle_timer_waits_for_request_timeout_while_streaming() {
+        let mut timer = IdleTimer::new(Duration::from_secs(30));
+        let later = Instant::now() + Duration::from_secs(31);
+        assert!(timer.expired(false, later));
+        assert!(!timer.expired(true, later));
+        assert!(timer.expired(true, Instant::now() + REQUEST_TIMEOUT));
+
+        timer.touch();
+        assert!(!timer.expired(false, Instant::now()));
+        // A timeout longer than the request timeout is used as-is
+        assert_eq!(IdleTimer::new(Duration::from_secs(900)).limit(true), Duration::from_secs(900));
+    }
+}
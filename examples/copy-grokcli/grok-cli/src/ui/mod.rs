@@ -12,9 +12,24 @@ use crossterm::{
 };
 use std::io;
 use crate::agent::GrokAgent;
+use crate::grok::error::AgentError;
 use crate::types::{ChatEntry, ChatEntryType};
+use command_registry::CommandOutcome;
+use crate::utils::custom_commands::CustomCommands;
 use futures::stream::StreamExt;
 
+mod commands;
+mod file_picker;
+mod git_command;
+mod idle;
+mod model_picker;
+mod policy;
+mod todo_panel;
+mod tool_output;
+mod tool_renderers;
+
+pub use idle::{IdleTimeout, DEFAULT_NON_TTY_IDLE_TIMEOUT};
+
 pub struct ChatState {
     chat_history: Vec<ChatEntry>,
     input: String,
@@ -23,24 +38,53 @@ pub struct ChatState {
     command_hints: Vec<String>,
     selected_hint: usize,
     show_mention_hints: bool,
-    mention_hints: Vec<String>,
+    mention_hints: Vec<file_picker::FileMatch>,
     selected_mention_hint: usize,
+    /// Files of the workspace that `@` completes to
+    file_index: file_picker::FileIndex,
+    /// Format JSON, tables and ANSI colors in tool results (`pretty_tool_output` setting)
+    pretty_tool_output: bool,
+    /// Total tokens reported by streamed requests this session
+    tokens_used: u64,
+    /// Requests the client sent again after a rate limit, server error or network failure this session
+    retries: u32,
+    /// File edit waiting for y / n / a while auto-edit mode is off
+    pending_edit: Option<crate::agent::EditConfirmation>,
+    /// `ask_user` question waiting for an answer; Enter sends the typed text or the selected option
+    pending_question: Option<crate::agent::UserQuestion>,
+    selected_option: usize,
+    /// `/commit-and-push` waiting to stage the changes or for the proposed message to be confirmed
+    pending_commit: Option<git_command::PendingCommit>,
+    /// `custom_commands` from user settings and `.grok/commands/*.md`, registered after the built-ins
+    custom_commands: CustomCommands,
+    /// Set by `/regenerate [temperature]` for the attempt it sends
+    regenerate_temperature: Option<f64>,
+    /// How each tool's results are drawn when `pretty_tool_output` is on
+    tool_renderers: tool_renderers::RendererRegistry,
+    /// Text of the last reply if it stopped at the output length limit; Enter on an empty
+    /// prompt or `/continue` asks the model for the rest
+    truncated_reply: Option<String>,
+    /// Set while the streaming task waits for the network: (retry attempt, when it retries, the error)
+    offline: Option<(u32, std::time::Instant, String)>,
+    /// Set while the streaming task waits out a rate limit: (when it retries, the error)
+    rate_limited: Option<(std::time::Instant, String)>,
+    /// Set once the user asked to stop the running response, until the streaming task ends
+    stopping: bool,
+    /// Open while `/models` waits for the user to pick a model
+    model_picker: Option<model_picker::ModelPicker>,
+    /// The model's todo list as of its last `create_todo_list` or `update_todo_list` result
+    todos: Vec<crate::tools::TodoItem>,
+    /// Ctrl+T folds the todo panel down to its title
+    todo_panel_expanded: bool,
+    /// The last lines a running bash call printed, shown under its tool call: (call id, lines)
+    live_output: Option<(String, std::collections::VecDeque<String>)>,
 }
 
-const AVAILABLE_COMMANDS: &[&str] = &[
-    "/help - Show help information",
-    "/clear - Clear chat history",
-    "/models - Switch Grok Model",
-    "/commit-and-push - AI commit & push to remote",
-    "/exit - Exit the application",
-];
-
-const AVAILABLE_MENTIONS: &[&str] = &[
-    "@file - Mention a file",
-    "@model - Mention current model",
-    "@provider - Mention current provider",
-    "@history - Mention chat history",
-];
+/// Lines of a running command's output shown under its tool call
+const LIVE_OUTPUT_LINES: usize = 6;
+
+/// Times one streamed request is sent again after the provider said how long to wait
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
 
 fn get_welcome_message() -> String {
     "🤖 Welcome to starfellcode CLI!\n\n\
@@ -53,7 +97,14 @@ fn get_welcome_message() -> String {
     Type your request in natural language. Ctrl+C to clear, 'exit' to quit.".to_string()
 }
 
-pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<(), Box<dyn std::error::Error>> {
+/// `idle_timeout` ends the session with [`IdleTimeout`] after that long without input or model progress
+pub async fn run_app(
+    mut agent: GrokAgent,
+    initial_message: String,
+    pretty_tool_output: bool,
+    custom_commands: CustomCommands,
+    idle_timeout: Option<std::time::Duration>,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -62,7 +113,8 @@ pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<()
     let mut terminal = RatatuiTerminal::new(backend)?;
 
     let mut chat_state = ChatState {
-        chat_history: vec![],
+        // Non-empty when resuming a saved session
+        chat_history: agent.get_chat_history().clone(),
         input: String::new(),
         scroll: 0,
         show_command_hints: false,
@@ -71,6 +123,25 @@ pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<()
         show_mention_hints: false,
         mention_hints: vec![],
         selected_mention_hint: 0,
+        file_index: file_picker::FileIndex::new(std::env::current_dir().unwrap_or_default()),
+        pretty_tool_output,
+        tokens_used: 0,
+        retries: 0,
+        pending_edit: None,
+        pending_question: None,
+        selected_option: 0,
+        pending_commit: None,
+        custom_commands,
+        regenerate_temperature: None,
+        tool_renderers: tool_renderers::RendererRegistry::default(),
+        truncated_reply: None,
+        offline: None,
+        rate_limited: None,
+        stopping: false,
+        model_picker: None,
+        todos: agent.current_todos(),
+        todo_panel_expanded: true,
+        live_output: None,
     };
 
     // If there's an initial message, process it first
@@ -96,7 +167,9 @@ pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<()
             is_streaming: Some(true),
         });
 
-        let response_idx = chat_state.chat_history.len() - 1;
+        let mut response_idx = chat_state.chat_history.len() - 1;
+        // Set after tool results: the text that follows them streams into an entry of its own
+        let mut after_tools = false;
         
         match agent.process_user_message_stream(&initial_message).await {
             Ok(mut stream) => {
@@ -105,9 +178,43 @@ pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<()
                     match chunk_result {
                         Ok(chunk) => {
                             match chunk.chunk_type {
-                                crate::types::StreamingChunkType::Content => {
-                                    if let Some(content) = chunk.content {
-                                        chat_state.chat_history[response_idx].content.push_str(&content);
+                                crate::types::StreamingChunkType::Content | crate::types::StreamingChunkType::Note => {
+                                    let Some(mut content) = chunk.content else { continue };
+                                    if matches!(chunk.chunk_type, crate::types::StreamingChunkType::Note) {
+                                        content.push('\n');
+                                    }
+                                    if std::mem::take(&mut after_tools) {
+                                        chat_state.chat_history.push(ChatEntry {
+                                            entry_type: ChatEntryType::Assistant,
+                                            content: String::new(),
+                                            timestamp: chrono::Utc::now(),
+                                            tool_calls: None,
+                                            tool_call: None,
+                                            tool_result: None,
+                                            is_streaming: Some(true),
+                                        });
+                                        response_idx = chat_state.chat_history.len() - 1;
+                                    }
+                                    chat_state.chat_history[response_idx].content.push_str(&content);
+                                }
+                                crate::types::StreamingChunkType::ToolResult => {
+                                    if let (Some(call), Some(result)) = (chunk.tool_call, chunk.tool_result) {
+                                        let content = if result.success {
+                                            result.output.clone().unwrap_or_else(|| "Success".to_string())
+                                        } else {
+                                            result.error.clone().unwrap_or_else(|| "Error occurred".to_string())
+                                        };
+                                        chat_state.chat_history[response_idx].is_streaming = Some(false);
+                                        chat_state.chat_history.push(ChatEntry {
+                                            entry_type: ChatEntryType::ToolResult,
+                                            content,
+                                            timestamp: chrono::Utc::now(),
+                                            tool_calls: None,
+                                            tool_call: Some(call),
+                                            tool_result: Some(result),
+                                            is_streaming: None,
+                                        });
+                                        after_tools = true;
                                     }
                                 }
                                 crate::types::StreamingChunkType::Done => {
@@ -138,7 +245,8 @@ pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<()
     }
 
     // Run the main UI loop
-    let result = run_ui_loop(&mut terminal, &mut agent, &mut chat_state).await;
+    let result = run_ui_loop(&mut terminal, &mut agent, &mut chat_state, idle_timeout).await;
+    save_session(&agent, &chat_state.chat_history).await;
 
     // Restore terminal
     disable_raw_mode()?;
@@ -148,10 +256,99 @@ pub async fn run_app(mut agent: GrokAgent, initial_message: String) -> Result<()
     result
 }
 
+/// Commands whose name starts with what has been typed, or the arguments the command
+/// completes to once a space follows its name
+fn matching_commands(input: &str, custom: &CustomCommands) -> Vec<String> {
+    commands::registry(custom).hints(input)
+}
+
+/// Show the files matching the mention being typed, the text from the last `@` with no space
+/// after it, or hide the popup when there is none
+fn update_mention_hints(state: &mut ChatState) {
+    match state.input.rfind('@').map(|at| &state.input[at + 1..]).filter(|query| !query.contains(' ')) {
+        Some(query) => {
+            state.mention_hints = state.file_index.matches(query);
+            state.show_mention_hints = true;
+            state.selected_mention_hint = 0;
+            state.show_command_hints = false;
+        }
+        None => {
+            state.show_mention_hints = false;
+            state.mention_hints.clear();
+        }
+    }
+}
+
+/// Longest argument preview shown for a tool call that is still streaming
+const TOOL_PREVIEW_CHARS: usize = 240;
+
+/// Tool name plus the start of its (possibly incomplete) JSON arguments
+fn tool_call_preview(call: &crate::types::GrokToolCall) -> String {
+    let name = if call.function.name.is_empty() { "…" } else { &call.function.name };
+    let arguments = call.function.arguments.replace('\n', " ");
+    let total = arguments.chars().count();
+    if total > TOOL_PREVIEW_CHARS {
+        let preview: String = arguments.chars().take(TOOL_PREVIEW_CHARS).collect();
+        format!("{} {}… ({} chars)", name, preview, total)
+    } else {
+        format!("{} {}", name, arguments)
+    }
+}
+
+/// Persist the transcript under `.grok/sessions/` so `grok history` can find it
+async fn save_session(agent: &GrokAgent, entries: &[ChatEntry]) {
+    if let Ok(store) = crate::utils::session_store::SessionStore::new() {
+        let _ = store.save(&agent.session_file(entries)).await;
+    }
+}
+
+/// Keep a transcript that `/regenerate` is about to replace as its own session, so the
+/// earlier attempt can still be found with `grok history`
+async fn save_replaced_attempt(agent: &GrokAgent, entries: &[ChatEntry]) {
+    if let Ok(store) = crate::utils::session_store::SessionStore::new() {
+        let mut session = agent.session_file(entries);
+        session.id = format!("{}-attempt-{}", agent.session_id(), chrono::Utc::now().format("%H%M%S"));
+        let _ = store.save(&session).await;
+    }
+}
+
+/// Open `text` in `$VISUAL` / `$EDITOR` (default `vi`) and return what was saved. The TUI is
+/// suspended while the editor runs.
+fn edit_in_external_editor(
+    terminal: &mut RatatuiTerminal<CrosstermBackend<std::io::Stdout>>,
+    text: &str,
+) -> Result<String, String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let path = std::env::temp_dir().join(format!("grok-system-prompt-{}.md", std::process::id()));
+    std::fs::write(&path, text).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+
+    let _ = disable_raw_mode();
+    let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
+    // `code --wait` and similar: the first word is the program, the rest are its arguments
+    let mut words = editor.split_whitespace();
+    let status = std::process::Command::new(words.next().unwrap_or("vi")).args(words).arg(&path).status();
+    let _ = enable_raw_mode();
+    let _ = execute!(terminal.backend_mut(), EnterAlternateScreen);
+    let _ = terminal.clear();
+
+    let edited = match status {
+        Ok(status) if status.success() => std::fs::read_to_string(&path).map_err(|e| e.to_string()),
+        Ok(status) => Err(format!("{} exited with {}; the system prompt is unchanged", editor, status)),
+        Err(e) => Err(format!("Cannot run {}: {}. Set $EDITOR to your editor.", editor, e)),
+    };
+    let _ = std::fs::remove_file(&path);
+    edited
+}
+
 async fn run_ui_loop(
     terminal: &mut RatatuiTerminal<CrosstermBackend<std::io::Stdout>>,
     agent: &mut GrokAgent,
     state: &mut ChatState,
+    idle_timeout: Option<std::time::Duration>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use tokio::sync::mpsc;
         
@@ -159,40 +356,132 @@ async fn run_ui_loop(
     #[derive(Clone, Debug)]
     enum StreamMessage {
         Content(String),
+        /// Tool call still being generated: (call id, preview text)
+        ToolCallPreview(String, String),
+        /// A tool call finished running during the stream
+        ToolResult(crate::types::GrokToolCall, crate::types::ToolResult),
+        /// A line a running tool call printed: (call id, line)
+        ToolOutput(String, String),
+        /// Tokens used by one streamed request
+        TokenCount(u32),
+        /// How many times the client sent the last request again before it went through
+        Retried(u32),
+        /// "Changed: …" summary of the files the turn touched
+        ChangeSummary(ChatEntry),
+        /// The next prompt of a custom command, sent once the previous reply finished
+        NextStep(String),
+        /// The user stopped the run; the "Cancelled by user" transcript entry
+        Cancelled(ChatEntry),
+        /// The conversation as the streaming task left it, so the next turn continues from it
+        Conversation(Vec<crate::types::GrokMessage>),
+        /// The reply stopped at the output length limit: the text it got to
+        Truncated(String),
+        /// The request failed on a network error and is sent again after the delay
+        Offline(u32, std::time::Duration, String),
+        /// The provider rate-limited the request and said when to try again; it is sent again then
+        RateLimited(std::time::Duration, String),
         Done,
         Error(String),
     }
     
     let (tx, mut rx) = mpsc::channel::<StreamMessage>(100);
     let mut active_stream_task: Option<tokio::task::JoinHandle<()>> = None;
+    // Cancelled by Esc or Ctrl+X to stop the running task after its current tool call
+    let mut active_cancellation: Option<tokio_util::sync::CancellationToken> = None;
+    // File edits from the streaming task wait here for the user's answer
+    let (confirm_tx, mut confirm_rx) = mpsc::channel::<crate::agent::EditConfirmation>(1);
+    agent.set_edit_confirmer(confirm_tx);
+    let (ask_tx, mut ask_rx) = mpsc::channel::<crate::agent::UserQuestion>(1);
+    agent.set_user_asker(ask_tx);
+    let mut idle_timer = idle_timeout.map(idle::IdleTimer::new);
 
     loop {
+        if state.file_index.poll() && state.show_mention_hints {
+            update_mention_hints(state);
+        }
+        if let Some(timer) = &idle_timer {
+            let streaming = active_stream_task.as_ref().is_some_and(|task| !task.is_finished());
+            if timer.expired(streaming, std::time::Instant::now()) {
+                return Err(Box::new(IdleTimeout(timer.timeout())));
+            }
+        }
+
         // Draw UI
         terminal.draw(|f| {
             let size = f.area();
 
-            // Create vertical layout: header, chat area, input
+            // Create vertical layout: header, chat area, todo panel, input
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
                     Constraint::Length(3), // Header
                     Constraint::Min(10),   // Chat history
+                    Constraint::Length(todo_panel::height(&state.todos, state.todo_panel_expanded)),
                     Constraint::Length(3), // Input
                 ])
                 .split(size);
 
             // Header
             let header_block = Block::default();
-            f.render_widget(header_block, chunks[0]);
+            let mut header = vec![];
+            if agent.auto_edit() {
+                header.push(ratatui::text::Span::styled(
+                    "⏵⏵ auto-edit on (shift+tab to turn off)  ",
+                    Style::default().fg(Color::Magenta),
+                ));
+            }
+            if state.tokens_used > 0 {
+                header.push(ratatui::text::Span::styled(
+                    format!("Tokens used: {}", state.tokens_used),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if state.retries > 0 {
+                header.push(ratatui::text::Span::styled(
+                    format!("  Retries: {}", state.retries),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            f.render_widget(
+                Paragraph::new(ratatui::text::Line::from(header)).block(header_block),
+                chunks[0],
+            );
 
             // Chat history
             let chat_items: Vec<ListItem> = state.chat_history.iter()
                 .map(|entry| {
+                    if state.pretty_tool_output && matches!(entry.entry_type, ChatEntryType::ToolResult) {
+                        let base = Style::default().fg(Color::Yellow);
+                        let mut lines = vec![ratatui::text::Line::styled("🔧 Tool Result:", base)];
+                        lines.extend(state.tool_renderers.render(entry, base));
+                        return ListItem::new(lines);
+                    }
+                    let edit = entry.tool_call.as_ref().is_some_and(|call| matches!(call.function.name.as_str(), "str_replace_editor" | "insert_lines" | "delete_lines" | "edit_file"));
+                    if edit && matches!(entry.entry_type, ChatEntryType::ConfirmationPending) {
+                        // Edits show the diff they will make, colored like the tool result will be
+                        let base = Style::default().fg(Color::LightMagenta);
+                        let mut lines = vec![ratatui::text::Line::styled("✋ Approve?", base)];
+                        lines.extend(tool_renderers::diff_lines(&entry.content, base));
+                        return ListItem::new(lines);
+                    }
                     let content = match &entry.entry_type {
                         ChatEntryType::User => format!("👤 You: {}", entry.content),
                         ChatEntryType::Assistant => format!("🤖 Grok: {}", entry.content),
                         ChatEntryType::ToolResult => format!("🔧 Tool Result: {}", entry.content),
                         ChatEntryType::ToolCall => format!("🔧 Tool Call: {}", entry.content),
+                        ChatEntryType::ConfirmationPending => format!("✋ Approve?\n{}", entry.content),
+                        ChatEntryType::System => format!("⚙️  {}", entry.content),
+                    };
+                    // A running bash call shows the tail of its output
+                    let live = state.live_output.as_ref().filter(|(id, _)| {
+                        entry.is_streaming == Some(true) && entry.tool_call.as_ref().is_some_and(|call| call.id == *id)
+                    });
+                    let content = match live {
+                        Some((_, lines)) => {
+                            let tail: Vec<String> = lines.iter().map(|line| format!("  │ {}", line)).collect();
+                            format!("{}\n{}", content, tail.join("\n"))
+                        }
+                        None => content,
                     };
 
                     ListItem::new(content)
@@ -201,6 +490,8 @@ async fn run_ui_loop(
                             ChatEntryType::Assistant => Style::default().fg(Color::Cyan),
                             ChatEntryType::ToolResult => Style::default().fg(Color::Yellow),
                             ChatEntryType::ToolCall => Style::default().fg(Color::Magenta),
+                            ChatEntryType::ConfirmationPending => Style::default().fg(Color::LightMagenta),
+                            ChatEntryType::System => Style::default().fg(Color::DarkGray),
                         })
                 })
                 .collect();
@@ -209,37 +500,139 @@ async fn run_ui_loop(
                 .block(Block::default().borders(Borders::BOTTOM));
             f.render_widget(chat_list, chunks[1]);
 
+            // Todo panel
+            if !state.todos.is_empty() {
+                let title = todo_panel::title(&state.todos, state.todo_panel_expanded);
+                if state.todo_panel_expanded {
+                    let panel = Paragraph::new(todo_panel::lines(&state.todos))
+                        .block(Block::default().borders(Borders::ALL).title(title));
+                    f.render_widget(panel, chunks[2]);
+                } else {
+                    f.render_widget(Paragraph::new(title).style(Style::default().fg(Color::DarkGray)), chunks[2]);
+                }
+            }
+
             // Input area
-            let input_text = format!("> {}_", state.input);
-            let input_paragraph = Paragraph::new(input_text)
-                .block(Block::default());
-            f.render_widget(input_paragraph, chunks[2]);
+            let input_paragraph = match &state.pending_edit {
+                Some(edit) if edit.is_bash() => Paragraph::new(format!(
+                    "Run {}? [y]es  [n]o  [a]lways (every command this session)",
+                    edit.description
+                ))
+                .style(Style::default().fg(Color::Magenta)),
+                Some(edit) => Paragraph::new(format!(
+                    "Apply {}? [y]es  [n]o  [a]lways (auto-edit)",
+                    edit.description
+                ))
+                .style(Style::default().fg(Color::Magenta)),
+                None if state.pending_question.is_some() => Paragraph::new(format!("? {}_", state.input))
+                    .style(Style::default().fg(Color::Cyan)),
+                None if state.pending_commit.is_some() => {
+                    Paragraph::new(state.pending_commit.as_ref().unwrap().prompt()).style(Style::default().fg(Color::Magenta))
+                }
+                None if state.offline.is_some() => {
+                    let (attempt, retry_at, error) = state.offline.as_ref().unwrap();
+                    let wait = retry_at.saturating_duration_since(std::time::Instant::now()).as_secs();
+                    Paragraph::new(format!(
+                        "📡 Offline, retrying in {}s (attempt {}). Esc to stop. {}",
+                        wait, attempt, error
+                    ))
+                    .style(Style::default().fg(Color::Red))
+                }
+                None if state.rate_limited.is_some() => {
+                    let (retry_at, error) = state.rate_limited.as_ref().unwrap();
+                    let wait = retry_at.saturating_duration_since(std::time::Instant::now()).as_secs();
+                    Paragraph::new(format!("⏳ Rate limited, retrying in {}s. Esc to stop. {}", wait, error))
+                        .style(Style::default().fg(Color::Yellow))
+                }
+                None if state.stopping => Paragraph::new("⏹️  Stopping after the current step…")
+                    .style(Style::default().fg(Color::Yellow)),
+                None if state.truncated_reply.is_some() && state.input.is_empty() => {
+                    Paragraph::new("> ✂️ The reply was cut off at the length limit. Press Enter to continue it.")
+                        .style(Style::default().fg(Color::Yellow))
+                }
+                None => Paragraph::new(format!("> {}_", state.input)),
+            }
+            .block(Block::default());
+            f.render_widget(input_paragraph, chunks[3]);
             
+            // The /models picker
+            if let Some(picker) = &state.model_picker {
+                let (start, models) = picker.visible(model_picker::VISIBLE_MODELS);
+                let height = models.len() as u16 + 2;
+                let popup_area = Rect {
+                    x: chunks[3].x,
+                    y: chunks[3].y.saturating_sub(height),
+                    width: chunks[3].width,
+                    height,
+                };
+                let current = agent.get_current_model();
+                let items: Vec<ListItem> = models.iter().enumerate()
+                    .map(|(idx, model)| {
+                        let style = if start + idx == picker.selected() {
+                            Style::default().fg(Color::Black).bg(Color::Cyan)
+                        } else {
+                            Style::default().fg(Color::Cyan)
+                        };
+                        let marker = if model == current { " (current)" } else { "" };
+                        ListItem::new(format!("{}{}", model, marker)).style(style)
+                    })
+                    .collect();
+                let list = List::new(items).block(
+                    Block::default().borders(Borders::ALL).title("Model: Enter switches, Shift+Enter or s also saves, Esc cancels"),
+                );
+                f.render_widget(list, popup_area);
+            }
+
+            // Options of a pending ask_user question
+            else if let Some(question) = state.pending_question.as_ref().filter(|q| !q.options.is_empty()) {
+                let height = (question.options.len() as u16).min(8) + 2;
+                let popup_area = Rect {
+                    x: chunks[3].x,
+                    y: chunks[3].y.saturating_sub(height),
+                    width: chunks[3].width,
+                    height,
+                };
+                let items: Vec<ListItem> = question.options.iter().enumerate()
+                    .map(|(idx, option)| {
+                        let style = if idx == state.selected_option {
+                            Style::default().fg(Color::Black).bg(Color::Cyan)
+                        } else {
+                            Style::default().fg(Color::Cyan)
+                        };
+                        ListItem::new(format!("{}. {}", idx + 1, option)).style(style)
+                    })
+                    .collect();
+                let list = List::new(items).block(
+                    Block::default().borders(Borders::ALL).title("Answer: ↑↓ + Enter, a number, or your own words"),
+                );
+                f.render_widget(list, popup_area);
+            }
+
             // Show mention hints as overlay if available
-            if state.show_mention_hints && !state.mention_hints.is_empty() {
+            else if state.show_mention_hints && (!state.mention_hints.is_empty() || state.file_index.is_indexing()) {
                 // Create a popup area for hints (above the input)
-                let hints_height = (state.mention_hints.len() as u16).min(5) + 2; // +2 for border
+                let hints_height = (state.mention_hints.len() as u16).min(file_picker::VISIBLE_FILES as u16) + 2; // +2 for border
                 let popup_area = Rect {
-                    x: chunks[2].x,
-                    y: chunks[2].y.saturating_sub(hints_height),
-                    width: chunks[2].width,
+                    x: chunks[3].x,
+                    y: chunks[3].y.saturating_sub(hints_height),
+                    width: chunks[3].width,
                     height: hints_height,
                 };
                 
                 // Render mention hints popup
-                let hint_items: Vec<ListItem> = state.mention_hints.iter().take(5).enumerate()
+                let hint_items: Vec<ListItem> = state.mention_hints.iter().enumerate()
                     .map(|(idx, hint)| {
                         let style = if idx == state.selected_mention_hint {
                             Style::default().fg(Color::Black).bg(Color::Magenta)
                         } else {
                             Style::default().fg(Color::Magenta)
                         };
-                        ListItem::new(hint.clone()).style(style)
+                        ListItem::new(file_picker::highlighted(hint, style, file_picker::matched_style(style)))
                     })
                     .collect();
                 
                 let hints_list = List::new(hint_items)
-                    .block(Block::default().borders(Borders::ALL).title("Mentions"));
+                    .block(Block::default().borders(Borders::ALL).title(state.file_index.title()));
                 f.render_widget(hints_list, popup_area);
             }
             
@@ -248,9 +641,9 @@ async fn run_ui_loop(
                 // Create a popup area for hints (above the input)
                 let hints_height = (state.command_hints.len() as u16).min(5) + 2; // +2 for border
                 let popup_area = Rect {
-                    x: chunks[2].x,
-                    y: chunks[2].y.saturating_sub(hints_height),
-                    width: chunks[2].width,
+                    x: chunks[3].x,
+                    y: chunks[3].y.saturating_sub(hints_height),
+                    width: chunks[3].width,
                     height: hints_height,
                 };
                 
@@ -282,55 +675,176 @@ async fn run_ui_loop(
                     Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "timeout"))
                 }
             } => {
+                if let (Ok(_), Some(timer)) = (&event_result, idle_timer.as_mut()) {
+                    timer.touch();
+                }
+                if let Ok(Event::Resize(_, _)) = event_result {
+                    // Redraw at the new size right away instead of on the next keypress
+                    terminal.autoresize()?;
+                    continue;
+                }
                 if let Ok(Event::Key(key)) = event_result {
                     // Only process Press events, ignore Release and Repeat
                     if key.kind == KeyEventKind::Press {
+                        if let Some(edit) = state.pending_edit.take() {
+                            let decision = match key.code {
+                                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => crate::agent::EditDecision::Approve,
+                                KeyCode::Char('a') | KeyCode::Char('A') => crate::agent::EditDecision::ApproveAll,
+                                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => crate::agent::EditDecision::Reject,
+                                // Quitting leaves the call unrun
+                                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                                _ => {
+                                    state.pending_edit = Some(edit);
+                                    continue;
+                                }
+                            };
+                            agent.resolve_confirmation(&edit.id, decision);
+                            continue;
+                        }
+                        if let Some(commit) = state.pending_commit.take() {
+                            use git_command::PendingCommit;
+                            let reply = match (commit, key.code) {
+                                (_, KeyCode::Char('c')) if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                                (PendingCommit::Stage(git), KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter) => match git.stage_tracked() {
+                                    Ok(()) => git_command::propose(agent, &mut state.pending_commit, git).await,
+                                    Err(e) => format!("❌ git add -u failed: {}", e),
+                                },
+                                (PendingCommit::Message { git, message }, KeyCode::Char('e') | KeyCode::Char('E')) => {
+                                    match edit_in_external_editor(terminal, &message) {
+                                        Ok(edited) if edited.trim().is_empty() => {
+                                            state.pending_commit = Some(PendingCommit::Message { git, message });
+                                            "The edited message is empty; keeping the proposed one.".to_string()
+                                        }
+                                        Ok(edited) => {
+                                            let message = edited.trim().to_string();
+                                            let reply = format!("Commit message:\n\n{}", message);
+                                            state.pending_commit = Some(PendingCommit::Message { git, message });
+                                            reply
+                                        }
+                                        Err(e) => {
+                                            state.pending_commit = Some(PendingCommit::Message { git, message });
+                                            e
+                                        }
+                                    }
+                                }
+                                (PendingCommit::Message { git, message }, KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Char('c') | KeyCode::Char('C') | KeyCode::Enter) => {
+                                    let push = !matches!(key.code, KeyCode::Char('c') | KeyCode::Char('C'));
+                                    match git.steps(&message, push) {
+                                        Ok(steps) => {
+                                            // Runs like a response, so the commands' output streams in as tool calls
+                                            let tx_clone = tx.clone();
+                                            active_stream_task = Some(tokio::spawn(async move {
+                                                for step in steps {
+                                                    let id = step.call.id.clone();
+                                                    let _ = tx_clone.send(StreamMessage::ToolCallPreview(id.clone(), tool_call_preview(&step.call))).await;
+                                                    let (lines, mut line_rx) = tokio::sync::mpsc::channel(64);
+                                                    let tx_lines = tx_clone.clone();
+                                                    let forward = tokio::spawn(async move {
+                                                        while let Some(line) = line_rx.recv().await {
+                                                            let _ = tx_lines.send(StreamMessage::ToolOutput(id.clone(), line)).await;
+                                                        }
+                                                    });
+                                                    let result = step.run(lines).await;
+                                                    let _ = forward.await;
+                                                    let success = result.success;
+                                                    let _ = tx_clone.send(StreamMessage::ToolResult(step.call, result)).await;
+                                                    // Nothing is pushed after a failed commit
+                                                    if !success {
+                                                        break;
+                                                    }
+                                                }
+                                                let _ = tx_clone.send(StreamMessage::Done).await;
+                                            }));
+                                            continue;
+                                        }
+                                        Err(e) => format!("❌ {}", e),
+                                    }
+                                }
+                                (_, KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc) => "Cancelled /commit-and-push; nothing was committed.".to_string(),
+                                (commit, _) => {
+                                    state.pending_commit = Some(commit);
+                                    continue;
+                                }
+                            };
+                            state.chat_history.push(ChatEntry {
+                                entry_type: ChatEntryType::Assistant,
+                                content: reply,
+                                timestamp: chrono::Utc::now(),
+                                tool_calls: None,
+                                tool_call: None,
+                                tool_result: None,
+                                is_streaming: None,
+                            });
+                            continue;
+                        }
+                        if let Some(picker) = state.model_picker.as_mut() {
+                            let save = match key.code {
+                                KeyCode::Up => {
+                                    picker.up();
+                                    continue;
+                                }
+                                KeyCode::Down => {
+                                    picker.down();
+                                    continue;
+                                }
+                                KeyCode::Esc => {
+                                    state.model_picker = None;
+                                    continue;
+                                }
+                                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                                // Terminals that do not report Shift with Enter send a plain Enter, so `s` saves too
+                                KeyCode::Enter => key.modifiers.contains(KeyModifiers::SHIFT),
+                                KeyCode::Char('s') => true,
+                                _ => continue,
+                            };
+                            let model = picker.selected_model().to_string();
+                            state.model_picker = None;
+                            if model != agent.get_current_model() {
+                                state.chat_history.push(agent.set_model(&model));
+                            }
+                            if save {
+                                let content = match model_picker::save_default_model(&model).await {
+                                    Ok(()) => format!("Saved {} as default_model in ~/.grok/user-settings.json.", model),
+                                    Err(e) => format!("Using {} for this session only; saving the settings failed: {}", model, e),
+                                };
+                                state.chat_history.push(ChatEntry {
+                                    entry_type: ChatEntryType::System,
+                                    content,
+                                    timestamp: chrono::Utc::now(),
+                                    tool_calls: None,
+                                    tool_call: None,
+                                    tool_result: None,
+                                    is_streaming: None,
+                                });
+                            }
+                            continue;
+                        }
                         match key.code {
                             KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) && c == 'c' => {
                                 return Ok(());
                             },
+                            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                state.todo_panel_expanded = !state.todo_panel_expanded;
+                            },
+                            KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                if let Some(cancellation) = active_cancellation.as_ref().filter(|_| active_stream_task.is_some()) {
+                                    cancellation.cancel();
+                                    state.stopping = true;
+                                }
+                            },
                             KeyCode::Char(c) => {
                                 state.input.push(c);
                                 
-                                // Check for @ mentions
-                                if let Some(at_pos) = state.input.rfind('@') {
-                                    let after_at = &state.input[at_pos..];
-                                    if !after_at.contains(' ') {
-                                        // We're in a mention
-                                        state.show_mention_hints = true;
-                                        let mention_lower = after_at.to_lowercase();
-                                        state.mention_hints = AVAILABLE_MENTIONS
-                                            .iter()
-                                            .filter(|mention| {
-                                                let mention_name = mention.split(" - ").next().unwrap_or("");
-                                                mention_name.to_lowercase().starts_with(&mention_lower)
-                                            })
-                                            .map(|s| s.to_string())
-                                            .collect();
-                                        state.selected_mention_hint = 0;
-                                        state.show_command_hints = false;
-                                    } else {
-                                        state.show_mention_hints = false;
-                                        state.mention_hints.clear();
-                                    }
-                                } else {
-                                    state.show_mention_hints = false;
-                                    state.mention_hints.clear();
+                                // A new mention picks up files added since the index was walked
+                                if c == '@' {
+                                    state.file_index.refresh_if_stale();
                                 }
+                                update_mention_hints(state);
                                 
                                 // Update command hints when user types '/'
                                 if state.input.starts_with('/') && !state.show_mention_hints {
                                     state.show_command_hints = true;
-                                    let input_lower = state.input.to_lowercase();
-                                    state.command_hints = AVAILABLE_COMMANDS
-                                        .iter()
-                                        .filter(|cmd| {
-                                            // Extract command name (before the dash)
-                                            let cmd_name = cmd.split(" - ").next().unwrap_or("");
-                                            cmd_name.to_lowercase().starts_with(&input_lower)
-                                        })
-                                        .map(|s| s.to_string())
-                                        .collect();
+                                    state.command_hints = matching_commands(&state.input, &state.custom_commands);
                                     state.selected_hint = 0;
                                 } else if !state.show_mention_hints {
                                     state.show_command_hints = false;
@@ -339,26 +853,52 @@ async fn run_ui_loop(
                             },
                             KeyCode::Backspace => {
                                 state.input.pop();
+                                update_mention_hints(state);
                                 
                                 // Update command hints after backspace
                                 if state.input.starts_with('/') {
                                     state.show_command_hints = true;
-                                    let input_lower = state.input.to_lowercase();
-                                    state.command_hints = AVAILABLE_COMMANDS
-                                        .iter()
-                                        .filter(|cmd| {
-                                            // Extract command name (before the dash)
-                                            let cmd_name = cmd.split(" - ").next().unwrap_or("");
-                                            cmd_name.to_lowercase().starts_with(&input_lower)
-                                        })
-                                        .map(|s| s.to_string())
-                                        .collect();
+                                    state.command_hints = matching_commands(&state.input, &state.custom_commands);
                                     state.selected_hint = 0;
                                 } else {
                                     state.show_command_hints = false;
                                     state.command_hints.clear();
                                 }
                             },
+                            KeyCode::Up if state.pending_question.is_some() => {
+                                state.selected_option = state.selected_option.saturating_sub(1);
+                            },
+                            KeyCode::Down if state.pending_question.is_some() => {
+                                let count = state.pending_question.as_ref().map_or(0, |q| q.options.len());
+                                if state.selected_option + 1 < count {
+                                    state.selected_option += 1;
+                                }
+                            },
+                            KeyCode::Enter if state.pending_question.is_some() => {
+                                let question = state.pending_question.take().unwrap();
+                                let answer = if state.input.trim().is_empty() {
+                                    question.options.get(state.selected_option).cloned().unwrap_or_default()
+                                } else {
+                                    crate::agent::resolve_answer(&state.input, &question.options)
+                                };
+                                if answer.is_empty() {
+                                    state.pending_question = Some(question);
+                                    continue;
+                                }
+                                state.chat_history.push(ChatEntry {
+                                    entry_type: ChatEntryType::User,
+                                    content: answer.clone(),
+                                    timestamp: chrono::Utc::now(),
+                                    tool_calls: None,
+                                    tool_call: None,
+                                    tool_result: None,
+                                    is_streaming: None,
+                                });
+                                let _ = question.reply.send(answer);
+                                state.input.clear();
+                                state.show_command_hints = false;
+                                state.show_mention_hints = false;
+                            },
                             KeyCode::Up => {
                                 // Navigate up in mention hints
                                 if state.show_mention_hints && !state.mention_hints.is_empty() {
@@ -387,15 +927,32 @@ async fn run_ui_loop(
                                     }
                                 }
                             },
+                            KeyCode::BackTab => {
+                                let enabled = !agent.auto_edit();
+                                agent.set_auto_edit(enabled);
+                                let content = if enabled {
+                                    "Auto-edit mode on: file edits are applied without asking."
+                                } else {
+                                    "Auto-edit mode off: each file edit asks for confirmation."
+                                };
+                                state.chat_history.push(ChatEntry {
+                                    entry_type: ChatEntryType::Assistant,
+                                    content: content.to_string(),
+                                    timestamp: chrono::Utc::now(),
+                                    tool_calls: None,
+                                    tool_call: None,
+                                    tool_result: None,
+                                    is_streaming: None,
+                                });
+                            },
                             KeyCode::Tab => {
                                 // Auto-complete selected mention
                                 if state.show_mention_hints && !state.mention_hints.is_empty() {
-                                    let selected = &state.mention_hints[state.selected_mention_hint];
-                                    let mention = selected.split_whitespace().next().unwrap_or("");
+                                    let mention = format!("@{}", state.mention_hints[state.selected_mention_hint].path);
                                     // Replace from the last @ to the end
                                     if let Some(at_pos) = state.input.rfind('@') {
                                         state.input.truncate(at_pos);
-                                        state.input.push_str(mention);
+                                        state.input.push_str(&mention);
                                         state.input.push(' ');
                                     }
                                     state.show_mention_hints = false;
@@ -403,61 +960,85 @@ async fn run_ui_loop(
                                 }
                                 // Auto-complete selected command
                                 else if state.show_command_hints && !state.command_hints.is_empty() {
+                                    // "/name - description", or "/name args" for an argument
                                     let selected = &state.command_hints[state.selected_hint];
-                                    let cmd = selected.split_whitespace().next().unwrap_or("");
+                                    let cmd = selected.split(" - ").next().unwrap_or("");
                                     state.input = cmd.to_string();
                                     state.show_command_hints = false;
                                     state.command_hints.clear();
                                 }
                             },
                             KeyCode::Enter => {
+                                if state.input.trim().is_empty() && state.truncated_reply.is_some() {
+                                    state.input = "/continue".to_string();
+                                }
                                 if !state.input.trim().is_empty() {
-                                    let user_input = state.input.clone();
+                                    let mut user_input = state.input.clone();
                                     state.show_command_hints = false;
                                     state.command_hints.clear();
                                     state.show_mention_hints = false;
                                     state.mention_hints.clear();
-                                    
-                                    // Check if input is a command
-                                    if user_input.starts_with('/') {
-                                        let cmd_response = match user_input.trim() {
-                                            "/help" => {
-                                                "Available commands:\n\
-                                                /help - Show this help message\n\
-                                                /clear - Clear chat history\n\
-                                                /status - Show application status\n\
-                                                /model - Show current model\n\
-                                                /exit - Exit the application".to_string()
-                                            },
-                                            "/clear" => {
-                                                state.chat_history.clear();
-                                                "Chat history cleared.".to_string()
-                                            },
-                                            "/status" => {
-                                                "Status: Running\n\
-                                                Model: Grok\n\
-                                                Ready for input.".to_string()
-                                            },
-                                            "/model" => {
-                                                "Current model: grok-2\n\
-                                                Available models: grok-2, grok-vision".to_string()
-                                            },
-                                            "/exit" => {
-                                                return Ok(());
-                                            },
-                                            _ => format!("Unknown command: {}. Type /help for available commands.", user_input),
+
+                                    // /continue streams the rest of a cut-off reply into the same transcript entry
+                                    let continuation = (user_input.trim() == "/continue" && active_stream_task.is_none())
+                                        .then(|| state.truncated_reply.take())
+                                        .flatten();
+
+                                    // A command that sends prompts sends its first now and the rest after each reply
+                                    let mut next_steps = Vec::new();
+                                    if user_input.starts_with('/') && continuation.is_none() {
+                                        // The registry's commands borrow the agent and the state only while one runs
+                                        let outcome = {
+                                            let registry = commands::registry(&state.custom_commands);
+                                            match registry.resolve(&user_input) {
+                                                Some(Ok((command, args))) => {
+                                                    let mut ctx = commands::CommandContext {
+                                                        agent: &mut *agent,
+                                                        state: &mut *state,
+                                                        terminal: &mut *terminal,
+                                                        streaming: active_stream_task.is_some(),
+                                                    };
+                                                    command.execute(&mut ctx, &args).await
+                                                }
+                                                Some(Err(e)) => CommandOutcome::Reply(e.to_string()),
+                                                None => CommandOutcome::Done,
+                                            }
                                         };
-                                        
-                                        state.chat_history.push(ChatEntry {
-                                            entry_type: ChatEntryType::Assistant,
-                                            content: cmd_response,
-                                            timestamp: chrono::Utc::now(),
-                                            tool_calls: None,
-                                            tool_call: None,
-                                            tool_result: None,
-                                            is_streaming: None,
-                                        });
-                                    } else {
+                                        let reply = match outcome {
+                                            CommandOutcome::Prompts(mut prompts) if !prompts.is_empty() => {
+                                                user_input = prompts.remove(0);
+                                                next_steps = prompts;
+                                                None
+                                            }
+                                            CommandOutcome::Prompts(_) => Some(format!("{} expands to an empty prompt, so nothing was sent.", user_input.trim())),
+                                            CommandOutcome::Reply(content) => Some(content),
+                                            CommandOutcome::Done => {
+                                                state.input.clear();
+                                                continue;
+                                            }
+                                            CommandOutcome::Exit => return Ok(()),
+                                        };
+                                        if let Some(content) = reply {
+                                            state.chat_history.push(ChatEntry {
+                                                entry_type: ChatEntryType::Assistant,
+                                                content,
+                                                timestamp: chrono::Utc::now(),
+                                                tool_calls: None,
+                                                tool_call: None,
+                                                tool_result: None,
+                                                is_streaming: None,
+                                            });
+                                            state.input.clear();
+                                            continue;
+                                        }
+                                    }
+                                    let continues_last_entry = continuation.as_ref().is_some_and(|partial| {
+                                        state.chat_history.last().is_some_and(|e| {
+                                            matches!(e.entry_type, ChatEntryType::Assistant) && e.content.ends_with(partial.as_str())
+                                        })
+                                    });
+                                    if continuation.is_none() {
+                                        state.truncated_reply = None;
                                         // Add user message to chat immediately
                                         state.chat_history.push(ChatEntry {
                                             entry_type: ChatEntryType::User,
@@ -468,9 +1049,31 @@ async fn run_ui_loop(
                                             tool_result: None,
                                             is_streaming: None,
                                         });
+                                        let parts = agent.message_parts(&user_input).len();
+                                        if parts > 1 {
+                                            state.chat_history.push(ChatEntry {
+                                                entry_type: ChatEntryType::Assistant,
+                                                content: format!(
+                                                    "✂️ This message is about {} tokens, over max_message_tokens ({}); it is sent as {} consecutive parts.",
+                                                    crate::agent::estimate_tokens(&user_input),
+                                                    agent.max_message_tokens().unwrap_or_default(),
+                                                    parts
+                                                ),
+                                                timestamp: chrono::Utc::now(),
+                                                tool_calls: None,
+                                                tool_call: None,
+                                                tool_result: None,
+                                                is_streaming: None,
+                                            });
+                                        }
+                                    }
 
+                                    if continues_last_entry {
+                                        if let Some(entry) = state.chat_history.last_mut() {
+                                            entry.is_streaming = Some(true);
+                                        }
+                                    } else {
                                         // Add a temporary assistant message for streaming
-                                        let response_idx = state.chat_history.len();
                                         state.chat_history.push(ChatEntry {
                                             entry_type: ChatEntryType::Assistant,
                                             content: String::new(),
@@ -480,64 +1083,381 @@ async fn run_ui_loop(
                                             tool_result: None,
                                             is_streaming: Some(true),
                                         });
+                                    }
 
-                                        // Spawn background task for streaming
-                                        let user_msg = user_input.clone();
-                                        let mut agent_clone = (*agent).clone();
-                                        let tx_clone = tx.clone();
-                                        
-                                        let task = tokio::spawn(async move {
-                                                                                        
-                                            match agent_clone.process_user_message_stream(&user_msg).await {
-                                                Ok(mut stream) => {
+                                    // Spawn background task for streaming
+                                    let prompts: Vec<String> = std::iter::once(user_input.clone()).chain(next_steps).collect();
+                                    let mut agent_clone = (*agent).clone();
+                                    // Only this attempt uses the other temperature
+                                    if let Some(temperature) = state.regenerate_temperature.take() {
+                                        agent_clone.set_model_param("temperature", serde_json::json!(temperature));
+                                    }
+                                    let cancellation = tokio_util::sync::CancellationToken::new();
+                                    agent_clone.set_cancellation(cancellation.clone());
+                                    active_cancellation = Some(cancellation);
+                                    let tx_clone = tx.clone();
+                                    
+                                    let task = tokio::spawn(async move {
+                                        let outcome = async {
+                                            // The agent runs tool calls inside the stream: their results come back as
+                                            // chunks and the next response is streamed after them
+                                            for (step, user_msg) in prompts.iter().enumerate() {
+                                                if step > 0 {
+                                                    let _ = tx_clone.send(StreamMessage::NextStep(user_msg.clone())).await;
+                                                }
+                                                let mut offline_attempts = 0;
+                                                let mut rate_limit_retries = 0;
+                                                let mut retry = false;
+                                                loop {
+                                                    let next = match continuation.as_deref() {
+                                                        _ if retry => agent_clone.retry_stream().await,
+                                                        Some(partial) if step == 0 => agent_clone.continue_truncated_stream(partial).await,
+                                                        _ => agent_clone.process_user_message_stream(user_msg).await,
+                                                    };
+                                                    let mut stream = match next.map_err(|e| e.to_string()) {
+                                                        Ok(stream) => stream,
+                                                        Err(e) => return StreamMessage::Error(e),
+                                                    };
+                                                    let mut truncated = None;
+                                                    let mut from_cache = false;
+                                                    let mut model_output = false;
+                                                    let mut network_error = None;
+                                                    let mut rate_limited = None;
                                                     while let Some(chunk_result) = stream.next().await {
-                                                        match chunk_result {
-                                                            Ok(chunk) => {
-                                                                match chunk.chunk_type {
-                                                                    crate::types::StreamingChunkType::Content => {
-                                                                        if let Some(content) = chunk.content {
-                                                                            let _ = tx_clone.send(StreamMessage::Content(content)).await;
-                                                                        }
+                                                        let chunk = match chunk_result {
+                                                            Ok(chunk) => chunk,
+                                                            // Nothing of the reply has arrived, so the same request can be sent again
+                                                            Err(e) if !model_output && e.is_network() => {
+                                                                network_error = Some(e.to_string());
+                                                                break;
+                                                            }
+                                                            Err(e) if !model_output && rate_limit_retries < MAX_RATE_LIMIT_RETRIES => match e.retry_after() {
+                                                                Some(delay) => {
+                                                                    rate_limited = Some((delay, e.to_string()));
+                                                                    break;
+                                                                }
+                                                                None => return StreamMessage::Error(e.to_string()),
+                                                            },
+                                                            Err(e) => return StreamMessage::Error(e.to_string()),
+                                                        };
+                                                        // Tool results are in the conversation, so the request after them can be sent again
+                                                        model_output = match chunk.chunk_type {
+                                                            crate::types::StreamingChunkType::ToolResult => false,
+                                                            crate::types::StreamingChunkType::CacheHit | crate::types::StreamingChunkType::Note => model_output,
+                                                            _ => true,
+                                                        };
+                                                        match chunk.chunk_type {
+                                                            crate::types::StreamingChunkType::Content => {
+                                                                if let Some(content) = chunk.content {
+                                                                    let _ = tx_clone.send(StreamMessage::Content(content)).await;
+                                                                }
+                                                            }
+                                                            crate::types::StreamingChunkType::ToolCalls => {
+                                                                for call in chunk.tool_calls.unwrap_or_default() {
+                                                                    let preview = tool_call_preview(&call);
+                                                                    let _ = tx_clone.send(StreamMessage::ToolCallPreview(call.id, preview)).await;
+                                                                }
+                                                            }
+                                                            crate::types::StreamingChunkType::ToolResult => {
+                                                                match (chunk.tool_call, chunk.tool_result, chunk.content) {
+                                                                    (Some(call), Some(result), _) => {
+                                                                        let _ = tx_clone.send(StreamMessage::ToolResult(call, result)).await;
                                                                     }
-                                                                    crate::types::StreamingChunkType::Done => {
-                                                                        let _ = tx_clone.send(StreamMessage::Done).await;
-                                                                        break;
+                                                                    (Some(call), None, Some(line)) => {
+                                                                        let _ = tx_clone.send(StreamMessage::ToolOutput(call.id, line)).await;
                                                                     }
                                                                     _ => {}
                                                                 }
                                                             }
-                                                            Err(e) => {
-                                                                let _ = tx_clone.send(StreamMessage::Error(e.to_string())).await;
-                                                                break;
+                                                            crate::types::StreamingChunkType::Note => {
+                                                                if let Some(note) = chunk.content {
+                                                                    let _ = tx_clone.send(StreamMessage::Content(format!("{}\n", note))).await;
+                                                                }
                                                             }
+                                                            crate::types::StreamingChunkType::CacheHit => from_cache = true,
+                                                            crate::types::StreamingChunkType::Truncated => truncated = chunk.content,
+                                                            crate::types::StreamingChunkType::TokenCount => {
+                                                                if let Some(tokens) = chunk.token_count {
+                                                                    let _ = tx_clone.send(StreamMessage::TokenCount(tokens)).await;
+                                                                }
+                                                            }
+                                                            // Usage can follow the finish chunk, so keep reading to the end
+                                                            crate::types::StreamingChunkType::Done => {}
                                                         }
                                                     }
-                                                }
-                                                Err(e) => {
-                                                    let _ = tx_clone.send(StreamMessage::Error(e.to_string())).await;
+                                                    drop(stream);
+                                                    let retries = agent_clone.last_retries();
+                                                    if retries > 0 {
+                                                        let _ = tx_clone.send(StreamMessage::Retried(retries)).await;
+                                                    }
+                                                    // The agent ran no further tool calls and sent no further request
+                                                    if agent_clone.is_cancelled() {
+                                                        let _ = tx_clone.send(StreamMessage::Cancelled(agent_clone.record_cancellation())).await;
+                                                        return StreamMessage::Done;
+                                                    }
+                                                    if let Some(error) = network_error {
+                                                        offline_attempts += 1;
+                                                        let delay = AgentError::offline_retry_delay(offline_attempts);
+                                                        let _ = tx_clone.send(StreamMessage::Offline(offline_attempts, delay, error)).await;
+                                                        tokio::time::sleep(delay).await;
+                                                        retry = true;
+                                                        continue;
+                                                    }
+                                                    offline_attempts = 0;
+                                                    if let Some((delay, error)) = rate_limited {
+                                                        rate_limit_retries += 1;
+                                                        let _ = tx_clone.send(StreamMessage::RateLimited(delay, error)).await;
+                                                        tokio::time::sleep(delay).await;
+                                                        retry = true;
+                                                        continue;
+                                                    }
+                                                    // Shown in the transcript only; the agent keeps the reply as it was
+                                                    if from_cache {
+                                                        let _ = tx_clone.send(StreamMessage::Content("\n(from cache)".to_string())).await;
+                                                    }
+
+                                                    if let Some(entry) = agent_clone.finish_turn() {
+                                                        let _ = tx_clone.send(StreamMessage::ChangeSummary(entry)).await;
+                                                    }
+                                                    // A custom command stops here; its later steps would build on an unfinished reply
+                                                    if let Some(partial) = truncated {
+                                                        let _ = tx_clone.send(StreamMessage::Truncated(partial)).await;
+                                                        return StreamMessage::Done;
+                                                    }
+                                                    break;
                                                 }
                                             }
-                                        });
-                                        
-                                        active_stream_task = Some(task);
-                                    }
+                                            StreamMessage::Done
+                                        }
+                                        .await;
+                                        let _ = tx_clone.send(StreamMessage::Conversation(agent_clone.conversation())).await;
+                                        let _ = tx_clone.send(outcome).await;
+                                    });
+                                    
+                                    active_stream_task = Some(task);
                                     
                                     state.input.clear();
                                 }
                             },
+                            KeyCode::Esc if state.offline.is_some() || state.rate_limited.is_some() => {
+                                // Stop waiting for the network or the rate limit; the unanswered message is not kept
+                                if let Some(task) = active_stream_task.take() {
+                                    task.abort();
+                                }
+                                let reason = if state.offline.is_some() { "the network was unavailable" } else { "rate limited" };
+                                state.offline = None;
+                                state.rate_limited = None;
+                                for entry in state.chat_history.iter_mut() {
+                                    if entry.is_streaming == Some(true) {
+                                        entry.is_streaming = Some(false);
+                                    }
+                                }
+                                if let Some(entry) = state.chat_history.iter_mut().rev().find(|e| matches!(e.entry_type, ChatEntryType::Assistant)) {
+                                    entry.content.push_str(&format!("\n[Stopped: {}]", reason));
+                                }
+                            },
+                            KeyCode::Esc if active_stream_task.as_ref().is_some_and(|task| !task.is_finished()) => {
+                                if let Some(cancellation) = &active_cancellation {
+                                    cancellation.cancel();
+                                    state.stopping = true;
+                                }
+                            },
                             KeyCode::Esc => return Ok(()),
                             _ => {}
                         }
                     }
                 }
             }
+            Some(question) = ask_rx.recv() => {
+                if let Some(timer) = idle_timer.as_mut() {
+                    timer.touch();
+                }
+                let mut content = format!("❓ {}", question.question);
+                for (idx, option) in question.options.iter().enumerate() {
+                    content.push_str(&format!("\n  {}. {}", idx + 1, option));
+                }
+                state.chat_history.push(ChatEntry {
+                    entry_type: ChatEntryType::Assistant,
+                    content,
+                    timestamp: chrono::Utc::now(),
+                    tool_calls: None,
+                    tool_call: None,
+                    tool_result: None,
+                    is_streaming: None,
+                });
+                state.pending_question = Some(question);
+                state.selected_option = 0;
+            }
+            Some(edit) = confirm_rx.recv() => {
+                if let Some(timer) = idle_timer.as_mut() {
+                    timer.touch();
+                }
+                state.chat_history.push(edit.entry.clone());
+                state.pending_edit = Some(edit);
+            }
             // Handle stream updates from background task
             Some(update) = rx.recv() => {
+                if let Some(timer) = idle_timer.as_mut() {
+                    timer.touch();
+                }
+                // Anything but another failure means the request went through
+                state.offline = None;
+                state.rate_limited = None;
+                // Tool call previews get their own entry, updated in place while the arguments stream
+                let update = match update {
+                    StreamMessage::Offline(attempt, delay, error) => {
+                        state.offline = Some((attempt, std::time::Instant::now() + delay, error));
+                        continue;
+                    }
+                    StreamMessage::RateLimited(delay, error) => {
+                        state.rate_limited = Some((std::time::Instant::now() + delay, error));
+                        continue;
+                    }
+                    StreamMessage::ToolCallPreview(id, preview) => {
+                        let existing = state.chat_history.iter_mut().rev().find(|e| {
+                            matches!(e.entry_type, ChatEntryType::ToolCall)
+                                && e.is_streaming == Some(true)
+                                && e.tool_call.as_ref().is_some_and(|call| call.id == id)
+                        });
+                        match existing {
+                            Some(entry) => entry.content = preview,
+                            None => state.chat_history.push(ChatEntry {
+                                entry_type: ChatEntryType::ToolCall,
+                                content: preview,
+                                timestamp: chrono::Utc::now(),
+                                tool_calls: None,
+                                tool_call: Some(crate::types::GrokToolCall {
+                                    id,
+                                    call_type: "function".to_string(),
+                                    function: crate::types::GrokToolCallFunction {
+                                        name: String::new(),
+                                        arguments: String::new(),
+                                    },
+                                }),
+                                tool_result: None,
+                                is_streaming: Some(true),
+                            }),
+                        }
+                        continue;
+                    }
+                    StreamMessage::ToolOutput(id, line) => {
+                        let (_, lines) = match &mut state.live_output {
+                            Some(live) if live.0 == id => live,
+                            live => live.insert((id, std::collections::VecDeque::new())),
+                        };
+                        if lines.len() == LIVE_OUTPUT_LINES {
+                            lines.pop_front();
+                        }
+                        lines.push_back(line);
+                        continue;
+                    }
+                    StreamMessage::ToolResult(call, result) => {
+                        if state.live_output.as_ref().is_some_and(|(id, _)| *id == call.id) {
+                            state.live_output = None;
+                        }
+                        if matches!(call.function.name.as_str(), "create_todo_list" | "update_todo_list") {
+                            state.todos = agent.current_todos();
+                        }
+                        let content = if result.success {
+                            result.output.clone().unwrap_or_else(|| "Success".to_string())
+                        } else {
+                            result.error.clone().unwrap_or_else(|| "Error occurred".to_string())
+                        };
+                        for entry in state.chat_history.iter_mut() {
+                            if matches!(entry.entry_type, ChatEntryType::ToolCall)
+                                && entry.tool_call.as_ref().is_some_and(|c| c.id == call.id)
+                            {
+                                entry.is_streaming = Some(false);
+                            }
+                        }
+                        state.chat_history.push(ChatEntry {
+                            entry_type: ChatEntryType::ToolResult,
+                            content,
+                            timestamp: chrono::Utc::now(),
+                            tool_calls: None,
+                            tool_call: Some(call),
+                            tool_result: Some(result),
+                            is_streaming: None,
+                        });
+                        continue;
+                    }
+                    StreamMessage::Retried(retries) => {
+                        state.retries += retries;
+                        continue;
+                    }
+                    StreamMessage::TokenCount(tokens) => {
+                        state.tokens_used += tokens as u64;
+                        continue;
+                    }
+                    StreamMessage::ChangeSummary(entry) => {
+                        state.chat_history.push(entry);
+                        continue;
+                    }
+                    StreamMessage::Conversation(messages) => {
+                        agent.restore_conversation(messages);
+                        continue;
+                    }
+                    StreamMessage::Truncated(partial) => {
+                        state.truncated_reply = Some(partial);
+                        continue;
+                    }
+                    StreamMessage::NextStep(prompt) => {
+                        for entry in state.chat_history.iter_mut() {
+                            if matches!(entry.entry_type, ChatEntryType::Assistant) && entry.is_streaming == Some(true) {
+                                entry.is_streaming = Some(false);
+                            }
+                        }
+                        for (entry_type, content, is_streaming) in
+                            [(ChatEntryType::User, prompt, None), (ChatEntryType::Assistant, String::new(), Some(true))]
+                        {
+                            state.chat_history.push(ChatEntry {
+                                entry_type,
+                                content,
+                                timestamp: chrono::Utc::now(),
+                                tool_calls: None,
+                                tool_call: None,
+                                tool_result: None,
+                                is_streaming,
+                            });
+                        }
+                        continue;
+                    }
+                    StreamMessage::Cancelled(entry) => {
+                        state.chat_history.push(entry);
+                        continue;
+                    }
+                    StreamMessage::Done | StreamMessage::Error(_) => {
+                        state.stopping = false;
+                        active_cancellation = None;
+                        for entry in state.chat_history.iter_mut() {
+                            if matches!(entry.entry_type, ChatEntryType::ToolCall | ChatEntryType::Assistant) && entry.is_streaming == Some(true) {
+                                entry.is_streaming = Some(false);
+                            }
+                        }
+                        update
+                    }
+                    other => other,
+                };
                 // Find the last assistant message and append to it
                 if let Some(response_idx) = state.chat_history.iter().rposition(|e| matches!(e.entry_type, ChatEntryType::Assistant)) {
                     match update {
                         StreamMessage::Content(content) => {
-                            if response_idx < state.chat_history.len() {
+                            // Text after a tool result starts a new reply, so the transcript stays in order
+                            let after_tools = state
+                                .chat_history
+                                .last()
+                                .is_some_and(|e| matches!(e.entry_type, ChatEntryType::ToolResult | ChatEntryType::ToolCall));
+                            if after_tools {
+                                state.chat_history.push(ChatEntry {
+                                    entry_type: ChatEntryType::Assistant,
+                                    content,
+                                    timestamp: chrono::Utc::now(),
+                                    tool_calls: None,
+                                    tool_call: None,
+                                    tool_result: None,
+                                    is_streaming: Some(true),
+                                });
+                            } else if response_idx < state.chat_history.len() {
                                 state.chat_history[response_idx].content.push_str(&content);
                             }
                         }
@@ -546,6 +1466,7 @@ async fn run_ui_loop(
                                 state.chat_history[response_idx].is_streaming = Some(false);
                             }
                             active_stream_task = None;
+                            save_session(agent, &state.chat_history).await;
                         }
                         StreamMessage::Error(error) => {
                             if response_idx < state.chat_history.len() {
@@ -554,6 +1475,18 @@ async fn run_ui_loop(
                             }
                             active_stream_task = None;
                         }
+                        StreamMessage::ToolCallPreview(..)
+                        | StreamMessage::ToolResult(..)
+                        | StreamMessage::ToolOutput(..)
+                        | StreamMessage::TokenCount(_)
+                        | StreamMessage::Retried(_)
+                        | StreamMessage::ChangeSummary(_)
+                        | StreamMessage::NextStep(_)
+                        | StreamMessage::Conversation(_)
+                        | StreamMessage::Cancelled(_)
+                        | StreamMessage::Truncated(_)
+                        | StreamMessage::Offline(..)
+                        | StreamMessage::RateLimited(..) => {}
                     }
                 }
             }
@@ -0,0 +1,103 @@
+/// Rows of models the popup shows at once
+pub const VISIBLE_MODELS: usize = 8;
+
+/// The `/models` popup: the provider's models, or the configured list when its `/models`
+/// endpoint could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelPicker {
+    models: Vec<String>,
+    selected: usize,
+    /// Why the configured list is shown instead of the provider's
+    fallback_reason: Option<String>,
+}
+
+impl ModelPicker {
+    /// Start on `current`, which is listed first when the list does not have it
+    pub fn new(listed: Result<Vec<String>, String>, configured: Vec<String>, current: &str) -> Self {
+        let (mut models, fallback_reason) = match listed {
+            Ok(models) if !models.is_empty() => (models, None),
+            Ok(_) => (configured, Some("the provider listed no models".to_string())),
+            Err(reason) => (configured, Some(reason)),
+        };
+        if !models.iter().any(|model| model == current) {
+            models.insert(0, current.to_string());
+        }
+        let selected = models.iter().position(|model| model == current).unwrap_or(0);
+        Self { models, selected, fallback_reason }
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.models.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected_model(&self) -> &str {
+        &self.models[self.selected]
+    }
+
+    /// The window of at most `rows` models that keeps the selection in view, and where it starts
+    pub fn visible(&self, rows: usize) -> (usize, &[String]) {
+        let start = (self.selected + 1).saturating_sub(rows);
+        let end = (start + rows).min(self.models.len());
+        (start, &self.models[start..end])
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// What `/models` answers in the transcript when the popup opens
+    pub fn describe(&self) -> String {
+        let keys = "↑↓ to choose, Enter to switch for this session, Shift+Enter (or s) to also save it as default_model, Esc to cancel.";
+        match &self.fallback_reason {
+            Some(reason) => format!(
+                "Could not load the provider's models ({}), so these are the models from your settings. {}",
+                reason, keys
+            ),
+            None => format!("{} models from the provider. {}", self.models.len(), keys),
+        }
+    }
+}
+
+/// Write `model` to `default_model` in user settings, leaving the other settings alone
+pub async fn save_default_model(model: &str) -> Result<(), String> {
+    let manager = crate::utils::settings_manager::get_settings_manager().await.map_err(|e| e.to_string())?;
+    // A settings file that does not load is not overwritten with defaults
+    let mut settings = manager.load_user_settings().await.map_err(|e| e.to_string())?;
+    settings.default_model = Some(model.to_string());
+    manager.save_user_settings(&settings).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picker_starts_on_the_current_model_and_falls_back_to_the_configured_list() {
+        let listed: Vec<String> = (1..=12).map(|n| format!("model-{:02}", n)).collect();
+        let mut picker = ModelPicker::new(Ok(listed), vec![], "model-10");
+        assert_eq!(picker.selected_model(), "model-10");
+        let (start, rows) = picker.visible(VISIBLE_MODELS);
+        assert_eq!((start, rows.len()), (2, VISIBLE_MODELS));
+        assert_eq!(rows.last().unwrap(), "model-10");
+        picker.down();
+        picker.down();
+        picker.down();
+        assert_eq!(picker.selected_model(), "model-12");
+        assert!(picker.describe().starts_with("12 models from the provider."));
+
+        let configured = vec!["grok-4".to_string(), "grok-3-mini".to_string()];
+        let mut picker = ModelPicker::new(Err("404 Not Found".to_string()), configured, "local-llama");
+        assert_eq!(picker.visible(VISIBLE_MODELS).1, ["local-llama", "grok-4", "grok-3-mini"]);
+        picker.up();
+        assert_eq!(picker.selected(), 0);
+        picker.down();
+        assert_eq!(picker.selected_model(), "grok-4");
+        assert!(picker.describe().starts_with("Could not load the provider's models (404 Not Found)"));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::tools::{DangerousCommandMode, DangerousCommandRule};
+
+const USAGE: &str = "Usage: /policy [list | add <name> <pattern> | remove <name|pattern> | test <command>]";
+
+/// What `/policy …` asks for
+#[derive(Debug, PartialEq)]
+pub enum PolicyCommand {
+    List,
+    Add(DangerousCommandRule),
+    Remove(String),
+    Test(String),
+}
+
+/// Parse what follows `/policy`; `Err` holds the usage to show
+pub fn parse(arguments: &str) -> Result<PolicyCommand, String> {
+    let rest = arguments.trim();
+    let (action, argument) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let argument = argument.trim();
+    match (action, argument) {
+        ("" | "list", "") => Ok(PolicyCommand::List),
+        ("add", argument) => match argument.split_once(char::is_whitespace) {
+            Some((name, pattern)) if !pattern.trim().is_empty() => {
+                Ok(PolicyCommand::Add(DangerousCommandRule::new(name, pattern.trim())))
+            }
+            _ => Err("Usage: /policy add <name> <pattern>, e.g. /policy add force-push git push * --force".to_string()),
+        },
+        ("remove", argument) if !argument.is_empty() => Ok(PolicyCommand::Remove(argument.to_string())),
+        ("test", argument) if !argument.is_empty() => Ok(PolicyCommand::Test(argument.to_string())),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn disposition(mode: DangerousCommandMode) -> &'static str {
+    match mode {
+        DangerousCommandMode::Confirm => "held until you reply YES",
+        DangerousCommandMode::Block => "blocked (sandbox mode)",
+    }
+}
+
+fn rule_lines(rules: &[DangerousCommandRule]) -> String {
+    let width = rules.iter().map(|rule| rule.name.chars().count()).max().unwrap_or(0);
+    rules
+        .iter()
+        .map(|rule| format!("\n  {:width$}  {}", rule.name, rule.pattern, width = width))
+        .collect()
+}
+
+/// Every rule, the user's after the built-in ones, and what happens to a matching command
+pub fn describe(builtin: &[DangerousCommandRule], user: &[DangerousCommandRule], mode: DangerousCommandMode) -> String {
+    let mut text = format!(
+        "Dangerous command policy: a bash command that matches a rule is {}. Other commands run without asking.",
+        disposition(mode)
+    );
+    text.push_str(&format!("\n\nBuilt-in rules (always apply):{}", rule_lines(builtin)));
+    if user.is_empty() {
+        text.push_str("\n\nNo rules of your own. Add one with /policy add <name> <pattern>; * matches anything.");
+    } else {
+        text.push_str(&format!("\n\nYour rules (dangerous_commands in ~/.grok/user-settings.json):{}", rule_lines(user)));
+    }
+    text
+}
+
+/// Which rule `command` matches, the same way the bash tool checks it before running
+pub fn test(builtin: &[DangerousCommandRule], user: &[DangerousCommandRule], mode: DangerousCommandMode, command: &str) -> String {
+    let matches: Vec<(&str, &DangerousCommandRule)> = builtin
+        .iter()
+        .map(|rule| ("built-in", rule))
+        .chain(user.iter().map(|rule| ("your", rule)))
+        .filter(|(_, rule)| rule.matches(command))
+        .collect();
+    let Some((kind, rule)) = matches.first() else {
+        return format!("`{}` matches no rule, so it would run without asking.", command);
+    };
+    let mut text = format!(
+        "`{}` matches {} rule '{}' (`{}`), so it would be {}.",
+        command, kind, rule.name, rule.pattern, disposition(mode)
+    );
+    if matches.len() > 1 {
+        let others: Vec<String> = matches[1..].iter().map(|(_, rule)| format!("'{}'", rule.name)).collect();
+        text.push_str(&format!(" It also matches {}.", others.join(", ")));
+    }
+    text
+}
+
+/// The user rules after `command`, or why it cannot be applied
+pub fn apply(builtin: &[DangerousCommandRule], user: &[DangerousCommandRule], command: &PolicyCommand) -> Result<Vec<DangerousCommandRule>, String> {
+    let mut rules = user.to_vec();
+    match command {
+        PolicyCommand::Add(rule) => {
+            if builtin.iter().chain(user).any(|existing| existing.pattern == rule.pattern) {
+                return Err(format!("A rule for `{}` already exists.", rule.pattern));
+            }
+            rules.push(rule.clone());
+        }
+        PolicyCommand::Remove(target) => {
+            rules.retain(|rule| rule.name != *target && rule.pattern != *target);
+            if rules.len() == user.len() {
+                return Err(if builtin.iter().any(|rule| rule.name == *target || rule.pattern == *target) {
+                    format!("'{}' is a built-in rule; built-in rules cannot be removed.", target)
+                } else {
+                    format!("No rule of yours is named or matches the pattern '{}'.", target)
+                });
+            }
+        }
+        PolicyCommand::List | PolicyCommand::Test(_) => {}
+    }
+    Ok(rules)
+}
+
+/// Write the user rules to `dangerous_commands` in user settings, leaving the other settings alone
+pub async fn save_user_rules(rules: &[DangerousCommandRule]) -> Result<(), String> {
+    let manager = crate::utils::settings_manager::get_settings_manager().await.map_err(|e| e.to_string())?;
+    // A settings file that does not load is not overwritten with defaults
+    let mut settings = manager.load_user_settings().await.map_err(|e| e.to_string())?;
+    settings.dangerous_commands = (!rules.is_empty()).then(|| rules.to_vec());
+    manager.save_user_settings(&settings).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::default_dangerous_command_rules;
+
+    #[test]
+    fn test_policy_commands_edit_and_test_the_user_rules() {
+        let builtin = default_dangerous_command_rules();
+        assert_eq!(parse(""), Ok(PolicyCommand::List));
+        assert!(parse("add force-push").is_err());
+        assert!(parse("edit").unwrap_err().starts_with("Usage: /policy"));
+        let add = parse(" add force-push git push * --force").unwrap();
+        assert_eq!(add, PolicyCommand::Add(DangerousCommandRule::new("force-push", "git push * --force")));
+
+        let user = apply(&builtin, &[], &add).unwrap();
+        assert_eq!(user.len(), 1);
+        assert!(apply(&builtin, &user, &add).unwrap_err().contains("already exists"));
+        assert!(describe(&builtin, &user, DangerousCommandMode::Confirm).contains("force-push  git push * --force"));
+
+        let mode = DangerousCommandMode::Confirm;
+        assert_eq!(
+            test(&builtin, &user, mode, "git push origin main --force"),
+            "`git push origin main --force` matches your rule 'force-push' (`git push * --force`), so it would be held until you reply YES."
+        );
+        assert!(test(&builtin, &user, DangerousCommandMode::Block, "rm -rf /").contains("built-in rule 'delete-root'"));
+        assert!(test(&builtin, &user, mode, "git push").contains("matches no rule"));
+
+        assert!(apply(&builtin, &user, &PolicyCommand::Remove("delete-root".to_string())).unwrap_err().contains("built-in"));
+        assert_eq!(apply(&builtin, &user, &PolicyCommand::Remove("force-push".to_string())).unwrap(), vec![]);
+    }
+}
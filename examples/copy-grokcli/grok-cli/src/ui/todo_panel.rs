@@ -0,0 +1,86 @@
+//! The todo panel between the chat and the input: the list the model keeps with
+//! `create_todo_list` and `update_todo_list`, colored like the system prompt describes it.
+
+use crate::tools::TodoItem;
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+
+/// Most todos shown at once; the rest are summed up in a last line
+const MAX_VISIBLE_TODOS: usize = 10;
+
+/// Rows the panel takes: none without todos, the titled border alone when collapsed
+pub fn height(todos: &[TodoItem], expanded: bool) -> u16 {
+    match (todos.is_empty(), expanded) {
+        (true, _) => 0,
+        (false, false) => 1,
+        (false, true) => {
+            let rows = todos.len().min(MAX_VISIBLE_TODOS) + usize::from(todos.len() > MAX_VISIBLE_TODOS);
+            rows as u16 + 2
+        }
+    }
+}
+
+/// `Todos 3/5 (Ctrl+T to hide)`, with the ratio once any todo is done
+pub fn title(todos: &[TodoItem], expanded: bool) -> String {
+    let completed = todos.iter().filter(|todo| todo.status == "completed").count();
+    let progress = if completed > 0 { format!(" {}/{}", completed, todos.len()) } else { String::new() };
+    let all_done = if completed == todos.len() { " ✅ all done" } else { "" };
+    let toggle = if expanded { "Ctrl+T to hide" } else { "Ctrl+T to show" };
+    format!("Todos{}{} ({})", progress, all_done, toggle)
+}
+
+/// One line per todo: its status icon, its priority marker and its text
+pub fn lines(todos: &[TodoItem]) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = todos
+        .iter()
+        .take(MAX_VISIBLE_TODOS)
+        .map(|todo| {
+            let (icon, color) = match todo.status.as_str() {
+                "completed" => ("✅", Color::Green),
+                "in_progress" => ("🔄", Color::Cyan),
+                _ => ("⏳", Color::Yellow),
+            };
+            let priority = match todo.priority.as_str() {
+                "high" => "🔴",
+                "medium" => "🟡",
+                _ => "🟢",
+            };
+            Line::from(vec![
+                Span::raw(format!("{} {} ", icon, priority)),
+                Span::styled(todo.content.clone(), Style::default().fg(color)),
+            ])
+        })
+        .collect();
+    if todos.len() > MAX_VISIBLE_TODOS {
+        lines.push(Line::styled(format!("… {} more", todos.len() - MAX_VISIBLE_TODOS), Style::default().fg(Color::DarkGray)));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: &str, status: &str, priority: &str) -> TodoItem {
+        TodoItem { id: id.to_string(), content: format!("task {}", id), status: status.to_string(), priority: priority.to_string() }
+    }
+
+    #[test]
+    fn test_todo_panel_shows_progress_collapses_and_caps_its_rows() {
+        assert_eq!(height(&[], true), 0);
+        let todos = vec![todo("1", "completed", "high"), todo("2", "in_progress", "medium"), todo("3", "pending", "low")];
+        assert_eq!((height(&todos, false), height(&todos, true)), (1, 5));
+        assert_eq!(title(&todos, true), "Todos 1/3 (Ctrl+T to hide)");
+        assert_eq!(title(&todos[1..], false), "Todos (Ctrl+T to show)");
+        assert_eq!(title(&todos[..1], true), "Todos 1/1 ✅ all done (Ctrl+T to hide)");
+
+        let text: Vec<String> = lines(&todos).iter().map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect()).collect();
+        assert_eq!(text, vec!["✅ 🔴 task 1", "🔄 🟡 task 2", "⏳ 🟢 task 3"]);
+
+        let many: Vec<TodoItem> = (0..12).map(|n| todo(&n.to_string(), "pending", "low")).collect();
+        assert_eq!(height(&many, true), 13);
+        assert_eq!(lines(&many).last().unwrap().spans[0].content, "… 2 more");
+    }
+}
@@ -0,0 +1,258 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Outputs larger than this are shown as-is; formatting them is slow and rarely readable
+const MAX_PRETTY_BYTES: usize = 64 * 1024;
+/// Widest a single aligned column may get before the table falls back to raw text
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// What a tool result looks like, as far as display is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolOutputKind {
+    Json,
+    Table(char),
+    Ansi,
+    Plain,
+}
+
+/// Detect the output format. Detection is deliberately conservative: anything ambiguous is `Plain`.
+pub fn detect(content: &str) -> ToolOutputKind {
+    if content.len() > MAX_PRETTY_BYTES {
+        return ToolOutputKind::Plain;
+    }
+    if content.contains("\x1b[") {
+        return ToolOutputKind::Ansi;
+    }
+    let trimmed = content.trim();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && matches!(
+            serde_json::from_str::<serde_json::Value>(trimmed),
+            Ok(serde_json::Value::Object(_) | serde_json::Value::Array(_))
+        )
+    {
+        return ToolOutputKind::Json;
+    }
+    for delimiter in ['\t', ','] {
+        if is_table(trimmed, delimiter) {
+            return ToolOutputKind::Table(delimiter);
+        }
+    }
+    ToolOutputKind::Plain
+}
+
+/// At least two rows, every row with the same number (2+) of short fields, and no quoting
+/// that a naive split would get wrong
+fn is_table(content: &str, delimiter: char) -> bool {
+    let rows: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
+    if rows.len() < 2 || content.contains('"') {
+        return false;
+    }
+    let columns = rows[0].split(delimiter).count();
+    columns >= 2
+        && rows.iter().all(|row| {
+            let fields: Vec<&str> = row.split(delimiter).collect();
+            fields.len() == columns && fields.iter().all(|f| f.trim().chars().count() <= MAX_COLUMN_WIDTH)
+        })
+}
+
+/// Render a tool result for the transcript. The raw text sent to the model is unchanged.
+pub fn render(content: &str, base: Style) -> Vec<Line<'static>> {
+    match detect(content) {
+        ToolOutputKind::Json => render_json(content, base),
+        ToolOutputKind::Table(delimiter) => render_table(content, delimiter, base),
+        ToolOutputKind::Ansi => content.lines().map(|line| render_ansi_line(line, base)).collect(),
+        ToolOutputKind::Plain => plain(content, base),
+    }
+}
+
+fn plain(content: &str, base: Style) -> Vec<Line<'static>> {
+    content.lines().map(|line| Line::styled(line.to_string(), base)).collect()
+}
+
+fn render_json(content: &str, base: Style) -> Vec<Line<'static>> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(content.trim()) else {
+        return plain(content, base);
+    };
+    let pretty = serde_json::to_string_pretty(&value).unwrap_or_else(|_| content.to_string());
+    pretty.lines().map(|line| highlight_json_line(line, base)).collect()
+}
+
+/// Color keys, strings, numbers and literals in one line of pretty-printed JSON
+fn highlight_json_line(line: &str, base: Style) -> Line<'static> {
+    let key = Style::default().fg(Color::Cyan);
+    let string = Style::default().fg(Color::Green);
+    let number = Style::default().fg(Color::Yellow);
+    let literal = Style::default().fg(Color::Magenta);
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            let text: String = chars[start..i].iter().collect();
+            let is_key = chars[i..].iter().find(|c| !c.is_whitespace()) == Some(&':');
+            spans.push(Span::styled(text, if is_key { key } else { string }));
+        } else if c == '-' || c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_digit() || "-+.eE".contains(chars[i])) {
+                i += 1;
+            }
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), number));
+        } else if c.is_ascii_alphabetic() {
+            while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), literal));
+        } else {
+            while i < chars.len() && !(chars[i] == '"' || chars[i] == '-' || chars[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), base));
+        }
+    }
+    Line::from(spans)
+}
+
+fn render_table(content: &str, delimiter: char, base: Style) -> Vec<Line<'static>> {
+    let rows: Vec<Vec<String>> = content
+        .trim()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(delimiter).map(|field| field.trim().to_string()).collect())
+        .collect();
+    let columns = rows[0].len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|col| rows.iter().map(|row| row[col].chars().count()).max().unwrap_or(0))
+        .collect();
+
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let text = row
+                .iter()
+                .zip(&widths)
+                .map(|(field, width)| format!("{:<width$}", field, width = *width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            // The first row is usually a header
+            let style = if index == 0 { base.add_modifier(Modifier::BOLD) } else { base };
+            Line::styled(text.trim_end().to_string(), style)
+        })
+        .collect()
+}
+
+/// Keep SGR colors and bold from command output; other escape sequences are dropped
+fn render_ansi_line(line: &str, base: Style) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut style = base;
+    let mut text = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            text.push(c);
+            continue;
+        }
+        if chars.peek() != Some(&'[') {
+            continue;
+        }
+        chars.next();
+        let mut params = String::new();
+        let mut finalizer = None;
+        for c in chars.by_ref() {
+            if c.is_ascii_digit() || c == ';' {
+                params.push(c);
+            } else {
+                finalizer = Some(c);
+                break;
+            }
+        }
+        if finalizer != Some('m') {
+            continue;
+        }
+        if !text.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut text), style));
+        }
+        style = apply_sgr(style, base, &params);
+    }
+    if !text.is_empty() {
+        spans.push(Span::styled(text, style));
+    }
+    Line::from(spans)
+}
+
+fn apply_sgr(mut style: Style, base: Style, params: &str) -> Style {
+    const COLORS: [Color; 8] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Gray,
+    ];
+    const BRIGHT: [Color; 8] = [
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::White,
+    ];
+    for code in params.split(';') {
+        match code.parse::<u8>().unwrap_or(0) {
+            0 => style = base,
+            1 => style = style.add_modifier(Modifier::BOLD),
+            22 => style = style.remove_modifier(Modifier::BOLD),
+            n @ 30..=37 => style = style.fg(COLORS[(n - 30) as usize]),
+            39 => style = style.fg(base.fg.unwrap_or(Color::Reset)),
+            n @ 90..=97 => style = style.fg(BRIGHT[(n - 90) as usize]),
+            _ => {}
+        }
+    }
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_detection_and_rendering() {
+        assert_eq!(detect(r#"{"ok":true,"files":[1,2]}"#), ToolOutputKind::Json);
+        assert_eq!(detect("name,size\nmain.rs,120\nlib.rs,8"), ToolOutputKind::Table(','));
+        assert_eq!(detect("\x1b[31merror\x1b[0m: failed"), ToolOutputKind::Ansi);
+        // Prose with commas, and bracketed text that is not JSON, stay as they are
+        assert_eq!(detect("Done. Edited a, b\nand c, d, e"), ToolOutputKind::Plain);
+        assert_eq!(detect("[INFO] build started"), ToolOutputKind::Plain);
+
+        let json = render(r#"{"ok":true}"#, Style::default());
+        assert_eq!(text(&json), vec!["{", "  \"ok\": true", "}"]);
+        assert_eq!(json[1].spans[1].style.fg, Some(Color::Cyan));
+
+        let table = render("name\tsize\nmain.rs\t120", Style::default());
+        assert_eq!(text(&table), vec!["name     size", "main.rs  120"]);
+
+        let ansi = render("\x1b[31merror\x1b[0m: failed", Style::default());
+        assert_eq!(text(&ansi), vec!["error: failed"]);
+        assert_eq!(ansi[0].spans[0].style.fg, Some(Color::Red));
+    }
+}
@@ -0,0 +1,226 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use std::collections::HashMap;
+
+use super::tool_output;
+use crate::types::ChatEntry;
+
+/// Formats one successful tool result for the transcript. It gets the result text and the
+/// call's arguments, or `Null` when they are missing or not JSON.
+pub type ToolRenderer = fn(&str, &serde_json::Value, Style) -> Vec<Line<'static>>;
+
+/// Renderers keyed by tool name. Tools without one, and failed calls, use the generic
+/// [`tool_output::render`].
+#[derive(Clone)]
+pub struct RendererRegistry {
+    renderers: HashMap<&'static str, ToolRenderer>,
+}
+
+impl Default for RendererRegistry {
+    fn default() -> Self {
+        let mut registry = Self { renderers: HashMap::new() };
+        registry.register("create_todo_list", render_todo_list);
+        registry.register("update_todo_list", render_todo_list);
+        registry.register("search", render_search_results);
+        registry.register("str_replace_editor", render_replacement);
+        registry.register("edit_file", render_diff);
+        registry
+    }
+}
+
+impl RendererRegistry {
+    pub fn register(&mut self, tool: &'static str, renderer: ToolRenderer) {
+        self.renderers.insert(tool, renderer);
+    }
+
+    /// Lines for a tool result entry. The raw text sent to the model is unchanged.
+    pub fn render(&self, entry: &ChatEntry, base: Style) -> Vec<Line<'static>> {
+        let succeeded = entry.tool_result.as_ref().is_none_or(|result| result.success);
+        let renderer = entry
+            .tool_call
+            .as_ref()
+            .filter(|_| succeeded)
+            .and_then(|call| Some((call, self.renderers.get(call.function.name.as_str())?)));
+        match renderer {
+            Some((call, renderer)) => {
+                let arguments = serde_json::from_str(&call.function.arguments).unwrap_or(serde_json::Value::Null);
+                renderer(&entry.content, &arguments, base)
+            }
+            None => tool_output::render(&entry.content, base),
+        }
+    }
+}
+
+/// The todo tool marks items ○ pending, ◐ in progress and ● completed (with a leading `~`);
+/// they are shown with the icons and colors the system prompt tells the model about
+fn render_todo_list(content: &str, _arguments: &serde_json::Value, base: Style) -> Vec<Line<'static>> {
+    content
+        .lines()
+        .map(|line| {
+            let item = line.trim_start();
+            let (icon, style, text) = if let Some(text) = item.strip_prefix("● ") {
+                ("✅", Style::default().fg(Color::Green).add_modifier(Modifier::CROSSED_OUT), text.trim_start_matches('~'))
+            } else if let Some(text) = item.strip_prefix("◐ ") {
+                ("🔄", Style::default().fg(Color::Cyan), text)
+            } else if let Some(text) = item.strip_prefix("○ ") {
+                ("⏳", Style::default().fg(Color::Yellow), text)
+            } else {
+                return Line::styled(line.to_string(), base);
+            };
+            Line::from(vec![Span::raw(format!("  {} ", icon)), Span::styled(text.to_string(), style)])
+        })
+        .collect()
+}
+
+/// `Search results for "…":` followed by `  path (n matches)` lines, each with its `    12: line`
+/// matches and dimmed `    11- line` context, a `... +more` line and the `Files named like` list
+fn render_search_results(content: &str, _arguments: &serde_json::Value, base: Style) -> Vec<Line<'static>> {
+    let dim = Style::default().fg(Color::DarkGray);
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            if index == 0 || !line.starts_with(' ') {
+                return Line::styled(line.to_string(), base.add_modifier(Modifier::BOLD));
+            }
+            if line.trim_start().starts_with("... +") {
+                return Line::styled(line.to_string(), dim);
+            }
+            let numbered = line.trim_start();
+            let digits = numbered.len() - numbered.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if line.starts_with("    ") && digits > 0 {
+                let context = numbered[digits..].starts_with("- ");
+                return Line::from(vec![
+                    Span::styled(line[..line.len() - numbered.len() + digits + 1].to_string(), dim),
+                    Span::styled(numbered[digits + 1..].to_string(), if context { dim } else { base }),
+                ]);
+            }
+            match line.rfind(" (") {
+                Some(at) if line.ends_with(" matches)") => Line::from(vec![
+                    Span::styled(line[..at].to_string(), Style::default().fg(Color::Cyan)),
+                    Span::styled(line[at..].to_string(), dim),
+                ]),
+                _ => Line::styled(line.to_string(), Style::default().fg(Color::Cyan)),
+            }
+        })
+        .collect()
+}
+
+/// The result's diff below its first line, or for results that only say which file changed,
+/// the replaced and new text from the call's `old_str` and `new_str`
+fn render_replacement(content: &str, arguments: &serde_json::Value, base: Style) -> Vec<Line<'static>> {
+    if let Some((first, diff)) = content.split_once('\n') {
+        let mut lines = vec![Line::styled(first.to_string(), base)];
+        lines.extend(diff_lines(diff, base));
+        return lines;
+    }
+    let mut lines = vec![Line::styled(content.to_string(), base)];
+    let text = |key: &str| arguments.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let (old, new) = (text("old_str"), text("new_str"));
+    if old.is_empty() && new.is_empty() {
+        return lines;
+    }
+    lines.extend(old.lines().map(|line| Line::styled(format!("- {}", line), Style::default().fg(Color::Red))));
+    lines.extend(new.lines().map(|line| Line::styled(format!("+ {}", line), Style::default().fg(Color::Green))));
+    lines
+}
+
+fn render_diff(content: &str, _arguments: &serde_json::Value, base: Style) -> Vec<Line<'static>> {
+    diff_lines(content, base)
+}
+
+/// Unified diff coloring: headers bold, hunks cyan, additions green, removals red
+pub fn diff_lines(content: &str, base: Style) -> Vec<Line<'static>> {
+    content
+        .lines()
+        .map(|line| {
+            let style = if line.starts_with("+++") || line.starts_with("---") {
+                base.add_modifier(Modifier::BOLD)
+            } else if line.starts_with("@@") {
+                Style::default().fg(Color::Cyan)
+            } else if line.starts_with('+') {
+                Style::default().fg(Color::Green)
+            } else if line.starts_with('-') {
+                Style::default().fg(Color::Red)
+            } else {
+                base
+            };
+            Line::styled(line.to_string(), style)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatEntryType, GrokToolCall, GrokToolCallFunction, ToolResult};
+
+    fn entry(tool: &str, arguments: &str, content: &str, success: bool) -> ChatEntry {
+        ChatEntry {
+            entry_type: ChatEntryType::ToolResult,
+            content: content.to_string(),
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: Some(GrokToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: GrokToolCallFunction { name: tool.to_string(), arguments: arguments.to_string() },
+            }),
+            tool_result: Some(ToolResult { success, output: None, error: None, data: None }),
+            is_streaming: None,
+        }
+    }
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_registry_renders_by_tool_name_and_falls_back_to_generic_output() {
+        let registry = RendererRegistry::default();
+        let base = Style::default();
+
+        let todos = registry.render(&entry("update_todo_list", "{}", "● ~Write parser\n  ◐ Add tests\n  ○ Update docs", true), base);
+        assert_eq!(text(&todos), vec!["  ✅ Write parser", "  🔄 Add tests", "  ⏳ Update docs"]);
+        assert_eq!(todos[0].spans[1].style.fg, Some(Color::Green));
+        assert!(todos[0].spans[1].style.add_modifier.contains(Modifier::CROSSED_OUT));
+        assert_eq!(todos[1].spans[1].style.fg, Some(Color::Cyan));
+        assert_eq!(todos[2].spans[1].style.fg, Some(Color::Yellow));
+
+        let search = registry.render(
+            &entry("search", "{}", "Search results for \"parse\":\n  src/lib.rs (3 matches)\n    1- use std::io;\n    2: fn parse() {}\nFiles named like \"parse\":\n  src/parse.rs", true),
+            base,
+        );
+        assert_eq!(search[1].spans[0].content, "  src/lib.rs");
+        assert_eq!(search[1].spans[1].style.fg, Some(Color::DarkGray));
+        assert_eq!(search[2].spans[1].style.fg, Some(Color::DarkGray));
+        assert_eq!((search[3].spans[0].content.as_ref(), search[3].spans[1].style), ("    2:", base));
+        assert_eq!(search[4].style.add_modifier, Modifier::BOLD);
+        assert_eq!(search[5].style.fg, Some(Color::Cyan));
+
+        let replace = registry.render(
+            &entry("str_replace_editor", r#"{"path":"a.rs","old_str":"let x = 1;","new_str":"let x = 2;"}"#, "Successfully replaced text in a.rs", true),
+            base,
+        );
+        assert_eq!(text(&replace), vec!["Successfully replaced text in a.rs", "- let x = 1;", "+ let x = 2;"]);
+        assert_eq!(replace[2].style.fg, Some(Color::Green));
+        let diffed = registry.render(
+            &entry("str_replace_editor", "{}", "Successfully replaced text in a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1,1 +1,1 @@\n-let x = 1;\n+let x = 2;", true),
+            base,
+        );
+        assert_eq!(diffed.len(), 6);
+        assert_eq!(diffed[1].style.add_modifier, Modifier::BOLD);
+        assert_eq!((diffed[3].style.fg, diffed[4].style.fg, diffed[5].style.fg), (Some(Color::Cyan), Some(Color::Red), Some(Color::Green)));
+
+        // Failed calls and tools without a renderer get the generic formatting
+        let failed = registry.render(&entry("update_todo_list", "{}", "○ not a list", false), base);
+        assert_eq!(text(&failed), vec!["○ not a list"]);
+        let json = registry.render(&entry("bash", "{}", r#"{"ok":true}"#, true), base);
+        assert_eq!(text(&json), vec!["{", "  \"ok\": true", "}"]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where a resolved configuration value came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSource {
+    Flag,
+    /// Set in the process environment before any `.env` file was read
+    Environment,
+    EnvFile(PathBuf),
+    /// `.grok/project-settings.json`
+    ProjectSettings(PathBuf),
+    UserSettings,
+    Default,
+}
+
+impl std::fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueSource::Flag => write!(f, "command line"),
+            ValueSource::Environment => write!(f, "environment"),
+            ValueSource::EnvFile(path) | ValueSource::ProjectSettings(path) => write!(f, "{}", path.display()),
+            ValueSource::UserSettings => write!(f, "user settings"),
+            ValueSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// What loading the `.env` files did: the source of every key they mention, and conflicts
+#[derive(Debug, Default)]
+pub struct EnvFileReport {
+    pub sources: HashMap<String, ValueSource>,
+    pub warnings: Vec<String>,
+}
+
+static ENV_SOURCES: OnceLock<HashMap<String, ValueSource>> = OnceLock::new();
+static RESOLVED: OnceLock<Vec<ResolvedValue>> = OnceLock::new();
+
+/// `~/.grok/.env`
+pub fn home_env_file() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".grok").join(".env"))
+}
+
+/// The nearest `.env` in `start` or one of its parents, as `dotenvy::dotenv` would find it
+pub fn project_env_file(start: &Path) -> Option<PathBuf> {
+    start.ancestors().map(|dir| dir.join(".env")).find(|path| path.is_file())
+}
+
+/// Load the home `.env`, then the project `.env` on top of it. Variables already set in the
+/// process environment win over both, and the project file wins over the home file.
+pub fn load_env_files(home: Option<&Path>, project: Option<&Path>) -> EnvFileReport {
+    let mut report = EnvFileReport::default();
+    // Highest precedence first
+    let layers: Vec<(PathBuf, Vec<(String, String)>)> = [project, home]
+        .into_iter()
+        .flatten()
+        .filter(|path| path.is_file())
+        // The same file can be both when running from the home directory
+        .fold(Vec::<&Path>::new(), |mut paths, path| {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+            paths
+        })
+        .into_iter()
+        .filter_map(|path| {
+            let entries = dotenvy::from_path_iter(path).ok()?.filter_map(Result::ok).collect();
+            Some((path.to_path_buf(), entries))
+        })
+        .collect();
+
+    let mut keys: Vec<&String> = layers.iter().flat_map(|(_, entries)| entries.iter().map(|(k, _)| k)).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let mut definitions: Vec<(ValueSource, &str)> = Vec::new();
+        let process_value = std::env::var(key).ok();
+        if let Some(value) = &process_value {
+            definitions.push((ValueSource::Environment, value));
+        }
+        for (path, entries) in &layers {
+            if let Some((_, value)) = entries.iter().rev().find(|(k, _)| k == key) {
+                definitions.push((ValueSource::EnvFile(path.clone()), value));
+            }
+        }
+
+        let (winner, value) = definitions[0].clone();
+        let others: Vec<String> = definitions[1..]
+            .iter()
+            .filter(|(_, other)| *other != value)
+            .map(|(source, _)| source.to_string())
+            .collect();
+        if !others.is_empty() {
+            report.warnings.push(format!(
+                "{} is set with different values in {} and {}; using {}",
+                key,
+                winner,
+                others.join(", "),
+                winner
+            ));
+        }
+        report.sources.insert(key.clone(), winner);
+    }
+
+    // dotenvy never overrides a variable that is already set, so loading in precedence order
+    // leaves the right value in place
+    for (path, _) in &layers {
+        let _ = dotenvy::from_path(path);
+    }
+    report
+}
+
+/// Remember where each environment variable came from, for later `source_of_env` lookups
+pub fn record_env_sources(report: &EnvFileReport) {
+    let _ = ENV_SOURCES.set(report.sources.clone());
+}
+
+/// Source of an environment variable that is set: a `.env` file or the process environment
+pub fn source_of_env(key: &str) -> ValueSource {
+    ENV_SOURCES
+        .get()
+        .and_then(|sources| sources.get(key).cloned())
+        .unwrap_or(ValueSource::Environment)
+}
+
+/// A setting after precedence was applied, shown by `grok doctor` and `/settings`
+#[derive(Debug, Clone)]
+pub struct ResolvedValue {
+    pub name: &'static str,
+    pub value: String,
+    pub source: ValueSource,
+    /// Hide the value when displayed (API keys)
+    pub secret: bool,
+}
+
+impl ResolvedValue {
+    pub fn describe(&self) -> String {
+        let value = if self.secret && self.value.chars().count() > 8 {
+            format!("{}…", self.value.chars().take(4).collect::<String>())
+        } else if self.secret {
+            "…".to_string()
+        } else {
+            self.value.clone()
+        };
+        format!("{:<13} {}  ({})", self.name, value, self.source)
+    }
+}
+
+/// Resolve one setting as flag > environment / `.env` > project settings > user settings. A value
+/// set both in the environment and in user settings with a different value is reported, since
+/// only one is used; a project value is expected to override the user's.
+pub fn resolve_setting(
+    name: &'static str,
+    flag: Option<String>,
+    env_key: &str,
+    project: Option<(String, PathBuf)>,
+    settings: Option<String>,
+    warnings: &mut Vec<String>,
+) -> Option<ResolvedValue> {
+    let env = std::env::var(env_key).ok().filter(|value| !value.is_empty());
+    if let (Some(env_value), Some(settings_value)) = (&env, &settings)
+        && env_value != settings_value
+    {
+        warnings.push(format!(
+            "{} is set in {} ({}) and in user settings with a different value; the {} value is used{}",
+            name,
+            env_key,
+            source_of_env(env_key),
+            env_key,
+            if flag.is_some() { ", unless overridden on the command line" } else { "" }
+        ));
+    }
+
+    let secret = name == "api_key";
+    let layers = [
+        (flag, ValueSource::Flag),
+        (env, source_of_env(env_key)),
+        project.map_or((None, ValueSource::Default), |(value, path)| (Some(value), ValueSource::ProjectSettings(path))),
+        (settings, ValueSource::UserSettings),
+    ];
+    layers
+        .into_iter()
+        .find_map(|(value, source)| value.map(|value| ResolvedValue { name, value, source, secret }))
+}
+
+pub fn record_resolved(values: Vec<ResolvedValue>) {
+    let _ = RESOLVED.set(values);
+}
+
+/// Resolved settings as display lines, empty before startup finished resolving them
+pub fn resolved_lines() -> Vec<String> {
+    RESOLVED
+        .get()
+        .map(|values| values.iter().map(ResolvedValue::describe).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_env_overrides_home_and_conflicts_are_reported() {
+        let dir = std::env::temp_dir().join(format!("grok-env-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("project")).unwrap();
+        let home = dir.join("home.env");
+        let project = dir.join("project").join(".env");
+        std::fs::write(&home, "GROK_TEST_ENV_MODEL=home-model\nGROK_TEST_ENV_HOME_ONLY=1\n").unwrap();
+        std::fs::write(&project, "GROK_TEST_ENV_MODEL=project-model\n").unwrap();
+
+        let report = load_env_files(Some(&home), Some(&project));
+        assert_eq!(std::env::var("GROK_TEST_ENV_MODEL").unwrap(), "project-model");
+        assert_eq!(std::env::var("GROK_TEST_ENV_HOME_ONLY").unwrap(), "1");
+        assert_eq!(report.sources["GROK_TEST_ENV_MODEL"], ValueSource::EnvFile(project.clone()));
+        assert_eq!(report.sources["GROK_TEST_ENV_HOME_ONLY"], ValueSource::EnvFile(home.clone()));
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].starts_with("GROK_TEST_ENV_MODEL is set with different values"));
+        assert_eq!(project_env_file(&dir.join("project")), Some(project));
+
+        let mut warnings = Vec::new();
+        let resolved = resolve_setting(
+            "model",
+            None,
+            "GROK_TEST_ENV_MODEL",
+            None,
+            Some("settings-model".to_string()),
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(resolved.value, "project-model");
+        assert_eq!(warnings.len(), 1);
+        let flagged = resolve_setting("model", Some("flag".to_string()), "GROK_TEST_ENV_UNSET", None, None, &mut warnings).unwrap();
+        assert_eq!(flagged.source, ValueSource::Flag);
+        let project_file = dir.join("project").join(".grok").join("project-settings.json");
+        let project_model = Some(("project-default".to_string(), project_file.clone()));
+        let from_project = resolve_setting("model", None, "GROK_TEST_ENV_UNSET", project_model, Some("user".to_string()), &mut warnings).unwrap();
+        assert_eq!((from_project.value.as_str(), from_project.source), ("project-default", ValueSource::ProjectSettings(project_file)));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,202 @@
+use command_registry::{load_markdown_commands, PromptCommand};
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Slash commands the UI handles itself; a custom command cannot take one of these names
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    "help",
+    "clear",
+    "models",
+    "model",
+    "status",
+    "tool-choice",
+    "replay-tool",
+    "window",
+    "regenerate",
+    "continue",
+    "explain",
+    "cache",
+    "settings",
+    "system",
+    "policy",
+    "save",
+    "export",
+    "undo",
+    "resume",
+    "commit-and-push",
+    "init",
+    "reload-context",
+    "exit",
+];
+
+/// Most prompts one custom command sends
+pub const MAX_COMMAND_STEPS: usize = 5;
+
+/// Longest template preview shown in the command hints when there is no description
+const HINT_PREVIEW_CHARS: usize = 60;
+
+/// One entry of `custom_commands` in user settings: a prompt template, or a short chain of
+/// them. `{args}` in a template is replaced with the text typed after the command name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CustomCommand {
+    /// Shown next to the command in the hints
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Sent in order, each once the reply to the previous one has finished; `prompt` goes first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+}
+
+impl CustomCommand {
+    fn templates(&self) -> impl Iterator<Item = &String> {
+        self.prompt.iter().chain(self.steps.iter())
+    }
+
+    /// What the hints and `/help` show: the description, the start of the first template when
+    /// there is none, and the number of steps of a chain
+    fn about(&self) -> String {
+        let about = match &self.description {
+            Some(description) => description.clone(),
+            None => {
+                let first = self.templates().next().map(|t| t.replace('\n', " ")).unwrap_or_default();
+                if first.chars().count() > HINT_PREVIEW_CHARS {
+                    format!("{}…", first.chars().take(HINT_PREVIEW_CHARS).collect::<String>())
+                } else {
+                    first
+                }
+            }
+        };
+        let steps = self.templates().count();
+        if steps > 1 {
+            format!("{} ({} steps)", about, steps.min(MAX_COMMAND_STEPS))
+        } else {
+            about
+        }
+    }
+
+    fn to_prompt_command(&self, name: &str) -> PromptCommand {
+        PromptCommand::new(name, &self.about(), self.templates().take(MAX_COMMAND_STEPS).cloned().collect())
+    }
+}
+
+/// The custom commands that passed validation, by name without the slash
+#[derive(Debug, Clone, Default)]
+pub struct CustomCommands {
+    commands: BTreeMap<String, PromptCommand>,
+}
+
+impl CustomCommands {
+    /// Keep the usable commands from settings and from the `.md` files in `commands_dir`
+    /// (`.grok/commands` of the project). Names in settings may be written with or without the
+    /// slash; a name with whitespace, one that shadows a built-in command, or a command with no
+    /// prompt is dropped with a warning. A file named like a command from settings loses to it.
+    pub fn new(configured: BTreeMap<String, CustomCommand>, commands_dir: &Path) -> (Self, Vec<String>) {
+        let mut commands = BTreeMap::new();
+        let mut warnings = Vec::new();
+        for (name, command) in configured {
+            let name = name.trim().trim_start_matches('/').to_string();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                warnings.push(format!("Ignoring custom command '{}': names cannot be empty or contain spaces", name));
+            } else if BUILTIN_COMMANDS.contains(&name.as_str()) {
+                warnings.push(format!("Ignoring custom command /{}: it would shadow the built-in command", name));
+            } else if command.templates().all(|t| t.trim().is_empty()) {
+                warnings.push(format!("Ignoring custom command /{}: it has no prompt or steps", name));
+            } else {
+                if command.templates().count() > MAX_COMMAND_STEPS {
+                    warnings.push(format!(
+                        "Custom command /{} has more than {} steps; only the first {} run",
+                        name, MAX_COMMAND_STEPS, MAX_COMMAND_STEPS
+                    ));
+                }
+                commands.insert(name.clone(), command.to_prompt_command(&name));
+            }
+        }
+
+        let (from_files, file_warnings) = load_markdown_commands(commands_dir);
+        warnings.extend(file_warnings);
+        for command in from_files {
+            let name = command.name().to_string();
+            if BUILTIN_COMMANDS.contains(&name.as_str()) {
+                warnings.push(format!("Ignoring {}/{}.md: it would shadow the built-in command", commands_dir.display(), name));
+                continue;
+            }
+            match commands.entry(name) {
+                Entry::Occupied(taken) => warnings.push(format!(
+                    "Ignoring {}/{}.md: custom_commands in settings already has /{}",
+                    commands_dir.display(),
+                    taken.key(),
+                    taken.key()
+                )),
+                Entry::Vacant(slot) => {
+                    slot.insert(command);
+                }
+            }
+        }
+        (Self { commands }, warnings)
+    }
+
+    /// In name order
+    pub fn commands(&self) -> impl Iterator<Item = &PromptCommand> {
+        self.commands.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_commands_expand_args_chain_steps_and_load_markdown_files() {
+        let dir = std::env::temp_dir().join(format!("grok-custom-commands-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("changelog.md"), "---\ndescription: Draft a changelog entry\n---\nWrite a changelog entry for {args}.\n").unwrap();
+        std::fs::write(dir.join("explain-error.md"), "Loses to the settings entry").unwrap();
+        std::fs::write(dir.join("exit.md"), "Shadowed").unwrap();
+
+        let configured: BTreeMap<String, CustomCommand> = serde_json::from_str(
+            r#"{
+                "/review-and-test": {
+                    "description": "Review a file, then write tests for it",
+                    "prompt": "Review {args} for bugs.",
+                    "steps": ["Now write unit tests for {args}."]
+                },
+                "explain-error": { "prompt": "Explain this error and how to fix it: {args}" },
+                "clear": { "prompt": "shadowed" },
+                "empty": { "steps": ["  "] }
+            }"#,
+        )
+        .unwrap();
+        let (commands, warnings) = CustomCommands::new(configured, &dir);
+
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(warnings.iter().any(|w| w.contains("/clear")));
+        assert!(warnings.iter().any(|w| w.contains("exit.md")));
+        assert!(warnings.iter().any(|w| w.contains("explain-error.md")));
+        let by_name = |name: &str| commands.commands().find(|c| c.name() == name).unwrap().clone();
+        let commands_and_descriptions: Vec<(String, String)> = commands
+            .commands()
+            .map(|c| (c.name().to_string(), c.description().to_string()))
+            .collect();
+        assert_eq!(
+            commands_and_descriptions,
+            vec![
+                ("changelog".to_string(), "Draft a changelog entry".to_string()),
+                ("explain-error".to_string(), "Explain this error and how to fix it: {args}".to_string()),
+                ("review-and-test".to_string(), "Review a file, then write tests for it (2 steps)".to_string()),
+            ]
+        );
+        assert_eq!(
+            by_name("review-and-test").expand("src/lib.rs"),
+            vec!["Review src/lib.rs for bugs.".to_string(), "Now write unit tests for src/lib.rs.".to_string()]
+        );
+        assert_eq!(by_name("explain-error").expand(""), vec!["Explain this error and how to fix it:".to_string()]);
+        assert_eq!(by_name("changelog").expand("v2"), vec!["Write a changelog entry for v2.".to_string()]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// One file a turn changed, with line counts from comparing its content before and after
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Dirty files in a git work tree and their content, taken before a bash command runs
+#[derive(Debug, Default)]
+pub struct GitSnapshot {
+    root: PathBuf,
+    files: HashMap<PathBuf, Option<String>>,
+}
+
+/// Content of every file a turn touched, as it was before the turn first touched it.
+/// Comparing that with the disk at the end of the turn gives the turn's changes.
+#[derive(Debug, Clone, Default)]
+pub struct TurnChanges {
+    originals: BTreeMap<PathBuf, Option<String>>,
+}
+
+fn read(path: &Path) -> Option<String> {
+    std::fs::read(path).ok().map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files `git status` reports as changed or untracked, joined onto the work tree root
+fn dirty_paths(root: &Path) -> Vec<PathBuf> {
+    let Some(status) = git(root, &["status", "--porcelain", "-z", "--untracked-files=all"]) else {
+        return Vec::new();
+    };
+    let mut paths = Vec::new();
+    let mut entries = status.split('\0').filter(|entry| entry.len() > 3);
+    while let Some(entry) = entries.next() {
+        let (code, path) = entry.split_at(3);
+        paths.push(root.join(path));
+        // Renames and copies are followed by the original path
+        if (code.contains('R') || code.contains('C'))
+            && let Some(original) = entries.next()
+        {
+            paths.push(root.join(original));
+        }
+    }
+    paths
+}
+
+/// Lines added and removed between two versions, counted like `git diff --numstat` for
+/// edits that do not move lines around
+pub fn line_delta(old: &str, new: &str) -> (usize, usize) {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for line in old.lines() {
+        *counts.entry(line).or_default() += 1;
+    }
+    for line in new.lines() {
+        *counts.entry(line).or_default() -= 1;
+    }
+    counts.values().fold((0, 0), |(added, removed), &n| {
+        if n < 0 {
+            (added + (-n) as usize, removed)
+        } else {
+            (added, removed + n as usize)
+        }
+    })
+}
+
+/// One `@@ -old_start,old_lines +new_start,new_lines @@` range of a diff
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    /// Text git shows after the second `@@`, usually the enclosing function
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub context: String,
+}
+
+/// One file whose work-tree content differs from HEAD
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HeadDiffFile {
+    /// Relative to the work tree root
+    pub path: String,
+    /// "added", "modified", "deleted" or "untracked"
+    pub status: &'static str,
+    pub binary: bool,
+    pub added: usize,
+    pub removed: usize,
+    pub hunks: Vec<DiffHunk>,
+    /// The file's part of `git diff HEAD`; empty for untracked and binary files
+    #[serde(skip)]
+    pub patch: String,
+}
+
+/// `-12,3` or `+7` from a hunk header
+fn hunk_range(range: &str) -> Option<(usize, usize)> {
+    let (start, lines) = range[1..].split_once(',').unwrap_or((&range[1..], "1"));
+    Some((start.parse().ok()?, lines.parse().ok()?))
+}
+
+fn parse_hunk_header(line: &str) -> Option<DiffHunk> {
+    let rest = line.strip_prefix("@@ ")?;
+    let (ranges, context) = rest.split_once(" @@").unwrap_or((rest, ""));
+    let (old, new) = ranges.split_once(' ')?;
+    let (old_start, old_lines) = hunk_range(old)?;
+    let (new_start, new_lines) = hunk_range(new)?;
+    Some(DiffHunk { old_start, old_lines, new_start, new_lines, context: context.trim().to_string() })
+}
+
+/// Split `git diff` output into files, counting lines and collecting hunk headers
+fn parse_diff(diff: &str) -> Vec<HeadDiffFile> {
+    let mut files: Vec<HeadDiffFile> = Vec::new();
+    let mut in_hunks = false;
+    for line in diff.split_inclusive('\n') {
+        let text = line.trim_end_matches('\n');
+        if let Some(header) = text.strip_prefix("diff --git ") {
+            let path = header.rsplit_once(" b/").map_or(header, |(_, path)| path).to_string();
+            files.push(HeadDiffFile {
+                path,
+                status: "modified",
+                binary: false,
+                added: 0,
+                removed: 0,
+                hunks: Vec::new(),
+                patch: String::new(),
+            });
+            in_hunks = false;
+        }
+        let Some(file) = files.last_mut() else { continue };
+        file.patch.push_str(line);
+        if let Some(hunk) = parse_hunk_header(text) {
+            file.hunks.push(hunk);
+            in_hunks = true;
+        } else if in_hunks {
+            if text.starts_with('+') {
+                file.added += 1;
+            } else if text.starts_with('-') {
+                file.removed += 1;
+            }
+        } else if text.starts_with("new file mode") {
+            file.status = "added";
+        } else if text.starts_with("deleted file mode") {
+            file.status = "deleted";
+        } else if text.starts_with("Binary files") {
+            file.binary = true;
+        }
+    }
+    for file in files.iter_mut().filter(|file| file.binary) {
+        file.patch.clear();
+    }
+    files
+}
+
+/// How the work tree under `dir` differs from HEAD, including untracked files, optionally
+/// limited to `pathspec`. In a repository without commits every file counts as added.
+pub fn diff_against_head(dir: &Path, pathspec: Option<&str>) -> Result<Vec<HeadDiffFile>, String> {
+    let root = git(dir, &["rev-parse", "--show-toplevel"])
+        .map(|root| PathBuf::from(root.trim()))
+        .ok_or_else(|| format!("{} is not inside a git work tree", dir.display()))?;
+    // The empty tree, for a repository with no commits yet
+    let base = if git(dir, &["rev-parse", "--verify", "-q", "HEAD"]).is_some() {
+        "HEAD"
+    } else {
+        "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+    };
+    let pathspec = pathspec.unwrap_or(".");
+    let diff = git(
+        dir,
+        &["-c", "core.quotePath=false", "diff", "--no-color", "--no-renames", "--no-ext-diff", base, "--", pathspec],
+    )
+    .ok_or("git diff failed")?;
+    let mut files = parse_diff(&diff);
+
+    let untracked = git(dir, &["ls-files", "--others", "--exclude-standard", "--full-name", "-z", "--", pathspec]).unwrap_or_default();
+    for path in untracked.split('\0').filter(|path| !path.is_empty()) {
+        let bytes = std::fs::read(root.join(path)).unwrap_or_default();
+        let text = std::str::from_utf8(&bytes).ok();
+        files.push(HeadDiffFile {
+            path: path.to_string(),
+            status: "untracked",
+            binary: text.is_none(),
+            added: text.map_or(0, |text| text.lines().count()),
+            removed: 0,
+            hunks: Vec::new(),
+            patch: String::new(),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+impl GitSnapshot {
+    /// `None` outside a git work tree, where bash side effects are not tracked
+    pub fn take(dir: &Path) -> Option<Self> {
+        let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?.trim());
+        let files = dirty_paths(&root).into_iter().map(|path| {
+            let content = read(&path);
+            (path, content)
+        });
+        Some(Self { files: files.collect(), root })
+    }
+}
+
+impl TurnChanges {
+    /// Call before a tool writes `path`; only the first call in a turn keeps the content
+    pub fn record_before(&mut self, path: &Path) {
+        let path = absolute(path);
+        self.originals.entry(path).or_insert_with_key(|path| read(path));
+    }
+
+    /// Call after a bash command with the snapshot taken before it. Files that were clean
+    /// before take their original content from HEAD.
+    pub fn record_bash(&mut self, before: &GitSnapshot) {
+        let mut paths: Vec<PathBuf> = dirty_paths(&before.root);
+        paths.extend(before.files.keys().cloned());
+        for path in paths {
+            if self.originals.contains_key(&path) {
+                continue;
+            }
+            let original = match before.files.get(&path) {
+                Some(original) => original.clone(),
+                None => {
+                    let relative = path.strip_prefix(&before.root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+                    git(&before.root, &["show", &format!("HEAD:{}", relative)])
+                }
+            };
+            if original != read(&path) {
+                self.originals.insert(path, original);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.originals.clear();
+    }
+
+    /// Files whose content differs from before the turn, in path order
+    pub fn changes(&self) -> Vec<FileChange> {
+        self.originals
+            .iter()
+            .filter_map(|(path, original)| {
+                let current = read(path);
+                let (kind, (added, removed)) = match (original, &current) {
+                    (None, None) => return None,
+                    (None, Some(new)) => (ChangeKind::Created, line_delta("", new)),
+                    (Some(old), None) => (ChangeKind::Deleted, line_delta(old, "")),
+                    (Some(old), Some(new)) if old == new => return None,
+                    (Some(old), Some(new)) => (ChangeKind::Modified, line_delta(old, new)),
+                };
+                Some(FileChange { path: path.clone(), kind, added, removed })
+            })
+            .collect()
+    }
+
+    /// `git add` every changed file, deletions included, and return the staged paths. Files
+    /// outside a git work tree and files git ignores are left alone.
+    pub fn stage(&self) -> Vec<PathBuf> {
+        self.changes()
+            .into_iter()
+            .filter_map(|change| {
+                // Run git next to the file so each file goes to the repository it belongs to
+                let dir = change.path.parent().filter(|dir| dir.is_dir())?;
+                let name = change.path.file_name()?.to_string_lossy().into_owned();
+                git(dir, &["rev-parse", "--show-toplevel"])?;
+                if git(dir, &["check-ignore", "-q", "--", &name]).is_some() {
+                    return None;
+                }
+                git(dir, &["add", "-A", "--", &name])?;
+                Some(change.path)
+            })
+            .collect()
+    }
+
+    /// One-line changelog such as `Changed: src/a.rs (+3 -1), old.rs (deleted, -12)`
+    pub fn summary(&self) -> Option<String> {
+        let changes = self.changes();
+        if changes.is_empty() {
+            return None;
+        }
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let files: Vec<String> = changes
+            .iter()
+            .map(|change| {
+                let path = change.path.strip_prefix(&cwd).unwrap_or(&change.path).display();
+                match change.kind {
+                    ChangeKind::Created => format!("{} (new, +{})", path, change.added),
+                    ChangeKind::Deleted => format!("{} (deleted, -{})", path, change.removed),
+                    ChangeKind::Modified => format!("{} (+{} -{})", path, change.added, change.removed),
+                }
+            })
+            .collect();
+        Some(format!("📝 Changed: {}", files.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turn_changes_report_created_modified_and_deleted_files() {
+        let dir = std::env::temp_dir().join(format!("grok-changes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let modified = dir.join("a.rs");
+        let deleted = dir.join("b.rs");
+        let created = dir.join("c.rs");
+        let untouched = dir.join("d.rs");
+        std::fs::write(&modified, "one\ntwo\nthree\n").unwrap();
+        std::fs::write(&deleted, "x\ny\n").unwrap();
+        std::fs::write(&untouched, "same\n").unwrap();
+
+        let mut changes = TurnChanges::default();
+        for path in [&modified, &deleted, &created, &untouched] {
+            changes.record_before(path);
+        }
+        std::fs::write(&modified, "one\n2\nthree\nfour\n").unwrap();
+        // Only the content before the first write counts
+        changes.record_before(&modified);
+        std::fs::remove_file(&deleted).unwrap();
+        std::fs::write(&created, "new\n").unwrap();
+
+        let kinds: Vec<(ChangeKind, usize, usize)> =
+            changes.changes().iter().map(|c| (c.kind, c.added, c.removed)).collect();
+        assert_eq!(
+            kinds,
+            vec![(ChangeKind::Modified, 2, 1), (ChangeKind::Deleted, 0, 2), (ChangeKind::Created, 1, 0)]
+        );
+        let summary = changes.summary().unwrap();
+        assert!(summary.contains("a.rs (+2 -1)"), "{}", summary);
+        assert!(summary.contains("b.rs (deleted, -2)"));
+        assert!(!summary.contains("d.rs"));
+
+        changes.clear();
+        assert_eq!(changes.summary(), None);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_stage_adds_changed_files_but_not_ignored_or_outside_ones() {
+        let base = std::env::temp_dir().join(format!("grok-stage-{}", std::process::id()));
+        let repo = base.join("repo");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        if git(&repo, &["init", "-q"]).is_none() {
+            return;
+        }
+        std::fs::write(repo.join(".gitignore"), "*.log\n").unwrap();
+
+        let mut changes = TurnChanges::default();
+        let (edited, ignored, elsewhere) = (repo.join("main.rs"), repo.join("build.log"), outside.join("notes.txt"));
+        for path in [&edited, &ignored, &elsewhere] {
+            changes.record_before(path);
+            std::fs::write(path, "new\n").unwrap();
+        }
+
+        assert_eq!(changes.stage(), vec![absolute(&edited)]);
+        let staged = git(&repo, &["diff", "--staged", "--name-only"]).unwrap();
+        assert_eq!(staged.trim(), "main.rs");
+        std::fs::remove_dir_all(&base).ok();
+    }
+}
@@ -1 +1,9 @@
-pub mod settings_manager;
\ No newline at end of file
+pub mod settings_manager;
+pub mod config_sources;
+pub mod session_store;
+pub mod file_changes;
+pub mod storage;
+pub mod custom_commands;
+pub mod project_context;
+pub mod text_diff;
+pub mod transcript;
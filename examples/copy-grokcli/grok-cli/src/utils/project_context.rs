@@ -0,0 +1,195 @@
+//! Project instructions from `GROK.md` files, added to the system prompt. Each directory from
+//! the working directory up to the git root may have one, with `AGENTS.md` and `CLAUDE.md` read
+//! when it has no `GROK.md`; `~/.grok/GROK.md` applies to every project.
+
+use std::path::{Path, PathBuf};
+
+/// Names tried in each directory; the first one that exists is used
+pub const CONTEXT_FILE_NAMES: &[&str] = &["GROK.md", "AGENTS.md", "CLAUDE.md"];
+
+/// Characters kept from one file; the rest is cut with a note
+pub const MAX_FILE_CHARS: usize = 16_000;
+
+/// Characters of all files together; files past it are left out, lowest priority first
+pub const MAX_CONTEXT_CHARS: usize = 40_000;
+
+/// Entries of the directory listing `/init` sends to the model
+const MAX_OVERVIEW_ENTRIES: usize = 200;
+
+/// Lines of each key file `/init` sends
+const KEY_FILE_LINES: usize = 40;
+
+/// Files whose start says the most about a project: its readme, manifests and build files
+const KEY_FILES: &[&str] = &[
+    "README.md",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "Makefile",
+    "Dockerfile",
+    "CONTRIBUTING.md",
+];
+
+/// One instructions file, as it goes into the prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextFile {
+    pub path: PathBuf,
+    pub text: String,
+    /// The file was longer than `MAX_FILE_CHARS`
+    pub truncated: bool,
+}
+
+/// The instructions files found, highest priority first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectContext {
+    pub files: Vec<ContextFile>,
+    /// Files that did not fit in `MAX_CONTEXT_CHARS`
+    pub omitted: Vec<PathBuf>,
+}
+
+impl ProjectContext {
+    /// The files for `cwd`: its own first, then each parent's up to the git root (only `cwd`
+    /// outside a repository), then `~/.grok/GROK.md` under `home`
+    pub fn load(cwd: &Path, home: Option<&Path>) -> Self {
+        let mut candidates = Vec::new();
+        let in_repository = cwd.ancestors().any(|dir| dir.join(".git").exists());
+        for dir in cwd.ancestors() {
+            if let Some(path) = CONTEXT_FILE_NAMES.iter().map(|name| dir.join(name)).find(|path| path.is_file()) {
+                candidates.push(path);
+            }
+            if !in_repository || dir.join(".git").exists() {
+                break;
+            }
+        }
+        if let Some(home) = home {
+            candidates.push(home.join(".grok").join("GROK.md"));
+        }
+
+        let mut context = ProjectContext::default();
+        let mut budget = MAX_CONTEXT_CHARS;
+        for path in candidates {
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let truncated = text.chars().count() > MAX_FILE_CHARS;
+            let text: String = text.chars().take(MAX_FILE_CHARS).collect();
+            let size = text.chars().count();
+            if size > budget {
+                context.omitted.push(path);
+                continue;
+            }
+            budget -= size;
+            context.files.push(ContextFile { path, text, truncated });
+        }
+        context
+    }
+
+    /// The text added to the system prompt; `None` without any files
+    pub fn section(&self) -> Option<String> {
+        if self.files.is_empty() {
+            return None;
+        }
+        let mut section = String::from(
+            "\n\n# Project instructions\n\nFrom the project's instruction files. Where they disagree, the earlier file wins.",
+        );
+        for file in &self.files {
+            section.push_str(&format!("\n\n## {}\n{}", file.path.display(), file.text));
+            if file.truncated {
+                section.push_str(&format!("\n[Cut at {} characters]", MAX_FILE_CHARS));
+            }
+        }
+        Some(section)
+    }
+
+    /// Which files were loaded, for the chat
+    pub fn describe(&self) -> String {
+        if self.files.is_empty() {
+            return format!(
+                "No project instructions found. Add a {} here or at the repository root, or ~/.grok/GROK.md; /init writes a starter one.",
+                CONTEXT_FILE_NAMES[0]
+            );
+        }
+        let mut lines = vec![format!("Project instructions from {} file(s):", self.files.len())];
+        for file in &self.files {
+            let cut = if file.truncated { format!(", cut at {} characters", MAX_FILE_CHARS) } else { String::new() };
+            lines.push(format!("  {} ({} characters{})", file.path.display(), file.text.chars().count(), cut));
+        }
+        for path in &self.omitted {
+            lines.push(format!("  {} left out: over {} characters in all", path.display(), MAX_CONTEXT_CHARS));
+        }
+        lines.join("\n")
+    }
+}
+
+/// What `/init` shows the model about `root`: a listing of the files `.gitignore` lets through
+/// and the first lines of its readme, manifests and build files
+pub fn repository_overview(root: &Path) -> String {
+    let mut listing = Vec::new();
+    let walker = ignore::WalkBuilder::new(root).max_depth(Some(3)).build();
+    for entry in walker.flatten().skip(1) {
+        let path = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_string_lossy().to_string();
+        let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+        listing.push(if is_dir { format!("{}/", path) } else { path });
+        if listing.len() >= MAX_OVERVIEW_ENTRIES {
+            listing.push(format!("… (first {} entries)", MAX_OVERVIEW_ENTRIES));
+            break;
+        }
+    }
+    listing.sort();
+    let mut overview = format!("Files (three levels deep):\n{}", listing.join("\n"));
+    for name in KEY_FILES {
+        if let Ok(text) = std::fs::read_to_string(root.join(name)) {
+            let head: Vec<&str> = text.lines().take(KEY_FILE_LINES).collect();
+            overview.push_str(&format!("\n\n{} (first {} lines):\n{}", name, head.len(), head.join("\n")));
+        }
+    }
+    overview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_files_load_up_to_the_git_root_in_priority_order() {
+        let base = std::env::temp_dir().join(format!("grok-project-context-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (repo, home) = (base.join("repo"), base.join("home"));
+        let crate_dir = repo.join("crates").join("cli");
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(home.join(".grok")).unwrap();
+        // Above the git root, so never read
+        std::fs::write(base.join("GROK.md"), "outside").unwrap();
+        std::fs::write(repo.join("AGENTS.md"), "Run cargo test before committing.").unwrap();
+        std::fs::write(repo.join("CLAUDE.md"), "shadowed by AGENTS.md").unwrap();
+        std::fs::write(crate_dir.join("GROK.md"), "x".repeat(MAX_FILE_CHARS + 10)).unwrap();
+        std::fs::write(home.join(".grok").join("GROK.md"), "Answer briefly.").unwrap();
+
+        let context = ProjectContext::load(&crate_dir, Some(&home));
+        let paths: Vec<PathBuf> = context.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(paths, vec![crate_dir.join("GROK.md"), repo.join("AGENTS.md"), home.join(".grok").join("GROK.md")]);
+        assert!(context.files[0].truncated && context.files[0].text.chars().count() == MAX_FILE_CHARS);
+        let section = context.section().unwrap();
+        assert!(section.starts_with("\n\n# Project instructions"));
+        assert!(section.ends_with(&format!("## {}\nAnswer briefly.", home.join(".grok").join("GROK.md").display())));
+        assert!(!section.contains("outside") && !section.contains("shadowed"));
+
+        // Outside a repository only the directory itself is read
+        std::fs::remove_dir_all(repo.join(".git")).unwrap();
+        assert_eq!(ProjectContext::load(&crate_dir, None).files.len(), 1);
+
+        std::fs::write(repo.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        let overview = repository_overview(&repo);
+        assert!(overview.contains("crates/cli/GROK.md") && overview.contains("Cargo.toml (first 2 lines):\n[package]"));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
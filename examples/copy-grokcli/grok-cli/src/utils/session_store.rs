@@ -0,0 +1,294 @@
+use crate::types::{ChatEntry, ChatEntryType, GrokMessage};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use super::storage::{FileStorage, SharedStorage};
+
+const INDEX_FILE: &str = "index.json";
+const PREVIEW_CHARS: usize = 60;
+
+/// Format of the session files this build writes. Files from before versioning are a bare
+/// array of chat entries and read as version 0.
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+
+/// One saved session: the transcript shown in the UI and the conversation sent to the model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionFile {
+    pub version: u32,
+    pub id: String,
+    pub model: String,
+    pub created_at: DateTime<Utc>,
+    /// Where grok ran; the system prompt is rebuilt for the current directory on resume
+    pub working_directory: String,
+    pub entries: Vec<ChatEntry>,
+    /// Empty for version 0 files, which only kept the transcript
+    #[serde(default)]
+    pub messages: Vec<GrokMessage>,
+}
+
+impl SessionFile {
+    pub fn new(id: &str, model: &str, entries: Vec<ChatEntry>, messages: Vec<GrokMessage>) -> Self {
+        Self {
+            version: SESSION_FORMAT_VERSION,
+            id: id.to_string(),
+            model: model.to_string(),
+            created_at: entries.first().map_or_else(Utc::now, |entry| entry.timestamp),
+            working_directory: std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default(),
+            entries,
+            messages,
+        }
+    }
+
+    /// Read a session file of this or an older format
+    pub fn parse(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        if value.is_array() {
+            return Ok(Self { entries: serde_json::from_value(value)?, ..Self::default() });
+        }
+        let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or_default();
+        if version > u64::from(SESSION_FORMAT_VERSION) {
+            return Err(format!(
+                "This session was saved in format version {} by a newer grok; this build reads up to version {}. Update grok to open it.",
+                version, SESSION_FORMAT_VERSION
+            )
+            .into());
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// Index entry for a saved session, kept in `.grok/sessions/index.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub model: String,
+    pub first_user_message: String,
+    pub turn_count: usize,
+}
+
+/// Sessions are stored per project as `.grok/sessions/<id>.json` plus a metadata index
+pub struct SessionStore {
+    storage: SharedStorage,
+}
+
+impl SessionStore {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::with_dir(std::env::current_dir()?.join(".grok").join("sessions")))
+    }
+
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self::with_storage(Arc::new(FileStorage::new(dir)))
+    }
+
+    pub fn with_storage(storage: SharedStorage) -> Self {
+        Self { storage }
+    }
+
+    /// Indexed sessions, newest first. Sessions whose transcript was removed are left out.
+    pub async fn list(&self) -> Vec<SessionMeta> {
+        let mut sessions: Vec<SessionMeta> = match self.storage.get(INDEX_FILE) {
+            Ok(Some(content)) => serde_json::from_str(&content).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        if let Ok(keys) = self.storage.list("") {
+            sessions.retain(|s| keys.contains(&format!("{}.json", s.id)));
+        }
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        sessions
+    }
+
+    /// Save the session and refresh its index entry. Sessions without a user message are
+    /// skipped; `Ok(false)` says so.
+    pub async fn save(&self, session: &SessionFile) -> Result<bool, Box<dyn std::error::Error>> {
+        let entries = &session.entries;
+        let first_user_message = match entries.iter().find(|e| matches!(e.entry_type, ChatEntryType::User)) {
+            Some(entry) => preview(&entry.content),
+            None => return Ok(false),
+        };
+        let turn_count = entries.iter().filter(|e| matches!(e.entry_type, ChatEntryType::User)).count();
+
+        let now = Utc::now();
+        let mut sessions = self.list().await;
+        let created_at = sessions.iter().find(|s| s.id == session.id).map_or(session.created_at, |s| s.created_at);
+        let session = SessionFile { created_at, ..session.clone() };
+        self.storage.put(&format!("{}.json", session.id), &serde_json::to_string_pretty(&session)?)?;
+
+        sessions.retain(|s| s.id != session.id);
+        sessions.push(SessionMeta {
+            id: session.id.clone(),
+            created_at,
+            updated_at: now,
+            model: session.model.clone(),
+            first_user_message,
+            turn_count,
+        });
+        self.storage.put(INDEX_FILE, &serde_json::to_string_pretty(&sessions)?)?;
+        Ok(true)
+    }
+
+    /// Load a session by full id or unique id prefix
+    pub async fn load(&self, id: &str) -> Result<(SessionMeta, SessionFile), Box<dyn std::error::Error>> {
+        let meta = self.find(id).await?;
+        let content = self
+            .storage
+            .get(&format!("{}.json", meta.id))?
+            .ok_or_else(|| format!("Session {} is listed but its transcript is missing", meta.id))?;
+        let mut session = SessionFile::parse(&content).map_err(|e| format!("Session {}: {}", meta.id, e))?;
+        if session.id.is_empty() {
+            session.id = meta.id.clone();
+            session.model = meta.model.clone();
+            session.created_at = meta.created_at;
+        }
+        Ok((meta, session))
+    }
+
+    /// Delete a session by full id or unique id prefix, returning what was deleted
+    pub async fn delete(&self, id: &str) -> Result<SessionMeta, Box<dyn std::error::Error>> {
+        let meta = self.find(id).await?;
+        self.storage.delete(&format!("{}.json", meta.id))?;
+        let mut sessions = self.list().await;
+        sessions.retain(|s| s.id != meta.id);
+        self.storage.put(INDEX_FILE, &serde_json::to_string_pretty(&sessions)?)?;
+        Ok(meta)
+    }
+
+    async fn find(&self, id: &str) -> Result<SessionMeta, Box<dyn std::error::Error>> {
+        let matches: Vec<SessionMeta> = self.list().await.into_iter().filter(|s| s.id.starts_with(id)).collect();
+        match matches.len() {
+            0 => Err(format!("No saved session matches '{}'. Run `grok history` to list sessions.", id).into()),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            n => Err(format!("'{}' matches {} sessions; use a longer id.", id, n).into()),
+        }
+    }
+}
+
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.chars().count() > PREVIEW_CHARS {
+        format!("{}…", line.chars().take(PREVIEW_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Render a saved session as Markdown
+pub fn session_to_markdown(meta: &SessionMeta, entries: &[ChatEntry]) -> String {
+    let mut out = format!(
+        "# Session {}\n\n- Model: {}\n- Started: {}\n- Turns: {}\n",
+        meta.id,
+        meta.model,
+        meta.created_at.format("%Y-%m-%d %H:%M"),
+        meta.turn_count
+    );
+
+    for entry in entries {
+        match entry.entry_type {
+            ChatEntryType::User => out.push_str(&format!("\n## User\n\n{}\n", entry.content)),
+            ChatEntryType::Assistant => {
+                if !entry.content.trim().is_empty() {
+                    out.push_str(&format!("\n## Assistant\n\n{}\n", entry.content));
+                }
+            }
+            ChatEntryType::ToolCall => {
+                if let Some(call) = &entry.tool_call {
+                    out.push_str(&format!(
+                        "\n**Tool call:** `{}` `{}`\n",
+                        call.function.name, call.function.arguments
+                    ));
+                }
+            }
+            ChatEntryType::System => out.push_str(&format!("\n*{}*\n", entry.content)),
+            ChatEntryType::ConfirmationPending => {
+                out.push_str(&format!("\n**Asked to approve:**\n\n```\n{}\n```\n", entry.content));
+            }
+            ChatEntryType::ToolResult => {
+                out.push_str(&format!("\n<details><summary>Tool result</summary>\n\n```\n{}\n```\n\n</details>\n", entry.content));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_type: ChatEntryType, content: &str) -> ChatEntry {
+        ChatEntry {
+            entry_type,
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            tool_calls: None,
+            tool_call: None,
+            tool_result: None,
+            is_streaming: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_list_and_load_by_prefix() {
+        let dir = std::env::temp_dir().join(format!("grok-sessions-{}", std::process::id()));
+        let store = SessionStore::with_dir(dir.clone());
+        let entries = vec![
+            entry(ChatEntryType::User, "fix the build"),
+            entry(ChatEntryType::Assistant, "done"),
+            entry(ChatEntryType::User, "thanks"),
+        ];
+
+        let session = SessionFile::new("abc123", "grok-code-fast-1", entries, Vec::new());
+        assert!(store.save(&session).await.unwrap());
+        assert!(store.save(&session).await.unwrap());
+
+        let sessions = store.list().await;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].turn_count, 2);
+        assert_eq!(sessions[0].first_user_message, "fix the build");
+
+        let (meta, loaded) = store.load("abc").await.unwrap();
+        assert_eq!(meta.id, "abc123");
+        assert_eq!(loaded.entries.len(), 3);
+        assert!(session_to_markdown(&meta, &loaded.entries).contains("## User\n\nthanks"));
+
+        // A transcript removed by hand drops out of the list; `delete` removes both
+        store.save(&SessionFile::new("def456", "grok-4", vec![entry(ChatEntryType::User, "hi")], Vec::new())).await.unwrap();
+        std::fs::remove_file(dir.join("abc123.json")).unwrap();
+        assert_eq!(store.list().await.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), ["def456"]);
+        assert_eq!(store.delete("def").await.unwrap().id, "def456");
+        assert!(store.list().await.is_empty());
+        assert!(!dir.join("def456.json").exists());
+        assert!(store.delete("def").await.is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_session_files_are_versioned_and_old_transcripts_still_load() {
+        let dir = std::env::temp_dir().join(format!("grok-session-format-{}", std::process::id()));
+        let store = SessionStore::with_dir(dir.clone());
+        let message = GrokMessage { role: "user".to_string(), content: Some("hi".to_string()), tool_calls: None, tool_call_id: None };
+        let session = SessionFile::new("new1", "grok-4", vec![entry(ChatEntryType::User, "hi")], vec![message]);
+        store.save(&session).await.unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join("new1.json")).unwrap()).unwrap();
+        assert_eq!(saved["version"], SESSION_FORMAT_VERSION);
+        assert_eq!(saved["working_directory"], std::env::current_dir().unwrap().display().to_string());
+        assert_eq!(store.load("new1").await.unwrap().1.messages.len(), 1);
+
+        // A bare transcript from before versioning gets its metadata from the index
+        let old = vec![entry(ChatEntryType::User, "old question")];
+        store.save(&SessionFile::new("old1", "grok-3", old.clone(), Vec::new())).await.unwrap();
+        std::fs::write(dir.join("old1.json"), serde_json::to_string(&old).unwrap()).unwrap();
+        let (_, loaded) = store.load("old1").await.unwrap();
+        assert_eq!((loaded.version, loaded.id.as_str(), loaded.model.as_str()), (0, "old1", "grok-3"));
+        assert!(loaded.messages.is_empty());
+
+        let newer = serde_json::json!({ "version": SESSION_FORMAT_VERSION + 1, "entries": [] }).to_string();
+        std::fs::write(dir.join("new1.json"), newer).unwrap();
+        let error = store.load("new1").await.unwrap_err().to_string();
+        assert!(error.contains("newer grok"), "{}", error);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
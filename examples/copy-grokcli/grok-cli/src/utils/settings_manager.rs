@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use tokio;
+use std::sync::Arc;
+use super::storage::{FileStorage, MemoryStorage, SharedStorage};
 
 /// Current settings version - increment this when adding new models or changing settings structure
 /// This triggers automatic migration for existing users
@@ -21,43 +22,225 @@ pub struct UserSettings {
     pub settings_version: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_openai_compatible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// How many times to re-ask the model when it returns neither content nor tool calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub empty_response_retries: Option<u32>,
+    /// How many times a request is sent again after a rate limit, a server error or a network failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// Run file edits and bash commands without asking, like `--yolo`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_accept: Option<bool>,
+    /// Number of recent user turns sent to the model; unset sends the full history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_window: Option<usize>,
+    /// Bytes of file content one `view_file` call over several files returns (default 65536)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_max_bytes: Option<usize>,
+    /// Extra provider parameters (top_p, seed, response_format, ...) merged into each request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_params: Option<serde_json::Value>,
+    /// Format JSON, tables and ANSI colors in tool results shown in the UI (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pretty_tool_output: Option<bool>,
+    /// Extra bash command patterns that need a typed YES; the built-in rules always apply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dangerous_commands: Option<Vec<crate::tools::DangerousCommandRule>>,
+    /// Keep bash commands inside the directory grok started in, like `--confine-bash`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confine_bash: Option<bool>,
+    /// Let the file tools read and write outside the directory grok runs in (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_outside_workspace: Option<bool>,
+    /// With bash confined, run commands under `bash --restricted`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restricted_shell: Option<bool>,
+    /// Bash commands refused outright: deny and allow lists, writes outside the working directory
+    #[serde(rename = "bashPolicy", skip_serializing_if = "Option::is_none")]
+    pub bash_policy: Option<crate::tools::BashPolicy>,
+    /// Failed tool calls in a row before the agent steers the model or stops (0 disables)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_consecutive_tool_failures: Option<u32>,
+    /// "steer" (default) asks the model to reassess; "stop" ends the turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_failure_action: Option<crate::agent::ToolFailureAction>,
+    /// Add a "Changed: …" entry after each turn that created, modified or deleted files (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_summary: Option<bool>,
+    /// `git add` the files each turn created, modified or deleted (opt-in)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_stage: Option<bool>,
+    /// Summarize large tool results before sending them to the model (opt-in)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_result_summary: Option<crate::agent::ToolResultSummaryConfig>,
+    /// Tool results older than this many turns are sent as a one-line outcome (opt-in)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress_tool_results_after: Option<usize>,
+    /// Read-only tool calls from one reply that run at the same time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_concurrency: Option<usize>,
+    /// Estimated prompt tokens each request is trimmed to (opt-in)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_tokens: Option<usize>,
+    /// Seconds each tool may run, by tool name, on top of the built-in limits; 0 removes a limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_timeouts: Option<std::collections::HashMap<String, u64>>,
+    /// User messages over this many (estimated) tokens are sent as several consecutive parts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_message_tokens: Option<usize>,
+    /// Tool calls run from one assistant message; later calls are deferred to the next round
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tool_calls_per_message: Option<usize>,
+    /// Note what each tool call is for when the model runs it without saying why (opt-in)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explain_tools: Option<bool>,
+    /// Ask for Conventional Commits messages (`feat(ui): …`) in `/commit-and-push`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conventional_commits: Option<bool>,
+    /// Per-model tool-round budgets tried before the built-in table
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_rounds: Option<crate::agent::ToolRoundsConfig>,
+    /// Dollars per million prompt (`input`) and completion (`output`) tokens, by model name or a
+    /// part of it, for the cost shown by `/status`; tried before the built-in prices
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_prices: Option<std::collections::BTreeMap<String, crate::grok::usage::ModelPrice>>,
+    /// Slash commands that send prompt templates, by name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_commands: Option<std::collections::BTreeMap<String, crate::utils::custom_commands::CustomCommand>>,
+    /// Answer identical requests from disk (opt-in)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_cache: Option<crate::grok::response_cache::ResponseCacheConfig>,
+    /// MCP servers for every project, added with `grok mcp add --scope user`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_servers: Option<HashMap<String, serde_json::Value>>,
+    /// MCP servers connected at the same time during startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_concurrency: Option<usize>,
+    /// Seconds one MCP server may take to connect, and each request to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_timeout_secs: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct ProjectSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_servers: Option<HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format_on_write: Option<crate::tools::FormatOnWriteConfig>,
+    /// Command and diagnostic cap for the `check` tool, and whether it runs after each edit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<crate::tools::CheckConfig>,
+    /// Line ending for newly created files ("lf" or "crlf"); existing files keep their own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_file_line_ending: Option<crate::tools::LineEnding>,
+}
+
+/// Team defaults committed in `.grok/project-settings.json`; each set field wins over the
+/// user's settings when running in that directory
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ProjectOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Tools offered to the model; unset offers all of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// Appended to the built-in system prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
 }
 
 pub struct SettingsManager {
+    /// Shown in messages; for the filesystem backend this is the real file
     user_settings_path: PathBuf,
     project_settings_path: PathBuf,
+    user_storage: SharedStorage,
+    user_key: String,
+    project_storage: SharedStorage,
+}
+
+const USER_SETTINGS_KEY: &str = "user-settings.json";
+const PROJECT_SETTINGS_KEY: &str = "settings.json";
+const PROJECT_OVERRIDES_KEY: &str = "project-settings.json";
+
+/// Where settings come from, chosen once at startup by `--config`, `--no-config` or `GROK_CONFIG`
+#[derive(Debug, Clone)]
+pub enum ConfigSource {
+    /// `~/.grok/user-settings.json`
+    Default,
+    /// An explicitly specified user settings file, which must exist
+    File(PathBuf),
+    /// Ignore settings files and run purely from flags and environment
+    Disabled,
+}
+
+static CONFIG_SOURCE: std::sync::OnceLock<ConfigSource> = std::sync::OnceLock::new();
+
+/// Set the settings source for the rest of the process; later calls are ignored
+pub fn set_config_source(source: ConfigSource) {
+    let _ = CONFIG_SOURCE.set(source);
 }
 
 impl SettingsManager {
+    /// Settings manager backed by custom storage, e.g. for embedding or tests
+    pub fn with_storage(user_storage: SharedStorage, project_storage: SharedStorage) -> Self {
+        SettingsManager {
+            user_settings_path: PathBuf::from(USER_SETTINGS_KEY),
+            project_settings_path: PathBuf::from(PROJECT_SETTINGS_KEY),
+            user_storage,
+            user_key: USER_SETTINGS_KEY.to_string(),
+            project_storage,
+        }
+    }
+
+    /// Settings manager for an explicitly specified user settings file
+    pub fn with_user_settings_path(path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.is_file() {
+            return Err(format!("Config file not found: {}", path.display()).into());
+        }
+        let file_name = path.file_name().ok_or("Config path has no file name")?.to_string_lossy().to_string();
+        let parent = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        let project_dir = std::env::current_dir()?.join(".grok");
+        Ok(SettingsManager {
+            user_settings_path: path,
+            project_settings_path: project_dir.join(PROJECT_SETTINGS_KEY),
+            user_storage: Arc::new(FileStorage::new(parent)),
+            user_key: file_name,
+            project_storage: Arc::new(FileStorage::new(project_dir)),
+        })
+    }
+
+    /// Settings manager that never touches the filesystem (`--no-config`)
+    pub fn without_files() -> Self {
+        Self::with_storage(Arc::new(MemoryStorage::new()), Arc::new(MemoryStorage::new()))
+    }
+
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-        let user_settings_path = home_dir.join(".grok").join("user-settings.json");
-
-        let project_settings_path = std::env::current_dir()?.join(".grok").join("settings.json");
+        let user_dir = home_dir.join(".grok");
+        let project_dir = std::env::current_dir()?.join(".grok");
 
         // Create .grok directory in home if it doesn't exist
-        if let Some(parent) = user_settings_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
+        std::fs::create_dir_all(&user_dir)?;
 
         Ok(SettingsManager {
-            user_settings_path,
-            project_settings_path,
+            user_settings_path: user_dir.join(USER_SETTINGS_KEY),
+            project_settings_path: project_dir.join(PROJECT_SETTINGS_KEY),
+            user_storage: Arc::new(FileStorage::new(user_dir)),
+            user_key: USER_SETTINGS_KEY.to_string(),
+            project_storage: Arc::new(FileStorage::new(project_dir)),
         })
     }
 
     pub async fn load_user_settings(&self) -> Result<UserSettings, Box<dyn std::error::Error>> {
-        if self.user_settings_path.exists() {
-            let content = tokio::fs::read_to_string(&self.user_settings_path).await?;
-            let mut settings: UserSettings = serde_json::from_str(&content)?;
+        if let Some(content) = self.user_storage.get(&self.user_key)? {
+            let mut settings: UserSettings = serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid settings in {}: {}", self.user_settings_path.display(), e))?;
 
             // Check if migration is needed
             let current_version = settings.settings_version.unwrap_or(1);
@@ -110,6 +293,39 @@ impl SettingsManager {
             models: Some(self.get_default_models()),
             settings_version: Some(SETTINGS_VERSION),
             is_openai_compatible: Some(false),
+            provider: None,
+            empty_response_retries: None,
+            max_retries: None,
+            auto_accept: None,
+            history_window: None,
+            view_max_bytes: None,
+            model_params: None,
+            pretty_tool_output: None,
+            dangerous_commands: None,
+            confine_bash: None,
+            allow_outside_workspace: None,
+            restricted_shell: None,
+            bash_policy: None,
+            max_consecutive_tool_failures: None,
+            tool_failure_action: None,
+            change_summary: None,
+            auto_stage: None,
+            tool_result_summary: None,
+            max_message_tokens: None,
+            compress_tool_results_after: None,
+            tool_concurrency: None,
+            context_tokens: None,
+            tool_timeouts: None,
+            max_tool_calls_per_message: None,
+            explain_tools: None,
+            conventional_commits: None,
+            tool_rounds: None,
+            model_prices: None,
+            custom_commands: None,
+            response_cache: None,
+            mcp_servers: None,
+            mcp_concurrency: None,
+            mcp_timeout_secs: None,
         }
     }
 
@@ -137,36 +353,67 @@ impl SettingsManager {
 
     pub async fn save_user_settings(&self, settings: &UserSettings) -> Result<(), Box<dyn std::error::Error>> {
         let content = serde_json::to_string_pretty(settings)?;
-        tokio::fs::write(&self.user_settings_path, content).await?;
+        self.user_storage.put(&self.user_key, &content)?;
         Ok(())
     }
 
     pub async fn load_project_settings(&self) -> Result<ProjectSettings, Box<dyn std::error::Error>> {
-        if self.project_settings_path.exists() {
-            let content = tokio::fs::read_to_string(&self.project_settings_path).await?;
+        if let Some(content) = self.project_storage.get(PROJECT_SETTINGS_KEY)? {
             let settings: ProjectSettings = serde_json::from_str(&content)?;
             Ok(settings)
         } else {
             // Create default project settings if file doesn't exist
-            self.save_project_settings(&ProjectSettings {
-                model: Some("grok-code-fast-1".to_string()),
-                mcp_servers: None,
-            }).await?;
-            Ok(ProjectSettings {
+            let default_settings = ProjectSettings {
                 model: Some("grok-code-fast-1".to_string()),
-                mcp_servers: None,
-            })
+                ..Default::default()
+            };
+            self.save_project_settings(&default_settings).await?;
+            Ok(default_settings)
         }
     }
 
-    pub async fn save_project_settings(&self, settings: &ProjectSettings) -> Result<(), Box<dyn std::error::Error>> {
-        // Create .grok directory if it doesn't exist
-        if let Some(parent) = self.project_settings_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+    /// Read project settings without creating a default file when none exists. An invalid
+    /// file is reported in `warnings`, for the caller to show where it fits.
+    pub async fn read_project_settings(&self, warnings: &mut Vec<String>) -> Option<ProjectSettings> {
+        let content = self.project_storage.get(PROJECT_SETTINGS_KEY).ok()??;
+        match serde_json::from_str(&content) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                warnings.push(format!("Ignoring invalid {}: {}", self.project_settings_path.display(), e));
+                None
+            }
         }
+    }
 
+    /// Read `.grok/project-settings.json`; a missing or invalid file overrides nothing, and an
+    /// invalid one is reported in `warnings`
+    pub async fn read_project_overrides(&self, warnings: &mut Vec<String>) -> Option<ProjectOverrides> {
+        let content = self.project_storage.get(PROJECT_OVERRIDES_KEY).ok()??;
+        match serde_json::from_str(&content) {
+            Ok(overrides) => Some(overrides),
+            Err(e) => {
+                warnings.push(format!("Ignoring invalid {}: {}", self.project_overrides_path().display(), e));
+                None
+            }
+        }
+    }
+
+    /// Where project overrides are read from, shown as the source of the values they set
+    pub fn user_settings_path(&self) -> &std::path::Path {
+        &self.user_settings_path
+    }
+
+    pub fn project_settings_path(&self) -> &std::path::Path {
+        &self.project_settings_path
+    }
+
+    pub fn project_overrides_path(&self) -> PathBuf {
+        self.project_settings_path.with_file_name(PROJECT_OVERRIDES_KEY)
+    }
+
+    pub async fn save_project_settings(&self, settings: &ProjectSettings) -> Result<(), Box<dyn std::error::Error>> {
         let content = serde_json::to_string_pretty(settings)?;
-        tokio::fs::write(&self.project_settings_path, content).await?;
+        self.project_storage.put(PROJECT_SETTINGS_KEY, &content)?;
         Ok(())
     }
 
@@ -219,10 +466,7 @@ impl SettingsManager {
 
     pub async fn set_current_model(&self, model: &str) -> Result<(), Box<dyn std::error::Error>> {
         // Update project settings with the new model
-        let mut project_settings = self.load_project_settings().await.unwrap_or(ProjectSettings {
-            model: None,
-            mcp_servers: None,
-        });
+        let mut project_settings = self.load_project_settings().await.unwrap_or_default();
 
         project_settings.model = Some(model.to_string());
         self.save_project_settings(&project_settings).await?;
@@ -267,8 +511,76 @@ impl SettingsManager {
     }
 }
 
+/// Provider names that speak the native xAI API; any other provider is treated as OpenAI-compatible
+const NATIVE_PROVIDERS: &[&str] = &["xai", "x.ai", "grok"];
+
+/// One layer of provider configuration (CLI flag, environment, or settings file)
+pub struct ProviderLayer<'a> {
+    pub source: &'a str,
+    pub provider: Option<&'a str>,
+    pub openai_compatible: Option<bool>,
+}
+
+/// Whether a provider name implies the OpenAI-compatible request format
+pub fn provider_implies_openai_compatible(provider: &str) -> bool {
+    !NATIVE_PROVIDERS.contains(&provider.trim().to_lowercase().as_str())
+}
+
+/// Parse a boolean environment value such as `GROK_OPENAI_COMPATIBLE`
+pub fn parse_bool_value(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Resolve the OpenAI-compatibility flag from layers ordered by precedence (flag > env > settings).
+///
+/// The first layer that sets a provider or an explicit flag decides. Within a layer the explicit
+/// flag wins over the value implied by the provider, and a conflict between the two is reported
+/// as a warning.
+pub fn resolve_openai_compatible(layers: &[ProviderLayer]) -> (Option<bool>, Vec<String>) {
+    let mut warnings = Vec::new();
+
+    for layer in layers {
+        let implied = layer.provider.map(provider_implies_openai_compatible);
+
+        match (implied, layer.openai_compatible) {
+            (Some(implied), Some(explicit)) => {
+                if implied != explicit {
+                    warnings.push(format!(
+                        "{}: provider '{}' implies openai_compatible={}, but openai_compatible={} was set explicitly; using {}",
+                        layer.source,
+                        layer.provider.unwrap_or_default(),
+                        implied,
+                        explicit,
+                        explicit
+                    ));
+                }
+                return (Some(explicit), warnings);
+            }
+            (Some(value), None) | (None, Some(value)) => return (Some(value), warnings),
+            (None, None) => continue,
+        }
+    }
+
+    (None, warnings)
+}
+
 pub async fn get_settings_manager() -> Result<SettingsManager, Box<dyn std::error::Error>> {
-    SettingsManager::new()
+    let source = match CONFIG_SOURCE.get() {
+        Some(source) => source.clone(),
+        None => match std::env::var("GROK_CONFIG") {
+            Ok(path) if !path.is_empty() => ConfigSource::File(PathBuf::from(path)),
+            _ => ConfigSource::Default,
+        },
+    };
+    match source {
+        ConfigSource::Default => SettingsManager::new(),
+        ConfigSource::File(path) => SettingsManager::with_user_settings_path(path),
+        ConfigSource::Disabled => Ok(SettingsManager::without_files()),
+    }
 }
 
 pub async fn get_api_key() -> Option<String> {
@@ -282,6 +594,7 @@ pub async fn get_api_key() -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::storage::Storage;
 
     #[tokio::test]
     async fn test_settings_manager_creation() -> Result<(), Box<dyn std::error::Error>> {
@@ -289,4 +602,80 @@ mod tests {
         assert!(manager.user_settings_path.ends_with("user-settings.json"));
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_explicit_and_disabled_config() -> Result<(), Box<dyn std::error::Error>> {
+        let missing = std::env::temp_dir().join("grok-missing-settings.json");
+        assert!(SettingsManager::with_user_settings_path(missing).is_err());
+
+        let path = std::env::temp_dir().join(format!("grok-settings-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"api_key": "from-file", "settings_version": 2}"#)?;
+        let manager = SettingsManager::with_user_settings_path(path.clone())?;
+        assert_eq!(manager.load_user_settings().await?.api_key.as_deref(), Some("from-file"));
+        std::fs::remove_file(&path).ok();
+
+        let manager = SettingsManager::without_files();
+        assert!(manager.load_user_settings().await?.api_key.is_none());
+        assert!(manager.read_project_settings(&mut Vec::new()).await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_overrides_are_read_without_creating_a_file() {
+        let project = Arc::new(MemoryStorage::new());
+        let manager = SettingsManager::with_storage(Arc::new(MemoryStorage::new()), project.clone());
+        let mut warnings = Vec::new();
+        assert!(manager.read_project_overrides(&mut warnings).await.is_none());
+        assert!(project.get(PROJECT_OVERRIDES_KEY).unwrap().is_none());
+
+        project
+            .put(PROJECT_OVERRIDES_KEY, r#"{"model": "grok-3-mini", "temperature": 0.2, "tools": ["view_file"]}"#)
+            .unwrap();
+        let overrides = manager.read_project_overrides(&mut warnings).await.unwrap();
+        assert_eq!(overrides.model.as_deref(), Some("grok-3-mini"));
+        assert_eq!(overrides.temperature, Some(0.2));
+        assert_eq!(overrides.tools, Some(vec!["view_file".to_string()]));
+        assert!(overrides.system_prompt.is_none());
+        assert_eq!(manager.project_overrides_path(), PathBuf::from("project-settings.json"));
+
+        assert!(warnings.is_empty());
+
+        project.put(PROJECT_OVERRIDES_KEY, "{not json").unwrap();
+        assert!(manager.read_project_overrides(&mut warnings).await.is_none());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Ignoring invalid project-settings.json"), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_resolve_openai_compatible_precedence() {
+        let layers = [
+            ProviderLayer { source: "flag", provider: None, openai_compatible: None },
+            ProviderLayer { source: "env", provider: Some("openrouter"), openai_compatible: None },
+            ProviderLayer { source: "settings", provider: None, openai_compatible: Some(false) },
+        ];
+        let (resolved, warnings) = resolve_openai_compatible(&layers);
+        assert_eq!(resolved, Some(true));
+        assert!(warnings.is_empty());
+
+        let (resolved, _) = resolve_openai_compatible(&[]);
+        assert_eq!(resolved, None);
+    }
+
+    #[test]
+    fn test_resolve_openai_compatible_conflict_warns() {
+        let layers = [
+            ProviderLayer { source: "env", provider: Some("xai"), openai_compatible: Some(true) },
+        ];
+        let (resolved, warnings) = resolve_openai_compatible(&layers);
+        assert_eq!(resolved, Some(true));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("xai"));
+    }
+
+    #[test]
+    fn test_parse_bool_value() {
+        assert_eq!(parse_bool_value("TRUE"), Some(true));
+        assert_eq!(parse_bool_value("0"), Some(false));
+        assert_eq!(parse_bool_value("maybe"), None);
+    }
 }
\ No newline at end of file
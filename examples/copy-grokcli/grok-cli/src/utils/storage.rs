@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Key-value persistence used for settings and saved sessions.
+///
+/// Keys are `/`-separated relative names such as `user-settings.json` or `abc123.json`.
+/// The filesystem backend is the default; embedders can supply their own (for example an
+/// in-memory store for tests or a remote store shared by a team).
+pub trait Storage: Send + Sync {
+    fn get(&self, key: &str) -> io::Result<Option<String>>;
+    fn put(&self, key: &str, value: &str) -> io::Result<()>;
+    /// Keys directly under `prefix` (use `""` for the top level), sorted
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+    /// Deleting a missing key is not an error
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Shared handle to a storage backend
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// Stores each key as a file under a root directory. A write goes to a temporary file next
+/// to the target and is renamed over it, so an interrupted write never leaves half a file.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn path_for(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+impl Storage for FileStorage {
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        match std::fs::read_to_string(self.path_for(key)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&self, key: &str, value: &str) -> io::Result<()> {
+        let path = self.path_for(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Replace what a symlinked settings file points at, not the link
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+        let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let temp = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        std::fs::write(&temp, value)?;
+        std::fs::rename(&temp, &path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let dir = self.path_for(prefix);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut keys: Vec<String> = entries
+            .flatten()
+            .filter(|entry| entry.path().is_file())
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if prefix.is_empty() { name } else { format!("{}/{}", prefix.trim_end_matches('/'), name) }
+            })
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.path_for(key)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps everything in memory; nothing survives the process
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: Mutex<BTreeMap<String, String>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: &str, value: &str) -> io::Result<()> {
+        self.entries.lock().unwrap().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let dir = if prefix.is_empty() { String::new() } else { format!("{}/", prefix.trim_end_matches('/')) };
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(&dir) && !key[dir.len()..].contains('/'))
+            .cloned()
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(storage: &dyn Storage) {
+        assert_eq!(storage.get("a.json").unwrap(), None);
+        storage.put("a.json", "0").unwrap();
+        storage.put("a.json", "1").unwrap();
+        storage.put("sub/b.json", "2").unwrap();
+        assert_eq!(storage.get("a.json").unwrap().as_deref(), Some("1"));
+        assert_eq!(storage.list("").unwrap(), vec!["a.json"]);
+        assert_eq!(storage.list("sub").unwrap(), vec!["sub/b.json"]);
+        storage.delete("a.json").unwrap();
+        storage.delete("a.json").unwrap();
+        assert_eq!(storage.get("a.json").unwrap(), None);
+    }
+
+    #[test]
+    fn test_file_and_memory_backends_agree() {
+        exercise(&MemoryStorage::new());
+
+        let dir = std::env::temp_dir().join(format!("grok-storage-{}", std::process::id()));
+        exercise(&FileStorage::new(dir.clone()));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,232 @@
+use super::file_changes::line_delta;
+
+/// Combined size of the two versions above which no diff is computed
+pub const MAX_DIFF_BYTES: usize = 512 * 1024;
+
+/// Changed region (old lines × new lines) above which the line matching gets too slow
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Diff lines a tool result's output shows; `data.diff` always has all of them
+pub const PREVIEW_LINES: usize = 80;
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// How a file edit changed the file
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditDiff {
+    /// A unified diff and its added and removed line counts
+    Text { diff: String, additions: usize, deletions: usize },
+    /// Too large to diff; the counts are approximate
+    TooLarge { additions: usize, deletions: usize },
+    Binary,
+}
+
+impl EditDiff {
+    /// Compare two versions of `path`
+    pub fn between(path: &str, old: &[u8], new: &[u8]) -> Self {
+        let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else { return EditDiff::Binary };
+        if old.contains('\0') || new.contains('\0') {
+            return EditDiff::Binary;
+        }
+        if old.len() + new.len() > MAX_DIFF_BYTES {
+            let (additions, deletions) = line_delta(old, new);
+            return EditDiff::TooLarge { additions, deletions };
+        }
+        match unified_diff(path, old, new) {
+            Some((diff, additions, deletions)) => EditDiff::Text { diff, additions, deletions },
+            None => {
+                let (additions, deletions) = line_delta(old, new);
+                EditDiff::TooLarge { additions, deletions }
+            }
+        }
+    }
+
+    /// For `ToolResult.data`: `{ "diff", "additions", "deletions" }`, with `diff` null and a
+    /// `summary` when no diff is shown
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            EditDiff::Text { diff, additions, deletions } => {
+                serde_json::json!({ "diff": diff, "additions": additions, "deletions": deletions })
+            }
+            EditDiff::TooLarge { additions, deletions } => serde_json::json!({
+                "diff": null,
+                "additions": additions,
+                "deletions": deletions,
+                "summary": self.preview(),
+            }),
+            EditDiff::Binary => serde_json::json!({ "diff": null, "binary": true, "summary": self.preview() }),
+        }
+    }
+
+    /// The diff cut to [`PREVIEW_LINES`], or a summary line when there is none to show
+    pub fn preview(&self) -> String {
+        match self {
+            EditDiff::Text { diff, .. } => {
+                let total = diff.lines().count();
+                let mut preview: Vec<&str> = diff.lines().take(PREVIEW_LINES).collect();
+                let more = format!("… {} more diff lines", total.saturating_sub(PREVIEW_LINES));
+                if total > PREVIEW_LINES {
+                    preview.push(&more);
+                }
+                preview.join("\n")
+            }
+            EditDiff::TooLarge { additions, deletions } => {
+                format!("+{} -{} lines (the change is too large to show as a diff)", additions, deletions)
+            }
+            EditDiff::Binary => "Binary content changed; no diff shown".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// `--- a/path` / `+++ b/path` and `@@` hunks with [`CONTEXT_LINES`] of context, plus the
+/// added and removed line counts. `None` when the changed region is too large to match.
+fn unified_diff(path: &str, old: &str, new: &str) -> Option<(String, usize, usize)> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&a, &b)?;
+
+    let additions = ops.iter().filter(|(tag, _)| *tag == Tag::Insert).count();
+    let deletions = ops.iter().filter(|(tag, _)| *tag == Tag::Delete).count();
+    if additions + deletions == 0 {
+        return Some((String::new(), 0, 0));
+    }
+
+    // Ranges of `ops` to print: each change with its context, overlapping ranges merged
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (index, (tag, _)) in ops.iter().enumerate() {
+        if *tag == Tag::Equal {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    // Lines of each side before each op, for the hunk headers
+    let mut old_before = Vec::with_capacity(ops.len());
+    let mut new_before = Vec::with_capacity(ops.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    for (tag, _) in &ops {
+        old_before.push(old_line);
+        new_before.push(new_line);
+        if *tag != Tag::Insert {
+            old_line += 1;
+        }
+        if *tag != Tag::Delete {
+            new_line += 1;
+        }
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}", path, path);
+    for (start, end) in ranges {
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|(tag, _)| *tag != Tag::Insert).count();
+        let new_len = hunk.iter().filter(|(tag, _)| *tag != Tag::Delete).count();
+        // An empty side is numbered by the line before it, as in `diff -u`
+        let old_start = old_before[start] + usize::from(old_len > 0);
+        let new_start = new_before[start] + usize::from(new_len > 0);
+        diff.push_str(&format!("\n@@ -{},{} +{},{} @@", old_start, old_len, new_start, new_len));
+        for (tag, line) in hunk {
+            let marker = match tag {
+                Tag::Equal => ' ',
+                Tag::Delete => '-',
+                Tag::Insert => '+',
+            };
+            diff.push('\n');
+            diff.push(marker);
+            diff.push_str(line);
+        }
+    }
+    Some((diff, additions, deletions))
+}
+
+/// Line-level edit script from `a` to `b`: the common prefix and suffix, and a longest common
+/// subsequence of what is left between them
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Option<Vec<(Tag, &'a str)>> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (old, new) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    // lcs[i][j]: longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<(Tag, &str)> = a[..prefix].iter().map(|line| (Tag::Equal, *line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((Tag::Equal, old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+            ops.push((Tag::Delete, old[i]));
+            i += 1;
+        } else {
+            ops.push((Tag::Insert, new[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[a.len() - suffix..].iter().map(|line| (Tag::Equal, *line)));
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_diff_has_hunks_with_context_and_falls_back_to_a_summary() {
+        let old: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        let new = old.replace("line 2\n", "line two\n").replace("line 18\n", "").replace("line 20\n", "line 20\nline 21\n");
+        let diff = EditDiff::between("src/notes.txt", old.as_bytes(), new.as_bytes());
+        let EditDiff::Text { diff: text, additions, deletions } = &diff else { panic!("{:?}", diff) };
+        assert_eq!((*additions, *deletions), (2, 2));
+        assert_eq!(
+            text.as_str(),
+            "--- a/src/notes.txt\n+++ b/src/notes.txt\n\
+             @@ -1,5 +1,5 @@\n line 1\n-line 2\n+line two\n line 3\n line 4\n line 5\n\
+             @@ -15,6 +15,6 @@\n line 15\n line 16\n line 17\n-line 18\n line 19\n line 20\n+line 21"
+        );
+        assert_eq!(diff.to_json()["additions"], 2);
+        assert_eq!(diff.preview(), *text);
+
+        let created = EditDiff::between("new.txt", b"", b"a\nb\n");
+        assert!(created.preview().ends_with("@@ -0,0 +1,2 @@\n+a\n+b"), "{}", created.preview());
+
+        let long: String = (0..200).map(|n| format!("{}\n", n)).collect();
+        let replaced = EditDiff::between("long.txt", long.as_bytes(), long.replace('\n', "!\n").as_bytes());
+        assert_eq!(replaced.preview().lines().count(), PREVIEW_LINES + 1);
+        assert!(replaced.preview().ends_with(&format!("… {} more diff lines", 2 + 1 + 400 - PREVIEW_LINES)));
+
+        assert_eq!(EditDiff::between("image.png", b"\x89PNG\0\x01", b"\x89PNG\0\x02"), EditDiff::Binary);
+        assert_eq!(EditDiff::between("image.png", b"x", b"\x89PNG\0\x02").to_json()["diff"], serde_json::Value::Null);
+        let huge = "x\n".repeat(MAX_DIFF_BYTES);
+        assert_eq!(
+            EditDiff::between("huge.txt", huge.as_bytes(), b"y\n"),
+            EditDiff::TooLarge { additions: 1, deletions: MAX_DIFF_BYTES }
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! `/export` and `--export`: the chat as a Markdown file or a self-contained HTML page. Tool
+//! calls become collapsible blocks with their arguments and result; in HTML, diffs and code
+//! fences are highlighted with `hl-*` classes styled by the page's own stylesheet.
+
+use crate::types::{ChatEntry, ChatEntryType};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Markdown,
+    Html,
+}
+
+impl TranscriptFormat {
+    /// `md` / `markdown` or `html`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// HTML for a `.html` or `.htm` path, Markdown for anything else
+    pub fn for_path(path: &Path) -> Self {
+        path.extension().and_then(|extension| Self::parse(&extension.to_string_lossy())).unwrap_or(Self::Markdown)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+
+    /// `grok-transcript-<session>.md` in the current directory
+    pub fn default_path(&self, session_id: &str) -> PathBuf {
+        PathBuf::from(format!("grok-transcript-{}.{}", session_id, self.extension()))
+    }
+}
+
+/// What the transcript's header records
+pub struct TranscriptHeader {
+    pub model: String,
+    pub exported_at: chrono::DateTime<chrono::Local>,
+    /// `UsageSummary::describe`, e.g. `Usage: 3 requests, 1200 tokens`
+    pub usage: String,
+}
+
+fn local_time(entry: &ChatEntry) -> String {
+    entry.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// What a tool result entry shows: the call's name, its arguments pretty-printed, the result
+/// text and the diff of an edit
+struct ToolBlock {
+    name: String,
+    arguments: String,
+    failed: bool,
+    output: String,
+    diff: Option<String>,
+}
+
+fn tool_block(entry: &ChatEntry) -> ToolBlock {
+    let (name, arguments) = match &entry.tool_call {
+        Some(call) => {
+            let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+                .ok()
+                .and_then(|value| serde_json::to_string_pretty(&value).ok())
+                .unwrap_or_else(|| call.function.arguments.clone());
+            (call.function.name.clone(), arguments)
+        }
+        None => ("tool".to_string(), String::new()),
+    };
+    let result = entry.tool_result.as_ref();
+    ToolBlock {
+        name,
+        arguments,
+        failed: result.is_some_and(|result| !result.success),
+        output: entry.content.clone(),
+        diff: result.and_then(|result| result.data.as_ref()?.get("diff")?.as_str().map(str::to_string)),
+    }
+}
+
+/// Entries the transcript leaves out: tool call previews, which the results repeat, and
+/// approvals that were answered
+fn exported(entry: &ChatEntry) -> bool {
+    !matches!(entry.entry_type, ChatEntryType::ToolCall | ChatEntryType::ConfirmationPending)
+}
+
+pub fn render(format: TranscriptFormat, header: &TranscriptHeader, entries: &[ChatEntry]) -> String {
+    match format {
+        TranscriptFormat::Markdown => render_markdown(header, entries),
+        TranscriptFormat::Html => render_html(header, entries),
+    }
+}
+
+/// A fence longer than any run of backticks in `text`, so the text cannot close it
+fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn fenced(text: &str, language: &str) -> String {
+    let fence = fence(text);
+    format!("{}{}\n{}\n{}\n", fence, language, text.trim_end(), fence)
+}
+
+fn render_markdown(header: &TranscriptHeader, entries: &[ChatEntry]) -> String {
+    let mut out = format!(
+        "# Grok session transcript\n\n- Model: {}\n- Exported: {}\n- {}\n",
+        header.model,
+        header.exported_at.format("%Y-%m-%d %H:%M:%S %:z"),
+        header.usage.replace('\n', "\n  ")
+    );
+    for entry in entries.iter().filter(|entry| exported(entry)) {
+        out.push('\n');
+        match entry.entry_type {
+            ChatEntryType::User => out.push_str(&format!("## 👤 You · {}\n\n{}\n", local_time(entry), entry.content.trim_end())),
+            ChatEntryType::Assistant => out.push_str(&format!("## 🤖 Grok · {}\n\n{}\n", local_time(entry), entry.content.trim_end())),
+            ChatEntryType::System => out.push_str(&format!("> ⚙️ {}\n", entry.content.trim_end().replace('\n', "\n> "))),
+            ChatEntryType::ToolResult => {
+                let block = tool_block(entry);
+                let status = if block.failed { " (failed)" } else { "" };
+                out.push_str(&format!("<details>\n<summary>🔧 {}{} · {}</summary>\n\n", block.name, status, local_time(entry)));
+                out.push_str(&fenced(&block.arguments, "json"));
+                out.push('\n');
+                out.push_str(&fenced(&block.output, "text"));
+                if let Some(diff) = &block.diff {
+                    out.push('\n');
+                    out.push_str(&fenced(diff, "diff"));
+                }
+                out.push_str("\n</details>\n");
+            }
+            ChatEntryType::ToolCall | ChatEntryType::ConfirmationPending => {}
+        }
+    }
+    out
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:960px;margin:2em auto;padding:0 1em;color:#222}\
+header{border-bottom:1px solid #ccc;margin-bottom:1em}section{margin:1em 0}section h2{font-size:1em;margin:0 0 .3em}\
+.user h2{color:#2a7a2a}.assistant h2{color:#1a6a8a}.system{color:#777;font-style:italic}time{color:#888;font-weight:normal}\
+details{border:1px solid #ddd;border-radius:4px;padding:.3em .6em;margin:.5em 0}details.failed{border-color:#d88}\
+summary{cursor:pointer}pre{background:#f6f8fa;padding:.6em;overflow-x:auto;white-space:pre-wrap}\
+.hl-keyword{color:#a626a4}.hl-string{color:#50a14f}.hl-comment{color:#a0a1a7;font-style:italic}.hl-number{color:#986801}\
+.hl-diff-add{color:#22863a;background:#f0fff4}.hl-diff-del{color:#b31d28;background:#ffeef0}.hl-diff-hunk{color:#6f42c1}";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "class", "const", "continue", "def", "else", "enum", "export", "false", "fn", "for",
+    "from", "func", "function", "if", "impl", "import", "in", "interface", "let", "match", "mod", "mut", "null", "pub",
+    "return", "self", "static", "struct", "trait", "true", "type", "use", "var", "while",
+];
+
+fn span(class: &str, text: &str) -> String {
+    format!("<span class=\"hl-{}\">{}</span>", class, escape_html(text))
+}
+
+/// Escaped `code` with keywords, strings, numbers and `//` or `#` comments wrapped in spans.
+/// A line-based scan rather than a grammar: good enough to make a shared transcript readable.
+fn highlight_code(code: &str) -> String {
+    let mut out = String::new();
+    for (index, line) in code.split('\n').enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if (c == '/' && chars.get(i + 1) == Some(&'/')) || (c == '#' && chars[..i].iter().all(|c| c.is_whitespace())) {
+                out.push_str(&span("comment", &chars[i..].iter().collect::<String>()));
+                break;
+            } else if c == '"' || c == '\'' {
+                let end = (i + 1..chars.len()).find(|&j| chars[j] == c && chars[j - 1] != '\\').unwrap_or(chars.len() - 1);
+                out.push_str(&span("string", &chars[i..=end].iter().collect::<String>()));
+                i = end + 1;
+            } else if c.is_alphanumeric() || c == '_' {
+                let end = (i..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_')).unwrap_or(chars.len());
+                let word: String = chars[i..end].iter().collect();
+                if KEYWORDS.contains(&word.as_str()) {
+                    out.push_str(&span("keyword", &word));
+                } else if c.is_ascii_digit() {
+                    out.push_str(&span("number", &word));
+                } else {
+                    out.push_str(&escape_html(&word));
+                }
+                i = end;
+            } else {
+                out.push_str(&escape_html(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn highlight_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            if line.starts_with("+++") || line.starts_with("---") {
+                span("keyword", line)
+            } else if line.starts_with('+') {
+                span("diff-add", line)
+            } else if line.starts_with('-') {
+                span("diff-del", line)
+            } else if line.starts_with("@@") {
+                span("diff-hunk", line)
+            } else {
+                escape_html(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn code_block(language: &str, code: &str) -> String {
+    let highlighted = match language {
+        "diff" | "patch" => highlight_diff(code),
+        "" | "text" | "txt" => escape_html(code),
+        _ => highlight_code(code),
+    };
+    format!("<pre><code class=\"language-{}\">{}</code></pre>", escape_html(language), highlighted)
+}
+
+/// Message text to HTML: fenced code blocks are highlighted, the rest is escaped with its line breaks kept
+fn message_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut prose = Vec::new();
+    let mut code: Option<(String, Vec<&str>)> = None;
+    let flush = |prose: &mut Vec<&str>, out: &mut String| {
+        if prose.iter().any(|line| !line.trim().is_empty()) {
+            out.push_str(&format!("<p>{}</p>", escape_html(prose.join("\n").trim()).replace('\n', "<br>\n")));
+        }
+        prose.clear();
+    };
+    for line in text.lines() {
+        match (&mut code, line.trim_start().strip_prefix("```")) {
+            (None, Some(language)) => {
+                flush(&mut prose, &mut out);
+                code = Some((language.trim().to_string(), Vec::new()));
+            }
+            (Some((language, lines)), Some(_)) => {
+                out.push_str(&code_block(language, &lines.join("\n")));
+                code = None;
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, None) => prose.push(line),
+        }
+    }
+    // An unclosed fence still shows its code
+    if let Some((language, lines)) = code {
+        out.push_str(&code_block(&language, &lines.join("\n")));
+    }
+    flush(&mut prose, &mut out);
+    out
+}
+
+fn render_html(header: &TranscriptHeader, entries: &[ChatEntry]) -> String {
+    let mut body = String::new();
+    for entry in entries.iter().filter(|entry| exported(entry)) {
+        let time = format!("<time>{}</time>", local_time(entry));
+        match entry.entry_type {
+            ChatEntryType::User => {
+                body.push_str(&format!("<section class=\"user\"><h2>👤 You {}</h2>{}</section>\n", time, message_html(&entry.content)))
+            }
+            ChatEntryType::Assistant => {
+                body.push_str(&format!("<section class=\"assistant\"><h2>🤖 Grok {}</h2>{}</section>\n", time, message_html(&entry.content)))
+            }
+            ChatEntryType::System => body.push_str(&format!("<section class=\"system\">⚙️ {}</section>\n", escape_html(&entry.content))),
+            ChatEntryType::ToolResult => {
+                let block = tool_block(entry);
+                let (class, status) = if block.failed { (" class=\"failed\"", " (failed)") } else { ("", "") };
+                body.push_str(&format!(
+                    "<details{}><summary>🔧 {}{} {}</summary>{}{}",
+                    class,
+                    escape_html(&block.name),
+                    status,
+                    time,
+                    code_block("json", &block.arguments),
+                    code_block("text", &block.output)
+                ));
+                if let Some(diff) = &block.diff {
+                    body.push_str(&code_block("diff", diff));
+                }
+                body.push_str("</details>\n");
+            }
+            ChatEntryType::ToolCall | ChatEntryType::ConfirmationPending => {}
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Grok session transcript</title>\n<style>{}</style>\n</head>\n<body>\n<header><h1>Grok session transcript</h1><p>Model: {}<br>\nExported: {}<br>\n{}</p></header>\n{}</body>\n</html>\n",
+        STYLE,
+        escape_html(&header.model),
+        header.exported_at.format("%Y-%m-%d %H:%M:%S %:z"),
+        escape_html(&header.usage).replace('\n', "<br>\n"),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{GrokToolCall, GrokToolCallFunction, ToolResult};
+
+    fn entry(entry_type: ChatEntryType, content: &str) -> ChatEntry {
+        ChatEntry {
+            entry_type,
+            content: content.to_string(),
+            timestamp: chrono::Utc::now(),
+            tool_calls: None,
+            tool_call: None,
+            tool_result: None,
+            is_streaming: None,
+        }
+    }
+
+    #[test]
+    fn test_transcript_renders_sections_tool_blocks_and_escapes_html() {
+        let mut tool = entry(ChatEntryType::ToolResult, "<script>alert(1)</script>");
+        tool.tool_call = Some(GrokToolCall {
+            id: "call_1".to_string(),
+            call_type: "function".to_string(),
+            function: GrokToolCallFunction { name: "str_replace_editor".to_string(), arguments: r#"{"path":"a.rs"}"#.to_string() },
+        });
+        tool.tool_result = Some(ToolResult {
+            success: true,
+            output: None,
+            error: None,
+            data: Some(serde_json::json!({ "diff": "--- a.rs\n+++ a.rs\n@@ -1 +1 @@\n-old\n+new" })),
+        });
+        let entries = vec![
+            entry(ChatEntryType::User, "Fix it"),
+            entry(ChatEntryType::ToolCall, "preview"),
+            tool,
+            entry(ChatEntryType::Assistant, "Done:\n```rust\nfn main() { let s = \"<b>\"; } // ok\n```"),
+        ];
+        let header = TranscriptHeader { model: "grok-4".to_string(), exported_at: chrono::Local::now(), usage: "Usage: 2 requests".to_string() };
+
+        let markdown = render(TranscriptFormat::Markdown, &header, &entries);
+        assert!(markdown.starts_with("# Grok session transcript\n\n- Model: grok-4\n- Exported: "));
+        assert!(markdown.contains("## 👤 You · ") && markdown.contains("\n\nFix it\n"));
+        assert!(markdown.contains("<summary>🔧 str_replace_editor · "));
+        assert!(markdown.contains("```json\n{\n  \"path\": \"a.rs\"\n}\n```"));
+        assert!(markdown.contains("```diff\n--- a.rs\n"));
+        assert!(!markdown.contains("preview"));
+
+        let html = render(TranscriptFormat::Html, &header, &entries);
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;") && !html.contains("<script>"));
+        assert!(html.contains("<span class=\"hl-diff-del\">-old</span>\n<span class=\"hl-diff-add\">+new</span>"));
+        assert!(html.contains("<span class=\"hl-keyword\">fn</span> main() { <span class=\"hl-keyword\">let</span> s = <span class=\"hl-string\">&quot;&lt;b&gt;&quot;</span>; } <span class=\"hl-comment\">// ok</span>"));
+        assert!(html.contains("<section class=\"assistant\"><h2>🤖 Grok <time>"));
+
+        assert_eq!(fence("a ``` b"), "````");
+        assert_eq!(TranscriptFormat::for_path(Path::new("out.HTML")), TranscriptFormat::Html);
+        assert_eq!(TranscriptFormat::for_path(Path::new("out")), TranscriptFormat::Markdown);
+    }
+}
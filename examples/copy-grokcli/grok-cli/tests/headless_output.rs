@@ -0,0 +1,170 @@
+//! Runs the binary in headless mode against a local mock of the chat completions API and
+//! checks what `--output-format` prints and the exit code
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Answer each request with the next body, repeating the last one once they run out
+fn mock_api(bodies: Vec<String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for (index, stream) in listener.incoming().enumerate() {
+            let Ok(mut socket) = stream else { break };
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).unwrap_or(0);
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let body = &bodies[index.min(bodies.len() - 1)];
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes());
+        }
+    });
+    base_url
+}
+
+fn run_headless(bodies: Vec<String>, extra_args: &[&str]) -> Output {
+    run_headless_with_settings(bodies, None, extra_args)
+}
+
+/// Run with `settings` as the user settings file, or with no settings files at all
+fn run_headless_with_settings(bodies: Vec<String>, settings: Option<serde_json::Value>, extra_args: &[&str]) -> Output {
+    let base_url = mock_api(bodies);
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let run = RUNS.fetch_add(1, Ordering::SeqCst);
+    let home = std::env::temp_dir().join(format!("grok-headless-{}-{}", std::process::id(), run));
+    std::fs::create_dir_all(&home).unwrap();
+    let config_args = match settings {
+        Some(settings) => {
+            let path = home.join("user-settings.json");
+            std::fs::write(&path, settings.to_string()).unwrap();
+            vec!["--config".to_string(), path.display().to_string()]
+        }
+        None => vec!["--no-config".to_string()],
+    };
+    let output = Command::new(env!("CARGO_BIN_EXE_grok-cli"))
+        .args(config_args)
+        .args(["--api-key", "key", "--base-url", &base_url, "--prompt", "hi"])
+        .args(extra_args)
+        .current_dir(&home)
+        .env("HOME", &home)
+        .env_remove("GROK_API_KEY")
+        .env_remove("GROK_BASE_URL")
+        .env_remove("GROK_MODEL")
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&home);
+    output
+}
+
+fn reply(content: &str) -> String {
+    serde_json::json!({
+        "choices": [{"message": {"role": "assistant", "content": content}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+    })
+    .to_string()
+}
+
+fn tool_call_reply() -> String {
+    call_reply("no_such_tool", "{}")
+}
+
+fn call_reply(name: &str, arguments: &str) -> String {
+    serde_json::json!({
+        "choices": [{
+            "message": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": name, "arguments": arguments}}]
+            },
+            "finish_reason": "tool_calls"
+        }]
+    })
+    .to_string()
+}
+
+#[test]
+fn test_json_output_has_entries_usage_and_exit_reason() {
+    let output = run_headless(vec![reply("Hello there")], &["--output-format", "json"]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["exit_reason"], "completed");
+    let entries = document["entries"].as_array().unwrap();
+    assert_eq!(entries[0]["type"], "user");
+    assert_eq!(entries[0]["content"], "hi");
+    assert_eq!(entries.last().unwrap()["type"], "assistant");
+    assert_eq!(entries.last().unwrap()["content"], "Hello there");
+    assert_eq!(document["usage"]["requests"], 1);
+    assert_eq!(document["usage"]["prompt_tokens"], 12);
+    assert_eq!(document["usage"]["completion_tokens"], 3);
+}
+
+#[test]
+fn test_max_tool_rounds_exits_non_zero_and_text_prints_only_the_answer() {
+    let output = run_headless(vec![tool_call_reply()], &["--output-format", "jsonl", "--max-tool-rounds", "1"]);
+    assert_eq!(output.status.code(), Some(2));
+    let entries: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(entries.iter().any(|entry| entry["type"] == "tool_result"));
+
+    // --export writes the transcript too, without changing what is printed
+    let export = std::env::temp_dir().join(format!("grok-export-{}.html", std::process::id()));
+    let output = run_headless(vec![tool_call_reply(), reply("Done.")], &["--output-format", "text", "--export", &export.to_string_lossy()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Done.\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("🔧 no_such_tool failed"));
+    let html = std::fs::read_to_string(&export).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>") && html.contains("<details class=\"failed\"><summary>🔧 no_such_tool (failed)"));
+    assert!(html.contains("<section class=\"assistant\"><h2>🤖 Grok <time>") && html.contains("<p>Done.</p>"));
+    std::fs::remove_file(&export).ok();
+}
+
+#[test]
+fn test_mcp_tools_are_offered_and_called_under_their_namespaced_name() {
+    let settings = serde_json::json!({
+        "mcp_servers": {
+            "fake": {"name": "fake", "transport": {"type": "stdio", "command": env!("CARGO_BIN_EXE_fake-mcp-server")}}
+        }
+    });
+    let output = run_headless_with_settings(
+        vec![call_reply("mcp__fake__echo", r#"{"text":"from the model"}"#), reply("Echoed.")],
+        Some(settings),
+        &["--output-format", "json"],
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let result = document["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["type"] == "tool_result")
+        .unwrap()
+        .clone();
+    assert_eq!(result["tool_result"]["success"], true, "{}", result);
+    assert_eq!(result["tool_result"]["output"], r#"{"text":"from the model"}"#);
+}
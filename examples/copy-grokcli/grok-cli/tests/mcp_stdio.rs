@@ -0,0 +1,90 @@
+//! Talks to the fake stdio MCP server through the client's stdio transport and `grok mcp test`
+
+#[path = "../src/mcp/stdio.rs"]
+#[allow(dead_code)]
+mod stdio;
+
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use stdio::StdioConnection;
+
+async fn connect() -> StdioConnection {
+    StdioConnection::spawn(env!("CARGO_BIN_EXE_fake-mcp-server"), &[], &HashMap::new(), Duration::from_secs(5))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_handshake_lists_and_calls_tools() {
+    let connection = connect().await;
+    assert_eq!(connection.server_info["name"], "fake-mcp-server");
+
+    let tools = connection.list_tools().await.unwrap();
+    let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+    assert_eq!(names, ["echo", "crash"]);
+    assert_eq!(tools[0].input_schema["properties"]["text"]["type"], "string");
+
+    // Concurrent calls each get their own reply
+    let (first, second) = tokio::join!(
+        connection.call_tool("echo", json!({"text": "one"})),
+        connection.call_tool("echo", json!({"text": "two"}))
+    );
+    assert_eq!(first.unwrap()["content"][0]["text"], r#"{"text":"one"}"#);
+    assert_eq!(second.unwrap()["content"][0]["text"], r#"{"text":"two"}"#);
+
+    let error = connection.request("resources/list", json!({})).await.unwrap_err();
+    assert_eq!(error, "Method not found: resources/list (code -32601)");
+}
+
+#[tokio::test]
+async fn test_a_crash_fails_the_call_with_the_stderr_tail() {
+    let connection = connect().await;
+    let error = connection.call_tool("crash", json!({})).await.unwrap_err();
+    assert!(error.contains("exited"), "{}", error);
+    assert!(error.contains("fake-mcp-server: crashing on purpose"), "{}", error);
+    assert!(connection.has_exited());
+    assert_eq!(connection.call_tool("echo", json!({})).await.unwrap_err(), error);
+
+    let missing = StdioConnection::spawn("/nonexistent/mcp-server", &[], &HashMap::new(), Duration::from_secs(5)).await;
+    assert!(missing.err().unwrap().starts_with("Could not start /nonexistent/mcp-server"));
+}
+
+/// Run `grok mcp test <name>` with `servers` as the user settings' `mcp_servers`
+fn mcp_test(servers: serde_json::Value, name: &str) -> std::process::Output {
+    let home = std::env::temp_dir().join(format!("grok-mcp-test-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&home).unwrap();
+    let settings = home.join("user-settings.json");
+    std::fs::write(&settings, json!({ "mcp_servers": servers }).to_string()).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_grok-cli"))
+        .arg("--config")
+        .arg(&settings)
+        .args(["mcp", "test", name])
+        .current_dir(&home)
+        .env("HOME", &home)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&home);
+    output
+}
+
+#[test]
+fn test_mcp_test_prints_the_tools_or_exits_non_zero() {
+    let servers = json!({
+        "fake": {"name": "fake", "transport": {"type": "stdio", "command": env!("CARGO_BIN_EXE_fake-mcp-server")}},
+        "gone": {"name": "gone", "transport": {"type": "stdio", "command": "/nonexistent/mcp-server"}}
+    });
+    let output = mcp_test(servers.clone(), "fake");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.starts_with("✅ fake (fake-mcp-server 1.0.0: 2 tools, initialize "), "{}", stdout);
+    assert!(stdout.lines().any(|line| line.starts_with("echo ") && line.ends_with(" Return the arguments")), "{}", stdout);
+
+    let output = mcp_test(servers.clone(), "gone");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("❌ MCP server gone failed: Could not start /nonexistent/mcp-server"));
+
+    let output = mcp_test(servers, "missing");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No MCP server named missing"));
+}
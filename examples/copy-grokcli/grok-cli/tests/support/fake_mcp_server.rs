@@ -0,0 +1,60 @@
+//! A stdio MCP server for the integration tests. It offers an `echo` tool that returns its
+//! arguments as text, and a `crash` tool that writes to stderr and exits with status 3.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+fn main() {
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    // Clients have to skip output that is not JSON-RPC
+    writeln!(stdout, "fake-mcp-server ready").unwrap();
+    stdout.flush().unwrap();
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let Ok(message) = serde_json::from_str::<Value>(&line) else { continue };
+        // Notifications get no reply
+        let Some(id) = message.get("id").cloned() else { continue };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let reply = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "protocolVersion": params["protocolVersion"],
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "fake-mcp-server", "version": "1.0.0"}
+                }
+            }),
+            "tools/list" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {"tools": [
+                    {
+                        "name": "echo",
+                        "description": "Return the arguments",
+                        "inputSchema": {"type": "object", "properties": {"text": {"type": "string"}}}
+                    },
+                    {"name": "crash", "description": "Exit without answering", "inputSchema": {"type": "object"}}
+                ]}
+            }),
+            "tools/call" if params["name"] == "crash" => {
+                eprintln!("fake-mcp-server: crashing on purpose");
+                std::process::exit(3);
+            }
+            "tools/call" if params["name"] == "echo" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {"content": [{"type": "text", "text": params["arguments"].to_string()}]}
+            }),
+            method => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": -32601, "message": format!("Method not found: {}", method)}
+            }),
+        };
+        writeln!(stdout, "{}", reply).unwrap();
+        stdout.flush().unwrap();
+    }
+}
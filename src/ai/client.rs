@@ -76,7 +76,11 @@ struct ResponseMessage {
 }
 
 impl LLMClient {
-    pub fn new(config: LLMConfig) -> Self {
+    /// 创建客户端；base_url 无效时原样保留，请求时再报错。需要提前校验请使用 `try_new`
+    pub fn new(mut config: LLMConfig) -> Self {
+        if let Ok(endpoint) = config.chat_endpoint() {
+            config.base_url = endpoint;
+        }
         let mut headers = HeaderMap::new();
         if !config.api_key.is_empty() {
             headers.insert(
@@ -95,6 +99,50 @@ impl LLMClient {
         Self { client, config }
     }
 
+    /// 创建客户端并拒绝明显无效的 base_url
+    pub fn try_new(config: LLMConfig) -> Result<Self, String> {
+        config.chat_endpoint()?;
+        Ok(Self::new(config))
+    }
+
+    /// 实际请求的端点
+    pub fn endpoint(&self) -> &str {
+        &self.config.base_url
+    }
+
+    /// 所有重试点共用的重试配置
+    pub fn retry_config(&self) -> &crate::core::RetryConfig {
+        &self.config.retry
+    }
+
+    /// 探测端点是否可用（请求模型列表，不消耗 token）
+    pub async fn check_endpoint(&self) -> Result<String, String> {
+        let endpoint = self.config.base_url.as_str();
+        let probe_url = if let Some(root) = endpoint.strip_suffix("/api/chat").or_else(|| endpoint.strip_suffix("/api/generate")) {
+            format!("{}/api/tags", root)
+        } else if let Some(root) = endpoint.strip_suffix("/chat/completions").or_else(|| endpoint.strip_suffix("/messages")) {
+            format!("{}/models", root)
+        } else {
+            endpoint.to_string()
+        };
+
+        let response = self
+            .client
+            .get(&probe_url)
+            .timeout(Duration::from_secs(15))
+            .send()
+            .await
+            .map_err(|e| format!("无法连接 {}: {}", probe_url, e))?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200..=299 => Ok(format!("{} 响应 {}", probe_url, status)),
+            401 | 403 => Err(format!("{} 可以访问，但 API 密钥被拒绝 ({})", probe_url, status)),
+            404 => Err(format!("{} 返回 404，base_url 路径可能有误（缺少 /v1？）", probe_url)),
+            _ => Err(format!("{} 响应 {}", probe_url, status)),
+        }
+    }
+
     /// 生成非流式响应（支持工具调用）
     pub async fn generate_completion(
         &self,
@@ -198,7 +198,7 @@ impl AICodeModificationDetector {
     }
 
     /// 提取代码块
-    fn extract_code_blocks(response: &str) -> Vec<CodeBlock> {
+    pub fn extract_code_blocks(response: &str) -> Vec<CodeBlock> {
         let mut blocks = Vec::new();
         let re = Regex::new(r"```(\w*)\n([\s\S]*?)```").unwrap();
         
@@ -354,6 +354,16 @@ impl CodeMatcher {
         let old_content = fs::read_to_string(file_path)
             .map_err(|e| format!("无法读取文件: {}", e))?;
 
+        Self::replace_in_content(file_path, old_content, search, replace)
+    }
+
+    /// 在给定内容上执行搜索替换（不读写磁盘）
+    pub fn replace_in_content(
+        file_path: &str,
+        old_content: String,
+        search: &str,
+        replace: &str,
+    ) -> Result<CodeDiff, String> {
         // 尝试精确匹配
         if old_content.contains(search) {
             let new_content = old_content.replace(search, replace);
@@ -473,6 +483,120 @@ impl CodeMatcher {
     }
 }
 
+/// 批量修改失败：指出第几个修改、哪个文件、原因
+#[derive(Debug, Clone)]
+pub struct BatchApplyError {
+    /// 从 1 开始的修改序号
+    pub index: usize,
+    pub path: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for BatchApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "第 {} 个修改 ({}) 失败: {}", self.index, self.path, self.reason)
+    }
+}
+
+/// 已确认批量修改的全有或全无应用
+///
+/// 先在内存中计算所有文件的新内容并验证每个匹配都成功，全部通过后才写盘；
+/// 写盘过程中出错时恢复已写入的文件，保证不会留下半完成的修改。
+pub struct ModificationBatch;
+
+impl ModificationBatch {
+    /// 应用整个批次，成功时返回每个操作的结果消息
+    pub fn apply(ops: &[CodeModificationOp]) -> Result<Vec<String>, BatchApplyError> {
+        let (staged, messages) = Self::plan(ops)?;
+        Self::commit(&staged)?;
+        Ok(messages)
+    }
+
+    /// 计算最终文件状态（None 表示删除），同一文件的多次修改按顺序叠加
+    fn plan(ops: &[CodeModificationOp]) -> Result<(Vec<(String, Option<String>)>, Vec<String>), BatchApplyError> {
+        let mut staged: Vec<(String, Option<String>)> = Vec::new();
+        let mut messages = Vec::new();
+
+        for (i, op) in ops.iter().enumerate() {
+            let fail = |path: &str, reason: String| BatchApplyError {
+                index: i + 1,
+                path: path.to_string(),
+                reason,
+            };
+            let current = |staged: &Vec<(String, Option<String>)>, path: &str| -> Option<Option<String>> {
+                staged.iter().rev().find(|(p, _)| p == path).map(|(_, c)| c.clone())
+            };
+
+            let (path, new_state, message) = match op {
+                CodeModificationOp::Create { path, content } => {
+                    (path, Some(content.clone()), format!("✅ 文件已创建: {}", path))
+                }
+                CodeModificationOp::Modify { path, search, replace } => {
+                    let old_content = match current(&staged, path) {
+                        Some(Some(content)) => content,
+                        Some(None) => return Err(fail(path, "文件已在本批次中被删除".to_string())),
+                        None => fs::read_to_string(path).map_err(|e| fail(path, format!("无法读取文件: {}", e)))?,
+                    };
+                    let diff = CodeMatcher::replace_in_content(path, old_content, search, replace)
+                        .map_err(|e| fail(path, e))?;
+                    (path, Some(diff.new_content), format!("✅ 文件已修改: {}", path))
+                }
+                CodeModificationOp::Delete { path } => {
+                    let exists = match current(&staged, path) {
+                        Some(state) => state.is_some(),
+                        None => std::path::Path::new(path).is_file(),
+                    };
+                    if !exists {
+                        return Err(fail(path, "文件不存在".to_string()));
+                    }
+                    (path, None, format!("✅ 文件已删除: {}", path))
+                }
+            };
+
+            staged.retain(|(p, _)| p != path);
+            staged.push((path.clone(), new_state));
+            messages.push(message);
+        }
+
+        Ok((staged, messages))
+    }
+
+    /// 写入所有文件；任何一步失败都会恢复之前写入的文件
+    fn commit(staged: &[(String, Option<String>)]) -> Result<(), BatchApplyError> {
+        let originals: Vec<Option<Vec<u8>>> = staged.iter().map(|(path, _)| fs::read(path).ok()).collect();
+
+        for (i, (path, state)) in staged.iter().enumerate() {
+            let result = match state {
+                Some(content) => {
+                    if let Some(parent) = std::path::Path::new(path).parent() {
+                        if !parent.as_os_str().is_empty() {
+                            let _ = fs::create_dir_all(parent);
+                        }
+                    }
+                    fs::write(path, content)
+                }
+                None => fs::remove_file(path),
+            };
+
+            if let Err(e) = result {
+                for ((written, _), original) in staged[..i].iter().zip(&originals) {
+                    let _ = match original {
+                        Some(bytes) => fs::write(written, bytes),
+                        None => fs::remove_file(written),
+                    };
+                }
+                return Err(BatchApplyError {
+                    index: i + 1,
+                    path: path.clone(),
+                    reason: format!("写入失败: {}", e),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -511,4 +635,40 @@ mod tests {
         assert!(CodeMatcher::string_similarity("hello", "hallo") > 0.7);
         assert!(CodeMatcher::string_similarity("hello", "world") < 0.5);
     }
+
+    #[test]
+    fn test_batch_writes_nothing_when_a_hunk_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.rs");
+        fs::write(&a, "fn a() {}\n").unwrap();
+        fs::write(&b, "fn b() {}\n").unwrap();
+        let a = a.to_str().unwrap().to_string();
+        let b = b.to_str().unwrap().to_string();
+
+        let ops = vec![
+            CodeModificationOp::Modify { path: a.clone(), search: "fn a() {}".to_string(), replace: "fn a2() {}".to_string() },
+            CodeModificationOp::Modify { path: b.clone(), search: "fn missing() {}".to_string(), replace: "x".to_string() },
+        ];
+        let err = ModificationBatch::apply(&ops).unwrap_err();
+        assert_eq!(err.index, 2);
+        assert_eq!(err.path, b);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "fn a() {}\n");
+    }
+
+    #[test]
+    fn test_batch_stacks_edits_to_the_same_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.rs");
+        fs::write(&a, "one\ntwo\n").unwrap();
+        let a = a.to_str().unwrap().to_string();
+
+        let ops = vec![
+            CodeModificationOp::Modify { path: a.clone(), search: "one".to_string(), replace: "1".to_string() },
+            CodeModificationOp::Modify { path: a.clone(), search: "two".to_string(), replace: "2".to_string() },
+        ];
+        let messages = ModificationBatch::apply(&ops).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "1\n2\n");
+    }
 }
@@ -21,6 +21,16 @@ pub enum CommandType {
     ListProviders,  // /list-providers
     SaveConfig,     // /save-config
     LoadConfig,     // /load-config
+    // 固定上下文
+    Pin,            // /pin <file>
+    Unpin,          // /unpin <file> | /unpin all
+    Pins,           // /pins
+    Doctor,         // /doctor
+    Review,         // /review <file>
+    RetryConfig,    // /retry-config [set <attempts|delay|backoff> <value>]
+    Context,        // /context
+    Overview,       // /overview [max_tokens]
+    Scaffold,       // /scaffold [project_type]
     Unknown,
 }
 
@@ -30,7 +40,7 @@ pub struct Command {
     pub args: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MentionType {
     Model,      // @model - 提及当前模型
     Provider,   // @provider - 提及当前提供商
@@ -83,6 +93,15 @@ impl CommandParser {
             "list-providers" | "lp" => CommandType::ListProviders,
             "save-config" | "save" => CommandType::SaveConfig,
             "load-config" | "load" => CommandType::LoadConfig,
+            "pin" => CommandType::Pin,
+            "unpin" => CommandType::Unpin,
+            "pins" => CommandType::Pins,
+            "doctor" => CommandType::Doctor,
+            "review" => CommandType::Review,
+            "retry-config" => CommandType::RetryConfig,
+            "context" | "ctx" => CommandType::Context,
+            "overview" => CommandType::Overview,
+            "scaffold" => CommandType::Scaffold,
             _ => CommandType::Unknown,
         };
 
@@ -175,7 +194,7 @@ impl CommandParser {
 ║                    基础命令                                    ║
 ╠════════════════════════════════════════════════════════════════╣
 ║ /help, /h              - 显示此帮助信息                        ║
-║ /clear, /c             - 清除聊天历史                          ║
+║ /clear, /c [--yes]     - 清除聊天历史（--yes 跳过确认）        ║
 ║ /history, /hist        - 显示聊天历史                          ║
 ║ /status, /s            - 显示应用状态                          ║
 ║ /list-providers, /lp   - 列出所有可用的 AI 提供商              ║
@@ -204,6 +223,28 @@ impl CommandParser {
 ╠════════════════════════════════════════════════════════════════╣
 ║ /save-config, /save              - 保存当前配置到 .env         ║
 ║ /load-config, /load              - 从 .env 重新加载配置        ║
+║ /doctor                          - 检查配置并探测 API 端点     ║
+║ /retry-config [set <项> <值>]    - 查看/调整重试并保存到 .env  ║
+╠════════════════════════════════════════════════════════════════╣
+║                    固定上下文                                  ║
+╠════════════════════════════════════════════════════════════════╣
+║ /pin <file>                      - 固定文件，每轮自动注入      ║
+║ /unpin <file|all>                - 取消固定                    ║
+║ /pins                            - 列出固定的文件              ║
+║ /context, Ctrl+K                 - 查看注入文件及 token，可移除║
+║ /overview [max_tokens]           - 项目结构概览（遵循忽略规则）║
+║ /summarize <file>                - 文件摘要（结构和简短说明）  ║
+║ /scaffold [rust|node|python|go]  - 生成 .gitignore/CI 等并确认 ║
+╠════════════════════════════════════════════════════════════════╣
+║                    代码审查                                    ║
+╠════════════════════════════════════════════════════════════════╣
+║ /review <file>                   - 审查文件并逐条处理建议      ║
+║   a/Enter 应用  s/→ 跳过  ← 上一条  Esc 结束                   ║
+╠════════════════════════════════════════════════════════════════╣
+║                    快捷键（输入框为空时）                      ║
+╠════════════════════════════════════════════════════════════════╣
+║ y y                    - 复制最后一条 AI 回复                  ║
+║ y c                    - 复制最后一个代码块                    ║
 ╠════════════════════════════════════════════════════════════════╣
 ║                    可用提及                                    ║
 ╠════════════════════════════════════════════════════════════════╣
@@ -231,14 +272,14 @@ mod tests {
 
     #[test]
     fn test_parse_help_command() {
-        let cmd = CommandParser::parse_command("/help");
+        let cmd = CommandParser::parse("/help");
         assert!(cmd.is_some());
         assert_eq!(cmd.unwrap().command_type, CommandType::Help);
     }
 
     #[test]
     fn test_parse_model_command_with_args() {
-        let cmd = CommandParser::parse_command("/model gpt-4");
+        let cmd = CommandParser::parse("/model gpt-4");
         assert!(cmd.is_some());
         let cmd = cmd.unwrap();
         assert_eq!(cmd.command_type, CommandType::Model);
@@ -257,4 +298,13 @@ mod tests {
         let mentions = CommandParser::extract_mentions("Hey @model, what about @provider?");
         assert_eq!(mentions.len(), 2);
     }
+
+    #[test]
+    fn test_parse_pin_commands() {
+        let cmd = CommandParser::parse("/pin docs/spec.md").unwrap();
+        assert_eq!(cmd.command_type, CommandType::Pin);
+        assert_eq!(cmd.args, vec!["docs/spec.md"]);
+        assert_eq!(CommandParser::parse("/unpin all").unwrap().command_type, CommandType::Unpin);
+        assert_eq!(CommandParser::parse("/pins").unwrap().command_type, CommandType::Pins);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::core::RetryConfig;
 use serde::{Deserialize, Serialize};
 use std::env;
 
@@ -43,6 +44,9 @@ pub struct LLMConfig {
     pub base_url: String,
     pub temperature: f32,
     pub max_tokens: u32,
+    /// 所有 LLM 调用共用的重试配置
+    #[serde(default)]
+    pub retry: RetryConfig,
 }
 
 impl LLMConfig {
@@ -111,6 +115,7 @@ impl LLMConfig {
             base_url,
             temperature,
             max_tokens,
+            retry: RetryConfig::from_env(),
         })
     }
 
@@ -123,6 +128,7 @@ impl LLMConfig {
             base_url: "https://api.openai.com/v1/chat/completions".to_string(),
             temperature: 0.7,
             max_tokens: 200,
+            retry: RetryConfig::default(),
         }
     }
 
@@ -135,6 +141,7 @@ impl LLMConfig {
             base_url: "https://generativelanguage.googleapis.com/v1beta/openai/".to_string(),
             temperature: 0.7,
             max_tokens: 200,
+            retry: RetryConfig::default(),
         }
     }
 
@@ -147,6 +154,7 @@ impl LLMConfig {
             base_url: "https://api.deepseek.com/v1".to_string(),
             temperature: 0.7,
             max_tokens: 2048,
+            retry: RetryConfig::default(),
         }
     }
 
@@ -159,6 +167,7 @@ impl LLMConfig {
             base_url: "http://localhost:11434/api/chat".to_string(),
             temperature: 0.7,
             max_tokens: 200,
+            retry: RetryConfig::default(),
         }
     }
 
@@ -171,6 +180,7 @@ impl LLMConfig {
             base_url,
             temperature: 0.7,
             max_tokens: 200,
+            retry: RetryConfig::default(),
         }
     }
 
@@ -360,6 +370,9 @@ impl LLMConfig {
         content.push_str("\n# === General Settings ===\n");
         content.push_str(&format!("LLM_TEMPERATURE={}\n", self.temperature));
         content.push_str(&format!("LLM_MAX_TOKENS={}\n", self.max_tokens));
+        content.push_str(&format!("LLM_RETRY_ATTEMPTS={}\n", self.retry.max_attempts));
+        content.push_str(&format!("LLM_RETRY_DELAY_MS={}\n", self.retry.initial_delay_ms));
+        content.push_str(&format!("LLM_RETRY_BACKOFF={}\n", self.retry.backoff_multiplier));
 
         content
     }
@@ -376,6 +389,63 @@ impl LLMConfig {
         ]
     }
 
+    /// 校验 base_url 并返回实际请求的端点
+    ///
+    /// 去掉末尾斜杠；只给到 API 根（如 `https://api.deepseek.com/v1`）时补全
+    /// `/chat/completions`，Ollama 只给到主机时补全 `/api/chat`。
+    /// 明显无效的地址（缺少协议、非 http(s)、没有主机）直接报错。
+    pub fn chat_endpoint(&self) -> Result<String, String> {
+        let trimmed = self.base_url.trim().trim_end_matches('/');
+        let url = reqwest::Url::parse(trimmed)
+            .map_err(|e| format!("base_url 无效 '{}': {}（示例: https://api.openai.com/v1）", self.base_url, e))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(format!("base_url 无效 '{}': 只支持 http 或 https", self.base_url));
+        }
+        if url.host_str().is_none_or(|host| host.is_empty()) {
+            return Err(format!("base_url 无效 '{}': 缺少主机名", self.base_url));
+        }
+
+        const ENDPOINT_SUFFIXES: &[&str] = &["/chat/completions", "/api/chat", "/api/generate", "/messages"];
+        if ENDPOINT_SUFFIXES.iter().any(|suffix| url.path().ends_with(suffix)) {
+            return Ok(trimmed.to_string());
+        }
+        if self.provider == LLMProvider::Ollama && url.path().trim_matches('/').is_empty() {
+            return Ok(format!("{}/api/chat", trimmed));
+        }
+        Ok(format!("{}/chat/completions", trimmed))
+    }
+
+    /// 对能解析但很可能配错的 base_url 给出提示
+    pub fn base_url_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let Ok(url) = reqwest::Url::parse(self.base_url.trim()) else {
+            return warnings;
+        };
+        let host = url.host_str().unwrap_or_default();
+
+        const VERSIONED_API_HOSTS: &[&str] = &[
+            "api.openai.com",
+            "api.deepseek.com",
+            "api.anthropic.com",
+            "api.x.ai",
+            "generativelanguage.googleapis.com",
+        ];
+        let has_version = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .any(|segment| segment.starts_with('v') && segment[1..].starts_with(|c: char| c.is_ascii_digit()));
+        if !has_version && VERSIONED_API_HOSTS.contains(&host) {
+            warnings.push(format!("base_url '{}' 缺少版本路径，{} 通常需要 /v1 之类的前缀", self.base_url, host));
+        }
+
+        let is_local = host == "localhost" || host == "127.0.0.1" || host == "[::1]";
+        if url.scheme() == "http" && !is_local {
+            warnings.push(format!("base_url '{}' 使用明文 http，API 密钥将不加密传输", self.base_url));
+        }
+        warnings
+    }
+
     /// 获取配置状态信息
     pub fn get_status_info(&self) -> String {
         let api_key_display = if self.api_key == "local" {
@@ -394,3 +464,29 @@ impl LLMConfig {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_endpoint_normalization() {
+        let mut config = LLMConfig::default_ollama();
+        config.base_url = "http://localhost:11434/".to_string();
+        assert_eq!(config.chat_endpoint().unwrap(), "http://localhost:11434/api/chat");
+
+        config.provider = LLMProvider::DeepSeek;
+        config.base_url = "https://api.deepseek.com/v1/".to_string();
+        assert_eq!(config.chat_endpoint().unwrap(), "https://api.deepseek.com/v1/chat/completions");
+
+        config.base_url = "https://api.openai.com/v1/chat/completions".to_string();
+        assert_eq!(config.chat_endpoint().unwrap(), config.base_url);
+        assert!(config.base_url_warnings().is_empty());
+
+        config.base_url = "https://api.openai.com".to_string();
+        assert_eq!(config.base_url_warnings().len(), 1);
+
+        config.base_url = "api.openai.com/v1".to_string();
+        assert!(config.chat_endpoint().is_err());
+    }
+}
@@ -7,4 +7,5 @@ pub mod streaming;
 pub mod advanced_client;
 pub mod tools;
 pub mod code_modification;
+pub mod review;
 pub mod prompt_builder;
\ No newline at end of file
@@ -0,0 +1,187 @@
+//! 结构化代码审查建议
+//!
+//! 模型按约定格式返回建议（文件、行范围、替换内容、理由），解析后逐条展示，
+//! 用户可以接受（通过 `ModificationBatch` / `CodeMatcher` 应用）或跳过。
+
+use crate::ai::code_modification::CodeModificationOp;
+use serde::Deserialize;
+
+/// 审查建议代码块的语言标记
+pub const REVIEW_BLOCK_LANG: &str = "review-suggestions";
+
+/// 一条审查建议
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReviewSuggestion {
+    pub file: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub replacement: String,
+    #[serde(default)]
+    pub rationale: String,
+    /// 解析时从磁盘读取的原始行，应用时作为搜索块，前面的修改导致行号偏移也能定位
+    #[serde(skip)]
+    pub original: String,
+}
+
+impl ReviewSuggestion {
+    /// 转换为修改操作，复用确认后的批量应用流程
+    pub fn to_modification(&self) -> CodeModificationOp {
+        CodeModificationOp::Modify {
+            path: self.file.clone(),
+            search: self.original.clone(),
+            replace: self.replacement.trim_end_matches('\n').to_string(),
+        }
+    }
+
+    /// 读取行范围的原始内容
+    fn load_original(&mut self) -> Result<(), String> {
+        let content = std::fs::read_to_string(&self.file)
+            .map_err(|e| format!("{}: 无法读取文件: {}", self.file, e))?;
+        let lines: Vec<&str> = content.lines().collect();
+        if self.start_line == 0 || self.start_line > self.end_line || self.end_line > lines.len() {
+            return Err(format!(
+                "{}: 行范围 {}-{} 无效（文件共 {} 行）",
+                self.file,
+                self.start_line,
+                self.end_line,
+                lines.len()
+            ));
+        }
+        self.original = lines[self.start_line - 1..self.end_line].join("\n");
+        Ok(())
+    }
+}
+
+/// 从模型回复中解析审查建议，返回可用的建议和无法使用的原因
+pub fn parse_review_suggestions(response: &str) -> (Vec<ReviewSuggestion>, Vec<String>) {
+    let fence = format!("```{}", REVIEW_BLOCK_LANG);
+    let Some(start) = response.find(&fence) else {
+        return (Vec::new(), Vec::new());
+    };
+    let body = &response[start + fence.len()..];
+    let body = &body[..body.find("```").unwrap_or(body.len())];
+
+    let parsed: Vec<ReviewSuggestion> = match serde_json::from_str(body.trim()) {
+        Ok(parsed) => parsed,
+        Err(e) => return (Vec::new(), vec![format!("审查建议格式错误: {}", e)]),
+    };
+
+    let mut suggestions = Vec::new();
+    let mut errors = Vec::new();
+    for mut suggestion in parsed {
+        match suggestion.load_original() {
+            Ok(()) => suggestions.push(suggestion),
+            Err(e) => errors.push(e),
+        }
+    }
+    (suggestions, errors)
+}
+
+/// 单条建议的处理状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuggestionStatus {
+    Pending,
+    Accepted,
+    Skipped,
+    Failed(String),
+}
+
+/// 正在逐条处理的一组审查建议
+#[derive(Debug, Clone)]
+pub struct ReviewSession {
+    suggestions: Vec<ReviewSuggestion>,
+    statuses: Vec<SuggestionStatus>,
+    index: usize,
+}
+
+impl ReviewSession {
+    pub fn new(suggestions: Vec<ReviewSuggestion>) -> Self {
+        let statuses = vec![SuggestionStatus::Pending; suggestions.len()];
+        Self {
+            suggestions,
+            statuses,
+            index: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.suggestions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.suggestions.is_empty()
+    }
+
+    /// 当前位置（从 0 开始）
+    pub fn position(&self) -> usize {
+        self.index
+    }
+
+    pub fn current(&self) -> Option<(&ReviewSuggestion, &SuggestionStatus)> {
+        Some((self.suggestions.get(self.index)?, self.statuses.get(self.index)?))
+    }
+
+    pub fn mark_current(&mut self, status: SuggestionStatus) {
+        if let Some(slot) = self.statuses.get_mut(self.index) {
+            *slot = status;
+        }
+    }
+
+    /// 前进到下一条，已经是最后一条时返回 false
+    pub fn next(&mut self) -> bool {
+        if self.index + 1 < self.suggestions.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn previous(&mut self) -> bool {
+        if self.index > 0 {
+            self.index -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 结束时的统计
+    pub fn summary(&self) -> String {
+        let count = |target: fn(&SuggestionStatus) -> bool| self.statuses.iter().filter(|s| target(s)).count();
+        format!(
+            "审查结束：应用 {} 条，跳过 {} 条，失败 {} 条，未处理 {} 条",
+            count(|s| *s == SuggestionStatus::Accepted),
+            count(|s| *s == SuggestionStatus::Skipped),
+            count(|s| matches!(s, SuggestionStatus::Failed(_))),
+            count(|s| *s == SuggestionStatus::Pending),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_suggestions_captures_original_lines() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "fn a() {{}}\nfn b() {{ x.unwrap() }}\nfn c() {{}}\n").unwrap();
+        let path = file.path().to_string_lossy().replace('\\', "/");
+
+        let response = format!(
+            "Found one issue.\n\n```review-suggestions\n[\n  {{\"file\": \"{0}\", \"start_line\": 2, \"end_line\": 2, \"replacement\": \"fn b() {{ x? }}\", \"rationale\": \"avoid panics\"}},\n  {{\"file\": \"{0}\", \"start_line\": 9, \"end_line\": 12, \"replacement\": \"\"}}\n]\n```",
+            path
+        );
+        let (suggestions, errors) = parse_review_suggestions(&response);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(suggestions[0].original, "fn b() { x.unwrap() }");
+
+        let mut session = ReviewSession::new(suggestions);
+        session.mark_current(SuggestionStatus::Accepted);
+        assert!(!session.next());
+        assert!(session.summary().contains("应用 1 条"));
+    }
+}
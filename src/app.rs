@@ -3,17 +3,23 @@ use crate::ai::commands::{CommandParser, CommandType};
 use crate::ai::config::LLMConfig;
 use crate::ai::streaming::{StreamHandler, StreamingChatResponse};
 use crate::core::message::{Message, Role};
+use crate::core::pinned_context::PinnedFileStatus;
+use crate::core::context_files::{ContextFileSet, FileSource};
 use crate::core::history::ChatHistory;
-use crate::core::{GeminiArchitecture, ConversationEngine, ChatOrchestrator};
+use crate::core::{GeminiArchitecture, ConversationEngine, ChatOrchestrator, PinnedContext};
 use crate::ui::command_hints::CommandHints;
 use crate::commands::file_commands::FileCommandHandler;
+use command_registry::CommandOutcome;
 use crate::prompts;
-use crate::ai::code_modification::{AICodeModificationDetector, CodeModificationOp, CodeDiff, CodeMatcher};
+use crate::ai::code_modification::{AICodeModificationDetector, CodeModificationOp, CodeDiff, CodeMatcher, ModificationBatch};
+use crate::ai::review::{self, ReviewSession, SuggestionStatus};
 use crate::core::vibe_coding::{VibeWorkflowManager, VibeStage};
 use crate::commands::VibeCommandHandler;
 use crate::ui::filename_suggestion::FilenameSuggestion;
 use ratatui::{Frame, widgets::ScrollbarState};
 use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::collections::HashMap;
 use crate::ui;
 
 // ============ Action 系统 ============
@@ -125,6 +131,13 @@ pub enum AppAction {
         Abandon,  // 3. 放弃
     }
 
+/// /clear 确认前的预览：将删除的消息数和估算的 token 数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearPreview {
+    pub messages: usize,
+    pub tokens: usize,
+}
+
 pub struct App {
     pub should_quit: bool,
     pub chat_history: ChatHistory,
@@ -143,6 +156,10 @@ pub struct App {
     pub modification_confirmation_pending: bool,
     pub modification_selected_index: usize,
     pub modification_choice: ModificationChoice,
+    /// /clear 等待确认（1/Enter 确认，2/N/Esc 取消）
+    pub pending_clear: Option<ClearPreview>,
+    /// AI 消息的 Markdown 渲染缓存，按消息下标；渲染时只借用 App，所以放在锁里
+    pub markdown_cache: Mutex<HashMap<usize, crate::ui::markdown::StreamingMarkdown>>,
 
     // 聊天历史滚动
     pub chat_scroll_offset: usize,
@@ -191,11 +208,32 @@ pub struct App {
 
     // AI Agent - 类似 grok-cli 的 GrokAgent，支持工具调用
     pub ai_agent: Option<crate::core::AIAgent>,
+
+    // 固定上下文：每轮请求都会注入的参考文件
+    pub pinned_context: PinnedContext,
+
+    // 复制快捷键：输入框为空时按下 y 后等待第二个键（y=最后一条回复，c=最后一个代码块）
+    pub pending_copy_chord: bool,
+
+    // 结构化审查：逐条展示模型给出的修改建议，a 应用 / s 跳过
+    pub review_session: Option<ReviewSession>,
+    /// 上下文文件面板（Ctrl+K 或 /context）
+    pub context_panel: crate::ui::context_panel::ContextPanel,
+    /// 在面板里从目录提及中移除的文件（规范化路径），本会话内不再注入
+    context_exclusions: Vec<std::path::PathBuf>,
+    /// /overview 的项目概览（文件索引不变时复用缓存）
+    project_overview: crate::tools::ProjectOverviewTool,
+    /// /summarize 的文件摘要；配置 LLM 后附带 utility 模型写的说明（按 mtime 缓存）
+    file_summarizer: crate::tools::SummarizeFileTool,
+    /// `.grok/commands/*.md` 定义的提示命令，注册在内置命令之后
+    pub custom_commands: Vec<command_registry::PromptCommand>,
 }
 
 impl App {
     pub fn new() -> Self {
-        Self {
+        let (custom_commands, command_warnings) =
+            crate::commands::app_commands::load_custom_commands(Path::new(crate::commands::app_commands::CUSTOM_COMMANDS_DIR));
+        let mut app = Self {
             should_quit: false,
             chat_history: ChatHistory::new(100),
             input_text: String::new(),
@@ -211,6 +249,8 @@ impl App {
             modification_confirmation_pending: false,
             modification_selected_index: 0,
             modification_choice: ModificationChoice::Confirm,
+            pending_clear: None,
+            markdown_cache: Mutex::new(HashMap::new()),
             chat_scroll_offset: 0,
             scrollbar_state: ScrollbarState::default(),
             action_queue: ActionQueue::new(),
@@ -229,7 +269,22 @@ impl App {
             vibe_command_handler: VibeCommandHandler::new(),
             filename_suggestion: FilenameSuggestion::new(),
             ai_agent: None,
+            pinned_context: PinnedContext::new(),
+            pending_copy_chord: false,
+            review_session: None,
+            context_panel: crate::ui::context_panel::ContextPanel::new(),
+            context_exclusions: Vec::new(),
+            project_overview: crate::tools::ProjectOverviewTool::new(),
+            file_summarizer: crate::tools::SummarizeFileTool::new(),
+            custom_commands,
+        };
+        for warning in command_warnings {
+            app.chat_history.add_message(Message {
+                role: Role::System,
+                content: format!("⚠️ {}", warning),
+            });
         }
+        app
     }
 
     pub fn init_ai_client_with_config(&mut self, config: LLMConfig) {
@@ -239,7 +294,22 @@ impl App {
 
     fn update_llm_client(&mut self) {
         if let Some(config) = &self.llm_config {
-            let client = Arc::new(LLMClient::new(config.clone()));
+            let client = match LLMClient::try_new(config.clone()) {
+                Ok(client) => Arc::new(client),
+                Err(e) => {
+                    self.chat_history.add_message(Message {
+                        role: Role::System,
+                        content: format!("❌ {}", e),
+                    });
+                    return;
+                }
+            };
+            for warning in config.base_url_warnings() {
+                self.chat_history.add_message(Message {
+                    role: Role::System,
+                    content: format!("⚠️ {}", warning),
+                });
+            }
             self.llm_client = Some(client.clone());
             // 重新创建 conversation_engine 并设置 llm_client
             self.conversation_engine = ConversationEngine::new()
@@ -254,7 +324,9 @@ impl App {
                 max_tool_rounds: 50,
                 model: config.model.clone(),
                 enable_search: false,
+                utility_model: std::env::var("LLM_UTILITY_MODEL").ok(),
             };
+            self.file_summarizer = crate::tools::SummarizeFileTool::with_llm(client.clone(), agent_config.utility_model.clone());
             let ai_agent = crate::core::AIAgent::new(client, agent_config);
 
             // 注册标准工具
@@ -304,7 +376,9 @@ impl App {
             self.scroll_to_bottom();
 
             let client = self.llm_client.as_ref().unwrap().clone();
-            let input_clone = input.clone();
+            let (input_clone, files) = self.process_mentions(&input);
+            let pinned_message = self.build_pinned_context_message(&files.claimed_by_other_than(FileSource::Pinned));
+            self.report_context_duplicates(&files);
 
             tokio::spawn(async move {
                 let handler_clone = handler.clone();
@@ -313,10 +387,14 @@ impl App {
                     true
                 };
 
-                let messages = vec![ChatMessage {
+                let mut messages = Vec::new();
+                if let Some(pinned) = pinned_message {
+                    messages.push(pinned);
+                }
+                messages.push(ChatMessage {
                     role: "user".to_string(),
                     content: input_clone,
-                }];
+                });
 
                 match client.generate_completion_stream(messages, None, callback).await {
                     Ok(_) => {
@@ -329,7 +407,11 @@ impl App {
             });
         } else {
             // 如果 LLM client 未初始化，使用备用方案（Gemini）
-            let processed_input = self.process_mentions(&input);
+            let (mut processed_input, files) = self.process_mentions(&input);
+            self.report_context_duplicates(&files);
+            if let Some(pinned) = self.build_pinned_context_message(&files.claimed_by_other_than(FileSource::Pinned)) {
+                processed_input = format!("{}\n\n{}", pinned.content, processed_input);
+            }
             match self.gemini.chat(processed_input.clone()).await {
                 Ok(response) => {
                     self.chat_history.add_message(Message {
@@ -350,10 +432,148 @@ impl App {
         }
     }
 
+    /// 从磁盘刷新固定文件，构建注入到请求里的系统消息；`exclude` 中的文件已由本轮提及注入
+    fn build_pinned_context_message(&mut self, exclude: &[std::path::PathBuf]) -> Option<ChatMessage> {
+        if self.pinned_context.is_empty() {
+            return None;
+        }
+
+        let render = self.pinned_context.render_excluding(exclude);
+        for (path, status) in &render.statuses {
+            let note = match status {
+                PinnedFileStatus::Truncated { tokens } => {
+                    format!("⚠️ 固定文件 {} 超出预算，已截断到 {} tokens", path.display(), tokens)
+                }
+                PinnedFileStatus::Skipped => {
+                    format!("⚠️ 固定上下文预算已用尽，本轮跳过 {}", path.display())
+                }
+                PinnedFileStatus::Unreadable(err) => {
+                    format!("⚠️ 无法读取固定文件 {}: {}", path.display(), err)
+                }
+                PinnedFileStatus::Included { .. } => continue,
+            };
+            self.chat_history.add_message(Message {
+                role: Role::System,
+                content: note,
+            });
+        }
+
+        if render.text.is_empty() {
+            return None;
+        }
+        Some(ChatMessage {
+            role: "system".to_string(),
+            content: format!("The user pinned these reference files for this session:{}", render.text),
+        })
+    }
+
+    /// /doctor：展示当前配置、base_url 提示，并探测端点
+    pub async fn run_doctor(&self) -> String {
+        let Some(config) = &self.llm_config else {
+            return "❌ 尚未配置 AI 提供商，使用 /help 查看配置命令".to_string();
+        };
+
+        let mut lines = vec![config.get_status_info()];
+        match config.chat_endpoint() {
+            Ok(endpoint) => lines.push(format!("请求端点: {}", endpoint)),
+            Err(e) => {
+                lines.push(format!("❌ {}", e));
+                return lines.join("\n");
+            }
+        }
+        for warning in config.base_url_warnings() {
+            lines.push(format!("⚠️ {}", warning));
+        }
+        if let Some(client) = &self.llm_client {
+            match client.check_endpoint().await {
+                Ok(message) => lines.push(format!("✅ {}", message)),
+                Err(message) => lines.push(format!("❌ {}", message)),
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// /retry-config：查看或修改重试配置；修改后保存到 .env 并重建客户端，所有重试点立即生效
+    pub fn handle_retry_config(&mut self, args: &[String]) -> String {
+        let Some(config) = self.llm_config.as_mut() else {
+            return "❌ 尚未配置 AI 提供商，使用 /help 查看配置命令".to_string();
+        };
+        match args {
+            [] => config.retry.summary(),
+            [set, key, value] if set == "set" => {
+                if let Err(e) = config.retry.set(key, value) {
+                    return format!("❌ {}", e);
+                }
+                let summary = config.retry.summary();
+                let saved = config.save_to_env();
+                self.update_llm_client();
+                match saved {
+                    Ok(()) => format!("✓ {}（已保存到 .env）", summary),
+                    Err(e) => format!("✓ {}（保存 .env 失败: {}）", summary, e),
+                }
+            }
+            _ => "Usage: /retry-config [set <attempts|delay|backoff> <value>]".to_string(),
+        }
+    }
+
+    pub fn handle_pin_command(&mut self, command_type: &CommandType, args: &[String]) -> String {
+        match command_type {
+            CommandType::Pin => {
+                if args.is_empty() {
+                    return "Usage: /pin <file>".to_string();
+                }
+                match self.pinned_context.pin(&args.join(" ")) {
+                    Ok(path) => format!("📌 已固定 {}（每轮请求都会重新读取）", path.display()),
+                    Err(e) => format!("❌ {}", e),
+                }
+            }
+            CommandType::Unpin => {
+                let target = args.join(" ");
+                if target.is_empty() {
+                    "Usage: /unpin <file|all>".to_string()
+                } else if target == "all" {
+                    self.pinned_context.clear();
+                    "✓ 已清除所有固定文件".to_string()
+                } else if self.pinned_context.unpin(&target) {
+                    format!("✓ 已取消固定 {}", target)
+                } else {
+                    format!("❌ 未固定: {}", target)
+                }
+            }
+            _ => {
+                if self.pinned_context.is_empty() {
+                    return "没有固定的文件。使用 /pin <file> 添加".to_string();
+                }
+                let render = self.pinned_context.render();
+                let mut lines = vec![format!(
+                    "📌 固定的文件（{} / {} tokens）:",
+                    render.total_tokens,
+                    self.pinned_context.token_budget()
+                )];
+                for (path, status) in &render.statuses {
+                    let detail = match status {
+                        PinnedFileStatus::Included { tokens } => format!("{} tokens", tokens),
+                        PinnedFileStatus::Truncated { tokens } => format!("{} tokens，已截断", tokens),
+                        PinnedFileStatus::Skipped => "超出预算，跳过".to_string(),
+                        PinnedFileStatus::Unreadable(err) => format!("无法读取: {}", err),
+                    };
+                    lines.push(format!("  • {} ({})", path.display(), detail));
+                }
+                lines.join("\n")
+            }
+        }
+    }
+
     /// 处理消息中的 @ 提及，读取文件内容并注入
-    fn process_mentions(&self, input: &str) -> String {
+    /// 展开 `@file` / `@dir` 提及，与固定文件一起按规范化路径去重
+    ///
+    /// 返回处理后的消息和本轮的文件集合，固定文件由 `build_pinned_context_message` 注入
+    fn process_mentions(&self, input: &str) -> (String, ContextFileSet) {
         let mut result = input.to_string();
-        let mut file_contents = String::new();
+        let mut files = ContextFileSet::new();
+        for path in self.pinned_context.files() {
+            files.add(path, FileSource::Pinned);
+        }
 
         // 查找所有 @path 模式
         let mut i = 0;
@@ -371,33 +591,254 @@ impl App {
                     i += 1;
                 }
                 
-                // 尝试读取文件
-                if !path.is_empty() {
-                    match std::fs::read_to_string(&path) {
-                        Ok(content) => {
-                            file_contents.push_str(&format!(
-                                "\n\n<file_content path=\"{}\">\n{}\n</file_content>\n",
-                                path, content
-                            ));
-                            // 从结果中移除 @path
-                            result = result.replace(&format!("@{}", path), "");
-                        }
-                        Err(_) => {
-                            // 文件不存在，保留 @path 在消息中
+                let mention = std::path::Path::new(&path);
+                if !path.is_empty() && mention.is_file() {
+                    files.add(mention, FileSource::Mention);
+                    // 从结果中移除 @path
+                    result = result.replace(&format!("@{}", path), "");
+                } else if !path.is_empty() && mention.is_dir() {
+                    // 目录提及：展开其中的直接文件
+                    if let Ok(entries) = std::fs::read_dir(mention) {
+                        let mut children: Vec<_> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+                        children.sort();
+                        for child in children {
+                            let canonical = std::fs::canonicalize(&child).unwrap_or_else(|_| child.clone());
+                            if !self.context_exclusions.contains(&canonical) {
+                                files.add(&child, FileSource::Directory);
+                            }
                         }
+                        result = result.replace(&format!("@{}", path), "");
                     }
                 }
+                // 文件不存在，保留 @path 在消息中
             } else {
                 i += 1;
             }
         }
 
+        let mut file_contents = String::new();
+        for file in files.files_from(FileSource::Mention) {
+            if let Ok(content) = std::fs::read_to_string(&file.path) {
+                file_contents.push_str(&format!(
+                    "\n\n<file_content path=\"{}\">\n{}\n</file_content>\n",
+                    file.path.display(), content
+                ));
+            }
+        }
+        for file in files.files_from(FileSource::Directory) {
+            // 二进制等无法读取为文本的文件直接跳过
+            if let Ok(content) = std::fs::read_to_string(&file.path) {
+                file_contents.push_str(&format!(
+                    "\n\n<file_content path=\"{}\" source=\"directory\">\n{}\n</file_content>\n",
+                    file.path.display(), content
+                ));
+            }
+        }
+
         // 清理消息（移除多余空格）并添加文件内容
         let cleaned = result.trim().to_string();
-        if file_contents.is_empty() {
+        let processed = if file_contents.is_empty() {
             cleaned
         } else {
             format!("{}{}", cleaned, file_contents)
+        };
+        (processed, files)
+    }
+
+    /// /overview [max_tokens]：当前目录的项目概览
+    pub fn handle_overview(&mut self, args: &[String]) -> String {
+        let max_tokens = match args.first() {
+            Some(arg) => match arg.parse() {
+                Ok(n) => n,
+                Err(_) => return "Usage: /overview [max_tokens]".to_string(),
+            },
+            None => crate::tools::project_tools::DEFAULT_OVERVIEW_TOKENS,
+        };
+        let (overview, cached) = self.project_overview.overview(".", max_tokens);
+        if cached {
+            format!("{}\n(缓存，文件索引未变化)", overview)
+        } else {
+            overview
+        }
+    }
+
+    /// /summarize <file>：文件的结构摘要，配置了模型时附带自然语言说明
+    pub async fn handle_summarize(&mut self, args: &[String]) -> String {
+        let Some(path) = args.first() else {
+            return "Usage: /summarize <file>".to_string();
+        };
+        let summary = match self.file_summarizer.summarize(path, true).await {
+            Ok(summary) => summary,
+            Err(e) => return format!("❌ {}", e),
+        };
+        let mut text = summary["summary"].as_str().unwrap_or(path).to_string();
+        if let Some(description) = summary["description"].as_str() {
+            text.push_str(&format!("\n\n{}", description));
+        }
+        if let Some(error) = summary["description_error"].as_str() {
+            text.push_str(&format!("\n\n(自然语言摘要失败: {})", error));
+        }
+        if summary["cached"].as_bool() == Some(true) {
+            text.push_str("\n(缓存，文件未修改)");
+        }
+        text
+    }
+
+    /// /scaffold：把项目类型的模板文件作为批量创建交给修改确认，已存在的文件跳过
+    pub fn scaffold_project(&mut self, project_type: Option<&str>, root: &Path) -> String {
+        use crate::tools::scaffold_tool;
+
+        let Some(project_type) = project_type.or_else(|| scaffold_tool::detect_project_type(root)) else {
+            return format!(
+                "⚠️ 无法推断项目类型，请指定: /scaffold <{}>",
+                scaffold_tool::SCAFFOLD_TYPES.join("|")
+            );
+        };
+        let files = match scaffold_tool::scaffold_files(project_type, scaffold_tool::templates_dir().as_deref()) {
+            Ok(files) => files,
+            Err(e) => return format!("❌ {}", e),
+        };
+
+        let (existing, new): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| root.join(&file.path).exists());
+        let mut message = if new.is_empty() {
+            format!("✅ {} 脚手架的文件都已存在", project_type)
+        } else {
+            format!("📦 {} 脚手架: {} 个文件待确认", project_type, new.len())
+        };
+        if !existing.is_empty() {
+            let paths: Vec<&str> = existing.iter().map(|file| file.path.as_str()).collect();
+            message.push_str(&format!("（已存在，跳过: {}）", paths.join(", ")));
+        }
+
+        let ops = new
+            .into_iter()
+            .map(|file| CodeModificationOp::Create {
+                path: root.join(&file.path).to_string_lossy().to_string(),
+                content: file.content,
+            })
+            .collect();
+        self.queue_modifications(ops);
+        message
+    }
+
+    /// /clear：先显示将删除多少内容并等待确认，`--yes` 跳过确认直接清空
+    pub fn request_clear(&mut self, args: &[String]) -> String {
+        let messages = self.chat_history.get_messages().len();
+        if messages == 0 {
+            return "聊天历史已经是空的".to_string();
+        }
+        if args.iter().any(|arg| arg == "--yes" || arg == "-y") {
+            self.chat_history.clear();
+            return "✓ Chat history cleared".to_string();
+        }
+        let calculator = crate::core::TokenCalculator::from_model_name("gpt-4");
+        let tokens = self
+            .chat_history
+            .get_messages()
+            .iter()
+            .map(|message| calculator.count_tokens(&message.content))
+            .sum();
+        self.pending_clear = Some(ClearPreview { messages, tokens });
+        format!(
+            "⚠️ /clear 将删除 {} 条消息（约 {} tokens）。按 1 或 Enter 确认，2、N 或 Esc 取消（/clear --yes 跳过确认）",
+            messages, tokens
+        )
+    }
+
+    /// 确认 /clear
+    pub fn confirm_clear(&mut self) {
+        if self.pending_clear.take().is_some() {
+            self.chat_history.clear();
+            self.chat_history.add_message(Message {
+                role: Role::System,
+                content: "✓ Chat history cleared".to_string(),
+            });
+            self.scroll_to_bottom();
+        }
+    }
+
+    /// 取消 /clear，历史保持不变
+    pub fn cancel_clear(&mut self) {
+        if self.pending_clear.take().is_some() {
+            self.chat_history.add_message(Message {
+                role: Role::System,
+                content: "已取消 /clear，聊天历史保留".to_string(),
+            });
+            self.scroll_to_bottom();
+        }
+    }
+
+    /// 打开上下文文件面板
+    pub fn open_context_panel(&mut self) {
+        self.context_panel.open = true;
+        self.refresh_context_panel();
+    }
+
+    /// 按当前输入和固定文件重新计算下一次请求会注入的文件
+    pub fn refresh_context_panel(&mut self) {
+        let (_, files) = self.process_mentions(&self.input_text);
+        let pinned = self
+            .pinned_context
+            .render_excluding(&files.claimed_by_other_than(FileSource::Pinned));
+        let calculator = crate::core::TokenCalculator::from_model_name("gpt-4");
+
+        let entries = files
+            .iter()
+            .map(|file| {
+                let content = std::fs::read_to_string(&file.path).unwrap_or_default();
+                // 固定文件按预算截断后的实际注入量计算
+                let tokens = match pinned.statuses.iter().find(|(path, _)| *path == file.path) {
+                    Some((_, PinnedFileStatus::Included { tokens } | PinnedFileStatus::Truncated { tokens })) => *tokens,
+                    Some(_) => 0,
+                    None => calculator.count_tokens(&content),
+                };
+                crate::ui::context_panel::ContextPanelEntry {
+                    path: file.path.clone(),
+                    source: file.source,
+                    lines: content.lines().count(),
+                    tokens,
+                }
+            })
+            .collect();
+        self.context_panel.set_entries(entries);
+        self.context_panel.excluded = self.context_exclusions.len();
+    }
+
+    /// 从上下文中移除面板里选中的文件：固定文件取消固定，@file 从输入中删掉，目录里的文件记为排除
+    pub fn remove_selected_context_file(&mut self) {
+        let Some(entry) = self.context_panel.selected_entry().cloned() else {
+            return;
+        };
+        match entry.source {
+            FileSource::Pinned => {
+                self.pinned_context.unpin(&entry.path.to_string_lossy());
+            }
+            FileSource::Mention => {
+                let mention = format!("@{}", entry.path.display());
+                self.input_text = self
+                    .input_text
+                    .replace(&mention, "")
+                    .split(' ')
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                self.input_cursor = self.input_text.chars().count();
+            }
+            FileSource::Directory => {
+                let canonical = std::fs::canonicalize(&entry.path).unwrap_or(entry.path);
+                self.context_exclusions.push(canonical);
+            }
+        }
+        self.refresh_context_panel();
+    }
+
+    /// 提示本轮去掉了多少重复文件
+    fn report_context_duplicates(&mut self, files: &ContextFileSet) {
+        if files.duplicates() > 0 {
+            self.chat_history.add_message(Message {
+                role: Role::System,
+                content: format!("ℹ️ 上下文去重：{} 个重复文件只注入一次", files.duplicates()),
+            });
         }
     }
 
@@ -454,28 +895,187 @@ impl App {
             return;
         }
 
-        // 再次尝试解析为普通命令
-        if let Some(cmd) = CommandParser::parse(input) {
-            let response = match cmd.command_type {
-                CommandType::Help => CommandParser::get_help_text(),
-                CommandType::Clear => {
-                    self.chat_history.clear();
-                    "✓ Chat history cleared".to_string()
+        // 再次交给斜杠命令注册表
+        let registry = crate::commands::app_commands::registry(&self.custom_commands);
+        let outcome = match registry.resolve(input) {
+            Some(Ok((command, args))) => command.execute(self, &args).await,
+            Some(Err(e)) => CommandOutcome::Reply(e.to_string()),
+            None => return,
+        };
+        match outcome {
+            CommandOutcome::Reply(response) => {
+                self.chat_history.add_message(Message {
+                    role: Role::System,
+                    content: response,
+                });
+                self.scroll_to_bottom();
+            }
+            // 主界面只加载 .md 命令，每个命令只有一条提示
+            CommandOutcome::Prompts(prompts) => match prompts.first() {
+                Some(prompt) if self.llm_client.is_some() => self.start_streaming_chat(prompt).await,
+                Some(_) => {
+                    self.chat_history.add_message(Message {
+                        role: Role::System,
+                        content: "❌ 尚未配置 AI 提供商，使用 /help 查看配置命令".to_string(),
+                    });
                 }
-                // NOTE: Other command handlers would go here
-                _ => format!("Unknown command: {}", input),
-            };
+                None => {
+                    self.chat_history.add_message(Message {
+                        role: Role::System,
+                        content: format!("{} 展开后是空提示，没有发送", input.trim()),
+                    });
+                }
+            },
+            CommandOutcome::Done => {}
+            CommandOutcome::Exit => self.should_quit = true,
+        }
+    }
+
+    /// 按输入框内容刷新命令提示：命令名或命令参数的补全
+    pub fn update_command_hints(&mut self) {
+        let hints = crate::commands::app_commands::registry(&self.custom_commands).hints(&self.input_text);
+        self.command_hints.update_input(&self.input_text, hints);
+    }
 
+    /// /review：带行号发送文件，要求模型返回结构化建议
+    pub async fn start_review(&mut self, path: &str) -> Result<(), String> {
+        if path.is_empty() {
+            return Err("Usage: /review <file>".to_string());
+        }
+        if self.llm_client.is_none() {
+            return Err("❌ 尚未配置 AI 提供商，无法审查".to_string());
+        }
+        let content = std::fs::read_to_string(path).map_err(|e| format!("❌ 无法读取 {}: {}", path, e))?;
+        let numbered: Vec<String> = content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>5} | {}", i + 1, line))
+            .collect();
+
+        let system_prompt = format!(
+            "{}\n\n{}",
+            prompts::get_code_review_prompt(self.chat_history.get_messages().len()),
+            prompts::CodeReviewPrompts::structured_output_format()
+        );
+        let prompt = format!("Review `{}`:\n\n```\n{}\n```", path, numbered.join("\n"));
+        self.start_streaming_chat_with_system(system_prompt, &prompt).await;
+        Ok(())
+    }
+
+    /// 展示当前审查建议
+    pub fn show_current_review_suggestion(&mut self) {
+        let Some(session) = &self.review_session else {
+            return;
+        };
+        let Some((suggestion, status)) = session.current() else {
+            return;
+        };
+        let status_note = match status {
+            SuggestionStatus::Pending => String::new(),
+            SuggestionStatus::Accepted => "（已应用）".to_string(),
+            SuggestionStatus::Skipped => "（已跳过）".to_string(),
+            SuggestionStatus::Failed(e) => format!("（失败: {}）", e),
+        };
+        let content = format!(
+            "🔍 审查建议 {}/{}{}: {}:{}-{}\n理由: {}\n{}\n[a/Enter] 应用  [s/→] 跳过  [←] 上一条  [Esc] 结束",
+            session.position() + 1,
+            session.len(),
+            status_note,
+            suggestion.file,
+            suggestion.start_line,
+            suggestion.end_line,
+            if suggestion.rationale.is_empty() { "-" } else { &suggestion.rationale },
+            format_diff(&suggestion.original, &suggestion.replacement)
+        );
+        self.chat_history.add_message(Message {
+            role: Role::System,
+            content,
+        });
+        self.scroll_to_bottom();
+    }
+
+    /// 应用当前建议并前进
+    pub fn accept_review_suggestion(&mut self) {
+        let Some(session) = self.review_session.as_mut() else {
+            return;
+        };
+        let op = match session.current() {
+            Some((_, SuggestionStatus::Accepted)) => {
+                self.advance_review();
+                return;
+            }
+            Some((suggestion, _)) => suggestion.to_modification(),
+            None => return,
+        };
+
+        let message = match ModificationBatch::apply(&[op]) {
+            Ok(messages) => {
+                session.mark_current(SuggestionStatus::Accepted);
+                messages.join("\n")
+            }
+            Err(e) => {
+                session.mark_current(SuggestionStatus::Failed(e.reason.clone()));
+                format!("❌ 建议未应用: {}", e.reason)
+            }
+        };
+        self.chat_history.add_message(Message {
+            role: Role::System,
+            content: message,
+        });
+        self.advance_review();
+    }
+
+    /// 跳过当前建议
+    pub fn skip_review_suggestion(&mut self) {
+        if let Some(session) = self.review_session.as_mut() {
+            if session.current().is_some_and(|(_, status)| *status == SuggestionStatus::Pending) {
+                session.mark_current(SuggestionStatus::Skipped);
+            }
+        }
+        self.advance_review();
+    }
+
+    pub fn previous_review_suggestion(&mut self) {
+        if self.review_session.as_mut().is_some_and(|session| session.previous()) {
+            self.show_current_review_suggestion();
+        }
+    }
+
+    /// 结束审查并输出统计
+    pub fn end_review(&mut self) {
+        if let Some(session) = self.review_session.take() {
             self.chat_history.add_message(Message {
                 role: Role::System,
-                content: response,
+                content: format!("✅ {}", session.summary()),
             });
             self.scroll_to_bottom();
         }
     }
 
+    fn advance_review(&mut self) {
+        if self.review_session.as_mut().is_some_and(|session| session.next()) {
+            self.show_current_review_suggestion();
+        } else {
+            self.end_review();
+        }
+    }
+
     /// 处理 AI 响应中的代码修改指令
     pub fn process_ai_response_for_modifications(&mut self, response: &str) {
+        // 结构化审查建议走逐条确认流程
+        let (suggestions, errors) = review::parse_review_suggestions(response);
+        for error in errors {
+            self.chat_history.add_message(Message {
+                role: Role::System,
+                content: format!("⚠️ {}", error),
+            });
+        }
+        if !suggestions.is_empty() {
+            self.review_session = Some(ReviewSession::new(suggestions));
+            self.show_current_review_suggestion();
+            return;
+        }
+
         // 首先检测明确的修改指令
         let mut ops = AICodeModificationDetector::detect_modifications(response);
 
@@ -522,7 +1122,11 @@ impl App {
             return; // 不进入修改确认流程，让用户通过对话框选择
         }
 
-        // 为每个修改操作生成 Diff
+        self.queue_modifications(ops);
+    }
+
+    /// 为每个修改操作生成 Diff，放入待确认列表并激活确认对话
+    pub fn queue_modifications(&mut self, ops: Vec<CodeModificationOp>) {
         for op in ops {
             let diff = match &op {
                 CodeModificationOp::Create { path, content } => {
@@ -581,6 +1185,11 @@ impl App {
     }
 
     pub async fn start_streaming_chat(&mut self, prompt: &str) {
+        let system_prompt = self.generate_system_prompt();
+        self.start_streaming_chat_with_system(system_prompt, prompt).await;
+    }
+
+    async fn start_streaming_chat_with_system(&mut self, system_prompt: String, prompt: &str) {
         if self.llm_client.is_none() {
             return;
         }
@@ -598,7 +1207,6 @@ impl App {
 
         let client = self.llm_client.as_ref().unwrap().clone();
         let prompt = prompt.to_string();
-        let system_prompt = self.generate_system_prompt();
 
         tokio::spawn(async move {
             let handler_clone = handler.clone();
@@ -670,4 +1278,13 @@ impl App {
     pub fn scroll_to_bottom(&mut self) {
         self.chat_scroll_offset = 0;
     }
+
+    /// 终端尺寸变化：按新宽度重新计算折行后的滚动范围，把越界的滚动位置收回来；
+    /// 旧的选区坐标也随之失效
+    pub fn handle_resize(&mut self, width: u16, height: u16) {
+        let max_scroll = crate::ui::pixel_layout_v2::chat_max_scroll(self, ratatui::layout::Rect::new(0, 0, width, height));
+        self.chat_scroll_offset = self.chat_scroll_offset.min(max_scroll);
+        self.selection_start = None;
+        self.selection_end = None;
+    }
 }
\ No newline at end of file
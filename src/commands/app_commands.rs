@@ -0,0 +1,256 @@
+//! 主界面的斜杠命令，注册到与 grok-cli 共用的 [`CommandRegistry`]，命令提示和执行都由它驱动。
+//! `/vibc` 和文件命令有各自的解析器，在注册表之前处理。
+
+use command_registry::{
+    load_markdown_commands, CommandArgs, CommandFuture, CommandOutcome, CommandRegistry, PromptCommand, SlashCommand,
+};
+use crate::ai::commands::{CommandParser, CommandType};
+use crate::app::App;
+use std::future::ready;
+use std::path::Path;
+
+/// 项目里自定义提示命令所在的目录，每个 `.md` 文件一个命令
+pub const CUSTOM_COMMANDS_DIR: &str = ".grok/commands";
+
+type Run = for<'a> fn(&'a mut App, &'a CommandArgs) -> CommandFuture<'a>;
+
+/// 内置命令：帮助信息、第一个参数可补全的值和处理函数
+struct AppCommand {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    usage: &'static str,
+    description: &'static str,
+    completions: &'static [&'static str],
+    run: Run,
+}
+
+impl SlashCommand<App> for AppCommand {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn aliases(&self) -> &[&'static str] {
+        self.aliases
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn usage(&self) -> &str {
+        self.usage
+    }
+
+    fn complete(&self, args: &str) -> Vec<String> {
+        if args.contains(char::is_whitespace) {
+            return Vec::new();
+        }
+        self.completions.iter().filter(|value| value.starts_with(args)).map(|value| value.to_string()).collect()
+    }
+
+    fn execute<'a>(&'a self, ctx: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+        (self.run)(ctx, args)
+    }
+}
+
+fn builtins() -> Vec<AppCommand> {
+    vec![
+        AppCommand { name: "help", aliases: &["h"], usage: "", description: "Show help", completions: &[], run: help },
+        AppCommand {
+            name: "clear",
+            aliases: &["c"],
+            usage: "[--yes]",
+            description: "Clear chat history (--yes skips the confirmation)",
+            completions: &["--yes"],
+            run: clear,
+        },
+        AppCommand { name: "pin", aliases: &[], usage: "<file>", description: "Pin a file into every request", completions: &[], run: pin },
+        AppCommand { name: "unpin", aliases: &[], usage: "<file|all>", description: "Unpin a file", completions: &["all"], run: unpin },
+        AppCommand { name: "pins", aliases: &[], usage: "", description: "List pinned files", completions: &[], run: pins },
+        AppCommand {
+            name: "context",
+            aliases: &["ctx"],
+            usage: "",
+            description: "Show the injected files and their tokens",
+            completions: &[],
+            run: context,
+        },
+        AppCommand {
+            name: "overview",
+            aliases: &[],
+            usage: "[max_tokens]",
+            description: "Summarize the project structure",
+            completions: &[],
+            run: overview,
+        },
+        AppCommand {
+            name: "summarize",
+            aliases: &[],
+            usage: "<file>",
+            description: "Summarize a file without reading it in full",
+            completions: &[],
+            run: summarize,
+        },
+        AppCommand {
+            name: "scaffold",
+            aliases: &[],
+            usage: "[rust|node|python|go]",
+            description: "Generate .gitignore, CI and other project files",
+            completions: &["rust", "node", "python", "go"],
+            run: scaffold,
+        },
+        AppCommand {
+            name: "review",
+            aliases: &[],
+            usage: "<file>",
+            description: "Review a file and go through the suggestions",
+            completions: &[],
+            run: review,
+        },
+        AppCommand {
+            name: "doctor",
+            aliases: &[],
+            usage: "",
+            description: "Check the configuration and probe the API endpoint",
+            completions: &[],
+            run: doctor,
+        },
+        AppCommand {
+            name: "retry-config",
+            aliases: &[],
+            usage: "[set <key> <value>]",
+            description: "Show or change the retry settings",
+            completions: &["set"],
+            run: retry_config,
+        },
+        AppCommand { name: "exit", aliases: &["quit"], usage: "", description: "Exit", completions: &[], run: exit },
+    ]
+}
+
+/// 内置命令，然后是 `custom` 里的提示命令；与内置命令重名的在 [`load_custom_commands`] 里就已去掉
+pub fn registry(custom: &[PromptCommand]) -> CommandRegistry<App> {
+    let mut registry = CommandRegistry::new();
+    for command in builtins() {
+        let _ = registry.register(command);
+    }
+    for command in custom {
+        let _ = registry.register(command.clone());
+    }
+    registry
+}
+
+/// 读取 `dir` 下的 `.md` 提示命令，返回可用的命令和警告；与内置命令或别名重名的不用
+pub fn load_custom_commands(dir: &Path) -> (Vec<PromptCommand>, Vec<String>) {
+    let (loaded, mut warnings) = load_markdown_commands(dir);
+    let mut registry = registry(&[]);
+    let mut commands = Vec::new();
+    for command in loaded {
+        match registry.register(command.clone()) {
+            Ok(()) => commands.push(command),
+            Err(reason) => warnings.push(format!("Ignoring {}/{}.md: {}", dir.display(), command.name(), reason)),
+        }
+    }
+    (commands, warnings)
+}
+
+fn reply<'a>(text: String) -> CommandFuture<'a> {
+    Box::pin(ready(CommandOutcome::Reply(text)))
+}
+
+fn help<'a>(app: &'a mut App, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    let mut help = CommandParser::get_help_text();
+    let lines = registry(&app.custom_commands).help_lines();
+    let custom = &lines[builtins().len()..];
+    if !custom.is_empty() {
+        help.push_str(&format!("\n自定义命令（{}）：\n{}", CUSTOM_COMMANDS_DIR, custom.join("\n")));
+    }
+    reply(help)
+}
+
+fn clear<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.request_clear(args.words()))
+}
+
+fn pin<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.handle_pin_command(&CommandType::Pin, args.words()))
+}
+
+fn unpin<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.handle_pin_command(&CommandType::Unpin, args.words()))
+}
+
+fn pins<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.handle_pin_command(&CommandType::Pins, args.words()))
+}
+
+fn context<'a>(app: &'a mut App, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    app.open_context_panel();
+    Box::pin(ready(CommandOutcome::Done))
+}
+
+fn overview<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.handle_overview(args.words()))
+}
+
+fn summarize<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move { CommandOutcome::Reply(app.handle_summarize(args.words()).await) })
+}
+
+fn scaffold<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.scaffold_project(args.words().first().map(String::as_str), Path::new(".")))
+}
+
+fn review<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move {
+        match app.start_review(&args.words().join(" ")).await {
+            Ok(()) => CommandOutcome::Done,
+            Err(e) => CommandOutcome::Reply(e),
+        }
+    })
+}
+
+fn doctor<'a>(app: &'a mut App, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(async move { CommandOutcome::Reply(app.run_doctor().await) })
+}
+
+fn retry_config<'a>(app: &'a mut App, args: &'a CommandArgs) -> CommandFuture<'a> {
+    reply(app.handle_retry_config(args.words()))
+}
+
+fn exit<'a>(_app: &'a mut App, _args: &'a CommandArgs) -> CommandFuture<'a> {
+    Box::pin(ready(CommandOutcome::Exit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_custom_commands_skip_builtin_names_and_run_as_prompts() {
+        let dir = std::env::temp_dir().join(format!("app-commands-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("changelog.md"), "# Draft a changelog entry\nWrite a changelog entry for {args}.").unwrap();
+        std::fs::write(dir.join("ctx.md"), "shadows the /context alias").unwrap();
+
+        let (custom, warnings) = load_custom_commands(&dir);
+        assert_eq!(custom.len(), 1);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("/ctx is already a command"));
+
+        let registry = registry(&custom);
+        assert_eq!(registry.hints("/ch"), vec!["/changelog - Draft a changelog entry"]);
+        assert_eq!(registry.hints("/scaffold r"), vec!["/scaffold rust"]);
+
+        let mut app = App::new();
+        let (command, args) = registry.resolve("/changelog v2").unwrap().unwrap();
+        assert_eq!(
+            command.execute(&mut app, &args).await,
+            CommandOutcome::Prompts(vec!["# Draft a changelog entry\nWrite a changelog entry for v2.".to_string()])
+        );
+        let (command, args) = registry.resolve("/quit").unwrap().unwrap();
+        assert_eq!(command.execute(&mut app, &args).await, CommandOutcome::Exit);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
 /// 文件操作命令处理
+use crate::tools::code_tools::{find_symbol, FIND_SYMBOL_MAX_RESULTS};
+use crate::tools::file_tools::read_symbol;
 use crate::utils::code_file_handler::CodeFileHandler;
 
 #[derive(Debug, Clone)]
@@ -15,12 +17,14 @@ pub enum FileCommand {
     CancelModify,
     /// 删除文件: /delete-file <path>
     DeleteFile { path: String },
-    /// 读取文件: /read-file <path>
-    ReadFile { path: String },
+    /// 读取文件: /read-file <path> [symbol]，给出 symbol 时只显示该定义
+    ReadFile { path: String, symbol: Option<String> },
     /// 列出目录: /list-dir <path>
     ListDir { path: String },
     /// 搜索文件: /search-files <dir> <pattern>
     SearchFiles { directory: String, pattern: String },
+    /// 查找符号定义: /find-symbol <name> [dir]
+    FindSymbol { name: String, directory: String },
 }
 
 #[derive(Debug, Clone)]
@@ -158,7 +162,8 @@ impl FileCommandHandler {
                     return None;
                 }
                 let path = parts[1].to_string();
-                Some(FileCommand::ReadFile { path })
+                let symbol = parts.get(2).map(|symbol| symbol.to_string());
+                Some(FileCommand::ReadFile { path, symbol })
             }
             "/list-dir" => {
                 if parts.len() < 2 {
@@ -175,6 +180,14 @@ impl FileCommandHandler {
                 let pattern = parts[2].to_string();
                 Some(FileCommand::SearchFiles { directory, pattern })
             }
+            "/find-symbol" => {
+                if parts.len() < 2 {
+                    return None;
+                }
+                let name = parts[1].to_string();
+                let directory = parts.get(2).unwrap_or(&".").to_string();
+                Some(FileCommand::FindSymbol { name, directory })
+            }
             _ => None,
         }
     }
@@ -360,9 +373,21 @@ impl FileCommandHandler {
                     }
                 }
             }
-            FileCommand::ReadFile { path } => {
+            FileCommand::ReadFile { path, symbol } => {
                 let result = self.file_handler.read_file(&path);
-                if result.success {
+                if let (true, Some(symbol)) = (result.success, symbol) {
+                    let found = read_symbol(&path, result.data.as_deref().unwrap_or_default(), &symbol);
+                    FileCommandResult {
+                        success: found.success,
+                        message: match &found.error {
+                            None => format!("✅ {} 中的 {}", path, symbol),
+                            Some(error) => format!("❌ {}", error),
+                        },
+                        content: found.data["content"].as_str().map(str::to_string),
+                        requires_confirmation: false,
+                        diff: None,
+                    }
+                } else if result.success {
                     FileCommandResult {
                         success: true,
                         message: format!("✅ 文件已读取: {}", path),
@@ -420,6 +445,25 @@ impl FileCommandHandler {
                     }
                 }
             }
+            FileCommand::FindSymbol { name, directory } => {
+                let matches = find_symbol(&name, &directory);
+                let lines: Vec<String> = matches
+                    .iter()
+                    .take(FIND_SYMBOL_MAX_RESULTS)
+                    .map(|m| format!("{}:{}  {} {}", m["file"].as_str().unwrap_or_default(), m["line"], m["kind"].as_str().unwrap_or_default(), m["signature"].as_str().unwrap_or_default()))
+                    .collect();
+                FileCommandResult {
+                    success: !lines.is_empty(),
+                    message: if lines.is_empty() {
+                        format!("❌ {} 下没有找到 {} 的定义", directory, name)
+                    } else {
+                        format!("✅ {} 的定义（{} 处）", name, matches.len())
+                    },
+                    content: (!lines.is_empty()).then(|| lines.join("\n")),
+                    requires_confirmation: false,
+                    diff: None,
+                }
+            }
         }
     }
 }
@@ -440,6 +484,45 @@ mod tests {
         assert!(cmd.is_some());
     }
 
+    #[test]
+    fn test_read_file_by_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "use std::io;\n\nfn target() {\n    1;\n}\n\nfn other() {}\n").unwrap();
+        let path = path.to_string_lossy();
+        let mut handler = FileCommandHandler::new();
+
+        let cmd = FileCommandHandler::parse_command(&format!("/read-file {} target", path)).unwrap();
+        let result = handler.execute(cmd);
+        assert!(result.success);
+        assert!(result.content.unwrap().contains("   3: fn target() {"));
+
+        let cmd = FileCommandHandler::parse_command(&format!("/read-file {} missing", path)).unwrap();
+        let result = handler.execute(cmd);
+        assert!(!result.success);
+        assert!(result.message.contains("Available symbols: target, other"), "{}", result.message);
+    }
+
+    #[test]
+    fn test_find_symbol_lists_definitions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn parse_config() {}\n\npub struct Config;\n").unwrap();
+        let mut handler = FileCommandHandler::new();
+
+        let cmd = FileCommandHandler::parse_command(&format!("/find-symbol Config {}", dir.path().display())).unwrap();
+        let result = handler.execute(cmd);
+        assert!(result.success);
+        let content = result.content.unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(lines[0].ends_with("lib.rs:3  struct pub struct Config;"), "{}", content);
+        // 精确匹配排在部分匹配前面
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains("lib.rs:1  fn "), "{}", content);
+
+        let cmd = FileCommandHandler::parse_command(&format!("/find-symbol Missing {}", dir.path().display())).unwrap();
+        assert!(!handler.execute(cmd).success);
+    }
+
     #[test]
     fn test_parse_delete_file() {
         let cmd = FileCommandHandler::parse_command("/delete-file test.txt");
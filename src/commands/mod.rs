@@ -1,4 +1,5 @@
 /// 命令处理系统
+pub mod app_commands;
 pub mod file_commands;
 pub mod vibe_commands;
 
@@ -219,7 +219,7 @@ mod tests {
 
     #[test]
     fn test_execute_list_stages() {
-        let handler = VibeCommandHandler::new();
+        let mut handler = VibeCommandHandler::new();
         let result = handler.execute(VibeCommand::ListStages);
 
         assert!(result.success);
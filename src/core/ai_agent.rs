@@ -14,6 +14,8 @@ pub struct AIAgentConfig {
     pub max_tool_rounds: usize,
     pub model: String,
     pub enable_search: bool,
+    /// 用于摘要等辅助任务的低成本模型（None 时使用主模型）
+    pub utility_model: Option<String>,
 }
 
 impl Default for AIAgentConfig {
@@ -22,6 +24,7 @@ impl Default for AIAgentConfig {
             max_tool_rounds: 50, // 默认最多 50 轮工具调用
             model: "grok-code-fast-1".to_string(),
             enable_search: false,
+            utility_model: std::env::var("LLM_UTILITY_MODEL").ok(),
         }
     }
 }
@@ -74,12 +77,19 @@ impl AIAgent {
         // 代码工具
         registry.register(Arc::new(CodeSearchTool));
         registry.register(Arc::new(FunctionFinderTool));
+        registry.register(Arc::new(FindSymbolTool));
         registry.register(Arc::new(CodeStructureTool));
+        registry.register(Arc::new(SummarizeFileTool::with_llm(
+            self.llm_client.clone(),
+            self.config.utility_model.clone(),
+        )));
 
         // 项目工具
         registry.register(Arc::new(ProjectStructureTool));
+        registry.register(Arc::new(ProjectOverviewTool::new()));
         registry.register(Arc::new(DependencyAnalyzerTool));
         registry.register(Arc::new(BuildTool));
+        registry.register(Arc::new(ScaffoldTool));
 
         // Todo 工具（需要共享 manager）
         registry.register(Arc::new(CreateTodoListTool::new(self.todo_manager.clone())));
@@ -267,11 +277,13 @@ mod tests {
         use crate::ai::config::LLMConfig;
 
         let config = LLMConfig::from_env().unwrap_or_else(|_| LLMConfig {
+            provider: crate::ai::config::LLMProvider::OpenAI,
             api_key: "test".to_string(),
             base_url: "https://api.openai.com/v1".to_string(),
             model: "gpt-3.5-turbo".to_string(),
             temperature: 0.7,
             max_tokens: 1000,
+            retry: Default::default(),
         });
 
         let llm_client = Arc::new(LLMClient::new(config));
@@ -282,7 +294,8 @@ mod tests {
         agent.register_standard_tools().await;
 
         // 验证工具已注册
-        let registry = agent.tool_registry().lock().await;
+        let tool_registry = agent.tool_registry();
+        let registry = tool_registry.lock().await;
         assert!(registry.count() > 0);
     }
 }
@@ -11,7 +11,7 @@ use crate::ai::client::LLMClient;
 use crate::ai::code_modification::{AICodeModificationDetector, CodeModificationOp};
 use crate::core::{
     ConversationEngine, ConversationContext, UserIntent,
-    RetryHandler, RetryConfig, ErrorRecovery, StreamingOptimizer,
+    RetryHandler, ErrorRecovery, StreamingOptimizer,
     TokenCalculator, ContextWindowOptimizer, MessageHistory, HookManager,
 };
 use crate::core::tool_executor::ToolExecutor;
@@ -53,11 +53,12 @@ pub struct ChatOrchestrator {
 impl ChatOrchestrator {
     /// 创建新的对话编排器
     pub fn new(llm_client: Arc<LLMClient>) -> Self {
+        let retry_handler = RetryHandler::new(llm_client.retry_config().clone());
         Self {
             conversation_engine: ConversationEngine::new(),
             llm_client,
             message_history: MessageHistory::new(100, 10000),
-            retry_handler: RetryHandler::new(RetryConfig::default()),
+            retry_handler,
             error_recovery: ErrorRecovery::new(Default::default()),
             streaming_optimizer: StreamingOptimizer::new(Default::default()),
             token_calculator: TokenCalculator::from_model_name("gpt-4"),
@@ -223,11 +224,19 @@ impl ChatOrchestrator {
         Ok(ConversationContext::new(user_input, intent))
     }
     
-    /// 调用 LLM 流式（带重试）
-    async fn call_llm_streaming_with_retry<F>(&self, context: &ConversationContext, callback: F) -> Result<String, String>
-    where
-        F: FnMut(String) -> bool + Send + 'static,
-    {
+    /// 按意图选择系统提示词：审查、调试各用专门的提示词，代码生成和普通聊天用配对编程提示词；
+    /// 传入已有消息数，让提示词随对话进展调整
+    fn system_prompt_for(&self, intent: &UserIntent) -> String {
+        let message_count = self.message_history.get_messages().len();
+        match intent {
+            UserIntent::CodeReview { .. } => crate::prompts::get_code_review_prompt(message_count),
+            UserIntent::Debug { .. } => crate::prompts::get_debugging_prompt(message_count),
+            _ => crate::prompts::get_pair_programming_prompt(message_count),
+        }
+    }
+
+    /// 构建请求消息：意图对应的系统提示词 + 用户输入
+    fn build_messages(&self, context: &ConversationContext) -> Vec<crate::ai::client::ChatMessage> {
         let user_input = match &context.intent {
             UserIntent::Chat { query, .. } => query.clone(),
             UserIntent::FileMention { query, .. } => query.clone(),
@@ -237,18 +246,32 @@ impl ChatOrchestrator {
             UserIntent::Command { name, .. } => name.clone(),
         };
 
-        let messages = vec![
+        vec![
+            crate::ai::client::ChatMessage {
+                role: "system".to_string(),
+                content: self.system_prompt_for(&context.intent),
+            },
             crate::ai::client::ChatMessage {
                 role: "user".to_string(),
                 content: user_input,
-            }
-        ];
+            },
+        ]
+    }
+
+    /// 调用 LLM 流式（带重试）
+    async fn call_llm_streaming_with_retry<F>(&self, context: &ConversationContext, callback: F) -> Result<String, String>
+    where
+        F: FnMut(String) -> bool + Send + 'static,
+    {
+        let messages = self.build_messages(context);
 
         // 将回调包装在 Arc<Mutex> 中，使其可以在多次重试中共享
         let callback_arc = std::sync::Arc::new(std::sync::Mutex::new(callback));
         let mut last_error = String::new();
 
-        for attempt in 0..3 {
+        let retry = self.llm_client.retry_config();
+        let max_attempts = retry.max_attempts.max(1);
+        for attempt in 0..max_attempts {
             let response = Arc::new(std::sync::Mutex::new(String::new()));
             let response_for_callback = Arc::clone(&response);
             let callback_for_attempt = Arc::clone(&callback_arc);
@@ -275,35 +298,23 @@ impl ChatOrchestrator {
                 }
                 Err(e) => {
                     last_error = e.to_string();
-                    if attempt < 2 {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100 * (attempt as u64 + 1))).await;
+                    if attempt + 1 < max_attempts {
+                        tokio::time::sleep(retry.delay_before_retry(attempt)).await;
                     }
                 }
             }
         }
-        Err(format!("LLM 调用失败（3 次重试后）: {}", last_error))
+        Err(format!("LLM 调用失败（共尝试 {} 次）: {}", max_attempts, last_error))
     }
     
     /// 调用 LLM（带重试）
     async fn call_llm_with_retry(&self, context: &ConversationContext) -> Result<String, String> {
-        let user_input = match &context.intent {
-            UserIntent::Chat { query, .. } => query.clone(),
-            UserIntent::FileMention { query, .. } => query.clone(),
-            UserIntent::CodeReview { focus, .. } => focus.clone(),
-            UserIntent::Debug { issue, .. } => issue.clone(),
-            UserIntent::CodeGeneration { description, .. } => description.clone(),
-            UserIntent::Command { name, .. } => name.clone(),
-        };
-        
-        let messages = vec![
-            crate::ai::client::ChatMessage {
-                role: "user".to_string(),
-                content: user_input,
-            }
-        ];
+        let messages = self.build_messages(context);
         
         let mut last_error = String::new();
-        for attempt in 0..3 {
+        let retry = self.llm_client.retry_config();
+        let max_attempts = retry.max_attempts.max(1);
+        for attempt in 0..max_attempts {
             let response = Arc::new(std::sync::Mutex::new(String::new()));
             let response_for_callback = Arc::clone(&response);
             
@@ -323,13 +334,13 @@ impl ChatOrchestrator {
                 }
                 Err(e) => {
                     last_error = e.to_string();
-                    if attempt < 2 {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100 * (attempt as u64 + 1))).await;
+                    if attempt + 1 < max_attempts {
+                        tokio::time::sleep(retry.delay_before_retry(attempt)).await;
                     }
                 }
             }
         }
-        Err(format!("LLM 调用失败（3 次重试后）: {}", last_error))
+        Err(format!("LLM 调用失败（共尝试 {} 次）: {}", max_attempts, last_error))
     }
     
     /// 验证响应
@@ -430,7 +441,7 @@ mod tests {
     
     #[test]
     fn test_intent_identification() {
-        let orchestrator = ChatOrchestrator::new(Arc::new(LLMClient::new(Default::default())));
+        let orchestrator = ChatOrchestrator::new(Arc::new(LLMClient::new(crate::ai::config::LLMConfig::default_ollama())));
         
         // 测试文件提及
         let intent = orchestrator.identify_intent("@src/main.rs 这个文件有什么问题？");
@@ -449,9 +460,26 @@ mod tests {
         assert!(matches!(intent, Ok(UserIntent::Chat { .. })));
     }
     
+    #[test]
+    fn test_system_prompt_follows_intent() {
+        let orchestrator = ChatOrchestrator::new(Arc::new(LLMClient::new(crate::ai::config::LLMConfig::default_ollama())));
+        let system_prompt = |input: &str| {
+            let intent = orchestrator.identify_intent(input).unwrap();
+            let context = orchestrator.build_context(intent).unwrap();
+            let messages = orchestrator.build_messages(&context);
+            assert_eq!(messages[0].role, "system");
+            assert_eq!(messages[1].content, input);
+            messages[0].content.clone()
+        };
+
+        assert_eq!(system_prompt("请 review 这段代码"), crate::prompts::get_code_review_prompt(0));
+        assert_eq!(system_prompt("帮我 debug 这个问题"), crate::prompts::get_debugging_prompt(0));
+        assert_eq!(system_prompt("你好"), crate::prompts::get_pair_programming_prompt(0));
+    }
+
     #[test]
     fn test_response_validation() {
-        let orchestrator = ChatOrchestrator::new(Arc::new(LLMClient::new(Default::default())));
+        let orchestrator = ChatOrchestrator::new(Arc::new(LLMClient::new(crate::ai::config::LLMConfig::default_ollama())));
         
         // 测试空响应
         assert!(orchestrator.validate_response("").is_err());
@@ -0,0 +1,103 @@
+//! 上下文文件去重
+//!
+//! 同一个文件可能同时被 `@file` 提及、被 /pin 固定、又被目录提及覆盖。
+//! 按规范化路径去重，只保留最具体的一处来源，避免重复注入浪费 token。
+
+use std::path::{Path, PathBuf};
+
+/// 文件进入上下文的方式，按具体程度从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileSource {
+    /// 目录提及展开得到
+    Directory,
+    /// /pin 固定
+    Pinned,
+    /// 直接 `@file` 提及
+    Mention,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextFile {
+    /// 用户给出的路径，用于展示
+    pub path: PathBuf,
+    pub canonical: PathBuf,
+    pub source: FileSource,
+}
+
+/// 本轮请求涉及的文件集合
+#[derive(Debug, Default)]
+pub struct ContextFileSet {
+    files: Vec<ContextFile>,
+    duplicates: usize,
+}
+
+impl ContextFileSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加入文件；已存在时计为重复，并在新来源更具体时改用新来源的路径和框架
+    pub fn add(&mut self, path: &Path, source: FileSource) {
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(existing) = self.files.iter_mut().find(|f| f.canonical == canonical) {
+            self.duplicates += 1;
+            if source > existing.source {
+                existing.source = source;
+                existing.path = path.to_path_buf();
+            }
+            return;
+        }
+        self.files.push(ContextFile {
+            path: path.to_path_buf(),
+            canonical,
+            source,
+        });
+    }
+
+    /// 所有文件（按加入顺序）
+    pub fn iter(&self) -> impl Iterator<Item = &ContextFile> {
+        self.files.iter()
+    }
+
+    /// 最终归属于某个来源的文件（按加入顺序）
+    pub fn files_from(&self, source: FileSource) -> impl Iterator<Item = &ContextFile> {
+        self.files.iter().filter(move |f| f.source == source)
+    }
+
+    /// 被更具体的来源接管的文件的规范化路径
+    pub fn claimed_by_other_than(&self, source: FileSource) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|f| f.source != source)
+            .map(|f| f.canonical.clone())
+            .collect()
+    }
+
+    /// 去掉的重复次数
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_most_specific_source_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("shared.rs");
+        std::fs::write(&file, "fn shared() {}").unwrap();
+        let dotted = dir.path().join(".").join("shared.rs");
+
+        let mut set = ContextFileSet::new();
+        set.add(&file, FileSource::Pinned);
+        set.add(&file, FileSource::Directory);
+        set.add(&dotted, FileSource::Mention);
+
+        assert_eq!(set.duplicates(), 2);
+        assert_eq!(set.files_from(FileSource::Mention).count(), 1);
+        assert_eq!(set.files_from(FileSource::Pinned).count(), 0);
+        assert_eq!(set.claimed_by_other_than(FileSource::Pinned).len(), 1);
+    }
+}
@@ -418,6 +418,7 @@ impl ConversationEngine {
     }
     
     pub fn with_llm_client(mut self, client: Arc<LLMClient>) -> Self {
+        self.retry_handler = RetryHandler::new(client.retry_config().clone());
         self.llm_client = Some(client);
         self
     }
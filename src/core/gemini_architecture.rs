@@ -8,7 +8,6 @@
 /// 5. 对话轮次管理
 
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::time::sleep;
 
 use crate::ai::client::{ChatMessage, LLMClient};
@@ -30,26 +29,8 @@ pub enum StreamEventType {
     Complete,
 }
 
-/// 重试配置（参考 Gemini CLI 的 INVALID_CONTENT_RETRY_OPTIONS）
-#[derive(Debug, Clone)]
-pub struct RetryConfig {
-    /// 最大重试次数（1 初始 + N 重试）
-    pub max_attempts: u32,
-    /// 初始延迟（毫秒）
-    pub initial_delay_ms: u64,
-    /// 退避倍数
-    pub backoff_multiplier: f64,
-}
-
-impl Default for RetryConfig {
-    fn default() -> Self {
-        Self {
-            max_attempts: 2,
-            initial_delay_ms: 500,
-            backoff_multiplier: 2.0,
-        }
-    }
-}
+/// 重试配置与客户端共用同一份（参考 Gemini CLI 的 INVALID_CONTENT_RETRY_OPTIONS）
+pub use crate::core::retry_handler::RetryConfig;
 
 /// 响应验证器（参考 Gemini CLI 的 isValidResponse）
 pub struct ResponseValidator {
@@ -95,8 +76,6 @@ impl ResponseValidator {
     where
         F: FnMut() -> BoxFuture<'static, Result<T, String>>,
     {
-        let mut delay = self.config.initial_delay_ms;
-
         for attempt in 0..self.config.max_attempts {
             match operation().await {
                 Ok(result) => {
@@ -107,8 +86,7 @@ impl ResponseValidator {
                 }
                 Err(_e) if attempt < self.config.max_attempts - 1 => {
                     // Attempt failed, retrying
-                    sleep(Duration::from_millis(delay)).await;
-                    delay = (delay as f64 * self.config.backoff_multiplier) as u64;
+                    sleep(self.config.delay_before_retry(attempt)).await;
                 }
                 Err(e) => {
                     return Err(e);
@@ -421,6 +399,7 @@ impl GeminiArchitecture {
     }
 
     pub fn set_llm_client(&mut self, client: Arc<LLMClient>) {
+        self.validator = ResponseValidator::new(client.retry_config().clone());
         self.llm_client = Some(client);
     }
 
@@ -490,7 +469,6 @@ impl GeminiArchitecture {
         let retry_config = self.validator.config().clone();
         let max_attempts = retry_config.max_attempts.max(1);
         let mut attempt = 0;
-        let mut delay = retry_config.initial_delay_ms;
 
         loop {
             use std::sync::Mutex;
@@ -523,8 +501,7 @@ impl GeminiArchitecture {
                 }
             }
 
-            sleep(Duration::from_millis(delay)).await;
-            delay = (delay as f64 * retry_config.backoff_multiplier) as u64;
+            sleep(retry_config.delay_before_retry(attempt - 1)).await;
         }
     }
 
@@ -23,6 +23,8 @@ pub mod chat_orchestrator;
 pub mod health_check;
 pub mod vibe_coding;
 pub mod ai_agent;
+pub mod pinned_context;
+pub mod context_files;
 
 pub use conversation_engine::{ConversationEngine, ConversationContext, UserIntent};
 
@@ -37,4 +39,5 @@ pub use streaming_optimizer::StreamingOptimizer;
 pub use token_calculator::TokenCalculator;
 pub use context_optimizer::ContextWindowOptimizer;
 pub use chat_orchestrator::ChatOrchestrator;
-pub use ai_agent::{AIAgent, AIAgentConfig, AgentResponse, AgentStatus, convert_to_chat_messages};
\ No newline at end of file
+pub use ai_agent::{AIAgent, AIAgentConfig, AgentResponse, AgentStatus, convert_to_chat_messages};
+pub use pinned_context::PinnedContext;
@@ -0,0 +1,212 @@
+//! 固定上下文 - 会话内持续附加到每次请求的参考文件
+//!
+//! 与单条消息里的 `@file` 提及不同，固定的文件每一轮都会从磁盘重新读取，
+//! 并在 token 预算内注入到请求中，适合在专注任务期间一直参考的规格文档等。
+
+use crate::core::token_calculator::TokenCalculator;
+use std::path::{Path, PathBuf};
+
+/// 默认的固定上下文 token 预算
+pub const DEFAULT_PINNED_TOKEN_BUDGET: usize = 8000;
+
+/// 单个固定文件在本轮的注入结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum PinnedFileStatus {
+    /// 完整注入
+    Included { tokens: usize },
+    /// 超出剩余预算，已截断
+    Truncated { tokens: usize },
+    /// 预算已用尽，本轮跳过
+    Skipped,
+    /// 读取失败（文件被删除等）
+    Unreadable(String),
+}
+
+/// 本轮构建出的固定上下文
+#[derive(Debug, Clone)]
+pub struct PinnedContextRender {
+    pub text: String,
+    pub statuses: Vec<(PathBuf, PinnedFileStatus)>,
+    pub total_tokens: usize,
+}
+
+pub struct PinnedContext {
+    files: Vec<PathBuf>,
+    token_budget: usize,
+    calculator: TokenCalculator,
+}
+
+impl PinnedContext {
+    pub fn new() -> Self {
+        Self::with_budget(DEFAULT_PINNED_TOKEN_BUDGET)
+    }
+
+    pub fn with_budget(token_budget: usize) -> Self {
+        Self {
+            files: Vec::new(),
+            token_budget,
+            calculator: TokenCalculator::from_model_name("gpt-4"),
+        }
+    }
+
+    /// 固定一个文件，返回规范化后的路径
+    pub fn pin(&mut self, path: &str) -> Result<PathBuf, String> {
+        let path = PathBuf::from(path.trim());
+        if !path.is_file() {
+            return Err(format!("文件不存在: {}", path.display()));
+        }
+        if self.files.contains(&path) {
+            return Err(format!("已经固定: {}", path.display()));
+        }
+        self.files.push(path.clone());
+        Ok(path)
+    }
+
+    /// 取消固定，返回是否存在该文件
+    pub fn unpin(&mut self, path: &str) -> bool {
+        let path = Path::new(path.trim());
+        let before = self.files.len();
+        self.files.retain(|p| p != path);
+        self.files.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn token_budget(&self) -> usize {
+        self.token_budget
+    }
+
+    /// 从磁盘重新读取所有固定文件，按固定顺序在预算内拼接
+    pub fn render(&self) -> PinnedContextRender {
+        self.render_excluding(&[])
+    }
+
+    /// 同 `render`，但跳过已由其它来源注入的文件（按规范化路径比较）
+    pub fn render_excluding(&self, exclude: &[PathBuf]) -> PinnedContextRender {
+        let mut text = String::new();
+        let mut statuses = Vec::new();
+        let mut used = 0;
+
+        for path in &self.files {
+            if !exclude.is_empty() {
+                let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                if exclude.contains(&canonical) {
+                    continue;
+                }
+            }
+            let content = match std::fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    statuses.push((path.clone(), PinnedFileStatus::Unreadable(e.to_string())));
+                    continue;
+                }
+            };
+
+            let remaining = self.token_budget.saturating_sub(used);
+            if remaining == 0 {
+                statuses.push((path.clone(), PinnedFileStatus::Skipped));
+                continue;
+            }
+
+            let tokens = self.calculator.count_tokens(&content);
+            let (body, tokens, status) = if tokens <= remaining {
+                (content, tokens, PinnedFileStatus::Included { tokens })
+            } else {
+                let truncated = self.truncate_to_tokens(&content, remaining);
+                let tokens = self.calculator.count_tokens(&truncated);
+                (format!("{}\n... [截断：超出固定上下文预算]", truncated), tokens, PinnedFileStatus::Truncated { tokens })
+            };
+
+            used += tokens;
+            text.push_str(&format!("\n\n📌 Pinned file: {}\n```\n{}\n```", path.display(), body));
+            statuses.push((path.clone(), status));
+        }
+
+        PinnedContextRender {
+            text,
+            statuses,
+            total_tokens: used,
+        }
+    }
+
+    /// 按行截断到不超过给定 token 数
+    fn truncate_to_tokens(&self, content: &str, max_tokens: usize) -> String {
+        let mut result = String::new();
+        for line in content.lines() {
+            let candidate = if result.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}\n{}", result, line)
+            };
+            if self.calculator.count_tokens(&candidate) > max_tokens {
+                break;
+            }
+            result = candidate;
+        }
+        result
+    }
+
+    /// 状态栏摘要，例如 "📌 2: spec.md, main.rs"
+    pub fn status_summary(&self) -> Option<String> {
+        if self.files.is_empty() {
+            return None;
+        }
+        let names: Vec<String> = self
+            .files
+            .iter()
+            .map(|p| {
+                p.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| p.display().to_string())
+            })
+            .collect();
+        Some(format!("📌 {}: {}", self.files.len(), names.join(", ")))
+    }
+}
+
+impl Default for PinnedContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_pin_refreshes_and_respects_budget() {
+        let mut spec = tempfile::NamedTempFile::new().unwrap();
+        writeln!(spec, "first version").unwrap();
+        let mut big = tempfile::NamedTempFile::new().unwrap();
+        for i in 0..200 {
+            writeln!(big, "line {} of a long reference file", i).unwrap();
+        }
+
+        let mut pinned = PinnedContext::with_budget(100);
+        pinned.pin(spec.path().to_str().unwrap()).unwrap();
+        pinned.pin(big.path().to_str().unwrap()).unwrap();
+        assert!(pinned.pin(spec.path().to_str().unwrap()).is_err());
+
+        writeln!(spec, "second version").unwrap();
+        let render = pinned.render();
+        assert!(render.text.contains("second version"));
+        assert!(matches!(render.statuses[0].1, PinnedFileStatus::Included { .. }));
+        assert!(matches!(render.statuses[1].1, PinnedFileStatus::Truncated { .. }));
+        assert!(render.total_tokens <= 100);
+
+        assert!(pinned.unpin(spec.path().to_str().unwrap()));
+        assert_eq!(pinned.files().len(), 1);
+    }
+}
@@ -1,9 +1,13 @@
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 
 /// 控制重试行为的配置
-#[derive(Debug, Clone)]
+///
+/// 由 `LLMConfig` 持有，客户端、编排器和各架构组件都从 `LLMClient::retry_config` 读取，
+/// 可通过 `/retry-config` 在运行时查看和调整。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RetryConfig {
     /// 最大尝试次数（包含首次调用）
     pub max_attempts: u32,
@@ -23,6 +27,56 @@ impl Default for RetryConfig {
     }
 }
 
+impl RetryConfig {
+    /// 从 `LLM_RETRY_ATTEMPTS` / `LLM_RETRY_DELAY_MS` / `LLM_RETRY_BACKOFF` 读取，未设置或无效时用默认值
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        for (key, var) in [
+            ("attempts", "LLM_RETRY_ATTEMPTS"),
+            ("delay", "LLM_RETRY_DELAY_MS"),
+            ("backoff", "LLM_RETRY_BACKOFF"),
+        ] {
+            if let Ok(value) = std::env::var(var) {
+                let _ = config.set(key, &value);
+            }
+        }
+        config
+    }
+
+    /// 按名称修改一项：`attempts`（1-10）、`delay`（毫秒，0-60000）、`backoff`（1.0-10.0）
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "attempts" => match value.parse::<u32>() {
+                Ok(n) if (1..=10).contains(&n) => self.max_attempts = n,
+                _ => return Err(format!("attempts 需要 1-10 之间的整数，收到 '{}'", value)),
+            },
+            "delay" => match value.parse::<u64>() {
+                Ok(ms) if ms <= 60_000 => self.initial_delay_ms = ms,
+                _ => return Err(format!("delay 需要 0-60000 之间的毫秒数，收到 '{}'", value)),
+            },
+            "backoff" => match value.parse::<f64>() {
+                Ok(m) if (1.0..=10.0).contains(&m) => self.backoff_multiplier = m,
+                _ => return Err(format!("backoff 需要 1.0-10.0 之间的数字，收到 '{}'", value)),
+            },
+            _ => return Err(format!("未知配置项 '{}'，可选: attempts, delay, backoff", key)),
+        }
+        Ok(())
+    }
+
+    /// 第 `retry` 次重试（从 0 开始）前的等待时间
+    pub fn delay_before_retry(&self, retry: u32) -> Duration {
+        let ms = self.initial_delay_ms as f64 * self.backoff_multiplier.powi(retry as i32);
+        Duration::from_millis(ms as u64)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "重试配置: attempts={}（含首次） delay={}ms backoff={}x",
+            self.max_attempts, self.initial_delay_ms, self.backoff_multiplier
+        )
+    }
+}
+
 /// 可判断是否允许重试的错误类型
 pub trait RetryableError: std::error::Error {
     /// 当前错误是否可以通过重试恢复
@@ -51,7 +105,6 @@ impl RetryHandler {
         Fut: std::future::Future<Output = Result<T, E>>,
         E: RetryableError,
     {
-        let mut delay = self.config.initial_delay_ms;
         let max_attempts = self.config.max_attempts.max(1);
 
         for attempt in 0..max_attempts {
@@ -63,8 +116,7 @@ impl RetryHandler {
                         return Err(err);
                     }
 
-                    sleep(Duration::from_millis(delay)).await;
-                    delay = (delay as f64 * self.config.backoff_multiplier) as u64;
+                    sleep(self.config.delay_before_retry(attempt)).await;
                 }
             }
         }
@@ -72,3 +124,22 @@ impl RetryHandler {
         unreachable!("循环只能通过返回语句退出");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_validates_and_backoff_grows() {
+        let mut config = RetryConfig::default();
+        config.set("attempts", "5").unwrap();
+        config.set("delay", "100").unwrap();
+        assert!(config.set("attempts", "0").is_err());
+        assert!(config.set("backoff", "abc").is_err());
+        assert!(config.set("jitter", "1").is_err());
+
+        assert_eq!(config.max_attempts, 5);
+        assert_eq!(config.delay_before_retry(0), Duration::from_millis(100));
+        assert_eq!(config.delay_before_retry(2), Duration::from_millis(400));
+    }
+}
@@ -204,7 +204,7 @@ mod tests {
         
         if let Ok(Some(event)) = tokio::runtime::Runtime::new()
             .unwrap()
-            .block_on(async { Ok(receiver.recv().await) })
+            .block_on(async { Ok::<_, ()>(receiver.recv().await) })
         {
             assert!(matches!(event.event_type, StreamEventType::Chunk));
             assert_eq!(event.content, "test");
@@ -254,6 +254,8 @@ pub struct VibeWorkflowManager {
     pub stage: VibeStage,
     file_handler: CodeFileHandler,
     changes: Vec<CodeChange>,
+    /// PRD 和技术设计文档的保存目录
+    docs_dir: PathBuf,
 }
 
 impl VibeWorkflowManager {
@@ -263,6 +265,7 @@ impl VibeWorkflowManager {
             stage: VibeStage::Conceptualization,
             file_handler: CodeFileHandler::new(),
             changes: Vec::new(),
+            docs_dir: PathBuf::from("docs"),
         }
     }
 
@@ -272,12 +275,9 @@ impl VibeWorkflowManager {
         let prd = ProductRequirementsDoc::new(project.clone());
 
         // 保存 PRD 文件
-        let prd_path = PathBuf::from(format!("docs/prd_{}.md", project.id));
+        let prd_path = self.docs_dir.join(format!("prd_{}.md", project.id));
         if let Some(parent) = prd_path.parent() {
-            let _ = self.file_handler.create_file(
-                parent.to_str().unwrap(),
-                "# Vibe Project\n\nThis directory was created using Vibe Coding workflow.",
-            );
+            let _ = std::fs::create_dir_all(parent);
         }
 
         let result = self.file_handler.create_file(
@@ -304,8 +304,9 @@ impl VibeWorkflowManager {
             "基于模块化的软件设计，包含前端、后端和数据库层。".to_string(),
         );
 
+        let design_path = self.docs_dir.join(format!("technical_design_{}.md", prd.project.id));
         let result = self.file_handler.create_file(
-            &format!("docs/technical_design_{}.md", prd.project.id),
+            design_path.to_str().unwrap(),
             &design.to_markdown(),
         );
 
@@ -372,13 +373,16 @@ mod tests {
 
     #[test]
     fn test_project_creation() {
+        let dir = tempfile::tempdir().unwrap();
         let mut manager = VibeWorkflowManager::new();
+        manager.docs_dir = dir.path().join("docs");
         let project = manager.create_project(
             "Test Project".to_string(),
             "A test project for vibe coding".to_string(),
         );
 
-        assert!(project.is_ok());
+        let project = project.unwrap();
+        assert!(dir.path().join("docs").join(format!("prd_{}.md", project.id)).exists());
         assert_eq!(manager.stage, VibeStage::Conceptualization);
     }
 
@@ -1,7 +1,8 @@
 use crate::app::{App, AppAction, ModificationChoice};
-use crate::ai::code_modification::{CodeModificationOp, CodeMatcher};
+use crate::ai::code_modification::{AICodeModificationDetector, CodeModificationOp, ModificationBatch};
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
 use crate::ui::pixel_layout_v2::extract_text_from_chat_area;
+use crate::utils::i18n::{t, tf, Msg};
 
 fn estimate_chat_lines(app: &App) -> usize {
     let mut total = 0;
@@ -88,13 +89,70 @@ impl EventHandler {
         }
     }
 
-    /// 复制文本到系统剪贴板
+    /// 复制文本到系统剪贴板，系统剪贴板不可用时（如 SSH 会话）回退到 OSC52
     fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut clipboard = arboard::Clipboard::new()?;
-        clipboard.set_text(text.to_string())?;
+        let native = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text.to_string()));
+        if native.is_ok() {
+            return Ok(());
+        }
+
+        use base64::Engine;
+        use std::io::Write;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+        let mut stdout = std::io::stdout();
+        write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+        stdout.flush()?;
         Ok(())
     }
+
+    /// 复制最后一条 AI 回复（code_only 时只复制其中最后一个代码块）
+    fn copy_last_assistant_output(app: &mut App, code_only: bool) {
+        let last_reply = app
+            .chat_history
+            .get_messages()
+            .iter()
+            .rev()
+            .find(|m| m.role == crate::core::message::Role::Assistant && !m.content.trim().is_empty())
+            .map(|m| m.content.clone());
+
+        let (text, label) = match (last_reply, code_only) {
+            (None, _) => (None, Msg::NoReplyToCopy),
+            (Some(reply), false) => (Some(reply), Msg::LastReply),
+            (Some(reply), true) => match AICodeModificationDetector::extract_code_blocks(&reply).pop() {
+                Some(block) => (Some(block.content), Msg::LastCodeBlock),
+                None => (None, Msg::NoCodeBlockInReply),
+            },
+        };
+
+        let content = match text {
+            Some(text) => match Self::copy_to_clipboard(&text) {
+                Ok(()) => tf(Msg::Copied, &[t(label), &text.lines().count().to_string()]),
+                Err(e) => tf(Msg::CopyFailed, &[&e.to_string()]),
+            },
+            None => t(label).to_string(),
+        };
+        app.chat_history.add_message(crate::core::message::Message {
+            role: crate::core::message::Role::System,
+            content,
+        });
+        app.scroll_to_bottom();
+    }
     
+    /// 以全有或全无的方式应用已确认的修改批次
+    fn apply_pending_modifications(app: &mut App) {
+        let ops: Vec<CodeModificationOp> = app.pending_modifications.iter().map(|(op, _)| op.clone()).collect();
+        let messages = match ModificationBatch::apply(&ops) {
+            Ok(messages) => messages,
+            Err(e) => vec![tf(Msg::BatchNotApplied, &[&e.to_string()])],
+        };
+        for content in messages {
+            app.chat_history.add_message(crate::core::message::Message {
+                role: crate::core::message::Role::System,
+                content,
+            });
+        }
+    }
+
     pub fn handle_chat_event(app: &mut App, key: KeyEvent) -> AppAction {
         // 最高优先级：处理 AI 代码修改确认对话
         if app.modification_confirmation_pending && !app.pending_modifications.is_empty() {
@@ -118,94 +176,21 @@ impl EventHandler {
                     return AppAction::None;
                 }
                 KeyCode::Char('1') => {
-                    // 数字 1 - 确认
+                    // 数字 1 - 确认并立即执行
                     app.modification_choice = ModificationChoice::Confirm;
-                    // 立即执行
-                    if app.modification_choice == ModificationChoice::Confirm {
-                        // 执行修改
-                        for (op, _diff) in &app.pending_modifications {
-                            match op {
-                                crate::ai::code_modification::CodeModificationOp::Create { path, content } => {
-                                    // 创建文件
-                                    match std::fs::write(path, content) {
-                                        Ok(_) => {
-                                            app.chat_history.add_message(crate::core::message::Message {
-                                                role: crate::core::message::Role::System,
-                                                content: format!("✅ 文件已创建: {}", path),
-                                            });
-                                        }
-                                        Err(e) => {
-                                            app.chat_history.add_message(crate::core::message::Message {
-                                                role: crate::core::message::Role::System,
-                                                content: format!("❌ 创建文件失败: {}", e),
-                                            });
-                                        }
-                                    }
-                                }
-                                CodeModificationOp::Modify { path, search, replace } => {
-                                    // 修改文件 - 使用 CodeMatcher 进行模糊匹配
-                                    match CodeMatcher::find_and_replace(&path, &search, &replace) {
-                                        Ok(diff) => {
-                                            match std::fs::write(path, diff.new_content) {
-                                                Ok(_) => {
-                                                    app.chat_history.add_message(crate::core::message::Message {
-                                                        role: crate::core::message::Role::System,
-                                                        content: format!("✅ 文件已修改: {}", path),
-                                                    });
-                                                }
-                                                Err(e) => {
-                                                    app.chat_history.add_message(crate::core::message::Message {
-                                                        role: crate::core::message::Role::System,
-                                                        content: format!("❌ 修改文件失败: {}", e),
-                                                    });
-                                                }
-                                            }
-                                        }
-                                        Err(e) => {
-                                            app.chat_history.add_message(crate::core::message::Message {
-                                                role: crate::core::message::Role::System,
-                                                content: format!("❌ 代码匹配失败: {}", e),
-                                            });
-                                        }
-                                    }
-                                }
-                                crate::ai::code_modification::CodeModificationOp::Delete { path } => {
-                                    // 删除文件
-                                    match std::fs::remove_file(path) {
-                                        Ok(_) => {
-                                            app.chat_history.add_message(crate::core::message::Message {
-                                                role: crate::core::message::Role::System,
-                                                content: format!("✅ 文件已删除: {}", path),
-                                            });
-                                        }
-                                        Err(e) => {
-                                            app.chat_history.add_message(crate::core::message::Message {
-                                                role: crate::core::message::Role::System,
-                                                content: format!("❌ 删除文件失败: {}", e),
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        // 取消修改
-                        app.chat_history.add_message(crate::core::message::Message {
-                            role: crate::core::message::Role::System,
-                            content: "✅ 修改已取消".to_string(),
-                        });
-                    }
-                    
+                    Self::apply_pending_modifications(app);
+
                     // 清空待确认的修改
                     app.pending_modifications.clear();
                     app.modification_confirmation_pending = false;
+                    app.scroll_to_bottom();
                     return AppAction::None;
                 }
                 KeyCode::Char('2') | KeyCode::Char('n') | KeyCode::Char('N') => {
                     // 数字 2 或 N 键 - 取消
                     app.chat_history.add_message(crate::core::message::Message {
                         role: crate::core::message::Role::System,
-                        content: "✅ 修改已取消".to_string(),
+                        content: t(Msg::ModificationCancelled).to_string(),
                     });
                     
                     // 清空待确认的修改
@@ -220,7 +205,7 @@ impl EventHandler {
                     // 立即执行
                     app.chat_history.add_message(crate::core::message::Message {
                         role: crate::core::message::Role::System,
-                        content: "✅ 修改已放弃".to_string(),
+                        content: t(Msg::ModificationAbandoned).to_string(),
                     });
                     app.pending_modifications.clear();
                     app.modification_confirmation_pending = false;
@@ -231,7 +216,7 @@ impl EventHandler {
                     // Esc - 放弃
                     app.chat_history.add_message(crate::core::message::Message {
                         role: crate::core::message::Role::System,
-                        content: "✅ 修改已放弃".to_string(),
+                        content: t(Msg::ModificationAbandoned).to_string(),
                     });
                     app.pending_modifications.clear();
                     app.modification_confirmation_pending = false;
@@ -243,75 +228,13 @@ impl EventHandler {
                     match app.modification_choice {
                         ModificationChoice::Confirm => {
                             // 执行修改
-                            for (op, _diff) in &app.pending_modifications {
-                                match op {
-                                    crate::ai::code_modification::CodeModificationOp::Create { path, content } => {
-                                        match std::fs::write(path, content) {
-                                            Ok(_) => {
-                                                app.chat_history.add_message(crate::core::message::Message {
-                                                    role: crate::core::message::Role::System,
-                                                    content: format!("✅ 文件已创建: {}", path),
-                                                });
-                                            }
-                                            Err(e) => {
-                                                app.chat_history.add_message(crate::core::message::Message {
-                                                    role: crate::core::message::Role::System,
-                                                    content: format!("❌ 创建文件失败: {}", e),
-                                                });
-                                            }
-                                        }
-                                    }
-                                    crate::ai::code_modification::CodeModificationOp::Modify { path, search: _, replace } => {
-                                        match std::fs::read_to_string(path) {
-                                            Ok(content) => {
-                                                let new_content = content.replace(&content, &replace);
-                                                match std::fs::write(path, new_content) {
-                                                    Ok(_) => {
-                                                        app.chat_history.add_message(crate::core::message::Message {
-                                                            role: crate::core::message::Role::System,
-                                                            content: format!("✅ 文件已修改: {}", path),
-                                                        });
-                                                    }
-                                                    Err(e) => {
-                                                        app.chat_history.add_message(crate::core::message::Message {
-                                                            role: crate::core::message::Role::System,
-                                                            content: format!("❌ 修改文件失败: {}", e),
-                                                        });
-                                                    }
-                                                }
-                                            }
-                                            Err(e) => {
-                                                app.chat_history.add_message(crate::core::message::Message {
-                                                    role: crate::core::message::Role::System,
-                                                    content: format!("❌ 读取文件失败: {}", e),
-                                                });
-                                            }
-                                        }
-                                    }
-                                    crate::ai::code_modification::CodeModificationOp::Delete { path } => {
-                                        match std::fs::remove_file(path) {
-                                            Ok(_) => {
-                                                app.chat_history.add_message(crate::core::message::Message {
-                                                    role: crate::core::message::Role::System,
-                                                    content: format!("✅ 文件已删除: {}", path),
-                                                });
-                                            }
-                                            Err(e) => {
-                                                app.chat_history.add_message(crate::core::message::Message {
-                                                    role: crate::core::message::Role::System,
-                                                    content: format!("❌ 删除文件失败: {}", e),
-                                                });
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                            Self::apply_pending_modifications(app);
                         }
                         ModificationChoice::Cancel | ModificationChoice::Abandon => {
                             // 取消或放弃修改
                             app.chat_history.add_message(crate::core::message::Message {
                                 role: crate::core::message::Role::System,
-                                content: "✅ 修改已取消".to_string(),
+                                content: t(Msg::ModificationCancelled).to_string(),
                             });
                         }
                     }
@@ -325,6 +248,51 @@ impl EventHandler {
             }
         }
 
+        // /clear 的确认：与代码修改确认一样用 1/2 选择
+        if app.pending_clear.is_some() {
+            if key.kind == KeyEventKind::Press {
+                match key.code {
+                    KeyCode::Char('1') | KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => app.confirm_clear(),
+                    KeyCode::Char('2') | KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_clear(),
+                    _ => {}
+                }
+            }
+            return AppAction::None;
+        }
+
+        // 上下文文件面板：选择并移除注入的文件
+        if app.context_panel.open {
+            if key.kind == KeyEventKind::Press {
+                match key.code {
+                    KeyCode::Up => app.context_panel.select_previous(),
+                    KeyCode::Down => app.context_panel.select_next(),
+                    KeyCode::Char('d') | KeyCode::Delete => app.remove_selected_context_file(),
+                    KeyCode::Esc | KeyCode::Char('q') => app.context_panel.open = false,
+                    KeyCode::Char('k') if key.modifiers == KeyModifiers::CONTROL => app.context_panel.open = false,
+                    _ => {}
+                }
+            }
+            return AppAction::None;
+        }
+        if key.code == KeyCode::Char('k') && key.modifiers == KeyModifiers::CONTROL && key.kind == KeyEventKind::Press {
+            app.open_context_panel();
+            return AppAction::None;
+        }
+
+        // 结构化审查建议：逐条应用或跳过
+        if app.review_session.is_some() {
+            if key.kind == KeyEventKind::Press {
+                match key.code {
+                    KeyCode::Char('a') | KeyCode::Enter => app.accept_review_suggestion(),
+                    KeyCode::Char('s') | KeyCode::Right => app.skip_review_suggestion(),
+                    KeyCode::Left => app.previous_review_suggestion(),
+                    KeyCode::Esc | KeyCode::Char('q') => app.end_review(),
+                    _ => {}
+                }
+            }
+            return AppAction::None;
+        }
+
         // 新的高优先级：处理文件名建议对话框
         if app.filename_suggestion.is_visible() {
             match key.code {
@@ -357,7 +325,7 @@ impl EventHandler {
                         if let Some(backup_path) = result.backup_path {
                             app.chat_history.add_message(crate::core::message::Message {
                                 role: crate::core::message::Role::System,
-                                content: format!("💾 备份已创建: {}", backup_path.display()),
+                                content: tf(Msg::BackupCreated, &[&backup_path.display().to_string()]),
                             });
                         }
 
@@ -370,7 +338,7 @@ impl EventHandler {
                     app.filename_suggestion.hide();
                     app.chat_history.add_message(crate::core::message::Message {
                         role: crate::core::message::Role::System,
-                        content: "❌ 已取消文件创建".to_string(),
+                        content: t(Msg::FileCreationCancelled).to_string(),
                     });
                     app.scroll_to_bottom();
                     return AppAction::None;
@@ -440,16 +408,44 @@ impl EventHandler {
             }
         }
 
+        // 复制快捷键的第二个键：y=最后一条回复，c=最后一个代码块，其它键则把 y 当作普通输入
+        if app.pending_copy_chord && key.kind == KeyEventKind::Press {
+            app.pending_copy_chord = false;
+            match key.code {
+                KeyCode::Char('y') if key.modifiers.is_empty() => {
+                    Self::copy_last_assistant_output(app, false);
+                    return AppAction::None;
+                }
+                KeyCode::Char('c') if key.modifiers.is_empty() => {
+                    Self::copy_last_assistant_output(app, true);
+                    return AppAction::None;
+                }
+                KeyCode::Esc => return AppAction::None,
+                _ => {
+                    app.input_text.push('y');
+                    app.input_cursor = app.input_text.chars().count();
+                }
+            }
+        }
+
         match key.code {
+            KeyCode::Char('y')
+                if app.input_text.is_empty()
+                    && key.modifiers.is_empty()
+                    && key.kind == KeyEventKind::Press =>
+            {
+                // 输入框为空时 y 开始复制快捷键
+                app.pending_copy_chord = true;
+                AppAction::None
+            }
             KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
                 // Ctrl+C - 如果有选中文本则复制，否则退出
                 if !app.selected_text.is_empty() {
                     // 复制到剪贴板
-                    if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                        let _ = clipboard.set_text(app.selected_text.clone());
+                    if Self::copy_to_clipboard(&app.selected_text).is_ok() {
                         app.chat_history.add_message(crate::core::message::Message {
                             role: crate::core::message::Role::System,
-                            content: "✅ 已复制到剪贴板".to_string(),
+                            content: t(Msg::CopiedToClipboard).to_string(),
                         });
                         app.scroll_to_bottom();
                     }
@@ -523,7 +519,7 @@ impl EventHandler {
                         app.file_search.clear();
                     }
                 } else {
-                    app.command_hints.update_input(&app.input_text);
+                    app.update_command_hints();
                 }
                 
                 AppAction::None
@@ -630,7 +626,7 @@ impl EventHandler {
                     // 没有@符号，处理普通命令提示
                     app.mention_suggestions.close();
                     app.file_search.clear();
-                    app.command_hints.update_input(&app.input_text);
+                    app.update_command_hints();
                 }
 
                 AppAction::None
@@ -644,3 +640,38 @@ impl EventHandler {
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::message::{Message, Role};
+
+    #[test]
+    fn test_clear_shows_a_preview_and_waits_for_confirmation() {
+        let mut app = App::new();
+        for content in ["hello", "world"] {
+            app.chat_history.add_message(Message { role: Role::User, content: content.to_string() });
+        }
+
+        let preview = app.request_clear(&[]);
+        assert!(preview.contains("2 条消息"), "{}", preview);
+        assert_eq!(app.pending_clear.map(|p| p.messages), Some(2));
+        assert_eq!(app.chat_history.get_messages().len(), 2);
+
+        // 取消后历史不变，其他按键不会清空
+        EventHandler::handle_chat_event(&mut app, KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert!(app.pending_clear.is_some());
+        EventHandler::handle_chat_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(app.pending_clear.is_none());
+        assert_eq!(app.chat_history.get_messages().len(), 3);
+
+        app.request_clear(&[]);
+        EventHandler::handle_chat_event(&mut app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.chat_history.get_messages().len(), 1);
+
+        app.chat_history.add_message(Message { role: Role::User, content: "again".to_string() });
+        assert_eq!(app.request_clear(&["--yes".to_string()]), "✓ Chat history cleared");
+        assert!(app.chat_history.get_messages().is_empty());
+        assert!(app.pending_clear.is_none());
+    }
+}
@@ -106,6 +106,10 @@ async fn run_app<B: ratatui::backend::Backend>(
                             match action {
                                 crate::app::AppAction::SubmitChat => {
                                     app.handle_chat_submit().await;
+                                    // /exit
+                                    if app.should_quit {
+                                        return Ok(());
+                                    }
                                 }
                                 crate::app::AppAction::Quit => {
                                     return Ok(());
@@ -114,6 +118,12 @@ async fn run_app<B: ratatui::backend::Backend>(
                             }
                         }
                     }
+                    crossterm::event::Event::Resize(width, height) => {
+                        // 尺寸变化后立即重算折行和滚动范围并重绘，不等下一次按键或定时刷新
+                        app.handle_resize(width, height);
+                        terminal.autoresize()?;
+                        terminal.draw(|f| app.render(f))?;
+                    }
                     crossterm::event::Event::Mouse(mouse) => {
                         // 获取终端尺寸
                         let terminal_size = terminal.size().unwrap_or_default();
@@ -51,6 +51,23 @@ impl CodeReviewPrompts {
    - Recommend refactoring opportunities"
     }
 
+    /// 结构化审查输出格式，回复会被解析为可逐条应用的建议
+    pub fn structured_output_format() -> &'static str {
+        "Return every concrete fix as one fenced block tagged `review-suggestions` containing a JSON array:
+
+```review-suggestions
+[
+  {\"file\": \"src/lib.rs\", \"start_line\": 10, \"end_line\": 12, \"replacement\": \"new code for exactly those lines\", \"rationale\": \"why this change\"}
+]
+```
+
+Rules:
+- Line numbers are 1-based and inclusive, taken from the numbered listing you were given
+- `replacement` replaces the whole range; keep the original indentation
+- Keep each suggestion small and independent; do not overlap ranges
+- Outside the block, briefly summarize your findings"
+    }
+
     /// 根据对话历史调整审查重点
     fn focus_areas(message_count: usize) -> String {
         match message_count {
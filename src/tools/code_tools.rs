@@ -7,6 +7,13 @@ use std::fs;
 use std::path::Path;
 use std::pin::Pin;
 use std::future::Future;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use crate::ai::client::{ChatMessage, LLMClient};
+use crate::utils::code_file_handler::{definition_name, CodeFileHandler};
+use crate::utils::os_path::encode_path;
+use crate::utils::parallel_walk;
 
 /// 代码搜索工具
 pub struct CodeSearchTool;
@@ -49,6 +56,12 @@ impl Tool for CodeSearchTool {
                     param_type: "boolean".to_string(),
                     required: false,
                 },
+                ToolParameter {
+                    name: "max_results".to_string(),
+                    description: "最多返回的匹配数（按文件路径、行号排序，默认不限）".to_string(),
+                    param_type: "number".to_string(),
+                    required: false,
+                },
             ],
         }
     }
@@ -77,14 +90,16 @@ impl Tool for CodeSearchTool {
 
             let include_pattern = ctx.get_string("include_pattern");
             let case_sensitive = ctx.get_bool("case_sensitive").unwrap_or(false);
+            let max_results = ctx.get_number("max_results").map(|n| n as usize);
 
-            match search_code(&pattern, &path, include_pattern.as_deref(), case_sensitive) {
-                Ok(results) => ToolResult {
+            match search_code(&pattern, &path, include_pattern.as_deref(), case_sensitive, max_results) {
+                Ok((results, truncated)) => ToolResult {
                     success: true,
                     data: serde_json::json!({
                         "pattern": pattern,
                         "path": path,
-                        "matches": results
+                        "matches": results,
+                        "truncated": truncated
                     }),
                     error: None,
                 },
@@ -173,6 +188,92 @@ impl Tool for FunctionFinderTool {
     }
 }
 
+/// 跨文件查找符号定义的工具（"跳转到定义"）
+pub struct FindSymbolTool;
+
+impl Tool for FindSymbolTool {
+    fn name(&self) -> &str {
+        "find_symbol"
+    }
+
+    fn description(&self) -> &str {
+        "在整个项目中查找函数、结构体、类等符号的定义，返回所在文件和行号（精确匹配排在前面）；再用 read_file 的 symbol 参数查看定义"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: vec![
+                ToolParameter {
+                    name: "name".to_string(),
+                    description: "符号名，如 parse_config、Server".to_string(),
+                    param_type: "string".to_string(),
+                    required: true,
+                },
+                ToolParameter {
+                    name: "path".to_string(),
+                    description: "搜索的目录（默认当前目录）".to_string(),
+                    param_type: "string".to_string(),
+                    required: false,
+                },
+                ToolParameter {
+                    name: "max_results".to_string(),
+                    description: format!("最多返回的定义数（默认 {}）", FIND_SYMBOL_MAX_RESULTS),
+                    param_type: "number".to_string(),
+                    required: false,
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, call: ToolCall) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>> {
+        Box::pin(async move {
+            let ctx = ToolExecutionContext::new(call.tool_name, call.arguments);
+
+            let name = match ctx.get_string("name") {
+                Some(n) if !n.trim().is_empty() => n.trim().to_string(),
+                _ => return ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some("Missing required parameter: name".to_string()),
+                },
+            };
+            let path = ctx.get_string("path").unwrap_or_else(|| ".".to_string());
+            let max_results = ctx.get_number("max_results").map(|n| n as usize).unwrap_or(FIND_SYMBOL_MAX_RESULTS);
+
+            if !Path::new(&path).is_dir() {
+                return ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some(format!("Directory not found: {}", path)),
+                };
+            }
+
+            let mut matches = find_symbol(&name, &path);
+            if matches.is_empty() {
+                return ToolResult {
+                    success: false,
+                    data: serde_json::json!({ "name": name, "path": path, "matches": [] }),
+                    error: Some(format!("No definition of '{}' found under '{}'", name, path)),
+                };
+            }
+            let truncated = matches.len() > max_results;
+            matches.truncate(max_results);
+            ToolResult {
+                success: true,
+                data: serde_json::json!({
+                    "name": name,
+                    "path": path,
+                    "matches": matches,
+                    "truncated": truncated
+                }),
+                error: None,
+            }
+        })
+    }
+}
+
 /// 代码结构分析工具
 pub struct CodeStructureTool;
 
@@ -248,43 +349,280 @@ impl Tool for CodeStructureTool {
     }
 }
 
-fn search_code(pattern: &str, path: &str, include_pattern: Option<&str>, _case_sensitive: bool) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
-    let regex = Regex::new(pattern)?;
-    let mut results = Vec::new();
+/// 文件摘要工具
+///
+/// 返回结构化摘要（函数、类、导入、行数），可选地用 utility 模型生成自然语言摘要，
+/// 让 Agent 在决定完整读取前低成本地了解文件。结果按文件 mtime 缓存。
+pub struct SummarizeFileTool {
+    llm_client: Option<Arc<LLMClient>>,
+    utility_model: Option<String>,
+    cache: Mutex<HashMap<String, (SystemTime, serde_json::Value)>>,
+}
 
-    fn search_in_path(path: &Path, regex: &Regex, include_pattern: Option<&str>, results: &mut Vec<serde_json::Value>) -> Result<(), Box<dyn std::error::Error>> {
-        if path.is_file() {
-            // 检查文件扩展名
-            if let Some(pattern) = include_pattern {
-                let file_name = path.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("");
-                if !matches_pattern(file_name, pattern) {
-                    return Ok(());
-                }
+/// 发送给 utility 模型的最大字符数
+const SUMMARY_MAX_CHARS: usize = 12_000;
+
+impl SummarizeFileTool {
+    /// 仅结构化摘要
+    pub fn new() -> Self {
+        Self {
+            llm_client: None,
+            utility_model: None,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 启用自然语言摘要；`utility_model` 为空时使用客户端默认模型
+    pub fn with_llm(llm_client: Arc<LLMClient>, utility_model: Option<String>) -> Self {
+        Self {
+            llm_client: Some(llm_client),
+            utility_model,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 结构化摘要，`natural_language` 且配置了模型时附带 `description`；
+    /// 文件 mtime 未变时复用缓存，结果里的 `cached` 标明是否命中
+    pub async fn summarize(&self, path: &str, natural_language: bool) -> Result<serde_json::Value, String> {
+        let natural_language = natural_language && self.llm_client.is_some();
+        let mtime = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+
+        let cached = self.cache.lock().unwrap().get(path).cloned();
+        if let Some((cached_mtime, mut summary)) = cached {
+            let has_description = summary.get("description").is_some();
+            if cached_mtime == mtime && (has_description || !natural_language) {
+                summary["cached"] = serde_json::json!(true);
+                return Ok(summary);
             }
+        }
 
-            let content = fs::read_to_string(path)?;
-            for (line_num, line) in content.lines().enumerate() {
-                if regex.is_match(line) {
-                    results.push(serde_json::json!({
-                        "file": path.to_string_lossy(),
-                        "line": line_num + 1,
-                        "content": line.trim()
-                    }));
+        let handler = CodeFileHandler::with_options(false, false);
+        let context = handler.get_code_context(path);
+        if !context.success {
+            return Err(context.message);
+        }
+        let mut summary: serde_json::Value = context
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .unwrap_or_else(|| serde_json::json!({}));
+
+        if natural_language {
+            if let Some(client) = &self.llm_client {
+                match self.natural_language_summary(client, path).await {
+                    Ok(text) => summary["description"] = serde_json::json!(text),
+                    Err(e) => summary["description_error"] = serde_json::json!(e),
                 }
             }
-        } else if path.is_dir() {
-            for entry in fs::read_dir(path)? {
-                let entry = entry?;
-                search_in_path(&entry.path(), regex, include_pattern, results)?;
+        }
+
+        self.cache.lock().unwrap().insert(path.to_string(), (mtime, summary.clone()));
+        summary["cached"] = serde_json::json!(false);
+        Ok(summary)
+    }
+
+    async fn natural_language_summary(&self, client: &LLMClient, path: &str) -> Result<String, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let excerpt: String = content.chars().take(SUMMARY_MAX_CHARS).collect();
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "Summarize the purpose of this source file in 2-4 sentences: what it does, its main types/functions, and how it is likely used. Be concise.".to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: format!("File: {}\n```\n{}\n```", path, excerpt),
+            },
+        ];
+        client
+            .generate_completion(messages, self.utility_model.clone(), None)
+            .await
+            .map(|s| s.trim().to_string())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Default for SummarizeFileTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for SummarizeFileTool {
+    fn name(&self) -> &str {
+        "summarize_file"
+    }
+
+    fn description(&self) -> &str {
+        "获取文件的结构摘要（函数、类、导入、行数）和简短说明，无需读取完整内容。探索代码库时先用它决定要完整阅读哪些文件"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: vec![
+                ToolParameter {
+                    name: "path".to_string(),
+                    description: "文件路径".to_string(),
+                    param_type: "string".to_string(),
+                    required: true,
+                },
+                ToolParameter {
+                    name: "natural_language".to_string(),
+                    description: "是否生成自然语言摘要（默认true，未配置模型时忽略）".to_string(),
+                    param_type: "boolean".to_string(),
+                    required: false,
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, call: ToolCall) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>> {
+        Box::pin(async move {
+            let ctx = ToolExecutionContext::new(call.tool_name, call.arguments);
+
+            let path = match ctx.get_string("path") {
+                Some(p) => p,
+                None => return ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some("Missing required parameter: path".to_string()),
+                },
+            };
+            let natural_language = ctx.get_bool("natural_language").unwrap_or(true);
+
+            match self.summarize(&path, natural_language).await {
+                Ok(summary) => ToolResult {
+                    success: true,
+                    data: summary,
+                    error: None,
+                },
+                Err(e) => ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some(e),
+                },
             }
+        })
+    }
+}
+
+/// 并行读取、匹配文件（线程数见 `parallel_walk::search_concurrency`）。
+/// 结果按文件路径、行号排序，超过 `max_results` 时截断并返回 `true`
+fn search_code(
+    pattern: &str,
+    path: &str,
+    include_pattern: Option<&str>,
+    _case_sensitive: bool,
+    max_results: Option<usize>,
+) -> Result<(Vec<serde_json::Value>, bool), Box<dyn std::error::Error>> {
+    let regex = Regex::new(pattern)?;
+    if !Path::new(path).exists() {
+        return Err(format!("路径不存在: {}", path).into());
+    }
+
+    // 与原先的递归遍历一致：不跳过隐藏文件和 .gitignore 中的文件
+    let mut builder = ignore::WalkBuilder::new(path);
+    builder.standard_filters(false);
+    let per_file = parallel_walk::walk_map(builder, parallel_walk::search_concurrency(), |entry| {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            return None;
         }
-        Ok(())
+        // 检查文件扩展名
+        if let Some(pattern) = include_pattern {
+            if !matches_pattern(&entry.file_name().to_string_lossy(), pattern) {
+                return None;
+            }
+        }
+
+        // 二进制或无法读取的文件跳过
+        let content = fs::read_to_string(entry.path()).ok()?;
+        let matches: Vec<serde_json::Value> = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| regex.is_match(line))
+            .map(|(line_num, line)| serde_json::json!({
+                "file": encode_path(entry.path()),
+                "line": line_num + 1,
+                "content": line.trim()
+            }))
+            .collect();
+        (!matches.is_empty()).then_some(matches)
+    });
+
+    let mut results: Vec<serde_json::Value> = per_file.into_iter().flat_map(|(_, matches)| matches).collect();
+    let truncated = max_results.is_some_and(|max| results.len() > max);
+    if let Some(max) = max_results {
+        results.truncate(max);
     }
+    Ok((results, truncated))
+}
 
-    search_in_path(Path::new(path), &regex, include_pattern, &mut results)?;
-    Ok(results)
+/// `find_symbol` 默认返回的定义数
+pub const FIND_SYMBOL_MAX_RESULTS: usize = 20;
+
+/// 能提取符号的源文件扩展名
+const SYMBOL_FILE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "c", "h", "cpp", "cc", "hpp", "cs", "rb", "php", "swift", "scala", "sh",
+];
+
+/// 符号名与查询的匹配程度，越小越靠前；不匹配时为 `None`
+fn symbol_match_rank(symbol: &str, query: &str) -> Option<(u8, &'static str)> {
+    let (symbol_lower, query_lower) = (symbol.to_lowercase(), query.to_lowercase());
+    if symbol == query {
+        Some((0, "exact"))
+    } else if symbol_lower == query_lower {
+        Some((1, "case_insensitive"))
+    } else if symbol_lower.starts_with(&query_lower) {
+        Some((2, "prefix"))
+    } else if symbol_lower.contains(&query_lower) {
+        Some((3, "partial"))
+    } else {
+        None
+    }
+}
+
+/// 遍历 `path` 下的源文件，用 `extract_symbols` 找出名字匹配 `query` 的定义。
+/// 与 search_code 不同，这里遵守 .gitignore，target、node_modules 里的定义只会干扰跳转。
+/// 结果按匹配程度、文件路径、行号排序。
+pub fn find_symbol(query: &str, path: &str) -> Vec<serde_json::Value> {
+    let handler = CodeFileHandler::with_options(false, false);
+    let per_file = parallel_walk::walk_map(ignore::WalkBuilder::new(path), parallel_walk::search_concurrency(), |entry| {
+        let extension = entry.path().extension()?.to_str()?;
+        if !entry.file_type().is_some_and(|t| t.is_file()) || !SYMBOL_FILE_EXTENSIONS.contains(&extension) {
+            return None;
+        }
+        let content = fs::read_to_string(entry.path()).ok()?;
+        let lines: Vec<&str> = content.lines().collect();
+        let found: Vec<(u8, serde_json::Value)> = handler
+            .extract_symbols(&content)
+            .into_iter()
+            .filter_map(|symbol| {
+                let (rank, match_kind) = symbol_match_rank(&symbol.name, query)?;
+                // line_start 含文档注释和属性，行号取定义所在的那一行
+                let (line, kind) = (symbol.line_start - 1..symbol.line_end).find_map(|i| {
+                    let (kind, name) = definition_name(lines[i])?;
+                    (name == symbol.name).then_some((i + 1, kind))
+                })?;
+                Some((rank, serde_json::json!({
+                    "file": encode_path(entry.path()),
+                    "line": line,
+                    "line_end": symbol.line_end,
+                    "kind": kind,
+                    "signature": symbol.signature,
+                    "match": match_kind
+                })))
+            })
+            .collect();
+        (!found.is_empty()).then_some(found)
+    });
+
+    let mut matches: Vec<(u8, serde_json::Value)> = per_file.into_iter().flat_map(|(_, found)| found).collect();
+    // 同一文件内已按行号排列，稳定排序只需按匹配程度
+    matches.sort_by_key(|(rank, _)| *rank);
+    matches.into_iter().map(|(_, value)| value).collect()
 }
 
 fn matches_pattern(filename: &str, pattern: &str) -> bool {
@@ -465,4 +803,69 @@ class Calculator:
         let functions = result.data["functions"].as_array().unwrap();
         assert!(functions.len() >= 2); // Should find function and class
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_find_symbol_ranks_exact_definitions_first() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/server.rs"), "/// 服务器\npub struct Server;\n\nfn server_config() {}\n").unwrap();
+        fs::write(temp_dir.path().join("app.py"), "class ServerPool:\n    pass\n\ndef server():\n    pass\n").unwrap();
+        fs::write(temp_dir.path().join("notes.md"), "struct Server\n").unwrap();
+
+        let call = |name: &str| ToolCall {
+            tool_name: "find_symbol".to_string(),
+            arguments: [
+                ("name".to_string(), serde_json::json!(name)),
+                ("path".to_string(), serde_json::json!(temp_dir.path().to_str())),
+            ].into(),
+        };
+        let result = FindSymbolTool.execute(call("Server")).await;
+        assert!(result.success);
+        let found: Vec<(String, u64, &str)> = result.data["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| {
+                let file = Path::new(m["file"].as_str().unwrap()).file_name().unwrap().to_string_lossy().to_string();
+                (file, m["line"].as_u64().unwrap(), m["match"].as_str().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("server.rs".to_string(), 2, "exact"),
+                ("app.py".to_string(), 4, "case_insensitive"),
+                ("app.py".to_string(), 1, "prefix"),
+                ("server.rs".to_string(), 4, "prefix"),
+            ]
+        );
+        assert_eq!(result.data["matches"][0]["kind"], "struct");
+
+        let missing = FindSymbolTool.execute(call("Nowhere")).await;
+        assert!(!missing.success);
+        assert!(missing.error.unwrap().contains("No definition of 'Nowhere'"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_file_caches_by_mtime() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("lib.rs");
+        fs::write(&file_path, "use std::fs;\n\npub struct Config {}\n\nfn load() {}\n").unwrap();
+
+        let tool = SummarizeFileTool::new();
+        let call = || ToolCall {
+            tool_name: "summarize_file".to_string(),
+            arguments: [("path".to_string(), serde_json::json!(file_path.to_str()))].into(),
+        };
+
+        let first = tool.execute(call()).await;
+        assert!(first.success);
+        assert_eq!(first.data["functions"].as_array().unwrap().len(), 1);
+        assert_eq!(first.data["classes"][0], "Config");
+        assert_eq!(first.data["cached"], false);
+        assert!(first.data.get("description").is_none());
+
+        let second = tool.execute(call()).await;
+        assert_eq!(second.data["cached"], true);
+    }
+}
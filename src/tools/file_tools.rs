@@ -2,11 +2,65 @@
 /// 提供文件读取、写入、修改等功能
 
 use super::tool::{Tool, ToolCall, ToolDefinition, ToolParameter, ToolResult, ToolExecutionContext};
+use crate::utils::code_file_handler::CodeFileHandler;
+use crate::utils::os_path::{display_name, encode_path, is_non_utf8, resolve_path};
 use std::fs;
 use std::path::Path;
 use std::pin::Pin;
 use std::future::Future;
 
+/// 按符号查看时，定义前后额外显示的行数
+const SYMBOL_CONTEXT_LINES: usize = 2;
+
+/// 文件中名为 `symbol` 的定义（同名的全部返回），附前后几行；找不到时列出可用符号
+pub fn read_symbol(path: &str, content: &str, symbol: &str) -> ToolResult {
+    let lines: Vec<&str> = content.lines().collect();
+    let symbols = CodeFileHandler::with_options(false, false).extract_symbols(content);
+    let matches: Vec<_> = symbols.iter().filter(|s| s.name == symbol).collect();
+    if matches.is_empty() {
+        let mut names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        names.dedup();
+        return ToolResult {
+            success: false,
+            data: serde_json::json!({ "available_symbols": names }),
+            error: Some(format!(
+                "Symbol '{}' not found in '{}'. Available symbols: {}",
+                symbol,
+                path,
+                if names.is_empty() { "(none)".to_string() } else { names.join(", ") }
+            )),
+        };
+    }
+
+    let sections: Vec<String> = matches
+        .iter()
+        .map(|s| {
+            let start = s.line_start.saturating_sub(1 + SYMBOL_CONTEXT_LINES);
+            let end = (s.line_end + SYMBOL_CONTEXT_LINES).min(lines.len());
+            lines[start..end]
+                .iter()
+                .enumerate()
+                .map(|(i, line)| format!("{:4}: {}", start + i + 1, line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+    ToolResult {
+        success: true,
+        data: serde_json::json!({
+            "path": path,
+            "total_lines": lines.len(),
+            "symbol": symbol,
+            "matches": matches
+                .iter()
+                .map(|s| serde_json::json!({ "line_start": s.line_start, "line_end": s.line_end, "signature": s.signature }))
+                .collect::<Vec<_>>(),
+            "content": sections.join("\n...\n")
+        }),
+        error: None,
+    }
+}
+
 /// 文件读取工具
 pub struct FileReadTool;
 
@@ -16,7 +70,7 @@ impl Tool for FileReadTool {
     }
 
     fn description(&self) -> &str {
-        "读取文件内容，支持指定行范围"
+        "读取文件内容，支持指定行范围，或用 symbol 只查看某个函数、结构体、类的定义"
     }
 
     fn definition(&self) -> ToolDefinition {
@@ -42,6 +96,12 @@ impl Tool for FileReadTool {
                     param_type: "number".to_string(),
                     required: false,
                 },
+                ToolParameter {
+                    name: "symbol".to_string(),
+                    description: "符号名（可选）：只返回该函数、结构体、类等的定义及前后几行，不需要知道行号".to_string(),
+                    param_type: "string".to_string(),
+                    required: false,
+                },
             ],
         }
     }
@@ -59,7 +119,9 @@ impl Tool for FileReadTool {
                 },
             };
 
-            match fs::read_to_string(&path) {
+            let symbol = ctx.get_string("symbol");
+            match fs::read_to_string(resolve_path(&path)) {
+                Ok(content) if symbol.is_some() => read_symbol(&path, &content, symbol.as_deref().unwrap_or_default()),
                 Ok(content) => {
                     let lines: Vec<&str> = content.lines().collect();
                     let total_lines = lines.len();
@@ -157,7 +219,8 @@ impl Tool for FileWriteTool {
             };
 
             // 确保父目录存在
-            if let Some(parent) = Path::new(&path).parent() {
+            let real_path = resolve_path(&path);
+            if let Some(parent) = real_path.parent() {
                 if let Err(e) = fs::create_dir_all(parent) {
                     return ToolResult {
                         success: false,
@@ -168,7 +231,7 @@ impl Tool for FileWriteTool {
             }
 
             let bytes_written = content.len();
-            match fs::write(&path, content) {
+            match fs::write(&real_path, content) {
                 Ok(_) => ToolResult {
                     success: true,
                     data: serde_json::json!({
@@ -263,20 +326,22 @@ fn list_directory(path: &str, recursive: bool) -> Result<Vec<serde_json::Value>,
         for entry in dir_entries {
             let entry = entry?;
             let path = entry.path();
-            let file_name = path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("<invalid>")
-                .to_string();
+            let file_name = entry.file_name();
 
             let metadata = entry.metadata()?;
             let is_dir = metadata.is_dir();
 
-            entries.push(serde_json::json!({
-                "name": file_name,
-                "path": path.to_string_lossy(),
+            // path 用转义形式，传回 read_file 等工具时会还原成真实文件名
+            let mut item = serde_json::json!({
+                "name": display_name(&file_name),
+                "path": encode_path(&path),
                 "is_directory": is_dir,
                 "size": if is_dir { 0 } else { metadata.len() }
-            }));
+            });
+            if is_non_utf8(&file_name) {
+                item["non_utf8_name"] = serde_json::json!(true);
+            }
+            entries.push(item);
 
             if recursive && is_dir {
                 visit_dir(&path, entries, recursive)?;
@@ -286,7 +351,7 @@ fn list_directory(path: &str, recursive: bool) -> Result<Vec<serde_json::Value>,
         Ok(())
     }
 
-    visit_dir(Path::new(path), &mut entries, recursive)?;
+    visit_dir(&resolve_path(path), &mut entries, recursive)?;
     Ok(entries)
 }
 
@@ -354,4 +419,55 @@ mod tests {
         let entries = list_result.data["entries"].as_array().unwrap();
         assert_eq!(entries.len(), 3); // 2 files + 1 directory
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_read_file_by_symbol() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("lib.rs");
+        let body = (1..=40).map(|n| format!("// filler {}\n", n)).collect::<String>();
+        fs::write(&file_path, format!("{}fn target() {{\n    work();\n}}\n{}", body, body)).unwrap();
+
+        let read = |symbol: &str| {
+            FileReadTool.execute(ToolCall {
+                tool_name: "read_file".to_string(),
+                arguments: [
+                    ("path".to_string(), serde_json::json!(file_path.to_str())),
+                    ("symbol".to_string(), serde_json::json!(symbol)),
+                ].into(),
+            })
+        };
+
+        let result = read("target").await;
+        assert!(result.success);
+        let content = result.data["content"].as_str().unwrap();
+        assert_eq!(content.lines().count(), 3 + 2 * SYMBOL_CONTEXT_LINES);
+        assert!(content.starts_with("  39: // filler 39"));
+        assert_eq!(result.data["matches"][0]["line_start"], 41);
+
+        let missing = read("absent").await;
+        assert!(!missing.success);
+        assert!(missing.error.unwrap().ends_with("Available symbols: target"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_file_is_listed_and_readable_by_its_listed_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join(std::ffi::OsStr::from_bytes(b"r\xE9sum\xE9.txt")), "hello").unwrap();
+        fs::write(temp_dir.path().join("plain.txt"), "x").unwrap();
+
+        let entries = list_directory(temp_dir.path().to_str().unwrap(), false).unwrap();
+        assert_eq!(entries.len(), 2);
+        let entry = entries.iter().find(|e| e["non_utf8_name"] == true).unwrap();
+        assert_eq!(entry["name"], "r\\xE9sum\\xE9.txt [non-UTF-8 name]");
+
+        let read_call = ToolCall {
+            tool_name: "read_file".to_string(),
+            arguments: [("path".to_string(), entry["path"].clone())].into(),
+        };
+        let result = FileReadTool.execute(read_call).await;
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.data["content"].as_str().unwrap().contains("hello"));
+    }
+}
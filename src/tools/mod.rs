@@ -60,6 +60,9 @@ pub mod project_tools;
 /// Todo 管理工具 - 创建和更新任务列表
 pub mod todo_tool;
 
+/// 脚手架工具 - 按项目类型生成 .gitignore、CI 等常用文件
+pub mod scaffold_tool;
+
 /// 工具使用示例
 pub mod tool_examples;
 
@@ -70,7 +73,8 @@ pub use tool_registry::ToolRegistry;
 // 重新导出具体工具类，方便使用
 pub use file_tools::{FileReadTool, FileWriteTool, FileListTool};
 pub use str_replace_tool::StrReplaceTool;
-pub use code_tools::{CodeSearchTool, FunctionFinderTool, CodeStructureTool};
+pub use code_tools::{CodeSearchTool, FunctionFinderTool, FindSymbolTool, CodeStructureTool, SummarizeFileTool};
 pub use terminal_tools::{CommandExecuteTool, EnvironmentInfoTool};
-pub use project_tools::{ProjectStructureTool, DependencyAnalyzerTool, BuildTool};
-pub use todo_tool::{CreateTodoListTool, UpdateTodoListTool, TodoItem, TodoStatus};
\ No newline at end of file
+pub use project_tools::{ProjectStructureTool, ProjectOverviewTool, DependencyAnalyzerTool, BuildTool};
+pub use todo_tool::{CreateTodoListTool, UpdateTodoListTool};
+pub use scaffold_tool::ScaffoldTool;
\ No newline at end of file
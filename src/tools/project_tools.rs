@@ -2,10 +2,19 @@
 /// 提供项目结构分析、依赖管理、构建工具等功能
 
 use super::tool::{Tool, ToolCall, ToolDefinition, ToolParameter, ToolResult, ToolExecutionContext};
+use crate::core::token_calculator::TokenCalculator;
+use crate::utils::code_file_handler::{CodeContext, CodeFileHandler};
+use crate::utils::os_path::{display_name, encode_path};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::future::Future;
+use std::sync::Mutex;
+
+/// 概览默认的 token 上限
+pub const DEFAULT_OVERVIEW_TOKENS: usize = 1500;
 
 /// 项目结构分析工具
 pub struct ProjectStructureTool;
@@ -194,6 +203,315 @@ impl Tool for BuildTool {
     }
 }
 
+/// 项目概览工具：生成有 token 上限的仓库地图（顶层目录、配置文件、入口、模块结构）
+///
+/// 结果按路径缓存，文件索引（文件列表或修改时间）变化后重新生成。
+pub struct ProjectOverviewTool {
+    /// (路径, token 上限) -> (文件索引指纹, 概览)
+    cache: Mutex<HashMap<(String, usize), (u64, String)>>,
+}
+
+impl ProjectOverviewTool {
+    pub fn new() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 返回概览和是否命中缓存
+    pub fn overview(&self, path: &str, max_tokens: usize) -> (String, bool) {
+        let root = Path::new(path);
+        let files = index_project_files(root);
+        let fingerprint = index_fingerprint(root, &files);
+        let key = (path.to_string(), max_tokens);
+
+        if let Some((cached_fingerprint, overview)) = self.cache.lock().unwrap().get(&key) {
+            if *cached_fingerprint == fingerprint {
+                return (overview.clone(), true);
+            }
+        }
+
+        let overview = build_project_overview(root, &files, max_tokens);
+        self.cache.lock().unwrap().insert(key, (fingerprint, overview.clone()));
+        (overview, false)
+    }
+}
+
+impl Default for ProjectOverviewTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for ProjectOverviewTool {
+    fn name(&self) -> &str {
+        "project_overview"
+    }
+
+    fn description(&self) -> &str {
+        "生成项目概览：顶层目录、关键配置文件、入口文件和模块结构（遵循 .gitignore）。开始任务时先调用，可减少逐个查看文件"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: vec![
+                ToolParameter {
+                    name: "path".to_string(),
+                    description: "项目根目录路径（默认当前目录）".to_string(),
+                    param_type: "string".to_string(),
+                    required: false,
+                },
+                ToolParameter {
+                    name: "max_tokens".to_string(),
+                    description: format!("概览的 token 上限（默认 {}）", DEFAULT_OVERVIEW_TOKENS),
+                    param_type: "number".to_string(),
+                    required: false,
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, call: ToolCall) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>> {
+        Box::pin(async move {
+            let ctx = ToolExecutionContext::new(call.tool_name, call.arguments);
+            let path = ctx.get_string("path").unwrap_or_else(|| ".".to_string());
+            let max_tokens = ctx
+                .get_number("max_tokens")
+                .map(|n| n as usize)
+                .unwrap_or(DEFAULT_OVERVIEW_TOKENS);
+
+            if !Path::new(&path).is_dir() {
+                return ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some(format!("Not a directory: {}", path)),
+                };
+            }
+
+            let (overview, cached) = self.overview(&path, max_tokens);
+            ToolResult {
+                success: true,
+                data: serde_json::json!({
+                    "path": path,
+                    "overview": overview,
+                    "cached": cached
+                }),
+                error: None,
+            }
+        })
+    }
+}
+
+/// 项目文件索引：遵循 .gitignore，跳过隐藏文件和 target，返回排序后的相对路径
+fn index_project_files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = ignore::WalkBuilder::new(root)
+        .hidden(true)
+        .git_ignore(true)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| entry.path().strip_prefix(root).ok().map(Path::to_path_buf))
+        .filter(|path| !path.starts_with("target"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// 文件列表加修改时间的哈希，任何增删改都会改变它
+fn index_fingerprint(root: &Path, files: &[PathBuf]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for file in files {
+        file.hash(&mut hasher);
+        if let Ok(modified) = fs::metadata(root.join(file)).and_then(|m| m.modified()) {
+            modified.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+const KEY_CONFIG_FILES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "requirements.txt",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "tsconfig.json",
+    "Makefile",
+    "Dockerfile",
+];
+
+const ENTRY_POINT_FILES: &[&str] = &[
+    "main.rs",
+    "lib.rs",
+    "main.py",
+    "__main__.py",
+    "app.py",
+    "main.go",
+    "index.js",
+    "index.ts",
+    "main.ts",
+];
+
+const SOURCE_EXTENSIONS: &[&str] = &["rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "cpp", "h", "rb", "swift"];
+
+/// 每个模块目录最多列出的文件数
+const MAX_FILES_PER_MODULE: usize = 12;
+
+fn build_project_overview(root: &Path, files: &[PathBuf], max_tokens: usize) -> String {
+    let name = root
+        .canonicalize()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| root.display().to_string());
+    let mut lines = vec![format!("# 项目概览: {} ({} 个文件，已遵循 .gitignore)", name, files.len())];
+
+    // 顶层目录及其文件构成
+    let mut top_level: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    let mut root_files = Vec::new();
+    for file in files {
+        let mut components = file.components();
+        let first = components.next().map(|c| display_name(c.as_os_str())).unwrap_or_default();
+        if components.next().is_none() {
+            root_files.push(first);
+            continue;
+        }
+        let extension = file.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "-".to_string());
+        *top_level.entry(first).or_default().entry(extension).or_default() += 1;
+    }
+    lines.push(String::new());
+    lines.push("## 顶层目录".to_string());
+    for (dir, extensions) in &top_level {
+        let total: usize = extensions.values().sum();
+        let mut by_count: Vec<(&String, &usize)> = extensions.iter().collect();
+        by_count.sort_by(|a, b| b.1.cmp(a.1));
+        let kinds: Vec<String> = by_count.iter().take(3).map(|(ext, n)| format!("{} {}", ext, n)).collect();
+        lines.push(format!("- {}/ ({} 个文件: {})", dir, total, kinds.join(", ")));
+    }
+    if !root_files.is_empty() {
+        lines.push(format!("- 根目录文件: {}", root_files.join(", ")));
+    }
+
+    // 关键配置文件（最多两层）
+    lines.push(String::new());
+    lines.push("## 配置文件".to_string());
+    for file in files.iter().filter(|f| f.components().count() <= 2) {
+        let file_name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if KEY_CONFIG_FILES.contains(&file_name.as_str()) {
+            match config_package_name(&root.join(file), &file_name) {
+                Some(package) => lines.push(format!("- {} ({})", file.display(), package)),
+                None => lines.push(format!("- {}", file.display())),
+            }
+        }
+    }
+
+    // 入口文件，用 CodeFileHandler 提取语言、行数和函数
+    lines.push(String::new());
+    lines.push("## 入口文件".to_string());
+    let handler = CodeFileHandler::with_options(false, false);
+    for file in files {
+        let file_name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let is_bin = file.parent().is_some_and(|p| p.ends_with("src/bin"));
+        if !ENTRY_POINT_FILES.contains(&file_name.as_str()) && !is_bin {
+            continue;
+        }
+        let context = handler
+            .get_code_context(&encode_path(&root.join(file)))
+            .data
+            .and_then(|data| serde_json::from_str::<CodeContext>(&data).ok());
+        match context {
+            Some(context) => {
+                let functions: Vec<&str> = context.functions.iter().take(6).map(|f| f.name.as_str()).collect();
+                lines.push(format!(
+                    "- {} ({}, {} 行; fn: {})",
+                    file.display(),
+                    context.file_info.language,
+                    context.file_info.lines,
+                    if functions.is_empty() { "-".to_string() } else { functions.join(", ") }
+                ));
+            }
+            None => lines.push(format!("- {}", file.display())),
+        }
+    }
+
+    // 模块结构：按目录列出源文件
+    lines.push(String::new());
+    lines.push("## 模块结构".to_string());
+    let mut modules: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for file in files {
+        let is_source = file
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| SOURCE_EXTENSIONS.contains(&e));
+        if is_source {
+            let dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+            let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            modules.entry(dir).or_default().push(stem);
+        }
+    }
+    for (dir, names) in &modules {
+        let shown: Vec<&str> = names.iter().take(MAX_FILES_PER_MODULE).map(String::as_str).collect();
+        let more = names.len().saturating_sub(MAX_FILES_PER_MODULE);
+        let dir = if dir.as_os_str().is_empty() { ".".to_string() } else { dir.display().to_string() };
+        let suffix = if more > 0 { format!(" (+{})", more) } else { String::new() };
+        lines.push(format!("- {}/: {}{}", dir, shown.join(", "), suffix));
+    }
+
+    truncate_to_token_budget(lines, max_tokens)
+}
+
+/// 从 Cargo.toml / package.json 读出包名
+fn config_package_name(path: &Path, file_name: &str) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    match file_name {
+        "Cargo.toml" => {
+            let mut in_package = false;
+            for line in content.lines() {
+                let line = line.trim();
+                if line.starts_with('[') {
+                    in_package = line == "[package]";
+                } else if in_package && line.starts_with("name") {
+                    return line.split('=').nth(1).map(|v| v.trim().trim_matches('"').to_string());
+                } else if line == "[workspace]" {
+                    return Some("workspace".to_string());
+                }
+            }
+            content.contains("[workspace]").then(|| "workspace".to_string())
+        }
+        "package.json" => serde_json::from_str::<serde_json::Value>(&content)
+            .ok()?
+            .get("name")?
+            .as_str()
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// 按顺序保留能放进预算的行，其余用一行说明省略了多少
+fn truncate_to_token_budget(lines: Vec<String>, max_tokens: usize) -> String {
+    let calculator = TokenCalculator::from_model_name("gpt-4");
+    let mut used = 0;
+    let mut kept = Vec::new();
+    let total = lines.len();
+    for line in lines {
+        let tokens = calculator.count_tokens(&line) + 1;
+        if used + tokens > max_tokens {
+            break;
+        }
+        used += tokens;
+        kept.push(line);
+    }
+    let omitted = total - kept.len();
+    if omitted > 0 {
+        kept.push(format!("... 已截断，省略 {} 行（提高 max_tokens 可查看更多）", omitted));
+    }
+    kept.join("\n")
+}
+
 fn analyze_project_structure(path: &str) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
     let mut analysis = serde_json::json!({
         "languages": {},
@@ -578,6 +896,64 @@ serde = "1.0"
         assert!(analysis["package_managers"].as_array().unwrap().contains(&serde_json::json!("Cargo")));
     }
 
+    #[tokio::test]
+    async fn test_project_overview_is_bounded_cached_and_ignores_build_output() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+        fs::create_dir_all(root.join("src/ui")).unwrap();
+        fs::create_dir_all(root.join("generated")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\nfn run() {}\n").unwrap();
+        fs::write(root.join("src/ui/panel.rs"), "pub fn draw() {}\n").unwrap();
+        fs::write(root.join("generated/huge.rs"), "fn noise() {}\n").unwrap();
+        // ignore 只在 git 仓库中读取 .gitignore
+        fs::create_dir(root.join(".git")).unwrap();
+
+        let tool = ProjectOverviewTool::new();
+        let path = root.to_str().unwrap();
+        let call = ToolCall {
+            tool_name: "project_overview".to_string(),
+            arguments: [("path".to_string(), serde_json::json!(path))].into(),
+        };
+        let result = tool.execute(call).await;
+        assert!(result.success);
+        assert_eq!(result.data["cached"], false);
+        let overview = result.data["overview"].as_str().unwrap();
+        assert!(overview.contains("Cargo.toml (demo)"));
+        assert!(overview.contains("src/main.rs (Rust, 2 行; fn: main, run)"));
+        assert!(overview.contains("src/ui/: panel"));
+        assert!(!overview.contains("generated"));
+
+        assert!(tool.overview(path, DEFAULT_OVERVIEW_TOKENS).1);
+        fs::write(root.join("src/ui/list.rs"), "pub fn items() {}\n").unwrap();
+        let (refreshed, cached) = tool.overview(path, DEFAULT_OVERVIEW_TOKENS);
+        assert!(!cached);
+        assert!(refreshed.contains("src/ui/: list, panel"));
+
+        let (small, _) = tool.overview(path, 20);
+        assert!(small.contains("已截断"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_project_overview_reads_non_utf8_entry_files() {
+        use std::os::unix::ffi::OsStrExt;
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        let name = std::ffi::OsStr::from_bytes(b"caf\xE9");
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::create_dir(root.join(name)).unwrap();
+        fs::write(root.join(name).join("notes.txt"), "").unwrap();
+        let bin = Path::new("src/bin").join(std::ffi::OsStr::from_bytes(b"t\xE9st.rs"));
+        fs::write(root.join(&bin), "fn main() {}\n").unwrap();
+
+        let files = vec![bin, Path::new(name).join("notes.txt")];
+        let overview = build_project_overview(root, &files, DEFAULT_OVERVIEW_TOKENS);
+        assert!(overview.contains("(Rust, 1 行; fn: main)"), "{}", overview);
+        assert!(overview.contains("caf\\xE9 [non-UTF-8 name]/"), "{}", overview);
+    }
+
     #[tokio::test]
     async fn test_dependency_analysis() {
         let temp_dir = tempdir().unwrap();
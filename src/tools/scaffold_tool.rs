@@ -0,0 +1,244 @@
+//! 项目脚手架工具
+//! 按项目类型给出 .gitignore、.editorconfig、CI 配置等常用文件
+//!
+//! 内置模板可以被模板目录覆盖：`<模板目录>/<类型>/<相对路径>` 下的文件替换同路径的内置文件，
+//! 其余文件作为新增项。模板目录取 `SCAFFOLD_TEMPLATES_DIR`，默认 `~/.config/starfellcode/scaffold`。
+//! 工具本身不写盘，文件经批量修改确认后才写入。
+
+use super::tool::{Tool, ToolCall, ToolDefinition, ToolParameter, ToolResult, ToolExecutionContext};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::future::Future;
+
+/// 内置模板支持的项目类型
+pub const SCAFFOLD_TYPES: &[&str] = &["rust", "node", "python", "go"];
+
+/// 脚手架中的一个文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaffoldFile {
+    pub path: String,
+    pub content: String,
+}
+
+const EDITORCONFIG: &str = "root = true\n\n[*]\ncharset = utf-8\nend_of_line = lf\ninsert_final_newline = true\ntrim_trailing_whitespace = true\nindent_style = space\nindent_size = 4\n\n[*.{yml,yaml,json,toml}]\nindent_size = 2\n\n[*.md]\ntrim_trailing_whitespace = false\n";
+
+const RUST_GITIGNORE: &str = "/target\n**/*.rs.bk\n*.pdb\n.env\n";
+const RUST_CI: &str = "name: CI\n\non: [push, pull_request]\n\njobs:\n  test:\n    runs-on: ubuntu-latest\n    steps:\n      - uses: actions/checkout@v4\n      - uses: dtolnay/rust-toolchain@stable\n        with:\n          components: clippy, rustfmt\n      - run: cargo fmt --check\n      - run: cargo clippy --all-targets -- -D warnings\n      - run: cargo test\n";
+
+const NODE_GITIGNORE: &str = "node_modules/\ndist/\ncoverage/\n*.log\n.env\n";
+const NODE_CI: &str = "name: CI\n\non: [push, pull_request]\n\njobs:\n  test:\n    runs-on: ubuntu-latest\n    steps:\n      - uses: actions/checkout@v4\n      - uses: actions/setup-node@v4\n        with:\n          node-version: 20\n      - run: npm ci\n      - run: npm test\n";
+
+const PYTHON_GITIGNORE: &str = "__pycache__/\n*.py[cod]\n.venv/\nbuild/\ndist/\n*.egg-info/\n.pytest_cache/\n.env\n";
+const PYTHON_CI: &str = "name: CI\n\non: [push, pull_request]\n\njobs:\n  test:\n    runs-on: ubuntu-latest\n    steps:\n      - uses: actions/checkout@v4\n      - uses: actions/setup-python@v5\n        with:\n          python-version: \"3.12\"\n      - run: pip install -e . pytest\n      - run: pytest\n";
+
+const GO_GITIGNORE: &str = "/bin/\n*.exe\n*.test\n*.out\n.env\n";
+const GO_CI: &str = "name: CI\n\non: [push, pull_request]\n\njobs:\n  test:\n    runs-on: ubuntu-latest\n    steps:\n      - uses: actions/checkout@v4\n      - uses: actions/setup-go@v5\n        with:\n          go-version: stable\n      - run: go vet ./...\n      - run: go test ./...\n";
+
+const CI_PATH: &str = ".github/workflows/ci.yml";
+
+fn builtin_files(project_type: &str) -> Option<Vec<ScaffoldFile>> {
+    let (gitignore, ci) = match project_type {
+        "rust" => (RUST_GITIGNORE, RUST_CI),
+        "node" => (NODE_GITIGNORE, NODE_CI),
+        "python" => (PYTHON_GITIGNORE, PYTHON_CI),
+        "go" => (GO_GITIGNORE, GO_CI),
+        _ => return None,
+    };
+    Some(
+        [(".gitignore", gitignore), (".editorconfig", EDITORCONFIG), (CI_PATH, ci)]
+            .iter()
+            .map(|(path, content)| ScaffoldFile {
+                path: path.to_string(),
+                content: content.to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// 用户模板目录
+pub fn templates_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("SCAFFOLD_TEMPLATES_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    std::env::var("HOME")
+        .ok()
+        .map(|home| Path::new(&home).join(".config/starfellcode/scaffold"))
+}
+
+/// 根据项目根目录下的清单文件推断项目类型
+pub fn detect_project_type(root: &Path) -> Option<&'static str> {
+    [
+        ("Cargo.toml", "rust"),
+        ("package.json", "node"),
+        ("pyproject.toml", "python"),
+        ("requirements.txt", "python"),
+        ("go.mod", "go"),
+    ]
+    .iter()
+    .find(|(manifest, _)| root.join(manifest).is_file())
+    .map(|(_, project_type)| *project_type)
+}
+
+/// 某个项目类型的全部文件：内置模板叠加模板目录中的覆盖
+///
+/// 模板目录里有同名子目录的自定义类型也可以使用。
+pub fn scaffold_files(project_type: &str, templates: Option<&Path>) -> Result<Vec<ScaffoldFile>, String> {
+    let override_dir = templates.map(|dir| dir.join(project_type)).filter(|dir| dir.is_dir());
+    let mut files = match (builtin_files(project_type), &override_dir) {
+        (Some(files), _) => files,
+        (None, Some(_)) => Vec::new(),
+        (None, None) => {
+            return Err(format!(
+                "未知的项目类型: {}（可用: {}）",
+                project_type,
+                SCAFFOLD_TYPES.join(", ")
+            ))
+        }
+    };
+
+    if let Some(dir) = override_dir {
+        let mut overrides: Vec<ScaffoldFile> = ignore::WalkBuilder::new(&dir)
+            .hidden(false)
+            .git_ignore(false)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&dir).ok()?.to_string_lossy().replace('\\', "/");
+                let content = fs::read_to_string(entry.path()).ok()?;
+                Some(ScaffoldFile { path: relative, content })
+            })
+            .collect();
+        overrides.sort_by(|a, b| a.path.cmp(&b.path));
+        for file in overrides {
+            match files.iter_mut().find(|f| f.path == file.path) {
+                Some(existing) => *existing = file,
+                None => files.push(file),
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// 脚手架工具：返回建议的文件，由模型按需调整后以 "Create file `path`:" 代码块给出
+pub struct ScaffoldTool;
+
+impl Tool for ScaffoldTool {
+    fn name(&self) -> &str {
+        "scaffold"
+    }
+
+    fn description(&self) -> &str {
+        "按项目类型获取脚手架文件模板（.gitignore、.editorconfig、CI 配置）。根据项目调整后，对每个文件回复 \"Create file `路径`:\" 加代码块，用户会一次性确认写入"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: vec![
+                ToolParameter {
+                    name: "project_type".to_string(),
+                    description: format!("项目类型（{}），默认根据清单文件推断", SCAFFOLD_TYPES.join(", ")),
+                    param_type: "string".to_string(),
+                    required: false,
+                },
+                ToolParameter {
+                    name: "path".to_string(),
+                    description: "项目根目录路径（默认当前目录）".to_string(),
+                    param_type: "string".to_string(),
+                    required: false,
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, call: ToolCall) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>> {
+        Box::pin(async move {
+            let ctx = ToolExecutionContext::new(call.tool_name, call.arguments);
+            let root = PathBuf::from(ctx.get_string("path").unwrap_or_else(|| ".".to_string()));
+            let Some(project_type) = ctx
+                .get_string("project_type")
+                .or_else(|| detect_project_type(&root).map(str::to_string))
+            else {
+                return ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some(format!(
+                        "无法推断项目类型，请指定 project_type（{}）",
+                        SCAFFOLD_TYPES.join(", ")
+                    )),
+                };
+            };
+
+            match scaffold_files(&project_type, templates_dir().as_deref()) {
+                Ok(files) => ToolResult {
+                    success: true,
+                    data: serde_json::json!({
+                        "project_type": project_type,
+                        "files": files
+                            .iter()
+                            .map(|file| serde_json::json!({
+                                "path": file.path,
+                                "content": file.content,
+                                "exists": root.join(&file.path).exists()
+                            }))
+                            .collect::<Vec<_>>()
+                    }),
+                    error: None,
+                },
+                Err(e) => ToolResult {
+                    success: false,
+                    data: serde_json::json!(null),
+                    error: Some(e),
+                },
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_are_overridable_and_extendable() {
+        let dir = tempfile::tempdir().unwrap();
+        let rust = dir.path().join("rust");
+        fs::create_dir_all(rust.join(".github/workflows")).unwrap();
+        fs::write(rust.join(".gitignore"), "/target\n/custom\n").unwrap();
+        fs::write(rust.join("rustfmt.toml"), "max_width = 100\n").unwrap();
+
+        let files = scaffold_files("rust", Some(dir.path())).unwrap();
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec![".gitignore", ".editorconfig", CI_PATH, "rustfmt.toml"]);
+        assert_eq!(files[0].content, "/target\n/custom\n");
+
+        // 只有模板目录的自定义类型
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/README.md"), "# Docs\n").unwrap();
+        assert_eq!(scaffold_files("docs", Some(dir.path())).unwrap().len(), 1);
+        assert!(scaffold_files("cobol", Some(dir.path())).is_err());
+
+        fs::write(dir.path().join("go.mod"), "module demo\n").unwrap();
+        assert_eq!(detect_project_type(dir.path()), Some("go"));
+    }
+
+    #[test]
+    fn test_scaffold_command_queues_missing_files_for_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        fs::write(dir.path().join(".gitignore"), "/target\n").unwrap();
+
+        let mut app = crate::app::App::new();
+        let message = app.scaffold_project(None, dir.path());
+        assert!(message.contains("rust"), "{}", message);
+        assert!(message.contains(".gitignore"));
+        assert!(app.modification_confirmation_pending);
+        // 确认前不写盘，已存在的 .gitignore 不在批次中
+        assert_eq!(app.pending_modifications.len(), 2);
+        assert!(!dir.path().join(".editorconfig").exists());
+    }
+}
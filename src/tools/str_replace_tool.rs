@@ -2,6 +2,7 @@
 /// 实现文件文本替换功能（类似 grok-cli 的 str_replace_editor）
 
 use super::tool::{Tool, ToolCall, ToolDefinition, ToolParameter, ToolResult, ToolExecutionContext};
+use crate::utils::os_path::resolve_path;
 use std::fs;
 use std::io::{Read, Write};
 use std::pin::Pin;
@@ -86,7 +87,8 @@ impl Tool for StrReplaceTool {
             let replace_all = ctx.get_bool("replace_all").unwrap_or(false);
 
             // 读取文件内容
-            let content = match fs::read_to_string(&path) {
+            let real_path = resolve_path(&path);
+            let content = match fs::read_to_string(&real_path) {
                 Ok(content) => content,
                 Err(e) => return ToolResult {
                     success: false,
@@ -123,7 +125,7 @@ impl Tool for StrReplaceTool {
             };
 
             // 写回文件
-            match fs::write(&path, modified_content) {
+            match fs::write(&real_path, modified_content) {
                 Ok(_) => ToolResult {
                     success: true,
                     data: serde_json::json!({
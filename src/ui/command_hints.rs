@@ -14,7 +14,6 @@ struct CommandHint {
 
 pub struct CommandHints {
     pub visible: bool,
-    input: String,
     hints: Vec<CommandHint>,
     selected_index: usize,
 }
@@ -23,24 +22,22 @@ impl CommandHints {
     pub fn new() -> Self {
         Self {
             visible: false,
-            input: String::new(),
-            hints: vec![
-                CommandHint { command: "/help".to_string(), description: "Show help".to_string() },
-                CommandHint { command: "/clear".to_string(), description: "Clear chat history".to_string() },
-                CommandHint { command: "/status".to_string(), description: "Show app status".to_string() },
-                CommandHint { command: "/model".to_string(), description: "Set LLM model".to_string() },
-                CommandHint { command: "/provider".to_string(), description: "Set LLM provider".to_string() },
-                CommandHint { command: "/temp".to_string(), description: "Set temperature".to_string() },
-                CommandHint { command: "/tokens".to_string(), description: "Set max tokens".to_string() },
-                CommandHint { command: "/history".to_string(), description: "Show history".to_string() },
-            ],
+            hints: Vec::new(),
             selected_index: 0,
         }
     }
 
-    pub fn update_input(&mut self, input: &str) {
-        self.input = input.to_lowercase();
-        self.visible = self.input.starts_with('/');
+    /// `hints` 来自命令注册表：命令名的 "/name - description"，或命令参数的 "/name args"。
+    /// 已经在输参数但没有可补全的值时不显示
+    pub fn update_input(&mut self, input: &str, hints: Vec<String>) {
+        self.hints = hints
+            .into_iter()
+            .map(|hint| match hint.split_once(" - ") {
+                Some((command, description)) => CommandHint { command: command.to_string(), description: description.to_string() },
+                None => CommandHint { command: hint, description: String::new() },
+            })
+            .collect();
+        self.visible = input.starts_with('/') && (!self.hints.is_empty() || !input.contains(' '));
         self.selected_index = 0;
     }
 
@@ -48,10 +45,7 @@ impl CommandHints {
         if !self.visible {
             return vec![];
         }
-        self.hints
-            .iter()
-            .filter(|h| h.command.starts_with(&self.input))
-            .collect()
+        self.hints.iter().collect()
     }
 
     pub fn select_next(&mut self) {
@@ -78,7 +72,7 @@ impl CommandHints {
     }
 
     pub fn clear(&mut self) {
-        self.input.clear();
+        self.hints.clear();
         self.visible = false;
         self.selected_index = 0;
     }
@@ -99,14 +93,16 @@ impl CommandHints {
                 .iter()
                 .enumerate()
                 .map(|(i, hint)| {
-                    let content = Line::from(vec![
-                        Span::styled(
-                            format!("{:<15}", hint.command),
-                            Style::default().fg(theme.colors.primary).add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw(" - "),
-                        Span::styled(&hint.description, Style::default().fg(theme.colors.text_secondary)),
-                    ]);
+                    let mut spans = vec![Span::styled(
+                        format!("{:<15}", hint.command),
+                        Style::default().fg(theme.colors.primary).add_modifier(Modifier::BOLD),
+                    )];
+                    // 参数补全没有说明
+                    if !hint.description.is_empty() {
+                        spans.push(Span::raw(" - "));
+                        spans.push(Span::styled(&hint.description, Style::default().fg(theme.colors.text_secondary)));
+                    }
+                    let content = Line::from(spans);
                     if i == self.selected_index {
                         ListItem::new(content).style(Style::default().bg(theme.colors.selection))
                     } else {
@@ -0,0 +1,139 @@
+//! 终端过小时的紧凑布局
+//!
+//! 常规布局在窄/矮终端（小窗口、tmux 分屏）里会被切成无法使用的细条，
+//! 低于阈值时改为单栏：一行提示 + 最近消息 + 一行输入。
+
+use crate::app::App;
+use crate::core::message::Role;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+/// 常规布局需要的最小宽度
+pub const MIN_WIDTH: u16 = 40;
+/// 常规布局需要的最小高度（历史区 + 状态栏 + 输入区）
+pub const MIN_HEIGHT: u16 = 10;
+
+pub fn is_too_small(area: Rect) -> bool {
+    area.width < MIN_WIDTH || area.height < MIN_HEIGHT
+}
+
+/// 提示文字，放不下时改用短版本
+fn too_small_hint(area: Rect) -> String {
+    let full = format!(
+        "终端过小 ({}x{})，请调整到至少 {}x{}",
+        area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+    );
+    let short = format!("{}x{} 过小", area.width, area.height);
+    if unicode_width::UnicodeWidthStr::width(full.as_str()) <= area.width as usize {
+        full
+    } else {
+        short
+    }
+}
+
+/// 单栏渲染：提示行、最近消息（有空间时）、输入行
+pub fn render_compact(f: &mut Frame, app: &App, area: Rect) {
+    if area.width == 0 || area.height == 0 {
+        return;
+    }
+
+    let hint = Paragraph::new(Line::from(Span::styled(
+        too_small_hint(area),
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    )));
+    f.render_widget(hint, Rect { height: 1, ..area });
+    if area.height < 2 {
+        return;
+    }
+
+    let input_area = Rect {
+        y: area.y + area.height - 1,
+        height: 1,
+        ..area
+    };
+    let history_height = area.height.saturating_sub(2);
+    if history_height > 0 {
+        let history_area = Rect {
+            y: area.y + 1,
+            height: history_height,
+            ..area
+        };
+        let lines: Vec<Line> = app
+            .chat_history
+            .get_messages()
+            .iter()
+            .flat_map(|msg| {
+                let prefix = match msg.role {
+                    Role::User => "> ",
+                    Role::Assistant => "AI: ",
+                    Role::System => "* ",
+                };
+                msg.content
+                    .lines()
+                    .enumerate()
+                    .map(move |(i, line)| {
+                        if i == 0 {
+                            Line::from(format!("{}{}", prefix, line))
+                        } else {
+                            Line::from(format!("  {}", line))
+                        }
+                    })
+            })
+            .collect();
+        // 不折行，只保留底部能放下的行，保证最新消息可见
+        let skip = lines.len().saturating_sub(history_height as usize);
+        let tail: Vec<Line> = lines.into_iter().skip(skip).collect();
+        f.render_widget(Paragraph::new(tail), history_area);
+    }
+
+    let input = Paragraph::new(format!("▶ {}", app.input_text)).style(Style::default().fg(Color::White));
+    f.render_widget(input, input_area);
+
+    let cursor_col: usize = app
+        .input_text
+        .chars()
+        .take(app.input_cursor)
+        .map(|c| unicode_width::UnicodeWidthChar::width(c).unwrap_or(1))
+        .sum();
+    let max_col = area.width.saturating_sub(1);
+    f.set_cursor(
+        input_area.x + ((cursor_col as u16).saturating_add(2)).min(max_col),
+        input_area.y,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_tiny_terminals_render_fallback() {
+        let mut app = App::new();
+        app.input_text = "hello".to_string();
+        app.input_cursor = 5;
+
+        for (width, height) in [(1, 1), (12, 2), (30, 6), (80, 4)] {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal
+                .draw(|f| crate::ui::pixel_layout_v2::render_pixel_layout(f, &app))
+                .unwrap();
+            if height >= 2 {
+                let buffer = terminal.backend().buffer();
+                // 宽字符的第二格是空格，比较前去掉
+                let first_row: String = (0..width)
+                    .map(|x| buffer.get(x, 0).symbol().to_string())
+                    .collect::<String>()
+                    .replace(' ', "");
+                assert!(first_row.contains("过小"), "{}x{}: {}", width, height, first_row);
+            }
+        }
+        assert!(!is_too_small(Rect::new(0, 0, 80, 24)));
+    }
+}
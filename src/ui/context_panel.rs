@@ -0,0 +1,187 @@
+//! 上下文文件面板
+//!
+//! 列出下一次请求会注入的文件（固定文件 + 输入框里的 @file / @dir 提及），
+//! 显示行数和估算 token，以及相对上下文窗口的合计；选中后按 d 可以直接移除。
+
+use crate::core::context_files::FileSource;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::path::PathBuf;
+
+/// 未配置 `LLM_CONTEXT_WINDOW` 时假定的上下文窗口
+pub const DEFAULT_CONTEXT_WINDOW: usize = 128_000;
+
+#[derive(Debug, Clone)]
+pub struct ContextPanelEntry {
+    pub path: PathBuf,
+    pub source: FileSource,
+    pub lines: usize,
+    pub tokens: usize,
+}
+
+#[derive(Debug)]
+pub struct ContextPanel {
+    pub open: bool,
+    entries: Vec<ContextPanelEntry>,
+    selected: usize,
+    /// 本会话从目录提及中排除的文件数
+    pub excluded: usize,
+    context_window: usize,
+}
+
+impl ContextPanel {
+    pub fn new() -> Self {
+        let context_window = std::env::var("LLM_CONTEXT_WINDOW")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW);
+        Self {
+            open: false,
+            entries: Vec::new(),
+            selected: 0,
+            excluded: 0,
+            context_window,
+        }
+    }
+
+    /// 替换条目，选中位置保持在范围内
+    pub fn set_entries(&mut self, entries: Vec<ContextPanelEntry>) {
+        self.entries = entries;
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn selected_entry(&self) -> Option<&ContextPanelEntry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.entries.iter().map(|e| e.tokens).sum()
+    }
+
+    /// 合计行，例如 "合计 ~1200 / 128000 tokens (0.9%)"
+    pub fn total_summary(&self) -> String {
+        let total = self.total_tokens();
+        format!(
+            "合计 ~{} / {} tokens ({:.1}%)",
+            total,
+            self.context_window,
+            total as f64 * 100.0 / self.context_window.max(1) as f64
+        )
+    }
+
+    /// 以浮层形式画在 `area` 中央
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(4).min(90);
+        let height = (self.entries.len() as u16 + 5).min(area.height);
+        if width < 20 || height < 5 {
+            return;
+        }
+        let panel = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+
+        let mut lines: Vec<Line> = Vec::new();
+        if self.entries.is_empty() {
+            lines.push(Line::from(Span::styled(
+                "  没有注入的文件（/pin 固定文件，或在输入中使用 @file）",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        for (index, entry) in self.entries.iter().enumerate() {
+            let tag = match entry.source {
+                FileSource::Pinned => "📌",
+                FileSource::Mention => "@ ",
+                FileSource::Directory => "📁",
+            };
+            let style = if index == self.selected {
+                Style::default().fg(Color::Black).bg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            lines.push(Line::from(Span::styled(
+                format!(
+                    " {} {}  {} 行  ~{} tokens",
+                    tag,
+                    entry.path.display(),
+                    entry.lines,
+                    entry.tokens
+                ),
+                style,
+            )));
+        }
+
+        let mut total = self.total_summary();
+        if self.excluded > 0 {
+            total.push_str(&format!("  已排除 {} 个目录文件", self.excluded));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            total,
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )));
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" 上下文文件  ↑↓ 选择  d 移除  Esc 关闭 ")
+            .border_style(Style::default().fg(Color::Cyan));
+        f.render_widget(Clear, panel);
+        f.render_widget(Paragraph::new(lines).block(block), panel);
+    }
+}
+
+impl Default for ContextPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::App;
+    use crate::core::context_files::FileSource;
+
+    #[test]
+    fn test_lists_and_removes_injected_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let pinned = dir.path().join("spec.md");
+        let mentioned = dir.path().join("main.rs");
+        std::fs::write(&pinned, "# spec\nline two\n").unwrap();
+        std::fs::write(&mentioned, "fn main() {}\n").unwrap();
+
+        let mut app = App::new();
+        app.pinned_context.pin(&pinned.to_string_lossy()).unwrap();
+        app.input_text = format!("explain @{} please", mentioned.display());
+        app.open_context_panel();
+
+        let entries = &app.context_panel.entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].source, FileSource::Pinned);
+        assert_eq!(entries[0].lines, 2);
+        assert!(app.context_panel.total_tokens() > 0);
+
+        app.context_panel.select_next();
+        app.remove_selected_context_file();
+        assert_eq!(app.input_text, "explain please");
+        app.remove_selected_context_file();
+        assert!(app.pinned_context.is_empty());
+        assert!(app.context_panel.entries.is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::app::App;
+use crate::utils::i18n::{t, Msg};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
@@ -124,9 +125,7 @@ fn render_chat_history(f: &mut Frame, app: &App, area: Rect) {
 
     // If no messages, show welcome message
     if chat_lines.is_empty() {
-        chat_lines.push(Line::from(Span::raw(
-            "Welcome to the chat! Type /help for commands or @mention for references.",
-        )));
+        chat_lines.push(Line::from(Span::raw(t(Msg::WelcomeTitle))));
     }
 
     let history_block = Block::default()
@@ -1,3 +1,4 @@
+use crate::utils::{fuzzy_score, parallel_walk};
 use ignore::WalkBuilder;
 use std::path::PathBuf;
 
@@ -43,33 +44,29 @@ impl FileSearchEngine {
 
         self.cache.clear();
 
-        // 使用 ignore crate 遍历文件，自动跳过 .gitignore 中的文件
+        // 使用 ignore crate 并行遍历文件，自动跳过 .gitignore 中的文件
         // 这与 Gemini CLI 的 list_directory 工具类似
-        let walker = WalkBuilder::new(&self.root_path)
+        let mut builder = WalkBuilder::new(&self.root_path);
+        builder
             .hidden(true)            // 隐藏隐藏文件（.git, .env 等）
             .ignore(true)            // 尊重 .gitignore
             .git_ignore(true)        // 尊重 .gitignore
-            .max_depth(None)         // 无限深度 - 递归遍历整个树
-            .build();
-
-        for result in walker {
-            if let Ok(entry) = result {
-                let path = entry.path().to_path_buf();
-                let path_str = path.to_string_lossy();
-
-                // 跳过 target 和 .git 目录中的内容
-                if !path_str.contains("target/")
-                    && !path_str.contains("target\\")
-                    && !path_str.contains(".git/")
-                    && !path_str.contains(".git\\") {
-                    // 同时保留文件和目录
-                    self.cache.push(path);
-                }
-            }
-        }
-
-        // 排序缓存
-        self.cache.sort();
+            .max_depth(None);        // 无限深度 - 递归遍历整个树
+
+        // 结果已按路径排序
+        self.cache = parallel_walk::walk_map(builder, parallel_walk::search_concurrency(), |entry| {
+            let path_str = entry.path().to_string_lossy();
+
+            // 跳过 target 和 .git 目录中的内容，同时保留文件和目录
+            let skipped = path_str.contains("target/")
+                || path_str.contains("target\\")
+                || path_str.contains(".git/")
+                || path_str.contains(".git\\");
+            (!skipped).then_some(())
+        })
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
         self.cache_built = true;
     }
 
@@ -80,10 +77,10 @@ impl FileSearchEngine {
         self.search();
     }
 
-    /// 执行搜索（支持全文检索和模糊匹配）
+    /// 执行搜索（模糊匹配）
     /// 支持多种搜索模式：
-    /// - @src -> 查找包含 "src" 的文件
-    /// - @src/main -> 查找路径中包含 "src" 和 "main" 的文件
+    /// - @src -> 查找路径中依次含有 s、r、c 的文件，src 开头的排在前面
+    /// - @src main -> 查找同时匹配 "src" 和 "main" 的文件
     /// - @.rs -> 查找扩展名为 .rs 的文件
     fn search(&mut self) {
         self.results.clear();
@@ -102,93 +99,11 @@ impl FileSearchEngine {
             return;
         }
 
-        // 分割查询为多个关键词（仅在有空格或 / 时分割）
-        let keywords: Vec<&str> = if search_query.contains(' ') || search_query.contains('/') {
-            // 有空格或 / 时，按这些分隔符分割
-            search_query
-                .split(|c| c == ' ' || c == '/')
-                .filter(|s| !s.is_empty())
-                .collect()
-        } else {
-            // 没有空格或 / 时，作为单个关键词
-            vec![search_query]
-        };
-
-        // 执行多关键词搜索
-        let mut matches: Vec<(String, usize)> = self
-            .cache
-            .iter()
-            .filter_map(|path| {
-                let path_str = path.to_string_lossy().to_lowercase();
-                
-                // 检查所有关键词是否都匹配
-                let all_match = keywords.iter().all(|kw| {
-                    let kw_lower = kw.to_lowercase();
-                    path_str.contains(&kw_lower)
-                });
-
-                if all_match {
-                    // 参考 Everything 搜索算法的排序策略
-                    let mut score = 0usize;
-                    
-                    // 获取文件名
-                    let file_name = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_lowercase();
-                    
-                    // 优先级 1: 完整文件名匹配（Everything 的最高优先级）
-                    if keywords.len() == 1 {
-                        let kw_lower = keywords[0].to_lowercase();
-                        if file_name == kw_lower {
-                            // 精确匹配文件名
-                            score += 1000000;
-                        } else if file_name.starts_with(&kw_lower) {
-                            // 文件名以关键词开头
-                            score += 500000;
-                        } else if file_name.contains(&kw_lower) {
-                            // 文件名包含关键词
-                            score += 100000;
-                        }
-                    }
-                    
-                    // 优先级 2: 路径中的匹配（Everything 的第二优先级）
-                    for kw in &keywords {
-                        let kw_lower = kw.to_lowercase();
-                        
-                        // 路径中的位置越前面得分越高
-                        if let Some(pos) = path_str.find(&kw_lower) {
-                            // 位置越前，得分越高
-                            let position_score = 10000 - (pos as usize / 10).min(10000);
-                            score += position_score;
-                        }
-                    }
-                    
-                    // 优先级 3: 目录优先级（src/ 目录下的文件优先）
-                    if path_str.starts_with("src/") || path_str.starts_with("src\\") {
-                        score += 50000;
-                    }
-                    
-                    // 优先级 4: 路径深度（浅层文件优先）
-                    let depth = path_str.matches('/').count() + path_str.matches('\\').count();
-                    let depth_score = 1000 - (depth as usize * 100).min(1000);
-                    score += depth_score;
-
-                    Some((format!("@{}", path.display()), score))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        // 按得分排序（降序）
-        matches.sort_by(|a, b| b.1.cmp(&a.1));
-
-        // 提取结果并限制数量
-        self.results = matches
+        // 与 grok-cli 的 @ 文件弹窗共用同一套 fzf 风格打分；空格分隔的多个词都要命中
+        let paths: Vec<String> = self.cache.iter().map(|p| p.display().to_string()).collect();
+        self.results = fuzzy_score::rank(&paths, search_query, 20)
             .into_iter()
-            .take(20)  // 增加显示数量
-            .map(|(path, _)| path)
+            .map(|(index, _)| format!("@{}", paths[index]))
             .collect();
     }
 
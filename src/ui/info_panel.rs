@@ -342,12 +342,13 @@ impl InfoPanel {
         }
     }
 
-    /// Update token stats
-    pub fn update_token_stats(&mut self, session_tokens: u32, total_tokens: u32) {
+    /// Update token stats; `cost_estimate` is the estimated session cost in dollars, if the model has a price
+    pub fn update_token_stats(&mut self, session_tokens: u32, total_tokens: u32, cost_estimate: Option<f64>) {
         for section in &mut self.sections {
             if let InfoSection::TokenStats(token_section) = section {
                 token_section.session_tokens = session_tokens;
                 token_section.tokens_used = total_tokens;
+                token_section.cost_estimate = cost_estimate;
                 break;
             }
         }
@@ -0,0 +1,278 @@
+//! 流式 Markdown 渲染
+//!
+//! 流式输出时每来一个 token 就重绘一次，若每次都重新解析整条回复，长回答的总开销是 O(n²)。
+//! `StreamingMarkdown` 把块边界（闭合的代码围栏、围栏外的空行）之前已渲染的行缓存起来，
+//! 之后只重新解析最后一个边界之后的尾部。
+//! 未闭合的代码围栏内按纯文本显示，等围栏闭合后再高亮，因此结果与一次性渲染整段文本相同。
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+const HEADING: Color = Color::Rgb(34, 211, 238);
+const INLINE_CODE: Color = Color::Rgb(251, 191, 36);
+const CODE: Color = Color::Rgb(204, 204, 204);
+const KEYWORD: Color = Color::Rgb(192, 132, 252);
+const STRING: Color = Color::Rgb(74, 222, 128);
+const NUMBER: Color = Color::Rgb(251, 146, 60);
+const MUTED: Color = Color::Rgb(119, 119, 119);
+
+/// 高亮的关键字，覆盖常见语言
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "class", "const", "continue", "def", "else", "enum", "export", "false",
+    "fn", "for", "from", "func", "function", "if", "impl", "import", "in", "interface", "let", "loop", "match",
+    "mod", "mut", "None", "null", "pub", "return", "self", "Self", "static", "struct", "trait", "True", "true",
+    "False", "type", "use", "var", "where", "while",
+];
+
+/// 带缓存的增量渲染：同一条消息在流式过程中反复调用 `render`
+#[derive(Debug, Default)]
+pub struct StreamingMarkdown {
+    /// 上次渲染的全文
+    source: String,
+    /// `source[..committed_len]` 以块边界结束，其渲染结果固定在 `committed`
+    committed_len: usize,
+    committed: Vec<Line<'static>>,
+    /// 边界之后尾部的渲染结果
+    tail: Vec<Line<'static>>,
+    /// 累计重新解析的字节数
+    processed_bytes: usize,
+}
+
+impl StreamingMarkdown {
+    pub fn render(&mut self, text: &str) -> Vec<Line<'static>> {
+        if text != self.source {
+            self.update(text);
+        }
+        let mut lines = self.committed.clone();
+        lines.extend(self.tail.iter().cloned());
+        lines
+    }
+
+    /// 累计重新解析的字节数；增量渲染时应接近全文长度，而不是随调用次数成倍增长
+    #[cfg(test)]
+    pub fn processed_bytes(&self) -> usize {
+        self.processed_bytes
+    }
+
+    fn update(&mut self, text: &str) {
+        // 已提交的前缀变了（例如换了一条消息），从头开始
+        let prefix_kept = text.len() >= self.committed_len
+            && text.as_bytes()[..self.committed_len] == self.source.as_bytes()[..self.committed_len];
+        if !prefix_kept {
+            self.committed_len = 0;
+            self.committed.clear();
+        }
+
+        let tail_start = self.committed_len;
+        let boundary = tail_start + last_block_boundary(&text[tail_start..]);
+        if boundary > tail_start {
+            render_into(&text[tail_start..boundary], &mut self.committed);
+            self.committed_len = boundary;
+        }
+        self.tail.clear();
+        render_into(&text[boundary..], &mut self.tail);
+        self.processed_bytes += text.len() - tail_start;
+        self.source.clear();
+        self.source.push_str(text);
+    }
+}
+
+/// 围栏行返回语言名（可能为空）
+fn fence_language(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    trimmed.strip_prefix("```").or_else(|| trimmed.strip_prefix("~~~")).map(str::trim)
+}
+
+/// `text` 中最后一个块边界（字节偏移，位于完整行之后），没有时为 0。
+/// 边界取在闭合围栏之后或围栏外的空行之后，此时解析状态与文本开头相同。
+fn last_block_boundary(text: &str) -> usize {
+    let mut boundary = 0;
+    let mut offset = 0;
+    let mut in_fence = false;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        if !line.ends_with('\n') {
+            break;
+        }
+        if fence_language(line).is_some() {
+            in_fence = !in_fence;
+            if !in_fence {
+                boundary = offset;
+            }
+        } else if !in_fence && line.trim().is_empty() {
+            boundary = offset;
+        }
+    }
+    boundary
+}
+
+fn render_into(text: &str, out: &mut Vec<Line<'static>>) {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        if fence_language(lines[i]).is_some() {
+            let close = lines[i + 1..].iter().position(|line| fence_language(line).is_some()).map(|p| i + 1 + p);
+            out.push(Line::styled(lines[i].to_string(), Style::default().fg(MUTED)));
+            let end = close.unwrap_or(lines.len());
+            for line in &lines[i + 1..end] {
+                // 未闭合的围栏先按纯文本显示
+                out.push(if close.is_some() { highlight_code(line) } else { Line::from(line.to_string()) });
+            }
+            if let Some(close) = close {
+                out.push(Line::styled(lines[close].to_string(), Style::default().fg(MUTED)));
+            }
+            i = end + 1;
+        } else {
+            out.push(render_text_line(lines[i]));
+            i += 1;
+        }
+    }
+}
+
+fn render_text_line(line: &str) -> Line<'static> {
+    let trimmed = line.trim_start();
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+        return Line::styled(
+            trimmed[hashes..].trim().to_string(),
+            Style::default().fg(HEADING).add_modifier(Modifier::BOLD),
+        );
+    }
+    if trimmed.len() >= 3 && (trimmed.chars().all(|c| c == '-') || trimmed.chars().all(|c| c == '*')) {
+        return Line::styled("─".repeat(24), Style::default().fg(MUTED));
+    }
+    if let Some(quote) = trimmed.strip_prefix("> ").or_else(|| (trimmed == ">").then_some("")) {
+        let style = Style::default().fg(MUTED).add_modifier(Modifier::ITALIC);
+        let mut spans = vec![Span::styled("│ ", style)];
+        spans.extend(render_inline(quote, style));
+        return Line::from(spans);
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            let indent = &line[..line.len() - trimmed.len()];
+            let mut spans = vec![Span::raw(format!("{}• ", indent))];
+            spans.extend(render_inline(item, Style::default()));
+            return Line::from(spans);
+        }
+    }
+    Line::from(render_inline(line, Style::default()))
+}
+
+/// 行内 `code` 和 **粗体**；没有配对的标记原样保留
+fn render_inline(text: &str, base: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut rest = text;
+    let mut plain = String::new();
+    while !rest.is_empty() {
+        let marker = if rest.starts_with('`') {
+            Some(("`", Style::default().fg(INLINE_CODE)))
+        } else if rest.starts_with("**") {
+            Some(("**", base.add_modifier(Modifier::BOLD)))
+        } else {
+            None
+        };
+        if let Some((marker, style)) = marker {
+            if let Some(end) = rest[marker.len()..].find(marker).filter(|&end| end > 0) {
+                if !plain.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut plain), base));
+                }
+                spans.push(Span::styled(rest[marker.len()..marker.len() + end].to_string(), style));
+                rest = &rest[marker.len() * 2 + end..];
+                continue;
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !plain.is_empty() || spans.is_empty() {
+        spans.push(Span::styled(plain, base));
+    }
+    spans
+}
+
+/// 逐行的简单高亮：关键字、字符串、数字和行尾注释
+fn highlight_code(line: &str) -> Line<'static> {
+    let code = Style::default().fg(CODE);
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if (c == '/' && chars.get(i + 1) == Some(&'/')) || (c == '#' && chars[..i].iter().all(|c| c.is_whitespace())) {
+            spans.push(Span::styled(chars[i..].iter().collect::<String>(), Style::default().fg(MUTED)));
+            break;
+        }
+        if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), Style::default().fg(STRING)));
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), Style::default().fg(NUMBER)));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let style = if KEYWORDS.contains(&word.as_str()) { Style::default().fg(KEYWORD) } else { code };
+            spans.push(Span::styled(word, style));
+        } else {
+            while i < chars.len() && !(chars[i].is_alphanumeric() || "_\"'/#".contains(chars[i])) {
+                i += 1;
+            }
+            // 单独的 / 或 # 不是注释
+            i = i.max(start + 1);
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), code));
+        }
+    }
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 一次性渲染整段文本，作为增量渲染的对照
+    fn render_markdown(text: &str) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        render_into(text, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn test_streaming_render_matches_full_render_and_only_reparses_the_tail() {
+        let answer = "# Plan\n\nUse `cargo test` and **check** output.\n\n```rust\nfn main() {\n    let x = 1; // one\n}\n```\n\n- done\n"
+            .repeat(20);
+        let mut renderer = StreamingMarkdown::default();
+        let mut end = 0;
+        while end < answer.len() {
+            end = (end + 7).min(answer.len());
+            while !answer.is_char_boundary(end) {
+                end += 1;
+            }
+            let streamed = renderer.render(&answer[..end]);
+            assert_eq!(streamed, render_markdown(&answer[..end]), "at byte {}", end);
+        }
+        // 每次只重新解析尾部，总量远小于每次都解析全文
+        let naive: usize = (0..answer.len()).step_by(7).sum();
+        assert!(renderer.processed_bytes() * 10 < naive, "{} vs {}", renderer.processed_bytes(), naive);
+
+        let lines = render_markdown("```rust\nlet x = 1;\n```");
+        assert_eq!(lines[1].spans[0].style.fg, Some(KEYWORD));
+        // 围栏未闭合时代码行是纯文本
+        let open = render_markdown("```rust\nlet x = 1;");
+        assert_eq!(open[1], Line::from("let x = 1;"));
+
+        // 换成另一条消息时从头渲染
+        assert_eq!(renderer.render("## Other"), render_markdown("## Other"));
+    }
+}
@@ -26,6 +26,10 @@ pub mod smart_chat_display;
 pub mod vibe_panel;
 pub mod filename_suggestion;
 pub mod input_area;
+pub mod compact_layout;
+pub mod context_panel;
+pub mod markdown;
+pub mod wrap;
 
 // pub use smart_chat_display::{
 //     SmartChatDisplay, SmartMessage, MessageRole, MessageType,
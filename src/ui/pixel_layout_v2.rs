@@ -5,13 +5,16 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, StatefulWidget, Wrap},
+    widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, StatefulWidget},
     Frame,
 };
 use crate::app::App;
 use crate::core::message::Role as AppRole;
 use crate::ui::avatar::PixelData;
 use crate::ui::input_area::render_input_area;
+use crate::ui::wrap::wrap_lines;
+use crate::utils::i18n::{t, Msg};
+use unicode_width::UnicodeWidthStr;
 use std::collections::HashMap;
 
 // ============================================================================
@@ -175,32 +178,60 @@ pub fn render_pixel_layout(f: &mut Frame, app: &App) {
     // 背景
     f.render_widget(Block::default().bg(theme.bg), size);
 
-    // 确保输入区最小为3行，给历史区更多空间
+    // 终端过小时改用单栏布局，避免切出无法使用的细条
+    if crate::ui::compact_layout::is_too_small(size) {
+        crate::ui::compact_layout::render_compact(f, app, size);
+        return;
+    }
+
+    let chunks = split_main_layout(size);
+
+    render_history_with_avatars(f, app, chunks[0], &theme);
+    render_status_bar(f, app, chunks[1], &theme);
+    render_input_area(f, app, chunks[2], &theme);
+
+    if app.context_panel.open {
+        app.context_panel.render(f, chunks[0]);
+    }
+}
+
+
+/// 垂直分割：历史 | 状态栏 | 输入
+fn split_main_layout(size: Rect) -> std::rc::Rc<[Rect]> {
+    // 输入区固定3行、状态栏1行，剩下的都给历史区；
+    // 终端较矮时先压缩历史区，不能把输入区挤没
     let input_height = 3;
     let status_height = 1;
 
-    // 垂直分割：历史 | 状态栏 | 输入
-    let chunks = Layout::default()
+    Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Min(12),                    // 历史区（增加到至少12行）
+            Constraint::Min(1),                     // 历史区
             Constraint::Length(status_height),      // 状态栏
             Constraint::Length(input_height),       // 输入区
         ])
-        .split(size);
-
-    render_history_with_avatars(f, app, chunks[0], &theme);
-    render_status_bar(f, chunks[1], &theme);
-    render_input_area(f, app, chunks[2], &theme);
+        .split(size)
 }
 
+/// 终端尺寸为 `size` 时历史区能向上滚动的最大行数
+pub fn chat_max_scroll(app: &App, size: Rect) -> usize {
+    if crate::ui::compact_layout::is_too_small(size) {
+        return 0;
+    }
+    let area = split_main_layout(size)[0];
+    let lines = wrap_lines(build_history_lines(app, &Theme::new()), area.width as usize);
+    lines.len().saturating_sub(area.height as usize)
+}
 
-/// 渲染历史区域(带头像)
-fn render_history_with_avatars(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
+/// 构建历史区的所有行
+fn build_history_lines(app: &App, theme: &Theme) -> Vec<Line<'static>> {
     let messages = app.chat_history.get_messages();
+    if messages.is_empty() && !app.is_streaming {
+        return welcome_lines(theme);
+    }
 
     // 构建所有消息的行内容
-    let mut all_lines: Vec<Line> = Vec::new();
+    let mut all_lines: Vec<Line<'static>> = Vec::new();
     let mut line_to_msg_map: Vec<usize> = Vec::new(); // 记录每行属于哪个消息
 
     for (msg_idx, msg) in messages.iter().enumerate() {
@@ -223,10 +254,19 @@ fn render_history_with_avatars(f: &mut Frame, app: &App, area: Rect, theme: &The
         )));
         line_to_msg_map.push(msg_idx);
 
-        // 添加消息内容
-        for line in msg.content.lines() {
-            all_lines.push(Line::from(format!("  {}", line)));
-            line_to_msg_map.push(msg_idx);
+        // 添加消息内容；AI 回复按 Markdown 渲染，流式过程中只重新解析新增的尾部
+        if msg.role == AppRole::Assistant {
+            let mut cache = app.markdown_cache.lock().unwrap();
+            for mut line in cache.entry(msg_idx).or_default().render(&msg.content) {
+                line.spans.insert(0, Span::raw("  "));
+                all_lines.push(line);
+                line_to_msg_map.push(msg_idx);
+            }
+        } else {
+            for line in msg.content.lines() {
+                all_lines.push(Line::from(format!("  {}", line)));
+                line_to_msg_map.push(msg_idx);
+            }
         }
 
         // 消息间空行（除了最后一条消息）
@@ -236,7 +276,43 @@ fn render_history_with_avatars(f: &mut Frame, app: &App, area: Rect, theme: &The
         }
     }
 
-    // 计算滚动偏移量 - 确保显示底部最新消息
+    all_lines
+}
+
+/// 空会话时的欢迎文本
+fn welcome_lines(theme: &Theme) -> Vec<Line<'static>> {
+    let command = Style::default().fg(theme.accent_ai);
+    vec![
+        Line::from(Span::styled(
+            t(Msg::WelcomeTitle),
+            Style::default().fg(theme.accent_ai).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw(t(Msg::WelcomeCommands)),
+            Span::styled("/help", command),
+            Span::raw(" | "),
+            Span::styled("/context", command),
+            Span::raw(" | "),
+            Span::styled("/overview", command),
+        ]),
+        Line::from(vec![
+            Span::raw(t(Msg::WelcomeMentions)),
+            Span::styled("@file", Style::default().fg(theme.accent_user)),
+            Span::raw(" | "),
+            Span::styled("@dir/", Style::default().fg(theme.accent_user)),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled(t(Msg::WelcomeStart), Style::default().fg(Color::Rgb(119, 119, 119)))),
+    ]
+}
+
+/// 渲染历史区域(带头像)
+fn render_history_with_avatars(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
+    // 先自行折行（保留缩进），行数即渲染后的实际行数
+    let all_lines = wrap_lines(build_history_lines(app, theme), area.width as usize);
+
+    // 计算滚动偏移量 - 确保显示底部最新消息；按折行后的行数计算，窗口变窄后不会算错
     let total_lines = all_lines.len() as u16;
     let visible_lines = area.height;
 
@@ -255,8 +331,7 @@ fn render_history_with_avatars(f: &mut Frame, app: &App, area: Rect, theme: &The
         .bg(theme.panel_bg);
 
     // 使用 Paragraph 的 scroll 方法渲染
-    let paragraph = Paragraph::new(all_lines.clone())
-        .wrap(Wrap { trim: true })
+    let paragraph = Paragraph::new(all_lines)
         .scroll((scroll_offset, 0))
         .block(history_block.clone());
 
@@ -280,7 +355,7 @@ fn render_history_with_avatars(f: &mut Frame, app: &App, area: Rect, theme: &The
 fn render_history(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     use crate::core::message::Role as AppRole;
 
-    let mut lines: Vec<Line> = Vec::new();
+    let mut lines: Vec<Line<'static>> = Vec::new();
 
     // 获取消息
     let messages = app.chat_history.get_messages();
@@ -303,7 +378,7 @@ fn render_history(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
 
         // 添加消息内容
         for line in msg.content.lines() {
-            lines.push(Line::from(line));
+            lines.push(Line::from(line.to_string()));
         }
 
         // 消息间隔
@@ -311,23 +386,35 @@ fn render_history(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     }
 
     // 渲染
-    let para = Paragraph::new(lines)
-        .wrap(Wrap { trim: true })
+    let para = Paragraph::new(wrap_lines(lines, area.width as usize))
         .scroll((app.chat_scroll_offset as u16, 0));
 
     f.render_widget(para, area);
 }
 
 /// 渲染状态栏
-fn render_status_bar(f: &mut Frame, area: Rect, _theme: &Theme) {
+fn render_status_bar(f: &mut Frame, app: &App, area: Rect, _theme: &Theme) {
+    let mut left = match app.pinned_context.status_summary() {
+        Some(pins) => format!("{}  {}", t(Msg::StatusConnected), pins),
+        None => t(Msg::StatusConnected).to_string(),
+    };
+    if let Some(review) = &app.review_session {
+        left.push_str(&format!("  🔍 REVIEW {}/{}", review.position() + 1, review.len()));
+    }
+    if let Some(clear) = &app.pending_clear {
+        left.push_str(&format!("  🗑 /clear {} 条消息？1 确认 / 2 取消", clear.messages));
+    }
+    let exit_hint = t(Msg::ExitHint);
+    let padding = (area.width as usize).saturating_sub(left.width() + exit_hint.width() + 1);
+
     let status_line = Line::from(vec![
         Span::styled(
-            "STATUS: CONNECTED",
+            left,
             Style::default().fg(Color::Rgb(119, 119, 119)),
         ),
-        Span::raw(" ".repeat(area.width.saturating_sub(30) as usize)),
+        Span::raw(" ".repeat(padding)),
         Span::styled(
-            "CTRL+C to EXIT",
+            exit_hint,
             Style::default().fg(Color::Rgb(119, 119, 119)),
         ),
     ]);
@@ -433,3 +520,37 @@ pub fn render_selection_highlight(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::message::Message;
+
+    #[test]
+    fn test_resize_reflows_scroll_bounds() {
+        let mut app = App::new();
+        app.chat_history.add_message(Message {
+            role: AppRole::Assistant,
+            content: "x".repeat(1000),
+        });
+
+        let wide = chat_max_scroll(&app, Rect::new(0, 0, 200, 20));
+        let narrow = chat_max_scroll(&app, Rect::new(0, 0, 40, 20));
+        assert_eq!(wide, 0);
+        assert!(narrow > 0, "1000 chars at width 40 wrap to more than 16 rows");
+
+        app.chat_scroll_offset = 50;
+        app.handle_resize(40, 20);
+        assert_eq!(app.chat_scroll_offset, narrow);
+    }
+
+    #[test]
+    fn test_short_terminals_keep_status_and_input_rows() {
+        for height in crate::ui::compact_layout::MIN_HEIGHT..20 {
+            let chunks = split_main_layout(Rect::new(0, 0, 40, height));
+            assert_eq!(chunks[1].height, 1, "height {}", height);
+            assert_eq!(chunks[2].height, 3, "height {}", height);
+            assert_eq!(chunks[0].height, height - 4, "height {}", height);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! 聊天历史的折行
+//!
+//! `Paragraph::wrap(Wrap { trim: true })` 会去掉每个折行的前导空白，代码缩进因此全部丢失，
+//! 过长的 URL、路径也只能整体挪到下一行。这里在渲染前按显示宽度自行折行：
+//! 每行保留前导缩进，``` 围起来的代码原样保留空格并在边界处硬断行，
+//! 正文按词边界折行，放不下的长词硬断开。样式按字符保留，折行后重新合并成 Span。
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+use unicode_width::UnicodeWidthChar;
+
+/// 带样式的单个字符
+type Cell = (char, Style);
+
+/// 按 `width` 折行；`width` 为 0 时原样返回
+pub fn wrap_lines(lines: Vec<Line<'static>>, width: usize) -> Vec<Line<'static>> {
+    if width == 0 {
+        return lines;
+    }
+    let mut out = Vec::with_capacity(lines.len());
+    let mut in_code = false;
+    for line in lines {
+        let cells = to_cells(&line);
+        let fence = is_fence(&cells);
+        let rows = if fence || in_code {
+            wrap_code_line(cells, width)
+        } else {
+            wrap_prose_line(cells, width)
+        };
+        out.extend(rows.into_iter().map(|row| to_line(&row, line.style, line.alignment)));
+        if fence {
+            in_code = !in_code;
+        }
+    }
+    out
+}
+
+fn to_cells(line: &Line) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    for span in &line.spans {
+        for c in span.content.chars() {
+            if c == '\t' {
+                cells.extend(std::iter::repeat((' ', span.style)).take(4));
+            } else {
+                cells.push((c, span.style));
+            }
+        }
+    }
+    cells
+}
+
+/// 相邻同样式的字符合并成一个 Span
+fn to_line(cells: &[Cell], style: Style, alignment: Option<ratatui::layout::Alignment>) -> Line<'static> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut text = String::new();
+    let mut current: Option<Style> = None;
+    for &(c, s) in cells {
+        if current.is_some_and(|cur| cur != s) {
+            spans.push(Span::styled(std::mem::take(&mut text), current.unwrap()));
+        }
+        current = Some(s);
+        text.push(c);
+    }
+    if let Some(s) = current {
+        spans.push(Span::styled(text, s));
+    }
+    Line { spans, style, alignment }
+}
+
+fn is_fence(cells: &[Cell]) -> bool {
+    let text: String = cells.iter().map(|(c, _)| c).collect();
+    let trimmed = text.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+fn cells_width(cells: &[Cell]) -> usize {
+    cells.iter().map(|(c, _)| c.width().unwrap_or(0)).sum()
+}
+
+fn indent_len(cells: &[Cell]) -> usize {
+    cells.iter().take_while(|(c, _)| c.is_whitespace()).count()
+}
+
+/// 按显示宽度硬断行，不考虑词边界；至少返回一段
+fn hard_break(cells: &[Cell], width: usize) -> Vec<Vec<Cell>> {
+    let mut pieces = Vec::new();
+    let mut current = Vec::new();
+    let mut current_width = 0;
+    for &cell in cells {
+        let w = cell.0.width().unwrap_or(0);
+        if current_width + w > width && !current.is_empty() {
+            pieces.push(std::mem::take(&mut current));
+            current_width = 0;
+        }
+        current.push(cell);
+        current_width += w;
+    }
+    pieces.push(current);
+    pieces
+}
+
+/// 续行沿用原行的缩进；缩进过宽时不再缩进，避免每行只剩几个字符
+fn continuation_indent(indent: &[Cell], width: usize) -> &[Cell] {
+    if cells_width(indent) * 2 > width {
+        &[]
+    } else {
+        indent
+    }
+}
+
+/// 代码行：原样保留空格，超宽时在边界处断开，续行保持缩进
+fn wrap_code_line(cells: Vec<Cell>, width: usize) -> Vec<Vec<Cell>> {
+    if cells_width(&cells) <= width {
+        return vec![cells];
+    }
+    let indent = continuation_indent(&cells[..indent_len(&cells)], width);
+    let mut rows = hard_break(&cells, width);
+    if indent.is_empty() {
+        return rows;
+    }
+
+    let rest = cells[rows[0].len()..].to_vec();
+    rows.truncate(1);
+    for piece in hard_break(&rest, width - cells_width(indent)) {
+        let mut row = indent.to_vec();
+        row.extend(piece);
+        rows.push(row);
+    }
+    rows
+}
+
+/// 正文行：保留前导缩进（列表、引用），在空白处折行，超宽的词硬断开
+fn wrap_prose_line(cells: Vec<Cell>, width: usize) -> Vec<Vec<Cell>> {
+    if cells_width(&cells) <= width {
+        return vec![cells];
+    }
+    let indent = indent_len(&cells);
+    let next_indent = continuation_indent(&cells[..indent], width).to_vec();
+
+    let mut rows = Vec::new();
+    let mut current = cells[..indent].to_vec();
+    let mut has_word = false;
+    let mut rest = &cells[indent..];
+    while !rest.is_empty() {
+        let space_len = rest.iter().take_while(|(c, _)| c.is_whitespace()).count();
+        let (space, after) = rest.split_at(space_len);
+        let word_len = after.iter().take_while(|(c, _)| !c.is_whitespace()).count();
+        let (word, after) = after.split_at(word_len);
+        rest = after;
+        if word.is_empty() {
+            break;
+        }
+
+        if has_word && cells_width(&current) + cells_width(space) + cells_width(word) > width {
+            rows.push(std::mem::replace(&mut current, next_indent.clone()));
+            has_word = false;
+        }
+        if has_word {
+            current.extend_from_slice(space);
+        }
+
+        let available = width.saturating_sub(cells_width(&current)).max(1);
+        if cells_width(word) <= available {
+            current.extend_from_slice(word);
+        } else {
+            // 不可断开的长词（URL、路径）：先填满当前行，剩余部分按整行宽度断开
+            let line_width = width.saturating_sub(cells_width(&next_indent)).max(1);
+            let head = hard_break(word, available).remove(0);
+            current.extend_from_slice(&head);
+            for piece in hard_break(&word[head.len()..], line_width) {
+                if piece.is_empty() {
+                    continue;
+                }
+                rows.push(std::mem::take(&mut current));
+                current = next_indent.clone();
+                current.extend(piece);
+            }
+        }
+        has_word = true;
+    }
+    rows.push(current);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unicode_width::UnicodeWidthStr;
+
+    fn wrap_text(text: &str, width: usize) -> Vec<String> {
+        let lines = text.lines().map(|line| Line::from(line.to_string())).collect();
+        wrap_lines(lines, width)
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap_keeps_code_indentation_and_breaks_long_tokens() {
+        let text = "Here is the fix:\n```rust\nfn main() {\n    let  x = 1;\n}\n```\n  - see https://example.com/a/very/long/path";
+        let lines = wrap_text(text, 20);
+        assert!(lines.contains(&"    let  x = 1;".to_string()));
+        assert!(lines.contains(&"fn main() {".to_string()));
+        assert!(lines.iter().all(|line| line.width() <= 20), "{:?}", lines);
+        // 长 URL 被断开，续行保持列表缩进
+        let url_lines: Vec<&String> = lines.iter().skip_while(|l| !l.contains("see")).collect();
+        assert!(url_lines.len() >= 2);
+        assert!(url_lines[1].starts_with("  "));
+        assert_eq!(url_lines.iter().map(|l| l.trim()).collect::<String>().replace("see", "see "), "- see https://example.com/a/very/long/path");
+
+        // 超宽代码行在边界处断开，续行保持缩进
+        let code = wrap_text("```\n        call(argument_one, argument_two)\n```", 24);
+        assert!(code[1].starts_with("        call("));
+        assert!(code[2].starts_with("        "));
+
+        // 正文在词边界折行，样式随字符保留
+        let styled = Line::from(vec![Span::raw("  alpha "), Span::styled("beta", Style::default().fg(ratatui::style::Color::Red))]);
+        let rows = wrap_lines(vec![styled], 9);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].spans[0].content, "  ");
+        assert_eq!(rows[1].spans[1].content, "beta");
+        assert_eq!(rows[1].spans[1].style.fg, Some(ratatui::style::Color::Red));
+    }
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::fs;
 use crate::fs::file_ops::{SafeFileOps, FileOpResult};
+use crate::utils::file_utils::{LineEnding, TextFormat};
+use crate::utils::os_path::{display_name, resolve_path};
 
 /// 代码文件信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +35,75 @@ pub struct FunctionInfo {
     pub signature: String,
 }
 
+/// 定义前可以出现的修饰词
+const SYMBOL_MODIFIERS: &[&str] = &[
+    "pub(crate) ", "pub(super) ", "pub(self) ", "pub ", "export default ", "export ", "default ",
+    "async ", "unsafe ", "const ", "static ", "abstract ", "public ", "private ", "protected ", "extern \"C\" ",
+];
+
+/// 引出符号定义的关键字
+const SYMBOL_KEYWORDS: &[&str] = &[
+    "fn ", "def ", "function ", "func ", "class ", "struct ", "enum ", "trait ", "interface ", "union ", "mod ", "type ",
+];
+
+/// 一行定义的关键字和名字，例如 `pub async fn run(` 得到 ("fn", "run")
+pub fn definition_name(line: &str) -> Option<(&'static str, String)> {
+    let mut rest = line.trim_start();
+    while let Some(modifier) = SYMBOL_MODIFIERS.iter().find(|m| rest.starts_with(*m)) {
+        rest = &rest[modifier.len()..];
+    }
+    let keyword = SYMBOL_KEYWORDS.iter().find(|k| rest.starts_with(*k))?;
+    rest = rest[keyword.len()..].trim_start();
+    // Go 方法的接收者：func (s *Server) Start()
+    if *keyword == "func " && rest.starts_with('(') {
+        rest = rest[rest.find(')')? + 1..].trim_start();
+    }
+    let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$').collect();
+    (!name.is_empty()).then(|| (keyword.trim_end(), name))
+}
+
+/// 定义结束的行（下标从 0 开始）：以冒号结尾的按缩进判断（Python），其余按花括号配对
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    if lines[start].trim_end().ends_with(':') {
+        let mut end = start;
+        for (i, line) in lines.iter().enumerate().skip(start + 1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if indent(line) <= indent(lines[start]) {
+                break;
+            }
+            end = i;
+        }
+        return end;
+    }
+
+    let mut depth = 0i32;
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let code = line.split("//").next().unwrap_or("");
+        for c in code.chars() {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+            if opened && depth <= 0 {
+                return i;
+            }
+        }
+        // `struct Unit;`、trait 中的方法声明等没有函数体
+        if !opened && code.trim_end().ends_with(';') {
+            return i;
+        }
+    }
+    if opened { lines.len() - 1 } else { start }
+}
+
 /// 文件操作结果
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileOperationResult {
@@ -75,6 +146,7 @@ impl FileOperationResult {
 pub struct CodeFileHandler {
     yolo_mode: bool,
     safe_ops: SafeFileOps,
+    new_file_line_ending: LineEnding,
 }
 
 impl CodeFileHandler {
@@ -83,6 +155,7 @@ impl CodeFileHandler {
         Self {
             yolo_mode: false,
             safe_ops: SafeFileOps::new(true, true),
+            new_file_line_ending: Self::default_line_ending(),
         }
     }
 
@@ -91,9 +164,32 @@ impl CodeFileHandler {
         Self {
             yolo_mode: false,
             safe_ops: SafeFileOps::new(enable_backups, enable_git),
+            new_file_line_ending: Self::default_line_ending(),
         }
     }
 
+    /// 新文件默认使用 LF，可通过 NEW_FILE_LINE_ENDING=crlf 覆盖
+    fn default_line_ending() -> LineEnding {
+        std::env::var("NEW_FILE_LINE_ENDING")
+            .ok()
+            .and_then(|v| LineEnding::from_string(&v))
+            .unwrap_or_default()
+    }
+
+    /// 设置新文件的换行风格（已有文件保持原样）
+    pub fn set_new_file_line_ending(&mut self, line_ending: LineEnding) {
+        self.new_file_line_ending = line_ending;
+    }
+
+    /// 已有文件沿用其格式，新文件使用配置的换行风格
+    fn preserve_format(&self, path: &str, content: &str) -> String {
+        let format = match fs::read_to_string(path) {
+            Ok(existing) => TextFormat::detect(&existing),
+            Err(_) => TextFormat::new_file(self.new_file_line_ending, content),
+        };
+        format.apply(content)
+    }
+
     /// 启用 YOLO 模式
     pub fn enable_yolo_mode(&mut self) {
         self.yolo_mode = true;
@@ -106,7 +202,7 @@ impl CodeFileHandler {
 
     /// 读取文件
     pub fn read_file(&self, path: &str) -> FileOperationResult {
-        match fs::read_to_string(path) {
+        match fs::read_to_string(resolve_path(path)) {
             Ok(content) => FileOperationResult::success(
                 format!("File read successfully: {}", path),
                 Some(content),
@@ -119,7 +215,8 @@ impl CodeFileHandler {
     pub fn write_file(&self, path: &str, content: &str) -> FileOperationResult {
         // 在 YOLO 模式下，直接写入（如果 SafeFileOps 已配置）
         // 否则需要确认
-        match self.safe_ops.write_file(path, content) {
+        let content = self.preserve_format(path, content);
+        match self.safe_ops.write_file(path, &content) {
             Ok(result) => {
                 if result.success {
                     FileOperationResult::success_with_backup(
@@ -137,6 +234,23 @@ impl CodeFileHandler {
 
     /// 修改文件（搜索并替换，带备份和 Git 集成）
     pub fn modify_file(&self, path: &str, search: &str, replace: &str) -> FileOperationResult {
+        // CRLF / BOM 文件先统一为 LF 再匹配，写回时恢复原格式
+        if let Ok(raw) = fs::read_to_string(path) {
+            let format = TextFormat::detect(&raw);
+            if format.line_ending == LineEnding::CrLf || format.bom {
+                let content = TextFormat::normalize(&raw);
+                let search = TextFormat::normalize(search);
+                if !content.contains(&search) {
+                    return FileOperationResult::error(format!(
+                        "Failed to modify file: Search text not found in file: {}",
+                        search
+                    ));
+                }
+                let new_content = content.replace(&search, &TextFormat::normalize(replace));
+                return self.write_file(path, &new_content);
+            }
+        }
+
         match self.safe_ops.modify_file(path, search, replace) {
             Ok(result) => {
                 if result.success {
@@ -163,7 +277,8 @@ impl CodeFileHandler {
         }
 
         // SafeFileOps.write_file 会自动创建父目录
-        match self.safe_ops.write_file(path, content) {
+        let content = TextFormat::new_file(self.new_file_line_ending, content).apply(content);
+        match self.safe_ops.write_file(path, &content) {
             Ok(result) => {
                 if result.success {
                     FileOperationResult::success_with_backup(
@@ -206,11 +321,12 @@ impl CodeFileHandler {
 
     /// 获取文件信息
     pub fn get_file_info(&self, path: &str) -> FileOperationResult {
-        let path_obj = Path::new(path);
+        let real_path = resolve_path(path);
+        let path_obj = real_path.as_path();
 
-        match fs::metadata(path) {
+        match fs::metadata(path_obj) {
             Ok(metadata) => {
-                let content = match fs::read_to_string(path) {
+                let content = match fs::read_to_string(path_obj) {
                     Ok(c) => c,
                     Err(_) => String::new(),
                 };
@@ -228,9 +344,8 @@ impl CodeFileHandler {
                     path: path_obj.to_path_buf(),
                     name: path_obj
                         .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown")
-                        .to_string(),
+                        .map(display_name)
+                        .unwrap_or_else(|| "unknown".to_string()),
                     extension,
                     size: metadata.len(),
                     lines,
@@ -255,10 +370,7 @@ impl CodeFileHandler {
 
                 for entry in entries.flatten() {
                     if let Ok(metadata) = entry.metadata() {
-                        let name = entry
-                            .file_name()
-                            .into_string()
-                            .unwrap_or_default();
+                        let name = display_name(&entry.file_name());
 
                         if metadata.is_dir() {
                             dirs.push(format!("[DIR] {}", name));
@@ -291,10 +403,7 @@ impl CodeFileHandler {
         if let Ok(entries) = fs::read_dir(directory) {
             for entry in entries.flatten() {
                 if let Ok(metadata) = entry.metadata() {
-                    let name = entry
-                        .file_name()
-                        .into_string()
-                        .unwrap_or_default();
+                    let name = display_name(&entry.file_name());
 
                     if name.contains(pattern) {
                         if metadata.is_dir() {
@@ -358,9 +467,8 @@ impl CodeFileHandler {
             path: path_obj.to_path_buf(),
             name: path_obj
                 .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
+                .map(display_name)
+                .unwrap_or_else(|| "unknown".to_string()),
             extension: extension.clone(),
             size: content.len() as u64,
             lines: content.lines().count(),
@@ -368,6 +476,35 @@ impl CodeFileHandler {
         }
     }
 
+    /// 提取函数、结构体、类等符号及其完整范围
+    ///
+    /// 与 `extract_functions` 不同，范围覆盖整个定义体，并包含紧挨着的文档注释、属性和装饰器。
+    pub fn extract_symbols(&self, content: &str) -> Vec<FunctionInfo> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut symbols = Vec::new();
+        for (idx, line) in lines.iter().enumerate() {
+            let Some((_, name)) = definition_name(line) else {
+                continue;
+            };
+            let mut first = idx;
+            while first > 0 {
+                let previous = lines[first - 1].trim_start();
+                if previous.starts_with("///") || previous.starts_with("#[") || previous.starts_with('@') {
+                    first -= 1;
+                } else {
+                    break;
+                }
+            }
+            symbols.push(FunctionInfo {
+                name,
+                line_start: first + 1,
+                line_end: block_end(&lines, idx) + 1,
+                signature: line.trim().to_string(),
+            });
+        }
+        symbols
+    }
+
     /// 提取函数
     fn extract_functions(&self, content: &str) -> Vec<FunctionInfo> {
         let mut functions = Vec::new();
@@ -513,6 +650,27 @@ mod tests {
         assert_eq!(handler.detect_language("js"), "JavaScript");
     }
 
+    #[test]
+    fn test_extract_symbols_covers_whole_definitions() {
+        let handler = CodeFileHandler::new();
+        let rust = "use std::fmt;\n\n/// 开始\n#[inline]\npub(crate) async fn start(a: u32)\n    -> u32\n{\n    if a > 0 { a } else { 0 }\n}\n\npub struct Unit;\n\nimpl Server {\n    fn stop(&self) {}\n}\n";
+        let symbols = handler.extract_symbols(rust);
+        let ranges: Vec<(&str, usize, usize)> =
+            symbols.iter().map(|s| (s.name.as_str(), s.line_start, s.line_end)).collect();
+        assert_eq!(ranges, vec![("start", 3, 9), ("Unit", 11, 11), ("stop", 14, 14)]);
+
+        let python = "@decorator\ndef run(x):\n    if x:\n\n        return 1\n    return 2\n\nclass Job:\n    pass\n";
+        let ranges: Vec<(String, usize, usize)> = handler
+            .extract_symbols(python)
+            .into_iter()
+            .map(|s| (s.name, s.line_start, s.line_end))
+            .collect();
+        assert_eq!(ranges, vec![("run".to_string(), 1, 6), ("Job".to_string(), 8, 9)]);
+
+        let go = "func (s *Server) Start() error {\n\treturn nil\n}\n";
+        assert_eq!(handler.extract_symbols(go)[0].name, "Start");
+    }
+
     #[test]
     fn test_yolo_mode() {
         let mut handler = CodeFileHandler::new();
@@ -520,4 +678,19 @@ mod tests {
         handler.enable_yolo_mode();
         assert!(handler.yolo_mode);
     }
+
+    #[test]
+    fn test_write_file_preserves_crlf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("win.txt");
+        fs::write(&path, "one\r\ntwo\r\n").unwrap();
+
+        let handler = CodeFileHandler::with_options(false, false);
+        let path_str = path.to_str().unwrap();
+        assert!(handler.write_file(path_str, "one\ntwo\nthree\n").success);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\r\ntwo\r\nthree\r\n");
+
+        assert!(handler.modify_file(path_str, "two\nthree", "2\n3").success);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\r\n2\r\n3\r\n");
+    }
 }
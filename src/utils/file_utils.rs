@@ -0,0 +1,98 @@
+//! 文本格式保持工具
+//!
+//! 读取时检测文件的换行风格、末尾换行和 BOM，写回时原样恢复，
+//! 避免 AI 修改后出现整文件的换行符差异。
+
+/// 换行风格
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "lf" | "unix" => Some(LineEnding::Lf),
+            "crlf" | "windows" => Some(LineEnding::CrLf),
+            _ => None,
+        }
+    }
+}
+
+/// 文件在磁盘上的文本格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextFormat {
+    pub line_ending: LineEnding,
+    pub trailing_newline: bool,
+    pub bom: bool,
+}
+
+impl TextFormat {
+    /// 新文件的默认格式
+    pub fn new_file(line_ending: LineEnding, content: &str) -> Self {
+        Self {
+            line_ending,
+            trailing_newline: content.ends_with('\n'),
+            bom: false,
+        }
+    }
+
+    /// 检测原始内容的格式（按多数换行符判断风格）
+    pub fn detect(raw: &str) -> Self {
+        let crlf = raw.matches("\r\n").count();
+        let lf = raw.matches('\n').count() - crlf;
+        Self {
+            line_ending: if crlf > lf { LineEnding::CrLf } else { LineEnding::Lf },
+            trailing_newline: raw.ends_with('\n'),
+            bom: raw.starts_with('\u{feff}'),
+        }
+    }
+
+    /// 去掉 BOM 并统一为 LF，便于与模型给出的文本匹配
+    pub fn normalize(raw: &str) -> String {
+        raw.strip_prefix('\u{feff}').unwrap_or(raw).replace("\r\n", "\n")
+    }
+
+    /// 将内容转换回该格式
+    pub fn apply(&self, content: &str) -> String {
+        let mut body = Self::normalize(content);
+        if self.trailing_newline && !body.ends_with('\n') {
+            body.push('\n');
+        } else if !self.trailing_newline {
+            while body.ends_with('\n') {
+                body.pop();
+            }
+        }
+        if self.line_ending == LineEnding::CrLf {
+            body = body.replace('\n', "\r\n");
+        }
+        if self.bom {
+            body.insert(0, '\u{feff}');
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_preserves_crlf_and_bom() {
+        let raw = "\u{feff}fn main() {\r\n}\r\n";
+        let format = TextFormat::detect(raw);
+        assert_eq!(format.line_ending, LineEnding::CrLf);
+        assert!(format.bom && format.trailing_newline);
+
+        let edited = TextFormat::normalize(raw).replace("}", "    run();\n}");
+        assert_eq!(format.apply(&edited), "\u{feff}fn main() {\r\n    run();\r\n}\r\n");
+    }
+
+    #[test]
+    fn test_missing_trailing_newline_is_kept() {
+        let format = TextFormat::detect("a\nb");
+        assert_eq!(format.apply("a\nb\nc\n"), "a\nb\nc");
+    }
+}
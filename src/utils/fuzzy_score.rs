@@ -0,0 +1,190 @@
+//! fzf 风格的模糊匹配打分，两个界面的 @ 文件提及共用：
+//! 本 crate 的 `FileSearchEngine`，以及 grok-cli 的文件选择弹窗（通过 `#[path]` 引入本文件）。
+//! 只依赖标准库，这样两边都能直接编译它。
+
+/// 每个命中字符的基础分
+const SCORE_MATCH: i32 = 16;
+/// 两个命中字符之间有间隔时：间隔的第一个字符扣分，之后每个字符再扣分
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+/// 命中单词开头（空白、`_`、`-`、`.` 等之后）的加分
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+/// 命中路径分隔符之后的字符，比普通单词开头略高，文件名和目录名的开头更靠前
+const BONUS_PATH_SEPARATOR: i32 = BONUS_BOUNDARY + 1;
+/// 驼峰处（小写后接大写）或数字开头的加分
+const BONUS_CAMEL: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+/// 连续命中时至少得到的加分
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+/// 查询首字符的加分倍数
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+/// 不可达状态的分数，留出余量避免加减时溢出
+const UNREACHABLE: i32 = i32::MIN / 2;
+
+/// 一次模糊匹配的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    /// 命中字符在候选串中的下标（按 char 计），升序
+    pub positions: Vec<usize>,
+}
+
+/// 查询中以空白分隔的每个词都要按顺序（不必连续）出现在 `candidate` 中，各词得分相加。
+/// 查询含大写字母时区分大小写，否则不区分（smart case）。空查询匹配一切，得 0 分。
+pub fn fuzzy_match(candidate: &str, query: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let chars: Vec<char> = candidate.chars().collect();
+    let text: Vec<char> = if case_sensitive { chars.clone() } else { chars.iter().map(|&c| fold(c)).collect() };
+    let bonuses: Vec<i32> = (0..chars.len())
+        .map(|i| bonus(if i == 0 { None } else { Some(chars[i - 1]) }, chars[i]))
+        .collect();
+
+    let mut total = FuzzyMatch { score: 0, positions: Vec::new() };
+    for term in query.split_whitespace() {
+        let term: Vec<char> = if case_sensitive { term.chars().collect() } else { term.chars().map(fold).collect() };
+        let found = match_term(&text, &bonuses, &term)?;
+        total.score += found.score;
+        total.positions.extend(found.positions);
+    }
+    total.positions.sort_unstable();
+    total.positions.dedup();
+    Some(total)
+}
+
+/// `candidates` 中匹配 `query` 的前 `limit` 项，即 (下标, 匹配)：分高的在前，
+/// 同分时短的在前，再按原顺序
+pub fn rank<S: AsRef<str>>(candidates: &[S], query: &str, limit: usize) -> Vec<(usize, FuzzyMatch)> {
+    let mut matches: Vec<(usize, FuzzyMatch)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| fuzzy_match(candidate.as_ref(), query).map(|found| (index, found)))
+        .collect();
+    matches.sort_by(|(a, a_match), (b, b_match)| {
+        b_match
+            .score
+            .cmp(&a_match.score)
+            .then_with(|| candidates[*a].as_ref().len().cmp(&candidates[*b].as_ref().len()))
+            .then(a.cmp(b))
+    });
+    matches.truncate(limit);
+    matches
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// 命中 `current` 时的位置加分，取决于它前面的字符
+fn bonus(previous: Option<char>, current: char) -> i32 {
+    match previous {
+        None => BONUS_BOUNDARY,
+        Some('/') | Some('\\') => BONUS_PATH_SEPARATOR,
+        Some(p) if !p.is_alphanumeric() && current.is_alphanumeric() => BONUS_BOUNDARY,
+        Some(p) if !p.is_alphanumeric() => 0,
+        Some(p) if (p.is_lowercase() && current.is_uppercase()) || (!p.is_numeric() && current.is_numeric()) => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+/// 单个词的最优对齐（类似 fzf 的 v2 算法）：动态规划求 `term` 每个字符落在 `text` 哪个位置
+/// 时总分最高，再回溯出命中位置
+fn match_term(text: &[char], bonuses: &[i32], term: &[char]) -> Option<FuzzyMatch> {
+    let (n, m) = (text.len(), term.len());
+    // 先确认是子序列，绝大多数候选在这里就被排除
+    let mut next = 0;
+    for &c in text {
+        if next < m && c == term[next] {
+            next += 1;
+        }
+    }
+    if next < m {
+        return None;
+    }
+
+    // score[i * n + j]：term[..=i] 已匹配且 term[i] 落在 text[j] 时的最高分
+    let mut score = vec![UNREACHABLE; m * n];
+    // 该位置所在连续段沿用的加分
+    let mut run_bonus = vec![0; m * n];
+    // term[i - 1] 落在的位置，用于回溯
+    let mut from = vec![0; m * n];
+    for i in 0..m {
+        // 隔着至少一个字符接在 term[i - 1] 后面的最高分（已扣除间隔）及其位置
+        let (mut gap_best, mut gap_from) = (UNREACHABLE, 0);
+        for j in 0..n {
+            if i > 0 && j >= 2 {
+                gap_best += SCORE_GAP_EXTENSION;
+                let previous = score[(i - 1) * n + j - 2];
+                if previous > UNREACHABLE && previous + SCORE_GAP_START > gap_best {
+                    gap_best = previous + SCORE_GAP_START;
+                    gap_from = j - 2;
+                }
+            }
+            if text[j] != term[i] {
+                continue;
+            }
+            let at = i * n + j;
+            if i == 0 {
+                score[at] = SCORE_MATCH + bonuses[j] * FIRST_CHAR_MULTIPLIER;
+                run_bonus[at] = bonuses[j];
+                continue;
+            }
+            if j == 0 {
+                continue;
+            }
+            let adjacent = score[(i - 1) * n + j - 1];
+            if adjacent > UNREACHABLE {
+                let carried = run_bonus[(i - 1) * n + j - 1].max(BONUS_CONSECUTIVE).max(bonuses[j]);
+                score[at] = adjacent + SCORE_MATCH + carried;
+                run_bonus[at] = carried;
+                from[at] = j - 1;
+            }
+            if gap_best > UNREACHABLE && gap_best + SCORE_MATCH + bonuses[j] > score[at] {
+                score[at] = gap_best + SCORE_MATCH + bonuses[j];
+                run_bonus[at] = bonuses[j];
+                from[at] = gap_from;
+            }
+        }
+    }
+
+    // 同分时取最靠前的结尾
+    let last_row = (m - 1) * n;
+    let end = (0..n)
+        .filter(|&j| score[last_row + j] > UNREACHABLE)
+        .max_by_key(|&j| (score[last_row + j], std::cmp::Reverse(j)))?;
+    let mut positions = vec![end; m];
+    for i in (1..m).rev() {
+        positions[i - 1] = from[i * n + positions[i]];
+    }
+    Some(FuzzyMatch { score: score[last_row + end], positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match_prefers_word_starts_and_consecutive_runs() {
+        // m 在 / 之后，r 在 . 之后
+        let found = fuzzy_match("src/main.rs", "mr").unwrap();
+        assert_eq!(found.positions, vec![4, 9]);
+
+        let ranked: Vec<usize> = rank(&["src/domain.rs", "src/main.rs", "docs/readme.md"], "main", 10)
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(ranked, vec![1, 0]);
+
+        // 多个词都要命中；含大写时区分大小写
+        assert!(fuzzy_match("src/ui/file_picker.rs", "ui pick").is_some());
+        assert!(fuzzy_match("src/ui/file_picker.rs", "ui zzz").is_none());
+        assert!(fuzzy_match("src/App.tsx", "App").is_some());
+        assert!(fuzzy_match("src/app.tsx", "App").is_none());
+        assert_eq!(fuzzy_match("anything", "").unwrap(), FuzzyMatch { score: 0, positions: vec![] });
+
+        // 连续命中胜过分散命中
+        let tight = fuzzy_match("tools/mod.rs", "mod").unwrap();
+        let loose = fuzzy_match("my_other_dir.rs", "mod").unwrap();
+        assert!(tight.score > loose.score);
+        assert_eq!(tight.positions, vec![6, 7, 8]);
+    }
+}
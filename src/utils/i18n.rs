@@ -0,0 +1,170 @@
+//! 界面文案的本地化
+//!
+//! 面向用户的提示、确认和欢迎文本按语言放在消息表里，不再在代码里混写中英文。
+//! 语言取自 `UI_LOCALE`（可写在 .env），其次是 `LC_ALL` / `LC_MESSAGES` / `LANG`；
+//! 以 `zh` 开头为中文，其余为英文。
+
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Zh,
+}
+
+impl Locale {
+    /// 解析 "zh_CN.UTF-8"、"en-US"、"zh" 这类取值
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        if value.is_empty() || value == "c" || value == "posix" {
+            return None;
+        }
+        if value.starts_with("zh") {
+            Some(Locale::Zh)
+        } else {
+            Some(Locale::En)
+        }
+    }
+
+    pub fn from_env() -> Self {
+        ["UI_LOCALE", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find_map(|value| Self::parse(&value))
+            .unwrap_or(Locale::En)
+    }
+
+    /// 当前进程的语言，首次调用时从环境读取
+    pub fn current() -> Self {
+        static LOCALE: OnceLock<Locale> = OnceLock::new();
+        *LOCALE.get_or_init(Self::from_env)
+    }
+
+    pub fn text(self, msg: Msg) -> &'static str {
+        match self {
+            Locale::En => english(msg),
+            Locale::Zh => chinese(msg),
+        }
+    }
+}
+
+/// 消息键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    ModificationCancelled,
+    ModificationAbandoned,
+    /// {0}: 错误信息
+    BatchNotApplied,
+    /// {0}: 备份路径
+    BackupCreated,
+    FileCreationCancelled,
+    CopiedToClipboard,
+    /// {0}: 复制的内容（LastReply / LastCodeBlock），{1}: 行数
+    Copied,
+    /// {0}: 错误信息
+    CopyFailed,
+    NoReplyToCopy,
+    NoCodeBlockInReply,
+    LastReply,
+    LastCodeBlock,
+    WelcomeTitle,
+    WelcomeCommands,
+    WelcomeMentions,
+    WelcomeStart,
+    StatusConnected,
+    ExitHint,
+}
+
+fn english(msg: Msg) -> &'static str {
+    match msg {
+        Msg::ModificationCancelled => "✅ Changes cancelled",
+        Msg::ModificationAbandoned => "✅ Changes discarded",
+        Msg::BatchNotApplied => "❌ Changes not applied: {0}. No files were written",
+        Msg::BackupCreated => "💾 Backup created: {0}",
+        Msg::FileCreationCancelled => "❌ File creation cancelled",
+        Msg::CopiedToClipboard => "✅ Copied to clipboard",
+        Msg::Copied => "✅ Copied {0} ({1} lines)",
+        Msg::CopyFailed => "❌ Copy failed: {0}",
+        Msg::NoReplyToCopy => "⚠️ No AI reply to copy",
+        Msg::NoCodeBlockInReply => "⚠️ The last reply has no code block",
+        Msg::LastReply => "the last reply",
+        Msg::LastCodeBlock => "the last code block",
+        Msg::WelcomeTitle => "Welcome to AI Pair Programming Chat! 👋",
+        Msg::WelcomeCommands => "Commands: ",
+        Msg::WelcomeMentions => "Mentions: ",
+        Msg::WelcomeStart => "Start typing to begin your conversation...",
+        Msg::StatusConnected => "STATUS: CONNECTED",
+        Msg::ExitHint => "CTRL+C to EXIT",
+    }
+}
+
+fn chinese(msg: Msg) -> &'static str {
+    match msg {
+        Msg::ModificationCancelled => "✅ 修改已取消",
+        Msg::ModificationAbandoned => "✅ 修改已放弃",
+        Msg::BatchNotApplied => "❌ 批量修改未应用：{0}。没有写入任何文件",
+        Msg::BackupCreated => "💾 备份已创建: {0}",
+        Msg::FileCreationCancelled => "❌ 已取消文件创建",
+        Msg::CopiedToClipboard => "✅ 已复制到剪贴板",
+        Msg::Copied => "✅ 已复制{0}（{1} 行）",
+        Msg::CopyFailed => "❌ 复制失败: {0}",
+        Msg::NoReplyToCopy => "⚠️ 没有可复制的 AI 回复",
+        Msg::NoCodeBlockInReply => "⚠️ 最后一条回复中没有代码块",
+        Msg::LastReply => "最后一条回复",
+        Msg::LastCodeBlock => "最后一个代码块",
+        Msg::WelcomeTitle => "欢迎使用 AI 结对编程！👋",
+        Msg::WelcomeCommands => "命令: ",
+        Msg::WelcomeMentions => "提及: ",
+        Msg::WelcomeStart => "输入内容开始对话...",
+        Msg::StatusConnected => "状态: 已连接",
+        Msg::ExitHint => "CTRL+C 退出",
+    }
+}
+
+/// 当前语言的文案
+pub fn t(msg: Msg) -> &'static str {
+    Locale::current().text(msg)
+}
+
+/// 当前语言的文案，`{0}`、`{1}` … 依次替换为参数
+pub fn tf(msg: Msg, args: &[&str]) -> String {
+    format_message(t(msg), args)
+}
+
+fn format_message(template: &str, args: &[&str]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(template.to_string(), |text, (i, arg)| text.replace(&format!("{{{}}}", i), arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_detection_and_catalogs() {
+        assert_eq!(Locale::parse("zh_CN.UTF-8"), Some(Locale::Zh));
+        assert_eq!(Locale::parse("en_US.UTF-8"), Some(Locale::En));
+        assert_eq!(Locale::parse("C"), None);
+
+        assert_eq!(Locale::Zh.text(Msg::ModificationCancelled), "✅ 修改已取消");
+        assert_eq!(Locale::En.text(Msg::ModificationCancelled), "✅ Changes cancelled");
+        assert_eq!(
+            format_message(Locale::En.text(Msg::Copied), &["the last reply", "3"]),
+            "✅ Copied the last reply (3 lines)"
+        );
+
+        // 两种语言的占位符要一致
+        for msg in [Msg::BatchNotApplied, Msg::BackupCreated, Msg::Copied, Msg::CopyFailed] {
+            for i in 0..2 {
+                let placeholder = format!("{{{}}}", i);
+                assert_eq!(
+                    Locale::En.text(msg).contains(&placeholder),
+                    Locale::Zh.text(msg).contains(&placeholder),
+                    "{:?}",
+                    msg
+                );
+            }
+        }
+    }
+}
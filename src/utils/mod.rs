@@ -1,4 +1,8 @@
 pub mod project;
 pub mod conversation_manager;
 pub mod file_utils;
-pub mod code_file_handler;
\ No newline at end of file
+pub mod code_file_handler;
+pub mod i18n;
+pub mod parallel_walk;
+pub mod fuzzy_score;
+pub mod os_path;
\ No newline at end of file
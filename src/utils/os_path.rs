@@ -0,0 +1,115 @@
+//! 非 UTF-8 文件名
+//!
+//! 工具的参数和结果都是 JSON 字符串，装不下非 UTF-8 的文件名。这类名字里无法解码的字节
+//! 写成 `\xNN`，显示时再加上标记；工具收到这样的路径时还原成原来的字节，
+//! 因此列表里看到的路径可以原样传回给读写工具。
+//! 只有 Unix 的文件名可以是任意字节，其他平台按有损转换处理。
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// 显示名后面的标记，提示这个名字不是 UTF-8
+pub const NON_UTF8_MARKER: &str = " [non-UTF-8 name]";
+
+/// 文件名或路径是否不是合法的 UTF-8
+pub fn is_non_utf8(name: &OsStr) -> bool {
+    name.to_str().is_none()
+}
+
+/// 路径的字符串形式：合法 UTF-8 原样返回，无法解码的字节写成 `\xNN`
+pub fn encode_path(path: &Path) -> String {
+    encode_os_str(path.as_os_str())
+}
+
+/// 列表中显示的名字：非 UTF-8 的名字为转义形式加 `NON_UTF8_MARKER`
+pub fn display_name(name: &OsStr) -> String {
+    if is_non_utf8(name) {
+        format!("{}{}", encode_os_str(name), NON_UTF8_MARKER)
+    } else {
+        name.to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(unix)]
+fn encode_os_str(name: &OsStr) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut encoded = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        encoded.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            encoded.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(not(unix))]
+fn encode_os_str(name: &OsStr) -> String {
+    name.to_string_lossy().into_owned()
+}
+
+/// 工具参数中的路径对应的真实路径：按字面存在就用字面路径，
+/// 否则把 `\xNN` 还原成字节后存在的话用还原的路径，都不存在时仍用字面路径（例如新建文件）
+pub fn resolve_path(path: &str) -> PathBuf {
+    let literal = PathBuf::from(path);
+    if literal.exists() || !path.contains("\\x") {
+        return literal;
+    }
+    match decode_path(path) {
+        Some(decoded) if decoded.exists() => decoded,
+        _ => literal,
+    }
+}
+
+#[cfg(unix)]
+fn decode_path(path: &str) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i..i + 4).filter(|b| b.starts_with(b"\\x"));
+        match escape.and_then(|b| u8::from_str_radix(std::str::from_utf8(&b[2..]).ok()?, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(std::ffi::OsString::from_vec(decoded)))
+}
+
+#[cfg(not(unix))]
+fn decode_path(_path: &str) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_names_round_trip_through_their_encoded_form() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempfile::tempdir().unwrap();
+        let name = OsStr::from_bytes(b"caf\xE9.txt");
+        let real = dir.path().join(name);
+        std::fs::write(&real, "hello").unwrap();
+
+        assert_eq!(display_name(name), "caf\\xE9.txt [non-UTF-8 name]");
+        assert_eq!(display_name(OsStr::new("café.txt")), "café.txt");
+        let encoded = encode_path(&real);
+        assert!(encoded.ends_with("caf\\xE9.txt"));
+        assert_eq!(resolve_path(&encoded), real);
+        assert_eq!(std::fs::read_to_string(resolve_path(&encoded)).unwrap(), "hello");
+
+        // 不存在的路径按字面使用
+        let missing = dir.path().join("new\\x41.txt");
+        assert_eq!(resolve_path(missing.to_str().unwrap()), missing);
+    }
+}
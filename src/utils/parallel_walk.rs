@@ -0,0 +1,116 @@
+//! 并行目录遍历
+//!
+//! 代码搜索和 @ 文件补全的缓存共用：用 ignore 的并行遍历器读取、匹配文件，
+//! 结果按路径排序，顺序与线程数无关。
+//! 线程数取 `SEARCH_THREADS`（可写在 .env），未设置或为 0 时使用 CPU 核数。
+
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 遍历使用的线程数
+pub fn search_concurrency() -> usize {
+    std::env::var("SEARCH_THREADS")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+}
+
+/// 用 `threads` 个线程遍历 `builder`，对每个条目调用 `f`，返回按路径排序的非空结果
+pub fn walk_map<T, F>(mut builder: WalkBuilder, threads: usize, f: F) -> Vec<(PathBuf, T)>
+where
+    T: Send,
+    F: Fn(&DirEntry) -> Option<T> + Sync,
+{
+    let results = Mutex::new(Vec::new());
+    builder.threads(threads.max(1)).build_parallel().run(|| {
+        // 每个线程先攒在本地，结束时再合并，减少锁竞争
+        let mut local = LocalResults { items: Vec::new(), shared: &results };
+        let f = &f;
+        Box::new(move |entry| {
+            if let Ok(entry) = entry {
+                if let Some(value) = f(&entry) {
+                    local.items.push((entry.into_path(), value));
+                }
+            }
+            WalkState::Continue
+        })
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    results
+}
+
+struct LocalResults<'a, T> {
+    items: Vec<(PathBuf, T)>,
+    shared: &'a Mutex<Vec<(PathBuf, T)>>,
+}
+
+impl<T> Drop for LocalResults<'_, T> {
+    fn drop(&mut self) {
+        self.shared.lock().unwrap().append(&mut self.items);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+
+    fn write_tree(root: &std::path::Path, dirs: usize, files: usize, repeat: usize) {
+        for d in 0..dirs {
+            let sub = root.join(format!("pkg{:02}/src", d));
+            fs::create_dir_all(&sub).unwrap();
+            for f in 0..files {
+                let body = format!("fn item_{}_{}() {{}}\n", d, f).repeat(repeat);
+                fs::write(sub.join(format!("m{:02}.rs", f)), body).unwrap();
+            }
+        }
+    }
+
+    fn count_matches(entry: &DirEntry) -> Option<usize> {
+        let content = fs::read_to_string(entry.path()).ok()?;
+        Some(content.lines().filter(|line| line.contains("fn item_")).count())
+    }
+
+    #[test]
+    fn test_parallel_walk_matches_serial_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(dir.path(), 3, 4, 2);
+        fs::write(dir.path().join("pkg00/notes.txt"), "no items here\n").unwrap();
+
+        let serial = walk_map(WalkBuilder::new(dir.path()), 1, count_matches);
+        let parallel = walk_map(WalkBuilder::new(dir.path()), 4, count_matches);
+        assert_eq!(serial, parallel);
+        // 目录读不出内容，返回 None 的条目不出现在结果里
+        assert_eq!(serial.len(), 13);
+        assert!(serial.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(serial.iter().map(|(_, count)| count).sum::<usize>(), 24);
+    }
+
+    /// 计时用，`cargo test -- --ignored` 时运行
+    #[test]
+    #[ignore]
+    fn bench_parallel_walk_speedup() {
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(dir.path(), 40, 50, 200);
+        let run = |threads| {
+            let start = Instant::now();
+            let results = walk_map(WalkBuilder::new(dir.path()), threads, count_matches);
+            (results, start.elapsed())
+        };
+
+        let (serial, serial_time) = run(1);
+        let (parallel, parallel_time) = run(8);
+        assert_eq!(serial, parallel);
+        println!(
+            "2000 个文件: 1 线程 {:?}, 8 线程 {:?}（{:.1}x）",
+            serial_time,
+            parallel_time,
+            serial_time.as_secs_f64() / parallel_time.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+}